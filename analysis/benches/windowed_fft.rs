@@ -0,0 +1,64 @@
+//! Time the windowed FFT of the analysis against the allocating transform
+//! it replaced. Run with `cargo bench -p analysis`.
+//!
+//! A plain timing loop rather than criterion, so the benchmark adds no
+//! dependencies. Numbers vary by a few percent between runs.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rustfft::{num_complex::Complex, FftPlanner};
+
+use analysis::fft::{build_hanning_window, WindowedFft};
+
+const FRAMES: usize = 20_000;
+
+fn signal(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|n| (n as f32 * 0.05).sin() * 0.5 + (n as f32 * 0.31).sin() * 0.25)
+        .collect()
+}
+
+fn report(name: &str, window_size: usize, elapsed: Duration) {
+    println!(
+        "{:<24} window {:>5}: {:>8.0} ns/frame",
+        name,
+        window_size,
+        elapsed.as_nanos() as f64 / FRAMES as f64
+    );
+}
+
+// The transform as it was done before planning and scratch space were reused
+fn allocating(samples: &[f32], window_size: usize) -> Duration {
+    let fft = FftPlanner::new().plan_fft_forward(window_size);
+    let window = build_hanning_window(window_size);
+    let mut buffer = vec![Complex::new(0.0, 0.0); window_size];
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        for (i, &sample) in samples.iter().enumerate() {
+            buffer[i] = Complex::new(sample * window[i], 0.0);
+        }
+        fft.process(&mut buffer);
+        black_box(&buffer);
+    }
+    start.elapsed()
+}
+
+fn windowed(samples: &[f32], window_size: usize) -> Duration {
+    let mut fft = WindowedFft::new(window_size);
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        black_box(fft.process(black_box(samples)));
+    }
+    start.elapsed()
+}
+
+fn main() {
+    for window_size in [512, 1024, 2048, 4096] {
+        let samples = signal(window_size);
+        report("allocating", window_size, allocating(&samples, window_size));
+        report("windowed", window_size, windowed(&samples, window_size));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::energy::TransitionProfile;
 use crate::features::*;
 use crate::fft::*;
 
@@ -27,6 +28,7 @@ pub struct AnalysisResult {
     pub spectral_skewness: f32,
     pub spectral_kurtosis: f32,
     pub chromagram: Vec<f32>,
+    pub transitions: TransitionProfile,
 }
 
 pub fn analyze_audio(file_path: &str, window_size: usize, overlap_size: usize) -> AnalysisResult {
@@ -77,6 +79,7 @@ pub fn analyze_audio(file_path: &str, window_size: usize, overlap_size: usize) -
         spectral_skewness,
         spectral_kurtosis,
         chromagram,
+        transitions: audio_desc.transitions,
     }
 }
 
@@ -93,6 +96,8 @@ pub struct NormalizedAnalysisResult {
     pub spectral_skewness: f32,
     pub spectral_kurtosis: f32,
     pub chromagram: Vec<f32>,
+    // Mix points are in seconds and left as they are
+    pub transitions: TransitionProfile,
 }
 
 pub fn normalize_analysis_result(result: AnalysisResult) -> NormalizedAnalysisResult {
@@ -133,5 +138,6 @@ pub fn normalize_analysis_result(result: AnalysisResult) -> NormalizedAnalysisRe
         spectral_skewness: normalized_spectral_skewness,
         spectral_kurtosis: normalized_spectral_kurtosis,
         chromagram: normalized_chromagram,
+        transitions: result.transitions,
     }
 }
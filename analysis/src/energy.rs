@@ -0,0 +1,196 @@
+use std::collections::VecDeque;
+
+/// Length of the intro and the outro looked at for mix points.
+pub const EDGE_SECONDS: f64 = 15.0;
+/// Length of one energy measurement.
+pub const BLOCK_SECONDS: f64 = 0.25;
+/// Part of the energy of the whole track a block needs to count as the
+/// track being in full swing.
+pub const MIX_THRESHOLD: f32 = 0.5;
+/// Tracks at least this long are taken for mixes or live recordings, and
+/// get markers where their sections start.
+pub const LONG_MIX_SECONDS: f64 = 20.0 * 60.0;
+/// Length of the energy compared before and after a possible marker.
+pub const SECTION_SECONDS: f64 = 8.0;
+/// How many times louder a section has to be than the seconds before it.
+pub const SECTION_ONSET_RATIO: f32 = 2.0;
+/// Closest two markers can be.
+pub const MIN_MARKER_SPACING: f64 = 90.0;
+
+/// Energy of the start and the end of a track, with the points to mix it in
+/// and out at.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TransitionProfile {
+    /// RMS energy of every block of the first `EDGE_SECONDS`.
+    pub intro: Vec<f32>,
+    /// RMS energy of every block of the last `EDGE_SECONDS`.
+    pub outro: Vec<f32>,
+    /// Seconds from the start where the track reaches its energy.
+    pub mix_in: f64,
+    /// Seconds from the start after which the track fades away.
+    pub mix_out: f64,
+    /// Seconds from the start where a section of a long mix kicks in after
+    /// a breakdown, empty for shorter tracks.
+    pub markers: Vec<f64>,
+}
+
+/// Measures the energy of a track frame by frame, keeping only the blocks
+/// of the intro and the outro.
+pub struct EnergyTracker {
+    block_frames: usize,
+    edge_blocks: usize,
+    block_sum: f64,
+    block_count: usize,
+    total_sum: f64,
+    total_frames: usize,
+    blocks: usize,
+    intro: Vec<f32>,
+    outro: VecDeque<f32>,
+    // Every block, only looked at for long mixes
+    levels: Vec<f32>,
+}
+
+impl EnergyTracker {
+    pub fn new(sample_rate: u32) -> Self {
+        let block_frames = ((sample_rate as f64 * BLOCK_SECONDS) as usize).max(1);
+
+        EnergyTracker {
+            block_frames,
+            edge_blocks: (EDGE_SECONDS / BLOCK_SECONDS) as usize,
+            block_sum: 0.0,
+            block_count: 0,
+            total_sum: 0.0,
+            total_frames: 0,
+            blocks: 0,
+            intro: Vec::new(),
+            outro: VecDeque::new(),
+            levels: Vec::new(),
+        }
+    }
+
+    /// Add one frame, the average of its channels.
+    pub fn push(&mut self, sample: f32) {
+        let square = (sample as f64) * (sample as f64);
+        self.block_sum += square;
+        self.block_count += 1;
+        self.total_sum += square;
+        self.total_frames += 1;
+
+        if self.block_count == self.block_frames {
+            self.end_block();
+        }
+    }
+
+    fn end_block(&mut self) {
+        let rms = (self.block_sum / self.block_count as f64).sqrt() as f32;
+        self.block_sum = 0.0;
+        self.block_count = 0;
+        self.blocks += 1;
+
+        if self.intro.len() < self.edge_blocks {
+            self.intro.push(rms);
+        }
+        self.outro.push_back(rms);
+        if self.outro.len() > self.edge_blocks {
+            self.outro.pop_front();
+        }
+        self.levels.push(rms);
+    }
+
+    pub fn finish(mut self) -> TransitionProfile {
+        // The last block is shorter than the others
+        if self.block_count > 0 {
+            self.end_block();
+        }
+        if self.total_frames == 0 {
+            return TransitionProfile::default();
+        }
+
+        let track_rms = (self.total_sum / self.total_frames as f64).sqrt() as f32;
+        let threshold = track_rms * MIX_THRESHOLD;
+        let length = self.total_frames as f64 / self.block_frames as f64 * BLOCK_SECONDS;
+        let outro: Vec<f32> = self.outro.into_iter().collect();
+
+        // The intro is skipped over until the first loud block, a quiet
+        // intro is overlapped completely
+        let mix_in = self
+            .intro
+            .iter()
+            .position(|x| *x >= threshold)
+            .unwrap_or(self.intro.len()) as f64
+            * BLOCK_SECONDS;
+        // The track is faded out after the last loud block of the outro
+        let outro_start = (self.blocks - outro.len()) as f64 * BLOCK_SECONDS;
+        let mix_out = match outro.iter().rposition(|x| *x >= threshold) {
+            Some(index) => (outro_start + (index + 1) as f64 * BLOCK_SECONDS).min(length),
+            None => outro_start,
+        };
+
+        let markers = if length >= LONG_MIX_SECONDS {
+            find_section_markers(&self.levels, threshold)
+        } else {
+            Vec::new()
+        };
+
+        TransitionProfile {
+            intro: self.intro,
+            outro,
+            mix_in: mix_in.min(length),
+            mix_out: mix_out.max(mix_in.min(length)),
+            markers,
+        }
+    }
+}
+
+/// Find where sections start in the block energies of a long track.
+///
+/// A section starts where the energy of the next `SECTION_SECONDS` is
+/// `SECTION_ONSET_RATIO` times the energy of the ones before and loud
+/// enough to be in full swing. The strongest onsets win over the ones
+/// closer than `MIN_MARKER_SPACING` to them.
+///
+/// # Arguments
+/// * `levels` - The RMS energy of every block of `BLOCK_SECONDS`.
+/// * `threshold` - The energy a section has to reach.
+///
+/// # Returns
+/// * `Vec<f64>` - The starts of the sections in seconds, in order.
+pub fn find_section_markers(levels: &[f32], threshold: f32) -> Vec<f64> {
+    let window = (SECTION_SECONDS / BLOCK_SECONDS) as usize;
+    if levels.len() < window * 2 {
+        return Vec::new();
+    }
+
+    let mut sums = Vec::with_capacity(levels.len() + 1);
+    sums.push(0.0f64);
+    for level in levels {
+        sums.push(sums.last().unwrap() + *level as f64);
+    }
+    let mean = |start: usize| ((sums[start + window] - sums[start]) / window as f64) as f32;
+
+    let mut onsets: Vec<(usize, f32)> = (window..=levels.len() - window)
+        .filter_map(|index| {
+            let before = mean(index - window);
+            let after = mean(index);
+            if after < threshold || after < before * SECTION_ONSET_RATIO {
+                return None;
+            }
+            Some((index, after / before.max(f32::EPSILON)))
+        })
+        .collect();
+    onsets.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let spacing = (MIN_MARKER_SPACING / BLOCK_SECONDS) as usize;
+    let mut markers: Vec<usize> = Vec::new();
+    for (index, _) in onsets {
+        if markers.iter().all(|x| x.abs_diff(index) >= spacing) {
+            markers.push(index);
+        }
+    }
+    markers.sort();
+
+    markers
+        .into_iter()
+        .map(|x| x as f64 * BLOCK_SECONDS)
+        .collect()
+}
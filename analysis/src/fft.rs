@@ -1,5 +1,6 @@
-use log::debug;
-use rustfft::{num_complex::Complex, FftPlanner};
+use std::sync::Arc;
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
 use symphonia::core::audio::AudioBufferRef;
 use symphonia::core::audio::Signal;
 use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
@@ -11,12 +12,16 @@ use symphonia::core::formats::Track;
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
+use tracing::debug;
+
+use crate::energy::{EnergyTracker, TransitionProfile};
 
 pub struct AudioDescription {
     pub sample_rate: u32,
     pub duration: f64,
     pub total_samples: usize,
     pub spectrum: Vec<Complex<f32>>,
+    pub transitions: TransitionProfile,
 }
 
 pub fn build_hanning_window(window_size: usize) -> Vec<f32> {
@@ -27,6 +32,127 @@ pub fn build_hanning_window(window_size: usize) -> Vec<f32> {
         .collect()
 }
 
+/// A forward FFT over Hanning windowed frames, planned once and reused.
+///
+/// The planner picks the fastest SIMD implementation the CPU supports
+/// (AVX, SSE or NEON), and the scratch space is kept between frames so
+/// processing a frame doesn't allocate. Hand-written `std::simd` paths are
+/// out of scope, they need a nightly compiler and wouldn't beat the planner.
+pub struct WindowedFft {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl WindowedFft {
+    pub fn new(window_size: usize) -> Self {
+        let fft = FftPlanner::new().plan_fft_forward(window_size);
+        let scratch = vec![Complex::new(0.0, 0.0); fft.get_inplace_scratch_len()];
+
+        WindowedFft {
+            fft,
+            window: build_hanning_window(window_size),
+            buffer: vec![Complex::new(0.0, 0.0); window_size],
+            scratch,
+        }
+    }
+
+    pub fn window_size(&self) -> usize {
+        self.window.len()
+    }
+
+    /// Transform one frame, shorter frames are padded with silence.
+    pub fn process(&mut self, samples: &[f32]) -> &[Complex<f32>] {
+        let len = samples.len().min(self.buffer.len());
+        for ((value, &sample), &weight) in self.buffer[..len]
+            .iter_mut()
+            .zip(&samples[..len])
+            .zip(&self.window[..len])
+        {
+            *value = Complex::new(sample * weight, 0.0);
+        }
+        self.buffer[len..].fill(Complex::new(0.0, 0.0));
+
+        self.fft
+            .process_with_scratch(&mut self.buffer, &mut self.scratch);
+        &self.buffer
+    }
+}
+
+// Averages the spectra of overlapping windows over all samples pushed
+struct SpectrumAccumulator {
+    fft: WindowedFft,
+    overlap_size: usize,
+    avg_spectrum: Vec<Complex<f32>>,
+    count: usize,
+    total_samples: usize,
+    // Samples waiting until there are enough for one window
+    sample_buffer: Vec<f32>,
+}
+
+impl SpectrumAccumulator {
+    fn new(window_size: usize, overlap_size: usize) -> Self {
+        SpectrumAccumulator {
+            fft: WindowedFft::new(window_size),
+            overlap_size,
+            avg_spectrum: vec![Complex::new(0.0, 0.0); window_size],
+            count: 0,
+            total_samples: 0,
+            sample_buffer: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, sample: f32) {
+        let window_size = self.fft.window_size();
+        self.sample_buffer.push(sample);
+        self.total_samples += 1;
+
+        // Process the buffer when it reaches the window size
+        while self.sample_buffer.len() >= window_size {
+            let spectrum = self.fft.process(&self.sample_buffer[..window_size]);
+            debug!("FFT processed");
+
+            for (sum, value) in self.avg_spectrum.iter_mut().zip(spectrum) {
+                *sum += value;
+            }
+
+            self.count += 1;
+
+            // Remove the processed samples, keeping the overlap
+            self.sample_buffer
+                .drain(..(window_size - self.overlap_size));
+        }
+    }
+
+    fn finish(mut self) -> (Vec<Complex<f32>>, usize) {
+        // Process any remaining samples in the buffer
+        if !self.sample_buffer.is_empty() {
+            // The remaining samples are padded with zeros to reach window_size
+            let spectrum = self.fft.process(&self.sample_buffer);
+            debug!("FFT processed for remaining samples");
+
+            for (sum, value) in self.avg_spectrum.iter_mut().zip(spectrum) {
+                *sum += value;
+            }
+
+            self.count += 1;
+        }
+
+        if self.count == 0 {
+            panic!("No audio data processed");
+        }
+
+        // Calculate the final average spectrum.
+        for value in self.avg_spectrum.iter_mut() {
+            *value /= self.count as f32;
+        }
+        debug!("Final average spectrum calculated");
+
+        (self.avg_spectrum, self.total_samples)
+    }
+}
+
 pub fn get_format(file_path: &str) -> Result<Box<dyn FormatReader>, Error> {
     // Open the media source.
     let src = std::fs::File::open(file_path).expect("failed to open media");
@@ -74,7 +200,52 @@ pub fn get_codec_information(track: &Track) -> Result<(u32, f64), symphonia::cor
     Ok((sample_rate, duration_in_seconds))
 }
 
+// Frames converted from DSD before they are fed plane by plane, like a
+// decoded packet
+const DSD_PACKET_FRAMES: usize = 4096;
+
+fn fft_dsd(file_path: &str, window_size: usize, overlap_size: usize) -> AudioDescription {
+    let file = std::fs::File::open(file_path).expect("failed to open media");
+    let mut reader =
+        dsd::DsdReader::new(std::io::BufReader::new(file)).expect("unsupported DSD file");
+    let info = reader.info();
+    let channels = info.channels as usize;
+
+    let mut accumulator = SpectrumAccumulator::new(window_size, overlap_size);
+    let mut energy = EnergyTracker::new(info.pcm_rate);
+    loop {
+        let packet: Vec<f32> = reader.by_ref().take(DSD_PACKET_FRAMES * channels).collect();
+        if packet.is_empty() {
+            debug!("End of stream");
+            break;
+        }
+
+        for frame in packet.chunks(channels) {
+            energy.push(frame.iter().sum::<f32>() / channels as f32);
+        }
+
+        for channel in 0..channels {
+            for &sample in packet.iter().skip(channel).step_by(channels) {
+                accumulator.push(sample);
+            }
+        }
+    }
+
+    let (spectrum, total_samples) = accumulator.finish();
+    AudioDescription {
+        sample_rate: info.pcm_rate,
+        duration: info.duration().as_secs_f64(),
+        total_samples,
+        spectrum,
+        transitions: energy.finish(),
+    }
+}
+
 pub fn fft(file_path: &str, window_size: usize, overlap_size: usize) -> AudioDescription {
+    if dsd::is_dsd_path(std::path::Path::new(file_path)) {
+        return fft_dsd(file_path, window_size, overlap_size);
+    }
+
     // Get the audio track.
     let mut format = get_format(file_path).expect("no supported audio tracks");
     let track = format
@@ -97,19 +268,9 @@ pub fn fft(file_path: &str, window_size: usize, overlap_size: usize) -> AudioDes
     // Store the track identifier, it will be used to filter packets.
     let track_id = track.id;
 
-    // Prepare FFT planner and buffers.
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(window_size);
-    let mut buffer = vec![Complex::new(0.0, 0.0); window_size];
-    let mut avg_spectrum = vec![Complex::new(0.0, 0.0); window_size];
-    let mut count = 0;
-    let mut total_samples = 0;
-
-    // Precompute Hanning window
-    let hanning_window: Vec<f32> = build_hanning_window(window_size);
-
-    // Buffer to hold audio samples until we have enough for one window
-    let mut sample_buffer: Vec<f32> = Vec::new();
+    // Prepare the FFT and buffers.
+    let mut accumulator = SpectrumAccumulator::new(window_size, overlap_size);
+    let mut energy = EnergyTracker::new(sample_rate);
 
     // Decode loop.
     loop {
@@ -148,35 +309,25 @@ pub fn fft(file_path: &str, window_size: usize, overlap_size: usize) -> AudioDes
         // Macro to handle different AudioBufferRef types
         macro_rules! process_audio_buffer {
             ($buf:expr) => {
-                for plane in $buf.planes().planes() {
+                let planes = $buf.planes();
+                for plane in planes.planes() {
                     debug!("Processing plane with len: {}", plane.len());
                     for &sample in plane.iter() {
                         let sample: f32 = IntoSample::<f32>::into_sample(sample);
-                        sample_buffer.push(sample);
-                        total_samples += 1;
-
-                        // Process the buffer when it reaches the window size
-                        while sample_buffer.len() >= window_size {
-                            let chunk = &sample_buffer[..window_size];
-                            for (i, &sample) in chunk.iter().enumerate() {
-                                let windowed_sample = sample * hanning_window[i];
-                                buffer[i] = Complex::new(windowed_sample, 0.0);
-                            }
-
-                            fft.process(&mut buffer);
-                            debug!("FFT processed");
-
-                            for (i, value) in buffer.iter().enumerate() {
-                                avg_spectrum[i] += value;
-                            }
-
-                            count += 1;
-
-                            // Remove the processed samples, keeping the overlap
-                            sample_buffer.drain(..(window_size - overlap_size));
-                        }
+                        accumulator.push(sample);
                     }
                 }
+
+                // Energy is measured on the channels mixed down
+                let channels = planes.planes().len();
+                for frame in 0..$buf.frames() {
+                    let sum: f32 = planes
+                        .planes()
+                        .iter()
+                        .map(|plane| IntoSample::<f32>::into_sample(plane[frame]))
+                        .sum();
+                    energy.push(sum / channels as f32);
+                }
             };
         }
 
@@ -224,39 +375,12 @@ pub fn fft(file_path: &str, window_size: usize, overlap_size: usize) -> AudioDes
         }
     }
 
-    // Process any remaining samples in the buffer
-    if !sample_buffer.is_empty() {
-        // Pad the remaining samples with zeros to reach window_size
-        sample_buffer.resize(window_size, 0.0);
-        for (i, &sample) in sample_buffer.iter().enumerate() {
-            let windowed_sample = sample * hanning_window[i];
-            buffer[i] = Complex::new(windowed_sample, 0.0);
-        }
-
-        fft.process(&mut buffer);
-        debug!("FFT processed for remaining samples");
-
-        for (i, value) in buffer.iter().enumerate() {
-            avg_spectrum[i] += value;
-        }
-
-        count += 1;
-    }
-
-    if count == 0 {
-        panic!("No audio data processed");
-    }
-
-    // Calculate the final average spectrum.
-    for value in avg_spectrum.iter_mut() {
-        *value /= count as f32;
-    }
-    debug!("Final average spectrum calculated");
-
+    let (spectrum, total_samples) = accumulator.finish();
     AudioDescription {
         sample_rate,
         duration: duration_in_seconds,
         total_samples,
-        spectrum: avg_spectrum,
+        spectrum,
+        transitions: energy.finish(),
     }
 }
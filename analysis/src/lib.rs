@@ -1,3 +1,7 @@
+pub mod energy;
 pub mod fft;
 pub mod features;
+pub mod loudness;
+pub mod media_file;
+pub mod seek_table;
 pub mod analysis;
\ No newline at end of file
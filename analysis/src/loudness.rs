@@ -0,0 +1,299 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tracing::debug;
+
+/// Loudness ReplayGain 2.0 brings every track to, in LUFS.
+pub const REFERENCE_LOUDNESS: f64 = -18.0;
+
+// Gating blocks are 400 ms long and start every 100 ms
+const SUB_BLOCKS: usize = 4;
+const SUB_BLOCK_SECONDS: f64 = 0.1;
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+// Frames converted from DSD at once
+const DSD_PACKET_FRAMES: usize = 4096;
+
+/// Integrated loudness and sample peak of a track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// EBU R128 integrated loudness in LUFS, `None` for silence and tracks
+    /// shorter than a gating block.
+    pub integrated: Option<f64>,
+    /// The largest absolute sample value, 1.0 is full scale.
+    pub peak: f32,
+}
+
+impl Loudness {
+    /// The ReplayGain 2.0 track gain in dB.
+    pub fn track_gain(&self) -> Option<f32> {
+        self.integrated.map(|x| (REFERENCE_LOUDNESS - x) as f32)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Biquad {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[1] * self.y[0]
+            - self.a[2] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+// The K-weighting of ITU-R BS.1770, a high shelf modelling the head
+// followed by a high pass, designed for any sample rate
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    let f0 = 1681.974450955533;
+    let gain = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+// Surround channels count more, the LFE channel of 5.1 not at all
+fn channel_weight(channel: usize, channels: usize) -> f64 {
+    if channels < 6 {
+        return 1.0;
+    }
+    match channel {
+        3 => 0.0,
+        4 | 5 => 1.41,
+        _ => 1.0,
+    }
+}
+
+fn to_loudness(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+/// Measures the integrated loudness of a track as defined by EBU R128.
+pub struct LoudnessMeter {
+    channels: usize,
+    filters: Vec<[Biquad; 2]>,
+    sub_block_frames: usize,
+    sub_block_sum: f64,
+    sub_block_count: usize,
+    recent: VecDeque<f64>,
+    blocks: Vec<f64>,
+    peak: f32,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        let channels = channels.max(1);
+
+        LoudnessMeter {
+            channels,
+            filters: vec![k_weighting(sample_rate); channels],
+            sub_block_frames: ((sample_rate as f64 * SUB_BLOCK_SECONDS) as usize).max(1),
+            sub_block_sum: 0.0,
+            sub_block_count: 0,
+            recent: VecDeque::with_capacity(SUB_BLOCKS),
+            blocks: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+    /// Add interleaved frames, a trailing partial frame is ignored.
+    pub fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (channel, &sample) in frame.iter().enumerate() {
+                self.peak = self.peak.max(sample.abs());
+                let [shelf, high_pass] = &mut self.filters[channel];
+                let filtered = high_pass.process(shelf.process(sample as f64));
+                self.sub_block_sum += channel_weight(channel, self.channels) * filtered * filtered;
+            }
+
+            self.sub_block_count += 1;
+            if self.sub_block_count == self.sub_block_frames {
+                self.finish_sub_block();
+            }
+        }
+    }
+
+    fn finish_sub_block(&mut self) {
+        if self.recent.len() == SUB_BLOCKS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(self.sub_block_sum);
+        self.sub_block_sum = 0.0;
+        self.sub_block_count = 0;
+
+        if self.recent.len() == SUB_BLOCKS {
+            let frames = (SUB_BLOCKS * self.sub_block_frames) as f64;
+            self.blocks.push(self.recent.iter().sum::<f64>() / frames);
+        }
+    }
+
+    pub fn finish(self) -> Loudness {
+        let audible: Vec<f64> = self
+            .blocks
+            .into_iter()
+            .filter(|x| *x > 0.0 && to_loudness(*x) > ABSOLUTE_GATE)
+            .collect();
+
+        let integrated = if audible.is_empty() {
+            None
+        } else {
+            let mean = audible.iter().sum::<f64>() / audible.len() as f64;
+            let gate = to_loudness(mean) + RELATIVE_GATE;
+            let gated: Vec<f64> = audible
+                .into_iter()
+                .filter(|x| to_loudness(*x) > gate)
+                .collect();
+            Some(to_loudness(gated.iter().sum::<f64>() / gated.len() as f64))
+        };
+
+        Loudness {
+            integrated,
+            peak: self.peak,
+        }
+    }
+}
+
+fn measure_dsd(file_path: &str) -> Result<Loudness, Error> {
+    let file = std::fs::File::open(file_path)?;
+    let mut reader = dsd::DsdReader::new(std::io::BufReader::new(file))
+        .map_err(|_| Error::Unsupported("unsupported DSD file"))?;
+    let info = reader.info();
+    let channels = info.channels as usize;
+
+    let mut meter = LoudnessMeter::new(info.pcm_rate, channels);
+    loop {
+        let packet: Vec<f32> = reader.by_ref().take(DSD_PACKET_FRAMES * channels).collect();
+        if packet.is_empty() {
+            break;
+        }
+        meter.push(&packet);
+    }
+
+    Ok(meter.finish())
+}
+
+/// Decode a file and measure its loudness, much faster than a full
+/// analysis since nothing but the decoder runs.
+pub fn measure_loudness(file_path: &str) -> Result<Loudness, Error> {
+    if dsd::is_dsd_path(std::path::Path::new(file_path)) {
+        return measure_dsd(file_path);
+    }
+
+    let src = std::fs::File::open(file_path)?;
+    let mss = MediaSourceStream::new(Box::new(src), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = std::path::Path::new(file_path).extension() {
+        hint.with_extension(&ext.to_string_lossy());
+    }
+
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let mut format = probed.format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(Error::Unsupported("no supported audio tracks"))?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .ok_or(Error::Unsupported("no sample rate found"))?;
+
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut meter: Option<LoudnessMeter> = None;
+    let mut buffer: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(_)) | Err(Error::ResetRequired) => {
+                debug!("End of stream");
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(Error::IoError(_)) | Err(Error::DecodeError(_)) => {
+                debug!("Skipping a packet that can't be decoded");
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        let spec = *decoded.spec();
+        let buffer =
+            buffer.get_or_insert_with(|| SampleBuffer::<f32>::new(decoded.capacity() as u64, spec));
+        if buffer.capacity() < decoded.capacity() * spec.channels.count() {
+            *buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        }
+        buffer.copy_interleaved_ref(decoded);
+
+        meter
+            .get_or_insert_with(|| LoudnessMeter::new(sample_rate, spec.channels.count()))
+            .push(buffer.samples());
+    }
+
+    Ok(meter.map(LoudnessMeter::finish).unwrap_or(Loudness {
+        integrated: None,
+        peak: 0.0,
+    }))
+}
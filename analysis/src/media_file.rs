@@ -0,0 +1,154 @@
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use tracing::warn;
+
+/// How many times a transient failure is retried before giving up.
+const MAX_RETRIES: u32 = 3;
+/// Delay before the first retry, doubled for every following attempt.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub enum OpenError {
+    /// The file lives on storage that can't be reached right now, e.g. an
+    /// unmounted or disconnected network share.
+    Offline(io::Error),
+    /// The storage is there but the file was deleted or moved.
+    Missing(io::Error),
+    /// The file is there but empty or unreadable.
+    Unavailable(io::Error),
+}
+
+impl std::fmt::Display for OpenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenError::Offline(e) => write!(f, "Source offline: {}", e),
+            OpenError::Missing(e) => write!(f, "Source missing: {}", e),
+            OpenError::Unavailable(e) => write!(f, "Source unavailable: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for OpenError {}
+
+impl From<OpenError> for io::Error {
+    fn from(e: OpenError) -> Self {
+        match e {
+            OpenError::Offline(e) | OpenError::Missing(e) | OpenError::Unavailable(e) => e,
+        }
+    }
+}
+
+/// Errors network file systems report while a share is reconnecting.
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::StaleNetworkFileHandle
+            | ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::ResourceBusy
+    )
+}
+
+fn is_network_failure(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::StaleNetworkFileHandle
+            | ErrorKind::TimedOut
+            | ErrorKind::NetworkDown
+            | ErrorKind::NetworkUnreachable
+            | ErrorKind::HostUnreachable
+            | ErrorKind::NotConnected
+    )
+}
+
+fn try_open(path: &Path) -> io::Result<File> {
+    // Revalidate the file first, a share may have changed since the queue was built
+    let meta = fs::metadata(path)?;
+    if !meta.is_file() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "not a regular file",
+        ));
+    }
+    if meta.len() == 0 {
+        return Err(io::Error::new(ErrorKind::UnexpectedEof, "file is empty"));
+    }
+
+    File::open(path)
+}
+
+/// Open a media file, retrying transient failures of network
+/// file systems.
+///
+/// Without the library root a file that isn't found is always missing, use
+/// `open_library_file` to tell an offline library apart.
+///
+/// # Arguments
+/// * `path` - The full path of the media file.
+///
+/// # Returns
+/// * `Result<File, OpenError>` - The opened file, or why it can't be played
+///   right now.
+pub fn open_media_file(path: &Path) -> Result<File, OpenError> {
+    open(path, None)
+}
+
+/// Open a media file of a library, retrying transient failures of network
+/// file systems.
+///
+/// A file that isn't found is offline when the library root can't be
+/// listed either, e.g. an unmounted share, and missing otherwise.
+///
+/// # Arguments
+/// * `path` - The full path of the media file.
+/// * `lib_path` - The root directory of the library the file is in.
+///
+/// # Returns
+/// * `Result<File, OpenError>` - The opened file, or why it can't be played
+///   right now.
+pub fn open_library_file(path: &Path, lib_path: &Path) -> Result<File, OpenError> {
+    open(path, Some(lib_path))
+}
+
+fn open(path: &Path, lib_path: Option<&Path>) -> Result<File, OpenError> {
+    let mut attempt = 0;
+
+    loop {
+        match try_open(path) {
+            Ok(file) => return Ok(file),
+            Err(e) if is_transient(&e) && attempt < MAX_RETRIES => {
+                let delay = RETRY_DELAY * 2u32.pow(attempt);
+                warn!(
+                    "Transient error opening {:?} ({}), retrying in {:?}",
+                    path, e, delay
+                );
+                thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(e) => return Err(classify(lib_path, e)),
+        }
+    }
+}
+
+fn classify(lib_path: Option<&Path>, e: io::Error) -> OpenError {
+    if is_network_failure(&e) {
+        return OpenError::Offline(e);
+    }
+
+    // Only an unreachable root means the share itself went away, a folder
+    // missing inside a reachable library was deleted or moved like a track
+    if e.kind() == ErrorKind::NotFound {
+        if lib_path.is_some_and(|x| fs::read_dir(x).is_err()) {
+            return OpenError::Offline(e);
+        }
+
+        return OpenError::Missing(e);
+    }
+
+    OpenError::Unavailable(e)
+}
@@ -0,0 +1,438 @@
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind, Read, Seek};
+use std::path::Path;
+
+// Junk skipped while looking for the next MP3 frame, e.g. a broken tag
+const MAX_RESYNC: usize = 64 * 1024;
+// Bytes read per step while looking for a frame
+const RESYNC_CHUNK: usize = 4096;
+
+/// Where decoding has to start to reach a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeekPoint {
+    /// The first frame decoded from `byte_offset`, counted from the start
+    /// of the audio.
+    pub frame: u64,
+    /// The position of the packet in the file.
+    pub byte_offset: u64,
+}
+
+/// A coarse map from frames to byte offsets, about one point per second.
+///
+/// Decoding starts at the point before the target and skips the frames up
+/// to it, which is fast and exact for formats without a usable index of
+/// their own, like VBR MP3 without a TOC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeekTable {
+    pub sample_rate: u32,
+    /// Bytes at the start of the file every decoder needs, the Ogg headers
+    /// for example. Zero for MP3.
+    pub header_len: u64,
+    pub points: Vec<SeekPoint>,
+}
+
+impl SeekTable {
+    /// The last point at or before the given frame.
+    pub fn point_before(&self, frame: u64) -> Option<&SeekPoint> {
+        let index = self.points.partition_point(|x| x.frame <= frame);
+        index.checked_sub(1).map(|x| &self.points[x])
+    }
+
+    /// Serialize the table, little-endian and without padding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(12 + self.points.len() * 16);
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&self.header_len.to_le_bytes());
+        for point in &self.points {
+            bytes.extend_from_slice(&point.frame.to_le_bytes());
+            bytes.extend_from_slice(&point.byte_offset.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Read a table written by `to_bytes`, `None` if the bytes are corrupt.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 12 || !(bytes.len() - 12).is_multiple_of(16) {
+            return None;
+        }
+
+        let u64_at = |x: usize| u64::from_le_bytes(bytes[x..x + 8].try_into().unwrap());
+        let points = (12..bytes.len())
+            .step_by(16)
+            .map(|x| SeekPoint {
+                frame: u64_at(x),
+                byte_offset: u64_at(x + 8),
+            })
+            .collect();
+
+        Some(SeekTable {
+            sample_rate: u32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            header_len: u64_at(4),
+            points,
+        })
+    }
+}
+
+/// Whether the file is of a format the decoder seeks slowly or inaccurately.
+pub fn needs_seek_table(file_path: &Path) -> bool {
+    file_path
+        .extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| matches!(x.to_ascii_lowercase().as_str(), "mp3" | "ogg" | "oga"))
+}
+
+/// Build the seek table of an MP3 or Ogg Vorbis file without decoding it.
+///
+/// # Arguments
+/// * `file_path` - The full path of the media file.
+///
+/// # Returns
+/// * `io::Result<Option<SeekTable>>` - The table, `None` for other formats
+///   and for files without any audio frame.
+pub fn build_seek_table(file_path: &Path) -> io::Result<Option<SeekTable>> {
+    let extension = file_path
+        .extension()
+        .and_then(|x| x.to_str())
+        .unwrap_or_default();
+
+    build_seek_table_from(File::open(file_path)?, extension)
+}
+
+/// Build the seek table of a file already opened, see `build_seek_table`.
+pub fn build_seek_table_from<R: Read + Seek>(
+    reader: R,
+    extension: &str,
+) -> io::Result<Option<SeekTable>> {
+    let mut reader = Scanner::new(reader);
+
+    match extension.to_ascii_lowercase().as_str() {
+        "mp3" => scan_mp3(&mut reader),
+        "ogg" | "oga" => scan_ogg(&mut reader),
+        _ => Ok(None),
+    }
+}
+
+// Reads at offsets, keeping the buffer for the short jumps between frames
+struct Scanner<R> {
+    reader: BufReader<R>,
+    position: u64,
+}
+
+impl<R: Read + Seek> Scanner<R> {
+    fn new(reader: R) -> Self {
+        Scanner {
+            reader: BufReader::new(reader),
+            position: 0,
+        }
+    }
+
+    fn seek_to(&mut self, offset: u64) -> io::Result<()> {
+        if offset != self.position {
+            self.reader
+                .seek_relative(offset as i64 - self.position as i64)?;
+            self.position = offset;
+        }
+        Ok(())
+    }
+
+    // Fill the buffer from the offset, false at the end of the file
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<bool> {
+        self.seek_to(offset)?;
+        match self.reader.read_exact(buf) {
+            Ok(()) => {
+                self.position += buf.len() as u64;
+                Ok(true)
+            }
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                self.position = self.reader.stream_position()?;
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // Up to `len` bytes from the offset, fewer at the end of the file
+    fn read_chunk(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.seek_to(offset)?;
+        let mut chunk = Vec::with_capacity(len);
+        self.reader
+            .by_ref()
+            .take(len as u64)
+            .read_to_end(&mut chunk)?;
+        self.position += chunk.len() as u64;
+        Ok(chunk)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FrameHeader {
+    mpeg1: bool,
+    mono: bool,
+    sample_rate: u32,
+    samples: u32,
+    length: u64,
+}
+
+fn parse_frame_header(h: [u8; 4]) -> Option<FrameHeader> {
+    if h[0] != 0xFF || h[1] & 0xE0 != 0xE0 {
+        return None;
+    }
+
+    // 0 is MPEG 2.5, 1 is reserved, 2 is MPEG 2 and 3 is MPEG 1
+    let version = (h[1] >> 3) & 0x03;
+    // 1 is layer III, 2 is layer II and 3 is layer I
+    let layer = (h[1] >> 1) & 0x03;
+    let bitrate_index = (h[2] >> 4) as usize;
+    let rate_index = ((h[2] >> 2) & 0x03) as usize;
+    // Free format frames don't tell their length
+    if version == 1 || layer == 0 || bitrate_index == 0 || bitrate_index == 15 || rate_index == 3 {
+        return None;
+    }
+
+    let mpeg1 = version == 3;
+    let kbps: u32 = match (mpeg1, layer) {
+        (true, 3) => [
+            0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+        ],
+        (true, 2) => [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+        ],
+        (true, _) => [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ],
+        (false, 3) => [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+        ],
+        (false, _) => [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    }[bitrate_index];
+    let sample_rate = [44100, 48000, 32000][rate_index]
+        / match version {
+            3 => 1,
+            2 => 2,
+            _ => 4,
+        };
+    let bitrate = kbps * 1000;
+    let padding = ((h[2] >> 1) & 0x01) as u32;
+
+    let (samples, length) = match layer {
+        3 => (384, (12 * bitrate / sample_rate + padding) * 4),
+        2 => (1152, 144 * bitrate / sample_rate + padding),
+        _ if mpeg1 => (1152, 144 * bitrate / sample_rate + padding),
+        _ => (576, 72 * bitrate / sample_rate + padding),
+    };
+
+    Some(FrameHeader {
+        mpeg1,
+        mono: h[3] >> 6 == 3,
+        sample_rate,
+        samples,
+        length: length as u64,
+    })
+}
+
+fn read_frame_header<R: Read + Seek>(
+    reader: &mut Scanner<R>,
+    offset: u64,
+) -> io::Result<Option<FrameHeader>> {
+    let mut header = [0; 4];
+    if !reader.read_at(offset, &mut header)? {
+        return Ok(None);
+    }
+
+    Ok(parse_frame_header(header))
+}
+
+// The size of an ID3v2 tag at the start of the file, zero without one
+fn id3v2_len<R: Read + Seek>(reader: &mut Scanner<R>) -> io::Result<u64> {
+    let mut header = [0; 10];
+    if !reader.read_at(0, &mut header)? || &header[0..3] != b"ID3" {
+        return Ok(0);
+    }
+
+    // Sizes are syncsafe, seven bits per byte
+    let size = header[6..10]
+        .iter()
+        .fold(0u64, |size, x| (size << 7) | (*x & 0x7F) as u64);
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+
+    Ok(10 + size + footer)
+}
+
+// The Xing, Info and VBRI frames describe the stream and hold no audio
+fn is_info_frame<R: Read + Seek>(
+    reader: &mut Scanner<R>,
+    offset: u64,
+    header: &FrameHeader,
+) -> io::Result<bool> {
+    let side_info = match (header.mpeg1, header.mono) {
+        (true, true) => 17,
+        (true, false) => 32,
+        (false, true) => 9,
+        (false, false) => 17,
+    };
+
+    let mut tag = [0; 4];
+    if reader.read_at(offset + 4 + side_info, &mut tag)? && (&tag == b"Xing" || &tag == b"Info") {
+        return Ok(true);
+    }
+
+    Ok(reader.read_at(offset + 36, &mut tag)? && &tag == b"VBRI")
+}
+
+// Find the next frame that is followed by another one of the same stream
+fn resync<R: Read + Seek>(
+    reader: &mut Scanner<R>,
+    offset: u64,
+    sample_rate: Option<u32>,
+) -> io::Result<Option<(u64, FrameHeader)>> {
+    let mut start = offset;
+
+    while start - offset < MAX_RESYNC as u64 {
+        // Overlap the chunks so headers across their borders are found
+        let chunk = reader.read_chunk(start, RESYNC_CHUNK + 3)?;
+        if chunk.len() < 4 {
+            return Ok(None);
+        }
+
+        for index in 0..chunk.len() - 3 {
+            let candidate = start + index as u64;
+            let Some(header) = parse_frame_header(chunk[index..index + 4].try_into().unwrap())
+            else {
+                continue;
+            };
+            if sample_rate.is_some_and(|x| x != header.sample_rate) {
+                continue;
+            }
+            let next = read_frame_header(reader, candidate + header.length)?;
+            if next.is_some_and(|x| x.sample_rate == header.sample_rate) {
+                return Ok(Some((candidate, header)));
+            }
+        }
+
+        start += RESYNC_CHUNK as u64;
+    }
+
+    Ok(None)
+}
+
+fn scan_mp3<R: Read + Seek>(reader: &mut Scanner<R>) -> io::Result<Option<SeekTable>> {
+    let audio_start = id3v2_len(reader)?;
+    let Some((mut offset, first)) = resync(reader, audio_start, None)? else {
+        return Ok(None);
+    };
+    let sample_rate = first.sample_rate;
+
+    // The info frame isn't decoded into audio, the next frame is the first
+    if is_info_frame(reader, offset, &first)? {
+        offset += first.length;
+    }
+
+    let mut points = Vec::new();
+    let mut frame = 0;
+    let mut next_point = 0;
+
+    loop {
+        let header = match read_frame_header(reader, offset)? {
+            Some(header) if header.sample_rate == sample_rate => header,
+            // Junk between frames or the ID3v1 tag at the end
+            _ => match resync(reader, offset, Some(sample_rate))? {
+                Some((next_offset, header)) => {
+                    offset = next_offset;
+                    header
+                }
+                None => break,
+            },
+        };
+
+        if frame >= next_point {
+            points.push(SeekPoint {
+                frame,
+                byte_offset: offset,
+            });
+            next_point = frame + sample_rate as u64;
+        }
+
+        frame += header.samples as u64;
+        offset += header.length;
+    }
+
+    Ok((!points.is_empty()).then_some(SeekTable {
+        sample_rate,
+        header_len: 0,
+        points,
+    }))
+}
+
+fn scan_ogg<R: Read + Seek>(reader: &mut Scanner<R>) -> io::Result<Option<SeekTable>> {
+    let mut offset = 0;
+    let mut serial = None;
+    let mut sample_rate = 0;
+    let mut header_len = None;
+    let mut points = Vec::new();
+    let mut last_granule = 0;
+    let mut next_point = 0;
+
+    loop {
+        let mut header = [0; 27];
+        if !reader.read_at(offset, &mut header)? || &header[0..4] != b"OggS" {
+            break;
+        }
+
+        let continued = header[5] & 0x01 != 0;
+        let granule = i64::from_le_bytes(header[6..14].try_into().unwrap());
+        let page_serial = u32::from_le_bytes(header[14..18].try_into().unwrap());
+        let mut lacing = vec![0; header[26] as usize];
+        if !reader.read_at(offset + 27, &mut lacing)? {
+            break;
+        }
+        let body_offset = offset + 27 + lacing.len() as u64;
+        let body_len: u64 = lacing.iter().map(|x| *x as u64).sum();
+
+        match serial {
+            None => {
+                // Only Vorbis is decoded by the player
+                let mut identification = [0; 16];
+                if !reader.read_at(body_offset, &mut identification)?
+                    || &identification[0..7] != b"\x01vorbis"
+                {
+                    return Ok(None);
+                }
+                sample_rate = u32::from_le_bytes(identification[12..16].try_into().unwrap());
+                serial = Some(page_serial);
+            }
+            // Chained and multiplexed streams are left to the decoder
+            Some(serial) if serial != page_serial => return Ok(None),
+            Some(_) => {}
+        }
+
+        // Header pages don't end any audio packet
+        if header_len.is_none() && granule != 0 {
+            header_len = Some(offset);
+        }
+
+        if header_len.is_some() && granule >= 0 {
+            // A page continuing a packet can't be decoded on its own
+            let position = last_granule as u64;
+            if !continued && position >= next_point && sample_rate > 0 {
+                points.push(SeekPoint {
+                    frame: position,
+                    byte_offset: offset,
+                });
+                next_point = position + sample_rate as u64;
+            }
+            last_granule = granule;
+        }
+
+        offset = body_offset + body_len;
+    }
+
+    Ok(match header_len {
+        Some(header_len) if !points.is_empty() => Some(SeekTable {
+            sample_rate,
+            header_len,
+            points,
+        }),
+        _ => None,
+    })
+}
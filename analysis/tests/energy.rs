@@ -0,0 +1,60 @@
+use analysis::energy::{EnergyTracker, BLOCK_SECONDS, EDGE_SECONDS};
+
+const SAMPLE_RATE: u32 = 1000;
+
+fn push_seconds(tracker: &mut EnergyTracker, seconds: f64, amplitude: f32) {
+    for index in 0..(seconds * SAMPLE_RATE as f64) as usize {
+        let sign = if index % 2 == 0 { 1.0 } else { -1.0 };
+        tracker.push(sign * amplitude);
+    }
+}
+
+#[test]
+fn mix_points_skip_quiet_edges() {
+    let mut tracker = EnergyTracker::new(SAMPLE_RATE);
+    push_seconds(&mut tracker, 4.0, 0.01);
+    push_seconds(&mut tracker, 60.0, 0.5);
+    push_seconds(&mut tracker, 6.0, 0.01);
+    let profile = tracker.finish();
+
+    let edge_blocks = (EDGE_SECONDS / BLOCK_SECONDS) as usize;
+    assert_eq!(profile.intro.len(), edge_blocks);
+    assert_eq!(profile.outro.len(), edge_blocks);
+    assert!((profile.mix_in - 4.0).abs() < 1e-9, "{}", profile.mix_in);
+    assert!((profile.mix_out - 64.0).abs() < 1e-9, "{}", profile.mix_out);
+}
+
+#[test]
+fn short_and_silent_tracks_have_sane_mix_points() {
+    let mut tracker = EnergyTracker::new(SAMPLE_RATE);
+    push_seconds(&mut tracker, 3.0, 0.5);
+    let profile = tracker.finish();
+    assert_eq!(profile.mix_in, 0.0);
+    assert!((profile.mix_out - 3.0).abs() < 1e-9, "{}", profile.mix_out);
+
+    let mut tracker = EnergyTracker::new(SAMPLE_RATE);
+    push_seconds(&mut tracker, 3.0, 0.0);
+    let profile = tracker.finish();
+    assert!(profile.mix_in <= profile.mix_out);
+
+    assert_eq!(EnergyTracker::new(SAMPLE_RATE).finish().mix_out, 0.0);
+}
+
+#[test]
+fn long_mixes_get_markers_after_breakdowns() {
+    let mut tracker = EnergyTracker::new(SAMPLE_RATE);
+    push_seconds(&mut tracker, 600.0, 0.5);
+    push_seconds(&mut tracker, 30.0, 0.01);
+    push_seconds(&mut tracker, 40.0, 0.5);
+    // A shallower breakdown too close to the previous one to count
+    push_seconds(&mut tracker, 10.0, 0.1);
+    push_seconds(&mut tracker, 560.0, 0.5);
+    let profile = tracker.finish();
+    assert_eq!(profile.markers, vec![630.0]);
+
+    let mut tracker = EnergyTracker::new(SAMPLE_RATE);
+    push_seconds(&mut tracker, 60.0, 0.5);
+    push_seconds(&mut tracker, 30.0, 0.01);
+    push_seconds(&mut tracker, 60.0, 0.5);
+    assert!(tracker.finish().markers.is_empty());
+}
@@ -0,0 +1,49 @@
+use analysis::loudness::LoudnessMeter;
+
+const SAMPLE_RATE: u32 = 48000;
+
+// A 997 Hz tone on both channels, at the given level in dBFS
+fn push_tone(meter: &mut LoudnessMeter, seconds: f64, level: f64) {
+    let amplitude = 10f64.powf(level / 20.0);
+    let frames = (seconds * SAMPLE_RATE as f64) as usize;
+    let samples: Vec<f32> = (0..frames)
+        .flat_map(|index| {
+            let phase = 2.0 * std::f64::consts::PI * 997.0 * index as f64 / SAMPLE_RATE as f64;
+            let sample = (amplitude * phase.sin()) as f32;
+            [sample, sample]
+        })
+        .collect();
+    meter.push(&samples);
+}
+
+#[test]
+fn a_reference_tone_is_measured_at_its_level() {
+    let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+    push_tone(&mut meter, 10.0, -23.0);
+    let loudness = meter.finish();
+
+    let integrated = loudness.integrated.unwrap();
+    assert!((integrated + 23.0).abs() < 0.1, "{}", integrated);
+    assert!((loudness.track_gain().unwrap() - 5.0).abs() < 0.1);
+    assert!((loudness.peak - 10f32.powf(-23.0 / 20.0)).abs() < 1e-3);
+}
+
+#[test]
+fn quiet_passages_are_gated() {
+    let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+    push_tone(&mut meter, 10.0, -23.0);
+    push_tone(&mut meter, 10.0, -60.0);
+    let integrated = meter.finish().integrated.unwrap();
+    assert!((integrated + 23.0).abs() < 0.2, "{}", integrated);
+}
+
+#[test]
+fn silence_and_short_tracks_have_no_loudness() {
+    let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+    push_tone(&mut meter, 2.0, -200.0);
+    assert_eq!(meter.finish().integrated, None);
+
+    let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+    push_tone(&mut meter, 0.3, -23.0);
+    assert_eq!(meter.finish().integrated, None);
+}
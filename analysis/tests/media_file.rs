@@ -0,0 +1,38 @@
+use std::fs;
+
+use analysis::media_file::{open_library_file, open_media_file, OpenError};
+
+#[test]
+fn open_failures_are_told_apart() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let path = dir.path().join("track.flac");
+    fs::write(&path, b"fLaC").unwrap();
+    assert!(open_media_file(&path).is_ok());
+
+    // A deleted track, next to files that are still there
+    let result = open_media_file(&dir.path().join("deleted.flac"));
+    assert!(matches!(result, Err(OpenError::Missing(_))));
+
+    // A folder gone from a reachable library was deleted as well
+    let result = open_library_file(&dir.path().join("album").join("track.flac"), dir.path());
+    assert!(matches!(result, Err(OpenError::Missing(_))));
+
+    // Only an unreachable root means the share went away
+    let share = dir.path().join("share");
+    let result = open_library_file(&share.join("album").join("track.flac"), &share);
+    assert!(matches!(result, Err(OpenError::Offline(_))));
+    let result = open_media_file(&share.join("album").join("track.flac"));
+    assert!(matches!(result, Err(OpenError::Missing(_))));
+
+    let path = dir.path().join("empty.flac");
+    fs::write(&path, b"").unwrap();
+    assert!(matches!(
+        open_media_file(&path),
+        Err(OpenError::Unavailable(_))
+    ));
+    assert!(matches!(
+        open_media_file(dir.path()),
+        Err(OpenError::Unavailable(_))
+    ));
+}
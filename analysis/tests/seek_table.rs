@@ -0,0 +1,142 @@
+use std::io::Cursor;
+
+use analysis::seek_table::{build_seek_table_from, SeekPoint, SeekTable};
+
+// MPEG 1 layer III, 128 kbps, 44.1 kHz, stereo
+const MP3_HEADER: [u8; 4] = [0xFF, 0xFB, 0x90, 0x00];
+const MP3_FRAME_LEN: usize = 417;
+const MP3_FRAME_SAMPLES: u64 = 1152;
+
+fn mp3_frame(tag: Option<&[u8; 4]>) -> Vec<u8> {
+    let mut frame = vec![0; MP3_FRAME_LEN];
+    frame[..4].copy_from_slice(&MP3_HEADER);
+    if let Some(tag) = tag {
+        frame[36..40].copy_from_slice(tag);
+    }
+    frame
+}
+
+fn ogg_page(flags: u8, granule: i64, body: &[u8]) -> Vec<u8> {
+    let mut page = b"OggS".to_vec();
+    page.extend_from_slice(&[0, flags]);
+    page.extend_from_slice(&granule.to_le_bytes());
+    page.extend_from_slice(&7u32.to_le_bytes());
+    page.extend_from_slice(&[0; 8]);
+    page.push(1);
+    page.push(body.len() as u8);
+    page.extend_from_slice(body);
+    page
+}
+
+#[test]
+fn mp3_frames_are_found_between_tags_and_junk() {
+    // An ID3v2 tag with ten bytes of content
+    let mut file = b"ID3\x03\x00\x00\x00\x00\x00\x0A".to_vec();
+    file.extend_from_slice(&[0; 10]);
+    file.extend(mp3_frame(Some(b"Info")));
+    let audio_start = file.len() as u64;
+
+    let mut junk_after = 0;
+    for index in 0..100 {
+        file.extend(mp3_frame(None));
+        if index == 50 {
+            file.extend_from_slice(b"not a frame");
+            junk_after = file.len() as u64;
+        }
+    }
+    // An ID3v1 tag at the end
+    file.extend_from_slice(b"TAG");
+    file.extend_from_slice(&[0; 125]);
+
+    let table = build_seek_table_from(Cursor::new(file), "MP3")
+        .unwrap()
+        .unwrap();
+    assert_eq!(table.sample_rate, 44100);
+    assert_eq!(table.header_len, 0);
+
+    // One point per second of audio, 38.3 frames each
+    let frame_len = MP3_FRAME_LEN as u64;
+    assert_eq!(
+        table.points,
+        vec![
+            SeekPoint {
+                frame: 0,
+                byte_offset: audio_start,
+            },
+            SeekPoint {
+                frame: 39 * MP3_FRAME_SAMPLES,
+                byte_offset: audio_start + 39 * frame_len,
+            },
+            SeekPoint {
+                frame: 78 * MP3_FRAME_SAMPLES,
+                byte_offset: junk_after + 27 * frame_len,
+            },
+        ]
+    );
+}
+
+#[test]
+fn ogg_pages_continuing_a_packet_are_not_seek_points() {
+    let mut identification = b"\x01vorbis".to_vec();
+    identification.extend_from_slice(&0u32.to_le_bytes());
+    identification.push(2);
+    identification.extend_from_slice(&44100u32.to_le_bytes());
+    identification.resize(30, 0);
+
+    let mut file = ogg_page(0x02, 0, &identification);
+    file.extend(ogg_page(0, 0, &[0; 50]));
+    let header_len = file.len() as u64;
+
+    let mut offsets = Vec::new();
+    for page in 1..=6 {
+        offsets.push(file.len() as u64);
+        // The fifth audio page continues a packet of the fourth
+        let flags = if page == 5 { 0x01 } else { 0 };
+        file.extend(ogg_page(flags, page * 22050, &[0; 100]));
+    }
+
+    let table = build_seek_table_from(Cursor::new(file), "ogg")
+        .unwrap()
+        .unwrap();
+    assert_eq!(table.sample_rate, 44100);
+    assert_eq!(table.header_len, header_len);
+    assert_eq!(
+        table
+            .points
+            .iter()
+            .map(|x| (x.frame, x.byte_offset))
+            .collect::<Vec<_>>(),
+        vec![(0, offsets[0]), (44100, offsets[2]), (110250, offsets[5])]
+    );
+}
+
+#[test]
+fn tables_survive_serialization() {
+    let table = SeekTable {
+        sample_rate: 48000,
+        header_len: 4096,
+        points: vec![
+            SeekPoint {
+                frame: 0,
+                byte_offset: 4096,
+            },
+            SeekPoint {
+                frame: 48000,
+                byte_offset: 20000,
+            },
+        ],
+    };
+
+    assert_eq!(
+        SeekTable::from_bytes(&table.to_bytes()),
+        Some(table.clone())
+    );
+    assert_eq!(SeekTable::from_bytes(&[0; 13]), None);
+
+    assert_eq!(table.point_before(47999).unwrap().frame, 0);
+    assert_eq!(table.point_before(48000).unwrap().frame, 48000);
+    assert_eq!(
+        build_seek_table_from(Cursor::new(vec![0; 64]), "flac").unwrap(),
+        None
+    );
+}
@@ -1,9 +1,14 @@
 use std::path::PathBuf;
 use tracing_subscriber::filter::EnvFilter;
 
-use database::actions::analysis::{analysis_audio_library, empty_progress_callback as empty_analysis_progress_callback};
-use database::actions::metadata::{empty_progress_callback  as empty_scan_progress_callback, scan_audio_library};
+use database::actions::analysis::{
+    analysis_audio_library, empty_progress_callback as empty_analysis_progress_callback,
+};
+use database::actions::metadata::{
+    empty_progress_callback as empty_scan_progress_callback, scan_audio_library,
+};
 use database::actions::recommendation::sync_recommendation;
+use database::actions::throttle::AnalysisPace;
 use database::connection::{connect_main_db, connect_recommendation_db, connect_search_db};
 
 #[tokio::main]
@@ -40,9 +45,16 @@ async fn main() {
     .await;
 
     // Analyze the audio files in the database
-    analysis_audio_library(&main_db, &root_path, 10, empty_analysis_progress_callback, None)
-        .await
-        .expect("Audio analysis failed");
+    analysis_audio_library(
+        &main_db,
+        &root_path,
+        None,
+        || AnalysisPace::new(10),
+        empty_analysis_progress_callback,
+        None,
+    )
+    .await
+    .expect("Audio analysis failed");
 
     let analysis_db = connect_recommendation_db(&path).unwrap();
     let _ = sync_recommendation(&main_db, &analysis_db).await;
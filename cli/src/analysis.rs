@@ -2,6 +2,7 @@ use std::path::Path;
 
 use database::actions::analysis::{analysis_audio_library, empty_progress_callback};
 use database::actions::recommendation::sync_recommendation;
+use database::actions::throttle::AnalysisPace;
 use database::connection::{MainDbConnection, RecommendationDbConnection};
 
 pub async fn analyse_audio_library(
@@ -9,7 +10,16 @@ pub async fn analyse_audio_library(
     analysis_db: &RecommendationDbConnection,
     path: &Path,
 ) {
-    if let Err(e) = analysis_audio_library(main_db, path, 10, empty_progress_callback, None).await {
+    if let Err(e) = analysis_audio_library(
+        main_db,
+        path,
+        None,
+        || AnalysisPace::new(10),
+        empty_progress_callback,
+        None,
+    )
+    .await
+    {
         eprintln!("Audio analysis failed: {}", e);
         return;
     }
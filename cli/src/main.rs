@@ -1,9 +1,9 @@
 use clap::{Parser, Subcommand};
 use database::actions::search::search_for;
 use dunce::canonicalize;
-use log::{error, info};
 use rune::index::index_audio_library;
 use std::path::PathBuf;
+use tracing::{error, info};
 use tracing_subscriber::filter::EnvFilter;
 
 use database::actions::metadata::{empty_progress_callback, scan_audio_library};
@@ -1,8 +1,8 @@
 use dunce::canonicalize;
-use log::{debug, error, info};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use tokio::task;
+use tracing::{debug, error, info};
 
 use database::actions::file::get_random_files;
 use database::connection::MainDbConnection;
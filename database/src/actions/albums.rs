@@ -1,10 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use sea_orm::prelude::*;
 
-use crate::entities::{albums, media_file_albums};
+use crate::entities::{albums, media_file_albums, media_metadata};
 use crate::{get_all_ids, get_by_id, get_by_ids, get_groups};
 
+use super::collation::sort_name;
 use super::utils::CountByFirstLetter;
 
 impl CountByFirstLetter for albums::Entity {
@@ -17,7 +18,69 @@ impl CountByFirstLetter for albums::Entity {
     }
 }
 
-get_groups!(get_albums_groups, albums, media_file_albums, AlbumId);
+get_groups!(
+    get_albums_groups,
+    albums,
+    media_file_albums,
+    AlbumId,
+    |x: &albums::Model| sort_name(&x.name, x.sort_name.as_deref()).to_string()
+);
 get_all_ids!(get_media_file_ids_of_album, media_file_albums, AlbumId);
-get_by_ids!(get_albums_by_ids, albums);
+get_by_ids!(get_albums_by_ids, albums, media_file_albums, AlbumId);
 get_by_id!(get_album_by_id, albums);
+
+/// Get the album and track number of the given files.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, (i32, i32)>, DbErr>` - The album ID and track number by file ID,
+///   files without an album or track number are left out.
+pub async fn get_album_tracks_of_files(
+    db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, (i32, i32)>, DbErr> {
+    let items = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .all(db)
+        .await?;
+
+    Ok(items
+        .into_iter()
+        .filter_map(|x| Some((x.media_file_id, (x.album_id, x.track_number?))))
+        .collect())
+}
+
+/// Get the disc number of the given files.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, i32>, DbErr>` - The disc number by file ID, files
+///   without a valid tag are left out.
+pub async fn get_disc_numbers_of_files(
+    db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, i32>, DbErr> {
+    let entries = media_metadata::Entity::find()
+        .filter(
+            media_metadata::Column::FileId
+                .is_in(file_ids.to_vec())
+                .and(media_metadata::Column::MetaKey.eq("disc_number")),
+        )
+        .all(db)
+        .await?;
+
+    // Tags like "1/2" carry the number of discs as well
+    Ok(entries
+        .into_iter()
+        .filter_map(|x| {
+            let disc = x.meta_value.split('/').next()?.trim().parse().ok()?;
+            Some((x.file_id, disc))
+        })
+        .collect())
+}
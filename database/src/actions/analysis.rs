@@ -1,7 +1,11 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::panic::AssertUnwindSafe;
 use std::path::Path;
 use std::sync::Arc;
 
-use log::{error, info};
+use futures::FutureExt;
+
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
 use sea_orm::entity::prelude::*;
@@ -10,11 +14,20 @@ use sea_orm::QuerySelect;
 use sea_orm::{ActiveValue, TransactionTrait};
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 
 use analysis::analysis::{analyze_audio, normalize_analysis_result, NormalizedAnalysisResult};
+use analysis::media_file::open_media_file;
 
+use crate::connection::{is_library_root_reachable, AnalysisCacheConnection};
 use crate::entities::{media_analysis, media_files};
 
+use super::analysis_cache::{cache_analysis, get_cached_analysis};
+use super::analysis_exchange::{to_active_model, AnalysisFeatures};
+use super::mix_markers::set_onset_markers;
+use super::seek_tables::{build_seek_tables, insert_seek_tables};
+use super::throttle::AnalysisPace;
+
 use super::utils::DatabaseExecutor;
 
 pub fn empty_progress_callback(_processed: usize, _total: usize) {}
@@ -24,19 +37,31 @@ struct FileIdResult {
     file_id: i32, // or whatever the type of FileId is
 }
 
-pub async fn analysis_audio_library<F>(
+/// Analyse every file of the library that has no analysis results yet.
+///
+/// Files whose content hash is found in `analysis_cache` reuse the results
+/// of another library instead of being decoded again, and new results are
+/// added to the cache.
+///
+/// `pace` is asked before every batch how many files to analyse and how
+/// long to rest afterwards, so analysis can slow down on a hot or
+/// unplugged device.
+pub async fn analysis_audio_library<F, P>(
     main_db: &DatabaseConnection,
     lib_path: &Path,
-    batch_size: usize,
+    analysis_cache: Option<&AnalysisCacheConnection>,
+    pace: P,
     progress_callback: F,
     cancel_token: Option<CancellationToken>,
 ) -> Result<usize, sea_orm::DbErr>
 where
     F: Fn(usize, usize) + Send + Sync + 'static,
+    P: Fn() -> AnalysisPace,
 {
+    let mut current_pace = pace();
     info!(
-        "Starting audio library analysis with batch size: {}",
-        batch_size
+        "Starting audio library analysis with pace: {:?}",
+        current_pace
     );
 
     let total_tasks = media_files::Entity::find().count(main_db).await? as usize;
@@ -61,9 +86,15 @@ where
     let mut total_processed = existed_tasks.len();
 
     loop {
+        let next_pace = pace();
+        if next_pace != current_pace {
+            info!("Analysis pace changed: {:?}", next_pace);
+            current_pace = next_pace;
+        }
+
         // Fetch the next batch of files
         let files: Vec<media_files::Model> = cursor
-            .first(batch_size.try_into().unwrap())
+            .first(current_pace.batch_size.try_into().unwrap())
             .all(main_db)
             .await?;
 
@@ -79,21 +110,61 @@ where
             }
         }
 
+        // Reuse the results of copies analysed in any library
+        let cached = match analysis_cache {
+            Some(cache) => {
+                let file_hashes: Vec<String> = files.iter().map(|x| x.file_hash.clone()).collect();
+                get_cached_analysis(cache, &file_hashes)
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!("Unable to read the analysis cache: {}", e);
+                        HashMap::new()
+                    })
+            }
+            None => HashMap::new(),
+        };
+        let (cached_files, uncached_files): (Vec<_>, Vec<_>) = files
+            .iter()
+            .cloned()
+            .partition(|x| cached.contains_key(&x.file_hash));
+
+        // Stop instead of failing every file while the share is gone,
+        // the remaining files are picked up by the next run
+        if !uncached_files.is_empty() && !is_library_root_reachable(lib_path) {
+            return Err(sea_orm::DbErr::Custom(format!(
+                "Library root is offline: {:?}",
+                lib_path
+            )));
+        }
+
         let lib_path = Arc::new(lib_path.to_path_buf());
 
-        info!("Starting a new batch: {} tasks", files.len());
+        info!(
+            "Starting a new batch: {} tasks, {} cached",
+            uncached_files.len(),
+            cached_files.len()
+        );
 
         // Parallel processing using rayon
-        let analysis_results: Vec<_> = files
+        let analysis_results: Vec<_> = uncached_files
             .par_iter()
             .map(|file| {
                 let lib_path: Arc<std::path::PathBuf> = Arc::clone(&lib_path);
                 let file = file.clone();
 
                 async move {
-                    let result = analysis_file(&file, &lib_path).await;
+                    // A single broken file must not take down the whole batch
+                    let result = AssertUnwindSafe(analysis_file(&file, &lib_path))
+                        .catch_unwind()
+                        .await
+                        .map_err(|_| {
+                            DbErr::Custom(format!("Analysis panicked: {}", file.file_name))
+                        })?
+                        .map_err(|e| {
+                            DbErr::Custom(format!("Unable to read {}: {}", file.file_name, e))
+                        })?;
                     info!("Analysed: {}", file.file_name);
-                    Ok::<_, sea_orm::DbErr>((file.id, Some(result)))
+                    Ok::<_, sea_orm::DbErr>((file.id, file.file_hash, Some(result)))
                 }
             })
             .collect::<Vec<_>>();
@@ -101,25 +172,68 @@ where
         // Await all the futures
         let analysis_results: Vec<_> = futures::future::join_all(analysis_results).await;
 
+        // Seek tables only need the frame headers, cached files get them too
+        let seek_tables = if is_library_root_reachable(&lib_path) {
+            build_seek_tables(&files, &lib_path)
+        } else {
+            Vec::new()
+        };
+
+        // Files analysed on demand while the batch was running keep their results
+        let batch_ids: Vec<i32> = files.iter().map(|x| x.id).collect();
+        let analysed_meanwhile: HashSet<i32> = media_analysis::Entity::find()
+            .select_only()
+            .column(media_analysis::Column::FileId)
+            .filter(media_analysis::Column::FileId.is_in(batch_ids))
+            .into_tuple::<i32>()
+            .all(main_db)
+            .await?
+            .into_iter()
+            .collect();
+
         // Start a transaction
         let txn = main_db.begin().await?;
 
+        for file in &cached_files {
+            if analysed_meanwhile.contains(&file.id) {
+                total_processed += 1;
+                continue;
+            }
+            media_analysis::Entity::insert(to_active_model(file.id, &cached[&file.file_hash]))
+                .exec(&txn)
+                .await?;
+            total_processed += 1;
+        }
+
+        let mut new_results = Vec::new();
         for result in analysis_results {
             match result {
-                Ok((file_id, Some(normalized_result))) => {
+                Ok((file_id, _, Some(_))) if analysed_meanwhile.contains(&file_id) => {
+                    total_processed += 1;
+                }
+                Ok((file_id, file_hash, Some(normalized_result))) => {
+                    new_results.push((file_hash, features_of_result(&normalized_result)));
                     insert_analysis_result(&txn, file_id, normalized_result).await?;
                     total_processed += 1;
                 }
-                Ok((_, None)) => {} // File was already processed
+                Ok((_, _, None)) => {} // File was already processed
                 Err(e) => {
                     error!("Error processing file: {:?}", e);
                 }
             }
         }
 
+        insert_seek_tables(&txn, &seek_tables).await?;
+
         // Commit the transaction
         txn.commit().await?;
 
+        if let Some(cache) = analysis_cache {
+            if let Err(e) = cache_analysis(cache, &new_results).await {
+                warn!("Unable to update the analysis cache: {}", e);
+            }
+        }
+
         // Update progress
         progress_callback(total_processed, total_tasks);
 
@@ -128,12 +242,110 @@ where
             info!("Moving cursor after file ID: {}", last_file.id);
             cursor.after(last_file.id);
         }
+
+        // Let the device rest, waking up early if cancelled
+        if !current_pace.pause.is_zero() {
+            match cancel_token {
+                Some(ref token) => {
+                    let _ = tokio::time::timeout(current_pace.pause, token.cancelled()).await;
+                }
+                None => tokio::time::sleep(current_pace.pause).await,
+            }
+        }
     }
 
     info!("Audio library analysis completed.");
     Ok(total_tasks)
 }
 
+/// Analyse a single file right away instead of waiting for its batch.
+///
+/// Results already in the database or in `analysis_cache` are used as they
+/// are, the file is only decoded when neither has it.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `lib_path` - The root of the library.
+/// * `analysis_cache` - Results shared between libraries, if available.
+/// * `file_id` - The ID of the file.
+///
+/// # Returns
+/// * `Result<Option<media_analysis::Model>, DbErr>` - The analysis of the
+///   file, `None` if the file is not in the library.
+pub async fn analyse_single_file(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    analysis_cache: Option<&AnalysisCacheConnection>,
+    file_id: i32,
+) -> Result<Option<media_analysis::Model>, DbErr> {
+    let Some(file) = media_files::Entity::find_by_id(file_id)
+        .one(main_db)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let existing = media_analysis::Entity::find()
+        .filter(media_analysis::Column::FileId.eq(file_id))
+        .one(main_db)
+        .await?;
+    if existing.is_some() {
+        return Ok(existing);
+    }
+
+    let cached = match analysis_cache {
+        Some(cache) => get_cached_analysis(cache, std::slice::from_ref(&file.file_hash))
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Unable to read the analysis cache: {}", e);
+                HashMap::new()
+            })
+            .remove(&file.file_hash),
+        None => None,
+    };
+
+    match cached {
+        Some(features) => {
+            media_analysis::Entity::insert(to_active_model(file.id, &features))
+                .exec(main_db)
+                .await?;
+        }
+        None => {
+            if !is_library_root_reachable(lib_path) {
+                return Err(DbErr::Custom(format!(
+                    "Library root is offline: {:?}",
+                    lib_path
+                )));
+            }
+
+            let result = AssertUnwindSafe(analysis_file(&file, lib_path))
+                .catch_unwind()
+                .await
+                .map_err(|_| DbErr::Custom(format!("Analysis panicked: {}", file.file_name)))?
+                .map_err(|e| DbErr::Custom(format!("Unable to read {}: {}", file.file_name, e)))?;
+            info!("Analysed on demand: {}", file.file_name);
+
+            if let Some(cache) = analysis_cache {
+                let entries = [(file.file_hash.clone(), features_of_result(&result))];
+                if let Err(e) = cache_analysis(cache, &entries).await {
+                    warn!("Unable to update the analysis cache: {}", e);
+                }
+            }
+            insert_analysis_result(main_db, file.id, result).await?;
+        }
+    }
+
+    if is_library_root_reachable(lib_path) {
+        let seek_tables = build_seek_tables(std::slice::from_ref(&file), lib_path);
+        insert_seek_tables(main_db, &seek_tables).await?;
+    }
+
+    media_analysis::Entity::find()
+        .filter(media_analysis::Column::FileId.eq(file_id))
+        .one(main_db)
+        .await
+}
+
 /// Process a file if it has not been analyzed yet. Perform audio analysis and store the results
 /// in the database.
 ///
@@ -141,10 +353,16 @@ where
 /// * `db` - A reference to the database connection.
 /// * `file` - A reference to the file model.
 /// * `root_path` - The root path for the audio files.
-async fn analysis_file(file: &media_files::Model, lib_path: &Path) -> NormalizedAnalysisResult {
+async fn analysis_file(
+    file: &media_files::Model,
+    lib_path: &Path,
+) -> io::Result<NormalizedAnalysisResult> {
     // Construct the full path to the file
     let file_path = lib_path.join(&file.directory).join(&file.file_name);
 
+    // Files on a share that is reconnecting are retried like in playback
+    open_media_file(&file_path)?;
+
     // Perform audio analysis
     let analysis_result = analyze_audio(
         file_path.to_str().unwrap(),
@@ -153,7 +371,29 @@ async fn analysis_file(file: &media_files::Model, lib_path: &Path) -> Normalized
     );
 
     // Normalize the analysis result
-    normalize_analysis_result(analysis_result)
+    Ok(normalize_analysis_result(analysis_result))
+}
+
+fn features_of_result(result: &NormalizedAnalysisResult) -> AnalysisFeatures {
+    let mut features = [None; 19];
+    let spectral = [
+        result.spectral_centroid,
+        result.spectral_flatness,
+        result.spectral_slope,
+        result.spectral_rolloff,
+        result.spectral_spread,
+        result.spectral_skewness,
+        result.spectral_kurtosis,
+    ];
+
+    for (feature, value) in features
+        .iter_mut()
+        .zip(spectral.iter().chain(result.chromagram.iter()))
+    {
+        *feature = Some(*value as f64);
+    }
+
+    features
 }
 
 /// Insert the normalized analysis result into the database.
@@ -191,12 +431,15 @@ where
         chroma9: ActiveValue::Set(Some(result.chromagram[9] as f64)),
         chroma10: ActiveValue::Set(Some(result.chromagram[10] as f64)),
         chroma11: ActiveValue::Set(Some(result.chromagram[11] as f64)),
+        mix_in: ActiveValue::Set(Some(result.transitions.mix_in)),
+        mix_out: ActiveValue::Set(Some(result.transitions.mix_out)),
         ..Default::default()
     };
 
     media_analysis::Entity::insert(new_analysis)
         .exec(db)
         .await?;
+    set_onset_markers(db, file_id, &result.transitions.markers).await?;
 
     Ok(())
 }
@@ -357,3 +600,32 @@ pub async fn get_centralized_analysis_result(
         ],
     }
 }
+
+/// Get the points to mix the given files in and out at, found while analysing.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, (f64, f64)>, DbErr>` - The mix-in and mix-out points in seconds of
+///   every file analysed with them.
+pub async fn get_mix_points_of_files(
+    db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, (f64, f64)>, DbErr> {
+    let items: Vec<(i32, Option<f64>, Option<f64>)> = media_analysis::Entity::find()
+        .select_only()
+        .column(media_analysis::Column::FileId)
+        .column(media_analysis::Column::MixIn)
+        .column(media_analysis::Column::MixOut)
+        .filter(media_analysis::Column::FileId.is_in(file_ids.to_vec()))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    Ok(items
+        .into_iter()
+        .filter_map(|(file_id, mix_in, mix_out)| Some((file_id, (mix_in?, mix_out?))))
+        .collect())
+}
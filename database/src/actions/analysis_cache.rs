@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue;
+
+use crate::connection::AnalysisCacheConnection;
+use crate::entities::analysis_cache;
+
+use super::analysis_exchange::AnalysisFeatures;
+
+fn features_of(x: &analysis_cache::Model) -> AnalysisFeatures {
+    [
+        x.spectral_centroid,
+        x.spectral_flatness,
+        x.spectral_slope,
+        x.spectral_rolloff,
+        x.spectral_spread,
+        x.spectral_skewness,
+        x.spectral_kurtosis,
+        x.chroma0,
+        x.chroma1,
+        x.chroma2,
+        x.chroma3,
+        x.chroma4,
+        x.chroma5,
+        x.chroma6,
+        x.chroma7,
+        x.chroma8,
+        x.chroma9,
+        x.chroma10,
+        x.chroma11,
+    ]
+}
+
+fn to_active_model(file_hash: &str, features: &AnalysisFeatures) -> analysis_cache::ActiveModel {
+    let [spectral_centroid, spectral_flatness, spectral_slope, spectral_rolloff, spectral_spread, spectral_skewness, spectral_kurtosis, chroma0, chroma1, chroma2, chroma3, chroma4, chroma5, chroma6, chroma7, chroma8, chroma9, chroma10, chroma11] =
+        *features;
+
+    analysis_cache::ActiveModel {
+        id: ActiveValue::NotSet,
+        file_hash: ActiveValue::Set(file_hash.to_string()),
+        spectral_centroid: ActiveValue::Set(spectral_centroid),
+        spectral_flatness: ActiveValue::Set(spectral_flatness),
+        spectral_slope: ActiveValue::Set(spectral_slope),
+        spectral_rolloff: ActiveValue::Set(spectral_rolloff),
+        spectral_spread: ActiveValue::Set(spectral_spread),
+        spectral_skewness: ActiveValue::Set(spectral_skewness),
+        spectral_kurtosis: ActiveValue::Set(spectral_kurtosis),
+        chroma0: ActiveValue::Set(chroma0),
+        chroma1: ActiveValue::Set(chroma1),
+        chroma2: ActiveValue::Set(chroma2),
+        chroma3: ActiveValue::Set(chroma3),
+        chroma4: ActiveValue::Set(chroma4),
+        chroma5: ActiveValue::Set(chroma5),
+        chroma6: ActiveValue::Set(chroma6),
+        chroma7: ActiveValue::Set(chroma7),
+        chroma8: ActiveValue::Set(chroma8),
+        chroma9: ActiveValue::Set(chroma9),
+        chroma10: ActiveValue::Set(chroma10),
+        chroma11: ActiveValue::Set(chroma11),
+    }
+}
+
+/// Look up analysis results of files analysed in any library.
+///
+/// # Arguments
+/// * `cache` - A reference to the analysis cache connection.
+/// * `file_hashes` - The content hashes of the files.
+///
+/// # Returns
+/// * `Result<HashMap<String, AnalysisFeatures>, DbErr>` - The results of the cached hashes.
+pub async fn get_cached_analysis(
+    cache: &AnalysisCacheConnection,
+    file_hashes: &[String],
+) -> Result<HashMap<String, AnalysisFeatures>, DbErr> {
+    let items = analysis_cache::Entity::find()
+        .filter(analysis_cache::Column::FileHash.is_in(file_hashes.to_vec()))
+        .all(cache)
+        .await?;
+
+    Ok(items
+        .iter()
+        .map(|x| (x.file_hash.clone(), features_of(x)))
+        .collect())
+}
+
+/// Store analysis results so other libraries can reuse them, hashes that
+/// are already cached keep their results.
+///
+/// # Arguments
+/// * `cache` - A reference to the analysis cache connection.
+/// * `entries` - The content hashes and analysis results of the files.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the results were stored.
+pub async fn cache_analysis(
+    cache: &AnalysisCacheConnection,
+    entries: &[(String, AnalysisFeatures)],
+) -> Result<(), DbErr> {
+    let items = entries
+        .iter()
+        .map(|(file_hash, features)| to_active_model(file_hash, features));
+
+    analysis_cache::Entity::insert_many(items)
+        .on_conflict(
+            OnConflict::column(analysis_cache::Column::FileHash)
+                .do_nothing()
+                .to_owned(),
+        )
+        .do_nothing()
+        .exec(cache)
+        .await?;
+
+    Ok(())
+}
+
+/// Drop cached results so the files are analysed again.
+///
+/// # Arguments
+/// * `cache` - A reference to the analysis cache connection.
+/// * `file_hashes` - The content hashes of the files.
+///
+/// # Returns
+/// * `Result<u64, DbErr>` - The number of removed results.
+pub async fn forget_cached_analysis(
+    cache: &AnalysisCacheConnection,
+    file_hashes: &[String],
+) -> Result<u64, DbErr> {
+    let result = analysis_cache::Entity::delete_many()
+        .filter(analysis_cache::Column::FileHash.is_in(file_hashes.to_vec()))
+        .exec(cache)
+        .await?;
+
+    Ok(result.rows_affected)
+}
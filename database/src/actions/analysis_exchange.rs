@@ -0,0 +1,353 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, QuerySelect, TransactionTrait};
+use tracing::{info, warn};
+
+use super::analysis::get_centralized_analysis_result;
+use crate::entities::{media_analysis, media_files};
+
+// The first line of every export, bumped when the columns change
+const HEADER: &str = "# rune analysis v1";
+const FEATURES: [&str; 19] = [
+    "spectral_centroid",
+    "spectral_flatness",
+    "spectral_slope",
+    "spectral_rolloff",
+    "spectral_spread",
+    "spectral_skewness",
+    "spectral_kurtosis",
+    "chroma0",
+    "chroma1",
+    "chroma2",
+    "chroma3",
+    "chroma4",
+    "chroma5",
+    "chroma6",
+    "chroma7",
+    "chroma8",
+    "chroma9",
+    "chroma10",
+    "chroma11",
+];
+
+/// The spectral features followed by the chromagram, in the column order.
+pub type AnalysisFeatures = [Option<f64>; 19];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AnalysisImportReport {
+    /// Files that received analysis results.
+    pub imported: usize,
+    /// Entries for files that are not in the library or already analysed.
+    pub skipped: usize,
+}
+
+pub(crate) fn features_of(x: &media_analysis::Model) -> AnalysisFeatures {
+    [
+        x.spectral_centroid,
+        x.spectral_flatness,
+        x.spectral_slope,
+        x.spectral_rolloff,
+        x.spectral_spread,
+        x.spectral_skewness,
+        x.spectral_kurtosis,
+        x.chroma0,
+        x.chroma1,
+        x.chroma2,
+        x.chroma3,
+        x.chroma4,
+        x.chroma5,
+        x.chroma6,
+        x.chroma7,
+        x.chroma8,
+        x.chroma9,
+        x.chroma10,
+        x.chroma11,
+    ]
+}
+
+pub(crate) fn to_active_model(
+    file_id: i32,
+    features: &AnalysisFeatures,
+) -> media_analysis::ActiveModel {
+    let [spectral_centroid, spectral_flatness, spectral_slope, spectral_rolloff, spectral_spread, spectral_skewness, spectral_kurtosis, chroma0, chroma1, chroma2, chroma3, chroma4, chroma5, chroma6, chroma7, chroma8, chroma9, chroma10, chroma11] =
+        *features;
+
+    media_analysis::ActiveModel {
+        id: ActiveValue::NotSet,
+        file_id: ActiveValue::Set(file_id),
+        spectral_centroid: ActiveValue::Set(spectral_centroid),
+        spectral_flatness: ActiveValue::Set(spectral_flatness),
+        spectral_slope: ActiveValue::Set(spectral_slope),
+        spectral_rolloff: ActiveValue::Set(spectral_rolloff),
+        spectral_spread: ActiveValue::Set(spectral_spread),
+        spectral_skewness: ActiveValue::Set(spectral_skewness),
+        spectral_kurtosis: ActiveValue::Set(spectral_kurtosis),
+        chroma0: ActiveValue::Set(chroma0),
+        chroma1: ActiveValue::Set(chroma1),
+        chroma2: ActiveValue::Set(chroma2),
+        chroma3: ActiveValue::Set(chroma3),
+        chroma4: ActiveValue::Set(chroma4),
+        chroma5: ActiveValue::Set(chroma5),
+        chroma6: ActiveValue::Set(chroma6),
+        chroma7: ActiveValue::Set(chroma7),
+        chroma8: ActiveValue::Set(chroma8),
+        chroma9: ActiveValue::Set(chroma9),
+        chroma10: ActiveValue::Set(chroma10),
+        chroma11: ActiveValue::Set(chroma11),
+        // Only files analysed here get mix points
+        mix_in: ActiveValue::NotSet,
+        mix_out: ActiveValue::NotSet,
+    }
+}
+
+// Quote a CSV cell if it has to be, doubling the quotes inside
+fn csv_cell(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Parse one exported line into the file hash and its features
+fn parse_line(line: &str) -> Option<(String, AnalysisFeatures)> {
+    let mut cells = line.split('\t');
+    let file_hash = cells.next()?.to_string();
+
+    let mut features = [None; 19];
+    for feature in features.iter_mut() {
+        let cell = cells.next()?;
+        *feature = if cell.is_empty() {
+            None
+        } else {
+            Some(cell.parse::<f64>().ok()?)
+        };
+    }
+
+    if cells.next().is_some() {
+        return None;
+    }
+
+    Some((file_hash, features))
+}
+
+/// Write the analysis results of the library to a file, keyed by file hash
+/// so they can be imported into a library on another machine.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `path` - The file to write, replaced if it exists.
+///
+/// # Returns
+/// * `Result<usize, Box<dyn std::error::Error>>` - The number of exported files.
+pub async fn export_analysis(
+    db: &DatabaseConnection,
+    path: &Path,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let hashes: HashMap<i32, String> = media_files::Entity::find()
+        .select_only()
+        .column(media_files::Column::Id)
+        .column(media_files::Column::FileHash)
+        .into_tuple::<(i32, String)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", HEADER)?;
+    writeln!(writer, "file_hash\t{}", FEATURES.join("\t"))?;
+
+    // Copies of a file share their hash, export it once
+    let mut exported: HashSet<&str> = HashSet::new();
+    for analysis in media_analysis::Entity::find().all(db).await? {
+        let Some(file_hash) = hashes.get(&analysis.file_id) else {
+            continue;
+        };
+        if !exported.insert(file_hash) {
+            continue;
+        }
+
+        let cells: Vec<String> = features_of(&analysis)
+            .iter()
+            .map(|x| x.map(|x| x.to_string()).unwrap_or_default())
+            .collect();
+        writeln!(writer, "{}\t{}", file_hash, cells.join("\t"))?;
+    }
+    writer.flush()?;
+
+    info!("Exported the analysis of {} files", exported.len());
+    Ok(exported.len())
+}
+
+/// Read analysis results written by [`export_analysis`] and store them for
+/// every file of the library with the same hash.
+///
+/// Files that are already analysed keep their results.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `path` - The exported file.
+///
+/// # Returns
+/// * `Result<AnalysisImportReport, Box<dyn std::error::Error>>` - What was imported.
+pub async fn import_analysis(
+    db: &DatabaseConnection,
+    path: &Path,
+) -> Result<AnalysisImportReport, Box<dyn std::error::Error>> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+    if lines.next().transpose()?.as_deref() != Some(HEADER) {
+        return Err("Not an analysis export of a supported version".into());
+    }
+    // Column names
+    lines.next().transpose()?;
+
+    let mut files_by_hash: HashMap<String, Vec<i32>> = HashMap::new();
+    for (id, file_hash) in media_files::Entity::find()
+        .select_only()
+        .column(media_files::Column::Id)
+        .column(media_files::Column::FileHash)
+        .into_tuple::<(i32, String)>()
+        .all(db)
+        .await?
+    {
+        files_by_hash.entry(file_hash).or_default().push(id);
+    }
+
+    let mut analysed: HashSet<i32> = media_analysis::Entity::find()
+        .select_only()
+        .column(media_analysis::Column::FileId)
+        .into_tuple::<i32>()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut report = AnalysisImportReport::default();
+    let txn = db.begin().await?;
+
+    for line in lines {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+
+        let Some((file_hash, features)) = parse_line(&line) else {
+            warn!("Skipping a malformed analysis entry");
+            report.skipped += 1;
+            continue;
+        };
+
+        let file_ids: Vec<i32> = files_by_hash
+            .get(&file_hash)
+            .map(|x| {
+                x.iter()
+                    .copied()
+                    .filter(|x| !analysed.contains(x))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if file_ids.is_empty() {
+            report.skipped += 1;
+            continue;
+        }
+
+        for file_id in file_ids {
+            analysed.insert(file_id);
+            media_analysis::Entity::insert(to_active_model(file_id, &features))
+                .exec(&txn)
+                .await?;
+            report.imported += 1;
+        }
+    }
+
+    txn.commit().await?;
+
+    info!(
+        "Imported the analysis of {} files, skipped {} entries",
+        report.imported, report.skipped
+    );
+    Ok(report)
+}
+
+/// Write the feature vector of every analysed track to a CSV file for
+/// clustering or plotting them with other tools.
+///
+/// Each track gets a `track` row, a last `mean` row holds the aggregate
+/// [`get_centralized_analysis_result`] computes for them. Missing features
+/// are left empty.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `path` - The file to write, replaced if it exists.
+/// * `file_ids` - The files to export, `None` for the whole library.
+///
+/// # Returns
+/// * `Result<usize, Box<dyn std::error::Error>>` - The number of exported tracks.
+pub async fn export_analysis_vectors(
+    db: &DatabaseConnection,
+    path: &Path,
+    file_ids: Option<&[i32]>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut query = media_analysis::Entity::find().find_also_related(media_files::Entity);
+    if let Some(file_ids) = file_ids {
+        query = query.filter(media_analysis::Column::FileId.is_in(file_ids.to_vec()));
+    }
+    let mut results = query.all(db).await?;
+    results.sort_by_key(|(x, _)| x.file_id);
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "row,file_id,directory,file_name,{}",
+        FEATURES.join(",")
+    )?;
+
+    let mut exported = Vec::with_capacity(results.len());
+    for (analysis, file) in &results {
+        // Deleted files wait for their grace period to end
+        let Some(file) = file.as_ref().filter(|x| x.deleted_at.is_none()) else {
+            continue;
+        };
+        exported.push(analysis.file_id);
+
+        let cells: Vec<String> = features_of(analysis)
+            .iter()
+            .map(|x| x.map(|x| x.to_string()).unwrap_or_default())
+            .collect();
+        writeln!(
+            writer,
+            "track,{},{},{},{}",
+            file.id,
+            csv_cell(&file.directory),
+            csv_cell(&file.file_name),
+            cells.join(",")
+        )?;
+    }
+
+    if !exported.is_empty() {
+        let mean = get_centralized_analysis_result(db, exported.clone()).await;
+        let cells: Vec<String> = [
+            mean.spectral_centroid,
+            mean.spectral_flatness,
+            mean.spectral_slope,
+            mean.spectral_rolloff,
+            mean.spectral_spread,
+            mean.spectral_skewness,
+            mean.spectral_kurtosis,
+        ]
+        .iter()
+        .chain(mean.chromagram.iter())
+        .map(|x| x.to_string())
+        .collect();
+        writeln!(writer, "mean,,,,{}", cells.join(","))?;
+    }
+    writer.flush()?;
+
+    info!("Exported the feature vectors of {} tracks", exported.len());
+    Ok(exported.len())
+}
@@ -5,6 +5,7 @@ use sea_orm::prelude::*;
 use crate::entities::{artists, media_file_artists};
 use crate::{get_all_ids, get_by_id, get_by_ids, get_groups};
 
+use super::collation::sort_name;
 use super::utils::CountByFirstLetter;
 
 impl CountByFirstLetter for artists::Entity {
@@ -17,7 +18,13 @@ impl CountByFirstLetter for artists::Entity {
     }
 }
 
-get_groups!(get_artists_groups, artists, media_file_artists, ArtistId);
+get_groups!(
+    get_artists_groups,
+    artists,
+    media_file_artists,
+    ArtistId,
+    |x: &artists::Model| sort_name(&x.name, x.sort_name.as_deref()).to_string()
+);
 get_all_ids!(get_media_file_ids_of_artist, media_file_artists, ArtistId);
-get_by_ids!(get_artists_by_ids, artists);
+get_by_ids!(get_artists_by_ids, artists, media_file_artists, ArtistId);
 get_by_id!(get_artist_by_id, artists);
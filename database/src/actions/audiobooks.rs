@@ -0,0 +1,309 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue;
+
+use crate::entities::{directory_content_types, media_files, media_metadata};
+use crate::entities::{playback_positions, prelude};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    Music,
+    Audiobook,
+    // Single-file DJ mixes and live recordings, navigated by markers
+    // instead of being split into tracks
+    LongMix,
+}
+
+impl ContentType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentType::Music => "music",
+            ContentType::Audiobook => "audiobook",
+            ContentType::LongMix => "long_mix",
+        }
+    }
+}
+
+impl From<&str> for ContentType {
+    fn from(value: &str) -> Self {
+        match value {
+            "audiobook" => ContentType::Audiobook,
+            "long_mix" => ContentType::LongMix,
+            _ => ContentType::Music,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Audiobook {
+    pub title: String,
+    pub file_ids: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AudiobookSeries {
+    // Empty for books that don't belong to a series
+    pub name: String,
+    pub books: Vec<Audiobook>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AudiobookAuthor {
+    pub name: String,
+    pub series: Vec<AudiobookSeries>,
+}
+
+/// Set the content type of a directory and everything below it.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `directory` - The directory relative to the library root.
+/// * `content_type` - The content type of the files in the directory.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the flag was stored.
+pub async fn set_directory_content_type(
+    db: &DatabaseConnection,
+    directory: &str,
+    content_type: ContentType,
+) -> Result<(), DbErr> {
+    let item = directory_content_types::ActiveModel {
+        directory: ActiveValue::Set(directory.to_string()),
+        content_type: ActiveValue::Set(content_type.as_str().to_string()),
+        ..Default::default()
+    };
+
+    prelude::DirectoryContentTypes::insert(item)
+        .on_conflict(
+            OnConflict::column(directory_content_types::Column::Directory)
+                .update_column(directory_content_types::Column::ContentType)
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Get all directories with an explicit content type.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Vec<(String, ContentType)>, DbErr>` - The flagged directories.
+pub async fn get_directory_content_types(
+    db: &DatabaseConnection,
+) -> Result<Vec<(String, ContentType)>, DbErr> {
+    let items = prelude::DirectoryContentTypes::find().all(db).await?;
+
+    Ok(items
+        .into_iter()
+        .map(|x| {
+            let content_type = ContentType::from(x.content_type.as_str());
+            (x.directory, content_type)
+        })
+        .collect())
+}
+
+/// Resolve the content type of a directory, the closest flagged ancestor wins.
+pub(crate) fn resolve_content_type(
+    directory: &str,
+    flags: &[(String, ContentType)],
+) -> ContentType {
+    let directory = Path::new(directory);
+
+    flags
+        .iter()
+        .filter(|(flagged, _)| directory.starts_with(flagged))
+        .max_by_key(|(flagged, _)| Path::new(flagged).components().count())
+        .map(|(_, content_type)| *content_type)
+        .unwrap_or(ContentType::Music)
+}
+
+/// Find out which of the given files belong to audiobooks.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files to check.
+///
+/// # Returns
+/// * `Result<HashSet<i32>, DbErr>` - The IDs of the audiobook files.
+pub async fn get_audiobook_file_ids(
+    db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashSet<i32>, DbErr> {
+    let flags = get_directory_content_types(db).await?;
+    if !flags.iter().any(|(_, x)| *x == ContentType::Audiobook) {
+        return Ok(HashSet::new());
+    }
+
+    let files = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_in(file_ids.to_vec()))
+        .all(db)
+        .await?;
+
+    Ok(files
+        .into_iter()
+        .filter(|file| resolve_content_type(&file.directory, &flags) == ContentType::Audiobook)
+        .map(|file| file.id)
+        .collect())
+}
+
+/// Group all audiobook files by author, series and book.
+///
+/// The author is read from the album artist (falling back to the artist),
+/// the series from the content group and the book from the album.
+/// Chapters of a book are ordered by track number and file name.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Vec<AudiobookAuthor>, DbErr>` - The audiobook library, sorted by author.
+pub async fn get_audiobooks(db: &DatabaseConnection) -> Result<Vec<AudiobookAuthor>, DbErr> {
+    let flags = get_directory_content_types(db).await?;
+    if !flags.iter().any(|(_, x)| *x == ContentType::Audiobook) {
+        return Ok(Vec::new());
+    }
+
+    let files: Vec<media_files::Model> = media_files::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .filter(|file| resolve_content_type(&file.directory, &flags) == ContentType::Audiobook)
+        .collect();
+
+    let file_ids: Vec<i32> = files.iter().map(|x| x.id).collect();
+    let metadata_entries = media_metadata::Entity::find()
+        .filter(media_metadata::Column::FileId.is_in(file_ids).and(
+            media_metadata::Column::MetaKey.is_in([
+                "album_artist",
+                "artist",
+                "content_group",
+                "album",
+                "track_number",
+            ]),
+        ))
+        .all(db)
+        .await?;
+
+    let mut metadata_map: HashMap<i32, HashMap<String, String>> = HashMap::new();
+    for entry in metadata_entries {
+        metadata_map
+            .entry(entry.file_id)
+            .or_default()
+            .insert(entry.meta_key, entry.meta_value);
+    }
+
+    // author -> series -> book -> [(track number, file name, file id)]
+    type Chapters = Vec<(i32, String, i32)>;
+    let mut tree: BTreeMap<String, BTreeMap<String, BTreeMap<String, Chapters>>> = BTreeMap::new();
+
+    for file in files {
+        let empty = HashMap::new();
+        let metadata = metadata_map.get(&file.id).unwrap_or(&empty);
+
+        let author = metadata
+            .get("album_artist")
+            .or_else(|| metadata.get("artist"))
+            .cloned()
+            .unwrap_or_default();
+        let series = metadata.get("content_group").cloned().unwrap_or_default();
+        let book = metadata
+            .get("album")
+            .cloned()
+            .unwrap_or_else(|| file.directory.clone());
+        let track_number = metadata
+            .get("track_number")
+            .and_then(|x| x.split('/').next())
+            .and_then(|x| x.trim().parse::<i32>().ok())
+            .unwrap_or(i32::MAX);
+
+        tree.entry(author)
+            .or_default()
+            .entry(series)
+            .or_default()
+            .entry(book)
+            .or_default()
+            .push((track_number, file.file_name, file.id));
+    }
+
+    Ok(tree
+        .into_iter()
+        .map(|(name, series)| AudiobookAuthor {
+            name,
+            series: series
+                .into_iter()
+                .map(|(name, books)| AudiobookSeries {
+                    name,
+                    books: books
+                        .into_iter()
+                        .map(|(title, mut chapters)| {
+                            chapters.sort();
+                            Audiobook {
+                                title,
+                                file_ids: chapters.into_iter().map(|x| x.2).collect(),
+                            }
+                        })
+                        .collect(),
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+/// Get the remembered playback position of a file.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_id` - The ID of the file.
+///
+/// # Returns
+/// * `Result<Option<f64>, DbErr>` - The position in seconds, if one was saved.
+pub async fn get_playback_position(
+    db: &DatabaseConnection,
+    file_id: i32,
+) -> Result<Option<f64>, DbErr> {
+    let item = prelude::PlaybackPositions::find()
+        .filter(playback_positions::Column::MediaFileId.eq(file_id))
+        .one(db)
+        .await?;
+
+    Ok(item.map(|x| x.position))
+}
+
+/// Remember the playback position of a file.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_id` - The ID of the file.
+/// * `position` - The position in seconds.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the position was stored.
+pub async fn set_playback_position(
+    db: &DatabaseConnection,
+    file_id: i32,
+    position: f64,
+) -> Result<(), DbErr> {
+    let item = playback_positions::ActiveModel {
+        media_file_id: ActiveValue::Set(file_id),
+        position: ActiveValue::Set(position),
+        ..Default::default()
+    };
+
+    prelude::PlaybackPositions::insert(item)
+        .on_conflict(
+            OnConflict::column(playback_positions::Column::MediaFileId)
+                .update_column(playback_positions::Column::Position)
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,315 @@
+use std::collections::{HashMap, HashSet};
+
+use sea_orm::prelude::*;
+use sea_orm::QuerySelect;
+
+use crate::entities::{media_files, media_loudness, media_metadata};
+
+pub const BPM_META_KEY: &str = "bpm";
+pub const INITIAL_KEY_META_KEY: &str = "initial_key";
+
+// Pitch classes of the note letters, C is 0
+const NOTES: [(char, u8); 7] = [
+    ('C', 0),
+    ('D', 2),
+    ('E', 4),
+    ('F', 5),
+    ('G', 7),
+    ('A', 9),
+    ('B', 11),
+];
+
+/// The key of a track, as DJ software tags it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MusicalKey {
+    /// Pitch class of the tonic, C is 0 and B is 11.
+    pub tonic: u8,
+    pub minor: bool,
+}
+
+impl MusicalKey {
+    /// Parse a key written like "Am", "F# minor", "Dbmaj" or as a Camelot
+    /// code like "8A".
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        Self::parse_camelot(value).or_else(|| Self::parse_name(value))
+    }
+
+    fn parse_camelot(value: &str) -> Option<Self> {
+        let letter = value.chars().last()?;
+        let number: u8 = value[..value.len() - letter.len_utf8()].parse().ok()?;
+        let letter = letter.to_ascii_uppercase();
+        if !(1..=12).contains(&number) || (letter != 'A' && letter != 'B') {
+            return None;
+        }
+
+        // Seven is its own inverse modulo 12, it undoes the circle of fifths
+        let major = (7 * (number as u32 + 4) % 12) as u8;
+        Some(if letter == 'B' {
+            MusicalKey {
+                tonic: major,
+                minor: false,
+            }
+        } else {
+            MusicalKey {
+                tonic: (major + 9) % 12,
+                minor: true,
+            }
+        })
+    }
+
+    fn parse_name(value: &str) -> Option<Self> {
+        let mut chars = value.chars();
+        let letter = chars.next()?.to_ascii_uppercase();
+        let (_, mut tonic) = NOTES.iter().find(|(x, _)| *x == letter)?;
+
+        let mut rest = chars.as_str();
+        if let Some(x) = rest.strip_prefix(['#', '♯']) {
+            tonic = (tonic + 1) % 12;
+            rest = x;
+        } else if let Some(x) = rest.strip_prefix(['b', '♭']) {
+            tonic = (tonic + 11) % 12;
+            rest = x;
+        }
+
+        let minor = match rest.trim().to_lowercase().as_str() {
+            "" | "maj" | "major" => false,
+            "m" | "min" | "minor" => true,
+            _ => return None,
+        };
+
+        Some(MusicalKey { tonic, minor })
+    }
+
+    /// The hour of the key on the Camelot wheel, from 1 to 12. Neighbours
+    /// are a fifth apart.
+    pub fn camelot_number(&self) -> u8 {
+        // Minor keys share the hour of their relative major
+        let major = if self.minor {
+            (self.tonic + 3) % 12
+        } else {
+            self.tonic
+        };
+        ((major as u32 * 7 + 7) % 12) as u8 + 1
+    }
+
+    /// How far apart two keys are for mixing, `None` if they clash.
+    ///
+    /// The same key is 0, the relative key and the neighbours on the wheel
+    /// are 1.
+    pub fn distance(&self, other: &MusicalKey) -> Option<u8> {
+        let (a, b) = (self.camelot_number(), other.camelot_number());
+        let hours = (a + 12 - b) % 12;
+        match (hours, self.minor == other.minor) {
+            (0, true) => Some(0),
+            (0, false) | (1 | 11, true) => Some(1),
+            _ => None,
+        }
+    }
+}
+
+/// How the energy of an auto-DJ queue develops from track to track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnergyProgression {
+    Steady,
+    Rising,
+    Falling,
+}
+
+impl From<&str> for EnergyProgression {
+    fn from(value: &str) -> Self {
+        match value {
+            "rising" => EnergyProgression::Rising,
+            "falling" => EnergyProgression::Falling,
+            _ => EnergyProgression::Steady,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoDjOptions {
+    /// Tracks in the queue, the seed included.
+    pub length: usize,
+    /// How far the tempo may drift between two tracks, as a fraction.
+    pub tempo_tolerance: f64,
+    pub energy: EnergyProgression,
+    /// The largest change of loudness between two tracks, in LU.
+    pub max_energy_step: f64,
+}
+
+impl Default for AutoDjOptions {
+    fn default() -> Self {
+        AutoDjOptions {
+            length: 30,
+            tempo_tolerance: 0.06,
+            energy: EnergyProgression::Steady,
+            max_energy_step: 3.0,
+        }
+    }
+}
+
+/// What the auto-DJ knows of a track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DjTrack {
+    pub file_id: i32,
+    pub bpm: f64,
+    pub key: MusicalKey,
+    /// Integrated loudness in LUFS, `None` before the loudness scan.
+    pub energy: Option<f64>,
+}
+
+/// How far apart two tempos are as a fraction of the first one, tracks at
+/// half or double the tempo mix as well.
+pub fn tempo_difference(from: f64, to: f64) -> f64 {
+    [to, to * 2.0, to / 2.0]
+        .iter()
+        .map(|x| (x - from).abs() / from)
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn energy_allowed(from: Option<f64>, to: Option<f64>, options: &AutoDjOptions) -> bool {
+    let (Some(from), Some(to)) = (from, to) else {
+        return true;
+    };
+    let step = to - from;
+    let max = options.max_energy_step;
+
+    match options.energy {
+        EnergyProgression::Steady => step.abs() <= max,
+        EnergyProgression::Rising => (0.0..=max).contains(&step),
+        EnergyProgression::Falling => (-max..=0.0).contains(&step),
+    }
+}
+
+/// Chain tracks that mix into each other, starting with the seed.
+///
+/// Every next track is in a compatible key, within the tempo tolerance and
+/// follows the energy progression. The closest key wins, then the closest
+/// tempo. The chain ends early when no track fits.
+///
+/// # Returns
+/// * `Vec<i32>` - The file IDs in the order to play them, the seed first.
+pub fn chain_tracks(seed: &DjTrack, candidates: &[DjTrack], options: &AutoDjOptions) -> Vec<i32> {
+    let mut chain = vec![seed.file_id];
+    let mut used: HashSet<i32> = HashSet::from([seed.file_id]);
+    let mut current = seed;
+
+    while chain.len() < options.length {
+        let next = candidates
+            .iter()
+            .filter(|x| !used.contains(&x.file_id))
+            .filter(|x| tempo_difference(current.bpm, x.bpm) <= options.tempo_tolerance)
+            .filter(|x| energy_allowed(current.energy, x.energy, options))
+            .filter_map(|x| Some((current.key.distance(&x.key)?, x)))
+            .min_by(|(a_distance, a), (b_distance, b)| {
+                a_distance
+                    .cmp(b_distance)
+                    .then_with(|| {
+                        tempo_difference(current.bpm, a.bpm)
+                            .total_cmp(&tempo_difference(current.bpm, b.bpm))
+                    })
+                    .then_with(|| a.file_id.cmp(&b.file_id))
+            });
+
+        let Some((_, next)) = next else {
+            break;
+        };
+        chain.push(next.file_id);
+        used.insert(next.file_id);
+        current = next;
+    }
+
+    chain
+}
+
+fn parse_bpm(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|x| x.is_finite() && *x > 0.0)
+}
+
+/// The tempo tags of some files.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `file_ids` - The files to look up.
+///
+/// # Returns
+/// * `Result<HashMap<i32, f64>, DbErr>` - The BPM of the files tagged with one.
+pub async fn get_tempos(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, f64>, DbErr> {
+    let tags: Vec<(i32, String)> = media_metadata::Entity::find()
+        .select_only()
+        .column(media_metadata::Column::FileId)
+        .column(media_metadata::Column::MetaValue)
+        .filter(media_metadata::Column::MetaKey.eq(BPM_META_KEY))
+        .filter(media_metadata::Column::FileId.is_in(file_ids.to_vec()))
+        .into_tuple()
+        .all(main_db)
+        .await?;
+
+    Ok(tags
+        .into_iter()
+        .filter_map(|(file_id, value)| Some((file_id, parse_bpm(&value)?)))
+        .collect())
+}
+
+/// The tracks of the library tagged with a tempo and a key.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Vec<DjTrack>, DbErr>` - The tracks that are not deleted, by file ID.
+pub async fn get_dj_tracks(main_db: &DatabaseConnection) -> Result<Vec<DjTrack>, DbErr> {
+    let tags: Vec<(i32, String, String)> = media_metadata::Entity::find()
+        .select_only()
+        .column(media_metadata::Column::FileId)
+        .column(media_metadata::Column::MetaKey)
+        .column(media_metadata::Column::MetaValue)
+        .inner_join(media_files::Entity)
+        .filter(media_files::Column::DeletedAt.is_null())
+        .filter(media_metadata::Column::MetaKey.is_in([BPM_META_KEY, INITIAL_KEY_META_KEY]))
+        .into_tuple()
+        .all(main_db)
+        .await?;
+
+    let mut bpms: HashMap<i32, f64> = HashMap::new();
+    let mut keys: HashMap<i32, MusicalKey> = HashMap::new();
+    for (file_id, meta_key, meta_value) in tags {
+        if meta_key == BPM_META_KEY {
+            if let Some(bpm) = parse_bpm(&meta_value) {
+                bpms.insert(file_id, bpm);
+            }
+        } else if let Some(key) = MusicalKey::parse(&meta_value) {
+            keys.insert(file_id, key);
+        }
+    }
+
+    let energies: HashMap<i32, f64> = media_loudness::Entity::find()
+        .filter(media_loudness::Column::FileId.is_in(bpms.keys().copied().collect::<Vec<_>>()))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .filter_map(|x| Some((x.file_id, x.integrated_loudness?)))
+        .collect();
+
+    let mut tracks: Vec<DjTrack> = bpms
+        .into_iter()
+        .filter_map(|(file_id, bpm)| {
+            Some(DjTrack {
+                file_id,
+                bpm,
+                key: *keys.get(&file_id)?,
+                energy: energies.get(&file_id).copied(),
+            })
+        })
+        .collect();
+    tracks.sort_by_key(|x| x.file_id);
+
+    Ok(tracks)
+}
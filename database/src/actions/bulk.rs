@@ -0,0 +1,234 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use chrono::Utc;
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, QueryOrder, TransactionTrait};
+use tracing::{info, warn};
+
+use crate::actions::playlists::DuplicatePolicy;
+use crate::actions::search::{remove_term, CollectionType};
+use crate::connection::SearchDbConnection;
+use crate::entities::{media_analysis, media_file_playlists, media_files, playlists};
+
+/// Delete files for good instead of moving them to the trash.
+pub const PERMANENT_DELETE_KEY: &str = "library.permanent_delete";
+
+/// What happened to every file of an operation on many files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BulkReport {
+    pub succeeded: Vec<i32>,
+    /// Files left alone because there was nothing to do, like those already
+    /// in the playlist.
+    pub skipped: Vec<i32>,
+    pub failed: Vec<i32>,
+    /// Why some of the failed files failed.
+    pub errors: HashMap<i32, String>,
+}
+
+/// How `delete_files` gets rid of files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Move files to the trash of the operating system.
+    Trash,
+    Permanent,
+}
+
+impl DeleteMode {
+    /// Read the mode from the `PERMANENT_DELETE_KEY` setting, files go to the
+    /// trash unless it is "true".
+    pub fn from_setting(value: Option<&str>) -> Self {
+        match value {
+            Some("true") => DeleteMode::Permanent,
+            _ => DeleteMode::Trash,
+        }
+    }
+}
+
+pub(crate) fn remove_file(path: &Path, mode: DeleteMode) -> Result<(), String> {
+    match mode {
+        DeleteMode::Permanent => match fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        },
+        // The trash can't take a file that is already gone
+        DeleteMode::Trash if !path.exists() => Ok(()),
+        DeleteMode::Trash => trash::delete(path).map_err(|e| e.to_string()),
+    }
+}
+
+/// Append files to the end of a playlist in the given order.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `playlist_id` - The ID of the playlist.
+/// * `file_ids` - The IDs of the files.
+/// * `policy` - What to do with files already in the playlist.
+///
+/// # Returns
+/// * `Result<BulkReport, DbErr>` - The added files and the duplicates left out,
+///   nothing is added if one fails.
+pub async fn add_files_to_playlist(
+    db: &DatabaseConnection,
+    playlist_id: i32,
+    file_ids: &[i32],
+    policy: DuplicatePolicy,
+) -> Result<BulkReport, DbErr> {
+    let txn = db.begin().await?;
+
+    let playlist = playlists::Entity::find_by_id(playlist_id)
+        .one(&txn)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound(format!("Playlist not found: {}", playlist_id)))?;
+
+    let items = media_file_playlists::Entity::find()
+        .filter(media_file_playlists::Column::PlaylistId.eq(playlist_id))
+        .order_by_asc(media_file_playlists::Column::Position)
+        .all(&txn)
+        .await?;
+    let next_position = items.last().map_or(0, |x| x.position + 1);
+    let present: HashSet<i32> = items.into_iter().map(|x| x.media_file_id).collect();
+
+    let (added, skipped) = policy.apply(&present, file_ids);
+    let mut report = BulkReport {
+        skipped,
+        ..Default::default()
+    };
+    let mut new_items = Vec::new();
+    for (file_id, position) in added.into_iter().zip(next_position..) {
+        new_items.push(media_file_playlists::ActiveModel {
+            playlist_id: ActiveValue::Set(playlist_id),
+            media_file_id: ActiveValue::Set(file_id),
+            position: ActiveValue::Set(position),
+            ..Default::default()
+        });
+        report.succeeded.push(file_id);
+    }
+
+    if !new_items.is_empty() {
+        media_file_playlists::Entity::insert_many(new_items)
+            .exec(&txn)
+            .await?;
+
+        let mut playlist: playlists::ActiveModel = playlist.into();
+        playlist.updated_at = ActiveValue::Set(Utc::now().to_rfc3339());
+        playlist.update(&txn).await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(report)
+}
+
+/// Delete files from the disk and remove them from the library.
+///
+/// Files that are already gone from the disk are removed from the library
+/// as well.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `search_db` - The search index the tracks are removed from.
+/// * `lib_path` - The root path of the library.
+/// * `file_ids` - The IDs of the files.
+/// * `mode` - Whether files are moved to the trash or deleted for good.
+/// * `progress_callback` - Called with the number of handled files and the total.
+///
+/// # Returns
+/// * `Result<BulkReport, DbErr>` - The removed files, and the ones that couldn't be
+///   deleted with the reason why.
+pub async fn delete_files<F>(
+    main_db: &DatabaseConnection,
+    search_db: &mut SearchDbConnection,
+    lib_path: &Path,
+    file_ids: &[i32],
+    mode: DeleteMode,
+    progress_callback: F,
+) -> Result<BulkReport, DbErr>
+where
+    F: Fn(usize, usize),
+{
+    let files = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_in(file_ids.to_vec()))
+        .all(main_db)
+        .await?;
+
+    let mut report = BulkReport::default();
+    let found: HashSet<i32> = files.iter().map(|x| x.id).collect();
+    for file_id in file_ids.iter().filter(|x| !found.contains(x)) {
+        report.failed.push(*file_id);
+        report
+            .errors
+            .insert(*file_id, "Not in the library".to_string());
+    }
+
+    let total = files.len();
+    for (index, file) in files.iter().enumerate() {
+        let path = lib_path.join(&file.directory).join(&file.file_name);
+        match remove_file(&path, mode) {
+            Ok(_) => {
+                info!("Deleted {:?} ({:?})", path, mode);
+                report.succeeded.push(file.id);
+            }
+            Err(e) => {
+                warn!("Unable to delete {:?}: {}", path, e);
+                report.failed.push(file.id);
+                report.errors.insert(file.id, e);
+            }
+        }
+        progress_callback(index + 1, total);
+    }
+
+    if report.succeeded.is_empty() {
+        return Ok(report);
+    }
+
+    let txn = main_db.begin().await?;
+    media_files::Entity::delete_many()
+        .filter(media_files::Column::Id.is_in(report.succeeded.clone()))
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+
+    for file_id in &report.succeeded {
+        remove_term(search_db, CollectionType::Track, *file_id);
+    }
+    search_db.commit().unwrap();
+
+    Ok(report)
+}
+
+/// Remove the analysis results of files so the next analysis run reads
+/// them again.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<Vec<String>, DbErr>` - The content hashes of the files, to drop
+///   them from the analysis cache as well.
+pub async fn reset_analysis(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<Vec<String>, DbErr> {
+    let txn = main_db.begin().await?;
+
+    let file_hashes = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_in(file_ids.to_vec()))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|x| x.file_hash)
+        .collect();
+
+    media_analysis::Entity::delete_many()
+        .filter(media_analysis::Column::FileId.is_in(file_ids.to_vec()))
+        .exec(&txn)
+        .await?;
+
+    txn.commit().await?;
+
+    Ok(file_hashes)
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, QueryOrder};
+
+use metadata::checksum::EmbeddedChecksum;
+
+use crate::actions::verification::VERIFIED_CHECKSUM_MISMATCH;
+use crate::entities::{media_checksums, media_files, media_verifications};
+
+/// Why a file looks degraded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Degradation {
+    /// The checksum the format embeds is different from the one stored by an
+    /// earlier scan, the audio was rewritten or the header got damaged.
+    ChecksumChanged {
+        kind: String,
+        previous: String,
+        current: String,
+        changed_at: String,
+    },
+    /// The decoded audio doesn't match the embedded checksum, found by the
+    /// last verification.
+    ChecksumMismatch,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DegradedFile {
+    pub file_id: i32,
+    pub directory: String,
+    pub file_name: String,
+    pub problems: Vec<Degradation>,
+}
+
+/// Store the checksum a file embeds of its audio, keeping the previous
+/// value when a rescan reads a different one.
+///
+/// Unlike the file hash, the checksum survives tag edits, so a change means
+/// the audio itself changed.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_id` - The ID of the file.
+/// * `checksum` - The checksum read from the file, `None` leaves the stored
+///   one untouched.
+///
+/// # Returns
+/// * `Result<bool, DbErr>` - Whether the checksum differs from the stored one.
+pub async fn record_embedded_checksum<C>(
+    db: &C,
+    file_id: i32,
+    checksum: Option<EmbeddedChecksum>,
+) -> Result<bool, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let Some(checksum) = checksum else {
+        return Ok(false);
+    };
+    let value = checksum.to_hex();
+
+    let stored = media_checksums::Entity::find()
+        .filter(media_checksums::Column::FileId.eq(file_id))
+        .one(db)
+        .await?;
+
+    match stored {
+        None => {
+            media_checksums::ActiveModel {
+                file_id: ActiveValue::Set(file_id),
+                kind: ActiveValue::Set(checksum.kind().to_string()),
+                value: ActiveValue::Set(value),
+                previous_value: ActiveValue::Set(None),
+                changed_at: ActiveValue::Set(None),
+                ..Default::default()
+            }
+            .insert(db)
+            .await?;
+            Ok(false)
+        }
+        Some(stored) if stored.kind == checksum.kind() && stored.value == value => Ok(false),
+        Some(stored) => {
+            let previous = stored.value.clone();
+            let mut active_model: media_checksums::ActiveModel = stored.into();
+            active_model.kind = ActiveValue::Set(checksum.kind().to_string());
+            active_model.value = ActiveValue::Set(value);
+            active_model.previous_value = ActiveValue::Set(Some(previous));
+            active_model.changed_at = ActiveValue::Set(Some(Utc::now().to_rfc3339()));
+            active_model.update(db).await?;
+            Ok(true)
+        }
+    }
+}
+
+/// List the files whose audio may have silently degraded: the embedded
+/// checksum changed between scans, or the audio no longer matches it.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Vec<DegradedFile>, DbErr>` - The files by ID, missing files
+///   are left out.
+pub async fn get_degradation_report(db: &DatabaseConnection) -> Result<Vec<DegradedFile>, DbErr> {
+    let mut report: BTreeMap<i32, DegradedFile> = BTreeMap::new();
+
+    let changed = media_checksums::Entity::find()
+        .find_also_related(media_files::Entity)
+        .filter(media_checksums::Column::PreviousValue.is_not_null())
+        .filter(media_files::Column::DeletedAt.is_null())
+        .order_by_asc(media_checksums::Column::FileId)
+        .all(db)
+        .await?;
+
+    for (checksum, file) in changed {
+        let (Some(file), Some(previous)) = (file, checksum.previous_value) else {
+            continue;
+        };
+        report
+            .entry(file.id)
+            .or_insert_with(|| DegradedFile {
+                file_id: file.id,
+                directory: file.directory,
+                file_name: file.file_name,
+                problems: Vec::new(),
+            })
+            .problems
+            .push(Degradation::ChecksumChanged {
+                kind: checksum.kind,
+                previous,
+                current: checksum.value,
+                changed_at: checksum.changed_at.unwrap_or_default(),
+            });
+    }
+
+    let mismatched = media_verifications::Entity::find()
+        .find_also_related(media_files::Entity)
+        .filter(media_verifications::Column::Status.eq(VERIFIED_CHECKSUM_MISMATCH))
+        .filter(media_files::Column::DeletedAt.is_null())
+        .order_by_asc(media_verifications::Column::FileId)
+        .all(db)
+        .await?;
+
+    for (_, file) in mismatched {
+        let Some(file) = file else {
+            continue;
+        };
+        report
+            .entry(file.id)
+            .or_insert_with(|| DegradedFile {
+                file_id: file.id,
+                directory: file.directory,
+                file_name: file.file_name,
+                problems: Vec::new(),
+            })
+            .problems
+            .push(Degradation::ChecksumMismatch);
+    }
+
+    Ok(report.into_values().collect())
+}
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+
+use sea_orm::prelude::*;
+
+use crate::entities::{media_file_albums, media_files, media_metadata};
+
+use super::utils::sort_key;
+
+/// How a track belongs to a work of classical music.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkTags {
+    // Empty if unknown
+    pub composer: String,
+    pub work: String,
+    pub movement: String,
+    pub movement_number: Option<i32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Movement {
+    pub file_id: i32,
+    pub number: Option<i32>,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Work {
+    pub composer: String,
+    pub title: String,
+    pub movements: Vec<Movement>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Composer {
+    pub name: String,
+    pub work_count: usize,
+    pub track_count: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AlbumWorks {
+    /// The works on the album, in the order they start.
+    pub works: Vec<Work>,
+    /// Tracks that aren't part of a work, in album order.
+    pub other_file_ids: Vec<i32>,
+}
+
+const WORK_META_KEYS: [&str; 7] = [
+    "composer",
+    "sort_composer",
+    "work",
+    "movement_name",
+    "movement_number",
+    "track_title",
+    "track_number",
+];
+
+fn parse_number(value: &str) -> Option<i32> {
+    value.split('/').next()?.trim().parse().ok()
+}
+
+fn parse_roman_numeral(value: &str) -> Option<i32> {
+    let digit = |x: char| match x {
+        'I' => Some(1),
+        'V' => Some(5),
+        'X' => Some(10),
+        'L' => Some(50),
+        'C' => Some(100),
+        _ => None,
+    };
+    let digits = value.chars().map(digit).collect::<Option<Vec<i32>>>()?;
+    if digits.is_empty() || digits.len() > 8 {
+        return None;
+    }
+
+    let mut number = 0;
+    for (index, value) in digits.iter().enumerate() {
+        match digits.get(index + 1) {
+            Some(next) if next > value => number -= value,
+            _ => number += value,
+        }
+    }
+    (number > 0).then_some(number)
+}
+
+// Movements are often titled like "II. Adagio"
+fn movement_number_of_title(movement: &str) -> Option<i32> {
+    let (numeral, _) = movement.split_once(". ")?;
+    parse_roman_numeral(numeral.trim())
+}
+
+fn names_composer(name: &str, composer: &str) -> bool {
+    composer.to_lowercase().contains(&name.to_lowercase())
+}
+
+// Split titles like "Composer: Work - Movement", or "Work: Movement" when the
+// composer is tagged and isn't the one in front of the colon
+fn split_title(title: &str, composer: &str) -> Option<(Option<String>, String, String)> {
+    let (head, rest) = title.split_once(": ")?;
+    let (head, rest) = (head.trim(), rest.trim());
+    if head.is_empty() || rest.is_empty() {
+        return None;
+    }
+
+    if composer.is_empty() || names_composer(head, composer) {
+        let (work, movement) = rest.split_once(" - ")?;
+        let (work, movement) = (work.trim(), movement.trim());
+        if work.is_empty() || movement.is_empty() {
+            return None;
+        }
+        return Some((
+            Some(head.to_string()),
+            work.to_string(),
+            movement.to_string(),
+        ));
+    }
+
+    Some((None, head.to_string(), rest.to_string()))
+}
+
+/// Find out which work and movement a track is, from its tags.
+///
+/// The `work` and `movement_name` tags are used when present, otherwise the
+/// title is split when it reads like "Composer: Work - Movement", or like
+/// "Work: Movement" for tracks tagged with a composer.
+///
+/// # Arguments
+/// * `metadata` - The tags of the track, by meta key.
+///
+/// # Returns
+/// * `Option<WorkTags>` - None if the track isn't part of a work.
+pub fn parse_work_tags(metadata: &HashMap<String, String>) -> Option<WorkTags> {
+    let get = |key: &str| {
+        metadata
+            .get(key)
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+    };
+    let title = get("track_title").unwrap_or_default();
+    let mut composer = get("composer").unwrap_or_default().to_string();
+
+    let (work, movement) = match get("work") {
+        Some(work) => {
+            // Titles often repeat the work in front of the movement
+            let movement = get("movement_name").unwrap_or_else(|| {
+                title
+                    .strip_prefix(work)
+                    .map(|x| x.trim_start_matches([':', '-', ' ']))
+                    .filter(|x| !x.is_empty())
+                    .unwrap_or(title)
+            });
+            (work.to_string(), movement.to_string())
+        }
+        None => {
+            let (named_composer, work, movement) = split_title(title, &composer)?;
+            if composer.is_empty() {
+                composer = named_composer.unwrap_or_default();
+            }
+            (work, movement)
+        }
+    };
+
+    let movement_number = get("movement_number")
+        .and_then(parse_number)
+        .or_else(|| movement_number_of_title(&movement));
+
+    Some(WorkTags {
+        composer,
+        work,
+        movement,
+        movement_number,
+    })
+}
+
+struct WorkTrack {
+    file_id: i32,
+    tags: WorkTags,
+    track_number: Option<i32>,
+    sort_composer: Option<String>,
+}
+
+async fn get_work_tracks(
+    db: &DatabaseConnection,
+    file_ids: Vec<i32>,
+) -> Result<HashMap<i32, WorkTrack>, DbErr> {
+    let metadata_entries = media_metadata::Entity::find()
+        .filter(
+            media_metadata::Column::FileId
+                .is_in(file_ids)
+                .and(media_metadata::Column::MetaKey.is_in(WORK_META_KEYS)),
+        )
+        .all(db)
+        .await?;
+
+    let mut metadata_map: HashMap<i32, HashMap<String, String>> = HashMap::new();
+    for entry in metadata_entries {
+        metadata_map
+            .entry(entry.file_id)
+            .or_default()
+            .insert(entry.meta_key, entry.meta_value);
+    }
+
+    Ok(metadata_map
+        .into_iter()
+        .filter_map(|(file_id, metadata)| {
+            let tags = parse_work_tags(&metadata)?;
+            let track = WorkTrack {
+                file_id,
+                tags,
+                track_number: metadata.get("track_number").and_then(|x| parse_number(x)),
+                sort_composer: metadata.get("sort_composer").cloned(),
+            };
+            Some((file_id, track))
+        })
+        .collect())
+}
+
+async fn get_all_work_tracks(db: &DatabaseConnection) -> Result<Vec<WorkTrack>, DbErr> {
+    let file_ids: Vec<i32> = media_files::Entity::find()
+        .filter(media_files::Column::DeletedAt.is_null())
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|x| x.id)
+        .collect();
+
+    Ok(get_work_tracks(db, file_ids).await?.into_values().collect())
+}
+
+// Group tracks into works in the order the works first appear, movements
+// ordered by their number and then by track number
+fn group_works(tracks: impl IntoIterator<Item = WorkTrack>) -> Vec<Work> {
+    let mut works: Vec<(Work, Vec<(i32, i32)>)> = Vec::new();
+    let mut positions: HashMap<(String, String), usize> = HashMap::new();
+
+    for track in tracks {
+        let key = (track.tags.composer.clone(), track.tags.work.clone());
+        let position = *positions.entry(key).or_insert_with(|| {
+            works.push((
+                Work {
+                    composer: track.tags.composer.clone(),
+                    title: track.tags.work.clone(),
+                    movements: Vec::new(),
+                },
+                Vec::new(),
+            ));
+            works.len() - 1
+        });
+
+        let (work, order) = &mut works[position];
+        work.movements.push(Movement {
+            file_id: track.file_id,
+            number: track.tags.movement_number,
+            title: track.tags.movement,
+        });
+        order.push((
+            track.tags.movement_number.unwrap_or(i32::MAX),
+            track.track_number.unwrap_or(i32::MAX),
+        ));
+    }
+
+    works
+        .into_iter()
+        .map(|(mut work, order)| {
+            let mut movements: Vec<_> = order.into_iter().zip(work.movements).collect();
+            movements.sort_by_key(|(order, movement)| (*order, movement.file_id));
+            work.movements = movements.into_iter().map(|x| x.1).collect();
+            work
+        })
+        .collect()
+}
+
+/// Get every composer with works in the library.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Vec<Composer>, DbErr>` - The composers, sorted by their sort tag if there is one.
+///   Works without a known composer are counted under an empty name.
+pub async fn get_composers(db: &DatabaseConnection) -> Result<Vec<Composer>, DbErr> {
+    let tracks = get_all_work_tracks(db).await?;
+
+    let mut sort_names: HashMap<String, Option<String>> = HashMap::new();
+    for track in &tracks {
+        let sort_name = sort_names.entry(track.tags.composer.clone()).or_default();
+        if sort_name.is_none() {
+            sort_name.clone_from(&track.sort_composer);
+        }
+    }
+
+    let mut composers: HashMap<String, Composer> = HashMap::new();
+    for work in group_works(tracks) {
+        let composer = composers
+            .entry(work.composer.clone())
+            .or_insert_with(|| Composer {
+                name: work.composer.clone(),
+                ..Default::default()
+            });
+        composer.work_count += 1;
+        composer.track_count += work.movements.len();
+    }
+
+    let mut composers: Vec<Composer> = composers.into_values().collect();
+    composers.sort_by_cached_key(|x| {
+        let sort_name = sort_names.get(&x.name).cloned().flatten();
+        sort_key(&x.name, sort_name.as_deref())
+    });
+
+    Ok(composers)
+}
+
+/// Get the works of a composer.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `composer` - The name of the composer, empty for works without a known composer.
+///
+/// # Returns
+/// * `Result<Vec<Work>, DbErr>` - The works, sorted by title.
+pub async fn get_works_of_composer(
+    db: &DatabaseConnection,
+    composer: &str,
+) -> Result<Vec<Work>, DbErr> {
+    let tracks = get_all_work_tracks(db)
+        .await?
+        .into_iter()
+        .filter(|x| x.tags.composer == composer);
+
+    let mut works = group_works(tracks);
+    works.sort_by_cached_key(|x| sort_key(&x.title, None));
+
+    Ok(works)
+}
+
+/// Group the tracks of an album by the works they belong to.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `album_id` - The ID of the album.
+///
+/// # Returns
+/// * `Result<AlbumWorks, DbErr>` - The works and the remaining tracks of the album.
+pub async fn get_album_works(db: &DatabaseConnection, album_id: i32) -> Result<AlbumWorks, DbErr> {
+    let mut items = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::AlbumId.eq(album_id))
+        .all(db)
+        .await?;
+    items.sort_by_key(|x| (x.track_number.unwrap_or(i32::MAX), x.media_file_id));
+
+    let file_ids: Vec<i32> = items.iter().map(|x| x.media_file_id).collect();
+    let mut tracks = get_work_tracks(db, file_ids.clone()).await?;
+
+    let mut work_tracks = Vec::new();
+    let mut other_file_ids = Vec::new();
+    for file_id in file_ids {
+        match tracks.remove(&file_id) {
+            Some(track) => work_tracks.push(track),
+            None => other_file_ids.push(file_id),
+        }
+    }
+
+    Ok(AlbumWorks {
+        works: group_works(work_tracks),
+        other_file_ids,
+    })
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use sea_orm::prelude::*;
+use sea_orm::QuerySelect;
+
+use crate::connection::{MainDbConnection, RecommendationDbConnection};
+use crate::entities::{media_file_artists, media_file_playlists, media_metadata};
+
+use super::metadata::parse_year;
+use super::recommendation::get_recommendation_by_file_id;
+
+const ARTIST_WEIGHT: f32 = 3.0;
+const GENRE_WEIGHT: f32 = 2.0;
+/// Weight of one playlist both files are in.
+const PLAYLIST_WEIGHT: f32 = 1.0;
+const MAX_SHARED_PLAYLISTS: usize = 3;
+/// Weight of two files released the same year.
+const YEAR_WEIGHT: f32 = 1.0;
+/// Years apart after which the release date doesn't bring files closer.
+const YEAR_RANGE: i32 = 10;
+
+const DATE_KEYS: [&str; 2] = ["original_date", "date"];
+
+// Files sharing a link with the seed, with the number of shared links
+async fn get_shared_links<E, C>(
+    db: &DatabaseConnection,
+    file_column: C,
+    link_column: C,
+    seed_id: i32,
+) -> Result<HashMap<i32, usize>, DbErr>
+where
+    E: EntityTrait,
+    C: ColumnTrait,
+{
+    let links: Vec<i32> = E::find()
+        .select_only()
+        .column(link_column)
+        .filter(file_column.eq(seed_id))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let mut shared: HashMap<i32, usize> = HashMap::new();
+    if links.is_empty() {
+        return Ok(shared);
+    }
+
+    let files: Vec<(i32, i32)> = E::find()
+        .select_only()
+        .column(file_column)
+        .column(link_column)
+        .filter(link_column.is_in(links))
+        .filter(file_column.ne(seed_id))
+        .into_tuple()
+        .all(db)
+        .await?;
+    for (file_id, _) in files {
+        *shared.entry(file_id).or_default() += 1;
+    }
+
+    Ok(shared)
+}
+
+/// Score every file against the seed by what their tags and playlists have
+/// in common, used before the audio of the library is analysed.
+///
+/// Shared artists weigh the most, then the genre, the playlists both files
+/// are in, and how close their release years are.
+///
+/// # Arguments
+/// * `main_db` - The database holding the library.
+/// * `seed_id` - The ID of the file to find similar files for.
+/// * `n` - The number of files to return.
+///
+/// # Returns
+/// * `Result<Vec<(i32, f32)>, DbErr>` - The most similar files and their score, the best first.
+pub async fn get_recommendation_by_metadata(
+    main_db: &MainDbConnection,
+    seed_id: i32,
+    n: usize,
+) -> Result<Vec<(i32, f32)>, DbErr> {
+    let mut scores: HashMap<i32, f32> = HashMap::new();
+
+    let artists = get_shared_links::<media_file_artists::Entity, _>(
+        main_db,
+        media_file_artists::Column::MediaFileId,
+        media_file_artists::Column::ArtistId,
+        seed_id,
+    )
+    .await?;
+    for file_id in artists.into_keys() {
+        *scores.entry(file_id).or_default() += ARTIST_WEIGHT;
+    }
+
+    let playlists = get_shared_links::<media_file_playlists::Entity, _>(
+        main_db,
+        media_file_playlists::Column::MediaFileId,
+        media_file_playlists::Column::PlaylistId,
+        seed_id,
+    )
+    .await?;
+    for (file_id, count) in playlists {
+        *scores.entry(file_id).or_default() +=
+            PLAYLIST_WEIGHT * count.min(MAX_SHARED_PLAYLISTS) as f32;
+    }
+
+    let seed_tags: Vec<(String, String)> = media_metadata::Entity::find()
+        .select_only()
+        .column(media_metadata::Column::MetaKey)
+        .column(media_metadata::Column::MetaValue)
+        .filter(media_metadata::Column::FileId.eq(seed_id))
+        .into_tuple()
+        .all(main_db)
+        .await?;
+    let seed_tags: HashMap<String, String> = seed_tags.into_iter().collect();
+
+    if let Some(genre) = seed_tags.get("genre").filter(|x| !x.trim().is_empty()) {
+        let files: Vec<(i32, String)> = media_metadata::Entity::find()
+            .select_only()
+            .column(media_metadata::Column::FileId)
+            .column(media_metadata::Column::MetaValue)
+            .filter(media_metadata::Column::MetaKey.eq("genre"))
+            .filter(media_metadata::Column::FileId.ne(seed_id))
+            .into_tuple()
+            .all(main_db)
+            .await?;
+        let genre = genre.trim().to_lowercase();
+        for (file_id, _) in files
+            .into_iter()
+            .filter(|(_, x)| x.trim().to_lowercase() == genre)
+        {
+            *scores.entry(file_id).or_default() += GENRE_WEIGHT;
+        }
+    }
+
+    let seed_year = DATE_KEYS
+        .iter()
+        .find_map(|key| parse_year(seed_tags.get(*key)?));
+    if let Some(seed_year) = seed_year {
+        let dates: Vec<(i32, String, String)> = media_metadata::Entity::find()
+            .select_only()
+            .column(media_metadata::Column::FileId)
+            .column(media_metadata::Column::MetaKey)
+            .column(media_metadata::Column::MetaValue)
+            .filter(media_metadata::Column::MetaKey.is_in(DATE_KEYS))
+            .filter(media_metadata::Column::FileId.ne(seed_id))
+            .into_tuple()
+            .all(main_db)
+            .await?;
+
+        // The original date wins over the date of the release
+        let mut years: HashMap<i32, (bool, i32)> = HashMap::new();
+        for (file_id, key, value) in dates {
+            let Some(year) = parse_year(&value) else {
+                continue;
+            };
+            let original = key == DATE_KEYS[0];
+            let entry = years.entry(file_id).or_insert((original, year));
+            if original && !entry.0 {
+                *entry = (original, year);
+            }
+        }
+
+        for (file_id, (_, year)) in years {
+            let distance = (year - seed_year).abs();
+            if distance < YEAR_RANGE {
+                *scores.entry(file_id).or_default() +=
+                    YEAR_WEIGHT * (1.0 - distance as f32 / YEAR_RANGE as f32);
+            }
+        }
+    }
+
+    let mut scores: Vec<(i32, f32)> = scores.into_iter().collect();
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    scores.truncate(n);
+
+    Ok(scores)
+}
+
+/// Recommend files similar to a seed, by their analysis when the seed is
+/// analysed and by their metadata until then.
+///
+/// # Arguments
+/// * `main_db` - The database holding the library.
+/// * `recommend_db` - The recommendation database.
+/// * `seed_id` - The ID of the file to find similar files for.
+/// * `n` - The number of files to return.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The seed followed by the recommended files.
+pub async fn get_recommendation_with_fallback(
+    main_db: &MainDbConnection,
+    recommend_db: &RecommendationDbConnection,
+    seed_id: i32,
+    n: usize,
+) -> Result<Vec<i32>, DbErr> {
+    if let Ok(recommendations) = get_recommendation_by_file_id(recommend_db, seed_id, n) {
+        return Ok(recommendations
+            .into_iter()
+            .map(|(id, _)| id as i32)
+            .collect());
+    }
+
+    // The seed comes first, like it does from the index
+    let recommendations =
+        get_recommendation_by_metadata(main_db, seed_id, n.saturating_sub(1)).await?;
+
+    Ok(std::iter::once(seed_id)
+        .chain(recommendations.into_iter().map(|(id, _)| id))
+        .collect())
+}
@@ -0,0 +1,108 @@
+use std::cmp::Ordering;
+
+use icu_collator::options::CollatorOptions;
+use icu_collator::preferences::CollationNumericOrdering;
+use icu_collator::{Collator, CollatorBorrowed, CollatorPreferences};
+use icu_locale_core::Locale;
+use sea_orm::prelude::*;
+use tracing::warn;
+
+use metadata::normalize::{clean_name, strip_article};
+
+use super::settings::get_setting;
+use super::utils::generate_group_name;
+
+/// The locale artists, albums and playlists are sorted for, a BCP 47 tag
+/// like `zh` or `sv`. Unset libraries use the root collation, which orders
+/// every script reasonably but none of them by its local rules.
+pub const COLLATION_LOCALE_KEY: &str = "library.collation_locale";
+
+/// The name a collection is sorted by, its sort tag if there is one.
+pub fn sort_name<'a>(name: &'a str, sort_name: Option<&'a str>) -> &'a str {
+    sort_name.unwrap_or(name)
+}
+
+/// The group a collection is listed under.
+///
+/// # Arguments
+/// * `name` - The name of the artist or album.
+/// * `sort_name` - Its sort tag, which always decides the group.
+/// * `ignore_article` - Whether a leading "The" of the name is skipped.
+///
+/// # Returns
+/// * `String` - The initial of the group, see `generate_group_name`.
+pub fn collection_group(name: &str, sort_name: Option<&str>, ignore_article: bool) -> String {
+    match sort_name {
+        Some(sort_name) => generate_group_name(sort_name),
+        None if ignore_article => generate_group_name(strip_article(&clean_name(name))),
+        None => generate_group_name(name),
+    }
+}
+
+/// Compares names by the rules of a locale, with numbers in their numeric
+/// order so "Vol. 2" comes before "Vol. 10".
+pub struct NameCollator {
+    collator: CollatorBorrowed<'static>,
+}
+
+impl NameCollator {
+    /// Create a collator for a BCP 47 tag, unknown tags fall back to the
+    /// root collation.
+    pub fn new(locale: &str) -> Self {
+        let locale = locale.parse::<Locale>().unwrap_or_else(|e| {
+            warn!("Unknown collation locale {:?}: {}", locale, e);
+            Locale::UNKNOWN
+        });
+
+        let mut preferences = CollatorPreferences::from(&locale);
+        preferences.numeric_ordering = Some(CollationNumericOrdering::True);
+        let collator = Collator::try_new(preferences, CollatorOptions::default())
+            .or_else(|e| {
+                warn!("No collation data for {}: {}", locale, e);
+                Collator::try_new(Default::default(), CollatorOptions::default())
+            })
+            .expect("The root collation is built in");
+
+        Self { collator }
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        self.collator.compare(a, b)
+    }
+
+    /// Compare the initials of groups, `#` comes last like in fast-scroll
+    /// bars.
+    pub fn compare_groups(&self, a: &str, b: &str) -> Ordering {
+        match (a == "#", b == "#") {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => self.compare(a, b),
+        }
+    }
+
+    /// Stable sort of items by a name computed once for every item.
+    pub fn sort_by_key<T, F>(&self, items: &mut Vec<T>, key: F)
+    where
+        F: Fn(&T) -> String,
+    {
+        let mut keyed: Vec<(String, T)> = items.drain(..).map(|x| (key(&x), x)).collect();
+        keyed.sort_by(|a, b| self.compare(&a.0, &b.0));
+        items.extend(keyed.into_iter().map(|x| x.1));
+    }
+}
+
+/// Create the collator of a library.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<NameCollator, DbErr>` - The collator of the configured locale.
+pub async fn get_collator<C>(main_db: &C) -> Result<NameCollator, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let locale = get_setting(main_db, COLLATION_LOCALE_KEY).await?;
+    Ok(NameCollator::new(locale.as_deref().unwrap_or("und")))
+}
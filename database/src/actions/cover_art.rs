@@ -1,15 +1,26 @@
 use dunce::canonicalize;
 use sea_orm::{
-    ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, FromQueryResult,
-    Order, QueryFilter, QueryTrait,
+    ActiveValue, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait,
+    FromQueryResult, Order, QueryFilter, QuerySelect, QueryTrait, TransactionTrait,
 };
+use std::collections::HashMap;
 use std::path::Path;
+use tracing::warn;
 
-use migration::{Func, SimpleExpr};
+use migration::{Expr, Func, SimpleExpr};
 
 use metadata::cover_art::extract_cover_art_binary;
+use metadata::mosaic::{compose_mosaic, MOSAIC_TILES};
+use metadata::palette::{extract_palette, PALETTE_SIZE};
+use metadata::placeholder::encode_blurhash;
 
-use crate::entities::{media_cover_art, media_files};
+use crate::entities::{media_cover_art, media_files, playlists};
+
+use super::playlists::get_playlist_items;
+
+// Mosaics are stored under the IDs of the covers they show, so playlists
+// whose first covers didn't change share the same one
+const MOSAIC_HASH_PREFIX: &str = "mosaic:";
 
 pub async fn get_magic_cover_art(
     db: &DatabaseConnection,
@@ -72,6 +83,13 @@ pub async fn sync_cover_art_by_file_id(
                         id: ActiveValue::NotSet,
                         file_hash: ActiveValue::Set(cover_art.crc.clone()),
                         binary: ActiveValue::Set(cover_art.data.clone()),
+                        color_palette: ActiveValue::Set(Some(encode_palette(&extract_palette(
+                            &cover_art.data,
+                            PALETTE_SIZE,
+                        )))),
+                        blurhash: ActiveValue::Set(Some(
+                            encode_blurhash(&cover_art.data).unwrap_or_default(),
+                        )),
                     };
 
                     let insert_result = media_cover_art::Entity::insert(new_cover_art)
@@ -106,6 +124,8 @@ pub async fn sync_cover_art_by_file_id(
                         id: ActiveValue::NotSet,
                         file_hash: ActiveValue::Set(String::new()),
                         binary: ActiveValue::Set(Vec::new()),
+                        color_palette: ActiveValue::Set(Some(String::new())),
+                        blurhash: ActiveValue::Set(Some(String::new())),
                     };
 
                     let insert_result = media_cover_art::Entity::insert(new_magic_cover_art)
@@ -143,12 +163,341 @@ pub async fn get_cover_art_by_id(
     }
 }
 
+fn encode_palette(palette: &[u32]) -> String {
+    palette
+        .iter()
+        .map(|x| format!("{:06x}", x))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn decode_palette(palette: &str) -> Vec<u32> {
+    palette
+        .split(',')
+        .filter_map(|x| u32::from_str_radix(x, 16).ok())
+        .collect()
+}
+
+/// Get the dominant colors of a cover art, most common first.
+///
+/// Covers stored before palettes were extracted get their palette on the
+/// first request.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `id` - The ID of the cover art.
+///
+/// # Returns
+/// * `Result<Vec<u32>, DbErr>` - The colors as `0xRRGGBB`, empty if the cover doesn't exist.
+pub async fn get_cover_art_palette(
+    db: &DatabaseConnection,
+    id: i32,
+) -> Result<Vec<u32>, sea_orm::DbErr> {
+    let Some(cover_art) = media_cover_art::Entity::find_by_id(id).one(db).await? else {
+        return Ok(Vec::new());
+    };
+
+    if let Some(palette) = &cover_art.color_palette {
+        return Ok(decode_palette(palette));
+    }
+
+    let palette = extract_palette(&cover_art.binary, PALETTE_SIZE);
+
+    let mut active_model: media_cover_art::ActiveModel = cover_art.into();
+    active_model.color_palette = ActiveValue::Set(Some(encode_palette(&palette)));
+    media_cover_art::Entity::update(active_model)
+        .exec(db)
+        .await?;
+
+    Ok(palette)
+}
+
+/// Get the blurhashes of cover arts, used as placeholders while they load.
+///
+/// Covers stored before blurhashes were computed get theirs on the first
+/// request.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `ids` - The IDs of the cover arts.
+///
+/// # Returns
+/// * `Result<HashMap<i32, String>, DbErr>` - The blurhash of every cover that has one.
+pub async fn get_cover_art_blurhashes(
+    db: &DatabaseConnection,
+    ids: &[i32],
+) -> Result<HashMap<i32, String>, sea_orm::DbErr> {
+    // Leave the images out unless they are needed
+    let stored: Vec<(i32, Option<String>)> = media_cover_art::Entity::find()
+        .select_only()
+        .column(media_cover_art::Column::Id)
+        .column(media_cover_art::Column::Blurhash)
+        .filter(media_cover_art::Column::Id.is_in(ids.to_vec()))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let mut result = HashMap::new();
+    let mut missing = Vec::new();
+    for (id, blurhash) in stored {
+        match blurhash {
+            Some(blurhash) => {
+                result.insert(id, blurhash);
+            }
+            None => missing.push(id),
+        }
+    }
+
+    if !missing.is_empty() {
+        let cover_arts = media_cover_art::Entity::find()
+            .filter(media_cover_art::Column::Id.is_in(missing))
+            .all(db)
+            .await?;
+
+        for cover_art in cover_arts {
+            // Covers that can't be decoded are stored empty so they aren't retried
+            let blurhash = encode_blurhash(&cover_art.binary).unwrap_or_default();
+            let id = cover_art.id;
+
+            // Readers can't store it, it is computed again next time
+            let mut active_model: media_cover_art::ActiveModel = cover_art.into();
+            active_model.blurhash = ActiveValue::Set(Some(blurhash.clone()));
+            if let Err(e) = media_cover_art::Entity::update(active_model).exec(db).await {
+                warn!("Unable to store the blurhash of cover art {}: {}", id, e);
+            }
+
+            result.insert(id, blurhash);
+        }
+    }
+
+    result.retain(|_, x| !x.is_empty());
+    Ok(result)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoverArtGarbageReport {
+    pub removed: usize,
+    pub reclaimed_bytes: u64,
+}
+
+/// Remove cover arts no media file or playlist refers to anymore.
+///
+/// The placeholder for files without cover art is kept even if it is
+/// unused, it is recreated on the next scan anyway.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<CoverArtGarbageReport, DbErr>` - The number of removed covers and their size.
+pub async fn collect_cover_art_garbage(
+    db: &DatabaseConnection,
+) -> Result<CoverArtGarbageReport, sea_orm::DbErr> {
+    // Covers are picked again by the delete itself, one a scan starts using
+    // in between is kept instead of taking its files along by cascade
+    let unreferenced = Condition::all()
+        .add(media_cover_art::Column::FileHash.ne(String::new()))
+        .add(
+            media_cover_art::Column::Id.not_in_subquery(
+                media_files::Entity::find()
+                    .select_only()
+                    .column(media_files::Column::CoverArtId)
+                    .filter(media_files::Column::CoverArtId.is_not_null())
+                    .into_query(),
+            ),
+        )
+        // Mosaics of playlists
+        .add(
+            media_cover_art::Column::Id.not_in_subquery(
+                playlists::Entity::find()
+                    .select_only()
+                    .column(playlists::Column::CoverArtId)
+                    .filter(playlists::Column::CoverArtId.is_not_null())
+                    .into_query(),
+            ),
+        );
+
+    let txn = db.begin().await?;
+    let reclaimed_bytes: Option<i64> = media_cover_art::Entity::find()
+        .select_only()
+        .column_as(Expr::cust("SUM(LENGTH(\"binary\"))"), "size")
+        .filter(unreferenced.clone())
+        .into_tuple()
+        .one(&txn)
+        .await?
+        .flatten();
+    let result = media_cover_art::Entity::delete_many()
+        .filter(unreferenced)
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+
+    Ok(CoverArtGarbageReport {
+        removed: result.rows_affected as usize,
+        reclaimed_bytes: reclaimed_bytes.unwrap_or(0) as u64,
+    })
+}
+
+/// Generate the mosaic of a playlist from the covers of its first tracks.
+///
+/// Must be called after the items of the playlist changed. Playlists with
+/// fewer than two distinct covers have no mosaic.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `lib_path` - The root path of the library, to extract covers not read yet.
+/// * `playlist_id` - The ID of the playlist.
+///
+/// # Returns
+/// * `Result<Option<i32>, DbErr>` - The cover art ID of the mosaic.
+pub async fn sync_playlist_cover_art(
+    db: &DatabaseConnection,
+    lib_path: &str,
+    playlist_id: i32,
+) -> Result<Option<i32>, sea_orm::DbErr> {
+    let Some(playlist) = playlists::Entity::find_by_id(playlist_id).one(db).await? else {
+        return Ok(None);
+    };
+
+    let item_ids = get_playlist_items(db, playlist_id).await?;
+    let files: HashMap<i32, media_files::Model> = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_in(item_ids.clone()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect();
+
+    let mut magic_cover_art_id = get_magic_cover_art_id(db).await;
+    let mut cover_art_ids = Vec::new();
+    for file_id in item_ids {
+        if cover_art_ids.len() == MOSAIC_TILES {
+            break;
+        }
+        let Some(file) = files.get(&file_id) else {
+            continue;
+        };
+
+        let cover_art_id = match file.cover_art_id {
+            Some(cover_art_id) => Some(cover_art_id),
+            // Covers are extracted the first time they are shown
+            None if Path::new(lib_path)
+                .join(&file.directory)
+                .join(&file.file_name)
+                .exists() =>
+            {
+                let cover_art = sync_cover_art_by_file_id(db, lib_path, file_id).await?;
+                magic_cover_art_id = get_magic_cover_art_id(db).await;
+                cover_art.map(|x| x.0)
+            }
+            None => None,
+        };
+
+        if let Some(cover_art_id) = cover_art_id {
+            if Some(cover_art_id) != magic_cover_art_id && !cover_art_ids.contains(&cover_art_id) {
+                cover_art_ids.push(cover_art_id);
+            }
+        }
+    }
+
+    let mosaic_id = if cover_art_ids.len() < 2 {
+        None
+    } else {
+        let file_hash = format!(
+            "{}{}",
+            MOSAIC_HASH_PREFIX,
+            cover_art_ids
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+
+        let existing = media_cover_art::Entity::find()
+            .filter(media_cover_art::Column::FileHash.eq(file_hash.clone()))
+            .one(db)
+            .await?;
+
+        match existing {
+            Some(existing) => Some(existing.id),
+            None => {
+                let mut binaries: HashMap<i32, Vec<u8>> = media_cover_art::Entity::find()
+                    .filter(media_cover_art::Column::Id.is_in(cover_art_ids.clone()))
+                    .all(db)
+                    .await?
+                    .into_iter()
+                    .map(|x| (x.id, x.binary))
+                    .collect();
+                let covers: Vec<Vec<u8>> = cover_art_ids
+                    .iter()
+                    .filter_map(|x| binaries.remove(x))
+                    .collect();
+
+                match compose_mosaic(&covers) {
+                    Some(data) => {
+                        let mosaic = media_cover_art::ActiveModel {
+                            id: ActiveValue::NotSet,
+                            file_hash: ActiveValue::Set(file_hash),
+                            color_palette: ActiveValue::Set(Some(encode_palette(
+                                &extract_palette(&data, PALETTE_SIZE),
+                            ))),
+                            blurhash: ActiveValue::Set(Some(
+                                encode_blurhash(&data).unwrap_or_default(),
+                            )),
+                            binary: ActiveValue::Set(data),
+                        };
+
+                        let result = media_cover_art::Entity::insert(mosaic).exec(db).await?;
+                        Some(result.last_insert_id)
+                    }
+                    None => None,
+                }
+            }
+        }
+    };
+
+    if playlist.cover_art_id != mosaic_id {
+        let mut active_model: playlists::ActiveModel = playlist.into();
+        active_model.cover_art_id = ActiveValue::Set(mosaic_id);
+        playlists::Entity::update(active_model).exec(db).await?;
+    }
+
+    Ok(mosaic_id)
+}
+
+/// Generate the mosaics of every playlist, see `sync_playlist_cover_art`.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `lib_path` - The root path of the library.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if every playlist was handled.
+pub async fn sync_all_playlist_cover_arts(
+    db: &DatabaseConnection,
+    lib_path: &str,
+) -> Result<(), sea_orm::DbErr> {
+    let playlist_ids: Vec<i32> = playlists::Entity::find()
+        .select_only()
+        .column(playlists::Column::Id)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    for playlist_id in playlist_ids {
+        sync_playlist_cover_art(db, lib_path, playlist_id).await?;
+    }
+
+    Ok(())
+}
+
 pub async fn get_random_cover_art_ids(
     db: &DatabaseConnection,
     n: usize,
 ) -> Result<Vec<media_cover_art::Model>, Box<dyn std::error::Error>> {
     let mut query: sea_orm::sea_query::SelectStatement = media_cover_art::Entity::find()
         .filter(media_cover_art::Column::FileHash.ne(String::new()))
+        .filter(media_cover_art::Column::FileHash.not_like(format!("{}%", MOSAIC_HASH_PREFIX)))
         .as_query()
         .to_owned();
 
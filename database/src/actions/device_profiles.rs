@@ -0,0 +1,85 @@
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue;
+
+use crate::entities::output_device_profiles;
+
+/// The playback settings remembered for an output device, like headphones
+/// or a DAC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceProfile {
+    pub volume: f32,
+    pub night_mode: bool,
+    pub crossfeed: bool,
+}
+
+/// Remember the settings of an output device, replacing the ones saved
+/// before.
+///
+/// # Arguments
+/// * `user_db` - A reference to the database connection.
+/// * `device_name` - The name of the device as the system reports it.
+/// * `profile` - The settings to remember.
+pub async fn save_device_profile(
+    user_db: &DatabaseConnection,
+    device_name: &str,
+    profile: DeviceProfile,
+) -> Result<(), DbErr> {
+    let item = output_device_profiles::ActiveModel {
+        device_name: ActiveValue::Set(device_name.to_string()),
+        volume: ActiveValue::Set(profile.volume as f64),
+        night_mode: ActiveValue::Set(profile.night_mode),
+        crossfeed: ActiveValue::Set(profile.crossfeed),
+        ..Default::default()
+    };
+
+    output_device_profiles::Entity::insert(item)
+        .on_conflict(
+            OnConflict::column(output_device_profiles::Column::DeviceName)
+                .update_columns([
+                    output_device_profiles::Column::Volume,
+                    output_device_profiles::Column::NightMode,
+                    output_device_profiles::Column::Crossfeed,
+                ])
+                .to_owned(),
+        )
+        .exec(user_db)
+        .await?;
+
+    Ok(())
+}
+
+/// Get the remembered settings of an output device.
+///
+/// # Arguments
+/// * `user_db` - A reference to the database connection.
+/// * `device_name` - The name of the device as the system reports it.
+///
+/// # Returns
+/// * `Result<Option<DeviceProfile>, DbErr>` - The settings, `None` for
+///   devices never played through.
+pub async fn get_device_profile(
+    user_db: &DatabaseConnection,
+    device_name: &str,
+) -> Result<Option<DeviceProfile>, DbErr> {
+    let item = output_device_profiles::Entity::find()
+        .filter(output_device_profiles::Column::DeviceName.eq(device_name))
+        .one(user_db)
+        .await?;
+
+    Ok(item.map(|x| DeviceProfile {
+        volume: x.volume as f32,
+        night_mode: x.night_mode,
+        crossfeed: x.crossfeed,
+    }))
+}
+
+/// Get the names of all devices with remembered settings.
+pub async fn get_profiled_device_names(user_db: &DatabaseConnection) -> Result<Vec<String>, DbErr> {
+    Ok(output_device_profiles::Entity::find()
+        .all(user_db)
+        .await?
+        .into_iter()
+        .map(|x| x.device_name)
+        .collect())
+}
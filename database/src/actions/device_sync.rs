@@ -0,0 +1,374 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use chrono::Utc;
+use metadata::transcode::{is_transcode_codec, transcode_file};
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveValue, QueryOrder};
+use tracing::warn;
+
+use crate::entities::{media_files, sync_devices, synced_files};
+
+use super::albums::{get_album_by_id, get_album_tracks_of_files, get_media_file_ids_of_album};
+use super::file::get_files_by_ids;
+use super::metadata::get_metadata_summary_by_file_ids;
+use super::playlists::{get_playlist_by_id, get_playlist_items};
+
+/// A folder music is exported to, like a phone or an SD card.
+///
+/// # Arguments
+/// * `user_db` - The database holding the sync state.
+/// * `name` - A name for the device, unique.
+/// * `path` - The folder the music is written to.
+/// * `codec` - The codec to transcode to, `None` to copy the files as they are.
+///
+/// # Returns
+/// * `Result<sync_devices::Model, DbErr>` - The new device.
+pub async fn create_sync_device(
+    user_db: &DatabaseConnection,
+    name: &str,
+    path: &str,
+    codec: Option<String>,
+) -> Result<sync_devices::Model, DbErr> {
+    if let Some(codec) = codec.as_deref().filter(|x| !is_transcode_codec(x)) {
+        return Err(DbErr::Custom(format!("Can't transcode to {}", codec)));
+    }
+
+    sync_devices::ActiveModel {
+        name: ActiveValue::Set(name.to_string()),
+        path: ActiveValue::Set(path.to_string()),
+        codec: ActiveValue::Set(codec.map(|x| x.to_lowercase())),
+        ..Default::default()
+    }
+    .insert(user_db)
+    .await
+}
+
+pub async fn get_sync_devices(
+    user_db: &DatabaseConnection,
+) -> Result<Vec<sync_devices::Model>, DbErr> {
+    sync_devices::Entity::find()
+        .order_by_asc(sync_devices::Column::Id)
+        .all(user_db)
+        .await
+}
+
+/// Forget a device and what was synced to it, the files on it are left alone.
+pub async fn remove_sync_device(user_db: &DatabaseConnection, device_id: i32) -> Result<(), DbErr> {
+    synced_files::Entity::delete_many()
+        .filter(synced_files::Column::DeviceId.eq(device_id))
+        .exec(user_db)
+        .await?;
+    sync_devices::Entity::delete_by_id(device_id)
+        .exec(user_db)
+        .await?;
+
+    Ok(())
+}
+
+/// The collections to put on a device.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncSelection {
+    pub playlist_ids: Vec<i32>,
+    pub album_ids: Vec<i32>,
+}
+
+/// What a sync did to a device.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncReport {
+    pub copied: usize,
+    pub transcoded: usize,
+    /// Files already on the device and unchanged in the library.
+    pub unchanged: usize,
+    /// Files no longer selected, taken off the device.
+    pub removed: usize,
+    /// Files that couldn't be written, with the reason.
+    pub failed: Vec<(i32, String)>,
+    /// The M3U8 file written for every collection.
+    pub playlists: Vec<PathBuf>,
+}
+
+// Names of collections become file names on all kinds of file systems
+fn file_name_of(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|x| match x {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            x if x.is_control() => '_',
+            x => x,
+        })
+        .collect();
+    let name = name.trim_end_matches(['.', ' ']);
+
+    if name.is_empty() {
+        "Untitled".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+// Files keep their place in the library, with the extension of the codec
+// they are transcoded to
+fn target_path_of(file: &media_files::Model, codec: Option<&str>) -> (String, bool) {
+    let mut path = Path::new(&file.directory).join(&file.file_name);
+    let transcode = codec.filter(|x| !file.extension.eq_ignore_ascii_case(x));
+    if let Some(codec) = transcode {
+        path.set_extension(codec);
+    }
+
+    (
+        path.to_string_lossy().replace('\\', "/"),
+        transcode.is_some(),
+    )
+}
+
+async fn get_collections(
+    main_db: &DatabaseConnection,
+    selection: &SyncSelection,
+) -> Result<Vec<(String, Vec<i32>)>, DbErr> {
+    let mut collections = Vec::new();
+
+    for playlist_id in &selection.playlist_ids {
+        let Some(playlist) = get_playlist_by_id(main_db, *playlist_id).await? else {
+            continue;
+        };
+        collections.push((
+            playlist.name,
+            get_playlist_items(main_db, *playlist_id).await?,
+        ));
+    }
+
+    for album_id in &selection.album_ids {
+        let Some(album) = get_album_by_id(main_db, *album_id).await? else {
+            continue;
+        };
+        let mut file_ids = get_media_file_ids_of_album(main_db, *album_id).await?;
+        let tracks = get_album_tracks_of_files(main_db, &file_ids).await?;
+        file_ids.sort_by_key(|x| (tracks.get(x).map_or(i32::MAX, |x| x.1), *x));
+        collections.push((album.name, file_ids));
+    }
+
+    Ok(collections)
+}
+
+fn write_file(
+    lib_path: &Path,
+    device_path: &Path,
+    file: &media_files::Model,
+    target: &str,
+    transcode: bool,
+) -> anyhow::Result<()> {
+    let src = lib_path.join(&file.directory).join(&file.file_name);
+    let dst = device_path.join(target);
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Written next to the copy on the device, which is kept if this fails
+    let codec = dst.extension().and_then(|x| x.to_str()).unwrap_or_default();
+    let partial = dst.with_extension(format!("{}.partial", codec));
+    let result = if transcode {
+        transcode_file(&src, &partial, codec).map_err(anyhow::Error::from)
+    } else {
+        fs::copy(&src, &partial)
+            .map(|_| ())
+            .with_context(|| format!("Failed to copy {:?}", src))
+    };
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    fs::rename(&partial, &dst).with_context(|| format!("Failed to write {:?}", dst))?;
+
+    Ok(())
+}
+
+async fn write_playlists(
+    main_db: &DatabaseConnection,
+    device_path: &Path,
+    collections: &[(String, Vec<i32>)],
+    targets: &HashMap<i32, String>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let file_ids: Vec<i32> = targets.keys().copied().collect();
+    let summaries: HashMap<i32, _> = get_metadata_summary_by_file_ids(main_db, file_ids)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect();
+
+    let mut paths = Vec::new();
+    for (name, file_ids) in collections {
+        let mut content = String::from("#EXTM3U\n");
+        for file_id in file_ids {
+            let Some(target) = targets.get(file_id) else {
+                continue;
+            };
+            if let Some(summary) = summaries.get(file_id) {
+                content.push_str(&format!(
+                    "#EXTINF:{},{} - {}\n",
+                    summary.duration.round() as i64,
+                    summary.artist,
+                    summary.title
+                ));
+            }
+            content.push_str(target);
+            content.push('\n');
+        }
+
+        let path = device_path.join(format!("{}.m3u8", file_name_of(name)));
+        fs::write(&path, content).with_context(|| format!("Failed to write {:?}", path))?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+/// Put the selected playlists and albums on a device, next to an M3U8 file
+/// for every one of them.
+///
+/// Files already synced are only written again when they changed in the
+/// library.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `user_db` - The database holding the sync state.
+/// * `lib_path` - The root path of the library.
+/// * `device_id` - The ID of the device.
+/// * `selection` - The collections to put on the device.
+/// * `prune` - Take the files synced before but no longer selected off the device.
+///
+/// # Returns
+/// * `Result<SyncReport>` - What was done, files that fail don't stop the sync.
+pub async fn sync_device(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    lib_path: &Path,
+    device_id: i32,
+    selection: &SyncSelection,
+    prune: bool,
+) -> anyhow::Result<SyncReport> {
+    let Some(device) = sync_devices::Entity::find_by_id(device_id)
+        .one(user_db)
+        .await?
+    else {
+        bail!("Device not found: {}", device_id);
+    };
+    let device_path = PathBuf::from(&device.path);
+    fs::create_dir_all(&device_path)
+        .with_context(|| format!("Failed to open the device folder {:?}", device_path))?;
+
+    let collections = get_collections(main_db, selection).await?;
+    let mut file_ids: Vec<i32> = collections
+        .iter()
+        .flat_map(|(_, ids)| ids.iter().copied())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    file_ids.sort_unstable();
+
+    let files = get_files_by_ids(main_db, &file_ids).await?;
+    let synced: HashMap<i32, synced_files::Model> = synced_files::Entity::find()
+        .filter(synced_files::Column::DeviceId.eq(device_id))
+        .all(user_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.file_id, x))
+        .collect();
+
+    // Files that fail to sync stay selected, their copy on the device is kept
+    let selected: HashSet<i32> = files
+        .iter()
+        .filter(|x| x.deleted_at.is_none())
+        .map(|x| x.id)
+        .collect();
+
+    let mut report = SyncReport::default();
+    let mut targets = HashMap::new();
+    for file in files.iter().filter(|x| x.deleted_at.is_none()) {
+        let (target, transcode) = target_path_of(file, device.codec.as_deref());
+
+        let up_to_date = synced.get(&file.id).is_some_and(|x| {
+            x.file_hash == file.file_hash
+                && x.target_path == target
+                && device_path.join(&target).exists()
+        });
+        if up_to_date {
+            report.unchanged += 1;
+            targets.insert(file.id, target);
+            continue;
+        }
+
+        let previous = synced.get(&file.id);
+        if let Err(e) = write_file(lib_path, &device_path, file, &target, transcode) {
+            warn!("Failed to sync file {}: {:#}", file.id, e);
+            report.failed.push((file.id, format!("{:#}", e)));
+            if let Some(previous) = previous.filter(|x| device_path.join(&x.target_path).exists()) {
+                targets.insert(file.id, previous.target_path.clone());
+            }
+            continue;
+        }
+
+        // The file moved on the device, after a rename or a codec switch
+        if let Some(previous) = previous
+            .filter(|x| x.target_path != target && !targets.values().any(|y| *y == x.target_path))
+        {
+            let path = device_path.join(&previous.target_path);
+            if path.exists() {
+                fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+            }
+        }
+        if transcode {
+            report.transcoded += 1;
+        } else {
+            report.copied += 1;
+        }
+
+        synced_files::Entity::insert(synced_files::ActiveModel {
+            device_id: ActiveValue::Set(device_id),
+            file_id: ActiveValue::Set(file.id),
+            target_path: ActiveValue::Set(target.clone()),
+            file_hash: ActiveValue::Set(file.file_hash.clone()),
+            synced_at: ActiveValue::Set(Utc::now().to_rfc3339()),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([synced_files::Column::DeviceId, synced_files::Column::FileId])
+                .update_columns([
+                    synced_files::Column::TargetPath,
+                    synced_files::Column::FileHash,
+                    synced_files::Column::SyncedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(user_db)
+        .await?;
+        targets.insert(file.id, target);
+    }
+
+    if prune {
+        let stale: Vec<&synced_files::Model> = synced
+            .values()
+            .filter(|x| !selected.contains(&x.file_id))
+            .collect();
+        for item in &stale {
+            let path = device_path.join(&item.target_path);
+            if path.exists() {
+                fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+            }
+        }
+
+        report.removed = synced_files::Entity::delete_many()
+            .filter(synced_files::Column::Id.is_in(stale.iter().map(|x| x.id)))
+            .exec(user_db)
+            .await?
+            .rows_affected as usize;
+    }
+
+    report.playlists = write_playlists(main_db, &device_path, &collections, &targets).await?;
+
+    Ok(report)
+}
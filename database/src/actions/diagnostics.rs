@@ -0,0 +1,61 @@
+use sea_orm::prelude::*;
+use sea_orm::{PaginatorTrait, QuerySelect};
+use sea_orm_migration::MigratorTrait;
+
+use migration::Migrator;
+
+use crate::entities::{albums, artists, media_analysis, media_cover_art, media_files, playlists};
+
+/// Size of a library, as attached to bug reports.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LibraryStats {
+    pub tracks: u64,
+    pub albums: u64,
+    pub artists: u64,
+    pub playlists: u64,
+    pub analysed: u64,
+    pub cover_arts: u64,
+    /// Length of every track together, in seconds.
+    pub total_duration: f64,
+}
+
+/// Count what a library holds.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<LibraryStats, DbErr>` - The counts, zero for an empty library.
+pub async fn get_library_stats(main_db: &DatabaseConnection) -> Result<LibraryStats, DbErr> {
+    let total_duration: Option<f64> = media_files::Entity::find()
+        .select_only()
+        .column_as(media_files::Column::Duration.sum(), "total_duration")
+        .into_tuple()
+        .one(main_db)
+        .await?
+        .flatten();
+
+    Ok(LibraryStats {
+        tracks: media_files::Entity::find().count(main_db).await?,
+        albums: albums::Entity::find().count(main_db).await?,
+        artists: artists::Entity::find().count(main_db).await?,
+        playlists: playlists::Entity::find().count(main_db).await?,
+        analysed: media_analysis::Entity::find().count(main_db).await?,
+        cover_arts: media_cover_art::Entity::find().count(main_db).await?,
+        total_duration: total_duration.unwrap_or(0.),
+    })
+}
+
+/// The name of the last migration applied to the main database.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Option<String>, DbErr>` - The migration, `None` for an empty database.
+pub async fn get_schema_version(main_db: &DatabaseConnection) -> Result<Option<String>, DbErr> {
+    Ok(Migrator::get_applied_migrations(main_db)
+        .await?
+        .last()
+        .map(|x| x.name().to_string()))
+}
@@ -0,0 +1,251 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Duration, Utc};
+use sea_orm::prelude::*;
+use sea_orm::QuerySelect;
+
+use crate::entities::media_file_artists;
+
+use super::auto_dj::{get_tempos, tempo_difference};
+use super::play_history::get_play_counts_since;
+use super::settings::{get_setting, set_setting};
+use super::skips::{down_rank, get_skip_penalties};
+
+/// The setting holding the most tracks of one artist in a generated mix.
+pub const MAX_PER_ARTIST_KEY: &str = "mix.max_per_artist";
+pub const DEFAULT_MAX_PER_ARTIST: usize = 2;
+/// The setting holding how many hours a played track stays out of
+/// generated mixes.
+pub const REPLAY_HOURS_KEY: &str = "mix.replay_hours";
+pub const DEFAULT_REPLAY_HOURS: i64 = 24;
+// Longer windows would reach past the dates chrono can hold
+const MAX_REPLAY_HOURS: i64 = 24 * 365 * 100;
+/// The setting holding how far apart the tempos of neighbouring tracks
+/// should be, as a fraction.
+pub const MIN_TEMPO_VARIANCE_KEY: &str = "mix.min_tempo_variance";
+pub const DEFAULT_MIN_TEMPO_VARIANCE: f64 = 0.04;
+
+/// Constraints on the tracks of radio mode and daily mixes, zero turns one
+/// off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MixPolicy {
+    pub max_per_artist: usize,
+    pub replay_hours: i64,
+    /// Tracks whose tempo is this close to the one before, as a fraction of
+    /// it, are moved further down the mix. Half and double tempos count as
+    /// the same.
+    pub min_tempo_variance: f64,
+}
+
+impl Default for MixPolicy {
+    fn default() -> Self {
+        MixPolicy {
+            max_per_artist: DEFAULT_MAX_PER_ARTIST,
+            replay_hours: DEFAULT_REPLAY_HOURS,
+            min_tempo_variance: DEFAULT_MIN_TEMPO_VARIANCE,
+        }
+    }
+}
+
+/// Get the constraints of generated mixes.
+///
+/// # Arguments
+/// * `db` - The database holding the settings of the user.
+///
+/// # Returns
+/// * `Result<MixPolicy, DbErr>` - The stored policy, or the defaults.
+pub async fn get_mix_policy(db: &DatabaseConnection) -> Result<MixPolicy, DbErr> {
+    let max_per_artist = get_setting(db, MAX_PER_ARTIST_KEY)
+        .await?
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(DEFAULT_MAX_PER_ARTIST);
+    let replay_hours = get_setting(db, REPLAY_HOURS_KEY)
+        .await?
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(DEFAULT_REPLAY_HOURS);
+    let min_tempo_variance = get_setting(db, MIN_TEMPO_VARIANCE_KEY)
+        .await?
+        .and_then(|x| x.parse::<f64>().ok())
+        .filter(|x| x.is_finite())
+        .unwrap_or(DEFAULT_MIN_TEMPO_VARIANCE);
+
+    Ok(MixPolicy {
+        max_per_artist,
+        replay_hours: replay_hours.clamp(0, MAX_REPLAY_HOURS),
+        min_tempo_variance: min_tempo_variance.max(0.),
+    })
+}
+
+/// Store the constraints of generated mixes.
+///
+/// # Arguments
+/// * `db` - The database holding the settings of the user.
+/// * `policy` - The new policy.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the policy was stored.
+pub async fn set_mix_policy(db: &DatabaseConnection, policy: &MixPolicy) -> Result<(), DbErr> {
+    set_setting(db, MAX_PER_ARTIST_KEY, policy.max_per_artist.to_string()).await?;
+    set_setting(db, REPLAY_HOURS_KEY, policy.replay_hours.to_string()).await?;
+    set_setting(
+        db,
+        MIN_TEMPO_VARIANCE_KEY,
+        policy.min_tempo_variance.to_string(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Drop the tracks of a mix that break the policy, keeping the order of
+/// the others.
+///
+/// Files in `keep` always stay, but still count towards their artists.
+/// Files without artists are never limited.
+pub fn diversify(
+    file_ids: Vec<i32>,
+    keep: &[i32],
+    artists: &HashMap<i32, Vec<i32>>,
+    recently_played: &HashSet<i32>,
+    policy: &MixPolicy,
+) -> Vec<i32> {
+    let mut artist_counts: HashMap<i32, usize> = HashMap::new();
+
+    file_ids
+        .into_iter()
+        .filter(|id| {
+            let file_artists = artists.get(id).map(Vec::as_slice).unwrap_or_default();
+            let kept = keep.contains(id);
+
+            if !kept {
+                if recently_played.contains(id) {
+                    return false;
+                }
+                if policy.max_per_artist > 0
+                    && file_artists.iter().any(|x| {
+                        artist_counts.get(x).copied().unwrap_or(0) >= policy.max_per_artist
+                    })
+                {
+                    return false;
+                }
+            }
+
+            for artist in file_artists {
+                *artist_counts.entry(*artist).or_default() += 1;
+            }
+            true
+        })
+        .collect()
+}
+
+/// Move tracks down a mix until their tempo is far enough from the one
+/// before, keeping the order otherwise.
+///
+/// Files in `keep` are never moved, and no file is moved past them. Files
+/// without a tempo go anywhere. When no file fits the next one stays.
+pub fn spread_tempos(
+    file_ids: Vec<i32>,
+    keep: &[i32],
+    tempos: &HashMap<i32, f64>,
+    min_variance: f64,
+) -> Vec<i32> {
+    if min_variance <= 0. {
+        return file_ids;
+    }
+
+    let mut remaining = file_ids;
+    let mut spread = Vec::with_capacity(remaining.len());
+    let mut previous: Option<f64> = None;
+
+    while !remaining.is_empty() {
+        let fits = |id: &i32| match (previous, tempos.get(id)) {
+            (Some(previous), Some(tempo)) => tempo_difference(previous, *tempo) >= min_variance,
+            _ => true,
+        };
+        let index = match remaining
+            .iter()
+            .position(|id| keep.contains(id) || fits(id))
+        {
+            Some(index) if !keep.contains(&remaining[index]) => index,
+            _ => 0,
+        };
+
+        let id = remaining.remove(index);
+        previous = tempos.get(&id).copied();
+        spread.push(id);
+    }
+
+    spread
+}
+
+/// Apply the stored mix policy to recommended files, and move the files
+/// skipped often towards the end.
+///
+/// # Arguments
+/// * `main_db` - The database holding the library.
+/// * `user_db` - The database holding the play history and the policy.
+/// * `user_id` - The ID of the profile whose recent plays are left out.
+/// * `file_ids` - The recommended files, the best first.
+/// * `keep` - Files that stay in the mix whatever the policy, like its seed.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The files left in the mix.
+pub async fn apply_mix_policy(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    file_ids: Vec<i32>,
+    keep: &[i32],
+) -> Result<Vec<i32>, DbErr> {
+    let policy = get_mix_policy(user_db).await?;
+
+    let recently_played: HashSet<i32> = if policy.replay_hours > 0 {
+        let since = Utc::now() - Duration::hours(policy.replay_hours);
+        get_play_counts_since(user_db, user_id, since)
+            .await?
+            .into_keys()
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    let mut artists: HashMap<i32, Vec<i32>> = HashMap::new();
+    if policy.max_per_artist > 0 {
+        let links: Vec<(i32, i32)> = media_file_artists::Entity::find()
+            .select_only()
+            .column(media_file_artists::Column::MediaFileId)
+            .column(media_file_artists::Column::ArtistId)
+            .filter(media_file_artists::Column::MediaFileId.is_in(file_ids.clone()))
+            .into_tuple()
+            .all(main_db)
+            .await?;
+        for (file_id, artist_id) in links {
+            artists.entry(file_id).or_default().push(artist_id);
+        }
+    }
+
+    let file_ids = diversify(file_ids, keep, &artists, &recently_played, &policy);
+
+    // Often skipped files go last, whatever is kept stays where it is
+    let penalties = get_skip_penalties(user_db, &file_ids).await?;
+    let file_ids = down_rank(file_ids, |id| {
+        if keep.contains(id) {
+            0.
+        } else {
+            penalties.get(id).copied().unwrap_or(0.)
+        }
+    });
+
+    // Tempos are spread last, on the final order of the mix
+    if policy.min_tempo_variance > 0. {
+        let tempos = get_tempos(main_db, &file_ids).await?;
+        Ok(spread_tempos(
+            file_ids,
+            keep,
+            &tempos,
+            policy.min_tempo_variance,
+        ))
+    } else {
+        Ok(file_ids)
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Result;
+use sea_orm::DatabaseConnection;
+use tracing::{info, warn};
+
+use metadata::describe::describe_file;
+use metadata::reader::get_metadata;
+use metadata::scanner::AudioScanner;
+
+use crate::actions::file::get_file_ids_by_descriptions;
+use crate::actions::index::index_media_files;
+use crate::actions::metadata::sync_file_descriptions;
+use crate::connection::SearchDbConnection;
+
+/// The setting holding the folder new files are imported from, the watcher
+/// is off while it is unset.
+pub const DROP_FOLDER_KEY: &str = "library.drop_folder";
+/// The setting holding the pattern imported files are renamed by, like
+/// `{album_artist}/{album}/{track} {title}`. Files keep their path inside
+/// the drop folder while it is unset.
+pub const DROP_FOLDER_PATTERN_KEY: &str = "library.drop_folder_pattern";
+
+// Characters that can't be part of a file name on some platform
+const RESERVED_CHARACTERS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Whether a folder can be used as the drop folder of a library.
+///
+/// Files in the library are picked up by scans already, and imports move
+/// files into the library, so neither folder may contain the other.
+pub fn is_valid_drop_folder(lib_path: &Path, drop_path: &Path) -> bool {
+    !drop_path.starts_with(lib_path) && !lib_path.starts_with(drop_path)
+}
+
+/// Remembers the files seen in the drop folder between polls, so files that
+/// are still being copied are left alone.
+#[derive(Debug, Default)]
+pub struct DropFolderWatch {
+    seen: HashMap<PathBuf, (u64, Option<SystemTime>)>,
+}
+
+impl DropFolderWatch {
+    /// List the audio files of the drop folder that kept their size and
+    /// modification time since the previous poll.
+    pub fn settled_files(&mut self, drop_path: &Path) -> Vec<PathBuf> {
+        let mut seen = HashMap::new();
+        let mut settled = Vec::new();
+
+        let mut scanner = AudioScanner::new(&drop_path);
+        while !scanner.has_ended() {
+            for entry in scanner.read_files(64) {
+                let Ok(file_metadata) = entry.metadata() else {
+                    continue;
+                };
+                let state = (file_metadata.len(), file_metadata.modified().ok());
+
+                if self.seen.get(entry.path()) == Some(&state) {
+                    settled.push(entry.path().to_path_buf());
+                }
+                seen.insert(entry.path().to_path_buf(), state);
+            }
+        }
+
+        self.seen = seen;
+        settled
+    }
+}
+
+fn sanitize_component(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|x| {
+            if RESERVED_CHARACTERS.contains(&x) || x.is_control() {
+                '_'
+            } else {
+                x
+            }
+        })
+        .collect();
+
+    // Trailing dots and spaces are dropped by Windows
+    value
+        .trim()
+        .trim_end_matches(['.', ' '])
+        .trim_start_matches('.')
+        .to_string()
+}
+
+fn first_number(value: &str) -> Option<u32> {
+    value.split('/').next()?.trim().parse().ok()
+}
+
+fn placeholder_value(name: &str, tags: &HashMap<&str, &str>, source: &Path) -> String {
+    let tag = |key: &str| tags.get(key).map(|x| x.trim()).filter(|x| !x.is_empty());
+
+    match name {
+        "artist" => tag("artist").unwrap_or("Unknown Artist").to_string(),
+        "album_artist" => tag("album_artist")
+            .or_else(|| tag("artist"))
+            .unwrap_or("Unknown Artist")
+            .to_string(),
+        "album" => tag("album").unwrap_or("Unknown Album").to_string(),
+        "title" => tag("track_title")
+            .map(String::from)
+            .or_else(|| source.file_stem().map(|x| x.to_string_lossy().to_string()))
+            .unwrap_or_default(),
+        "track" => tag("track_number")
+            .and_then(first_number)
+            .map(|x| format!("{:02}", x))
+            .unwrap_or_default(),
+        "disc" => tag("disc_number")
+            .and_then(first_number)
+            .map(|x| x.to_string())
+            .unwrap_or_default(),
+        "year" => tag("original_date")
+            .or_else(|| tag("date"))
+            .and_then(|x| x.get(0..4))
+            .unwrap_or_default()
+            .to_string(),
+        "genre" => tag("genre").unwrap_or("Unknown Genre").to_string(),
+        _ => tag(name).unwrap_or_default().to_string(),
+    }
+}
+
+/// Build the path of an imported file from a pattern.
+///
+/// Placeholders are `{artist}`, `{album_artist}`, `{album}`, `{title}`,
+/// `{track}`, `{disc}`, `{year}` and `{genre}`, any other name is looked up
+/// as a tag. `/` separates directories and the extension of the source is
+/// kept.
+///
+/// # Arguments
+/// * `pattern` - The pattern, like `{album_artist}/{album}/{track} {title}`.
+/// * `tags` - The tags of the file.
+/// * `source` - The path of the file in the drop folder.
+///
+/// # Returns
+/// * `PathBuf` - The path relative to the library root.
+pub fn render_import_path(pattern: &str, tags: &[(String, String)], source: &Path) -> PathBuf {
+    let tags: HashMap<&str, &str> = tags
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+
+    let mut path = PathBuf::new();
+    for component in pattern.split('/') {
+        let mut rendered = String::new();
+        let mut rest = component;
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            rendered.push_str(&rest[..start]);
+            let name = &rest[start + 1..start + end];
+            rendered.push_str(&sanitize_component(&placeholder_value(name, &tags, source)));
+            rest = &rest[start + end + 1..];
+        }
+        rendered.push_str(rest);
+
+        let rendered = sanitize_component(&rendered);
+        if !rendered.is_empty() {
+            path.push(rendered);
+        }
+    }
+
+    if path.as_os_str().is_empty() {
+        path.push(sanitize_component(&placeholder_value(
+            "title", &tags, source,
+        )));
+    }
+
+    match source.extension() {
+        Some(extension) => {
+            let file_name = format!(
+                "{}.{}",
+                path.file_name().unwrap_or_default().to_string_lossy(),
+                extension.to_string_lossy().to_lowercase()
+            );
+            path.set_file_name(file_name);
+            path
+        }
+        None => path,
+    }
+}
+
+// Files already in the library are never replaced
+fn unique_destination(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|x| format!(".{}", x.to_string_lossy()))
+        .unwrap_or_default();
+
+    (2..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|x| !x.exists())
+        .unwrap()
+}
+
+// Renaming fails across file systems, the drop folder is often on another
+// disk than the library
+fn move_file(source: &Path, destination: &Path) -> io::Result<()> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if fs::rename(source, destination).is_ok() {
+        return Ok(());
+    }
+    fs::copy(source, destination)?;
+    fs::remove_file(source)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedFile {
+    /// The path the file had in the drop folder.
+    pub source: PathBuf,
+    /// The path of the file relative to the library root.
+    pub destination: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportFailure {
+    pub source: PathBuf,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub imported: Vec<ImportedFile>,
+    pub failed: Vec<ImportFailure>,
+}
+
+/// Move files from the drop folder into the library, then add them to the
+/// database and the search index.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `search_db` - The search index the files are added to.
+/// * `lib_path` - The root of the library.
+/// * `drop_path` - The drop folder the files are in.
+/// * `files` - The full paths of the files to import.
+/// * `pattern` - Renames the files, see `render_import_path`. `None` keeps
+///   their path inside the drop folder.
+///
+/// # Returns
+/// * `Result<ImportReport>` - The files moved and the ones left in the drop
+///   folder.
+pub async fn import_files(
+    main_db: &DatabaseConnection,
+    search_db: &mut SearchDbConnection,
+    lib_path: &Path,
+    drop_path: &Path,
+    files: &[PathBuf],
+    pattern: Option<&str>,
+) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+
+    for source in files {
+        let relative_path = match pattern {
+            Some(pattern) => {
+                let tags = match get_metadata(&source.to_string_lossy(), None) {
+                    Ok(tags) => tags,
+                    Err(e) => {
+                        warn!("Unable to read the tags of {}: {}", source.display(), e);
+                        Vec::new()
+                    }
+                };
+                render_import_path(pattern, &tags, source)
+            }
+            None => source
+                .strip_prefix(drop_path)
+                .unwrap_or(source)
+                .to_path_buf(),
+        };
+
+        let destination = unique_destination(lib_path.join(relative_path));
+        match move_file(source, &destination) {
+            Ok(_) => {
+                info!("Imported {} as {}", source.display(), destination.display());
+                report.imported.push(ImportedFile {
+                    source: source.clone(),
+                    destination: destination
+                        .strip_prefix(lib_path)
+                        .unwrap_or(&destination)
+                        .to_path_buf(),
+                });
+            }
+            Err(e) => {
+                warn!("Unable to import {}: {}", source.display(), e);
+                report.failed.push(ImportFailure {
+                    source: source.clone(),
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+
+    if report.imported.is_empty() {
+        return Ok(report);
+    }
+
+    let mut descriptions: Vec<_> = report
+        .imported
+        .iter()
+        .map(|x| describe_file(&lib_path.join(&x.destination), lib_path).ok())
+        .collect();
+    sync_file_descriptions(main_db, search_db, &mut descriptions).await?;
+
+    let file_ids = get_file_ids_by_descriptions(main_db, &descriptions).await?;
+    index_media_files(main_db, search_db, file_ids).await?;
+
+    Ok(report)
+}
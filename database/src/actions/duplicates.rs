@@ -0,0 +1,184 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use sea_orm::prelude::*;
+use sea_orm::{QueryOrder, QuerySelect};
+
+use crate::entities::media_files;
+
+/// Extensions of the formats that don't lose anything.
+pub const LOSSLESS_EXTENSIONS: &[&str] = &["flac", "wav", "aiff", "aif", "ape", "wv", "dsf", "dff"];
+
+pub fn is_lossless(extension: &str) -> bool {
+    LOSSLESS_EXTENSIONS
+        .iter()
+        .any(|x| x.eq_ignore_ascii_case(extension.trim_start_matches('.')))
+}
+
+// Lossless first, then the higher sample rate, then the file indexed first
+fn source_rank(file: &media_files::Model) -> (bool, i32, Reverse<i32>) {
+    (
+        is_lossless(&file.extension),
+        file.sample_rate,
+        Reverse(file.id),
+    )
+}
+
+/// Files of the library with the same content, found in several places.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub file_hash: String,
+    /// The copy shown when browsing.
+    pub preferred: i32,
+    /// The other copies, in the order they were indexed.
+    pub others: Vec<i32>,
+}
+
+fn to_groups(files: Vec<media_files::Model>) -> Vec<DuplicateGroup> {
+    let mut by_hash: HashMap<String, Vec<media_files::Model>> = HashMap::new();
+    for file in files {
+        by_hash
+            .entry(file.file_hash.clone())
+            .or_default()
+            .push(file);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(file_hash, files)| {
+            let preferred = files.iter().max_by_key(|x| source_rank(x)).unwrap().id;
+            let mut others: Vec<i32> = files
+                .iter()
+                .map(|x| x.id)
+                .filter(|x| *x != preferred)
+                .collect();
+            others.sort_unstable();
+
+            DuplicateGroup {
+                file_hash,
+                preferred,
+                others,
+            }
+        })
+        .collect();
+    groups.sort_by_key(|x| x.preferred);
+
+    groups
+}
+
+/// Find the files present more than once in the library, e.g. when several
+/// folders of the library hold the same albums.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Vec<DuplicateGroup>, DbErr>` - Every set of copies, missing files left out.
+pub async fn get_duplicate_groups(
+    main_db: &DatabaseConnection,
+) -> Result<Vec<DuplicateGroup>, DbErr> {
+    let hashes: Vec<String> = media_files::Entity::find()
+        .select_only()
+        .column(media_files::Column::FileHash)
+        .filter(media_files::Column::DeletedAt.is_null())
+        .group_by(media_files::Column::FileHash)
+        .having(Expr::expr(media_files::Column::Id.count()).gt(1))
+        .into_tuple()
+        .all(main_db)
+        .await?;
+    if hashes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let files = media_files::Entity::find()
+        .filter(media_files::Column::FileHash.is_in(hashes))
+        .filter(media_files::Column::DeletedAt.is_null())
+        .order_by_asc(media_files::Column::Id)
+        .all(main_db)
+        .await?;
+
+    Ok(to_groups(files))
+}
+
+/// Get the copies hidden when browsing, in favour of their preferred copy.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The IDs of the hidden files.
+pub async fn get_hidden_duplicates(main_db: &DatabaseConnection) -> Result<Vec<i32>, DbErr> {
+    Ok(get_duplicate_groups(main_db)
+        .await?
+        .into_iter()
+        .flat_map(|x| x.others)
+        .collect())
+}
+
+/// Get every copy of a file, so its statistics can be merged.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `file_id` - The ID of the file.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The IDs of all copies including the file itself, empty if it doesn't exist.
+pub async fn get_copies_of_file(
+    main_db: &DatabaseConnection,
+    file_id: i32,
+) -> Result<Vec<i32>, DbErr> {
+    let Some(file) = media_files::Entity::find_by_id(file_id)
+        .one(main_db)
+        .await?
+    else {
+        return Ok(vec![]);
+    };
+
+    let mut copies: Vec<i32> = media_files::Entity::find()
+        .select_only()
+        .column(media_files::Column::Id)
+        .filter(media_files::Column::FileHash.eq(file.file_hash))
+        .filter(
+            media_files::Column::DeletedAt
+                .is_null()
+                .or(media_files::Column::Id.eq(file_id)),
+        )
+        .order_by_asc(media_files::Column::Id)
+        .into_tuple()
+        .all(main_db)
+        .await?;
+    if !copies.contains(&file_id) {
+        copies.insert(0, file_id);
+    }
+
+    Ok(copies)
+}
+
+/// Move the counts of hidden copies onto their preferred copy.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `counts` - A count for every file, e.g. its plays.
+///
+/// # Returns
+/// * `Result<HashMap<i32, usize>, DbErr>` - The counts with every set of copies counted once.
+pub async fn merge_duplicate_counts(
+    main_db: &DatabaseConnection,
+    counts: HashMap<i32, usize>,
+) -> Result<HashMap<i32, usize>, DbErr> {
+    let preferred: HashMap<i32, i32> = get_duplicate_groups(main_db)
+        .await?
+        .into_iter()
+        .flat_map(|x| x.others.into_iter().map(move |other| (other, x.preferred)))
+        .collect();
+
+    let mut merged = HashMap::new();
+    for (file_id, count) in counts {
+        *merged
+            .entry(preferred.get(&file_id).copied().unwrap_or(file_id))
+            .or_default() += count;
+    }
+
+    Ok(merged)
+}
@@ -0,0 +1,273 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use sea_orm::prelude::*;
+use sea_orm::QuerySelect;
+
+use crate::entities::{albums, media_file_albums};
+
+use super::albums::get_albums_by_ids;
+use super::collation::{get_collator, sort_name};
+use super::cover_art::get_magic_cover_art_id;
+use super::library::get_album_cover_ids;
+
+/// How release years are bucketed when browsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Era {
+    Year,
+    Decade,
+}
+
+impl Era {
+    /// The first year of the bucket a year falls in, 0 stands for an
+    /// unknown year.
+    pub fn start_of(&self, year: Option<i32>) -> i32 {
+        match (self, year) {
+            (_, None) | (_, Some(0)) => 0,
+            (Era::Year, Some(year)) => year,
+            (Era::Decade, Some(year)) => year - year.rem_euclid(10),
+        }
+    }
+}
+
+/// Number of albums and tracks released in a year or decade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EraCount {
+    /// First year of the era, 0 for albums of unknown year.
+    pub start: i32,
+    pub albums: usize,
+    pub tracks: usize,
+}
+
+// Year of every album, or of the given ones
+async fn get_album_years(
+    db: &DatabaseConnection,
+    album_ids: Option<Vec<i32>>,
+) -> Result<Vec<(i32, Option<i32>)>, DbErr> {
+    let mut query = albums::Entity::find()
+        .select_only()
+        .column(albums::Column::Id)
+        .column(albums::Column::Year);
+    if let Some(album_ids) = album_ids {
+        query = query.filter(albums::Column::Id.is_in(album_ids));
+    }
+
+    query.into_tuple().all(db).await
+}
+
+fn count_eras(
+    era: Era,
+    album_years: impl IntoIterator<Item = Option<i32>>,
+    track_years: impl IntoIterator<Item = Option<i32>>,
+) -> Vec<EraCount> {
+    let mut counts: BTreeMap<i32, EraCount> = BTreeMap::new();
+    let new_count = |start| EraCount {
+        start,
+        ..Default::default()
+    };
+
+    for year in album_years {
+        let start = era.start_of(year);
+        counts
+            .entry(start)
+            .or_insert_with(|| new_count(start))
+            .albums += 1;
+    }
+    for year in track_years {
+        let start = era.start_of(year);
+        counts
+            .entry(start)
+            .or_insert_with(|| new_count(start))
+            .tracks += 1;
+    }
+
+    counts.into_values().collect()
+}
+
+/// Count the albums and tracks of every year or decade.
+///
+/// The year of a track is the year of its album.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `era` - Whether to count by year or by decade.
+///
+/// # Returns
+/// * `Result<Vec<EraCount>, DbErr>` - The eras with albums, oldest first and
+///   the unknown year before all others.
+pub async fn count_by_era(db: &DatabaseConnection, era: Era) -> Result<Vec<EraCount>, DbErr> {
+    let album_years: HashMap<i32, Option<i32>> =
+        get_album_years(db, None).await?.into_iter().collect();
+    let track_albums: Vec<i32> = media_file_albums::Entity::find()
+        .select_only()
+        .column(media_file_albums::Column::AlbumId)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    Ok(count_eras(
+        era,
+        album_years.values().copied(),
+        track_albums
+            .into_iter()
+            .map(|x| album_years.get(&x).copied().flatten()),
+    ))
+}
+
+/// Get the albums released in some years or decades, with their covers.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `era` - Whether `starts` are years or decades.
+/// * `starts` - The first year of every era, 0 for albums of unknown year.
+///
+/// # Returns
+/// * `Result<Vec<(i32, Vec<(albums::Model, HashSet<i32>)>)>, DbErr>` - The albums of every
+///   requested era in the requested order, sorted like the album list.
+pub async fn get_albums_by_era(
+    db: &DatabaseConnection,
+    era: Era,
+    starts: Vec<i32>,
+) -> Result<Vec<(i32, Vec<(albums::Model, HashSet<i32>)>)>, DbErr> {
+    let album_ids: Vec<i32> = get_album_years(db, None)
+        .await?
+        .into_iter()
+        .filter(|(_, year)| starts.contains(&era.start_of(*year)))
+        .map(|(id, _)| id)
+        .collect();
+
+    let mut items = get_albums_by_ids(db, &album_ids).await?;
+    get_collator(db).await?.sort_by_key(&mut items, |x| {
+        sort_name(&x.name, x.sort_name.as_deref()).to_string()
+    });
+
+    let magic_cover_art_id = get_magic_cover_art_id(db).await.unwrap_or(-1);
+    let mut cover_ids = get_album_cover_ids(db, &items).await?;
+
+    let mut grouped: HashMap<i32, Vec<(albums::Model, HashSet<i32>)>> = HashMap::new();
+    for album in items {
+        let mut covers = cover_ids.remove(&album.id).unwrap_or_default();
+        covers.remove(&magic_cover_art_id);
+        grouped
+            .entry(era.start_of(album.year))
+            .or_default()
+            .push((album, covers));
+    }
+
+    Ok(starts
+        .into_iter()
+        .map(|start| (start, grouped.remove(&start).unwrap_or_default()))
+        .collect())
+}
+
+/// Year of the album of every given track.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the tracks.
+///
+/// # Returns
+/// * `Result<HashMap<i32, Option<i32>>, DbErr>` - The year of every track, `None` if unknown.
+pub async fn get_track_years(
+    db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, Option<i32>>, DbErr> {
+    let links: Vec<(i32, i32)> = media_file_albums::Entity::find()
+        .select_only()
+        .column(media_file_albums::Column::MediaFileId)
+        .column(media_file_albums::Column::AlbumId)
+        .filter(media_file_albums::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .into_tuple()
+        .all(db)
+        .await?;
+    let album_ids: Vec<i32> = links.iter().map(|(_, album_id)| *album_id).collect();
+    let album_years: HashMap<i32, Option<i32>> = get_album_years(db, Some(album_ids))
+        .await?
+        .into_iter()
+        .collect();
+
+    Ok(file_ids
+        .iter()
+        .map(|file_id| {
+            let year = links
+                .iter()
+                .find(|(x, _)| x == file_id)
+                .and_then(|(_, album_id)| album_years.get(album_id).copied().flatten());
+            (*file_id, year)
+        })
+        .collect())
+}
+
+/// Count search results by year or decade, so they can be narrowed by era.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `era` - Whether to count by year or by decade.
+/// * `album_ids` - The albums found.
+/// * `file_ids` - The tracks found.
+///
+/// # Returns
+/// * `Result<Vec<EraCount>, DbErr>` - The eras of the results, oldest first.
+pub async fn get_era_facets(
+    db: &DatabaseConnection,
+    era: Era,
+    album_ids: &[i32],
+    file_ids: &[i32],
+) -> Result<Vec<EraCount>, DbErr> {
+    let album_years: HashMap<i32, Option<i32>> = get_album_years(db, Some(album_ids.to_vec()))
+        .await?
+        .into_iter()
+        .collect();
+    let track_years = get_track_years(db, file_ids).await?;
+
+    Ok(count_eras(
+        era,
+        album_years.into_values(),
+        track_years.into_values(),
+    ))
+}
+
+/// Keep the search results released between two years.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `from` - The first year kept, `None` for no bound.
+/// * `to` - The last year kept, `None` for no bound.
+/// * `album_ids` - The albums found.
+/// * `file_ids` - The tracks found.
+///
+/// # Returns
+/// * `Result<(Vec<i32>, Vec<i32>), DbErr>` - The albums and tracks kept, in their order.
+///   Results of unknown year are dropped once a bound is set.
+pub async fn filter_by_years(
+    db: &DatabaseConnection,
+    from: Option<i32>,
+    to: Option<i32>,
+    album_ids: Vec<i32>,
+    file_ids: Vec<i32>,
+) -> Result<(Vec<i32>, Vec<i32>), DbErr> {
+    if from.is_none() && to.is_none() {
+        return Ok((album_ids, file_ids));
+    }
+
+    let within = |year: Option<i32>| match year {
+        Some(year) => from.is_none_or(|x| year >= x) && to.is_none_or(|x| year <= x),
+        None => false,
+    };
+
+    let album_years: HashMap<i32, Option<i32>> = get_album_years(db, Some(album_ids.clone()))
+        .await?
+        .into_iter()
+        .collect();
+    let track_years = get_track_years(db, &file_ids).await?;
+
+    Ok((
+        album_ids
+            .into_iter()
+            .filter(|x| within(album_years.get(x).copied().flatten()))
+            .collect(),
+        file_ids
+            .into_iter()
+            .filter(|x| within(track_years.get(x).copied().flatten()))
+            .collect(),
+    ))
+}
@@ -0,0 +1,224 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveValue, QuerySelect};
+
+use crate::entities::{media_file_albums, media_files, playback_exclusions};
+
+use super::search::CollectionType;
+
+/// A directory, with everything below it, or an album kept out of generated queues.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ExclusionTarget {
+    /// A directory relative to the library root.
+    Directory(String),
+    Album(i32),
+}
+
+impl ExclusionTarget {
+    fn key(&self) -> (i64, String) {
+        match self {
+            ExclusionTarget::Directory(directory) => {
+                (CollectionType::Directory.into(), directory.clone())
+            }
+            ExclusionTarget::Album(id) => (CollectionType::Album.into(), id.to_string()),
+        }
+    }
+
+    fn from_key(collection_type: i64, target: String) -> Option<Self> {
+        match CollectionType::try_from(collection_type).ok()? {
+            CollectionType::Directory => Some(ExclusionTarget::Directory(target)),
+            CollectionType::Album => target.parse().ok().map(ExclusionTarget::Album),
+            _ => None,
+        }
+    }
+}
+
+/// Where files are kept out of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exclusion {
+    /// Shuffling leaves the files where they are in the queue.
+    Shuffle,
+    /// Radio mode and daily mixes never pick the files.
+    Recommendations,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExclusionFlags {
+    pub never_shuffle: bool,
+    pub exclude_from_recommendations: bool,
+}
+
+impl ExclusionFlags {
+    pub fn contains(&self, exclusion: Exclusion) -> bool {
+        match exclusion {
+            Exclusion::Shuffle => self.never_shuffle,
+            Exclusion::Recommendations => self.exclude_from_recommendations,
+        }
+    }
+}
+
+/// Set the exclusion flags of a directory or an album.
+///
+/// Flags of a directory apply to everything below it, unless a deeper
+/// directory has flags of its own.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `target` - The directory or album.
+/// * `flags` - The new flags, clearing every flag removes the entry.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the flags were stored.
+pub async fn set_exclusion_flags(
+    db: &DatabaseConnection,
+    target: &ExclusionTarget,
+    flags: ExclusionFlags,
+) -> Result<(), DbErr> {
+    let (collection_type, target) = target.key();
+
+    if flags == ExclusionFlags::default() {
+        playback_exclusions::Entity::delete_many()
+            .filter(playback_exclusions::Column::CollectionType.eq(collection_type))
+            .filter(playback_exclusions::Column::Target.eq(target))
+            .exec(db)
+            .await?;
+
+        return Ok(());
+    }
+
+    playback_exclusions::Entity::insert(playback_exclusions::ActiveModel {
+        collection_type: ActiveValue::Set(collection_type),
+        target: ActiveValue::Set(target),
+        never_shuffle: ActiveValue::Set(flags.never_shuffle),
+        exclude_from_recommendations: ActiveValue::Set(flags.exclude_from_recommendations),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::columns([
+            playback_exclusions::Column::CollectionType,
+            playback_exclusions::Column::Target,
+        ])
+        .update_columns([
+            playback_exclusions::Column::NeverShuffle,
+            playback_exclusions::Column::ExcludeFromRecommendations,
+        ])
+        .to_owned(),
+    )
+    .exec(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Get every directory and album with exclusion flags.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Vec<(ExclusionTarget, ExclusionFlags)>, DbErr>` - The flagged directories and albums.
+pub async fn get_exclusion_flags(
+    db: &DatabaseConnection,
+) -> Result<Vec<(ExclusionTarget, ExclusionFlags)>, DbErr> {
+    Ok(playback_exclusions::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|x| {
+            let flags = ExclusionFlags {
+                never_shuffle: x.never_shuffle,
+                exclude_from_recommendations: x.exclude_from_recommendations,
+            };
+            ExclusionTarget::from_key(x.collection_type, x.target).map(|target| (target, flags))
+        })
+        .collect())
+}
+
+// The closest flagged ancestor wins, like content types of directories
+fn resolve_directory_flags<'a>(
+    directory: &str,
+    flags: &'a [(String, ExclusionFlags)],
+) -> Option<&'a ExclusionFlags> {
+    let directory = Path::new(directory);
+
+    flags
+        .iter()
+        .filter(|(flagged, _)| directory.starts_with(flagged))
+        .max_by_key(|(flagged, _)| Path::new(flagged).components().count())
+        .map(|(_, flags)| flags)
+}
+
+/// Find out which of the given files are excluded from shuffling or
+/// recommendations, by their directory or by their album.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files to check.
+/// * `exclusion` - The exclusion to check.
+///
+/// # Returns
+/// * `Result<HashSet<i32>, DbErr>` - The IDs of the excluded files.
+pub async fn get_excluded_file_ids(
+    db: &DatabaseConnection,
+    file_ids: &[i32],
+    exclusion: Exclusion,
+) -> Result<HashSet<i32>, DbErr> {
+    let mut directories = Vec::new();
+    let mut albums = HashSet::new();
+    for (target, flags) in get_exclusion_flags(db).await? {
+        match target {
+            ExclusionTarget::Directory(directory) => directories.push((directory, flags)),
+            ExclusionTarget::Album(id) if flags.contains(exclusion) => {
+                albums.insert(id);
+            }
+            ExclusionTarget::Album(_) => {}
+        }
+    }
+
+    let mut excluded = HashSet::new();
+    if directories.iter().any(|(_, x)| x.contains(exclusion)) {
+        let files: Vec<(i32, String)> = media_files::Entity::find()
+            .select_only()
+            .column(media_files::Column::Id)
+            .column(media_files::Column::Directory)
+            .filter(media_files::Column::Id.is_in(file_ids.to_vec()))
+            .into_tuple()
+            .all(db)
+            .await?;
+
+        excluded.extend(
+            files
+                .into_iter()
+                .filter(|(_, directory)| {
+                    resolve_directory_flags(directory, &directories)
+                        .is_some_and(|x| x.contains(exclusion))
+                })
+                .map(|(id, _)| id),
+        );
+    }
+
+    if !albums.is_empty() {
+        let links: HashMap<i32, i32> = media_file_albums::Entity::find()
+            .select_only()
+            .column(media_file_albums::Column::MediaFileId)
+            .column(media_file_albums::Column::AlbumId)
+            .filter(media_file_albums::Column::MediaFileId.is_in(file_ids.to_vec()))
+            .into_tuple::<(i32, i32)>()
+            .all(db)
+            .await?
+            .into_iter()
+            .collect();
+
+        excluded.extend(
+            links
+                .into_iter()
+                .filter(|(_, album_id)| albums.contains(album_id))
+                .map(|(file_id, _)| file_id),
+        );
+    }
+
+    Ok(excluded)
+}
@@ -0,0 +1,100 @@
+use std::collections::HashSet;
+
+use sea_orm::prelude::*;
+use sea_orm::sea_query::{Func, SelectStatement};
+use sea_orm::{QuerySelect, QueryTrait};
+
+use crate::entities::media_metadata;
+
+use super::settings::{get_setting, set_setting};
+
+/// The field parental advisory tags are stored under.
+pub const ADVISORY_META_KEY: &str = "advisory";
+
+// iTunes writes 1 for explicit, 2 for clean and 4 for explicit in older
+// versions, other taggers write a word
+const EXPLICIT_VALUES: [&str; 5] = ["1", "4", "explicit", "true", "yes"];
+
+fn clean_mode_key(user_id: i32) -> String {
+    format!("users.{}.clean_mode", user_id)
+}
+
+/// Whether an advisory tag marks the track as explicit.
+pub fn is_explicit(value: &str) -> bool {
+    let value = value.trim().to_lowercase();
+    EXPLICIT_VALUES.contains(&value.as_str())
+}
+
+/// Select the IDs of every file tagged as explicit, to filter queries with.
+pub fn explicit_file_ids_query() -> SelectStatement {
+    media_metadata::Entity::find()
+        .select_only()
+        .column(media_metadata::Column::FileId)
+        .filter(media_metadata::Column::MetaKey.eq(ADVISORY_META_KEY))
+        .filter(
+            Expr::expr(Func::lower(Expr::col(media_metadata::Column::MetaValue)))
+                .is_in(EXPLICIT_VALUES),
+        )
+        .into_query()
+}
+
+/// Find out which of the given files are tagged as explicit.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files to check.
+///
+/// # Returns
+/// * `Result<HashSet<i32>, DbErr>` - The IDs of the explicit files.
+pub async fn get_explicit_file_ids(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashSet<i32>, DbErr> {
+    let tags: Vec<(i32, String)> = media_metadata::Entity::find()
+        .select_only()
+        .column(media_metadata::Column::FileId)
+        .column(media_metadata::Column::MetaValue)
+        .filter(media_metadata::Column::MetaKey.eq(ADVISORY_META_KEY))
+        .filter(media_metadata::Column::FileId.is_in(file_ids.to_vec()))
+        .into_tuple()
+        .all(main_db)
+        .await?;
+
+    Ok(tags
+        .into_iter()
+        .filter(|(_, value)| is_explicit(value))
+        .map(|(id, _)| id)
+        .collect())
+}
+
+/// Whether explicit tracks are kept out of browsing, search, shuffle and
+/// radio for a profile.
+///
+/// # Arguments
+/// * `user_db` - The database holding the settings.
+/// * `user_id` - The ID of the profile.
+///
+/// # Returns
+/// * `Result<bool, DbErr>` - Whether clean mode is on, off by default.
+pub async fn get_clean_mode(user_db: &DatabaseConnection, user_id: i32) -> Result<bool, DbErr> {
+    Ok(get_setting(user_db, &clean_mode_key(user_id))
+        .await?
+        .is_some_and(|x| x == "true"))
+}
+
+/// Turn clean mode on or off for a profile.
+///
+/// # Arguments
+/// * `user_db` - The database holding the settings.
+/// * `user_id` - The ID of the profile.
+/// * `enabled` - Whether explicit tracks are kept out.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the setting was stored.
+pub async fn set_clean_mode(
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    enabled: bool,
+) -> Result<(), DbErr> {
+    set_setting(user_db, &clean_mode_key(user_id), enabled.to_string()).await
+}
@@ -1,9 +1,14 @@
+use anyhow::Context;
+use metadata::chapters::{read_chapters, Chapter};
 use metadata::describe::FileDescription;
+use metadata::probe::{probe_file, ProbeError};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use sea_orm::entity::prelude::*;
 use sea_orm::{
-    ColumnTrait, Condition, EntityTrait, FromQueryResult, Order, QueryFilter, QuerySelect,
-    QueryTrait,
+    ColumnTrait, Condition, EntityTrait, FromQueryResult, Order, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait,
 };
+use std::collections::HashMap;
 use std::path::Path;
 
 use migration::{Func, SimpleExpr};
@@ -11,15 +16,142 @@ use migration::{Func, SimpleExpr};
 use crate::entities::{media_file_albums, media_file_artists, media_file_playlists, media_files};
 use crate::{get_by_id, get_by_ids};
 
+use super::duplicates::get_hidden_duplicates;
+use super::explicit::explicit_file_ids_query;
+use super::vibes::{feature_file_ids_query, FeatureRange};
+
 get_by_ids!(get_files_by_ids, media_files);
 get_by_id!(get_file_by_id, media_files);
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileValidation {
+    Ok,
+    NotInLibrary,
+    Missing,
+    Unreadable(String),
+    Undecodable(String),
+}
+
+/// Check that the files of the given tracks exist, are readable and decodable.
+///
+/// Only the start of every file is decoded, so this is cheap enough to run
+/// before large queue operations.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `lib_path` - The root path of the library.
+/// * `file_ids` - The IDs of the files to validate.
+///
+/// # Returns
+/// * `Result<Vec<(i32, FileValidation)>, DbErr>` - The status of every file, in the order of `file_ids`.
+pub async fn validate_files(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    file_ids: &[i32],
+) -> Result<Vec<(i32, FileValidation)>, sea_orm::DbErr> {
+    let files: HashMap<i32, media_files::Model> = get_files_by_ids(main_db, file_ids)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect();
+
+    let results = file_ids
+        .par_iter()
+        .map(|id| {
+            let Some(file) = files.get(id) else {
+                return (*id, FileValidation::NotInLibrary);
+            };
+
+            let file_path = lib_path.join(&file.directory).join(&file.file_name);
+            let status = match probe_file(&file_path) {
+                Ok(_) => FileValidation::Ok,
+                Err(ProbeError::FileNotFound) => FileValidation::Missing,
+                Err(ProbeError::Unreadable(e)) => FileValidation::Unreadable(e.to_string()),
+                Err(ProbeError::Undecodable(e)) => FileValidation::Undecodable(e),
+            };
+
+            (*id, status)
+        })
+        .collect();
+
+    Ok(results)
+}
+
+/// Read the chapters embedded in a file, e.g. the tracks of an album ripped
+/// to a single FLAC with a cuesheet.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `lib_path` - The root path of the library.
+/// * `file_id` - The ID of the file.
+///
+/// # Returns
+/// * `Result<Vec<Chapter>>` - The chapters in playback order, empty for plain files.
+pub async fn get_file_chapters(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    file_id: i32,
+) -> anyhow::Result<Vec<Chapter>> {
+    let file = get_file_by_id(main_db, file_id)
+        .await?
+        .with_context(|| format!("File not found: {}", file_id))?;
+
+    let file_path = lib_path.join(&file.directory).join(&file.file_name);
+    read_chapters(&file_path).with_context(|| format!("Failed to read chapters: {:?}", file_path))
+}
+
+/// The albums and artists a track links to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackLinks {
+    pub album_ids: Vec<i32>,
+    /// The main artist first, then the others in the order of the artist tag.
+    pub artist_ids: Vec<i32>,
+}
+
+/// Get the albums and artists of a track, so it can be navigated to them.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `file_id` - The ID of the file.
+///
+/// # Returns
+/// * `Result<TrackLinks, DbErr>` - The album and artist IDs, empty if the file isn't indexed.
+pub async fn get_track_links(
+    main_db: &DatabaseConnection,
+    file_id: i32,
+) -> Result<TrackLinks, DbErr> {
+    // Links are written in the order of the tags whenever a file is indexed
+    let album_ids = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::MediaFileId.eq(file_id))
+        .order_by_asc(media_file_albums::Column::Id)
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| x.album_id)
+        .collect();
+    let artist_ids = media_file_artists::Entity::find()
+        .filter(media_file_artists::Column::MediaFileId.eq(file_id))
+        .order_by_asc(media_file_artists::Column::Id)
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| x.artist_id)
+        .collect();
+
+    Ok(TrackLinks {
+        album_ids,
+        artist_ids,
+    })
+}
+
 pub async fn get_random_files(
     db: &DatabaseConnection,
     n: usize,
 ) -> Result<Vec<media_files::Model>, sea_orm::DbErr> {
-    let mut query: sea_orm::sea_query::SelectStatement =
-        media_files::Entity::find().as_query().to_owned();
+    let mut query: sea_orm::sea_query::SelectStatement = media_files::Entity::find()
+        .filter(media_files::Column::DeletedAt.is_null())
+        .as_query()
+        .to_owned();
     let select = query
         .order_by_expr(SimpleExpr::FunctionCall(Func::random()), Order::Asc)
         .limit(n as u64);
@@ -101,8 +233,20 @@ pub async fn get_media_files(
     db: &DatabaseConnection,
     cursor: usize,
     page_size: usize,
+    hide_explicit: bool,
 ) -> Result<Vec<media_files::Model>, sea_orm::DbErr> {
-    media_files::Entity::find()
+    // Files present more than once are listed once, by their preferred copy
+    let hidden = get_hidden_duplicates(db).await?;
+
+    let mut query = media_files::Entity::find()
+        .filter(media_files::Column::DeletedAt.is_null())
+        .filter(media_files::Column::Id.is_not_in(hidden));
+    if hide_explicit {
+        query = query
+            .filter(Expr::col(media_files::Column::Id).not_in_subquery(explicit_file_ids_query()));
+    }
+
+    query
         .cursor_by(media_files::Column::Id)
         .after(cursor as i32)
         .first(page_size as u64)
@@ -161,16 +305,34 @@ pub async fn get_duration_by_file_id(
     }
 }
 
+/// The tracks a compound query keeps, every filter left empty keeps all.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaFileFilter {
+    pub artist_ids: Option<Vec<i32>>,
+    pub album_ids: Option<Vec<i32>>,
+    pub playlist_ids: Option<Vec<i32>>,
+    /// Leave out the tracks marked explicit.
+    pub hide_explicit: bool,
+    /// Keep the analysed tracks within all of the ranges.
+    pub feature_ranges: Vec<FeatureRange>,
+}
+
 pub async fn compound_query_media_files(
     db: &DatabaseConnection,
-    artist_ids: Option<Vec<i32>>,
-    album_ids: Option<Vec<i32>>,
-    playlist_ids: Option<Vec<i32>>,
+    filter: MediaFileFilter,
     cursor: usize,
     page_size: usize,
 ) -> Result<Vec<media_files::Model>, sea_orm::DbErr> {
-    // Base query for media_files
-    let mut query = media_files::Entity::find();
+    let MediaFileFilter {
+        artist_ids,
+        album_ids,
+        playlist_ids,
+        hide_explicit,
+        feature_ranges,
+    } = filter;
+
+    // Base query for media_files, files missing from the library are hidden
+    let mut query = media_files::Entity::find().filter(media_files::Column::DeletedAt.is_null());
 
     // Filter by artist_ids if provided
     if let Some(artist_ids) = artist_ids {
@@ -198,7 +360,12 @@ pub async fn compound_query_media_files(
         );
     }
 
-    // Filter by album_ids if provided
+    // Playlists keep the copies they were given, elsewhere duplicates are listed once
+    if playlist_ids.is_none() {
+        query = query.filter(media_files::Column::Id.is_not_in(get_hidden_duplicates(db).await?));
+    }
+
+    // Filter by playlist_ids if provided
     if let Some(playlist_ids) = playlist_ids {
         let playlist_subquery = media_file_playlists::Entity::find()
             .select_only()
@@ -211,6 +378,18 @@ pub async fn compound_query_media_files(
         );
     }
 
+    if hide_explicit {
+        query = query
+            .filter(Expr::col(media_files::Column::Id).not_in_subquery(explicit_file_ids_query()));
+    }
+
+    // Vibe filters keep the analysed files within the ranges
+    if !feature_ranges.is_empty() {
+        query = query.filter(
+            Expr::col(media_files::Column::Id).in_subquery(feature_file_ids_query(&feature_ranges)),
+        );
+    }
+
     // Use cursor pagination
     let mut cursor_by_id = query.cursor_by(media_files::Column::Id);
 
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue;
+
+use crate::entities::{gain_offsets, media_metadata, prelude};
+
+use super::loudness::get_measured_gains_of_files;
+
+/// Parse a ReplayGain tag value such as `-6.48 dB`.
+pub fn parse_replay_gain(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let value = match value.len().checked_sub(2) {
+        Some(end) if value.is_char_boundary(end) && value[end..].eq_ignore_ascii_case("db") => {
+            &value[..end]
+        }
+        _ => value,
+    };
+
+    value
+        .trim()
+        .trim_start_matches('+')
+        .parse::<f32>()
+        .ok()
+        .filter(|x| x.is_finite())
+}
+
+/// Get the ReplayGain track gain of the given files.
+///
+/// Files without a valid tag get the gain measured by the loudness pass.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, f32>, DbErr>` - The gain in dB of every file with a
+///   valid tag or a measured loudness.
+pub async fn get_replay_gains_of_files(
+    db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, f32>, DbErr> {
+    let entries = media_metadata::Entity::find()
+        .filter(
+            media_metadata::Column::FileId
+                .is_in(file_ids.to_vec())
+                .and(media_metadata::Column::MetaKey.eq("replaygain_track_gain")),
+        )
+        .all(db)
+        .await?;
+
+    let mut gains = get_measured_gains_of_files(db, file_ids).await?;
+    // Tags written by a dedicated tool win over our measurement
+    gains.extend(
+        entries
+            .into_iter()
+            .filter_map(|x| parse_replay_gain(&x.meta_value).map(|gain| (x.file_id, gain))),
+    );
+
+    Ok(gains)
+}
+
+/// Get all per-track gain offsets set by the user.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<HashMap<i32, f32>, DbErr>` - The offset in dB of every adjusted file.
+pub async fn get_gain_offsets(db: &DatabaseConnection) -> Result<HashMap<i32, f32>, DbErr> {
+    let items = prelude::GainOffsets::find().all(db).await?;
+
+    Ok(items
+        .into_iter()
+        .map(|x| (x.media_file_id, x.offset as f32))
+        .collect())
+}
+
+/// Set the gain offset of a file, zero removes it.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_id` - The ID of the file.
+/// * `offset` - The offset in dB applied on top of ReplayGain.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the offset was stored.
+pub async fn set_gain_offset(
+    db: &DatabaseConnection,
+    file_id: i32,
+    offset: f32,
+) -> Result<(), DbErr> {
+    if offset == 0.0 {
+        prelude::GainOffsets::delete_many()
+            .filter(gain_offsets::Column::MediaFileId.eq(file_id))
+            .exec(db)
+            .await?;
+        return Ok(());
+    }
+
+    let item = gain_offsets::ActiveModel {
+        media_file_id: ActiveValue::Set(file_id),
+        offset: ActiveValue::Set(offset as f64),
+        ..Default::default()
+    };
+
+    prelude::GainOffsets::insert(item)
+        .on_conflict(
+            OnConflict::column(gain_offsets::Column::MediaFileId)
+                .update_column(gain_offsets::Column::Offset)
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use sea_orm::prelude::*;
+use sea_orm::ActiveValue;
+
+use metadata::gapless::GaplessInfo;
+
+use crate::entities::{albums, media_file_albums, media_files, media_gapless};
+
+// Lossy formats pad the audio on both ends, only the encoder delay and
+// padding stored along tell how much to trim
+const PADDED_EXTENSIONS: [&str; 5] = ["mp3", "m4a", "m4b", "mp4", "aac"];
+
+/// Store the encoder delay and padding of a file, replacing what was stored
+/// before.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_id` - The ID of the file.
+/// * `info` - The trimming read from the file, `None` removes it.
+pub async fn set_gapless_info<C>(
+    db: &C,
+    file_id: i32,
+    info: Option<GaplessInfo>,
+) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    media_gapless::Entity::delete_many()
+        .filter(media_gapless::Column::FileId.eq(file_id))
+        .exec(db)
+        .await?;
+
+    if let Some(info) = info {
+        media_gapless::ActiveModel {
+            file_id: ActiveValue::Set(file_id),
+            leading_frames: ActiveValue::Set(info.leading_frames as i64),
+            valid_frames: ActiveValue::Set(info.valid_frames.map(|x| x as i64)),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Get the encoder delay and padding of the given files.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, GaplessInfo>, DbErr>` - The trimming by file ID,
+///   files that play untrimmed are left out.
+pub async fn get_gapless_info_of_files<C>(
+    db: &C,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, GaplessInfo>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let items = media_gapless::Entity::find()
+        .filter(media_gapless::Column::FileId.is_in(file_ids.to_vec()))
+        .all(db)
+        .await?;
+
+    Ok(items
+        .into_iter()
+        .map(|x| {
+            (
+                x.file_id,
+                GaplessInfo {
+                    leading_frames: x.leading_frames.max(0) as u64,
+                    valid_frames: x.valid_frames.map(|x| x.max(0) as u64),
+                },
+            )
+        })
+        .collect())
+}
+
+/// Why the tracks of an album won't join without a gap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GaplessProblem {
+    /// The tracks don't share a sample rate, so the output is reopened
+    /// between some of them. Holds the rates, lowest first.
+    MixedSampleRates(Vec<i32>),
+    /// Lossy tracks without encoder delay and padding, their silent priming
+    /// and padding is heard between the tracks. Holds the file IDs.
+    MissingEncoderDelay(Vec<i32>),
+}
+
+/// An album whose tracks won't play gapless.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlbumGaplessReport {
+    pub album_id: i32,
+    pub album_name: String,
+    pub problems: Vec<GaplessProblem>,
+}
+
+/// Whether files of an extension need their encoder delay and padding to be
+/// trimmed for gapless playback.
+pub fn is_padded_format(extension: &str) -> bool {
+    PADDED_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+}
+
+/// Check which albums can't play gapless, before a click between two tracks
+/// makes the user wonder why.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `album_ids` - The albums to check, `None` for all of them.
+///
+/// # Returns
+/// * `Result<Vec<AlbumGaplessReport>, DbErr>` - The albums with problems, by
+///   album ID. Missing files are not counted.
+pub async fn check_gapless_albums(
+    db: &DatabaseConnection,
+    album_ids: Option<&[i32]>,
+) -> Result<Vec<AlbumGaplessReport>, DbErr> {
+    let mut query = media_file_albums::Entity::find()
+        .find_also_related(media_files::Entity)
+        .filter(media_files::Column::DeletedAt.is_null());
+    if let Some(album_ids) = album_ids {
+        query = query.filter(media_file_albums::Column::AlbumId.is_in(album_ids.to_vec()));
+    }
+    let tracks = query.all(db).await?;
+
+    let file_ids: Vec<i32> = tracks.iter().map(|(x, _)| x.media_file_id).collect();
+    let trimmed = get_gapless_info_of_files(db, &file_ids).await?;
+
+    let mut sample_rates: BTreeMap<i32, BTreeSet<i32>> = BTreeMap::new();
+    let mut untrimmed: BTreeMap<i32, Vec<i32>> = BTreeMap::new();
+    for (track, file) in tracks {
+        let Some(file) = file else {
+            continue;
+        };
+        sample_rates
+            .entry(track.album_id)
+            .or_default()
+            .insert(file.sample_rate);
+        if is_padded_format(&file.extension) && !trimmed.contains_key(&file.id) {
+            untrimmed.entry(track.album_id).or_default().push(file.id);
+        }
+    }
+
+    let mut problems: BTreeMap<i32, Vec<GaplessProblem>> = BTreeMap::new();
+    for (album_id, rates) in sample_rates {
+        if rates.len() > 1 {
+            problems
+                .entry(album_id)
+                .or_default()
+                .push(GaplessProblem::MixedSampleRates(
+                    rates.into_iter().collect(),
+                ));
+        }
+    }
+    for (album_id, mut file_ids) in untrimmed {
+        file_ids.sort();
+        problems
+            .entry(album_id)
+            .or_default()
+            .push(GaplessProblem::MissingEncoderDelay(file_ids));
+    }
+
+    let names: HashMap<i32, String> = albums::Entity::find()
+        .filter(albums::Column::Id.is_in(problems.keys().copied().collect::<Vec<_>>()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x.name))
+        .collect();
+
+    Ok(problems
+        .into_iter()
+        .map(|(album_id, problems)| AlbumGaplessReport {
+            album_id,
+            album_name: names.get(&album_id).cloned().unwrap_or_default(),
+            problems,
+        })
+        .collect())
+}
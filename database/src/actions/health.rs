@@ -0,0 +1,295 @@
+use arroy::distances::Euclidean;
+use arroy::Reader;
+use sea_orm::prelude::*;
+use sea_orm::sea_query::SelectStatement;
+use sea_orm::{PaginatorTrait, QuerySelect, QueryTrait, Set, TransactionTrait};
+use sea_orm_migration::MigratorTrait;
+use tracing::{info, warn};
+
+use migration::Migrator;
+
+use crate::connection::{MainDbConnection, RecommendationDbConnection, SearchDbConnection};
+use crate::entities::{albums, artists, media_analysis, media_cover_art, media_files};
+
+use super::collation::collection_group;
+use super::index::{rebuild_search_index, IGNORE_ARTICLE_KEY};
+use super::recommendation::sync_recommendation;
+use super::settings::get_setting;
+
+/// Outcome of one check of the library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthStatus {
+    Healthy,
+    /// Something was wrong and was fixed.
+    Repaired(String),
+    /// Something is wrong and couldn't be fixed, e.g. in a read-only library.
+    Problem(String),
+}
+
+fn repair_outcome<T, E: std::fmt::Display>(
+    result: Result<T, E>,
+    repaired: &str,
+    problem: &str,
+) -> HealthStatus {
+    match result {
+        Ok(_) => {
+            info!("{}", repaired);
+            HealthStatus::Repaired(repaired.to_string())
+        }
+        Err(e) => {
+            warn!("{}: {}", problem, e);
+            HealthStatus::Problem(format!("{}: {}", problem, e))
+        }
+    }
+}
+
+/// Check that every migration of the main database was applied, and none
+/// unknown to this version.
+///
+/// # Arguments
+/// * `main_db` - The main database.
+/// * `repair` - Whether missing migrations are applied.
+///
+/// # Returns
+/// * `HealthStatus` - Whether the schema is the one this version expects.
+pub async fn check_schema(main_db: &MainDbConnection, repair: bool) -> HealthStatus {
+    // Migrations applied by a newer version are reported as an error
+    let pending = match Migrator::get_pending_migrations(main_db).await {
+        Ok(pending) => pending,
+        Err(e) => return HealthStatus::Problem(format!("Unknown schema version: {}", e)),
+    };
+    if pending.is_empty() {
+        return HealthStatus::Healthy;
+    }
+
+    let problem = format!("{} migrations are not applied", pending.len());
+    if !repair {
+        return HealthStatus::Problem(problem);
+    }
+    repair_outcome(
+        Migrator::up(main_db, None).await,
+        "Applied the missing migrations",
+        &problem,
+    )
+}
+
+/// Check that the search index is readable and uses the current schema.
+///
+/// # Arguments
+/// * `main_db` - The main database the index is rebuilt from.
+/// * `search_db` - The search index.
+/// * `repair` - Whether the index is rebuilt when it is outdated or damaged.
+///
+/// # Returns
+/// * `HealthStatus` - Whether the index can be searched.
+pub async fn check_search_index(
+    main_db: &MainDbConnection,
+    search_db: &mut SearchDbConnection,
+    repair: bool,
+) -> HealthStatus {
+    let problem = if search_db.outdated {
+        "The search index was recreated with a newer schema".to_string()
+    } else {
+        match search_db.index.validate_checksum() {
+            Ok(damaged) if damaged.is_empty() => return HealthStatus::Healthy,
+            Ok(damaged) => format!("{} search index files are damaged", damaged.len()),
+            Err(e) => format!("The search index is unreadable: {}", e),
+        }
+    };
+    if !repair {
+        return HealthStatus::Problem(problem);
+    }
+
+    let damaged = !search_db.outdated;
+    let result = async {
+        if damaged {
+            search_db
+                .w
+                .delete_all_documents()
+                .map_err(|e| DbErr::Custom(e.to_string()))?;
+        }
+        rebuild_search_index(main_db, search_db, 500).await
+    }
+    .await;
+    if result.is_ok() {
+        search_db.outdated = false;
+    }
+    repair_outcome(result, "Rebuilt the search index", &problem)
+}
+
+/// Check that the recommendation index can be opened and holds every
+/// analysed track.
+///
+/// # Arguments
+/// * `main_db` - The main database holding the analyses.
+/// * `recommend_db` - The recommendation index.
+/// * `repair` - Whether the index is synced again when it doesn't match.
+///
+/// # Returns
+/// * `HealthStatus` - Whether recommendations can be made for the whole library.
+pub async fn check_recommendation_index(
+    main_db: &MainDbConnection,
+    recommend_db: &RecommendationDbConnection,
+    repair: bool,
+) -> HealthStatus {
+    let analysed = match media_analysis::Entity::find().count(main_db).await {
+        Ok(analysed) => analysed,
+        Err(e) => return HealthStatus::Problem(format!("Unable to count analyses: {}", e)),
+    };
+
+    let indexed = recommend_db
+        .env
+        .read_txn()
+        .map_err(|e| e.to_string())
+        .and_then(|rtxn| {
+            Reader::<Euclidean>::open(&rtxn, 0, recommend_db.db)
+                .map(|reader| reader.n_items())
+                .map_err(|e| e.to_string())
+        });
+    let problem = match indexed {
+        Ok(indexed) if indexed == analysed => return HealthStatus::Healthy,
+        Ok(indexed) => format!(
+            "The recommendation index holds {} of {} analysed tracks",
+            indexed, analysed
+        ),
+        // An index is only built once something was analysed
+        Err(_) if analysed == 0 => return HealthStatus::Healthy,
+        Err(e) => format!("The recommendation index is unreadable: {}", e),
+    };
+    if !repair {
+        return HealthStatus::Problem(problem);
+    }
+
+    repair_outcome(
+        sync_recommendation(main_db, recommend_db).await,
+        "Synced the recommendation index",
+        &problem,
+    )
+}
+
+fn stored_cover_art_ids() -> SelectStatement {
+    media_cover_art::Entity::find()
+        .select_only()
+        .column(media_cover_art::Column::Id)
+        .into_query()
+}
+
+/// Check that the covers files point to are stored.
+///
+/// SQLite only enforces foreign keys on connections that turn them on, so
+/// a library edited with the `sqlite3` shell or another tool, or restored
+/// from a partial backup, can point to covers that are gone.
+///
+/// # Arguments
+/// * `main_db` - The main database.
+/// * `repair` - Whether files pointing to missing covers are reset, their
+///   cover is extracted again the next time it is requested.
+///
+/// # Returns
+/// * `HealthStatus` - Whether every cover can be loaded.
+pub async fn check_cover_arts(main_db: &MainDbConnection, repair: bool) -> HealthStatus {
+    let dangling: Result<Vec<i32>, DbErr> = media_files::Entity::find()
+        .select_only()
+        .column(media_files::Column::Id)
+        .filter(media_files::Column::CoverArtId.is_not_null())
+        .filter(media_files::Column::CoverArtId.not_in_subquery(stored_cover_art_ids()))
+        .into_tuple()
+        .all(main_db)
+        .await;
+    let problem = match dangling {
+        Ok(dangling) if dangling.is_empty() => return HealthStatus::Healthy,
+        Ok(dangling) => format!("{} files point to missing covers", dangling.len()),
+        Err(e) => return HealthStatus::Problem(format!("Covers are unreadable: {}", e)),
+    };
+    if !repair {
+        return HealthStatus::Problem(problem);
+    }
+
+    let result = media_files::Entity::update_many()
+        .col_expr(
+            media_files::Column::CoverArtId,
+            Expr::value(Option::<i32>::None),
+        )
+        .filter(media_files::Column::CoverArtId.not_in_subquery(stored_cover_art_ids()))
+        .exec(main_db)
+        .await;
+    repair_outcome(result, "Reset files pointing to missing covers", &problem)
+}
+
+async fn outdated_groups(
+    main_db: &MainDbConnection,
+) -> Result<(Vec<(i32, String)>, Vec<(i32, String)>), DbErr> {
+    let ignore_article = get_setting(main_db, IGNORE_ARTICLE_KEY).await?.as_deref() == Some("true");
+    let regroup = |id: i32, name: &str, sort_name: Option<&str>, group: &str| {
+        let expected = collection_group(name, sort_name, ignore_article);
+        (expected != group).then_some((id, expected))
+    };
+
+    let artists = artists::Entity::find()
+        .all(main_db)
+        .await?
+        .into_iter()
+        .filter_map(|x| regroup(x.id, &x.name, x.sort_name.as_deref(), &x.group))
+        .collect();
+    let albums = albums::Entity::find()
+        .all(main_db)
+        .await?
+        .into_iter()
+        .filter_map(|x| regroup(x.id, &x.name, x.sort_name.as_deref(), &x.group))
+        .collect();
+
+    Ok((artists, albums))
+}
+
+/// Check that artists and albums are listed under the group their name gives
+/// with the current settings and initials.
+///
+/// # Arguments
+/// * `main_db` - The main database.
+/// * `repair` - Whether outdated groups are replaced.
+///
+/// # Returns
+/// * `HealthStatus` - Whether the indexed lists are up to date.
+pub async fn check_collection_groups(main_db: &MainDbConnection, repair: bool) -> HealthStatus {
+    let (artists, albums) = match outdated_groups(main_db).await {
+        Ok(outdated) => outdated,
+        Err(e) => return HealthStatus::Problem(format!("Groups are unreadable: {}", e)),
+    };
+    if artists.is_empty() && albums.is_empty() {
+        return HealthStatus::Healthy;
+    }
+
+    let problem = format!(
+        "{} artists and {} albums are listed under an outdated group",
+        artists.len(),
+        albums.len()
+    );
+    if !repair {
+        return HealthStatus::Problem(problem);
+    }
+
+    let result = async {
+        let txn = main_db.begin().await?;
+        for (id, group) in artists {
+            artists::ActiveModel {
+                id: Set(id),
+                group: Set(group),
+                ..Default::default()
+            }
+            .update(&txn)
+            .await?;
+        }
+        for (id, group) in albums {
+            albums::ActiveModel {
+                id: Set(id),
+                group: Set(group),
+                ..Default::default()
+            }
+            .update(&txn)
+            .await?;
+        }
+        txn.commit().await
+    }
+    .await;
+    repair_outcome(result, "Regrouped artists and albums", &problem)
+}
@@ -0,0 +1,418 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Datelike, Duration, NaiveDate, TimeZone, Utc};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use sea_orm::prelude::*;
+use sea_orm::QuerySelect;
+use tracing::warn;
+
+use crate::connection::{MainDbConnection, RecommendationDbConnection};
+use crate::entities::{albums, artists, media_analysis, media_files, playlists};
+
+use super::audiobooks::get_audiobook_file_ids;
+use super::cover_art::get_magic_cover_art_id;
+use super::diversity::apply_mix_policy;
+use super::duplicates::merge_duplicate_counts;
+use super::exclusions::{get_excluded_file_ids, Exclusion};
+use super::library::{get_album_cover_ids, get_artist_cover_ids, get_playlist_cover_ids};
+use super::metadata::get_metadata_summary_by_file_ids;
+use super::pinned::get_pinned_collections;
+use super::play_history::{get_play_counts_since, get_recently_played};
+use super::recommendation::{get_recommendation_by_file_id, get_recommendation_by_vector};
+use super::search::CollectionType;
+use super::taste::get_taste_profile;
+
+/// Number of recently played tracks on the home screen.
+pub const RECENTLY_PLAYED_SIZE: usize = 20;
+/// Number of daily mixes on the home screen.
+pub const DAILY_MIXES: usize = 3;
+/// Number of tracks in a daily mix, including its seed.
+pub const DAILY_MIX_SIZE: usize = 25;
+/// Days of history used to pick the seeds of the daily mixes.
+pub const DAILY_MIX_HISTORY_DAYS: i64 = 30;
+/// Number of tracks picked for the taste of the user.
+pub const FOR_YOU_SIZE: usize = 20;
+
+/// A pinned collection with what the home screen shows of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinnedItem {
+    pub collection_type: CollectionType,
+    pub id: i32,
+    pub name: String,
+    pub cover_ids: Vec<i32>,
+}
+
+/// Tracks similar to a seed track, the seed comes first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailyMix {
+    pub seed_id: i32,
+    pub file_ids: Vec<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HomePayload {
+    pub pinned: Vec<PinnedItem>,
+    pub recently_played: Vec<i32>,
+    pub daily_mixes: Vec<DailyMix>,
+    /// Tracks closest to the taste profile, empty until something analysed was played.
+    pub for_you: Vec<i32>,
+}
+
+// Files that can show up on the home screen: in the library and not audiobooks
+async fn filter_playable_files(
+    main_db: &MainDbConnection,
+    file_ids: Vec<i32>,
+) -> Result<Vec<i32>, DbErr> {
+    let existing: HashSet<i32> = media_files::Entity::find()
+        .select_only()
+        .column(media_files::Column::Id)
+        .filter(media_files::Column::Id.is_in(file_ids.clone()))
+        .filter(media_files::Column::DeletedAt.is_null())
+        .into_tuple()
+        .all(main_db)
+        .await?
+        .into_iter()
+        .collect();
+    let audiobooks = get_audiobook_file_ids(main_db, &file_ids).await?;
+
+    Ok(file_ids
+        .into_iter()
+        .filter(|x| existing.contains(x) && !audiobooks.contains(x))
+        .collect())
+}
+
+// Files daily mixes can pick: playable and not excluded from recommendations
+async fn filter_mix_files(
+    main_db: &MainDbConnection,
+    file_ids: Vec<i32>,
+) -> Result<Vec<i32>, DbErr> {
+    let file_ids = filter_playable_files(main_db, file_ids).await?;
+    let excluded = get_excluded_file_ids(main_db, &file_ids, Exclusion::Recommendations).await?;
+
+    Ok(file_ids
+        .into_iter()
+        .filter(|x| !excluded.contains(x))
+        .collect())
+}
+
+/// Pick the seed tracks of the daily mixes of a day.
+///
+/// Seeds are drawn from the analysed tracks played most in the last
+/// `DAILY_MIX_HISTORY_DAYS` days. Without any recent history they are drawn
+/// from the tracks closest to the taste profile, or from all analysed tracks.
+/// The same day always gives the same seeds for the same library.
+///
+/// # Arguments
+/// * `main_db` - The database holding the library.
+/// * `user_db` - The database holding the play history.
+/// * `user_id` - The ID of the profile.
+/// * `day` - The day of the mixes.
+/// * `count` - The number of seeds.
+/// * `taste` - Tracks closest to the taste profile, the closest first.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The IDs of the seed files.
+pub async fn get_daily_mix_seeds(
+    main_db: &MainDbConnection,
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    day: NaiveDate,
+    count: usize,
+    taste: &[i32],
+) -> Result<Vec<i32>, DbErr> {
+    let analysed: HashSet<i32> = media_analysis::Entity::find()
+        .select_only()
+        .column(media_analysis::Column::FileId)
+        .into_tuple()
+        .all(main_db)
+        .await?
+        .into_iter()
+        .collect();
+
+    let since = Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).unwrap())
+        - Duration::days(DAILY_MIX_HISTORY_DAYS);
+    let counts = get_play_counts_since(user_db, user_id, since).await?;
+    let mut played: Vec<(i32, usize)> = merge_duplicate_counts(main_db, counts)
+        .await?
+        .into_iter()
+        .filter(|(file_id, _)| analysed.contains(file_id))
+        .collect();
+    played.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let taste: Vec<i32> = taste
+        .iter()
+        .copied()
+        .filter(|x| analysed.contains(x))
+        .take(count * 3)
+        .collect();
+    let candidates: Vec<i32> = if played.is_empty() && !taste.is_empty() {
+        taste
+    } else if played.is_empty() {
+        let mut candidates: Vec<i32> = analysed.into_iter().collect();
+        candidates.sort_unstable();
+        candidates
+    } else {
+        // Leave some room so the seeds change from one day to another
+        played
+            .into_iter()
+            .take(count * 3)
+            .map(|(file_id, _)| file_id)
+            .collect()
+    };
+
+    let mut candidates = filter_mix_files(main_db, candidates).await?;
+    let mut rng = StdRng::seed_from_u64(day.num_days_from_ce() as u64);
+    candidates.shuffle(&mut rng);
+    candidates.truncate(count);
+
+    Ok(candidates)
+}
+
+async fn get_pinned_items(
+    main_db: &MainDbConnection,
+    user_db: &DatabaseConnection,
+    user_id: i32,
+) -> Result<Vec<PinnedItem>, DbErr> {
+    let pinned = get_pinned_collections(user_db, user_id).await?;
+    let ids_of = |collection_type: CollectionType| -> Vec<i32> {
+        pinned
+            .iter()
+            .filter(|(x, _)| *x == collection_type)
+            .map(|(_, id)| *id)
+            .collect()
+    };
+
+    let magic_cover_art_id = get_magic_cover_art_id(main_db).await.unwrap_or(-1);
+    let mut items: HashMap<(CollectionType, i32), (String, Vec<i32>)> = HashMap::new();
+    let mut insert = |collection_type: CollectionType, id: i32, name: String, covers: Vec<i32>| {
+        let mut covers: Vec<i32> = covers
+            .into_iter()
+            .filter(|x| *x != magic_cover_art_id)
+            .collect();
+        covers.sort_unstable();
+        items.insert((collection_type, id), (name, covers));
+    };
+
+    let album_list = albums::Entity::find()
+        .filter(albums::Column::Id.is_in(ids_of(CollectionType::Album)))
+        .all(main_db)
+        .await?;
+    let mut album_covers = get_album_cover_ids(main_db, &album_list).await?;
+    for album in album_list {
+        let covers = album_covers.remove(&album.id).unwrap_or_default();
+        insert(
+            CollectionType::Album,
+            album.id,
+            album.name,
+            covers.into_iter().collect(),
+        );
+    }
+
+    let artist_list = artists::Entity::find()
+        .filter(artists::Column::Id.is_in(ids_of(CollectionType::Artist)))
+        .all(main_db)
+        .await?;
+    let mut artist_covers = get_artist_cover_ids(main_db, &artist_list).await?;
+    for artist in artist_list {
+        let covers = artist_covers.remove(&artist.id).unwrap_or_default();
+        insert(
+            CollectionType::Artist,
+            artist.id,
+            artist.name,
+            covers.into_iter().collect(),
+        );
+    }
+
+    let playlist_list = playlists::Entity::find()
+        .filter(playlists::Column::Id.is_in(ids_of(CollectionType::Playlist)))
+        .all(main_db)
+        .await?;
+    let mut playlist_covers = get_playlist_cover_ids(main_db, &playlist_list).await?;
+    for playlist in playlist_list {
+        // The mosaic stands for the whole playlist when there is one
+        let covers = match playlist.cover_art_id {
+            Some(x) => vec![x],
+            None => playlist_covers
+                .remove(&playlist.id)
+                .unwrap_or_default()
+                .into_iter()
+                .collect(),
+        };
+        insert(CollectionType::Playlist, playlist.id, playlist.name, covers);
+    }
+
+    let track_ids = filter_playable_files(main_db, ids_of(CollectionType::Track)).await?;
+    let track_covers: HashMap<i32, Option<i32>> = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_in(track_ids.clone()))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x.cover_art_id))
+        .collect();
+    for track in get_metadata_summary_by_file_ids(main_db, track_ids).await? {
+        let name = if track.title.is_empty() {
+            track.file_name
+        } else {
+            track.title
+        };
+        let covers = track_covers
+            .get(&track.id)
+            .copied()
+            .flatten()
+            .into_iter()
+            .collect();
+        insert(CollectionType::Track, track.id, name, covers);
+    }
+
+    // Collections removed from the library since they were pinned are left out
+    Ok(pinned
+        .into_iter()
+        .filter_map(|(collection_type, id)| {
+            items
+                .remove(&(collection_type.clone(), id))
+                .map(|(name, cover_ids)| PinnedItem {
+                    collection_type,
+                    id,
+                    name,
+                    cover_ids,
+                })
+        })
+        .collect())
+}
+
+// Files closest to the taste profile, as found in the recommendation index
+async fn get_taste_files(
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    recommend_db: &RecommendationDbConnection,
+    count: usize,
+) -> Result<Vec<i32>, DbErr> {
+    let Some(profile) = get_taste_profile(user_db, user_id).await? else {
+        return Ok(Vec::new());
+    };
+
+    match get_recommendation_by_vector(recommend_db, &profile.vector, count) {
+        Ok(x) => Ok(x.into_iter().map(|(id, _)| id as i32).collect()),
+        Err(e) => {
+            warn!("No tracks for the taste profile: {}", e);
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Pick tracks for the taste of the user, leaving out the ones played lately.
+///
+/// # Arguments
+/// * `main_db` - The database holding the library.
+/// * `user_db` - The database holding the taste profile and the play history.
+/// * `user_id` - The ID of the profile.
+/// * `recommend_db` - The recommendation database.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The tracks, the closest to the profile first.
+pub async fn get_for_you(
+    main_db: &MainDbConnection,
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    recommend_db: &RecommendationDbConnection,
+) -> Result<Vec<i32>, DbErr> {
+    // Over-fetch, recent plays and excluded files are dropped
+    let file_ids = get_taste_files(user_db, user_id, recommend_db, FOR_YOU_SIZE * 2).await?;
+    let file_ids = filter_mix_files(main_db, file_ids).await?;
+    let mut file_ids = apply_mix_policy(main_db, user_db, user_id, file_ids, &[]).await?;
+    file_ids.truncate(FOR_YOU_SIZE);
+
+    Ok(file_ids)
+}
+
+/// Build the daily mixes of a day.
+///
+/// # Arguments
+/// * `main_db` - The database holding the library.
+/// * `user_db` - The database holding the play history.
+/// * `user_id` - The ID of the profile.
+/// * `recommend_db` - The recommendation database.
+/// * `day` - The day of the mixes.
+///
+/// # Returns
+/// * `Result<Vec<DailyMix>, DbErr>` - The mixes, seeds without recommendations are left out.
+pub async fn get_daily_mixes(
+    main_db: &MainDbConnection,
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    recommend_db: &RecommendationDbConnection,
+    day: NaiveDate,
+) -> Result<Vec<DailyMix>, DbErr> {
+    let taste = get_taste_files(user_db, user_id, recommend_db, DAILY_MIXES * 3).await?;
+    let seeds = get_daily_mix_seeds(main_db, user_db, user_id, day, DAILY_MIXES, &taste).await?;
+
+    let mut mixes = Vec::new();
+    for seed_id in seeds {
+        // Over-fetch, the mix policy drops some of them
+        let recommendations =
+            match get_recommendation_by_file_id(recommend_db, seed_id, DAILY_MIX_SIZE * 2) {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("No daily mix for file {}: {}", seed_id, e);
+                    continue;
+                }
+            };
+
+        let file_ids = std::iter::once(seed_id)
+            .chain(
+                recommendations
+                    .into_iter()
+                    .map(|(id, _)| id as i32)
+                    .filter(|x| *x != seed_id),
+            )
+            .collect();
+        let file_ids = filter_mix_files(main_db, file_ids).await?;
+        let mut file_ids =
+            apply_mix_policy(main_db, user_db, user_id, file_ids, &[seed_id]).await?;
+        file_ids.truncate(DAILY_MIX_SIZE);
+
+        mixes.push(DailyMix { seed_id, file_ids });
+    }
+
+    Ok(mixes)
+}
+
+/// Collect everything the start screen shows in one go: the pinned
+/// collections, the tracks played last, the daily mixes and the tracks
+/// picked for the taste of the user.
+///
+/// # Arguments
+/// * `main_db` - The database holding the library.
+/// * `user_db` - The database holding the pins and the play history.
+/// * `user_id` - The ID of the profile.
+/// * `recommend_db` - The recommendation database.
+/// * `day` - The day of the daily mixes.
+///
+/// # Returns
+/// * `Result<HomePayload, DbErr>` - The content of the start screen.
+pub async fn get_home_payload(
+    main_db: &MainDbConnection,
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    recommend_db: &RecommendationDbConnection,
+    day: NaiveDate,
+) -> Result<HomePayload, DbErr> {
+    let pinned = get_pinned_items(main_db, user_db, user_id).await?;
+
+    // Over-fetch a little, some of the files may be gone since
+    let recently_played = get_recently_played(user_db, user_id, RECENTLY_PLAYED_SIZE * 2).await?;
+    let mut recently_played = filter_playable_files(main_db, recently_played).await?;
+    recently_played.truncate(RECENTLY_PLAYED_SIZE);
+
+    let daily_mixes = get_daily_mixes(main_db, user_db, user_id, recommend_db, day).await?;
+    let for_you = get_for_you(main_db, user_db, user_id, recommend_db).await?;
+
+    Ok(HomePayload {
+        pinned,
+        recently_played,
+        daily_mixes,
+        for_you,
+    })
+}
@@ -1,13 +1,43 @@
-use log::{error, info};
-use sea_orm::{prelude::*, ActiveValue};
-use sea_orm::{DatabaseConnection, Set, TransactionTrait};
+use std::collections::HashMap;
 
-use crate::actions::search::{add_term, CollectionType};
-use crate::actions::utils::generate_group_name;
+use sea_orm::{prelude::*, ActiveValue};
+use sea_orm::{Condition, DatabaseConnection, QueryOrder, Set, TransactionTrait};
+use tracing::{error, info};
+
+use crate::actions::collation::collection_group;
+use crate::actions::lyrics::get_lyrics;
+use crate::actions::merge::get_merge_aliases;
+use crate::actions::search::{
+    add_album_term, add_term, add_track_term_with_lyrics, remove_term, CollectionType,
+};
+use crate::actions::settings::get_setting;
+use crate::actions::tag_mappings::get_searchable_tags;
 use crate::connection::SearchDbConnection;
-use crate::entities::{albums, artists, media_file_albums, media_file_artists, media_files};
+use crate::entities::{
+    albums, artists, media_file_albums, media_file_artists, media_files, playlists,
+};
+
+use metadata::normalize::canonical_name;
+
+use super::metadata::{
+    get_metadata_summary_by_file_ids, get_metadata_summary_by_files, MetadataSummary,
+};
+
+/// Whether a leading "The" is ignored when grouping artists and albums,
+/// existing groups follow a change the next time the library is opened.
+pub const IGNORE_ARTICLE_KEY: &str = "library.ignore_article";
+
+fn non_empty(x: &str) -> Option<&str> {
+    Some(x.trim()).filter(|x| !x.is_empty())
+}
 
-use super::metadata::get_metadata_summary_by_file_ids;
+fn album_artist(summary: &MetadataSummary) -> &str {
+    if summary.album_artist.is_empty() {
+        &summary.artist
+    } else {
+        &summary.album_artist
+    }
+}
 
 pub async fn index_media_files(
     main_db: &DatabaseConnection,
@@ -17,28 +47,105 @@ pub async fn index_media_files(
     info!("Indexing media: {:?}", file_ids);
     // Fetch metadata summary for provided file_ids
     let metadata_summaries = get_metadata_summary_by_file_ids(main_db, file_ids.clone()).await?;
+    let searchable_tags = get_searchable_tags(main_db, file_ids.clone()).await?;
+    let lyrics = get_lyrics(main_db, file_ids.clone()).await?;
+    let ignore_article = get_setting(main_db, IGNORE_ARTICLE_KEY).await?.as_deref() == Some("true");
+    let canonical = |name: &str| canonical_name(name, ignore_article);
+    let group_of =
+        |name: &str, sort_name: Option<&str>| collection_group(name, sort_name, ignore_article);
+
+    // Merged names are matched the way names are grouped
+    let artist_aliases: HashMap<String, String> =
+        get_merge_aliases(main_db, CollectionType::Artist)
+            .await?
+            .into_iter()
+            .map(|(source, target)| (canonical(&source), target))
+            .collect();
+    let album_aliases: HashMap<String, String> = get_merge_aliases(main_db, CollectionType::Album)
+        .await?
+        .into_iter()
+        .map(|(source, target)| (canonical(&source), target))
+        .collect();
     let mut modified = false;
 
+    // Pick up documents written by the last commit before comparing them
+    search_db.r.reload().ok();
+
     let txn = main_db.begin().await?;
 
+    // Artists and albums the files belonged to before, tag edits may leave
+    // some of them without tracks
+    let previous_artist_ids: Vec<i32> = media_file_artists::Entity::find()
+        .filter(media_file_artists::Column::MediaFileId.is_in(file_ids.clone()))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|x| x.artist_id)
+        .collect();
+    let previous_album_ids: Vec<i32> = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::MediaFileId.is_in(file_ids.clone()))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|x| x.album_id)
+        .collect();
+
     for summary in metadata_summaries {
         // Process artists
         let artists = metadata::artist::split_artists(&summary.artist);
         let mut artist_ids = Vec::new();
 
+        // The sort tag is for the whole artist field, it can't be split
+        let sort_artist = match artists.len() {
+            1 => non_empty(&summary.sort_artist),
+            _ => None,
+        };
+
         for artist_name in artists {
+            // Names merged into another artist are indexed under it
+            let artist_name = artist_aliases
+                .get(&canonical(&artist_name))
+                .cloned()
+                .unwrap_or(artist_name);
+            let canonical_artist = canonical(&artist_name);
             let artist = artists::ActiveModel {
                 name: Set(artist_name.clone()),
-                group: Set(generate_group_name(&artist_name)),
+                group: Set(group_of(&artist_name, sort_artist)),
+                sort_name: Set(sort_artist.map(str::to_string)),
+                canonical_name: Set(Some(canonical_artist.clone())),
                 ..Default::default()
             };
 
+            // Rows indexed before canonical names existed are matched by name
             let existing_artist = artists::Entity::find()
-                .filter(artists::Column::Name.eq(artist_name.clone()))
+                .filter(
+                    Condition::any()
+                        .add(artists::Column::CanonicalName.eq(canonical_artist.clone()))
+                        .add(artists::Column::Name.eq(artist_name.clone())),
+                )
+                .order_by_asc(artists::Column::Id)
                 .one(&txn)
                 .await?;
 
             let artist_id = if let Some(existing) = existing_artist {
+                let sort_changed =
+                    sort_artist.is_some() && existing.sort_name.as_deref() != sort_artist;
+                let canonical_changed =
+                    existing.canonical_name.as_deref() != Some(canonical_artist.as_str());
+
+                if sort_changed || canonical_changed {
+                    let mut active: artists::ActiveModel = existing.clone().into();
+                    if sort_changed {
+                        active.sort_name = artist.sort_name;
+                    }
+                    // The display name stays the one seen first
+                    active.group = Set(group_of(
+                        &existing.name,
+                        sort_artist.or(existing.sort_name.as_deref()),
+                    ));
+                    active.canonical_name = artist.canonical_name;
+                    active.update(&txn).await?;
+                }
                 existing.id
             } else {
                 modified = true;
@@ -52,7 +159,9 @@ pub async fn index_media_files(
                 inserted_artist.last_insert_id
             };
 
-            artist_ids.push(artist_id);
+            if !artist_ids.contains(&artist_id) {
+                artist_ids.push(artist_id);
+            }
         }
 
         // Clean up old artist relationships
@@ -73,29 +182,85 @@ pub async fn index_media_files(
                 .await?;
         }
 
+        // Keep the artist and album names of the track searchable
+        if add_track_term_with_lyrics(
+            search_db,
+            summary.id,
+            &summary.title,
+            &summary.artist,
+            &summary.album,
+            searchable_tags
+                .get(&summary.id)
+                .map(|x| x.as_str())
+                .unwrap_or_default(),
+            lyrics
+                .get(&summary.id)
+                .map(|x| x.as_str())
+                .unwrap_or_default(),
+        ) {
+            modified = true;
+        }
+
         // Process album
-        let album_name = summary.album;
+        let album_name = album_aliases
+            .get(&canonical(&summary.album))
+            .cloned()
+            .unwrap_or_else(|| summary.album.clone());
+        let canonical_album = canonical(&album_name);
+        let sort_album = non_empty(&summary.sort_album);
         let album = albums::ActiveModel {
             name: Set(album_name.clone()),
-            group: Set(generate_group_name(&album_name)),
+            group: Set(group_of(&album_name, sort_album)),
+            sort_name: Set(sort_album.map(str::to_string)),
+            year: Set(summary.year),
+            canonical_name: Set(Some(canonical_album.clone())),
             ..Default::default()
         };
 
         let existing_album = albums::Entity::find()
-            .filter(albums::Column::Name.eq(album_name.clone()))
+            .filter(
+                Condition::any()
+                    .add(albums::Column::CanonicalName.eq(canonical_album.clone()))
+                    .add(albums::Column::Name.eq(album_name.clone())),
+            )
+            .order_by_asc(albums::Column::Id)
             .one(&txn)
             .await?;
 
         let album_id = if let Some(existing) = existing_album {
+            // Tracks of an album may disagree, the earliest year wins
+            let year = match (existing.year, summary.year) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            let sort_changed = sort_album.is_some() && existing.sort_name.as_deref() != sort_album;
+            let canonical_changed =
+                existing.canonical_name.as_deref() != Some(canonical_album.as_str());
+
+            if sort_changed || canonical_changed || year != existing.year {
+                let mut active: albums::ActiveModel = existing.clone().into();
+                if sort_changed {
+                    active.sort_name = album.sort_name;
+                }
+                if sort_changed || canonical_changed {
+                    active.group = Set(group_of(
+                        &existing.name,
+                        sort_album.or(existing.sort_name.as_deref()),
+                    ));
+                }
+                active.canonical_name = album.canonical_name;
+                active.year = Set(year);
+                active.update(&txn).await?;
+            }
             existing.id
         } else {
             modified = true;
             let inserted_album = albums::Entity::insert(album).exec(&txn).await?;
-            add_term(
+            add_album_term(
                 search_db,
-                CollectionType::Album,
                 inserted_album.last_insert_id,
-                &album_name.clone(),
+                &album_name,
+                album_artist(&summary),
             );
             inserted_album.last_insert_id
         };
@@ -119,9 +284,44 @@ pub async fn index_media_files(
             .await?;
     }
 
+    // Drop the artists and albums whose last track was moved elsewhere
+    let used_artist_ids: Vec<i32> = media_file_artists::Entity::find()
+        .filter(media_file_artists::Column::ArtistId.is_in(previous_artist_ids.clone()))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|x| x.artist_id)
+        .collect();
+    for artist_id in previous_artist_ids {
+        if !used_artist_ids.contains(&artist_id) {
+            let result = artists::Entity::delete_by_id(artist_id).exec(&txn).await?;
+            if result.rows_affected > 0 {
+                modified = true;
+                remove_term(search_db, CollectionType::Artist, artist_id);
+            }
+        }
+    }
+
+    let used_album_ids: Vec<i32> = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::AlbumId.is_in(previous_album_ids.clone()))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|x| x.album_id)
+        .collect();
+    for album_id in previous_album_ids {
+        if !used_album_ids.contains(&album_id) {
+            let result = albums::Entity::delete_by_id(album_id).exec(&txn).await?;
+            if result.rows_affected > 0 {
+                modified = true;
+                remove_term(search_db, CollectionType::Album, album_id);
+            }
+        }
+    }
+
     txn.commit().await?;
     if modified {
-        search_db.w.commit().unwrap();
+        search_db.commit().unwrap();
     }
 
     Ok(())
@@ -205,3 +405,93 @@ pub async fn index_audio_library(
     info!("Audio indexing analysis completed.");
     Ok(())
 }
+
+/// Write every artist, album, playlist and track into the search index again.
+///
+/// Used when the index was recreated because its schema changed.
+///
+/// # Arguments
+/// * `main_db` - A reference to the main database connection.
+/// * `search_db` - A mutable reference to the search database connection.
+/// * `batch_size` - The number of tracks read from the database at once.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok once the index is committed.
+pub async fn rebuild_search_index(
+    main_db: &DatabaseConnection,
+    search_db: &mut SearchDbConnection,
+    batch_size: u64,
+) -> Result<(), sea_orm::DbErr> {
+    info!("Rebuilding the search index");
+
+    for artist in artists::Entity::find().all(main_db).await? {
+        add_term(search_db, CollectionType::Artist, artist.id, &artist.name);
+    }
+
+    for playlist in playlists::Entity::find().all(main_db).await? {
+        add_term(
+            search_db,
+            CollectionType::Playlist,
+            playlist.id,
+            &playlist.name,
+        );
+    }
+
+    // Albums are indexed with the artist of one of their tracks
+    let mut album_files: HashMap<i32, i32> = HashMap::new();
+    for item in media_file_albums::Entity::find().all(main_db).await? {
+        album_files
+            .entry(item.album_id)
+            .or_insert(item.media_file_id);
+    }
+    let summaries: HashMap<i32, MetadataSummary> =
+        get_metadata_summary_by_file_ids(main_db, album_files.values().copied().collect())
+            .await?
+            .into_iter()
+            .map(|x| (x.id, x))
+            .collect();
+
+    for album in albums::Entity::find().all(main_db).await? {
+        let artist = album_files
+            .get(&album.id)
+            .and_then(|file_id| summaries.get(file_id))
+            .map(album_artist)
+            .unwrap_or_default();
+        add_album_term(search_db, album.id, &album.name, artist);
+    }
+
+    let mut cursor = media_files::Entity::find().cursor_by(media_files::Column::Id);
+    loop {
+        let files = cursor.first(batch_size).all(main_db).await?;
+        let Some(last_file) = files.last() else {
+            break;
+        };
+        cursor.after(last_file.id);
+
+        let file_ids: Vec<i32> = files.iter().map(|x| x.id).collect();
+        let searchable_tags = get_searchable_tags(main_db, file_ids.clone()).await?;
+        let lyrics = get_lyrics(main_db, file_ids).await?;
+        for summary in get_metadata_summary_by_files(main_db, files).await? {
+            add_track_term_with_lyrics(
+                search_db,
+                summary.id,
+                &summary.title,
+                &summary.artist,
+                &summary.album,
+                searchable_tags
+                    .get(&summary.id)
+                    .map(|x| x.as_str())
+                    .unwrap_or_default(),
+                lyrics
+                    .get(&summary.id)
+                    .map(|x| x.as_str())
+                    .unwrap_or_default(),
+            );
+        }
+    }
+
+    search_db.commit().unwrap();
+    info!("Search index rebuilt");
+
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use sea_orm::prelude::*;
+
+use super::playlists::replace_playlist_items;
+use super::ratings::{restore_ratings, MAX_RATING};
+
+/// Number of operations the hub can undo.
+pub const DEFAULT_JOURNAL_SIZE: usize = 50;
+
+/// A change that can be undone, with the state before and after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// The items of a playlist changed, in order.
+    PlaylistItems {
+        playlist_id: i32,
+        before: Vec<i32>,
+        after: Vec<i32>,
+    },
+    /// Files were rated by a profile, files missing from a map are unrated.
+    Ratings {
+        user_id: i32,
+        file_ids: Vec<i32>,
+        before: HashMap<i32, i32>,
+        after: HashMap<i32, i32>,
+    },
+    /// The play queue changed. The journal only keeps track of it, the queue
+    /// belongs to the player and is restored by the caller.
+    Queue { before: Vec<i32>, after: Vec<i32> },
+}
+
+impl Operation {
+    /// Describe files rated with `rating` the way `set_ratings` stores it.
+    pub fn rating_change(
+        user_id: i32,
+        file_ids: Vec<i32>,
+        before: HashMap<i32, i32>,
+        rating: i32,
+    ) -> Self {
+        let rating = rating.clamp(0, MAX_RATING);
+        let after = if rating == 0 {
+            HashMap::new()
+        } else {
+            file_ids.iter().map(|x| (*x, rating)).collect()
+        };
+
+        Operation::Ratings {
+            user_id,
+            file_ids,
+            before,
+            after,
+        }
+    }
+
+    /// The operation reverting this one.
+    pub fn inverse(self) -> Self {
+        match self {
+            Operation::PlaylistItems {
+                playlist_id,
+                before,
+                after,
+            } => Operation::PlaylistItems {
+                playlist_id,
+                before: after,
+                after: before,
+            },
+            Operation::Ratings {
+                user_id,
+                file_ids,
+                before,
+                after,
+            } => Operation::Ratings {
+                user_id,
+                file_ids,
+                before: after,
+                after: before,
+            },
+            Operation::Queue { before, after } => Operation::Queue {
+                before: after,
+                after: before,
+            },
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Operation::PlaylistItems { .. } => "playlist_items",
+            Operation::Ratings { .. } => "ratings",
+            Operation::Queue { .. } => "queue",
+        }
+    }
+
+    fn is_noop(&self) -> bool {
+        match self {
+            Operation::PlaylistItems { before, after, .. } => before == after,
+            Operation::Ratings { before, after, .. } => before == after,
+            Operation::Queue { before, after } => before == after,
+        }
+    }
+
+    // Bring the library to the state after the operation
+    async fn apply(
+        &self,
+        main_db: &DatabaseConnection,
+        user_db: &DatabaseConnection,
+    ) -> Result<(), DbErr> {
+        match self {
+            Operation::PlaylistItems {
+                playlist_id, after, ..
+            } => replace_playlist_items(main_db, *playlist_id, after).await,
+            Operation::Ratings {
+                user_id,
+                file_ids,
+                after,
+                ..
+            } => restore_ratings(user_db, *user_id, file_ids, after).await,
+            Operation::Queue { .. } => Ok(()),
+        }
+    }
+}
+
+/// Keeps the latest destructive operations so they can be undone and redone.
+///
+/// Only the last `capacity` operations are kept, recording a new operation
+/// forgets everything that was undone before.
+pub struct OperationJournal {
+    capacity: usize,
+    undo: Mutex<VecDeque<Operation>>,
+    redo: Mutex<Vec<Operation>>,
+}
+
+impl Default for OperationJournal {
+    fn default() -> Self {
+        Self::new(DEFAULT_JOURNAL_SIZE)
+    }
+}
+
+impl OperationJournal {
+    pub fn new(capacity: usize) -> Self {
+        OperationJournal {
+            capacity,
+            undo: Mutex::new(VecDeque::new()),
+            redo: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Record an operation that was just done, operations changing nothing
+    /// are ignored.
+    pub fn record(&self, operation: Operation) {
+        if operation.is_noop() || self.capacity == 0 {
+            return;
+        }
+
+        let mut undo = self.undo.lock().unwrap();
+        undo.push_back(operation);
+        while undo.len() > self.capacity {
+            undo.pop_front();
+        }
+        self.redo.lock().unwrap().clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.lock().unwrap().is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.lock().unwrap().is_empty()
+    }
+
+    /// Revert the last recorded operation.
+    ///
+    /// # Arguments
+    /// * `main_db` - The database holding the playlists.
+    /// * `user_db` - The database holding the ratings.
+    ///
+    /// # Returns
+    /// * `Result<Option<Operation>, DbErr>` - The operation that was applied
+    ///   to revert it, `None` if there is nothing to undo. Queue operations
+    ///   must be applied by the caller. The operation stays in the journal if
+    ///   it couldn't be reverted.
+    pub async fn undo_last_operation(
+        &self,
+        main_db: &DatabaseConnection,
+        user_db: &DatabaseConnection,
+    ) -> Result<Option<Operation>, DbErr> {
+        let Some(operation) = self.undo.lock().unwrap().pop_back() else {
+            return Ok(None);
+        };
+
+        let inverse = operation.clone().inverse();
+        if let Err(e) = inverse.apply(main_db, user_db).await {
+            self.undo.lock().unwrap().push_back(operation);
+            return Err(e);
+        }
+
+        self.redo.lock().unwrap().push(operation);
+        Ok(Some(inverse))
+    }
+
+    /// Apply the last undone operation again.
+    ///
+    /// # Arguments
+    /// * `main_db` - The database holding the playlists.
+    /// * `user_db` - The database holding the ratings.
+    ///
+    /// # Returns
+    /// * `Result<Option<Operation>, DbErr>` - The operation that was applied,
+    ///   `None` if there is nothing to redo. Queue operations must be applied
+    ///   by the caller.
+    pub async fn redo_last_operation(
+        &self,
+        main_db: &DatabaseConnection,
+        user_db: &DatabaseConnection,
+    ) -> Result<Option<Operation>, DbErr> {
+        let Some(operation) = self.redo.lock().unwrap().pop() else {
+            return Ok(None);
+        };
+
+        if let Err(e) = operation.apply(main_db, user_db).await {
+            self.redo.lock().unwrap().push(operation);
+            return Err(e);
+        }
+
+        self.undo.lock().unwrap().push_back(operation.clone());
+        Ok(Some(operation))
+    }
+}
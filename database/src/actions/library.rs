@@ -1,7 +1,13 @@
+use anyhow::{bail, Context, Result};
 use sea_orm::QuerySelect;
 use sea_orm::{DatabaseConnection, DbErr, EntityTrait, QueryOrder};
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+use crate::connection::{connect_main_db, connect_recommendation_db, connect_search_db};
 
 use crate::entities::{
     albums, artists, media_file_albums, media_file_artists, media_file_playlists, playlists,
@@ -84,3 +90,77 @@ pub async fn get_latest_albums_and_artists(
 
     Ok((top_albums_with_cover_ids, top_artists_with_cover_ids))
 }
+
+const DATA_DIR: &str = ".rune";
+const STAGING_DIR: &str = ".rune-staging";
+
+async fn initialize_library_data(staging_path: &Path) -> Result<()> {
+    let staging_str = staging_path
+        .to_str()
+        .context("Invalid UTF-8 sequence in path")?;
+
+    let main_db = connect_main_db(staging_str)
+        .await
+        .context("Failed to initialize the main database")?;
+    main_db
+        .close()
+        .await
+        .context("Failed to close the main database")?;
+
+    let search_db = connect_search_db(staging_str)
+        .map_err(|e| anyhow::anyhow!("Failed to initialize the search index: {}", e))?;
+    search_db
+        .w
+        .wait_merging_threads()
+        .context("Failed to close the search index")?;
+
+    let recommend_db = connect_recommendation_db(staging_str)
+        .map_err(|e| anyhow::anyhow!("Failed to initialize the recommendation store: {}", e))?;
+    recommend_db.env.prepare_for_closing().wait();
+
+    Ok(())
+}
+
+/// Create the data of a new library: the SQLite schema, the tantivy index
+/// and the recommendation store.
+///
+/// Everything is created in a staging directory first and moved into place
+/// with a single rename, so a failure at any step leaves no half-initialized
+/// library behind.
+///
+/// # Arguments
+/// * `lib_path` - The root directory of the media library.
+///
+/// # Returns
+/// * `Result<PathBuf>` - The data directory of the created library.
+pub async fn create_library(lib_path: &Path) -> Result<PathBuf> {
+    if !lib_path.is_dir() {
+        bail!("Library path is not a directory: {:?}", lib_path);
+    }
+
+    let data_path = lib_path.join(DATA_DIR);
+    if data_path.exists() {
+        bail!("Library is already initialized: {:?}", data_path);
+    }
+
+    // Leftovers of an interrupted creation
+    let staging_path = lib_path.join(STAGING_DIR);
+    if staging_path.exists() {
+        fs::remove_dir_all(&staging_path)
+            .with_context(|| format!("Failed to clean up {:?}", staging_path))?;
+    }
+
+    info!("Creating library: {:?}", lib_path);
+
+    let result = match initialize_library_data(&staging_path).await {
+        Ok(_) => fs::rename(staging_path.join(DATA_DIR), &data_path)
+            .with_context(|| format!("Failed to move library data into {:?}", data_path)),
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = fs::remove_dir_all(&staging_path) {
+        error!("Failed to clean up {:?}: {}", staging_path, e);
+    }
+
+    result.map(|_| data_path)
+}
@@ -0,0 +1,202 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate};
+use sea_orm::prelude::*;
+use sea_orm::{QueryOrder, QuerySelect};
+
+use crate::entities::{media_file_artists, media_files, user_logs};
+
+use super::settings::{get_setting, set_setting};
+
+/// Daily listening goal of a profile that never chose one.
+pub const DEFAULT_DAILY_GOAL_MINUTES: u32 = 30;
+
+fn daily_goal_key(user_id: i32) -> String {
+    format!("users.{}.daily_goal_minutes", user_id)
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ListeningStats {
+    /// Days in a row with at least one play, up to today. A streak isn't
+    /// broken before the day ends, so it may end yesterday.
+    pub current_streak: usize,
+    pub longest_streak: usize,
+    pub minutes_today: f64,
+    pub daily_goal_minutes: u32,
+    /// Part of the daily goal reached today, between 0 and 1.
+    pub goal_progress: f64,
+    /// Artists first played this month, in the order they were discovered.
+    pub new_artist_ids: Vec<i32>,
+}
+
+/// Count the days in a row played, up to today and the longest ever.
+///
+/// # Arguments
+/// * `days` - The days with at least one play.
+/// * `today` - The day the current streak is counted to.
+///
+/// # Returns
+/// * `(usize, usize)` - The current streak and the longest streak.
+pub fn count_streaks(days: &BTreeSet<NaiveDate>, today: NaiveDate) -> (usize, usize) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for day in days.iter().filter(|x| **x <= today) {
+        run = match previous {
+            Some(previous) if *day - previous == Duration::days(1) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(*day);
+    }
+
+    let current = match previous {
+        Some(last) if today - last <= Duration::days(1) => run,
+        _ => 0,
+    };
+
+    (current, longest)
+}
+
+/// Get the daily listening goal of a profile.
+///
+/// # Arguments
+/// * `user_db` - The database holding the settings.
+/// * `user_id` - The ID of the profile.
+///
+/// # Returns
+/// * `Result<u32, DbErr>` - The goal in minutes, the default one if none was chosen.
+pub async fn get_daily_goal(user_db: &DatabaseConnection, user_id: i32) -> Result<u32, DbErr> {
+    Ok(get_setting(user_db, &daily_goal_key(user_id))
+        .await?
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(DEFAULT_DAILY_GOAL_MINUTES))
+}
+
+/// Set the daily listening goal of a profile.
+///
+/// # Arguments
+/// * `user_db` - The database holding the settings.
+/// * `user_id` - The ID of the profile.
+/// * `minutes` - The new goal, at least a minute.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the goal was stored.
+pub async fn set_daily_goal(
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    minutes: u32,
+) -> Result<(), DbErr> {
+    set_setting(
+        user_db,
+        &daily_goal_key(user_id),
+        minutes.max(1).to_string(),
+    )
+    .await
+}
+
+/// Compute the streaks, the progress of the daily goal and the artists
+/// discovered this month of a profile.
+///
+/// Plays are stored in UTC, days are counted in UTC too.
+///
+/// # Arguments
+/// * `main_db` - The database holding the library.
+/// * `user_db` - The database holding the play history.
+/// * `user_id` - The ID of the profile.
+/// * `today` - The day the streak and the goal are counted for.
+///
+/// # Returns
+/// * `Result<ListeningStats, DbErr>` - The statistics, empty without plays.
+pub async fn get_listening_stats(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    today: NaiveDate,
+) -> Result<ListeningStats, DbErr> {
+    let plays: Vec<(i32, String, f64)> = user_logs::Entity::find()
+        .select_only()
+        .column(user_logs::Column::FileId)
+        .column(user_logs::Column::ListenTime)
+        .column(user_logs::Column::Progress)
+        .filter(user_logs::Column::UserId.eq(user_id))
+        .order_by_asc(user_logs::Column::ListenTime)
+        .into_tuple()
+        .all(user_db)
+        .await?;
+    let plays: Vec<(i32, NaiveDate, f64)> = plays
+        .into_iter()
+        .filter_map(|(file_id, listen_time, progress)| {
+            let day = DateTime::parse_from_rfc3339(&listen_time)
+                .ok()?
+                .date_naive();
+            Some((file_id, day, progress))
+        })
+        .collect();
+
+    let days: BTreeSet<NaiveDate> = plays.iter().map(|(_, day, _)| *day).collect();
+    let (current_streak, longest_streak) = count_streaks(&days, today);
+
+    let played_today: Vec<(i32, f64)> = plays
+        .iter()
+        .filter(|(_, day, _)| *day == today)
+        .map(|(file_id, _, progress)| (*file_id, *progress))
+        .collect();
+    let durations: HashMap<i32, f64> = media_files::Entity::find()
+        .select_only()
+        .column(media_files::Column::Id)
+        .column(media_files::Column::Duration)
+        .filter(
+            media_files::Column::Id
+                .is_in(played_today.iter().map(|(id, _)| *id).collect::<Vec<_>>()),
+        )
+        .into_tuple::<(i32, f64)>()
+        .all(main_db)
+        .await?
+        .into_iter()
+        .collect();
+    let minutes_today = played_today
+        .iter()
+        .map(|(file_id, progress)| durations.get(file_id).copied().unwrap_or(0.) * progress)
+        .sum::<f64>()
+        / 60.;
+
+    // The first play of every artist, plays come in order
+    let file_ids: HashSet<i32> = plays.iter().map(|(file_id, _, _)| *file_id).collect();
+    let mut artists_of_files: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (file_id, artist_id) in media_file_artists::Entity::find()
+        .select_only()
+        .column(media_file_artists::Column::MediaFileId)
+        .column(media_file_artists::Column::ArtistId)
+        .filter(media_file_artists::Column::MediaFileId.is_in(file_ids))
+        .into_tuple::<(i32, i32)>()
+        .all(main_db)
+        .await?
+    {
+        artists_of_files.entry(file_id).or_default().push(artist_id);
+    }
+
+    let mut played_artists = HashSet::new();
+    let mut new_artist_ids = Vec::new();
+    for (file_id, day, _) in &plays {
+        for artist_id in artists_of_files.get(file_id).into_iter().flatten() {
+            if !played_artists.insert(*artist_id) {
+                continue;
+            }
+            if day.year() == today.year() && day.month() == today.month() && *day <= today {
+                new_artist_ids.push(*artist_id);
+            }
+        }
+    }
+
+    let daily_goal_minutes = get_daily_goal(user_db, user_id).await?;
+
+    Ok(ListeningStats {
+        current_streak,
+        longest_streak,
+        minutes_today,
+        daily_goal_minutes,
+        goal_progress: (minutes_today / daily_goal_minutes as f64).min(1.),
+        new_artist_ids,
+    })
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::io;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, QuerySelect, TransactionTrait};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use analysis::loudness::{measure_loudness, Loudness, REFERENCE_LOUDNESS};
+use analysis::media_file::open_media_file;
+
+use crate::connection::is_library_root_reachable;
+use crate::entities::{media_files, media_loudness};
+
+use super::throttle::AnalysisPace;
+
+fn measure_file(file: &media_files::Model, lib_path: &Path) -> io::Result<Loudness> {
+    let file_path = lib_path.join(&file.directory).join(&file.file_name);
+    open_media_file(&file_path)?;
+
+    measure_loudness(&file_path.to_string_lossy()).map_err(io::Error::other)
+}
+
+/// Measure the loudness of every file that wasn't measured yet.
+///
+/// This is the fast pass of the analysis: files are only decoded, so
+/// ReplayGain is available long before the features of a large library are.
+/// It is paced and cancelled like `analysis_audio_library`.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `lib_path` - The root of the library.
+/// * `pace` - Asked before every batch how many files to measure and how
+///   long to rest afterwards.
+/// * `progress_callback` - Called with the files measured and the total
+///   after every batch.
+/// * `cancel_token` - Stops the pass after the current batch.
+///
+/// # Returns
+/// * `Result<usize, DbErr>` - The number of files in the library.
+pub async fn scan_loudness<F, P>(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    pace: P,
+    progress_callback: F,
+    cancel_token: Option<CancellationToken>,
+) -> Result<usize, DbErr>
+where
+    F: Fn(usize, usize) + Send + Sync + 'static,
+    P: Fn() -> AnalysisPace,
+{
+    let total_tasks = media_files::Entity::find().count(main_db).await? as usize;
+
+    let measured: Vec<i32> = media_loudness::Entity::find()
+        .select_only()
+        .column(media_loudness::Column::FileId)
+        .into_tuple()
+        .all(main_db)
+        .await?;
+    info!("Media files already measured: {}", measured.len());
+
+    let mut total_processed = measured.len();
+    let mut cursor = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_not_in(measured))
+        .cursor_by(media_files::Column::Id);
+
+    loop {
+        let current_pace = pace();
+        let files: Vec<media_files::Model> = cursor
+            .first(current_pace.batch_size as u64)
+            .all(main_db)
+            .await?;
+
+        if files.is_empty() {
+            break;
+        }
+
+        if cancel_token.as_ref().is_some_and(|x| x.is_cancelled()) {
+            info!("Cancellation requested. Exiting loop.");
+            break;
+        }
+
+        if !is_library_root_reachable(lib_path) {
+            return Err(DbErr::Custom(format!(
+                "Library root is offline: {:?}",
+                lib_path
+            )));
+        }
+
+        let results: Vec<_> = files
+            .par_iter()
+            .map(|file| {
+                let result = catch_unwind(AssertUnwindSafe(|| measure_file(file, lib_path)))
+                    .unwrap_or_else(|_| Err(io::Error::other("measuring panicked")));
+                (file, result)
+            })
+            .collect();
+
+        let txn = main_db.begin().await?;
+        for (file, result) in results {
+            match result {
+                Ok(loudness) => {
+                    media_loudness::ActiveModel {
+                        file_id: ActiveValue::Set(file.id),
+                        integrated_loudness: ActiveValue::Set(loudness.integrated),
+                        peak: ActiveValue::Set(loudness.peak as f64),
+                        ..Default::default()
+                    }
+                    .insert(&txn)
+                    .await?;
+                    total_processed += 1;
+                }
+                Err(e) => error!("Unable to measure {}: {}", file.file_name, e),
+            }
+        }
+        txn.commit().await?;
+
+        progress_callback(total_processed, total_tasks);
+
+        if let Some(last_file) = files.last() {
+            cursor.after(last_file.id);
+        }
+
+        if !current_pace.pause.is_zero() {
+            match cancel_token {
+                Some(ref token) => {
+                    let _ = tokio::time::timeout(current_pace.pause, token.cancelled()).await;
+                }
+                None => tokio::time::sleep(current_pace.pause).await,
+            }
+        }
+    }
+
+    info!("Loudness scan completed.");
+    Ok(total_tasks)
+}
+
+/// Get the track gain measured by `scan_loudness`.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, f32>, DbErr>` - The gain in dB to reach the
+///   ReplayGain reference loudness, silent and unmeasured files are left out.
+pub async fn get_measured_gains_of_files<C>(
+    db: &C,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, f32>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let items = media_loudness::Entity::find()
+        .filter(media_loudness::Column::FileId.is_in(file_ids.to_vec()))
+        .all(db)
+        .await?;
+
+    Ok(items
+        .into_iter()
+        .filter_map(|x| {
+            x.integrated_loudness
+                .map(|loudness| (x.file_id, (REFERENCE_LOUDNESS - loudness) as f32))
+        })
+        .collect())
+}
@@ -0,0 +1,217 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::ops::Range;
+
+use sea_orm::prelude::*;
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::*;
+use tantivy::schema::Value as _;
+use tantivy::tokenizer::TextAnalyzer;
+use tracing::warn;
+
+use crate::connection::SearchDbConnection;
+use crate::entities::media_metadata;
+
+// The tag embedded lyrics are read from
+const LYRICS_KEY: &str = "lyrics";
+// Lines shown for every hit, the UI only previews the match
+const SNIPPET_LINES: usize = 3;
+
+// Drop the `<mm:ss.xx>` word timings of enhanced LRC
+fn strip_word_timings(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('<') {
+        result.push_str(&rest[..start]);
+        let tail = &rest[start + 1..];
+        match tail.find('>') {
+            Some(end) if tail.starts_with(|c: char| c.is_ascii_digit()) => rest = &tail[end + 1..],
+            _ => {
+                result.push('<');
+                rest = tail;
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+fn plain_line(line: &str) -> Option<String> {
+    let mut rest = line.trim();
+    let mut timed = false;
+
+    while let Some(tail) = rest.strip_prefix('[') {
+        let Some(end) = tail.find(']') else {
+            break;
+        };
+        let tag = &tail[..end];
+        if tag.starts_with(|c: char| c.is_ascii_digit()) {
+            timed = true;
+            rest = tail[end + 1..].trim_start();
+        } else if !timed && tag.contains(':') {
+            // Headers like `[ar:Artist]` aren't part of the lyrics
+            return None;
+        } else {
+            break;
+        }
+    }
+
+    Some(strip_word_timings(rest).trim().to_string())
+}
+
+/// Turn lyrics into plain text, removing the timings of synced lyrics.
+///
+/// # Arguments
+/// * `text` - The lyrics as they are tagged, plain or in the LRC format.
+///
+/// # Returns
+/// * `String` - One line of lyrics per line, without timings and headers.
+pub fn plain_lyrics(text: &str) -> String {
+    text.lines()
+        .filter_map(plain_line)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Get the lyrics embedded in files.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, String>, DbErr>` - The plain lyrics by file ID,
+///   files without lyrics are left out.
+pub async fn get_lyrics<C>(db: &C, file_ids: Vec<i32>) -> Result<HashMap<i32, String>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let mut result: HashMap<i32, String> = HashMap::new();
+    let entries = media_metadata::Entity::find()
+        .filter(media_metadata::Column::FileId.is_in(file_ids))
+        .filter(media_metadata::Column::MetaKey.eq(LYRICS_KEY))
+        .all(db)
+        .await?;
+
+    // Files may carry lyrics in several languages
+    for entry in entries {
+        let lyrics = plain_lyrics(&entry.meta_value);
+        if lyrics.is_empty() {
+            continue;
+        }
+        let text = result.entry(entry.file_id).or_default();
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&lyrics);
+    }
+
+    Ok(result)
+}
+
+/// A line of lyrics with the byte ranges of the words that matched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricsSnippet {
+    pub line: String,
+    pub highlights: Vec<Range<usize>>,
+}
+
+/// A track whose lyrics match a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricsHit {
+    pub id: i64,
+    pub score: f32,
+    pub snippets: Vec<LyricsSnippet>,
+}
+
+fn tokens(analyzer: &mut TextAnalyzer, text: &str) -> Vec<(String, Range<usize>)> {
+    let mut result = Vec::new();
+    let mut stream = analyzer.token_stream(text);
+    while stream.advance() {
+        let token = stream.token();
+        result.push((token.text.clone(), token.offset_from..token.offset_to));
+    }
+
+    result
+}
+
+fn snippets(
+    analyzer: &mut TextAnalyzer,
+    lyrics: &str,
+    terms: &HashSet<String>,
+) -> Vec<LyricsSnippet> {
+    lyrics
+        .lines()
+        .filter_map(|line| {
+            let highlights: Vec<Range<usize>> = tokens(analyzer, line)
+                .into_iter()
+                .filter(|(text, _)| terms.contains(text))
+                .map(|(_, range)| range)
+                .collect();
+            (!highlights.is_empty()).then(|| LyricsSnippet {
+                line: line.to_string(),
+                highlights,
+            })
+        })
+        .take(SNIPPET_LINES)
+        .collect()
+}
+
+/// Search the lyrics of tracks, every word of the query has to be found.
+///
+/// # Arguments
+/// * `search_db` - The search index.
+/// * `query_str` - The words to look for.
+/// * `n` - The maximum number of tracks.
+///
+/// # Returns
+/// * `Result<Vec<LyricsHit>, Box<dyn Error>>` - The best matching tracks
+///   first, with the lines the words were found in.
+pub fn search_lyrics(
+    search_db: &SearchDbConnection,
+    query_str: &str,
+    n: usize,
+) -> Result<Vec<LyricsHit>, Box<dyn Error>> {
+    let schema = &search_db.schema;
+    let field_lyrics = schema.get_field("lyrics").unwrap();
+    let field_id = schema.get_field("id").unwrap();
+
+    let mut query_parser = QueryParser::for_index(&search_db.index, vec![field_lyrics]);
+    // A single common word matches most songs, quoting a line is expected
+    query_parser.set_conjunction_by_default();
+    let query = query_parser.parse_query(query_str)?;
+
+    let mut analyzer = search_db.index.tokenizer_for_field(field_lyrics)?;
+    let terms: HashSet<String> = tokens(&mut analyzer, query_str)
+        .into_iter()
+        .map(|(text, _)| text)
+        .collect();
+
+    let searcher = search_db.index.reader()?.searcher();
+    let top_docs = searcher.search(&query, &TopDocs::with_limit(n))?;
+
+    let mut hits = Vec::new();
+    for (score, doc_address) in top_docs {
+        let doc: TantivyDocument = searcher.doc(doc_address)?;
+        let Some(id) = doc.get_first(field_id).and_then(|x| x.as_i64()) else {
+            warn!("Id not inserted while searching for the document");
+            continue;
+        };
+        let lyrics = doc
+            .get_first(field_lyrics)
+            .and_then(|x| x.as_str())
+            .unwrap_or_default();
+
+        hits.push(LyricsHit {
+            id,
+            score,
+            snippets: snippets(&mut analyzer, lyrics, &terms),
+        });
+    }
+
+    Ok(hits)
+}
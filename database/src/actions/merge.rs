@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveValue, QueryOrder, TransactionTrait};
+
+use crate::connection::SearchDbConnection;
+use crate::entities::{albums, artists, collection_merges};
+
+use super::albums::{get_album_by_id, get_media_file_ids_of_album};
+use super::artists::{get_artist_by_id, get_media_file_ids_of_artist};
+use metadata::normalize::canonical_name;
+
+use super::index::{index_media_files, IGNORE_ARTICLE_KEY};
+use super::metadata::get_metadata_summary_by_file_ids;
+use super::search::CollectionType;
+use super::settings::get_setting;
+
+fn unsupported(collection_type: &CollectionType) -> DbErr {
+    DbErr::Custom(format!(
+        "Can't merge collections of type {:?}",
+        collection_type
+    ))
+}
+
+async fn get_name(
+    db: &DatabaseConnection,
+    collection_type: &CollectionType,
+    id: i32,
+) -> Result<String, DbErr> {
+    let name = match collection_type {
+        CollectionType::Artist => get_artist_by_id(db, id).await?.map(|x| x.name),
+        CollectionType::Album => get_album_by_id(db, id).await?.map(|x| x.name),
+        _ => return Err(unsupported(collection_type)),
+    };
+
+    name.ok_or_else(|| DbErr::RecordNotFound(format!("{:?} {} not found", collection_type, id)))
+}
+
+async fn get_id_by_name(
+    db: &DatabaseConnection,
+    collection_type: &CollectionType,
+    name: &str,
+) -> Result<Option<i32>, DbErr> {
+    match collection_type {
+        CollectionType::Artist => Ok(artists::Entity::find()
+            .filter(artists::Column::Name.eq(name))
+            .one(db)
+            .await?
+            .map(|x| x.id)),
+        CollectionType::Album => Ok(albums::Entity::find()
+            .filter(albums::Column::Name.eq(name))
+            .one(db)
+            .await?
+            .map(|x| x.id)),
+        _ => Err(unsupported(collection_type)),
+    }
+}
+
+async fn get_file_ids(
+    db: &DatabaseConnection,
+    collection_type: &CollectionType,
+    id: i32,
+) -> Result<Vec<i32>, DbErr> {
+    let mut file_ids = match collection_type {
+        CollectionType::Artist => get_media_file_ids_of_artist(db, id).await?,
+        CollectionType::Album => get_media_file_ids_of_album(db, id).await?,
+        _ => return Err(unsupported(collection_type)),
+    };
+    file_ids.sort_unstable();
+    file_ids.dedup();
+
+    Ok(file_ids)
+}
+
+/// Get the names merged into other artists or albums.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `collection_type` - Artists or albums.
+///
+/// # Returns
+/// * `Result<HashMap<String, String>, DbErr>` - The name files are indexed under for every
+///   name merged away.
+pub async fn get_merge_aliases(
+    db: &impl ConnectionTrait,
+    collection_type: CollectionType,
+) -> Result<HashMap<String, String>, DbErr> {
+    Ok(collection_merges::Entity::find()
+        .filter(collection_merges::Column::CollectionType.eq(i64::from(collection_type)))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|x| (x.source_name, x.target_name))
+        .collect())
+}
+
+/// Get the names that were merged into an artist or an album.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `collection_type` - Artists or albums.
+/// * `id` - The ID of the artist or album.
+///
+/// # Returns
+/// * `Result<Vec<String>, DbErr>` - The names in the order they were merged.
+pub async fn get_merged_names(
+    db: &DatabaseConnection,
+    collection_type: CollectionType,
+    id: i32,
+) -> Result<Vec<String>, DbErr> {
+    let name = get_name(db, &collection_type, id).await?;
+
+    Ok(collection_merges::Entity::find()
+        .filter(collection_merges::Column::CollectionType.eq(i64::from(collection_type)))
+        .filter(collection_merges::Column::TargetName.eq(name))
+        .order_by_asc(collection_merges::Column::Id)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|x| x.source_name)
+        .collect())
+}
+
+/// Get the tracks a merge would move, without merging.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `collection_type` - Artists or albums.
+/// * `source_id` - The ID of the artist or album merged away.
+/// * `target_id` - The ID of the artist or album kept.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The IDs of the files moving to the target.
+pub async fn preview_merge(
+    db: &DatabaseConnection,
+    collection_type: CollectionType,
+    source_id: i32,
+    target_id: i32,
+) -> Result<Vec<i32>, DbErr> {
+    if source_id == target_id {
+        return Err(DbErr::Custom(
+            "Can't merge a collection into itself".to_string(),
+        ));
+    }
+    get_name(db, &collection_type, target_id).await?;
+    get_name(db, &collection_type, source_id).await?;
+
+    get_file_ids(db, &collection_type, source_id).await
+}
+
+/// Merge an artist or an album into another one, e.g. `Beatles` into
+/// `The Beatles`.
+///
+/// The tracks of the source move to the target and the source is removed.
+/// The name of the source is remembered, so tracks tagged with it keep going
+/// to the target when they are scanned again.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `search_db` - The search index, updated with the merge.
+/// * `collection_type` - Artists or albums.
+/// * `source_id` - The ID of the artist or album merged away.
+/// * `target_id` - The ID of the artist or album kept.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The IDs of the files moved to the target.
+pub async fn merge_collections(
+    main_db: &DatabaseConnection,
+    search_db: &mut SearchDbConnection,
+    collection_type: CollectionType,
+    source_id: i32,
+    target_id: i32,
+) -> Result<Vec<i32>, DbErr> {
+    let file_ids = preview_merge(main_db, collection_type.clone(), source_id, target_id).await?;
+    let source_name = get_name(main_db, &collection_type, source_id).await?;
+    let target_name = get_name(main_db, &collection_type, target_id).await?;
+    let collection_type = i64::from(collection_type);
+
+    let txn = main_db.begin().await?;
+
+    // Names merged into the source follow it
+    collection_merges::Entity::update_many()
+        .col_expr(
+            collection_merges::Column::TargetName,
+            Expr::value(target_name.clone()),
+        )
+        .filter(collection_merges::Column::CollectionType.eq(collection_type))
+        .filter(collection_merges::Column::TargetName.eq(source_name.clone()))
+        .exec(&txn)
+        .await?;
+
+    collection_merges::Entity::insert(collection_merges::ActiveModel {
+        collection_type: ActiveValue::Set(collection_type),
+        source_name: ActiveValue::Set(source_name),
+        target_name: ActiveValue::Set(target_name),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::columns([
+            collection_merges::Column::CollectionType,
+            collection_merges::Column::SourceName,
+        ])
+        .update_column(collection_merges::Column::TargetName)
+        .to_owned(),
+    )
+    .exec(&txn)
+    .await?;
+
+    txn.commit().await?;
+
+    // Indexing again links the tracks to the target and drops the empty source
+    index_media_files(main_db, search_db, file_ids.clone()).await?;
+
+    Ok(file_ids)
+}
+
+async fn get_tracks_tagged_with(
+    db: &DatabaseConnection,
+    collection_type: &CollectionType,
+    id: i32,
+    name: &str,
+) -> Result<Vec<i32>, DbErr> {
+    let file_ids = get_file_ids(db, collection_type, id).await?;
+    // Tags are matched the way they are grouped when indexing
+    let ignore_article = get_setting(db, IGNORE_ARTICLE_KEY).await?.as_deref() == Some("true");
+    let name = canonical_name(name, ignore_article);
+
+    Ok(get_metadata_summary_by_file_ids(db, file_ids)
+        .await?
+        .into_iter()
+        .filter(|x| match collection_type {
+            CollectionType::Artist => metadata::artist::split_artists(&x.artist)
+                .iter()
+                .any(|artist| canonical_name(artist, ignore_article) == name),
+            _ => canonical_name(&x.album, ignore_article) == name,
+        })
+        .map(|x| x.id)
+        .collect())
+}
+
+/// Get the tracks splitting a merged name away would move, without splitting.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `collection_type` - Artists or albums.
+/// * `id` - The ID of the artist or album the name was merged into.
+/// * `name` - The name merged into it.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The IDs of the files tagged with the name.
+pub async fn preview_split(
+    db: &DatabaseConnection,
+    collection_type: CollectionType,
+    id: i32,
+    name: &str,
+) -> Result<Vec<i32>, DbErr> {
+    if !get_merged_names(db, collection_type.clone(), id)
+        .await?
+        .iter()
+        .any(|x| x == name)
+    {
+        return Err(DbErr::RecordNotFound(format!(
+            "{} wasn't merged into {:?} {}",
+            name, collection_type, id
+        )));
+    }
+
+    get_tracks_tagged_with(db, &collection_type, id, name).await
+}
+
+/// Undo the merge of a name, the tracks tagged with it go back to an artist
+/// or album of their own.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `search_db` - The search index, updated with the split.
+/// * `collection_type` - Artists or albums.
+/// * `id` - The ID of the artist or album the name was merged into.
+/// * `name` - The name to split away.
+///
+/// # Returns
+/// * `Result<(Option<i32>, Vec<i32>), DbErr>` - The ID of the artist or album created for
+///   the name, `None` if no track is tagged with it anymore, and the IDs of the moved files.
+pub async fn split_collection(
+    main_db: &DatabaseConnection,
+    search_db: &mut SearchDbConnection,
+    collection_type: CollectionType,
+    id: i32,
+    name: &str,
+) -> Result<(Option<i32>, Vec<i32>), DbErr> {
+    let file_ids = preview_split(main_db, collection_type.clone(), id, name).await?;
+
+    collection_merges::Entity::delete_many()
+        .filter(collection_merges::Column::CollectionType.eq(i64::from(collection_type.clone())))
+        .filter(collection_merges::Column::SourceName.eq(name))
+        .exec(main_db)
+        .await?;
+
+    if file_ids.is_empty() {
+        return Ok((None, file_ids));
+    }
+
+    index_media_files(main_db, search_db, file_ids.clone()).await?;
+    let new_id = get_id_by_name(main_db, &collection_type, name).await?;
+
+    Ok((new_id, file_ids))
+}
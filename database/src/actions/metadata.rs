@@ -1,21 +1,31 @@
-use log::{debug, error, info};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{debug, error, info, warn};
 
 use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
 use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::OnConflict;
 use sea_orm::{ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
 use sea_orm::{DatabaseConnection, TransactionTrait};
 use tokio_util::sync::CancellationToken;
 
+use metadata::checksum::{read_embedded_checksum, EmbeddedChecksum};
 use metadata::describe::{describe_file, FileDescription};
-use metadata::reader::get_metadata;
+use metadata::gapless::{read_gapless_info, GaplessInfo};
+use metadata::reader::get_metadata_with_custom_fields;
 use metadata::scanner::AudioScanner;
 
+use crate::actions::checksums::record_embedded_checksum;
 use crate::actions::file::get_file_ids_by_descriptions;
+use crate::actions::gapless::set_gapless_info;
 use crate::actions::index::index_media_files;
 use crate::actions::search::{add_term, remove_term, CollectionType};
-use crate::connection::SearchDbConnection;
+use crate::actions::seek_tables::refresh_seek_table;
+use crate::actions::settings::{get_setting, remove_setting, set_setting};
+use crate::actions::tag_mappings::get_custom_fields;
+use crate::connection::{is_library_root_reachable, SearchDbConnection};
 use crate::entities::{albums, artists, media_file_albums, media_files};
 use crate::entities::{media_file_artists, media_metadata};
 
@@ -25,13 +35,37 @@ use super::utils::DatabaseExecutor;
 pub struct FileMetadata {
     pub path: PathBuf,
     pub metadata: Vec<(String, String)>,
+    /// The encoder delay and padding of lossy files.
+    pub gapless: Option<GaplessInfo>,
+    /// The checksum the format keeps of the audio, like the MD5 of FLAC.
+    pub embedded_checksum: Option<EmbeddedChecksum>,
 }
 
-pub fn read_metadata(description: &FileDescription) -> Option<FileMetadata> {
-    match get_metadata(description.full_path.to_str().unwrap(), None) {
+pub fn read_metadata(
+    description: &FileDescription,
+    custom_fields: &HashMap<String, String>,
+) -> Option<FileMetadata> {
+    let path = description.full_path.to_str().unwrap();
+    match get_metadata_with_custom_fields(path, None, custom_fields) {
         Ok(metadata) => Some(FileMetadata {
             path: description.rel_path.clone(),
             metadata,
+            gapless: read_gapless_info(&description.full_path).unwrap_or_else(|e| {
+                warn!(
+                    "Unable to read the gapless info of {}: {}",
+                    description.rel_path.display(),
+                    e
+                );
+                None
+            }),
+            embedded_checksum: read_embedded_checksum(&description.full_path).unwrap_or_else(|e| {
+                warn!(
+                    "Unable to read the embedded checksum of {}: {}",
+                    description.rel_path.display(),
+                    e
+                );
+                None
+            }),
         }),
         Err(err) => {
             error!(
@@ -52,9 +86,11 @@ pub async fn sync_file_descriptions(
 ) -> Result<()> {
     debug!("Starting to process multiple files");
 
+    let custom_fields = get_custom_fields(main_db).await?;
+
     // Start a transaction
     let txn = main_db.begin().await?;
-    let mut search_term: Option<(i32, String)> = None;
+    let mut search_terms: Vec<(i32, String)> = Vec::new();
 
     let mut update_search_term = |file_id: i32, metadata: &FileMetadata| {
         if let Some((_, value)) = metadata
@@ -62,7 +98,7 @@ pub async fn sync_file_descriptions(
             .iter()
             .find(|(key, _)| key == "track_title")
         {
-            search_term = Some((file_id, value.clone()));
+            search_terms.push((file_id, value.clone()));
         }
     };
 
@@ -79,12 +115,21 @@ pub async fn sync_file_descriptions(
                     .one(&txn)
                     .await?;
 
-                if let Some(existing_file) = existing_file {
+                if let Some(mut existing_file) = existing_file {
                     debug!(
                         "File exists in the database: {}",
                         description.file_name.clone()
                     );
 
+                    if existing_file.deleted_at.is_some() {
+                        // The file is back, its record was never removed
+                        info!("Restoring file: {}", description.file_name.clone());
+                        let mut active_model: media_files::ActiveModel =
+                            existing_file.clone().into();
+                        active_model.deleted_at = ActiveValue::Set(None);
+                        existing_file = active_model.update(&txn).await?;
+                    }
+
                     // File exists in the database
                     if existing_file.last_modified == description.last_modified {
                         // If the file's last modified date hasn't changed, skip it
@@ -118,6 +163,26 @@ pub async fn sync_file_descriptions(
                             {
                                 bail!("Failed to update last modified: {}", e);
                             }
+
+                            // The tags may still differ from the stored ones, for
+                            // example when the reader learned new keys
+                            if let Some(x) = read_metadata(description, &custom_fields) {
+                                if is_metadata_changed(&txn, existing_file.id, &x).await? {
+                                    debug!(
+                                        "Tags changed, updating metadata: {}",
+                                        description.file_name.clone()
+                                    );
+
+                                    if let Err(e) =
+                                        update_file_metadata(&txn, &existing_file, description, &x)
+                                            .await
+                                    {
+                                        bail!("Failed to update file metadata: {}", e);
+                                    }
+
+                                    update_search_term(existing_file.id, &x);
+                                }
+                            }
                         } else {
                             // If the hash is different, update the metadata
                             debug!(
@@ -132,7 +197,7 @@ pub async fn sync_file_descriptions(
                                 bail!("Failed to update file codec information: {}", e);
                             }
 
-                            let file_metadata = read_metadata(description);
+                            let file_metadata = read_metadata(description, &custom_fields);
 
                             match file_metadata {
                                 Some(x) => {
@@ -161,7 +226,7 @@ pub async fn sync_file_descriptions(
                         description.file_name.clone()
                     );
 
-                    let file_metadata = read_metadata(description);
+                    let file_metadata = read_metadata(description, &custom_fields);
 
                     if let Some(ref x) = file_metadata {
                         if let Err(e) = insert_new_file(&txn, search_db, x, description).await {
@@ -185,10 +250,12 @@ pub async fn sync_file_descriptions(
     // Commit the transaction
     txn.commit().await?;
 
-    if let Some((id, name)) = search_term {
-        add_term(search_db, CollectionType::Track, id, &name);
+    if !search_terms.is_empty() {
+        for (id, name) in search_terms {
+            add_term(search_db, CollectionType::Track, id, &name);
+        }
 
-        search_db.w.commit().unwrap();
+        search_db.commit().unwrap();
     }
 
     debug!("Finished syncing file data");
@@ -203,6 +270,8 @@ pub async fn process_files(
 ) -> Result<(), Box<dyn std::error::Error>> {
     debug!("Starting to process multiple files");
 
+    let custom_fields = get_custom_fields(main_db).await?;
+
     // Start a transaction
     let txn = main_db.begin().await?;
 
@@ -259,7 +328,7 @@ pub async fn process_files(
                             update_file_codec_information(&txn, &existing_file, description)
                                 .await?;
 
-                            let file_metadata = read_metadata(description);
+                            let file_metadata = read_metadata(description, &custom_fields);
 
                             match file_metadata {
                                 Some(x) => {
@@ -282,7 +351,7 @@ pub async fn process_files(
                         description.file_name.clone()
                     );
 
-                    let file_metadata = read_metadata(description);
+                    let file_metadata = read_metadata(description, &custom_fields);
 
                     match file_metadata {
                         Some(x) => {
@@ -304,7 +373,7 @@ pub async fn process_files(
     // Commit the transaction
     txn.commit().await?;
     if modified {
-        search_db.w.commit().unwrap();
+        search_db.commit().unwrap();
     }
 
     info!("Finished processing multiple files");
@@ -326,6 +395,38 @@ where
     Ok(())
 }
 
+/// Check whether the tags read from a file differ from the stored ones.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_id` - The ID of the file.
+/// * `metadata` - The tags read from the file.
+///
+/// # Returns
+/// * `Result<bool, DbErr>` - `true` if any tag was added, removed or changed.
+pub async fn is_metadata_changed<E>(
+    db: &E,
+    file_id: i32,
+    metadata: &FileMetadata,
+) -> Result<bool, sea_orm::DbErr>
+where
+    E: DatabaseExecutor + sea_orm::ConnectionTrait,
+{
+    let mut stored: Vec<(String, String)> = media_metadata::Entity::find()
+        .filter(media_metadata::Column::FileId.eq(file_id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|x| (x.meta_key, x.meta_value))
+        .collect();
+    let mut read = metadata.metadata.clone();
+
+    stored.sort();
+    read.sort();
+
+    Ok(stored != read)
+}
+
 pub async fn update_file_metadata<E>(
     db: &E,
     existing_file: &media_files::Model,
@@ -362,6 +463,9 @@ where
     media_metadata::Entity::insert_many(new_metadata)
         .exec(db)
         .await?;
+    set_gapless_info(db, existing_file.id, metadata.gapless).await?;
+    refresh_seek_table(db, existing_file.id, &description.full_path).await?;
+    record_embedded_checksum(db, existing_file.id, metadata.embedded_checksum).await?;
     Ok(())
 }
 
@@ -410,22 +514,48 @@ where
         last_modified: ActiveValue::Set(description.last_modified.clone()),
         ..Default::default()
     };
-    let inserted_file = media_files::Entity::insert(new_file).exec(main_db).await?;
+    // A scan interrupted after this point inserts the file again when it
+    // resumes, update the existing record instead
+    media_files::Entity::insert(new_file)
+        .on_conflict(
+            OnConflict::columns([
+                media_files::Column::Directory,
+                media_files::Column::FileName,
+            ])
+            .update_columns([
+                media_files::Column::Extension,
+                media_files::Column::FileHash,
+                media_files::Column::SampleRate,
+                media_files::Column::Duration,
+                media_files::Column::LastModified,
+            ])
+            .to_owned(),
+        )
+        .exec(main_db)
+        .await?;
+
+    let Some(inserted_file) = media_files::Entity::find()
+        .filter(media_files::Column::Directory.eq(description.directory.clone()))
+        .filter(media_files::Column::FileName.eq(description.file_name.clone()))
+        .one(main_db)
+        .await?
+    else {
+        bail!("File record missing after insert");
+    };
+    let file_id = inserted_file.id;
 
     if let Some((_, value)) = metadata
         .metadata
         .iter()
         .find(|(key, _)| key == "track_title")
     {
-        add_term(
-            search_db,
-            CollectionType::Track,
-            inserted_file.last_insert_id,
-            value,
-        );
+        add_term(search_db, CollectionType::Track, file_id, value);
     }
 
-    let file_id = inserted_file.last_insert_id;
+    media_metadata::Entity::delete_many()
+        .filter(media_metadata::Column::FileId.eq(file_id))
+        .exec(main_db)
+        .await?;
 
     // Insert metadata
     let new_metadata: Vec<media_metadata::ActiveModel> = metadata
@@ -448,16 +578,38 @@ where
             bail!("Failed to insert new metadata: {}", e);
         }
     }
+    set_gapless_info(main_db, file_id, metadata.gapless).await?;
+    record_embedded_checksum(main_db, file_id, metadata.embedded_checksum).await?;
 
     Ok(())
 }
 
-async fn clean_up_database(
+/// The setting holding how many days missing files are kept before they
+/// are removed for good.
+pub const DELETED_GRACE_DAYS_KEY: &str = "library.deleted_grace_days";
+pub const DEFAULT_DELETED_GRACE_DAYS: i64 = 30;
+
+/// The setting holding the last file of the last finished batch while a
+/// scan is running, relative to the library root.
+pub const SCAN_CHECKPOINT_KEY: &str = "scan.checkpoint";
+
+async fn get_deleted_grace_period(main_db: &DatabaseConnection) -> chrono::Duration {
+    let days = match get_setting(main_db, DELETED_GRACE_DAYS_KEY).await {
+        Ok(Some(x)) => x.parse::<i64>().unwrap_or(DEFAULT_DELETED_GRACE_DAYS),
+        _ => DEFAULT_DELETED_GRACE_DAYS,
+    };
+
+    chrono::Duration::days(days.max(0))
+}
+
+pub async fn clean_up_database(
     main_db: &DatabaseConnection,
     search_db: &mut SearchDbConnection,
     root_path: &Path,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let db_files = media_files::Entity::find().all(main_db).await?;
+    let grace_period = get_deleted_grace_period(main_db).await;
+    let now = Utc::now();
 
     let mut modified = false;
 
@@ -466,19 +618,43 @@ async fn clean_up_database(
             .join(PathBuf::from(&db_file.directory))
             .join(PathBuf::from(&db_file.file_name));
         if !full_path.exists() {
-            info!("Cleaning {}", full_path.to_str().unwrap());
-            // Delete the file record
-            media_files::Entity::delete_by_id(db_file.id)
-                .exec(main_db)
-                .await?;
-
-            modified = true;
-            remove_term(search_db, CollectionType::Track, db_file.id)
+            // Never mistake a share that went away for deleted files
+            if !is_library_root_reachable(root_path) {
+                warn!("Library root went offline, stopping cleanup");
+                break;
+            }
+
+            match &db_file.deleted_at {
+                None => {
+                    // Keep the record with its stats and playlists, the file
+                    // may come back
+                    info!("Marking {} as deleted", full_path.to_str().unwrap());
+                    let mut active_model: media_files::ActiveModel = db_file.clone().into();
+                    active_model.deleted_at = ActiveValue::Set(Some(now.to_rfc3339()));
+                    active_model.update(main_db).await?;
+
+                    modified = true;
+                    remove_term(search_db, CollectionType::Track, db_file.id)
+                }
+                Some(deleted_at) => {
+                    let expired = DateTime::parse_from_rfc3339(deleted_at)
+                        .map(|x| now.signed_duration_since(x) >= grace_period)
+                        .unwrap_or(true);
+
+                    if expired {
+                        info!("Cleaning {}", full_path.to_str().unwrap());
+                        // Delete the file record
+                        media_files::Entity::delete_by_id(db_file.id)
+                            .exec(main_db)
+                            .await?;
+                    }
+                }
+            }
         }
     }
 
     if modified {
-        search_db.w.commit().unwrap();
+        search_db.commit().unwrap();
     }
 
     Ok(())
@@ -497,11 +673,24 @@ pub async fn scan_audio_library<F>(
 where
     F: Fn(usize) + Send + Sync,
 {
+    if !is_library_root_reachable(lib_path) {
+        return Err(sea_orm::DbErr::Custom(format!(
+            "Library root is offline: {:?}",
+            lib_path
+        )));
+    }
+
     let root_path_str = lib_path.to_str().expect("Invalid UTF-8 sequence in path");
     let mut scanner = AudioScanner::new(&root_path_str);
 
     info!("Starting audio library scan");
 
+    // Continue after the last finished batch of an interrupted scan
+    if let Ok(Some(checkpoint)) = get_setting(main_db, SCAN_CHECKPOINT_KEY).await {
+        info!("Resuming the scan after {}", checkpoint);
+        scanner.resume_after(&checkpoint);
+    }
+
     // Get the total number of files to scan (assuming AudioScanner has this method)
     let mut processed_files = 0;
 
@@ -515,6 +704,8 @@ where
             }
         }
 
+        let batch_start = Instant::now();
+
         debug!("Reading metadata for the next 12 files");
         let files = scanner.read_files(12);
         let mut descriptions: Vec<Option<FileDescription>> = files
@@ -542,14 +733,31 @@ where
             Err(e) => error!("Error indexing files: {:?}", e),
         };
 
+        // The batch is stored and linked, a restarted scan can skip it
+        if let Some(relative_path) = files
+            .last()
+            .and_then(|x| x.path().strip_prefix(lib_path).ok())
+        {
+            let relative_path = relative_path.to_string_lossy().to_string();
+            if let Err(e) = set_setting(main_db, SCAN_CHECKPOINT_KEY, relative_path).await {
+                warn!("Failed to record the scan checkpoint: {:?}", e);
+            }
+        }
+
         // Update the number of processed files
         processed_files += files.len();
+        metrics::SCANNED_FILES.add(files.len() as u64);
+        metrics::SCAN_BATCHES.record(batch_start.elapsed());
 
         // Call the progress callback if it is provided
         progress_callback(processed_files);
     }
 
-    if cleanup {
+    remove_setting(main_db, SCAN_CHECKPOINT_KEY).await?;
+
+    if cleanup && !is_library_root_reachable(lib_path) {
+        warn!("Library root went offline during the scan, skipping cleanup.");
+    } else if cleanup {
         info!("Starting cleanup process.");
         match clean_up_database(main_db, search_db, lib_path).await {
             Ok(_) => info!("Cleanup completed successfully."),
@@ -568,10 +776,25 @@ pub struct MetadataSummary {
     pub directory: String,
     pub file_name: String,
     pub artist: String,
+    pub album_artist: String,
     pub album: String,
     pub title: String,
     pub track_number: Option<i32>,
     pub duration: f64,
+    pub sort_artist: String,
+    pub sort_album: String,
+    /// Year of the original release, or of this release if unknown.
+    pub year: Option<i32>,
+}
+
+/// Read the year from a date tag, e.g. `1999`, `1999-05-01` or `1999.05`.
+pub fn parse_year(date: &str) -> Option<i32> {
+    let date = date.trim();
+    let digits = date.chars().take_while(|x| x.is_ascii_digit()).count();
+    if digits != 4 {
+        return None;
+    }
+    date[..4].parse().ok()
 }
 
 pub async fn get_metadata_summary_by_files(
@@ -583,11 +806,18 @@ pub async fn get_metadata_summary_by_files(
 
     // Fetch all metadata entries for the given file IDs
     let metadata_entries: Vec<media_metadata::Model> = media_metadata::Entity::find()
-        .filter(
-            media_metadata::Column::FileId
-                .is_in(file_ids.clone())
-                .and(media_metadata::Column::MetaKey.is_in(["artist", "album", "track_title"])),
-        )
+        .filter(media_metadata::Column::FileId.is_in(file_ids.clone()).and(
+            media_metadata::Column::MetaKey.is_in([
+                "artist",
+                "album_artist",
+                "album",
+                "track_title",
+                "sort_artist",
+                "sort_album",
+                "original_date",
+                "date",
+            ]),
+        ))
         .all(db)
         .await?;
 
@@ -613,6 +843,7 @@ pub async fn get_metadata_summary_by_files(
             directory: file.directory.clone(),
             file_name: file.file_name.clone(),
             artist: metadata.get("artist").cloned().unwrap_or_default(),
+            album_artist: metadata.get("album_artist").cloned().unwrap_or_default(),
             album: metadata.get("album").cloned().unwrap_or_default(),
             title: metadata.get("track_title").cloned().unwrap_or_default(),
             track_number: metadata
@@ -620,6 +851,11 @@ pub async fn get_metadata_summary_by_files(
                 .map(|s| s.parse::<i32>().ok())
                 .unwrap_or(None),
             duration,
+            sort_artist: metadata.get("sort_artist").cloned().unwrap_or_default(),
+            sort_album: metadata.get("sort_album").cloned().unwrap_or_default(),
+            year: ["original_date", "date"]
+                .iter()
+                .find_map(|key| parse_year(metadata.get(*key)?)),
         };
 
         results.push(summary);
@@ -0,0 +1,130 @@
+use std::path::Path;
+
+use anyhow::Context;
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, QueryOrder};
+
+use analysis::energy::LONG_MIX_SECONDS;
+use metadata::chapters::read_chapters;
+
+use crate::actions::audiobooks::{get_directory_content_types, resolve_content_type, ContentType};
+use crate::actions::file::get_file_by_id;
+use crate::entities::media_markers;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerSource {
+    /// A chapter embedded in the file, like a cue of a FLAC cuesheet.
+    Chapter,
+    /// A section the analysis found where the energy kicks in.
+    Onset,
+}
+
+impl MarkerSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MarkerSource::Chapter => "chapter",
+            MarkerSource::Onset => "onset",
+        }
+    }
+}
+
+/// A navigable point inside a long mix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixMarker {
+    /// Seconds from the start of the file.
+    pub position: f64,
+    pub title: Option<String>,
+    pub source: MarkerSource,
+}
+
+/// Replace the markers the analysis found for a file.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_id` - The ID of the file.
+/// * `positions` - The starts of the sections in seconds.
+pub async fn set_onset_markers<C>(db: &C, file_id: i32, positions: &[f64]) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    media_markers::Entity::delete_many()
+        .filter(media_markers::Column::FileId.eq(file_id))
+        .exec(db)
+        .await?;
+
+    if positions.is_empty() {
+        return Ok(());
+    }
+
+    let models = positions.iter().map(|x| media_markers::ActiveModel {
+        file_id: ActiveValue::Set(file_id),
+        position: ActiveValue::Set(*x),
+        ..Default::default()
+    });
+    media_markers::Entity::insert_many(models).exec(db).await?;
+
+    Ok(())
+}
+
+/// Get the markers of a file that plays as one long mix.
+///
+/// Files in directories flagged as long mixes and files longer than
+/// `LONG_MIX_SECONDS` are long mixes. Their embedded chapters are used as
+/// markers, the sections found by the analysis if they have none.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `lib_path` - The root path of the library.
+/// * `file_id` - The ID of the file.
+///
+/// # Returns
+/// * `Result<Option<Vec<MixMarker>>>` - The markers in playback order,
+///   `None` if the file isn't a long mix.
+pub async fn get_mix_markers(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    file_id: i32,
+) -> anyhow::Result<Option<Vec<MixMarker>>> {
+    let file = get_file_by_id(main_db, file_id)
+        .await?
+        .with_context(|| format!("File not found: {}", file_id))?;
+
+    let flags = get_directory_content_types(main_db).await?;
+    let flagged = resolve_content_type(&file.directory, &flags) == ContentType::LongMix;
+    if !flagged && file.duration < LONG_MIX_SECONDS {
+        return Ok(None);
+    }
+
+    let file_path = lib_path.join(&file.directory).join(&file.file_name);
+    let chapters = read_chapters(&file_path)
+        .with_context(|| format!("Failed to read chapters: {:?}", file_path))?;
+    if !chapters.is_empty() {
+        return Ok(Some(
+            chapters
+                .into_iter()
+                .map(|x| MixMarker {
+                    position: x.start,
+                    title: x.title,
+                    source: MarkerSource::Chapter,
+                })
+                .collect(),
+        ));
+    }
+
+    let onsets = media_markers::Entity::find()
+        .filter(media_markers::Column::FileId.eq(file_id))
+        .order_by_asc(media_markers::Column::Position)
+        .all(main_db)
+        .await?;
+
+    Ok(Some(
+        onsets
+            .into_iter()
+            .map(|x| MixMarker {
+                position: x.position,
+                title: None,
+                source: MarkerSource::Onset,
+            })
+            .collect(),
+    ))
+}
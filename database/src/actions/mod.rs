@@ -1,12 +1,62 @@
 pub mod albums;
 pub mod analysis;
+pub mod analysis_cache;
+pub mod analysis_exchange;
 pub mod artists;
+pub mod audiobooks;
+pub mod auto_dj;
+pub mod bulk;
+pub mod checksums;
+pub mod classical;
+pub mod collation;
+pub mod cold_start;
 pub mod cover_art;
+pub mod device_profiles;
+pub mod device_sync;
+pub mod diagnostics;
+pub mod diversity;
+pub mod drop_folder;
+pub mod duplicates;
+pub mod eras;
+pub mod exclusions;
+pub mod explicit;
 pub mod file;
+pub mod gain;
+pub mod gapless;
+pub mod health;
+pub mod home;
 pub mod index;
+pub mod journal;
 pub mod library;
+pub mod listening_stats;
+pub mod loudness;
+pub mod lyrics;
+pub mod merge;
 pub mod metadata;
+pub mod mix_markers;
+pub mod music_map;
+pub mod path_tags;
+pub mod pinned;
+pub mod play_history;
+pub mod playback_queue;
 pub mod playlists;
+pub mod query_cache;
+pub mod ratings;
+pub mod recent_contexts;
 pub mod recommendation;
+pub mod remote;
 pub mod search;
+pub mod search_aliases;
+pub mod seek_tables;
+pub mod settings;
+pub mod shuffle;
+pub mod skips;
+pub mod tag_mappings;
+pub mod taste;
+pub mod throttle;
+pub mod track_detail;
+pub mod transcode;
+pub mod users;
 pub mod utils;
+pub mod verification;
+pub mod vibes;
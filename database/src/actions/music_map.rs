@@ -0,0 +1,289 @@
+use sea_orm::prelude::*;
+
+use super::analysis_exchange::features_of;
+use crate::entities::{media_analysis, media_files};
+
+/// Clusters of a map when the caller doesn't ask for a number.
+pub const DEFAULT_MAP_CLUSTERS: usize = 8;
+// More clusters than this can't be told apart by color
+const MAX_MAP_CLUSTERS: usize = 32;
+const POWER_ITERATIONS: usize = 100;
+const K_MEANS_ITERATIONS: usize = 50;
+
+/// Where a track lies on the map of the library.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapPoint {
+    pub file_id: i32,
+    /// Coordinates between -1 and 1, similar tracks lie close together.
+    pub x: f64,
+    pub y: f64,
+    pub cluster: usize,
+}
+
+fn distance_squared(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+// The part of a vector away from the given unit vectors, scaled to a length
+// of one, `None` if there is nothing left
+fn orthonormalize(mut vector: Vec<f64>, found: &[Vec<f64>]) -> Option<Vec<f64>> {
+    for found in found {
+        let overlap: f64 = vector.iter().zip(found).map(|(a, b)| a * b).sum();
+        for (x, y) in vector.iter_mut().zip(found) {
+            *x -= overlap * y;
+        }
+    }
+
+    let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+    (norm > 1e-9).then(|| vector.into_iter().map(|x| x / norm).collect())
+}
+
+/// Scale every dimension to a mean of zero and a standard deviation of one,
+/// so features measured in hertz don't outweigh the chromagram. Missing
+/// values take the mean.
+pub fn standardize(vectors: &[Vec<Option<f64>>]) -> Vec<Vec<f64>> {
+    let dimensions = vectors.first().map(|x| x.len()).unwrap_or(0);
+    let mut scales = Vec::with_capacity(dimensions);
+
+    for dimension in 0..dimensions {
+        let values: Vec<f64> = vectors
+            .iter()
+            .filter_map(|x| x[dimension])
+            .filter(|x| x.is_finite())
+            .collect();
+        let count = values.len().max(1) as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let variance = values.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / count;
+        scales.push((mean, variance.sqrt()));
+    }
+
+    vectors
+        .iter()
+        .map(|vector| {
+            vector
+                .iter()
+                .zip(&scales)
+                .map(|(value, (mean, deviation))| match value {
+                    Some(value) if value.is_finite() && *deviation > 0.0 => {
+                        (value - mean) / deviation
+                    }
+                    _ => 0.0,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The directions the centered vectors vary the most along, found by power
+/// iteration on their covariance.
+///
+/// # Arguments
+/// * `vectors` - Vectors with a mean of zero, see [`standardize`].
+/// * `count` - How many directions to find.
+///
+/// # Returns
+/// * `Vec<Vec<f64>>` - Unit vectors, the one with the most variance first.
+pub fn principal_components(vectors: &[Vec<f64>], count: usize) -> Vec<Vec<f64>> {
+    let dimensions = vectors.first().map(|x| x.len()).unwrap_or(0);
+    if dimensions == 0 {
+        return Vec::new();
+    }
+
+    let mut covariance = vec![vec![0.0; dimensions]; dimensions];
+    for vector in vectors {
+        for i in 0..dimensions {
+            for j in 0..dimensions {
+                covariance[i][j] += vector[i] * vector[j];
+            }
+        }
+    }
+
+    let mut components: Vec<Vec<f64>> = Vec::with_capacity(count);
+    for _ in 0..count.min(dimensions) {
+        // Start off the diagonal, or off an axis when the diagonal was
+        // found already
+        let mut starts = std::iter::once(vec![1.0; dimensions]).chain((0..dimensions).map(|i| {
+            let mut axis = vec![0.0; dimensions];
+            axis[i] = 1.0;
+            axis
+        }));
+        let Some(mut component) = starts.find_map(|x| orthonormalize(x, &components)) else {
+            break;
+        };
+
+        for _ in 0..POWER_ITERATIONS {
+            let next: Vec<f64> = covariance
+                .iter()
+                .map(|row| row.iter().zip(&component).map(|(a, b)| a * b).sum())
+                .collect();
+            // Stay clear of the directions found before, without any
+            // variance left the start is as good as any direction
+            match orthonormalize(next, &components) {
+                Some(next) => component = next,
+                None => break,
+            }
+        }
+
+        // Point the largest weight the positive way, so maps keep their
+        // orientation as the library grows
+        let largest = component
+            .iter()
+            .copied()
+            .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .unwrap_or(0.0);
+        if largest < 0.0 {
+            component.iter_mut().for_each(|x| *x = -*x);
+        }
+        components.push(component);
+    }
+
+    components
+}
+
+/// Group vectors around `k` centers with k-means.
+///
+/// The centers start at the vector closest to the mean and then at the
+/// ones farthest from the centers picked so far, so the same vectors always
+/// get the same clusters.
+///
+/// # Returns
+/// * `Vec<usize>` - The cluster of every vector, below `k`.
+pub fn k_means(vectors: &[Vec<f64>], k: usize) -> Vec<usize> {
+    let k = k.min(vectors.len());
+    if k == 0 {
+        return vec![0; vectors.len()];
+    }
+
+    let dimensions = vectors[0].len();
+    let mut mean = vec![0.0; dimensions];
+    for vector in vectors {
+        for (x, y) in mean.iter_mut().zip(vector) {
+            *x += y / vectors.len() as f64;
+        }
+    }
+
+    let closest = (0..vectors.len())
+        .min_by(|a, b| {
+            distance_squared(&vectors[*a], &mean).total_cmp(&distance_squared(&vectors[*b], &mean))
+        })
+        .unwrap();
+    let mut centers = vec![vectors[closest].clone()];
+    while centers.len() < k {
+        let farthest = (0..vectors.len())
+            .max_by(|a, b| {
+                let nearest = |i: usize| {
+                    centers
+                        .iter()
+                        .map(|x| distance_squared(&vectors[i], x))
+                        .fold(f64::INFINITY, f64::min)
+                };
+                nearest(*a).total_cmp(&nearest(*b))
+            })
+            .unwrap();
+        centers.push(vectors[farthest].clone());
+    }
+
+    let mut clusters = vec![0; vectors.len()];
+    for iteration in 0..K_MEANS_ITERATIONS {
+        let mut changed = false;
+        for (vector, cluster) in vectors.iter().zip(clusters.iter_mut()) {
+            let nearest = (0..k)
+                .min_by(|a, b| {
+                    distance_squared(vector, &centers[*a])
+                        .total_cmp(&distance_squared(vector, &centers[*b]))
+                })
+                .unwrap();
+            if nearest != *cluster {
+                *cluster = nearest;
+                changed = true;
+            }
+        }
+        if !changed && iteration > 0 {
+            break;
+        }
+
+        let mut sums = vec![vec![0.0; dimensions]; k];
+        let mut counts = vec![0usize; k];
+        for (vector, cluster) in vectors.iter().zip(&clusters) {
+            counts[*cluster] += 1;
+            for (x, y) in sums[*cluster].iter_mut().zip(vector) {
+                *x += y;
+            }
+        }
+        // Empty clusters keep their center
+        for ((center, sum), count) in centers.iter_mut().zip(sums).zip(counts) {
+            if count > 0 {
+                *center = sum.into_iter().map(|x| x / count as f64).collect();
+            }
+        }
+    }
+
+    clusters
+}
+
+/// Lay the analysed tracks of the library out on a plane for exploring it
+/// as a map.
+///
+/// The feature vectors are standardized, projected on their two principal
+/// components and grouped with k-means.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `clusters` - How many groups to find, capped at 32.
+///
+/// # Returns
+/// * `Result<Vec<MapPoint>, DbErr>` - The analysed files that are not deleted,
+///   by file ID.
+pub async fn get_music_map(
+    main_db: &DatabaseConnection,
+    clusters: usize,
+) -> Result<Vec<MapPoint>, DbErr> {
+    let mut analyses: Vec<media_analysis::Model> = media_analysis::Entity::find()
+        .inner_join(media_files::Entity)
+        .filter(media_files::Column::DeletedAt.is_null())
+        .all(main_db)
+        .await?;
+    analyses.sort_by_key(|x| x.file_id);
+
+    let vectors: Vec<Vec<Option<f64>>> = analyses.iter().map(|x| features_of(x).to_vec()).collect();
+    let vectors = standardize(&vectors);
+
+    let components = principal_components(&vectors, 2);
+    let mut coordinates: Vec<(f64, f64)> = vectors
+        .iter()
+        .map(|vector| {
+            let mut projected = components
+                .iter()
+                .map(|x| x.iter().zip(vector).map(|(a, b)| a * b).sum::<f64>());
+            (
+                projected.next().unwrap_or(0.0),
+                projected.next().unwrap_or(0.0),
+            )
+        })
+        .collect();
+
+    let extent = coordinates
+        .iter()
+        .map(|(x, y)| x.abs().max(y.abs()))
+        .fold(0.0, f64::max);
+    if extent > 0.0 {
+        for (x, y) in coordinates.iter_mut() {
+            *x /= extent;
+            *y /= extent;
+        }
+    }
+
+    let labels = k_means(&vectors, clusters.clamp(1, MAX_MAP_CLUSTERS));
+
+    Ok(analyses
+        .iter()
+        .zip(coordinates)
+        .zip(labels)
+        .map(|((analysis, (x, y)), cluster)| MapPoint {
+            file_id: analysis.file_id,
+            x,
+            y,
+            cluster,
+        })
+        .collect())
+}
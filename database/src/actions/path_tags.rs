@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, Condition, QueryOrder, TransactionTrait};
+use tracing::{info, warn};
+
+use metadata::path_tags::{infer_path_tags, PathPattern, PathPatternError, DEFAULT_PATH_PATTERNS};
+use metadata::tag_writer::write_tags;
+
+use crate::actions::bulk::BulkReport;
+use crate::actions::index::index_media_files;
+use crate::connection::SearchDbConnection;
+use crate::entities::{media_files, media_metadata};
+
+/// Tags read from the path of a file, to be reviewed before they are
+/// applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagProposal {
+    pub file_id: i32,
+    pub directory: String,
+    pub file_name: String,
+    /// The pattern the path matched.
+    pub pattern: String,
+    /// The stored values of the proposed fields, untagged fields are left
+    /// out.
+    pub current: Vec<(String, String)>,
+    pub proposed: Vec<(String, String)>,
+}
+
+/// Parse the patterns tags are read from paths with.
+///
+/// # Arguments
+/// * `patterns` - The patterns in the order they are tried, empty for
+///   `DEFAULT_PATH_PATTERNS`.
+///
+/// # Returns
+/// * `Result<Vec<PathPattern>, PathPatternError>` - The parsed patterns, the
+///   error of the first invalid one.
+pub fn parse_path_patterns(patterns: &[String]) -> Result<Vec<PathPattern>, PathPatternError> {
+    if patterns.is_empty() {
+        return DEFAULT_PATH_PATTERNS
+            .iter()
+            .map(|x| PathPattern::parse(x))
+            .collect();
+    }
+
+    patterns.iter().map(|x| PathPattern::parse(x)).collect()
+}
+
+/// Read the tags of the files in a folder from their paths, for folders of
+/// ripped CDs that were never tagged.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `directory` - The folder relative to the library root, files in its
+///   subfolders are included. Empty for the whole library.
+/// * `patterns` - The patterns in the order they are tried.
+///
+/// # Returns
+/// * `Result<Vec<TagProposal>, DbErr>` - The files whose path matched and
+///   whose tags would change, by path.
+pub async fn preview_path_tags(
+    main_db: &DatabaseConnection,
+    directory: &str,
+    patterns: &[PathPattern],
+) -> Result<Vec<TagProposal>, DbErr> {
+    let directory = directory.trim_matches('/');
+    let mut query = media_files::Entity::find().filter(media_files::Column::DeletedAt.is_null());
+    if !directory.is_empty() {
+        query = query.filter(
+            Condition::any()
+                .add(media_files::Column::Directory.eq(directory))
+                .add(media_files::Column::Directory.starts_with(format!("{}/", directory))),
+        );
+    }
+    let files = query
+        .order_by_asc(media_files::Column::Directory)
+        .order_by_asc(media_files::Column::FileName)
+        .all(main_db)
+        .await?;
+
+    let mut stored: HashMap<i32, Vec<(String, String)>> = HashMap::new();
+    for row in media_metadata::Entity::find()
+        .filter(media_metadata::Column::FileId.is_in(files.iter().map(|x| x.id)))
+        .all(main_db)
+        .await?
+    {
+        stored
+            .entry(row.file_id)
+            .or_default()
+            .push((row.meta_key, row.meta_value));
+    }
+
+    let mut proposals = Vec::new();
+    for file in files {
+        let path = Path::new(&file.directory).join(&file.file_name);
+        let Some((pattern, proposed)) = infer_path_tags(patterns, &path) else {
+            continue;
+        };
+
+        let stored = stored.remove(&file.id).unwrap_or_default();
+        let current: Vec<(String, String)> = stored
+            .into_iter()
+            .filter(|(key, _)| proposed.iter().any(|(field, _)| field == key))
+            .collect();
+        if proposed.iter().all(|x| current.contains(x)) {
+            continue;
+        }
+
+        proposals.push(TagProposal {
+            file_id: file.id,
+            directory: file.directory,
+            file_name: file.file_name,
+            pattern: pattern.as_str().to_string(),
+            current,
+            proposed,
+        });
+    }
+
+    Ok(proposals)
+}
+
+/// Store reviewed tags of many files and index them again.
+///
+/// Without writing back, the tags only live in the database and a rescan
+/// of a modified file reads the ones in the file again.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `search_db` - The search index the files are indexed again in.
+/// * `lib_path` - The root of the library.
+/// * `tags` - The tags by field of every file, fields left out keep their
+///   value.
+/// * `write_back` - Write the tags into the files as well.
+///
+/// # Returns
+/// * `Result<BulkReport, DbErr>` - The files tagged and the ones that
+///   couldn't be written, those are left untouched in the database too.
+pub async fn apply_path_tags(
+    main_db: &DatabaseConnection,
+    search_db: &mut SearchDbConnection,
+    lib_path: &Path,
+    tags: &[(i32, Vec<(String, String)>)],
+    write_back: bool,
+) -> Result<BulkReport, DbErr> {
+    let mut report = BulkReport::default();
+
+    let files: HashMap<i32, media_files::Model> = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_in(tags.iter().map(|(id, _)| *id)))
+        .filter(media_files::Column::DeletedAt.is_null())
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect();
+
+    let txn = main_db.begin().await?;
+    for (file_id, file_tags) in tags {
+        let Some(file) = files.get(file_id) else {
+            report.failed.push(*file_id);
+            report
+                .errors
+                .insert(*file_id, "The file is not in the library".to_string());
+            continue;
+        };
+        if file_tags.is_empty() {
+            report.skipped.push(*file_id);
+            continue;
+        }
+
+        if write_back {
+            let file_path = lib_path.join(&file.directory).join(&file.file_name);
+            if let Err(e) = write_tags(&file_path, file_tags) {
+                warn!("Unable to write the tags of {}: {}", file_path.display(), e);
+                report.failed.push(*file_id);
+                report.errors.insert(*file_id, e.to_string());
+                continue;
+            }
+        }
+
+        media_metadata::Entity::delete_many()
+            .filter(media_metadata::Column::FileId.eq(*file_id))
+            .filter(
+                media_metadata::Column::MetaKey
+                    .is_in(file_tags.iter().map(|(key, _)| key.as_str())),
+            )
+            .exec(&txn)
+            .await?;
+        media_metadata::Entity::insert_many(file_tags.iter().map(|(key, value)| {
+            media_metadata::ActiveModel {
+                file_id: ActiveValue::Set(*file_id),
+                meta_key: ActiveValue::Set(key.clone()),
+                meta_value: ActiveValue::Set(value.clone()),
+                ..Default::default()
+            }
+        }))
+        .exec(&txn)
+        .await?;
+
+        report.succeeded.push(*file_id);
+    }
+    txn.commit().await?;
+
+    info!("Tagged {} files from their paths", report.succeeded.len());
+    if !report.succeeded.is_empty() {
+        index_media_files(main_db, search_db, report.succeeded.clone()).await?;
+    }
+
+    Ok(report)
+}
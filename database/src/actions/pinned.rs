@@ -0,0 +1,168 @@
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, QueryOrder, TransactionTrait};
+
+use crate::entities::pinned_collections;
+
+use super::search::CollectionType;
+
+fn filter_collection(
+    user_id: i32,
+    collection_type: &CollectionType,
+    collection_id: i32,
+) -> sea_orm::Condition {
+    sea_orm::Condition::all()
+        .add(pinned_collections::Column::UserId.eq(user_id))
+        .add(pinned_collections::Column::CollectionType.eq(i64::from(collection_type.clone())))
+        .add(pinned_collections::Column::CollectionId.eq(collection_id))
+}
+
+/// Get the pinned collections in the order they are shown.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+///
+/// # Returns
+/// * `Result<Vec<(CollectionType, i32)>, DbErr>` - The type and ID of every pinned collection.
+pub async fn get_pinned_collections(
+    db: &DatabaseConnection,
+    user_id: i32,
+) -> Result<Vec<(CollectionType, i32)>, DbErr> {
+    let items = pinned_collections::Entity::find()
+        .filter(pinned_collections::Column::UserId.eq(user_id))
+        .order_by_asc(pinned_collections::Column::Position)
+        .order_by_asc(pinned_collections::Column::Id)
+        .all(db)
+        .await?;
+
+    Ok(items
+        .into_iter()
+        .filter_map(|x| {
+            CollectionType::try_from(x.collection_type)
+                .ok()
+                .map(|collection_type| (collection_type, x.collection_id))
+        })
+        .collect())
+}
+
+/// Pin a collection after the ones already pinned, pinning it again does nothing.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `collection_type` - The type of the collection.
+/// * `collection_id` - The ID of the collection.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the collection is pinned.
+pub async fn pin_collection(
+    db: &DatabaseConnection,
+    user_id: i32,
+    collection_type: CollectionType,
+    collection_id: i32,
+) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+
+    let existing = pinned_collections::Entity::find()
+        .filter(filter_collection(user_id, &collection_type, collection_id))
+        .one(&txn)
+        .await?;
+
+    if existing.is_none() {
+        let position = pinned_collections::Entity::find()
+            .filter(pinned_collections::Column::UserId.eq(user_id))
+            .order_by_desc(pinned_collections::Column::Position)
+            .one(&txn)
+            .await?
+            .map_or(0, |x| x.position + 1);
+
+        pinned_collections::ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            collection_type: ActiveValue::Set(collection_type.into()),
+            collection_id: ActiveValue::Set(collection_id),
+            position: ActiveValue::Set(position),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+    }
+
+    txn.commit().await
+}
+
+/// Unpin a collection.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `collection_type` - The type of the collection.
+/// * `collection_id` - The ID of the collection.
+///
+/// # Returns
+/// * `Result<bool, DbErr>` - Whether the collection was pinned.
+pub async fn unpin_collection(
+    db: &DatabaseConnection,
+    user_id: i32,
+    collection_type: CollectionType,
+    collection_id: i32,
+) -> Result<bool, DbErr> {
+    let result = pinned_collections::Entity::delete_many()
+        .filter(filter_collection(user_id, &collection_type, collection_id))
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected > 0)
+}
+
+/// Change the order of the pinned collections.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `order` - The pinned collections in their new order. Collections left
+///   out keep their relative order after the listed ones, collections that
+///   aren't pinned are ignored.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the new order is stored.
+pub async fn reorder_pinned_collections(
+    db: &DatabaseConnection,
+    user_id: i32,
+    order: &[(CollectionType, i32)],
+) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+
+    let items = pinned_collections::Entity::find()
+        .filter(pinned_collections::Column::UserId.eq(user_id))
+        .order_by_asc(pinned_collections::Column::Position)
+        .order_by_asc(pinned_collections::Column::Id)
+        .all(&txn)
+        .await?;
+
+    let rank = |item: &pinned_collections::Model| {
+        order
+            .iter()
+            .position(|(collection_type, collection_id)| {
+                i64::from(collection_type.clone()) == item.collection_type
+                    && *collection_id == item.collection_id
+            })
+            .unwrap_or(order.len())
+    };
+
+    let mut items: Vec<(usize, pinned_collections::Model)> =
+        items.into_iter().map(|x| (rank(&x), x)).collect();
+    // Stable, so unlisted collections keep their order
+    items.sort_by_key(|(rank, _)| *rank);
+
+    for (position, (_, item)) in items.into_iter().enumerate() {
+        if item.position == position as i32 {
+            continue;
+        }
+
+        let mut item: pinned_collections::ActiveModel = item.into();
+        item.position = ActiveValue::Set(position as i32);
+        item.update(&txn).await?;
+    }
+
+    txn.commit().await
+}
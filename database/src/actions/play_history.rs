@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use sea_orm::prelude::*;
+use sea_orm::sea_query::Alias;
+use sea_orm::{ActiveValue, QueryOrder, QuerySelect};
+
+use crate::entities::user_logs;
+
+/// Listening time after which a track counts as played, shorter tracks
+/// count once half of them was played.
+pub const MIN_PLAYED_SECONDS: f64 = 30.;
+
+/// Whether a track listened up to `position` counts as played.
+pub fn is_played(position: f64, duration: f64) -> bool {
+    position > 0. && position >= MIN_PLAYED_SECONDS.min(duration / 2.)
+}
+
+/// Remember that a file was played.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile that played the file.
+/// * `file_id` - The ID of the file.
+/// * `progress` - The part of the file that was played, between 0 and 1.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the play was stored.
+pub async fn log_play(
+    db: &DatabaseConnection,
+    user_id: i32,
+    file_id: i32,
+    progress: f64,
+) -> Result<(), DbErr> {
+    user_logs::ActiveModel {
+        user_id: ActiveValue::Set(user_id),
+        file_id: ActiveValue::Set(file_id),
+        listen_time: ActiveValue::Set(Utc::now().to_rfc3339()),
+        progress: ActiveValue::Set(progress.clamp(0., 1.)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Get the files played last, each one once.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `limit` - The most files returned.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The IDs of the files, the latest first.
+pub async fn get_recently_played(
+    db: &DatabaseConnection,
+    user_id: i32,
+    limit: usize,
+) -> Result<Vec<i32>, DbErr> {
+    let items: Vec<(i32, String)> = user_logs::Entity::find()
+        .select_only()
+        .column(user_logs::Column::FileId)
+        .column_as(user_logs::Column::ListenTime.max(), "last_played")
+        .filter(user_logs::Column::UserId.eq(user_id))
+        .group_by(user_logs::Column::FileId)
+        .order_by_desc(Expr::col(Alias::new("last_played")))
+        .limit(limit as u64)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    Ok(items.into_iter().map(|(file_id, _)| file_id).collect())
+}
+
+/// Count the plays of every file since a moment.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `since` - Plays before this moment are left out.
+///
+/// # Returns
+/// * `Result<HashMap<i32, usize>, DbErr>` - The number of plays of every played file.
+pub async fn get_play_counts_since(
+    db: &DatabaseConnection,
+    user_id: i32,
+    since: DateTime<Utc>,
+) -> Result<HashMap<i32, usize>, DbErr> {
+    let items: Vec<(i32, i64)> = user_logs::Entity::find()
+        .select_only()
+        .column(user_logs::Column::FileId)
+        .column_as(user_logs::Column::Id.count(), "plays")
+        .filter(user_logs::Column::UserId.eq(user_id))
+        .filter(user_logs::Column::ListenTime.gte(since.to_rfc3339()))
+        .group_by(user_logs::Column::FileId)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    Ok(items
+        .into_iter()
+        .map(|(file_id, plays)| (file_id, plays as usize))
+        .collect())
+}
@@ -0,0 +1,121 @@
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, QueryOrder, TransactionTrait};
+
+use crate::actions::settings::{get_setting, set_setting};
+use crate::actions::users::DEFAULT_USER_ID;
+use crate::entities::{playback_queue, prelude};
+
+const INDEX_KEY: &str = "playback.index";
+const POSITION_KEY: &str = "playback.position";
+
+// The default profile keeps the keys used before profiles existed
+fn user_key(key: &str, user_id: i32) -> String {
+    if user_id == DEFAULT_USER_ID {
+        key.to_string()
+    } else {
+        format!("users.{}.{}", user_id, key)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SavedPlaybackQueue {
+    pub file_ids: Vec<i32>,
+    pub index: Option<usize>,
+    pub position: f32,
+}
+
+/// Persist the playback queue together with the current index and position.
+///
+/// Everything is written in a single transaction, so an interrupted flush
+/// leaves the previously saved queue untouched.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile the queue belongs to.
+/// * `file_ids` - The file IDs in the queue, in playback order.
+/// * `index` - The index of the current track in the queue.
+/// * `position` - The playback position of the current track in seconds.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the queue was saved.
+pub async fn save_playback_queue(
+    main_db: &DatabaseConnection,
+    user_id: i32,
+    file_ids: &[i32],
+    index: Option<usize>,
+    position: f32,
+) -> Result<(), DbErr> {
+    let txn = main_db.begin().await?;
+
+    prelude::PlaybackQueue::delete_many()
+        .filter(playback_queue::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?;
+
+    if !file_ids.is_empty() {
+        let items =
+            file_ids
+                .iter()
+                .enumerate()
+                .map(|(position, file_id)| playback_queue::ActiveModel {
+                    user_id: ActiveValue::Set(user_id),
+                    media_file_id: ActiveValue::Set(*file_id),
+                    position: ActiveValue::Set(position as i32),
+                    ..Default::default()
+                });
+
+        prelude::PlaybackQueue::insert_many(items)
+            .exec(&txn)
+            .await?;
+    }
+
+    let index = index.map(|x| x.to_string()).unwrap_or_default();
+    set_setting(&txn, &user_key(INDEX_KEY, user_id), index).await?;
+    set_setting(&txn, &user_key(POSITION_KEY, user_id), position.to_string()).await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+/// Load the playback queue saved by `save_playback_queue`.
+///
+/// Files that were removed from the library since the queue was saved are
+/// dropped by the foreign key cascade, so the index is clamped to the
+/// remaining queue.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile the queue belongs to.
+///
+/// # Returns
+/// * `Result<SavedPlaybackQueue, DbErr>` - The saved queue, empty if nothing was saved.
+pub async fn get_playback_queue(
+    main_db: &DatabaseConnection,
+    user_id: i32,
+) -> Result<SavedPlaybackQueue, DbErr> {
+    let file_ids: Vec<i32> = prelude::PlaybackQueue::find()
+        .filter(playback_queue::Column::UserId.eq(user_id))
+        .order_by_asc(playback_queue::Column::Position)
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| x.media_file_id)
+        .collect();
+
+    let index = get_setting(main_db, &user_key(INDEX_KEY, user_id))
+        .await?
+        .and_then(|x| x.parse::<usize>().ok())
+        .filter(|x| *x < file_ids.len());
+
+    let position = get_setting(main_db, &user_key(POSITION_KEY, user_id))
+        .await?
+        .and_then(|x| x.parse::<f32>().ok())
+        .unwrap_or(0.0);
+
+    Ok(SavedPlaybackQueue {
+        file_ids,
+        index,
+        position: if index.is_some() { position } else { 0.0 },
+    })
+}
@@ -1,13 +1,16 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use sea_orm::prelude::*;
 use sea_orm::ActiveValue;
+use sea_orm::PaginatorTrait;
 use sea_orm::QueryOrder;
 use sea_orm::QuerySelect;
+use sea_orm::TransactionTrait;
 
 use chrono::Utc;
 
 use crate::actions::search::add_term;
+use crate::actions::search::remove_term;
 use crate::actions::search::CollectionType;
 use crate::connection::SearchDbConnection;
 use crate::entities::{media_file_playlists, playlists};
@@ -17,6 +20,69 @@ use crate::{get_all_ids, get_groups};
 
 use super::utils::CountByFirstLetter;
 
+/// What happens to files that are already in a playlist when they are added
+/// again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Add them anyway.
+    #[default]
+    Allow,
+    /// Leave them out and add the others.
+    Skip,
+    /// Add nothing when any of them is in the playlist, so the user can be asked.
+    Ask,
+}
+
+impl DuplicatePolicy {
+    /// Read the policy from its name, "allow", "skip" or "ask".
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "allow" => Some(DuplicatePolicy::Allow),
+            "skip" => Some(DuplicatePolicy::Skip),
+            "ask" => Some(DuplicatePolicy::Ask),
+            _ => None,
+        }
+    }
+
+    /// Split files into those to add and the duplicates left out.
+    ///
+    /// # Arguments
+    /// * `present` - The files already in the playlist.
+    /// * `file_ids` - The files to add, in order.
+    ///
+    /// # Returns
+    /// * `(Vec<i32>, Vec<i32>)` - The files to add and those left out, files
+    ///   repeated in `file_ids` count as duplicates too.
+    pub fn apply(self, present: &HashSet<i32>, file_ids: &[i32]) -> (Vec<i32>, Vec<i32>) {
+        if self == DuplicatePolicy::Allow {
+            return (file_ids.to_vec(), Vec::new());
+        }
+
+        let mut seen = present.clone();
+        let (added, skipped): (Vec<i32>, Vec<i32>) =
+            file_ids.iter().partition(|x| seen.insert(**x));
+
+        if self == DuplicatePolicy::Ask && !skipped.is_empty() {
+            return (Vec::new(), skipped);
+        }
+        (added, skipped)
+    }
+}
+
+async fn is_in_playlist(
+    db: &DatabaseConnection,
+    playlist_id: i32,
+    media_file_id: i32,
+) -> Result<bool, DbErr> {
+    let count = media_file_playlists::Entity::find()
+        .filter(media_file_playlists::Column::PlaylistId.eq(playlist_id))
+        .filter(media_file_playlists::Column::MediaFileId.eq(media_file_id))
+        .count(db)
+        .await?;
+
+    Ok(count > 0)
+}
+
 impl CountByFirstLetter for playlists::Entity {
     fn group_column() -> Self::Column {
         playlists::Column::Group
@@ -77,7 +143,7 @@ pub async fn create_playlist(
         &name.clone(),
     );
 
-    search_db.w.commit().unwrap();
+    search_db.commit().unwrap();
 
     Ok(inserted_playlist)
 }
@@ -149,11 +215,43 @@ pub async fn update_playlist(
         &updated_playlist.name.clone(),
     );
 
-    search_db.w.commit().unwrap();
+    search_db.commit().unwrap();
 
     Ok(updated_playlist)
 }
 
+/// Delete a playlist and its items, the media files are left alone.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `search_db` - The search index the playlist is taken out of.
+/// * `playlist_id` - The ID of the playlist to delete.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the playlist is gone, including when it didn't exist.
+pub async fn remove_playlist(
+    main_db: &DatabaseConnection,
+    search_db: &mut SearchDbConnection,
+    playlist_id: i32,
+) -> Result<(), DbErr> {
+    let txn = main_db.begin().await?;
+
+    media_file_playlists::Entity::delete_many()
+        .filter(media_file_playlists::Column::PlaylistId.eq(playlist_id))
+        .exec(&txn)
+        .await?;
+    playlists::Entity::delete_by_id(playlist_id)
+        .exec(&txn)
+        .await?;
+
+    txn.commit().await?;
+
+    remove_term(search_db, CollectionType::Playlist, playlist_id);
+    search_db.commit().unwrap();
+
+    Ok(())
+}
+
 /// Check for duplicate items in a playlist.
 ///
 /// # Arguments
@@ -194,18 +292,25 @@ pub async fn check_items_in_playlist(
 /// * `playlist_id` - The ID of the playlist to add the item to.
 /// * `media_file_id` - The ID of the media file to add.
 /// * `position` - The optional position of the media file in the playlist.
+/// * `policy` - What to do if the media file is already in the playlist.
 ///
 /// # Returns
-/// * `Result<Model, Box<dyn std::error::Error>>` - The created media file playlist model or an error.
+/// * `Result<Option<Model>, Box<dyn std::error::Error>>` - The created media file playlist model,
+///   `None` if the policy left the media file out, or an error.
 pub async fn add_item_to_playlist(
     db: &DatabaseConnection,
     playlist_id: i32,
     media_file_id: i32,
     position: Option<i32>,
-) -> Result<media_file_playlists::Model, Box<dyn std::error::Error>> {
+    policy: DuplicatePolicy,
+) -> Result<Option<media_file_playlists::Model>, Box<dyn std::error::Error>> {
     use media_file_playlists::Entity as MediaFilePlaylistEntity;
     use playlists::Entity as PlaylistEntity;
 
+    if policy != DuplicatePolicy::Allow && is_in_playlist(db, playlist_id, media_file_id).await? {
+        return Ok(None);
+    }
+
     // Determine the position to insert the item
     let position = match position {
         Some(pos) => pos,
@@ -242,7 +347,7 @@ pub async fn add_item_to_playlist(
 
     let _ = playlist.update(db).await?;
 
-    Ok(media_file_playlist)
+    Ok(Some(media_file_playlist))
 }
 
 /// Add a media file to a playlist.
@@ -251,16 +356,23 @@ pub async fn add_item_to_playlist(
 /// * `db` - A reference to the database connection.
 /// * `playlist_id` - The ID of the playlist to add the media file to.
 /// * `media_file_id` - The ID of the media file to add.
+/// * `policy` - What to do if the media file is already in the playlist.
 ///
 /// # Returns
-/// * `Result<Model, Box<dyn std::error::Error>>` - The created media file playlist model or an error.
+/// * `Result<Option<Model>, Box<dyn std::error::Error>>` - The created media file playlist model,
+///   `None` if the policy left the media file out, or an error.
 pub async fn add_media_file_to_playlist(
     db: &DatabaseConnection,
     playlist_id: i32,
     media_file_id: i32,
-) -> Result<media_file_playlists::Model, Box<dyn std::error::Error>> {
+    policy: DuplicatePolicy,
+) -> Result<Option<media_file_playlists::Model>, Box<dyn std::error::Error>> {
     use media_file_playlists::Entity as MediaFilePlaylistEntity;
 
+    if policy != DuplicatePolicy::Allow && is_in_playlist(db, playlist_id, media_file_id).await? {
+        return Ok(None);
+    }
+
     // Get the current maximum position in the playlist
     let max_position = MediaFilePlaylistEntity::find()
         .filter(media_file_playlists::Column::PlaylistId.eq(playlist_id))
@@ -280,7 +392,7 @@ pub async fn add_media_file_to_playlist(
     // Insert the new media file playlist into the database
     let media_file_playlist = new_media_file_playlist.insert(db).await?;
 
-    Ok(media_file_playlist)
+    Ok(Some(media_file_playlist))
 }
 
 /// Reorder a media file in a playlist.
@@ -342,3 +454,126 @@ pub async fn get_unique_playlist_groups(
 
     Ok(unique_groups)
 }
+
+/// Get the media files of a playlist in their playing order.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `playlist_id` - The ID of the playlist.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The IDs of the media files, ordered by position.
+pub async fn get_playlist_items(
+    db: &DatabaseConnection,
+    playlist_id: i32,
+) -> Result<Vec<i32>, DbErr> {
+    let items = media_file_playlists::Entity::find()
+        .filter(media_file_playlists::Column::PlaylistId.eq(playlist_id))
+        .order_by_asc(media_file_playlists::Column::Position)
+        .order_by_asc(media_file_playlists::Column::Id)
+        .all(db)
+        .await?;
+
+    Ok(items.into_iter().map(|x| x.media_file_id).collect())
+}
+
+/// Replace every item of a playlist.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `playlist_id` - The ID of the playlist.
+/// * `media_file_ids` - The new items of the playlist, in order.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the playlist was rewritten, it is left untouched otherwise.
+pub async fn replace_playlist_items(
+    db: &DatabaseConnection,
+    playlist_id: i32,
+    media_file_ids: &[i32],
+) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+
+    let playlist = playlists::Entity::find_by_id(playlist_id)
+        .one(&txn)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound(format!("Playlist not found: {}", playlist_id)))?;
+
+    media_file_playlists::Entity::delete_many()
+        .filter(media_file_playlists::Column::PlaylistId.eq(playlist_id))
+        .exec(&txn)
+        .await?;
+
+    let items: Vec<_> = media_file_ids
+        .iter()
+        .enumerate()
+        .map(
+            |(position, media_file_id)| media_file_playlists::ActiveModel {
+                playlist_id: ActiveValue::Set(playlist_id),
+                media_file_id: ActiveValue::Set(*media_file_id),
+                position: ActiveValue::Set(position as i32),
+                ..Default::default()
+            },
+        )
+        .collect();
+    if !items.is_empty() {
+        media_file_playlists::Entity::insert_many(items)
+            .exec(&txn)
+            .await?;
+    }
+
+    let mut playlist: playlists::ActiveModel = playlist.into();
+    playlist.updated_at = ActiveValue::Set(Utc::now().to_rfc3339());
+    playlist.update(&txn).await?;
+
+    txn.commit().await
+}
+
+/// Get the playlists every given media file appears in.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `media_file_ids` - The IDs of the media files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, Vec<playlists::Model>>, DbErr>` - The playlists of every media file,
+///   ordered by name. Files in no playlist are left out.
+pub async fn get_playlists_of_media_files(
+    db: &DatabaseConnection,
+    media_file_ids: &[i32],
+) -> Result<HashMap<i32, Vec<playlists::Model>>, DbErr> {
+    let items = media_file_playlists::Entity::find()
+        .filter(media_file_playlists::Column::MediaFileId.is_in(media_file_ids.to_vec()))
+        .all(db)
+        .await?;
+
+    let playlist_ids: Vec<i32> = items
+        .iter()
+        .map(|x| x.playlist_id)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let playlists: HashMap<i32, playlists::Model> = playlists::Entity::find()
+        .filter(playlists::Column::Id.is_in(playlist_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect();
+
+    let mut result: HashMap<i32, Vec<playlists::Model>> = HashMap::new();
+    for item in items {
+        let Some(playlist) = playlists.get(&item.playlist_id) else {
+            continue;
+        };
+        let entry = result.entry(item.media_file_id).or_default();
+        // A file can be added to the same playlist more than once
+        if !entry.iter().any(|x| x.id == playlist.id) {
+            entry.push(playlist.clone());
+        }
+    }
+    for playlists in result.values_mut() {
+        playlists.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,243 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::Mutex;
+
+use lru::LruCache;
+use sea_orm::prelude::*;
+
+use crate::entities::albums;
+
+use super::cover_art::{get_cover_art_by_id, get_cover_art_palette, sync_cover_art_by_file_id};
+use super::library::get_album_cover_ids;
+use super::track_detail::{get_track_detail, TrackDetail};
+
+/// Number of entries kept by every cache of the hub.
+pub const DEFAULT_QUERY_CACHE_SIZE: usize = 512;
+
+// A cache with a capacity of zero keeps nothing
+struct Lru<K: Hash + Eq, V> {
+    entries: Option<Mutex<LruCache<K, V>>>,
+}
+
+impl<K: Hash + Eq, V: Clone> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        Lru {
+            entries: NonZeroUsize::new(capacity).map(|x| Mutex::new(LruCache::new(x))),
+        }
+    }
+
+    fn get(&self, key: &K) -> Option<V> {
+        let entries = self.entries.as_ref()?;
+        let value = entries.lock().unwrap().get(key).cloned();
+        match value {
+            Some(_) => metrics::CACHE_HITS.increment(),
+            None => metrics::CACHE_MISSES.increment(),
+        }
+        value
+    }
+
+    fn put(&self, key: K, value: V) {
+        if let Some(entries) = &self.entries {
+            entries.lock().unwrap().put(key, value);
+        }
+    }
+
+    fn remove(&self, key: &K) {
+        if let Some(entries) = &self.entries {
+            entries.lock().unwrap().pop(key);
+        }
+    }
+
+    fn clear(&self) {
+        if let Some(entries) = &self.entries {
+            entries.lock().unwrap().clear();
+        }
+    }
+}
+
+/// Keeps the results of queries repeated while scrolling through the library.
+///
+/// Entries are dropped by the least recently used first. Actions writing to
+/// the library must call the matching `invalidate_*` method, or stale results
+/// are served until they are evicted.
+pub struct QueryCache {
+    // Cover art ID of every file, `None` for files without one
+    file_cover_arts: Lru<i32, Option<i32>>,
+    cover_arts: Lru<i32, Option<Vec<u8>>>,
+    palettes: Lru<i32, Vec<u32>>,
+    // Plays and ratings are those of one profile, the one they were read for
+    track_details: Lru<i32, (i32, TrackDetail)>,
+    album_cover_ids: Lru<i32, HashSet<i32>>,
+}
+
+impl QueryCache {
+    /// Create a cache holding up to `capacity` entries of every kind, a
+    /// capacity of zero disables caching.
+    pub fn new(capacity: usize) -> Self {
+        QueryCache {
+            file_cover_arts: Lru::new(capacity),
+            cover_arts: Lru::new(capacity),
+            palettes: Lru::new(capacity),
+            track_details: Lru::new(capacity),
+            album_cover_ids: Lru::new(capacity),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self::new(0)
+    }
+
+    /// Get the cover art of a file, see `sync_cover_art_by_file_id`.
+    pub async fn cover_art_of_file(
+        &self,
+        db: &DatabaseConnection,
+        lib_path: &str,
+        file_id: i32,
+    ) -> Result<Option<(i32, Vec<u8>)>, DbErr> {
+        match self.file_cover_arts.get(&file_id) {
+            Some(None) => return Ok(None),
+            Some(Some(cover_art_id)) => {
+                if let Some(Some(cover_art)) = self.cover_arts.get(&cover_art_id) {
+                    return Ok(Some((cover_art_id, cover_art)));
+                }
+            }
+            None => {}
+        }
+
+        let result = sync_cover_art_by_file_id(db, lib_path, file_id).await?;
+        self.file_cover_arts
+            .put(file_id, result.as_ref().map(|x| x.0));
+        if let Some((cover_art_id, cover_art)) = &result {
+            self.cover_arts.put(*cover_art_id, Some(cover_art.clone()));
+        }
+
+        Ok(result)
+    }
+
+    /// Get a cover art by its ID, see `get_cover_art_by_id`.
+    pub async fn cover_art(
+        &self,
+        db: &DatabaseConnection,
+        cover_art_id: i32,
+    ) -> Result<Option<Vec<u8>>, DbErr> {
+        if let Some(cover_art) = self.cover_arts.get(&cover_art_id) {
+            return Ok(cover_art);
+        }
+
+        let cover_art = get_cover_art_by_id(db, cover_art_id).await?;
+        self.cover_arts.put(cover_art_id, cover_art.clone());
+
+        Ok(cover_art)
+    }
+
+    /// Get the palette of a cover art, see `get_cover_art_palette`.
+    pub async fn cover_art_palette(
+        &self,
+        db: &DatabaseConnection,
+        cover_art_id: i32,
+    ) -> Result<Vec<u32>, DbErr> {
+        if let Some(palette) = self.palettes.get(&cover_art_id) {
+            return Ok(palette);
+        }
+
+        let palette = get_cover_art_palette(db, cover_art_id).await?;
+        self.palettes.put(cover_art_id, palette.clone());
+
+        Ok(palette)
+    }
+
+    /// Get the details of a track, see `get_track_detail`.
+    pub async fn track_detail(
+        &self,
+        main_db: &DatabaseConnection,
+        user_db: &DatabaseConnection,
+        user_id: i32,
+        lib_path: &Path,
+        file_id: i32,
+    ) -> anyhow::Result<TrackDetail> {
+        if let Some((cached_user_id, detail)) = self.track_details.get(&file_id) {
+            if cached_user_id == user_id {
+                return Ok(detail);
+            }
+        }
+
+        let detail = get_track_detail(main_db, user_db, user_id, lib_path, file_id).await?;
+        self.track_details.put(file_id, (user_id, detail.clone()));
+
+        Ok(detail)
+    }
+
+    /// Get the cover IDs of albums, see `get_album_cover_ids`.
+    ///
+    /// Only the albums missing from the cache are queried.
+    pub async fn album_cover_ids(
+        &self,
+        db: &DatabaseConnection,
+        albums: &[albums::Model],
+    ) -> Result<HashMap<i32, HashSet<i32>>, DbErr> {
+        let mut result = HashMap::new();
+        let mut missing = Vec::new();
+        for album in albums {
+            match self.album_cover_ids.get(&album.id) {
+                Some(cover_ids) => {
+                    result.insert(album.id, cover_ids);
+                }
+                None => missing.push(album.clone()),
+            }
+        }
+
+        if !missing.is_empty() {
+            let mut loaded = get_album_cover_ids(db, &missing).await?;
+            for album in missing {
+                let cover_ids = loaded.remove(&album.id).unwrap_or_default();
+                self.album_cover_ids.put(album.id, cover_ids.clone());
+                result.insert(album.id, cover_ids);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Forget everything about files whose tags, cover or analysis changed.
+    ///
+    /// Album covers are aggregated over many files, so all of them are dropped.
+    pub fn invalidate_files(&self, file_ids: &[i32]) {
+        for file_id in file_ids {
+            self.file_cover_arts.remove(file_id);
+            self.track_details.remove(file_id);
+        }
+        self.album_cover_ids.clear();
+    }
+
+    /// Forget the track details after playlists were changed.
+    pub fn invalidate_playlists(&self) {
+        self.track_details.clear();
+    }
+
+    /// Forget the track details after analysis results were written.
+    pub fn invalidate_analysis(&self) {
+        self.track_details.clear();
+    }
+
+    /// Forget all cover arts after some of them were removed.
+    pub fn invalidate_cover_arts(&self) {
+        self.file_cover_arts.clear();
+        self.cover_arts.clear();
+        self.palettes.clear();
+        self.album_cover_ids.clear();
+    }
+
+    /// Forget everything, used after scanning the library.
+    pub fn clear(&self) {
+        self.invalidate_cover_arts();
+        self.track_details.clear();
+    }
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUERY_CACHE_SIZE)
+    }
+}
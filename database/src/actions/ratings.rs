@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveValue, TransactionTrait};
+
+use crate::entities::{prelude, ratings};
+
+/// The highest rating, in stars.
+pub const MAX_RATING: i32 = 5;
+
+/// Get the ratings of files.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, i32>, DbErr>` - The rating of every rated file.
+pub async fn get_ratings(
+    db: &DatabaseConnection,
+    user_id: i32,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, i32>, DbErr> {
+    let items = prelude::Ratings::find()
+        .filter(ratings::Column::UserId.eq(user_id))
+        .filter(ratings::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .all(db)
+        .await?;
+
+    Ok(items
+        .into_iter()
+        .map(|x| (x.media_file_id, x.rating))
+        .collect())
+}
+
+/// Rate files at once, zero removes their rating.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `file_ids` - The IDs of the files.
+/// * `rating` - The rating in stars, clamped to `MAX_RATING`.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if all ratings were stored, none are if one fails.
+pub async fn set_ratings(
+    db: &DatabaseConnection,
+    user_id: i32,
+    file_ids: &[i32],
+    rating: i32,
+) -> Result<(), DbErr> {
+    let rating = rating.clamp(0, MAX_RATING);
+    let txn = db.begin().await?;
+
+    if rating == 0 {
+        prelude::Ratings::delete_many()
+            .filter(ratings::Column::UserId.eq(user_id))
+            .filter(ratings::Column::MediaFileId.is_in(file_ids.to_vec()))
+            .exec(&txn)
+            .await?;
+    } else {
+        for file_id in file_ids {
+            let item = ratings::ActiveModel {
+                user_id: ActiveValue::Set(user_id),
+                media_file_id: ActiveValue::Set(*file_id),
+                rating: ActiveValue::Set(rating),
+                ..Default::default()
+            };
+
+            prelude::Ratings::insert(item)
+                .on_conflict(
+                    OnConflict::columns([ratings::Column::UserId, ratings::Column::MediaFileId])
+                        .update_column(ratings::Column::Rating)
+                        .to_owned(),
+                )
+                .exec(&txn)
+                .await?;
+        }
+    }
+
+    txn.commit().await
+}
+
+/// Put back the ratings of files as they were read by `get_ratings`.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `file_ids` - The IDs of the files, those missing from `file_ratings` end up unrated.
+/// * `file_ratings` - The rating of every rated file.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if all ratings were restored, none are if one fails.
+pub async fn restore_ratings(
+    db: &DatabaseConnection,
+    user_id: i32,
+    file_ids: &[i32],
+    file_ratings: &HashMap<i32, i32>,
+) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+
+    prelude::Ratings::delete_many()
+        .filter(ratings::Column::UserId.eq(user_id))
+        .filter(ratings::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .exec(&txn)
+        .await?;
+
+    let items: Vec<_> = file_ids
+        .iter()
+        .filter_map(|file_id| {
+            file_ratings
+                .get(file_id)
+                .map(|rating| ratings::ActiveModel {
+                    user_id: ActiveValue::Set(user_id),
+                    media_file_id: ActiveValue::Set(*file_id),
+                    rating: ActiveValue::Set(*rating),
+                    ..Default::default()
+                })
+        })
+        .collect();
+    if !items.is_empty() {
+        prelude::Ratings::insert_many(items).exec(&txn).await?;
+    }
+
+    txn.commit().await
+}
@@ -0,0 +1,280 @@
+use chrono::Utc;
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, QueryOrder, QuerySelect, TransactionTrait};
+
+use crate::entities::{recent_context_items, recent_contexts};
+
+/// How many contexts are kept for every profile, older ones are forgotten.
+pub const RECENT_CONTEXTS_LIMIT: usize = 12;
+
+/// Something played from start to end as a whole, with the queue it left
+/// behind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecentContext {
+    pub id: i32,
+    /// "artist", "album", "playlist", or "track_mix", "artist_mix",
+    /// "album_mix" and "playlist_mix" for mixes seeded by them, "auto_dj" for
+    /// auto-DJ queues seeded by a track.
+    pub context_type: String,
+    pub context_id: i32,
+    pub file_ids: Vec<i32>,
+    pub index: Option<usize>,
+    /// The playback position of the current track in seconds.
+    pub position: f64,
+    pub played_at: String,
+}
+
+async fn get_items<C>(db: &C, recent_context_id: i32) -> Result<Vec<i32>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    Ok(recent_context_items::Entity::find()
+        .filter(recent_context_items::Column::RecentContextId.eq(recent_context_id))
+        .order_by_asc(recent_context_items::Column::Position)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|x| x.media_file_id)
+        .collect())
+}
+
+async fn set_items<C>(db: &C, recent_context_id: i32, file_ids: &[i32]) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    recent_context_items::Entity::delete_many()
+        .filter(recent_context_items::Column::RecentContextId.eq(recent_context_id))
+        .exec(db)
+        .await?;
+
+    if file_ids.is_empty() {
+        return Ok(());
+    }
+
+    let items =
+        file_ids
+            .iter()
+            .enumerate()
+            .map(|(position, file_id)| recent_context_items::ActiveModel {
+                recent_context_id: ActiveValue::Set(recent_context_id),
+                media_file_id: ActiveValue::Set(*file_id),
+                position: ActiveValue::Set(position as i32),
+                ..Default::default()
+            });
+    recent_context_items::Entity::insert_many(items)
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+async fn to_recent_context<C>(db: &C, model: recent_contexts::Model) -> Result<RecentContext, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let file_ids = get_items(db, model.id).await?;
+    // Files removed from the library are dropped by the foreign key cascade
+    let index = model
+        .current_index
+        .and_then(|x| usize::try_from(x).ok())
+        .filter(|x| *x < file_ids.len());
+
+    Ok(RecentContext {
+        id: model.id,
+        context_type: model.context_type,
+        context_id: model.context_id,
+        file_ids,
+        index,
+        position: if index.is_some() { model.position } else { 0.0 },
+        played_at: model.played_at,
+    })
+}
+
+async fn get_latest_model<C>(db: &C, user_id: i32) -> Result<Option<recent_contexts::Model>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    recent_contexts::Entity::find()
+        .filter(recent_contexts::Column::UserId.eq(user_id))
+        .order_by_desc(recent_contexts::Column::PlayedAt)
+        .order_by_desc(recent_contexts::Column::Id)
+        .one(db)
+        .await
+}
+
+/// Remember that a context started playing, replacing what was remembered
+/// of it before. The oldest contexts beyond `RECENT_CONTEXTS_LIMIT` are
+/// forgotten.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `context_type` - The kind of context, see `RecentContext`.
+/// * `context_id` - The ID of the collection or file the context is made of.
+/// * `file_ids` - The files queued for the context, in playback order.
+///
+/// # Returns
+/// * `Result<i32, DbErr>` - The ID of the remembered context.
+pub async fn record_recent_context(
+    db: &DatabaseConnection,
+    user_id: i32,
+    context_type: &str,
+    context_id: i32,
+    file_ids: &[i32],
+) -> Result<i32, DbErr> {
+    let txn = db.begin().await?;
+
+    recent_contexts::Entity::delete_many()
+        .filter(recent_contexts::Column::UserId.eq(user_id))
+        .filter(recent_contexts::Column::ContextType.eq(context_type))
+        .filter(recent_contexts::Column::ContextId.eq(context_id))
+        .exec(&txn)
+        .await?;
+
+    let context = recent_contexts::ActiveModel {
+        user_id: ActiveValue::Set(user_id),
+        context_type: ActiveValue::Set(context_type.to_string()),
+        context_id: ActiveValue::Set(context_id),
+        current_index: ActiveValue::Set((!file_ids.is_empty()).then_some(0)),
+        position: ActiveValue::Set(0.0),
+        played_at: ActiveValue::Set(Utc::now().to_rfc3339()),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    set_items(&txn, context.id, file_ids).await?;
+
+    let outdated: Vec<i32> = recent_contexts::Entity::find()
+        .select_only()
+        .column(recent_contexts::Column::Id)
+        .filter(recent_contexts::Column::UserId.eq(user_id))
+        .order_by_desc(recent_contexts::Column::PlayedAt)
+        .order_by_desc(recent_contexts::Column::Id)
+        // SQLite only takes an offset after a limit
+        .limit(i64::MAX as u64)
+        .offset(RECENT_CONTEXTS_LIMIT as u64)
+        .into_tuple()
+        .all(&txn)
+        .await?;
+    if !outdated.is_empty() {
+        recent_contexts::Entity::delete_many()
+            .filter(recent_contexts::Column::Id.is_in(outdated))
+            .exec(&txn)
+            .await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(context.id)
+}
+
+/// Save where playback is in the most recent context of a profile.
+///
+/// The queue is saved as it is, with the changes made to it since the
+/// context started. Nothing is saved when the current track isn't part of
+/// the context, the queue was then replaced by something else.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `file_ids` - The file IDs in the queue, in playback order.
+/// * `index` - The index of the current track in the queue.
+/// * `position` - The playback position of the current track in seconds.
+///
+/// # Returns
+/// * `Result<bool, DbErr>` - Whether the progress was saved.
+pub async fn save_context_progress(
+    db: &DatabaseConnection,
+    user_id: i32,
+    file_ids: &[i32],
+    index: Option<usize>,
+    position: f64,
+) -> Result<bool, DbErr> {
+    let Some(current) = index.and_then(|x| file_ids.get(x)) else {
+        return Ok(false);
+    };
+
+    let txn = db.begin().await?;
+
+    let Some(latest) = get_latest_model(&txn, user_id).await? else {
+        return Ok(false);
+    };
+    if !get_items(&txn, latest.id).await?.contains(current) {
+        return Ok(false);
+    }
+
+    set_items(&txn, latest.id, file_ids).await?;
+    let mut latest: recent_contexts::ActiveModel = latest.into();
+    latest.current_index = ActiveValue::Set(index.map(|x| x as i32));
+    latest.position = ActiveValue::Set(position);
+    latest.update(&txn).await?;
+
+    txn.commit().await?;
+
+    Ok(true)
+}
+
+/// Get the contexts a profile played most recently.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `limit` - The maximum number of contexts.
+///
+/// # Returns
+/// * `Result<Vec<RecentContext>, DbErr>` - The contexts, the most recent first.
+pub async fn get_recent_contexts(
+    db: &DatabaseConnection,
+    user_id: i32,
+    limit: usize,
+) -> Result<Vec<RecentContext>, DbErr> {
+    let models = recent_contexts::Entity::find()
+        .filter(recent_contexts::Column::UserId.eq(user_id))
+        .order_by_desc(recent_contexts::Column::PlayedAt)
+        .order_by_desc(recent_contexts::Column::Id)
+        .limit(limit as u64)
+        .all(db)
+        .await?;
+
+    let mut result = Vec::with_capacity(models.len());
+    for model in models {
+        result.push(to_recent_context(db, model).await?);
+    }
+
+    Ok(result)
+}
+
+/// Get a context to resume it, which makes it the most recent one.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `user_id` - The ID of the profile.
+/// * `id` - The ID of the context.
+///
+/// # Returns
+/// * `Result<Option<RecentContext>, DbErr>` - The context, `None` if the
+///   profile has no such context.
+pub async fn resume_recent_context(
+    db: &DatabaseConnection,
+    user_id: i32,
+    id: i32,
+) -> Result<Option<RecentContext>, DbErr> {
+    let txn = db.begin().await?;
+
+    let Some(model) = recent_contexts::Entity::find_by_id(id)
+        .filter(recent_contexts::Column::UserId.eq(user_id))
+        .one(&txn)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let mut active: recent_contexts::ActiveModel = model.into();
+    active.played_at = ActiveValue::Set(Utc::now().to_rfc3339());
+    let model = active.update(&txn).await?;
+    let context = to_recent_context(&txn, model).await?;
+
+    txn.commit().await?;
+
+    Ok(Some(context))
+}
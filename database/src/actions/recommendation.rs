@@ -1,15 +1,141 @@
 use arroy::distances::Euclidean;
 use arroy::{Reader, Writer};
+use heed::RoTxn;
 use rand::rngs::StdRng;
 use rand::SeedableRng;
 use sea_orm::entity::prelude::*;
 use std::collections::HashSet;
 use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
 
 use crate::connection::{MainDbConnection, RecommendationDbConnection};
+use crate::entities::media_analysis;
 
 use super::analysis::AggregatedAnalysisResult;
 
+const N_TREES_KEY: &str = "n_trees";
+const SEARCH_K_FACTOR_KEY: &str = "search_k_factor";
+
+pub const DEFAULT_SEARCH_K_FACTOR: usize = 15;
+
+/// How the recommendation index is built and searched.
+///
+/// The index is a forest of random projection trees: more trees cost disk
+/// space and build time, a larger search factor costs query time, and both
+/// find the true neighbours more often.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexParameters {
+    /// Number of trees to build, `None` lets arroy pick one from the size of
+    /// the library.
+    pub n_trees: Option<usize>,
+    /// Nodes looked at per tree and per requested recommendation.
+    pub search_k_factor: usize,
+}
+
+impl Default for IndexParameters {
+    fn default() -> Self {
+        IndexParameters {
+            n_trees: None,
+            search_k_factor: DEFAULT_SEARCH_K_FACTOR,
+        }
+    }
+}
+
+fn read_index_parameters(
+    db_conn: &RecommendationDbConnection,
+    rtxn: &RoTxn,
+) -> Result<IndexParameters, Box<dyn std::error::Error>> {
+    let Some(parameters) = db_conn.parameters else {
+        return Ok(IndexParameters::default());
+    };
+
+    let n_trees = parameters
+        .get(rtxn, N_TREES_KEY)?
+        .filter(|x| *x > 0)
+        .map(|x| x as usize);
+    let search_k_factor = parameters
+        .get(rtxn, SEARCH_K_FACTOR_KEY)?
+        .filter(|x| *x > 0)
+        .map_or(DEFAULT_SEARCH_K_FACTOR, |x| x as usize);
+
+    Ok(IndexParameters {
+        n_trees,
+        search_k_factor,
+    })
+}
+
+fn search_k(
+    reader: &Reader<Euclidean>,
+    parameters: &IndexParameters,
+    n: usize,
+) -> Result<NonZeroUsize, Box<dyn std::error::Error>> {
+    Ok(
+        NonZeroUsize::new(n * reader.n_trees() * parameters.search_k_factor)
+            .ok_or("Failed to create NonZeroUsize from search_k")?,
+    )
+}
+
+/// Get the parameters of the recommendation index.
+///
+/// # Arguments
+/// * `db_conn` - The tuple containing the LMDB environment and the Arroy database.
+///
+/// # Returns
+/// * `Result<IndexParameters, Box<dyn std::error::Error>>` - The stored parameters, or the defaults.
+pub fn get_index_parameters(
+    db_conn: &RecommendationDbConnection,
+) -> Result<IndexParameters, Box<dyn std::error::Error>> {
+    let rtxn = db_conn.env.read_txn()?;
+
+    read_index_parameters(db_conn, &rtxn)
+}
+
+/// Store the parameters of the recommendation index.
+///
+/// Trees already built are never split again, so changing their number
+/// clears the index. It has to be synced afterwards.
+///
+/// # Arguments
+/// * `db_conn` - The tuple containing the LMDB environment and the Arroy database.
+/// * `parameters` - The new parameters.
+///
+/// # Returns
+/// * `Result<bool, Box<dyn std::error::Error>>` - Whether the index was cleared.
+pub fn set_index_parameters(
+    db_conn: &RecommendationDbConnection,
+    parameters: IndexParameters,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let parameters_db = db_conn
+        .parameters
+        .ok_or("The recommendation database is read-only")?;
+    if parameters.search_k_factor == 0 || parameters.n_trees == Some(0) {
+        return Err("Index parameters must be greater than zero".into());
+    }
+
+    let mut wtxn = db_conn.env.write_txn()?;
+    let previous = read_index_parameters(db_conn, &wtxn)?;
+
+    parameters_db.put(
+        &mut wtxn,
+        N_TREES_KEY,
+        &(parameters.n_trees.unwrap_or(0) as u64),
+    )?;
+    parameters_db.put(
+        &mut wtxn,
+        SEARCH_K_FACTOR_KEY,
+        &(parameters.search_k_factor as u64),
+    )?;
+
+    let rebuild = previous.n_trees != parameters.n_trees;
+    if rebuild {
+        Writer::<Euclidean>::new(db_conn.db, 0, 17).clear(&mut wtxn)?;
+    }
+
+    wtxn.commit()?;
+
+    Ok(rebuild)
+}
+
 /// Get recommendations for a given item.
 ///
 /// # Arguments
@@ -28,8 +154,8 @@ pub fn get_recommendation_by_file_id(
     let db = db_conn.db;
     let rtxn = env.read_txn()?;
     let reader = Reader::<Euclidean>::open(&rtxn, 0, db)?;
-    let search_k = NonZeroUsize::new(n * reader.n_trees() * 15)
-        .ok_or("Failed to create NonZeroUsize from search_k")?;
+    let parameters = read_index_parameters(db_conn, &rtxn)?;
+    let search_k = search_k(&reader, &parameters, n)?;
 
     let item_id: u32 = item_id
         .try_into()
@@ -56,13 +182,6 @@ pub fn get_recommendation_by_parameter(
     parameter: AggregatedAnalysisResult,
     n: usize,
 ) -> Result<Vec<(u32, f32)>, Box<dyn std::error::Error>> {
-    let env = db_conn.env.clone();
-    let db = db_conn.db;
-    let rtxn = env.read_txn()?;
-    let reader = Reader::<Euclidean>::open(&rtxn, 0, db)?;
-    let search_k = NonZeroUsize::new(n * reader.n_trees() * 15)
-        .ok_or("Failed to create NonZeroUsize from search_k")?;
-
     let feature_vector: Vec<f32> = vec![
         parameter.spectral_centroid,
         parameter.spectral_flatness,
@@ -88,7 +207,7 @@ pub fn get_recommendation_by_parameter(
     .map(|x| x as f32)
     .collect();
 
-    let results = reader.nns_by_vector(&rtxn, &feature_vector, n, Some(search_k), None)?;
+    let results = get_recommendation_by_vector(db_conn, &feature_vector, n)?;
 
     if results.is_empty() {
         Err("No results found for the given parameter".into())
@@ -97,6 +216,53 @@ pub fn get_recommendation_by_parameter(
     }
 }
 
+/// Get the items closest to a feature vector.
+///
+/// # Arguments
+/// * `db_conn` - The tuple containing the LMDB environment and the Arroy database.
+/// * `vector` - The feature vector, laid out like `analysis_vector`.
+/// * `n` - The number of recommendations to retrieve.
+///
+/// # Returns
+/// * `Result<Vec<(u32, f32)>, Box<dyn std::error::Error>>` - A vector of recommended item IDs and their distances.
+pub fn get_recommendation_by_vector(
+    db_conn: &RecommendationDbConnection,
+    vector: &[f32],
+    n: usize,
+) -> Result<Vec<(u32, f32)>, Box<dyn std::error::Error>> {
+    let rtxn = db_conn.env.read_txn()?;
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, db_conn.db)?;
+    let parameters = read_index_parameters(db_conn, &rtxn)?;
+    let search_k = search_k(&reader, &parameters, n)?;
+
+    Ok(reader.nns_by_vector(&rtxn, vector, n, Some(search_k), None)?)
+}
+
+/// The feature vector of an analysed file in the recommendation database.
+pub fn analysis_vector(analysis: &media_analysis::Model) -> Vec<f32> {
+    vec![
+        analysis.spectral_centroid.unwrap_or(0.0) as f32,
+        analysis.spectral_flatness.unwrap_or(0.0) as f32,
+        analysis.spectral_slope.unwrap_or(0.0) as f32,
+        analysis.spectral_rolloff.unwrap_or(0.0) as f32,
+        analysis.spectral_spread.unwrap_or(0.0) as f32,
+        // analysis.spectral_skewness.unwrap_or(0.0) as f32,
+        // analysis.spectral_kurtosis.unwrap_or(0.0) as f32,
+        analysis.chroma0.unwrap_or(0.0) as f32,
+        analysis.chroma1.unwrap_or(0.0) as f32,
+        analysis.chroma2.unwrap_or(0.0) as f32,
+        analysis.chroma3.unwrap_or(0.0) as f32,
+        analysis.chroma4.unwrap_or(0.0) as f32,
+        analysis.chroma5.unwrap_or(0.0) as f32,
+        analysis.chroma6.unwrap_or(0.0) as f32,
+        analysis.chroma7.unwrap_or(0.0) as f32,
+        analysis.chroma8.unwrap_or(0.0) as f32,
+        analysis.chroma9.unwrap_or(0.0) as f32,
+        analysis.chroma10.unwrap_or(0.0) as f32,
+        analysis.chroma11.unwrap_or(0.0) as f32,
+    ]
+}
+
 /// Sync the recommendation database with the analysis data.
 ///
 /// # Arguments
@@ -113,9 +279,7 @@ pub async fn sync_recommendation(
     let arroy_db = db_conn.db;
 
     // Fetch all analysis data
-    let analyses = crate::entities::media_analysis::Entity::find()
-        .all(db)
-        .await?;
+    let analyses = media_analysis::Entity::find().all(db).await?;
 
     // Track existing IDs in the main database
     let mut existing_ids: HashSet<i32> = HashSet::new();
@@ -125,31 +289,12 @@ pub async fn sync_recommendation(
 
     // Open a write transaction for the recommendation database
     let mut wtxn = env.write_txn()?;
+    let parameters = read_index_parameters(db_conn, &wtxn)?;
     let writer = Writer::<Euclidean>::new(arroy_db, 0, 17); // Assuming 17 dimensions for the analysis data
 
     // Insert or update analysis data in the recommendation database
     for analysis in analyses {
-        let vector = vec![
-            analysis.spectral_centroid.unwrap_or(0.0) as f32,
-            analysis.spectral_flatness.unwrap_or(0.0) as f32,
-            analysis.spectral_slope.unwrap_or(0.0) as f32,
-            analysis.spectral_rolloff.unwrap_or(0.0) as f32,
-            analysis.spectral_spread.unwrap_or(0.0) as f32,
-            // analysis.spectral_skewness.unwrap_or(0.0) as f32,
-            // analysis.spectral_kurtosis.unwrap_or(0.0) as f32,
-            analysis.chroma0.unwrap_or(0.0) as f32,
-            analysis.chroma1.unwrap_or(0.0) as f32,
-            analysis.chroma2.unwrap_or(0.0) as f32,
-            analysis.chroma3.unwrap_or(0.0) as f32,
-            analysis.chroma4.unwrap_or(0.0) as f32,
-            analysis.chroma5.unwrap_or(0.0) as f32,
-            analysis.chroma6.unwrap_or(0.0) as f32,
-            analysis.chroma7.unwrap_or(0.0) as f32,
-            analysis.chroma8.unwrap_or(0.0) as f32,
-            analysis.chroma9.unwrap_or(0.0) as f32,
-            analysis.chroma10.unwrap_or(0.0) as f32,
-            analysis.chroma11.unwrap_or(0.0) as f32,
-        ];
+        let vector = analysis_vector(&analysis);
         writer.add_item(
             &mut wtxn,
             (analysis.file_id as usize).try_into().unwrap(),
@@ -159,7 +304,7 @@ pub async fn sync_recommendation(
 
     // Build the index
     let mut rng = StdRng::seed_from_u64(42);
-    writer.build(&mut wtxn, &mut rng, None)?;
+    writer.build(&mut wtxn, &mut rng, parameters.n_trees)?;
 
     // Commit the transaction
     wtxn.commit()?;
@@ -178,3 +323,149 @@ pub async fn sync_recommendation(
 
     Ok(())
 }
+
+/// Add the analysis of a single file to the recommendation database.
+///
+/// Only the trees touched by the new item are rebuilt, so a file analysed on
+/// demand can be recommended from without syncing the whole library.
+///
+/// # Arguments
+/// * `db_conn` - The tuple containing the LMDB environment and the Arroy database.
+/// * `analysis` - The analysis of the file.
+///
+/// # Returns
+/// * `Result<(), Box<dyn std::error::Error>>` - A result indicating success or failure.
+pub fn add_to_recommendation(
+    db_conn: &RecommendationDbConnection,
+    analysis: &media_analysis::Model,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let item_id: u32 = analysis
+        .file_id
+        .try_into()
+        .map_err(|_| "Failed to convert file_id to u32")?;
+
+    let mut wtxn = db_conn.env.write_txn()?;
+    let parameters = read_index_parameters(db_conn, &wtxn)?;
+    let writer = Writer::<Euclidean>::new(db_conn.db, 0, 17);
+    writer.add_item(&mut wtxn, item_id, &analysis_vector(analysis))?;
+
+    let mut rng = StdRng::seed_from_u64(42);
+    writer.build(&mut wtxn, &mut rng, parameters.n_trees)?;
+    wtxn.commit()?;
+
+    Ok(())
+}
+
+/// Recall and speed of the recommendation index with one search factor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexBenchmark {
+    pub search_k_factor: usize,
+    /// Part of the true nearest neighbours found, from 0 to 1.
+    pub recall: f32,
+    /// Average time of one query.
+    pub latency: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexBenchmarkReport {
+    /// Trees in the index as it is built now.
+    pub n_trees: usize,
+    pub n_items: u64,
+    pub results: Vec<IndexBenchmark>,
+}
+
+fn euclidean_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// Compare the index against an exhaustive search over the library, for
+/// every given search factor.
+///
+/// Queries start from library items spread evenly over the index. Trying
+/// another number of trees needs the index to be rebuilt first.
+///
+/// # Arguments
+/// * `db_conn` - The tuple containing the LMDB environment and the Arroy database.
+/// * `search_k_factors` - The search factors to try.
+/// * `samples` - The number of queries per search factor.
+/// * `n` - The number of recommendations per query.
+///
+/// # Returns
+/// * `Result<IndexBenchmarkReport, Box<dyn std::error::Error>>` - The recall and latency of every search factor.
+pub fn benchmark_index(
+    db_conn: &RecommendationDbConnection,
+    search_k_factors: &[usize],
+    samples: usize,
+    n: usize,
+) -> Result<IndexBenchmarkReport, Box<dyn std::error::Error>> {
+    let rtxn = db_conn.env.read_txn()?;
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, db_conn.db)?;
+
+    let mut vectors = Vec::new();
+    for id in reader.item_ids() {
+        if let Some(vector) = reader.item_vector(&rtxn, id)? {
+            vectors.push((id, vector));
+        }
+    }
+
+    let step = (vectors.len() / samples.max(1)).max(1);
+    let queries: Vec<&(u32, Vec<f32>)> = vectors.iter().step_by(step).take(samples).collect();
+
+    let exact: Vec<HashSet<u32>> = queries
+        .iter()
+        .map(|(_, query)| {
+            let mut distances: Vec<(u32, f32)> = vectors
+                .iter()
+                .map(|(id, vector)| (*id, euclidean_distance(query, vector)))
+                .collect();
+            distances.sort_by(|a, b| a.1.total_cmp(&b.1));
+            distances.into_iter().take(n).map(|(id, _)| id).collect()
+        })
+        .collect();
+
+    let mut results = Vec::new();
+    for &search_k_factor in search_k_factors {
+        let parameters = IndexParameters {
+            n_trees: None,
+            search_k_factor,
+        };
+        let search_k = search_k(&reader, &parameters, n)?;
+
+        let mut found = 0;
+        let mut expected = 0;
+        let mut elapsed = Duration::ZERO;
+        for ((id, _), neighbours) in queries.iter().zip(&exact) {
+            let start = Instant::now();
+            let approximate = reader
+                .nns_by_item(&rtxn, *id, n, Some(search_k), None)?
+                .unwrap_or_default();
+            elapsed += start.elapsed();
+
+            found += approximate
+                .iter()
+                .filter(|(x, _)| neighbours.contains(x))
+                .count();
+            expected += neighbours.len();
+        }
+
+        results.push(IndexBenchmark {
+            search_k_factor,
+            recall: if expected == 0 {
+                1.0
+            } else {
+                found as f32 / expected as f32
+            },
+            latency: elapsed / queries.len().max(1) as u32,
+        });
+    }
+
+    Ok(IndexBenchmarkReport {
+        n_trees: reader.n_trees(),
+        n_items: reader.n_items(),
+        results,
+    })
+}
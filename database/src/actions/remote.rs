@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, Condition, QueryOrder, QuerySelect, TransactionTrait};
+
+use crate::entities::{remote_files, remote_servers};
+
+use super::metadata::MetadataSummary;
+
+// Rows written per statement, SQLite limits the number of bound values
+const INSERT_CHUNK_SIZE: usize = 500;
+
+/// Remote files are queued with negative IDs, so they never collide with
+/// the files of the library.
+pub fn to_queue_id(remote_file_id: i32) -> i32 {
+    -remote_file_id
+}
+
+/// The ID of the remote file behind a queued item, `None` for files of the library.
+pub fn from_queue_id(queue_id: i32) -> Option<i32> {
+    (queue_id < 0).then_some(-queue_id)
+}
+
+/// The files of the library in a queue, to save it without the remote files
+/// the library database can't reference.
+///
+/// # Arguments
+/// * `queue_ids` - The IDs in the queue, see `to_queue_id`.
+/// * `index` - The index of the current item in the queue.
+///
+/// # Returns
+/// * `(Vec<i32>, Option<usize>)` - The file IDs and the index of the current
+///   item among them, or of the next file of the library if it was remote.
+pub fn without_remote_files(queue_ids: &[i32], index: Option<usize>) -> (Vec<i32>, Option<usize>) {
+    let is_local = |x: &&i32| from_queue_id(**x).is_none();
+    let file_ids: Vec<i32> = queue_ids.iter().filter(is_local).copied().collect();
+    let index = index
+        .map(|x| {
+            queue_ids[..x.min(queue_ids.len())]
+                .iter()
+                .filter(is_local)
+                .count()
+        })
+        .filter(|x| *x < file_ids.len());
+
+    (file_ids, index)
+}
+
+/// A Subsonic or OpenSubsonic server used as a library source.
+///
+/// # Arguments
+/// * `user_db` - The database holding the remote library.
+/// * `name` - A name for the server, unique.
+/// * `url` - The address of the server, without `/rest`.
+/// * `username` - The user to log in with.
+/// * `password` - The password of the user, kept to sign every request.
+///
+/// # Returns
+/// * `Result<remote_servers::Model, DbErr>` - The new server.
+pub async fn add_remote_server(
+    user_db: &DatabaseConnection,
+    name: &str,
+    url: &str,
+    username: &str,
+    password: &str,
+) -> Result<remote_servers::Model, DbErr> {
+    remote_servers::ActiveModel {
+        name: ActiveValue::Set(name.to_string()),
+        url: ActiveValue::Set(url.trim_end_matches('/').to_string()),
+        username: ActiveValue::Set(username.to_string()),
+        password: ActiveValue::Set(password.to_string()),
+        ..Default::default()
+    }
+    .insert(user_db)
+    .await
+}
+
+pub async fn get_remote_servers(
+    user_db: &DatabaseConnection,
+) -> Result<Vec<remote_servers::Model>, DbErr> {
+    remote_servers::Entity::find()
+        .order_by_asc(remote_servers::Column::Id)
+        .all(user_db)
+        .await
+}
+
+pub async fn get_remote_server_by_id(
+    user_db: &DatabaseConnection,
+    server_id: i32,
+) -> Result<Option<remote_servers::Model>, DbErr> {
+    remote_servers::Entity::find_by_id(server_id)
+        .one(user_db)
+        .await
+}
+
+/// Forget a server and the files cached from it.
+pub async fn remove_remote_server(
+    user_db: &DatabaseConnection,
+    server_id: i32,
+) -> Result<(), DbErr> {
+    remote_files::Entity::delete_many()
+        .filter(remote_files::Column::ServerId.eq(server_id))
+        .exec(user_db)
+        .await?;
+    remote_servers::Entity::delete_by_id(server_id)
+        .exec(user_db)
+        .await?;
+
+    Ok(())
+}
+
+/// A song as a remote server describes it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RemoteFileRecord {
+    pub remote_id: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub remote_album_id: Option<String>,
+    pub track_number: Option<i32>,
+    pub year: Option<i32>,
+    pub duration: f64,
+    pub extension: String,
+    pub cover_art: Option<String>,
+}
+
+/// Store what a server holds, in place of what was cached before.
+///
+/// Songs keep their local ID across syncs as long as the server keeps
+/// their remote ID, so queued remote files stay valid.
+///
+/// # Arguments
+/// * `user_db` - The database holding the remote library.
+/// * `server_id` - The ID of the server.
+/// * `records` - Every song on the server.
+///
+/// # Returns
+/// * `Result<usize, DbErr>` - The number of songs now cached.
+pub async fn replace_remote_files(
+    user_db: &DatabaseConnection,
+    server_id: i32,
+    records: Vec<RemoteFileRecord>,
+) -> Result<usize, DbErr> {
+    let txn = user_db.begin().await?;
+
+    let existing: HashMap<String, i32> = remote_files::Entity::find()
+        .filter(remote_files::Column::ServerId.eq(server_id))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|x| (x.remote_id, x.id))
+        .collect();
+    remote_files::Entity::delete_many()
+        .filter(remote_files::Column::ServerId.eq(server_id))
+        .exec(&txn)
+        .await?;
+
+    let mut seen = HashMap::new();
+    let models: Vec<remote_files::ActiveModel> = records
+        .into_iter()
+        // Servers may list a song on more than one album
+        .filter(|x| seen.insert(x.remote_id.clone(), ()).is_none())
+        .map(|x| remote_files::ActiveModel {
+            id: match existing.get(&x.remote_id) {
+                Some(id) => ActiveValue::Set(*id),
+                None => ActiveValue::NotSet,
+            },
+            server_id: ActiveValue::Set(server_id),
+            remote_id: ActiveValue::Set(x.remote_id),
+            title: ActiveValue::Set(x.title),
+            artist: ActiveValue::Set(x.artist),
+            album: ActiveValue::Set(x.album),
+            remote_album_id: ActiveValue::Set(x.remote_album_id),
+            track_number: ActiveValue::Set(x.track_number),
+            year: ActiveValue::Set(x.year),
+            duration: ActiveValue::Set(x.duration),
+            extension: ActiveValue::Set(x.extension),
+            cover_art: ActiveValue::Set(x.cover_art),
+        })
+        .collect();
+
+    let count = models.len();
+    // Rows with and without an ID can't share a statement
+    let (kept, new): (Vec<_>, Vec<_>) = models.into_iter().partition(|x| x.id.is_set());
+    for chunk in [kept, new].iter().flat_map(|x| x.chunks(INSERT_CHUNK_SIZE)) {
+        remote_files::Entity::insert_many(chunk.to_vec())
+            .exec(&txn)
+            .await?;
+    }
+
+    remote_servers::ActiveModel {
+        id: ActiveValue::Unchanged(server_id),
+        synced_at: ActiveValue::Set(Some(Utc::now().to_rfc3339())),
+        ..Default::default()
+    }
+    .update(&txn)
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(count)
+}
+
+/// Get a page of the files cached from a server, by artist, album and track.
+///
+/// # Arguments
+/// * `user_db` - The database holding the remote library.
+/// * `server_id` - The ID of the server.
+/// * `query` - Only files with it in their title, artist or album, if not empty.
+/// * `cursor` - The page to get.
+/// * `page_size` - The number of files in a page.
+pub async fn get_remote_files(
+    user_db: &DatabaseConnection,
+    server_id: i32,
+    query: &str,
+    cursor: usize,
+    page_size: usize,
+) -> Result<Vec<remote_files::Model>, DbErr> {
+    let mut condition = Condition::all().add(remote_files::Column::ServerId.eq(server_id));
+    let query = query.trim();
+    if !query.is_empty() {
+        condition = condition.add(
+            Condition::any()
+                .add(remote_files::Column::Title.contains(query))
+                .add(remote_files::Column::Artist.contains(query))
+                .add(remote_files::Column::Album.contains(query)),
+        );
+    }
+
+    remote_files::Entity::find()
+        .filter(condition)
+        .order_by_asc(remote_files::Column::Artist)
+        .order_by_asc(remote_files::Column::Album)
+        .order_by_asc(remote_files::Column::TrackNumber)
+        .order_by_asc(remote_files::Column::Id)
+        .offset((cursor * page_size) as u64)
+        .limit(page_size as u64)
+        .all(user_db)
+        .await
+}
+
+pub async fn get_remote_files_by_ids(
+    user_db: &DatabaseConnection,
+    ids: &[i32],
+) -> Result<Vec<remote_files::Model>, DbErr> {
+    remote_files::Entity::find()
+        .filter(remote_files::Column::Id.is_in(ids.to_vec()))
+        .all(user_db)
+        .await
+}
+
+/// Describe queued remote files the way files of the library are.
+///
+/// # Arguments
+/// * `user_db` - The database holding the remote library.
+/// * `queue_ids` - The IDs the files are queued with, see `to_queue_id`.
+///
+/// # Returns
+/// * `Result<Vec<MetadataSummary>, DbErr>` - The summaries, with the queue IDs as their IDs.
+pub async fn get_remote_summaries(
+    user_db: &DatabaseConnection,
+    queue_ids: &[i32],
+) -> Result<Vec<MetadataSummary>, DbErr> {
+    let ids: Vec<i32> = queue_ids.iter().filter_map(|x| from_queue_id(*x)).collect();
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    Ok(get_remote_files_by_ids(user_db, &ids)
+        .await?
+        .into_iter()
+        .map(|x| MetadataSummary {
+            id: to_queue_id(x.id),
+            directory: String::new(),
+            file_name: format!("{}.{}", x.remote_id, x.extension),
+            artist: x.artist.clone(),
+            album_artist: x.artist,
+            album: x.album,
+            title: x.title,
+            track_number: x.track_number,
+            duration: x.duration,
+            sort_artist: String::new(),
+            sort_album: String::new(),
+            year: x.year,
+        })
+        .collect())
+}
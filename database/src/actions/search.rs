@@ -2,14 +2,99 @@ use std::collections::HashMap;
 use std::error::Error;
 
 use deunicode::deunicode;
-use log::warn;
+use pinyin::ToPinyin;
 use tantivy::collector::{FilterCollector, TopDocs};
 use tantivy::doc;
-use tantivy::query::QueryParser;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, TermQuery};
 use tantivy::schema::*;
+use tracing::warn;
+use wana_kana::{ConvertJapanese, IsJapaneseChar};
 
 use crate::connection::SearchDbConnection;
 
+const ARTIST_BOOST: f32 = 0.5;
+const ALBUM_BOOST: f32 = 0.4;
+const TAGS_BOOST: f32 = 0.3;
+const EXACT_MATCH_BONUS: f32 = 1.0;
+// Fields whose words are offered as spelling corrections
+const SUGGESTION_FIELDS: [&str; 3] = ["name", "latinization", "artist"];
+
+/// Groups of names that should find the same items, such as the spellings
+/// of an artist in different scripts. Aliases are transitive, if `a` is an
+/// alias of `b` and `b` of `c`, all three are in one group.
+#[derive(Debug, Clone, Default)]
+pub struct SearchSynonyms {
+    groups: Vec<Vec<String>>,
+}
+
+impl SearchSynonyms {
+    pub fn new<I>(pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut groups: Vec<Vec<String>> = Vec::new();
+
+        for (term, alias) in pairs {
+            let term = term.trim().to_lowercase();
+            let alias = alias.trim().to_lowercase();
+            if term.is_empty() || alias.is_empty() || term == alias {
+                continue;
+            }
+
+            let mut merged = vec![term, alias];
+            groups.retain(|group| {
+                if group.iter().any(|x| merged.contains(x)) {
+                    merged.extend(group.iter().cloned());
+                    false
+                } else {
+                    true
+                }
+            });
+            merged.sort();
+            merged.dedup();
+            groups.push(merged);
+        }
+
+        SearchSynonyms { groups }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Rewrite the query with every alias of the names it contains.
+    ///
+    /// Names only match whole words. The original query is not included.
+    pub fn expand(&self, query: &str) -> Vec<String> {
+        let query = query.to_lowercase();
+        let mut expanded = Vec::new();
+
+        for group in &self.groups {
+            for name in group {
+                for (start, _) in query.match_indices(name.as_str()) {
+                    let end = start + name.len();
+                    let before = query[..start].chars().next_back();
+                    let after = query[end..].chars().next();
+                    if before.is_some_and(char::is_alphanumeric)
+                        || after.is_some_and(char::is_alphanumeric)
+                    {
+                        continue;
+                    }
+
+                    for alias in group.iter().filter(|x| *x != name) {
+                        let rewritten = format!("{}{}{}", &query[..start], alias, &query[end..]);
+                        if !expanded.contains(&rewritten) {
+                            expanded.push(rewritten);
+                        }
+                    }
+                }
+            }
+        }
+
+        expanded
+    }
+}
+
 #[derive(Eq, Hash, PartialEq, Clone, Debug)]
 pub enum CollectionType {
     Track,
@@ -56,15 +141,84 @@ pub fn remove_term(search_db: &mut SearchDbConnection, r#type: CollectionType, i
     search_db.w.delete_term(term);
 }
 
-pub fn add_term(search_db: &mut SearchDbConnection, r#type: CollectionType, id: i32, name: &str) {
+/// Spell the Chinese characters of a text in pinyin.
+///
+/// Every run of characters is written as separate syllables, joined, and
+/// as initials, so `周杰伦` can be found with `zhou jie lun`, `zhoujielun`
+/// or `zjl`. Other characters are dropped.
+pub fn to_pinyin(text: &str) -> String {
+    let mut words: Vec<String> = Vec::new();
+    let mut syllables: Vec<&str> = Vec::new();
+
+    let mut flush = |syllables: &mut Vec<&str>| {
+        if syllables.is_empty() {
+            return;
+        }
+        words.extend(syllables.iter().map(|x| x.to_string()));
+        if syllables.len() > 1 {
+            words.push(syllables.concat());
+            words.push(syllables.iter().filter_map(|x| x.get(..1)).collect());
+        }
+        syllables.clear();
+    };
+
+    for c in text.chars() {
+        match c.to_pinyin() {
+            Some(pinyin) => syllables.push(pinyin.plain()),
+            None => flush(&mut syllables),
+        }
+    }
+    flush(&mut syllables);
+
+    words.join(" ")
+}
+
+/// Spell the kana of a Japanese text in romaji, `あいみょん` becomes
+/// `aimyon`. Texts without kana give an empty string.
+pub fn to_romaji(text: &str) -> String {
+    if !text.chars().any(|c| c.is_kana()) {
+        return String::new();
+    }
+
+    text.to_romaji()
+}
+
+fn with_latinization(text: &str) -> String {
+    let mut forms = vec![text.to_string()];
+    for form in [deunicode(text), to_pinyin(text), to_romaji(text)] {
+        if !form.is_empty() && !forms.contains(&form) {
+            forms.push(form);
+        }
+    }
+
+    forms.join(" ")
+}
+
+#[allow(clippy::too_many_arguments)]
+fn add_document(
+    search_db: &mut SearchDbConnection,
+    r#type: CollectionType,
+    id: i32,
+    name: &str,
+    artist: &str,
+    album: &str,
+    tags: &str,
+    lyrics: &str,
+) {
     let schema = &search_db.schema;
     let term_name = schema.get_field("name").unwrap();
     let term_latinization = schema.get_field("latinization").unwrap();
     let term_id = schema.get_field("id").unwrap();
     let term_type = schema.get_field("type").unwrap();
     let term_tid = schema.get_field("tid").unwrap();
+    let term_artist = schema.get_field("artist").unwrap();
+    let term_album = schema.get_field("album").unwrap();
+    let term_pinyin = schema.get_field("pinyin").unwrap();
+    let term_romaji = schema.get_field("romaji").unwrap();
+    let term_tags = schema.get_field("tags").unwrap();
+    let term_lyrics = schema.get_field("lyrics").unwrap();
 
-    let tid = format!("{:?}-{:?}", term_type, term_id);
+    let tid = format!("{:?}-{:?}", r#type, id);
     let term = Term::from_field_text(term_tid, &tid);
 
     search_db.w.delete_term(term);
@@ -74,29 +228,206 @@ pub fn add_term(search_db: &mut SearchDbConnection, r#type: CollectionType, id:
         .add_document(doc!(
             term_name => name,
             term_latinization => deunicode(name),
+            term_pinyin => to_pinyin(name),
+            term_romaji => to_romaji(name),
             term_type => Into::<i64>::into(r#type),
             term_tid => tid,
             term_id => Into::<i64>::into(id),
+            term_artist => with_latinization(artist),
+            term_album => with_latinization(album),
+            term_tags => tags,
+            term_lyrics => lyrics,
         ))
         .unwrap();
 }
 
+pub fn add_term(search_db: &mut SearchDbConnection, r#type: CollectionType, id: i32, name: &str) {
+    add_document(search_db, r#type, id, name, "", "", "", "");
+}
+
+// Whether the stored document of an item already has these values
+fn is_indexed(
+    search_db: &SearchDbConnection,
+    r#type: CollectionType,
+    id: i32,
+    values: [(&str, &str); 5],
+) -> bool {
+    let schema = &search_db.schema;
+    let term_tid = schema.get_field("tid").unwrap();
+    let tid = format!("{:?}-{:?}", r#type, id);
+    let query = TermQuery::new(
+        Term::from_field_text(term_tid, &tid),
+        IndexRecordOption::Basic,
+    );
+
+    let searcher = search_db.r.searcher();
+    let Ok(top_docs) = searcher.search(&query, &TopDocs::with_limit(1)) else {
+        return false;
+    };
+    let Some(doc) = top_docs
+        .first()
+        .and_then(|(_, address)| searcher.doc::<TantivyDocument>(*address).ok())
+    else {
+        return false;
+    };
+
+    values.iter().all(|(field, value)| {
+        let stored = doc
+            .get_first(schema.get_field(field).unwrap())
+            .and_then(|x| x.as_str())
+            .unwrap_or_default();
+        stored == *value
+    })
+}
+
+/// Index a track with the names of its artists and album, so searching for
+/// either also finds the track. Replaces the previous document of the track.
+///
+/// Returns `false` if the committed document was already up to date.
+pub fn add_track_term(
+    search_db: &mut SearchDbConnection,
+    id: i32,
+    title: &str,
+    artist: &str,
+    album: &str,
+) -> bool {
+    add_track_term_with_tags(search_db, id, title, artist, album, "")
+}
+
+/// Like `add_track_term`, also indexing the values of searchable mapped tags.
+pub fn add_track_term_with_tags(
+    search_db: &mut SearchDbConnection,
+    id: i32,
+    title: &str,
+    artist: &str,
+    album: &str,
+    tags: &str,
+) -> bool {
+    add_track_term_with_lyrics(search_db, id, title, artist, album, tags, "")
+}
+
+/// Like `add_track_term_with_tags`, also indexing the lyrics of the track.
+pub fn add_track_term_with_lyrics(
+    search_db: &mut SearchDbConnection,
+    id: i32,
+    title: &str,
+    artist: &str,
+    album: &str,
+    tags: &str,
+    lyrics: &str,
+) -> bool {
+    let values = [
+        ("name", title),
+        ("artist", &with_latinization(artist)),
+        ("album", &with_latinization(album)),
+        ("tags", tags),
+        ("lyrics", lyrics),
+    ];
+    if is_indexed(search_db, CollectionType::Track, id, values) {
+        return false;
+    }
+
+    add_document(
+        search_db,
+        CollectionType::Track,
+        id,
+        title,
+        artist,
+        album,
+        tags,
+        lyrics,
+    );
+    true
+}
+
+/// Index an album with the name of its artist.
+pub fn add_album_term(search_db: &mut SearchDbConnection, id: i32, name: &str, artist: &str) {
+    add_document(
+        search_db,
+        CollectionType::Album,
+        id,
+        name,
+        artist,
+        "",
+        "",
+        "",
+    );
+}
+
+/// A search hit with the relevance score of the index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredItem {
+    pub id: i64,
+    pub score: f32,
+    pub name: String,
+}
+
 pub fn search_for(
     search_db: &mut SearchDbConnection,
     query_str: &str,
     n: usize,
 ) -> Result<HashMap<CollectionType, Vec<i64>>, Box<dyn Error>> {
+    let results = search_scored(search_db, query_str, n)?;
+
+    Ok(results
+        .into_iter()
+        .map(|(collection_type, items)| {
+            (collection_type, items.into_iter().map(|x| x.id).collect())
+        })
+        .collect())
+}
+
+/// Search every collection type, keeping the scores and names of the hits
+/// so they can be compared across types.
+pub fn search_scored(
+    search_db: &mut SearchDbConnection,
+    query_str: &str,
+    n: usize,
+) -> Result<HashMap<CollectionType, Vec<ScoredItem>>, Box<dyn Error>> {
     let schema = &search_db.schema;
     let term_name = schema.get_field("name").unwrap();
     let term_latinization = schema.get_field("latinization").unwrap();
+    let term_artist = schema.get_field("artist").unwrap();
+    let term_album = schema.get_field("album").unwrap();
+    let term_pinyin = schema.get_field("pinyin").unwrap();
+    let term_romaji = schema.get_field("romaji").unwrap();
+    let term_tags = schema.get_field("tags").unwrap();
     let field_id = schema.get_field("id").unwrap();
 
-    let query_parser = QueryParser::for_index(&search_db.index, vec![term_name, term_latinization]);
+    let mut query_parser = QueryParser::for_index(
+        &search_db.index,
+        vec![
+            term_name,
+            term_latinization,
+            term_pinyin,
+            term_romaji,
+            term_artist,
+            term_album,
+            term_tags,
+        ],
+    );
+    // A match on the name ranks above a match on the artist or album
+    query_parser.set_field_boost(term_artist, ARTIST_BOOST);
+    query_parser.set_field_boost(term_album, ALBUM_BOOST);
+    query_parser.set_field_boost(term_tags, TAGS_BOOST);
     let query = query_parser.parse_query(query_str)?;
+    let query: Box<dyn Query> = if search_db.synonyms.is_empty() {
+        query
+    } else {
+        // Either form of an aliased name finds the same items
+        let mut queries = vec![(Occur::Should, query)];
+        for alternative in search_db.synonyms.expand(query_str) {
+            match query_parser.parse_query(&alternative) {
+                Ok(x) => queries.push((Occur::Should, x)),
+                Err(e) => warn!("Failed to parse expanded query {}: {}", alternative, e),
+            }
+        }
+        Box::new(BooleanQuery::new(queries))
+    };
 
     let searcher = search_db.index.reader()?.searcher();
 
-    let mut results: HashMap<CollectionType, Vec<i64>> = HashMap::new();
+    let mut results: HashMap<CollectionType, Vec<ScoredItem>> = HashMap::new();
 
     for collection_type in [
         CollectionType::Track,
@@ -114,13 +445,22 @@ pub fn search_for(
 
         let top_docs = searcher.search(&query, &filter_collector)?;
 
-        for (_score, doc_address) in top_docs {
+        for (score, doc_address) in top_docs {
             let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
             if let Some(doc_id) = retrieved_doc.get_first(field_id) {
+                let name = retrieved_doc
+                    .get_first(term_name)
+                    .and_then(|x| x.as_str())
+                    .unwrap_or_default()
+                    .to_string();
                 results
                     .entry(collection_type.clone())
                     .or_default()
-                    .push(doc_id.as_i64().unwrap());
+                    .push(ScoredItem {
+                        id: doc_id.as_i64().unwrap(),
+                        score,
+                        name,
+                    });
             } else {
                 warn!("Id not inserted while searching for the document");
             }
@@ -129,3 +469,210 @@ pub fn search_for(
 
     Ok(results)
 }
+
+// How likely a hit of each type is what the user is looking for when the
+// scores are close, broader collections first
+fn type_weight(collection_type: &CollectionType) -> f32 {
+    match collection_type {
+        CollectionType::Artist => 1.2,
+        CollectionType::Album => 1.1,
+        CollectionType::Playlist => 1.05,
+        CollectionType::Track => 1.0,
+        CollectionType::Directory => 0.8,
+    }
+}
+
+/// Pick the hit that best answers the query across all types.
+///
+/// Scores of different types aren't directly comparable, documents with
+/// artist and album fields collect more matches than bare names. Every hit
+/// is scored relative to the best hit overall, weighted by its type, and a
+/// name equal to the query wins over partial matches.
+pub fn best_match(
+    results: &HashMap<CollectionType, Vec<ScoredItem>>,
+    query_str: &str,
+) -> Option<(CollectionType, i64)> {
+    let max_score = results
+        .values()
+        .flatten()
+        .map(|x| x.score)
+        .fold(0f32, f32::max);
+    if max_score <= 0.0 {
+        return None;
+    }
+
+    let query = query_str.trim().to_lowercase();
+    let query_latinization = deunicode(&query);
+
+    results
+        .iter()
+        .flat_map(|(collection_type, items)| items.iter().map(move |x| (collection_type, x)))
+        .map(|(collection_type, item)| {
+            let name = item.name.trim().to_lowercase();
+            let exact = name == query || deunicode(&name) == query_latinization;
+
+            let mut relevance = item.score / max_score * type_weight(collection_type);
+            if exact {
+                relevance += EXACT_MATCH_BONUS;
+            }
+
+            (collection_type, item.id, relevance)
+        })
+        // Break ties by type and ID so the result doesn't depend on the hash order
+        .max_by(|a, b| {
+            a.2.total_cmp(&b.2)
+                .then_with(|| type_weight(a.0).total_cmp(&type_weight(b.0)))
+                .then_with(|| b.1.cmp(&a.1))
+        })
+        .map(|(collection_type, id, _)| (collection_type.clone(), id))
+}
+
+// Edit distance between two words, counting a swap of neighbours as one edit
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = Vec::new();
+    let mut current: Vec<usize> = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        let before_previous = std::mem::replace(&mut previous, current.clone());
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before_previous[j - 2] + 1);
+            }
+        }
+    }
+
+    current[b.len()]
+}
+
+// Short words allow fewer typos, or every word would be a suggestion
+fn max_typos(word: &[char]) -> usize {
+    match word.len() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+/// Suggest corrected queries by replacing the words of the query that are
+/// not in the index with the closest indexed words.
+///
+/// Candidates are ranked by edit distance, then by how many documents
+/// contain them. Returns at most `count` queries, none if every word of the
+/// query is known.
+pub fn suggest_queries(
+    search_db: &SearchDbConnection,
+    query_str: &str,
+    count: usize,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let schema = &search_db.schema;
+    let fields: Vec<Field> = SUGGESTION_FIELDS
+        .iter()
+        .map(|x| schema.get_field(x).unwrap())
+        .collect();
+
+    let query = query_str.to_lowercase();
+    let words: Vec<Vec<char>> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.chars().collect())
+        .collect();
+    if words.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Candidates of every word with their distance and document frequency
+    let mut candidates: Vec<HashMap<String, (usize, u32)>> = vec![HashMap::new(); words.len()];
+    let mut known = vec![false; words.len()];
+
+    let searcher = search_db.index.reader()?.searcher();
+    for segment_reader in searcher.segment_readers() {
+        for field in &fields {
+            let inverted_index = segment_reader.inverted_index(*field)?;
+            let mut stream = inverted_index.terms().stream()?;
+
+            while stream.advance() {
+                let Ok(term) = std::str::from_utf8(stream.key()) else {
+                    continue;
+                };
+                let term_chars: Vec<char> = term.chars().collect();
+                let doc_freq = stream.value().doc_freq;
+
+                for (i, word) in words.iter().enumerate() {
+                    let typos = max_typos(word);
+                    if term_chars.len().abs_diff(word.len()) > typos {
+                        continue;
+                    }
+
+                    let distance = edit_distance(word, &term_chars);
+                    if distance == 0 {
+                        known[i] = true;
+                    } else if distance <= typos {
+                        let entry = candidates[i]
+                            .entry(term.to_string())
+                            .or_insert((distance, 0));
+                        entry.1 += doc_freq;
+                    }
+                }
+            }
+        }
+    }
+
+    let ranked: Vec<Vec<String>> = candidates
+        .into_iter()
+        .map(|x| {
+            let mut x: Vec<(String, (usize, u32))> = x.into_iter().collect();
+            x.sort_by(|a, b| {
+                a.1 .0
+                    .cmp(&b.1 .0)
+                    .then(b.1 .1.cmp(&a.1 .1))
+                    .then(a.0.cmp(&b.0))
+            });
+            x.into_iter().map(|(term, _)| term).collect()
+        })
+        .collect();
+
+    let corrected: Vec<usize> = (0..words.len())
+        .filter(|i| !known[*i] && !ranked[*i].is_empty())
+        .collect();
+    if corrected.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // The n-th suggestion uses the n-th candidate of every misspelled word
+    let mut suggestions: Vec<String> = Vec::new();
+    let rounds = corrected
+        .iter()
+        .map(|i| ranked[*i].len())
+        .max()
+        .unwrap_or(0);
+    for round in 0..rounds {
+        if suggestions.len() >= count {
+            break;
+        }
+
+        let suggestion = words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                if corrected.contains(&i) {
+                    let options = &ranked[i];
+                    options[round.min(options.len() - 1)].clone()
+                } else {
+                    word.iter().collect()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        if !suggestions.contains(&suggestion) {
+            suggestions.push(suggestion);
+        }
+    }
+
+    Ok(suggestions)
+}
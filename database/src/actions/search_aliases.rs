@@ -0,0 +1,92 @@
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue;
+
+use crate::actions::search::SearchSynonyms;
+use crate::entities::{prelude, search_aliases};
+
+/// Get all search aliases defined by the user.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Vec<search_aliases::Model>, DbErr>` - The aliases in the order they were added.
+pub async fn get_search_aliases(
+    db: &DatabaseConnection,
+) -> Result<Vec<search_aliases::Model>, DbErr> {
+    prelude::SearchAliases::find().all(db).await
+}
+
+/// Build the synonyms used by the search index from the stored aliases.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<SearchSynonyms, DbErr>` - The alias groups.
+pub async fn load_search_synonyms(db: &DatabaseConnection) -> Result<SearchSynonyms, DbErr> {
+    let aliases = get_search_aliases(db).await?;
+
+    Ok(SearchSynonyms::new(
+        aliases.into_iter().map(|x| (x.term, x.alias)),
+    ))
+}
+
+/// Make two names find the same items when searching.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `term` - A name, such as an artist.
+/// * `alias` - Another name for the same thing.
+///
+/// # Returns
+/// * `Result<Option<search_aliases::Model>, DbErr>` - The stored alias, `None` if the
+///   names are empty or equal.
+pub async fn add_search_alias(
+    db: &DatabaseConnection,
+    term: &str,
+    alias: &str,
+) -> Result<Option<search_aliases::Model>, DbErr> {
+    let term = term.trim();
+    let alias = alias.trim();
+    if term.is_empty() || alias.is_empty() || term.to_lowercase() == alias.to_lowercase() {
+        return Ok(None);
+    }
+
+    let item = search_aliases::ActiveModel {
+        term: ActiveValue::Set(term.to_string()),
+        alias: ActiveValue::Set(alias.to_string()),
+        ..Default::default()
+    };
+
+    prelude::SearchAliases::insert(item)
+        .on_conflict(
+            OnConflict::columns([search_aliases::Column::Term, search_aliases::Column::Alias])
+                .do_nothing()
+                .to_owned(),
+        )
+        .do_nothing()
+        .exec(db)
+        .await?;
+
+    prelude::SearchAliases::find()
+        .filter(search_aliases::Column::Term.eq(term))
+        .filter(search_aliases::Column::Alias.eq(alias))
+        .one(db)
+        .await
+}
+
+/// Remove a search alias.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `id` - The ID of the alias.
+///
+/// # Returns
+/// * `Result<bool, DbErr>` - Whether an alias was removed.
+pub async fn remove_search_alias(db: &DatabaseConnection, id: i32) -> Result<bool, DbErr> {
+    let result = prelude::SearchAliases::delete_by_id(id).exec(db).await?;
+
+    Ok(result.rows_affected > 0)
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue;
+use tracing::warn;
+
+use analysis::seek_table::{build_seek_table, needs_seek_table, SeekTable};
+
+use crate::entities::{media_files, media_seek_tables};
+
+/// Build the seek tables of the files in formats that need one.
+///
+/// Tables are only parsed from the frame headers, files that can't be read
+/// are left out and seek with their decoder.
+///
+/// # Arguments
+/// * `files` - The files to build tables for.
+/// * `lib_path` - The root of the library.
+///
+/// # Returns
+/// * `Vec<(i32, SeekTable)>` - The tables by file ID.
+pub fn build_seek_tables(files: &[media_files::Model], lib_path: &Path) -> Vec<(i32, SeekTable)> {
+    files
+        .par_iter()
+        .filter_map(|file| {
+            let file_path = lib_path.join(&file.directory).join(&file.file_name);
+            if !needs_seek_table(&file_path) {
+                return None;
+            }
+
+            match build_seek_table(&file_path) {
+                Ok(table) => table.map(|x| (file.id, x)),
+                Err(e) => {
+                    warn!(
+                        "Unable to build the seek table of {}: {}",
+                        file.file_name, e
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Store seek tables, replacing the previous table of a file.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `tables` - The tables by file ID.
+pub async fn insert_seek_tables<C>(db: &C, tables: &[(i32, SeekTable)]) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    if tables.is_empty() {
+        return Ok(());
+    }
+
+    let models = tables
+        .iter()
+        .map(|(file_id, table)| media_seek_tables::ActiveModel {
+            file_id: ActiveValue::Set(*file_id),
+            seek_table: ActiveValue::Set(table.to_bytes()),
+            ..Default::default()
+        });
+
+    media_seek_tables::Entity::insert_many(models)
+        .on_conflict(
+            OnConflict::column(media_seek_tables::Column::FileId)
+                .update_column(media_seek_tables::Column::SeekTable)
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Get the seek tables of the given files.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, SeekTable>, DbErr>` - The tables by file ID, files
+///   without a table or with a corrupt one are left out.
+pub async fn get_seek_tables_of_files<C>(
+    db: &C,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, SeekTable>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let items = media_seek_tables::Entity::find()
+        .filter(media_seek_tables::Column::FileId.is_in(file_ids.to_vec()))
+        .all(db)
+        .await?;
+
+    Ok(items
+        .into_iter()
+        .filter_map(|x| SeekTable::from_bytes(&x.seek_table).map(|table| (x.file_id, table)))
+        .collect())
+}
+
+/// Build the seek table of a file that changed on disk again.
+///
+/// Byte offsets move when tags are edited, a stale table would seek into
+/// the wrong frames. The table is removed if it can't be built.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_id` - The ID of the file.
+/// * `file_path` - The full path of the file.
+pub async fn refresh_seek_table<C>(db: &C, file_id: i32, file_path: &Path) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    media_seek_tables::Entity::delete_many()
+        .filter(media_seek_tables::Column::FileId.eq(file_id))
+        .exec(db)
+        .await?;
+
+    if !needs_seek_table(file_path) {
+        return Ok(());
+    }
+    match build_seek_table(file_path) {
+        Ok(Some(table)) => insert_seek_tables(db, &[(file_id, table)]).await,
+        Ok(None) => Ok(()),
+        Err(e) => {
+            warn!(
+                "Unable to build the seek table of {}: {}",
+                file_path.display(),
+                e
+            );
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue;
+
+use crate::entities::{prelude, settings};
+
+/// Read a single value from the settings table.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `key` - The key of the setting.
+///
+/// # Returns
+/// * `Result<Option<String>, DbErr>` - The stored value, or `None` if the key is not set.
+pub async fn get_setting<C>(db: &C, key: &str) -> Result<Option<String>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let setting = prelude::Settings::find()
+        .filter(settings::Column::Key.eq(key))
+        .one(db)
+        .await?;
+
+    Ok(setting.map(|x| x.value))
+}
+
+/// Write a single value into the settings table, replacing any existing value.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `key` - The key of the setting.
+/// * `value` - The new value of the setting.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the value was written.
+pub async fn set_setting<C>(db: &C, key: &str, value: String) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    let setting = settings::ActiveModel {
+        key: ActiveValue::Set(key.to_string()),
+        value: ActiveValue::Set(value),
+        ..Default::default()
+    };
+
+    prelude::Settings::insert(setting)
+        .on_conflict(
+            OnConflict::column(settings::Column::Key)
+                .update_column(settings::Column::Value)
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Remove a value from the settings table.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `key` - The key of the setting.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the key is no longer set.
+pub async fn remove_setting<C>(db: &C, key: &str) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    prelude::Settings::delete_many()
+        .filter(settings::Column::Key.eq(key))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use sea_orm::prelude::*;
+
+use crate::entities::{media_file_albums, media_file_artists};
+
+/// Shuffle whole groups while keeping the order inside every group.
+///
+/// # Arguments
+/// * `items` - The items with the group they belong to, in their original order.
+/// * `rng` - The source of randomness.
+///
+/// # Returns
+/// * `Vec<T>` - The items, grouped, with the groups in random order.
+pub fn shuffle_groups<T, G, R>(items: Vec<(T, G)>, rng: &mut R) -> Vec<T>
+where
+    G: Ord,
+    R: Rng + ?Sized,
+{
+    let mut groups: BTreeMap<G, Vec<T>> = BTreeMap::new();
+    for (item, group) in items {
+        groups.entry(group).or_default().push(item);
+    }
+
+    let mut groups: Vec<Vec<T>> = groups.into_values().collect();
+    groups.shuffle(rng);
+
+    groups.into_iter().flatten().collect()
+}
+
+/// Shuffle items so that items of the same group don't follow each other.
+///
+/// The group with the most items left is always picked next, unless it was
+/// just played. This only places two items of a group back-to-back when one
+/// group holds more than half of all items.
+///
+/// # Arguments
+/// * `items` - The items with the group they belong to.
+/// * `rng` - The source of randomness.
+///
+/// # Returns
+/// * `Vec<T>` - The shuffled items.
+pub fn spread_groups<T, G, R>(items: Vec<(T, G)>, rng: &mut R) -> Vec<T>
+where
+    G: Ord + Clone,
+    R: Rng + ?Sized,
+{
+    let total = items.len();
+    let mut groups: BTreeMap<G, Vec<T>> = BTreeMap::new();
+    for (item, group) in items {
+        groups.entry(group).or_default().push(item);
+    }
+
+    let mut groups: Vec<(G, Vec<T>)> = groups.into_iter().collect();
+    for (_, items) in groups.iter_mut() {
+        items.shuffle(rng);
+    }
+    // Break ties between equally large groups randomly
+    groups.shuffle(rng);
+
+    let mut result = Vec::with_capacity(total);
+    let mut last: Option<G> = None;
+
+    while result.len() < total {
+        let candidate = groups
+            .iter()
+            .enumerate()
+            .filter(|(_, (group, items))| !items.is_empty() && Some(group) != last.as_ref())
+            .max_by_key(|(_, (_, items))| items.len())
+            .map(|(index, _)| index);
+
+        // Only the group that was just played is left
+        let index = candidate.unwrap_or_else(|| {
+            groups
+                .iter()
+                .position(|(_, items)| !items.is_empty())
+                .unwrap()
+        });
+
+        let (group, items) = &mut groups[index];
+        result.push(items.pop().unwrap());
+        last = Some(group.clone());
+    }
+
+    result
+}
+
+/// Put some items of a new order back where they were.
+///
+/// # Arguments
+/// * `order` - The new order as indices into the original items.
+/// * `pinned` - The indices of the items that keep their place.
+///
+/// # Returns
+/// * `Vec<usize>` - The new order, with every pinned item at its own index and
+///   the other items in the given order around them.
+pub fn keep_in_place(order: Vec<usize>, pinned: &HashSet<usize>) -> Vec<usize> {
+    let total = order.len();
+    let mut others = order.into_iter().filter(|x| !pinned.contains(x));
+
+    (0..total)
+        .map(|index| {
+            if pinned.contains(&index) {
+                index
+            } else {
+                others.next().unwrap()
+            }
+        })
+        .collect()
+}
+
+/// Shuffle files at album granularity, every album is played as a whole.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files to shuffle.
+///
+/// # Returns
+/// * `Result<Vec<usize>, DbErr>` - The new order as indices into `file_ids`.
+pub async fn album_shuffle(db: &DatabaseConnection, file_ids: &[i32]) -> Result<Vec<usize>, DbErr> {
+    let albums: HashMap<i32, (i32, Option<i32>)> = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|x| (x.media_file_id, (x.album_id, x.track_number)))
+        .collect();
+
+    // Files without an album form a group of their own, negative keys never clash with albums
+    let mut items: Vec<(usize, i32, i32)> = file_ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| match albums.get(id) {
+            Some((album_id, track_number)) => (index, *album_id, track_number.unwrap_or(i32::MAX)),
+            None => (index, -(index as i32) - 1, 0),
+        })
+        .collect();
+
+    // Play every album from its first track
+    items.sort_by_key(|(index, album, track_number)| (*album, *track_number, *index));
+
+    let items = items
+        .into_iter()
+        .map(|(index, album, _)| (index, album))
+        .collect();
+
+    Ok(shuffle_groups(items, &mut rand::thread_rng()))
+}
+
+/// Shuffle files so the same artist doesn't play twice in a row.
+///
+/// Files with several artists are grouped by one of them, files without
+/// artists are not spread at all.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files to shuffle.
+///
+/// # Returns
+/// * `Result<Vec<usize>, DbErr>` - The new order as indices into `file_ids`.
+pub async fn artist_shuffle(
+    db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<Vec<usize>, DbErr> {
+    let mut artists: HashMap<i32, i32> = HashMap::new();
+    for x in media_file_artists::Entity::find()
+        .filter(media_file_artists::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .all(db)
+        .await?
+    {
+        let artist = artists.entry(x.media_file_id).or_insert(x.artist_id);
+        *artist = (*artist).min(x.artist_id);
+    }
+
+    let items = file_ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| {
+            let group = artists.get(id).copied().unwrap_or(-(index as i32) - 1);
+            (index, group)
+        })
+        .collect();
+
+    Ok(spread_groups(items, &mut rand::thread_rng()))
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use sea_orm::prelude::*;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{ActiveValue, QueryOrder, QuerySelect};
+
+use crate::entities::{track_skips, user_logs};
+
+/// Part of a track under which moving on counts as a skip.
+pub const SKIP_THRESHOLD: f64 = 0.2;
+/// Skips before a track starts being down-ranked.
+pub const MIN_SKIPS: i32 = 3;
+
+/// Whether a track left at `position` was skipped.
+pub fn is_skipped(position: f64, duration: f64) -> bool {
+    duration > 0. && position < duration * SKIP_THRESHOLD
+}
+
+/// How often a track is skipped compared to how often it is played.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkipStats {
+    pub file_id: i32,
+    pub skips: i32,
+    pub plays: i32,
+    pub last_skipped_at: String,
+    /// From 0 for tracks never skipped enough to 1 for tracks always skipped.
+    pub penalty: f64,
+}
+
+fn skip_penalty(skips: i32, plays: i32) -> f64 {
+    if skips < MIN_SKIPS {
+        return 0.;
+    }
+    skips as f64 / (skips + plays) as f64
+}
+
+/// Remember that a file was skipped.
+///
+/// # Arguments
+/// * `db` - The database holding the play history.
+/// * `file_id` - The ID of the skipped file.
+///
+/// # Returns
+/// * `Result<(), DbErr>` - Ok if the skip was stored.
+pub async fn log_skip(db: &DatabaseConnection, file_id: i32) -> Result<(), DbErr> {
+    let now = Utc::now().to_rfc3339();
+
+    track_skips::Entity::insert(track_skips::ActiveModel {
+        file_id: ActiveValue::Set(file_id),
+        skips: ActiveValue::Set(1),
+        last_skipped_at: ActiveValue::Set(now.clone()),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::column(track_skips::Column::FileId)
+            .value(
+                track_skips::Column::Skips,
+                Expr::col((track_skips::Entity, track_skips::Column::Skips)).add(1),
+            )
+            .value(track_skips::Column::LastSkippedAt, now)
+            .to_owned(),
+    )
+    .exec(db)
+    .await?;
+
+    Ok(())
+}
+
+async fn get_play_counts(
+    db: &DatabaseConnection,
+    file_ids: Vec<i32>,
+) -> Result<HashMap<i32, i32>, DbErr> {
+    let items: Vec<(i32, i64)> = user_logs::Entity::find()
+        .select_only()
+        .column(user_logs::Column::FileId)
+        .column_as(user_logs::Column::Id.count(), "plays")
+        .filter(user_logs::Column::FileId.is_in(file_ids))
+        .group_by(user_logs::Column::FileId)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    Ok(items
+        .into_iter()
+        .map(|(file_id, plays)| (file_id, plays as i32))
+        .collect())
+}
+
+async fn to_stats(
+    db: &DatabaseConnection,
+    skips: Vec<track_skips::Model>,
+) -> Result<Vec<SkipStats>, DbErr> {
+    let plays = get_play_counts(db, skips.iter().map(|x| x.file_id).collect()).await?;
+
+    Ok(skips
+        .into_iter()
+        .map(|x| {
+            let plays = plays.get(&x.file_id).copied().unwrap_or(0);
+            SkipStats {
+                file_id: x.file_id,
+                skips: x.skips,
+                plays,
+                last_skipped_at: x.last_skipped_at,
+                penalty: skip_penalty(x.skips, plays),
+            }
+        })
+        .collect())
+}
+
+/// Get the skips of every skipped file.
+///
+/// # Arguments
+/// * `db` - The database holding the play history.
+///
+/// # Returns
+/// * `Result<Vec<SkipStats>, DbErr>` - The skipped files, the most skipped first.
+pub async fn get_skip_stats(db: &DatabaseConnection) -> Result<Vec<SkipStats>, DbErr> {
+    let skips = track_skips::Entity::find()
+        .order_by_desc(track_skips::Column::Skips)
+        .order_by_asc(track_skips::Column::FileId)
+        .all(db)
+        .await?;
+
+    to_stats(db, skips).await
+}
+
+/// Get the down-ranking penalty of files.
+///
+/// # Arguments
+/// * `db` - The database holding the play history.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, f64>, DbErr>` - The penalty of every penalised file.
+pub async fn get_skip_penalties(
+    db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, f64>, DbErr> {
+    let skips = track_skips::Entity::find()
+        .filter(track_skips::Column::FileId.is_in(file_ids.to_vec()))
+        .filter(track_skips::Column::Skips.gte(MIN_SKIPS))
+        .all(db)
+        .await?;
+
+    Ok(to_stats(db, skips)
+        .await?
+        .into_iter()
+        .filter(|x| x.penalty > 0.)
+        .map(|x| (x.file_id, x.penalty))
+        .collect())
+}
+
+/// Forget the skips of files.
+///
+/// # Arguments
+/// * `db` - The database holding the play history.
+/// * `file_ids` - The IDs of the files, all files when empty.
+///
+/// # Returns
+/// * `Result<u64, DbErr>` - The number of files whose skips were forgotten.
+pub async fn reset_skips(db: &DatabaseConnection, file_ids: &[i32]) -> Result<u64, DbErr> {
+    let mut query = track_skips::Entity::delete_many();
+    if !file_ids.is_empty() {
+        query = query.filter(track_skips::Column::FileId.is_in(file_ids.to_vec()));
+    }
+
+    Ok(query.exec(db).await?.rows_affected)
+}
+
+/// Move penalised items towards the end, a full penalty sending an item
+/// past every other one. The order of the others is kept.
+pub fn down_rank<T: Copy>(items: Vec<T>, penalty: impl Fn(&T) -> f64) -> Vec<T> {
+    let len = items.len() as f64;
+    let mut ranked: Vec<(f64, T)> = items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| (index as f64 + penalty(&item).clamp(0., 1.) * len, item))
+        .collect();
+    ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    ranked.into_iter().map(|(_, item)| item).collect()
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue;
+
+use crate::entities::{media_metadata, prelude, tag_mappings};
+
+// Field names are stored as meta keys, so they follow the standard ones
+fn is_valid_field(field: &str) -> bool {
+    !field.is_empty()
+        && field
+            .chars()
+            .all(|x| x.is_ascii_lowercase() || x.is_ascii_digit() || x == '_')
+}
+
+/// Get all tag mappings defined by the user.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Vec<tag_mappings::Model>, DbErr>` - The mappings in the order they were added.
+pub async fn get_tag_mappings(db: &DatabaseConnection) -> Result<Vec<tag_mappings::Model>, DbErr> {
+    prelude::TagMappings::find().all(db).await
+}
+
+/// Get the fields tags are stored under while scanning.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<HashMap<String, String>, DbErr>` - The field of every mapped tag key.
+pub async fn get_custom_fields(db: &DatabaseConnection) -> Result<HashMap<String, String>, DbErr> {
+    Ok(get_tag_mappings(db)
+        .await?
+        .into_iter()
+        .map(|x| (x.tag_key, x.field))
+        .collect())
+}
+
+/// Store a tag of the files under a field of its own.
+///
+/// Only files scanned afterwards are read with the mapping.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `tag_key` - The key of the tag in the files, like `CUSTOM1`, matched regardless of case.
+/// * `field` - The field to store the tag under, made of lowercase letters, digits and `_`.
+/// * `searchable` - Whether searching for the value finds the track.
+///
+/// # Returns
+/// * `Result<Option<tag_mappings::Model>, DbErr>` - The stored mapping, `None` if the tag key
+///   is empty or the field isn't valid. An existing mapping of the tag is replaced.
+pub async fn set_tag_mapping(
+    db: &DatabaseConnection,
+    tag_key: &str,
+    field: &str,
+    searchable: bool,
+) -> Result<Option<tag_mappings::Model>, DbErr> {
+    let tag_key = tag_key.trim().to_uppercase();
+    let field = field.trim();
+    if tag_key.is_empty() || !is_valid_field(field) {
+        return Ok(None);
+    }
+
+    let item = tag_mappings::ActiveModel {
+        tag_key: ActiveValue::Set(tag_key.clone()),
+        field: ActiveValue::Set(field.to_string()),
+        searchable: ActiveValue::Set(searchable),
+        ..Default::default()
+    };
+
+    prelude::TagMappings::insert(item)
+        .on_conflict(
+            OnConflict::column(tag_mappings::Column::TagKey)
+                .update_columns([
+                    tag_mappings::Column::Field,
+                    tag_mappings::Column::Searchable,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+    prelude::TagMappings::find()
+        .filter(tag_mappings::Column::TagKey.eq(tag_key))
+        .one(db)
+        .await
+}
+
+/// Remove a tag mapping. Values already stored under its field are kept.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `id` - The ID of the mapping.
+///
+/// # Returns
+/// * `Result<bool, DbErr>` - Whether a mapping was removed.
+pub async fn remove_tag_mapping(db: &DatabaseConnection, id: i32) -> Result<bool, DbErr> {
+    let result = prelude::TagMappings::delete_by_id(id).exec(db).await?;
+
+    Ok(result.rows_affected > 0)
+}
+
+async fn get_mapped_values<C>(
+    db: &C,
+    file_ids: Vec<i32>,
+    searchable_only: bool,
+) -> Result<Vec<media_metadata::Model>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let mut fields: Vec<String> = prelude::TagMappings::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .filter(|x| x.searchable || !searchable_only)
+        .map(|x| x.field)
+        .collect();
+    if fields.is_empty() {
+        return Ok(Vec::new());
+    }
+    fields.sort();
+    fields.dedup();
+
+    media_metadata::Entity::find()
+        .filter(media_metadata::Column::FileId.is_in(file_ids))
+        .filter(media_metadata::Column::MetaKey.is_in(fields))
+        .all(db)
+        .await
+}
+
+/// Get the values of the mapped fields of a file.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_id` - The ID of the file.
+///
+/// # Returns
+/// * `Result<Vec<(String, String)>, DbErr>` - The fields and their values.
+pub async fn get_custom_tags_of_file(
+    db: &DatabaseConnection,
+    file_id: i32,
+) -> Result<Vec<(String, String)>, DbErr> {
+    Ok(get_mapped_values(db, vec![file_id], false)
+        .await?
+        .into_iter()
+        .map(|x| (x.meta_key, x.meta_value))
+        .collect())
+}
+
+/// Get the text that makes files findable by their searchable mapped fields.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files.
+///
+/// # Returns
+/// * `Result<HashMap<i32, String>, DbErr>` - The values of every file joined by spaces,
+///   files without any are left out.
+pub async fn get_searchable_tags<C>(
+    db: &C,
+    file_ids: Vec<i32>,
+) -> Result<HashMap<i32, String>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let mut result: HashMap<i32, String> = HashMap::new();
+    for entry in get_mapped_values(db, file_ids, true).await? {
+        let text = result.entry(entry.file_id).or_default();
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&entry.meta_value);
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,225 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, QueryOrder};
+
+use crate::entities::{media_analysis, taste_profiles, user_logs};
+
+use super::ratings::{get_ratings, MAX_RATING};
+use super::recommendation::analysis_vector;
+
+/// Days after which a play counts half as much in the taste profile.
+pub const TASTE_HALF_LIFE_DAYS: f64 = 30.;
+
+/// The average feature vector of the tracks a user listens to, recent and
+/// liked tracks weighing more.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TasteProfile {
+    pub vector: Vec<f32>,
+    /// Total weight of the plays in the profile, after decay.
+    pub weight: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TasteProfile {
+    /// Fold one play into the profile, decaying what was there before.
+    pub fn add(&mut self, vector: &[f32], weight: f64, at: DateTime<Utc>) {
+        let days = (at - self.updated_at).num_seconds().max(0) as f64 / 86400.;
+        let previous = self.weight * 0.5f64.powf(days / TASTE_HALF_LIFE_DAYS);
+        let total = previous + weight;
+
+        if total > 0. && self.vector.len() == vector.len() {
+            for (x, y) in self.vector.iter_mut().zip(vector) {
+                *x = ((*x as f64 * previous + *y as f64 * weight) / total) as f32;
+            }
+        } else if total > 0. {
+            self.vector = vector.to_vec();
+        }
+        self.weight = total;
+        self.updated_at = self.updated_at.max(at);
+    }
+}
+
+fn parse_profile(model: taste_profiles::Model) -> Option<TasteProfile> {
+    let vector = model
+        .vector
+        .split(',')
+        .map(|x| x.trim().parse().ok())
+        .collect::<Option<Vec<f32>>>()?;
+    let updated_at = DateTime::parse_from_rfc3339(&model.updated_at)
+        .ok()?
+        .with_timezone(&Utc);
+
+    Some(TasteProfile {
+        vector,
+        weight: model.weight,
+        updated_at,
+    })
+}
+
+/// Get the taste profile of the user.
+///
+/// # Arguments
+/// * `user_db` - The database holding the play history.
+/// * `user_id` - The ID of the profile.
+///
+/// # Returns
+/// * `Result<Option<TasteProfile>, DbErr>` - The profile, if anything was played yet.
+pub async fn get_taste_profile(
+    user_db: &DatabaseConnection,
+    user_id: i32,
+) -> Result<Option<TasteProfile>, DbErr> {
+    Ok(taste_profiles::Entity::find()
+        .filter(taste_profiles::Column::UserId.eq(user_id))
+        .one(user_db)
+        .await?
+        .and_then(parse_profile))
+}
+
+async fn save_taste_profile(
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    profile: &TasteProfile,
+) -> Result<(), DbErr> {
+    let existing = taste_profiles::Entity::find()
+        .filter(taste_profiles::Column::UserId.eq(user_id))
+        .one(user_db)
+        .await?;
+    let vector = profile
+        .vector
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let model = taste_profiles::ActiveModel {
+        id: existing.map_or(ActiveValue::NotSet, |x| ActiveValue::Unchanged(x.id)),
+        user_id: ActiveValue::Set(user_id),
+        vector: ActiveValue::Set(vector),
+        weight: ActiveValue::Set(profile.weight),
+        updated_at: ActiveValue::Set(profile.updated_at.to_rfc3339()),
+    };
+    model.save(user_db).await?;
+
+    Ok(())
+}
+
+// Plays count by how much of the track was heard, rated tracks up to twice
+fn play_weight(progress: f64, rating: Option<i32>) -> f64 {
+    let rating = rating.unwrap_or(0).clamp(0, MAX_RATING) as f64;
+    progress.clamp(0., 1.) * (1. + rating / MAX_RATING as f64)
+}
+
+/// Fold a play into the taste profile of the user.
+///
+/// # Arguments
+/// * `main_db` - The database holding the analysis of the library.
+/// * `user_db` - The database holding the play history and the ratings.
+/// * `user_id` - The ID of the profile that played the file.
+/// * `file_id` - The ID of the played file.
+/// * `progress` - The part of the file that was played, between 0 and 1.
+///
+/// # Returns
+/// * `Result<bool, DbErr>` - Whether the profile changed, files without analysis leave it alone.
+pub async fn update_taste_profile(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    file_id: i32,
+    progress: f64,
+) -> Result<bool, DbErr> {
+    let Some(analysis) = media_analysis::Entity::find()
+        .filter(media_analysis::Column::FileId.eq(file_id))
+        .one(main_db)
+        .await?
+    else {
+        return Ok(false);
+    };
+
+    let rating = get_ratings(user_db, user_id, &[file_id])
+        .await?
+        .remove(&file_id);
+    let now = Utc::now();
+    let mut profile = get_taste_profile(user_db, user_id)
+        .await?
+        .unwrap_or(TasteProfile {
+            vector: Vec::new(),
+            weight: 0.,
+            updated_at: now,
+        });
+    profile.add(
+        &analysis_vector(&analysis),
+        play_weight(progress, rating),
+        now,
+    );
+    save_taste_profile(user_db, user_id, &profile).await?;
+
+    Ok(true)
+}
+
+/// Build the taste profile again from the whole play history, for
+/// libraries played before profiles were kept.
+///
+/// # Arguments
+/// * `main_db` - The database holding the analysis of the library.
+/// * `user_db` - The database holding the play history and the ratings.
+/// * `user_id` - The ID of the profile.
+///
+/// # Returns
+/// * `Result<Option<TasteProfile>, DbErr>` - The new profile, if any analysed file was played.
+pub async fn rebuild_taste_profile(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    user_id: i32,
+) -> Result<Option<TasteProfile>, DbErr> {
+    let plays = user_logs::Entity::find()
+        .filter(user_logs::Column::UserId.eq(user_id))
+        .order_by_asc(user_logs::Column::ListenTime)
+        .all(user_db)
+        .await?;
+    let file_ids: Vec<i32> = plays
+        .iter()
+        .map(|x| x.file_id)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let vectors: HashMap<i32, Vec<f32>> = media_analysis::Entity::find()
+        .filter(media_analysis::Column::FileId.is_in(file_ids.clone()))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.file_id, analysis_vector(&x)))
+        .collect();
+    let ratings = get_ratings(user_db, user_id, &file_ids).await?;
+
+    let mut profile: Option<TasteProfile> = None;
+    for play in plays {
+        let Some(vector) = vectors.get(&play.file_id) else {
+            continue;
+        };
+        let Ok(at) = DateTime::parse_from_rfc3339(&play.listen_time) else {
+            continue;
+        };
+        let at = at.with_timezone(&Utc);
+
+        profile
+            .get_or_insert_with(|| TasteProfile {
+                vector: Vec::new(),
+                weight: 0.,
+                updated_at: at,
+            })
+            .add(
+                vector,
+                play_weight(play.progress, ratings.get(&play.file_id).copied()),
+                at,
+            );
+    }
+
+    if let Some(profile) = &profile {
+        save_taste_profile(user_db, user_id, profile).await?;
+    }
+
+    Ok(profile)
+}
@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Run analysis in the background at a lower pace, regardless of the power state.
+pub const BACKGROUND_PRIORITY_KEY: &str = "analysis.background_priority";
+
+// Temperature of any thermal zone, in millidegrees Celsius, above which
+// analysis nearly stops until the device cooled down
+const THERMAL_LIMIT: i64 = 85_000;
+const HOT_PAUSE: Duration = Duration::from_secs(10);
+const BATTERY_PAUSE: Duration = Duration::from_secs(2);
+const BACKGROUND_PAUSE: Duration = Duration::from_secs(1);
+
+/// How hard analysis may work, decided again before every batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnalysisPace {
+    /// Files analysed in one batch.
+    pub batch_size: usize,
+    /// Time to wait after each batch.
+    pub pause: Duration,
+}
+
+impl AnalysisPace {
+    /// Analyse batches of the given size without pausing.
+    pub fn new(batch_size: usize) -> Self {
+        AnalysisPace {
+            batch_size: batch_size.max(1),
+            pause: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PowerStatus {
+    /// The device runs from a discharging battery.
+    pub on_battery: bool,
+    /// A thermal zone is above the limit.
+    pub overheated: bool,
+}
+
+/// Read the power state of the device.
+///
+/// Only Linux exposes it without platform APIs, elsewhere the device is
+/// assumed to be plugged in and cool.
+pub fn read_power_status() -> PowerStatus {
+    if cfg!(target_os = "linux") {
+        read_power_status_from(Path::new("/sys/class"))
+    } else {
+        PowerStatus::default()
+    }
+}
+
+/// Read the power state from a sysfs class directory.
+///
+/// # Arguments
+/// * `class_dir` - The directory holding `power_supply` and `thermal`, usually `/sys/class`.
+pub fn read_power_status_from(class_dir: &Path) -> PowerStatus {
+    let read = |path: &Path| fs::read_to_string(path).map(|x| x.trim().to_string());
+
+    let on_battery = fs::read_dir(class_dir.join("power_supply"))
+        .map(|entries| {
+            entries.flatten().any(|entry| {
+                let path = entry.path();
+                read(&path.join("type")).is_ok_and(|x| x == "Battery")
+                    && read(&path.join("status")).is_ok_and(|x| x == "Discharging")
+            })
+        })
+        .unwrap_or(false);
+
+    let overheated = fs::read_dir(class_dir.join("thermal"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| {
+                    entry
+                        .file_name()
+                        .to_string_lossy()
+                        .starts_with("thermal_zone")
+                })
+                .filter_map(|entry| read(&entry.path().join("temp")).ok())
+                .filter_map(|x| x.parse::<i64>().ok())
+                .any(|x| x >= THERMAL_LIMIT)
+        })
+        .unwrap_or(false);
+
+    PowerStatus {
+        on_battery,
+        overheated,
+    }
+}
+
+/// Decide how fast analysis may run.
+///
+/// # Arguments
+/// * `batch_size` - The batch size when nothing holds analysis back.
+/// * `background` - If the user asked for background priority.
+/// * `status` - The current power state of the device.
+///
+/// # Returns
+/// * `AnalysisPace` - Smaller batches and longer pauses the more constrained the device is.
+pub fn analysis_pace(batch_size: usize, background: bool, status: PowerStatus) -> AnalysisPace {
+    if status.overheated {
+        return AnalysisPace {
+            batch_size: 1,
+            pause: HOT_PAUSE,
+        };
+    }
+
+    let mut pace = AnalysisPace::new(batch_size);
+    if status.on_battery {
+        pace.batch_size = (pace.batch_size / 2).max(1);
+        pace.pause += BATTERY_PAUSE;
+    }
+    if background {
+        pace.batch_size = (pace.batch_size / 2).max(1);
+        pace.pause += BACKGROUND_PAUSE;
+    }
+
+    pace
+}
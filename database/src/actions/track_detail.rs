@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use metadata::stream_info::{read_stream_info, StreamInfo};
+use sea_orm::prelude::*;
+use sea_orm::QueryOrder;
+use tracing::warn;
+
+use crate::actions::cover_art::get_magic_cover_art_id;
+use crate::actions::duplicates::get_copies_of_file;
+use crate::actions::file::get_file_by_id;
+use crate::entities::{
+    media_analysis, media_file_playlists, media_files, media_metadata, playlists, ratings,
+    user_logs,
+};
+
+/// Everything known about a track.
+#[derive(Debug, Clone)]
+pub struct TrackDetail {
+    pub file: media_files::Model,
+    pub path: PathBuf,
+    /// Size of the file in bytes, `None` if the file can't be read.
+    pub size: Option<u64>,
+    /// All tags of the file, in the order they were read.
+    pub tags: Vec<(String, String)>,
+    /// The encoding of the audio, `None` if the file can't be read.
+    pub stream: Option<StreamInfo>,
+    /// Audio features, `None` until the file was analysed.
+    pub analysis: Option<media_analysis::Model>,
+    pub play_count: usize,
+    pub last_played: Option<String>,
+    /// `None` if the track isn't rated.
+    pub rating: Option<i32>,
+    /// Playlists containing the track, in the order they were created.
+    pub playlists: Vec<playlists::Model>,
+    /// `None` if the file has no cover art.
+    pub cover_art_id: Option<i32>,
+}
+
+/// Collect everything known about a track in one go.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `user_db` - The database holding the play history and ratings.
+/// * `user_id` - The ID of the profile the plays and the rating are read for.
+/// * `lib_path` - The root path of the library.
+/// * `file_id` - The ID of the file.
+///
+/// # Returns
+/// * `Result<TrackDetail>` - The details, a missing file only leaves out what has to be read from it.
+pub async fn get_track_detail(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    user_id: i32,
+    lib_path: &Path,
+    file_id: i32,
+) -> anyhow::Result<TrackDetail> {
+    let file = get_file_by_id(main_db, file_id)
+        .await?
+        .with_context(|| format!("File not found: {}", file_id))?;
+    let path = lib_path.join(&file.directory).join(&file.file_name);
+
+    let size = fs::metadata(&path).map(|x| x.len()).ok();
+    let stream = read_stream_info(&path)
+        .map_err(|e| warn!("Failed to read stream info of {:?}: {}", path, e))
+        .ok();
+
+    let tags = media_metadata::Entity::find()
+        .filter(media_metadata::Column::FileId.eq(file_id))
+        .order_by_asc(media_metadata::Column::Id)
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.meta_key, x.meta_value))
+        .collect();
+
+    let analysis = media_analysis::Entity::find()
+        .filter(media_analysis::Column::FileId.eq(file_id))
+        .one(main_db)
+        .await?;
+
+    // Plays and ratings of other copies of the file count for this one
+    let copies = get_copies_of_file(main_db, file_id).await?;
+    let logs = user_logs::Entity::find()
+        .filter(user_logs::Column::UserId.eq(user_id))
+        .filter(user_logs::Column::FileId.is_in(copies.clone()))
+        .all(user_db)
+        .await?;
+    let last_played = logs.iter().map(|x| x.listen_time.clone()).max();
+
+    let mut copy_ratings = ratings::Entity::find()
+        .filter(ratings::Column::UserId.eq(user_id))
+        .filter(ratings::Column::MediaFileId.is_in(copies))
+        .all(user_db)
+        .await?;
+    copy_ratings.sort_by_key(|x| (x.media_file_id != file_id, x.media_file_id));
+    let rating = copy_ratings.first().map(|x| x.rating);
+
+    let playlist_ids: Vec<i32> = media_file_playlists::Entity::find()
+        .filter(media_file_playlists::Column::MediaFileId.eq(file_id))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| x.playlist_id)
+        .collect();
+    let playlists = playlists::Entity::find()
+        .filter(playlists::Column::Id.is_in(playlist_ids))
+        .order_by_asc(playlists::Column::Id)
+        .all(main_db)
+        .await?;
+
+    let magic_cover_art_id = get_magic_cover_art_id(main_db).await;
+    let cover_art_id = file.cover_art_id.filter(|x| Some(*x) != magic_cover_art_id);
+
+    Ok(TrackDetail {
+        file,
+        path,
+        size,
+        tags,
+        stream,
+        analysis,
+        play_count: logs.len(),
+        last_played,
+        rating,
+        playlists,
+        cover_art_id,
+    })
+}
@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use metadata::describe::describe_file;
+use metadata::transcode::{is_transcode_codec, transcode_file};
+use sea_orm::prelude::*;
+use sea_orm::ActiveValue;
+use tracing::{info, warn};
+
+use crate::entities::media_files;
+
+use super::bulk::{remove_file, BulkReport, DeleteMode};
+use super::file::get_file_by_id;
+
+/// Where a transcoded file goes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranscodeTarget {
+    /// Into a folder outside the library, in the directory the file has in
+    /// the library.
+    Folder(PathBuf),
+    /// In place of the file in the library, which keeps its plays, playlists
+    /// and analysis. The original file is deleted.
+    Replace,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscodeJob {
+    pub file_id: i32,
+    /// One of `TRANSCODE_CODECS`.
+    pub codec: String,
+    pub target: TranscodeTarget,
+}
+
+/// Files waiting to be transcoded, worked through one at a time.
+#[derive(Debug, Default)]
+pub struct TranscodeQueue {
+    jobs: Mutex<VecDeque<TranscodeJob>>,
+    running: AtomicBool,
+}
+
+impl TranscodeQueue {
+    pub fn push(&self, jobs: Vec<TranscodeJob>) {
+        self.jobs.lock().unwrap().extend(jobs);
+    }
+
+    /// Number of jobs waiting, the one being worked on left out.
+    pub fn len(&self) -> usize {
+        self.jobs.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Drop the jobs waiting, the file being transcoded is finished.
+    ///
+    /// # Returns
+    /// * `Vec<i32>` - The IDs of the files of the dropped jobs.
+    pub fn cancel(&self) -> Vec<i32> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(|x| x.file_id)
+            .collect()
+    }
+
+    fn next(&self) -> Option<TranscodeJob> {
+        self.jobs.lock().unwrap().pop_front()
+    }
+
+    /// Work through the queue until it is empty, jobs pushed meanwhile
+    /// included.
+    ///
+    /// # Arguments
+    /// * `main_db` - A reference to the database connection.
+    /// * `lib_path` - The root path of the library.
+    /// * `delete_mode` - How replaced files are deleted.
+    /// * `progress_callback` - Called with the jobs done and the jobs known so far.
+    ///
+    /// # Returns
+    /// * `Option<BulkReport>` - What happened to every file, `None` if the queue is already running.
+    pub async fn run<F>(
+        &self,
+        main_db: &DatabaseConnection,
+        lib_path: &Path,
+        delete_mode: DeleteMode,
+        progress_callback: F,
+    ) -> Option<BulkReport>
+    where
+        F: Fn(usize, usize),
+    {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+
+        let mut report = BulkReport::default();
+        let mut done = 0;
+        while let Some(job) = self.next() {
+            progress_callback(done, done + self.len() + 1);
+
+            match transcode_job(main_db, lib_path, &job, delete_mode).await {
+                Ok(()) => report.succeeded.push(job.file_id),
+                Err(e) => {
+                    warn!("Failed to transcode file {}: {}", job.file_id, e);
+                    report.failed.push(job.file_id);
+                    report.errors.insert(job.file_id, e);
+                }
+            }
+
+            done += 1;
+            progress_callback(done, done + self.len());
+        }
+
+        self.running.store(false, Ordering::SeqCst);
+        info!("Transcoded {} files", report.succeeded.len());
+
+        Some(report)
+    }
+}
+
+fn transcoded_name(file: &media_files::Model, codec: &str) -> String {
+    Path::new(&file.file_name)
+        .with_extension(codec)
+        .to_string_lossy()
+        .to_string()
+}
+
+async fn replace_file(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    file: media_files::Model,
+    codec: &str,
+    delete_mode: DeleteMode,
+) -> Result<(), String> {
+    let src = lib_path.join(&file.directory).join(&file.file_name);
+    let dst = lib_path
+        .join(&file.directory)
+        .join(transcoded_name(&file, codec));
+    if dst.exists() {
+        return Err(format!("{:?} already exists", dst));
+    }
+
+    transcode_file(&src, &dst, codec).map_err(|e| e.to_string())?;
+    let mut description = describe_file(&dst, lib_path).map_err(|e| e.to_string())?;
+    let file_hash = description.get_crc().map_err(|e| e.to_string())?;
+
+    let mut model: media_files::ActiveModel = file.into();
+    model.file_name = ActiveValue::Set(description.file_name);
+    model.extension = ActiveValue::Set(description.extension);
+    model.file_hash = ActiveValue::Set(file_hash);
+    model.last_modified = ActiveValue::Set(description.last_modified);
+    if let Err(e) = model.update(main_db).await {
+        // The library still points to the original file
+        let _ = fs::remove_file(&dst);
+        return Err(e.to_string());
+    }
+
+    remove_file(&src, delete_mode)
+}
+
+async fn transcode_job(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    job: &TranscodeJob,
+    delete_mode: DeleteMode,
+) -> Result<(), String> {
+    if !is_transcode_codec(&job.codec) {
+        return Err(format!("Can't transcode to {}", job.codec));
+    }
+    let codec = job.codec.to_lowercase();
+
+    let file = get_file_by_id(main_db, job.file_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("File not found: {}", job.file_id))?;
+    if file.extension.eq_ignore_ascii_case(&codec) {
+        return Err(format!("File is already {}", codec));
+    }
+
+    match &job.target {
+        TranscodeTarget::Folder(folder) => {
+            let dst = folder
+                .join(&file.directory)
+                .join(transcoded_name(&file, &codec));
+            if let Some(parent) = dst.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+
+            let src = lib_path.join(&file.directory).join(&file.file_name);
+            transcode_file(&src, &dst, &codec).map_err(|e| e.to_string())
+        }
+        TranscodeTarget::Replace => {
+            replace_file(main_db, lib_path, file, &codec, delete_mode).await
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, QueryOrder, TransactionTrait};
+
+use crate::entities::{
+    pinned_collections, playback_queue, ratings, recent_contexts, settings, taste_profiles,
+    user_logs, users,
+};
+
+use super::settings::{get_setting, set_setting};
+
+/// The profile created with the library, it can't be removed.
+pub const DEFAULT_USER_ID: i32 = 1;
+
+const ACTIVE_USER_KEY: &str = "users.active";
+
+pub async fn get_users(user_db: &DatabaseConnection) -> Result<Vec<users::Model>, DbErr> {
+    users::Entity::find()
+        .order_by_asc(users::Column::Id)
+        .all(user_db)
+        .await
+}
+
+/// Add a profile, with nothing played or rated yet.
+///
+/// # Arguments
+/// * `user_db` - The database holding the profiles.
+/// * `name` - A name for the profile, unique.
+///
+/// # Returns
+/// * `Result<users::Model, DbErr>` - The new profile.
+pub async fn create_user(user_db: &DatabaseConnection, name: &str) -> Result<users::Model, DbErr> {
+    users::ActiveModel {
+        name: ActiveValue::Set(name.trim().to_string()),
+        ..Default::default()
+    }
+    .insert(user_db)
+    .await
+}
+
+/// Forget a profile with everything recorded for it.
+///
+/// The default profile is kept, removing the active profile makes the
+/// default one active.
+///
+/// # Arguments
+/// * `user_db` - The database holding the profiles.
+/// * `user_id` - The ID of the profile.
+///
+/// # Returns
+/// * `Result<bool, DbErr>` - Whether the profile was removed.
+pub async fn remove_user(user_db: &DatabaseConnection, user_id: i32) -> Result<bool, DbErr> {
+    if user_id == DEFAULT_USER_ID {
+        return Ok(false);
+    }
+
+    let txn = user_db.begin().await?;
+
+    user_logs::Entity::delete_many()
+        .filter(user_logs::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?;
+    ratings::Entity::delete_many()
+        .filter(ratings::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?;
+    pinned_collections::Entity::delete_many()
+        .filter(pinned_collections::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?;
+    playback_queue::Entity::delete_many()
+        .filter(playback_queue::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?;
+    taste_profiles::Entity::delete_many()
+        .filter(taste_profiles::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?;
+    recent_contexts::Entity::delete_many()
+        .filter(recent_contexts::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?;
+    // Settings kept per profile, like the position in the queue
+    settings::Entity::delete_many()
+        .filter(settings::Column::Key.starts_with(format!("users.{}.", user_id)))
+        .exec(&txn)
+        .await?;
+    let result = users::Entity::delete_by_id(user_id).exec(&txn).await?;
+
+    if get_setting(&txn, ACTIVE_USER_KEY).await? == Some(user_id.to_string()) {
+        set_setting(&txn, ACTIVE_USER_KEY, DEFAULT_USER_ID.to_string()).await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(result.rows_affected > 0)
+}
+
+/// Get the profile plays, ratings, pins and the queue are recorded for.
+///
+/// # Arguments
+/// * `user_db` - The database holding the profiles.
+///
+/// # Returns
+/// * `Result<i32, DbErr>` - The ID of the profile, the default one if none was chosen.
+pub async fn get_active_user_id(user_db: &DatabaseConnection) -> Result<i32, DbErr> {
+    let Some(user_id) = get_setting(user_db, ACTIVE_USER_KEY)
+        .await?
+        .and_then(|x| x.parse::<i32>().ok())
+    else {
+        return Ok(DEFAULT_USER_ID);
+    };
+
+    // The profile may have been removed by an older version
+    let exists = users::Entity::find_by_id(user_id)
+        .one(user_db)
+        .await?
+        .is_some();
+
+    Ok(if exists { user_id } else { DEFAULT_USER_ID })
+}
+
+/// Make a profile the active one.
+///
+/// # Arguments
+/// * `user_db` - The database holding the profiles.
+/// * `user_id` - The ID of the profile.
+///
+/// # Returns
+/// * `Result<bool, DbErr>` - Whether the profile exists, nothing changes if it doesn't.
+pub async fn set_active_user_id(user_db: &DatabaseConnection, user_id: i32) -> Result<bool, DbErr> {
+    if users::Entity::find_by_id(user_id)
+        .one(user_db)
+        .await?
+        .is_none()
+    {
+        return Ok(false);
+    }
+
+    set_setting(user_db, ACTIVE_USER_KEY, user_id.to_string()).await?;
+
+    Ok(true)
+}
@@ -1,10 +1,18 @@
 use async_trait::async_trait;
 use deunicode::deunicode;
+use pinyin::ToPinyin;
 use sea_orm::prelude::*;
-use sea_orm::{ColumnTrait, DatabaseConnection, DatabaseTransaction, EntityTrait, QuerySelect};
+use sea_orm::sea_query::SelectStatement;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DatabaseTransaction, EntityTrait, QuerySelect, QueryTrait,
+};
 use std::future::Future;
 use std::pin::Pin;
 
+use crate::entities::media_files;
+
+use super::collation::get_collator;
+
 pub trait DatabaseExecutor: Send + Sync {}
 
 impl DatabaseExecutor for DatabaseConnection {}
@@ -18,25 +26,78 @@ pub fn first_char(s: &str) -> char {
     }
 }
 
+// Korean indexes merge the tense consonants into their plain form
+const HANGUL_INITIALS: [char; 19] = [
+    'ㄱ', 'ㄱ', 'ㄴ', 'ㄷ', 'ㄷ', 'ㄹ', 'ㅁ', 'ㅂ', 'ㅂ', 'ㅅ', 'ㅅ', 'ㅇ', 'ㅈ', 'ㅈ', 'ㅊ', 'ㅋ',
+    'ㅌ', 'ㅍ', 'ㅎ',
+];
+
+fn hangul_initial(c: char) -> Option<char> {
+    let index = (c as u32).checked_sub(0xAC00)?;
+    HANGUL_INITIALS.get((index / 588) as usize).copied()
+}
+
+fn is_latin(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0041}'..='\u{024F}'
+            | '\u{1E00}'..='\u{1EFF}'
+            | '\u{2C60}'..='\u{2C7F}'
+            | '\u{A720}'..='\u{A7FF}'
+            | '\u{FF21}'..='\u{FF5A}'
+    )
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3040}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9D}'
+    )
+}
+
+/// The initial a name is listed under in indexed lists.
+///
+/// Letters keep their own script, so Cyrillic and Greek names get sections
+/// of their own. Latin letters lose their accents, Chinese characters are
+/// grouped by the first letter of their pinyin, kana by their romaji and
+/// Hangul by its initial consonant. Digits and symbols go to `#`.
 pub fn generate_group_name(x: &str) -> String {
-    let c = first_char(x);
+    let Some(c) = x.trim_start().chars().next() else {
+        return '#'.to_string();
+    };
 
-    if c.is_lowercase() {
-        c.to_ascii_uppercase().to_string()
-    } else if c.is_ascii_digit() || !c.is_alphabetic() {
-        '#'.to_string()
+    let initial = if let Some(pinyin) = c.to_pinyin() {
+        pinyin.plain().chars().next()
+    } else if let Some(initial) = hangul_initial(c) {
+        return initial.to_string();
+    } else if !c.is_alphabetic() {
+        None
+    } else if is_latin(c) || is_kana(c) {
+        Some(first_char(&c.to_string()))
     } else {
-        c.to_string()
+        Some(c)
+    };
+
+    match initial {
+        Some(c) if c.is_alphabetic() => c.to_uppercase().collect(),
+        _ => '#'.to_string(),
     }
 }
 
+/// The key artists and albums are sorted by, their sort tag if there is one.
+pub fn sort_key(name: &str, sort_name: Option<&str>) -> String {
+    deunicode(sort_name.unwrap_or(name)).to_lowercase()
+}
+
 #[async_trait]
 pub trait CountByFirstLetter: EntityTrait {
     fn group_column() -> Self::Column;
     fn id_column() -> Self::Column;
 
+    /// Count the entities of every group, in the order of the library's
+    /// collation with `#` last.
     async fn count_by_first_letter(db: &DatabaseConnection) -> Result<Vec<(String, i32)>, DbErr> {
-        let results = Self::find()
+        let mut results = Self::find()
             .select_only()
             .column(Self::group_column())
             .column_as(Self::id_column().count(), "count")
@@ -45,10 +106,23 @@ pub trait CountByFirstLetter: EntityTrait {
             .all(db)
             .await?;
 
+        let collator = get_collator(db).await?;
+        results.sort_by(|a, b| collator.compare_groups(&a.0, &b.0));
+
         Ok(results)
     }
 }
 
+/// The IDs of the files in the library, files missing since a scan are left
+/// out until they return.
+pub fn live_file_ids_query() -> SelectStatement {
+    media_files::Entity::find()
+        .select_only()
+        .column(media_files::Column::Id)
+        .filter(media_files::Column::DeletedAt.is_null())
+        .into_query()
+}
+
 pub fn create_count_by_first_letter<E>() -> impl for<'a> Fn(
     &'a DatabaseConnection,
 ) -> Pin<
@@ -131,7 +205,7 @@ macro_rules! get_cover_ids {
 
 #[macro_export]
 macro_rules! get_groups {
-    ($fn_name:ident, $item_entity:ident, $related_entity:ident, $relation_column_name:ident) => {
+    ($fn_name:ident, $item_entity:ident, $related_entity:ident, $relation_column_name:ident $(, $sort_key:expr)?) => {
         pub async fn $fn_name(
             db: &DatabaseConnection,
             groups: Vec<String>,
@@ -149,6 +223,12 @@ macro_rules! get_groups {
                 .filter($item_entity::Column::Group.is_in(groups.clone()))
                 .all(db)
                 .await?;
+            $(
+                let mut entities = entities;
+                $crate::actions::collation::get_collator(db)
+                    .await?
+                    .sort_by_key(&mut entities, $sort_key);
+            )?
 
             // Step 2: Collect entity IDs
             let entity_ids: Vec<i32> = entities.iter().map(|x| x.id).collect();
@@ -200,6 +280,10 @@ macro_rules! get_all_ids {
             // Fetch related media files for these entities
             let ids = <$related_entity::Entity>::find()
                 .filter(<$related_entity::Column>::$relation_column_name.eq(id))
+                .filter(
+                    <$related_entity::Column>::MediaFileId
+                        .in_subquery($crate::actions::utils::live_file_ids_query()),
+                )
                 .all(db)
                 .await?
                 .into_iter()
@@ -226,6 +310,33 @@ macro_rules! get_by_ids {
             Ok(items)
         }
     };
+    // Collections made of the files they hold, hidden while all of them are
+    // missing
+    ($fn_name:ident, $item_entity:ident, $related_entity:ident, $relation_column_name:ident) => {
+        pub async fn $fn_name(
+            db: &DatabaseConnection,
+            ids: &[i32],
+        ) -> Result<Vec<$item_entity::Model>, sea_orm::DbErr> {
+            use sea_orm::{QuerySelect, QueryTrait};
+
+            let live_ids = <$related_entity::Entity>::find()
+                .select_only()
+                .column(<$related_entity::Column>::$relation_column_name)
+                .filter(
+                    <$related_entity::Column>::MediaFileId
+                        .in_subquery($crate::actions::utils::live_file_ids_query()),
+                )
+                .into_query();
+
+            let items = <$item_entity::Entity>::find()
+                .filter(<$item_entity::Column>::Id.is_in(ids.to_vec()))
+                .filter(<$item_entity::Column>::Id.in_subquery(live_ids))
+                .all(db)
+                .await?;
+
+            Ok(items)
+        }
+    };
 }
 
 #[macro_export]
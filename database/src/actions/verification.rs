@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+
+use chrono::Utc;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, QueryOrder, QuerySelect, TransactionTrait};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use metadata::probe::ProbeError;
+use metadata::verify::{verify_file, VerifyStatus};
+
+use crate::connection::is_library_root_reachable;
+use crate::entities::{media_files, media_verifications};
+
+pub const VERIFIED_OK: &str = "ok";
+pub const VERIFIED_TRUNCATED: &str = "truncated";
+pub const VERIFIED_CHECKSUM_MISMATCH: &str = "checksum_mismatch";
+pub const VERIFIED_CORRUPT: &str = "corrupt";
+pub const VERIFIED_UNREADABLE: &str = "unreadable";
+
+// Decoding is slow, small batches keep cancelling responsive
+const VERIFY_BATCH_SIZE: usize = 8;
+
+/// A file that failed the last verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationFailure {
+    pub file_id: i32,
+    pub directory: String,
+    pub file_name: String,
+    /// One of the `VERIFIED_` statuses other than `VERIFIED_OK`.
+    pub status: String,
+    pub detail: Option<String>,
+}
+
+fn status_of(result: Result<VerifyStatus, ProbeError>) -> (&'static str, Option<String>) {
+    match result {
+        Ok(VerifyStatus::Ok) => (VERIFIED_OK, None),
+        Ok(VerifyStatus::Truncated {
+            decoded_frames,
+            expected_frames,
+        }) => (
+            VERIFIED_TRUNCATED,
+            Some(format!(
+                "{} of {} frames decoded",
+                decoded_frames, expected_frames
+            )),
+        ),
+        Ok(VerifyStatus::ChecksumMismatch) => (VERIFIED_CHECKSUM_MISMATCH, None),
+        Ok(VerifyStatus::Corrupt(e)) => (VERIFIED_CORRUPT, Some(e)),
+        Err(e) => (VERIFIED_UNREADABLE, Some(e.to_string())),
+    }
+}
+
+/// Fully decode the files of the library to find truncated and corrupted
+/// ones, storing the status of every file.
+///
+/// Files verified before are skipped until their content changes, files
+/// that couldn't be read are tried again.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `lib_path` - The root of the library.
+/// * `progress_callback` - Called with the files verified and the total
+///   after every batch.
+/// * `cancel_token` - Stops the pass after the current batch.
+///
+/// # Returns
+/// * `Result<Vec<VerificationFailure>, DbErr>` - The files that failed, see
+///   `get_verification_failures`.
+pub async fn verify_library<F>(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    progress_callback: F,
+    cancel_token: Option<CancellationToken>,
+) -> Result<Vec<VerificationFailure>, DbErr>
+where
+    F: Fn(usize, usize) + Send + Sync,
+{
+    let files: Vec<media_files::Model> = media_files::Entity::find()
+        .filter(media_files::Column::DeletedAt.is_null())
+        .all(main_db)
+        .await?;
+    let verified: HashMap<i32, String> = media_verifications::Entity::find()
+        .select_only()
+        .column(media_verifications::Column::FileId)
+        .column(media_verifications::Column::FileHash)
+        .filter(media_verifications::Column::Status.ne(VERIFIED_UNREADABLE))
+        .into_tuple()
+        .all(main_db)
+        .await?
+        .into_iter()
+        .collect();
+
+    let total_tasks = files.len();
+    let pending: Vec<media_files::Model> = files
+        .into_iter()
+        .filter(|x| verified.get(&x.id) != Some(&x.file_hash))
+        .collect();
+    let mut total_processed = total_tasks - pending.len();
+    info!("Media files to verify: {}", pending.len());
+
+    for batch in pending.chunks(VERIFY_BATCH_SIZE) {
+        if cancel_token.as_ref().is_some_and(|x| x.is_cancelled()) {
+            info!("Cancellation requested. Exiting loop.");
+            break;
+        }
+
+        if !is_library_root_reachable(lib_path) {
+            return Err(DbErr::Custom(format!(
+                "Library root is offline: {:?}",
+                lib_path
+            )));
+        }
+
+        let results: Vec<_> = batch
+            .par_iter()
+            .map(|file| {
+                let file_path = lib_path.join(&file.directory).join(&file.file_name);
+                let result = catch_unwind(AssertUnwindSafe(|| verify_file(&file_path)))
+                    .unwrap_or_else(|_| Ok(VerifyStatus::Corrupt("decoding panicked".to_string())));
+                (file, status_of(result))
+            })
+            .collect();
+
+        let now = Utc::now().to_rfc3339();
+        let txn = main_db.begin().await?;
+        for (file, (status, detail)) in results {
+            media_verifications::Entity::delete_many()
+                .filter(media_verifications::Column::FileId.eq(file.id))
+                .exec(&txn)
+                .await?;
+            media_verifications::ActiveModel {
+                file_id: ActiveValue::Set(file.id),
+                file_hash: ActiveValue::Set(file.file_hash.clone()),
+                status: ActiveValue::Set(status.to_string()),
+                detail: ActiveValue::Set(detail),
+                verified_at: ActiveValue::Set(now.clone()),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+
+        total_processed += batch.len();
+        progress_callback(total_processed, total_tasks);
+    }
+
+    info!("Verification completed.");
+    get_verification_failures(main_db).await
+}
+
+/// List the files that failed their last verification.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+///
+/// # Returns
+/// * `Result<Vec<VerificationFailure>, DbErr>` - The failures by file ID,
+///   missing files are left out.
+pub async fn get_verification_failures(
+    main_db: &DatabaseConnection,
+) -> Result<Vec<VerificationFailure>, DbErr> {
+    let items = media_verifications::Entity::find()
+        .find_also_related(media_files::Entity)
+        .filter(media_verifications::Column::Status.ne(VERIFIED_OK))
+        .filter(media_files::Column::DeletedAt.is_null())
+        .order_by_asc(media_verifications::Column::FileId)
+        .all(main_db)
+        .await?;
+
+    Ok(items
+        .into_iter()
+        .filter_map(|(verification, file)| {
+            let file = file?;
+            Some(VerificationFailure {
+                file_id: file.id,
+                directory: file.directory,
+                file_name: file.file_name,
+                status: verification.status,
+                detail: verification.detail,
+            })
+        })
+        .collect())
+}
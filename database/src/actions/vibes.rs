@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+
+use sea_orm::prelude::*;
+use sea_orm::sea_query::SelectStatement;
+use sea_orm::{Condition, QueryOrder, QuerySelect, QueryTrait};
+
+use crate::entities::media_analysis;
+
+/// A feature of the analysis that tracks can be filtered by. Values are
+/// normalized by the analysis, see `normalize_analysis_result`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnalysisFeature {
+    SpectralCentroid,
+    SpectralFlatness,
+    SpectralSlope,
+    SpectralRolloff,
+    SpectralSpread,
+    SpectralSkewness,
+    SpectralKurtosis,
+}
+
+impl AnalysisFeature {
+    pub fn column(&self) -> media_analysis::Column {
+        match self {
+            AnalysisFeature::SpectralCentroid => media_analysis::Column::SpectralCentroid,
+            AnalysisFeature::SpectralFlatness => media_analysis::Column::SpectralFlatness,
+            AnalysisFeature::SpectralSlope => media_analysis::Column::SpectralSlope,
+            AnalysisFeature::SpectralRolloff => media_analysis::Column::SpectralRolloff,
+            AnalysisFeature::SpectralSpread => media_analysis::Column::SpectralSpread,
+            AnalysisFeature::SpectralSkewness => media_analysis::Column::SpectralSkewness,
+            AnalysisFeature::SpectralKurtosis => media_analysis::Column::SpectralKurtosis,
+        }
+    }
+}
+
+/// Tracks with a feature between two bounds, both included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeatureRange {
+    pub feature: AnalysisFeature,
+    /// The lowest value kept, `None` for no bound.
+    pub min: Option<f64>,
+    /// The highest value kept, `None` for no bound.
+    pub max: Option<f64>,
+}
+
+impl FeatureRange {
+    fn condition(&self) -> Condition {
+        let column = self.feature.column();
+        let mut condition = Condition::all().add(column.is_not_null());
+        if let Some(min) = self.min {
+            condition = condition.add(column.gte(min));
+        }
+        if let Some(max) = self.max {
+            condition = condition.add(column.lte(max));
+        }
+        condition
+    }
+}
+
+fn ranges_condition(ranges: &[FeatureRange]) -> Condition {
+    ranges
+        .iter()
+        .fold(Condition::all(), |all, x| all.add(x.condition()))
+}
+
+/// Select the IDs of the analysed files within every range, to filter
+/// queries with. Files without analysis are left out.
+pub fn feature_file_ids_query(ranges: &[FeatureRange]) -> SelectStatement {
+    media_analysis::Entity::find()
+        .select_only()
+        .column(media_analysis::Column::FileId)
+        .filter(ranges_condition(ranges))
+        .into_query()
+}
+
+/// Named buckets of the analysis for browsing by feel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Vibe {
+    /// A high spectral centroid, lots of treble.
+    Bright,
+    /// A low spectral centroid, mostly bass and mids.
+    Dark,
+    /// A tonal spectrum, as of acoustic instruments.
+    Acoustic,
+    /// A flat, noise-like spectrum, as of synthesizers and drum machines.
+    Electronic,
+}
+
+impl Vibe {
+    pub fn parse(name: &str) -> Option<Vibe> {
+        match name.trim().to_lowercase().as_str() {
+            "bright" => Some(Vibe::Bright),
+            "dark" => Some(Vibe::Dark),
+            "acoustic" => Some(Vibe::Acoustic),
+            "electronic" => Some(Vibe::Electronic),
+            _ => None,
+        }
+    }
+
+    fn feature(&self) -> AnalysisFeature {
+        match self {
+            Vibe::Bright | Vibe::Dark => AnalysisFeature::SpectralCentroid,
+            Vibe::Acoustic | Vibe::Electronic => AnalysisFeature::SpectralFlatness,
+        }
+    }
+
+    // Whether the vibe is the upper end of its feature
+    fn is_high(&self) -> bool {
+        matches!(self, Vibe::Bright | Vibe::Electronic)
+    }
+}
+
+// The value below which the given share of the sorted values lie
+fn quantile(sorted: &[f64], share: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let index = ((sorted.len() - 1) as f64 * share).round() as usize;
+    Some(sorted[index])
+}
+
+/// Turn vibes into ranges of the features behind them.
+///
+/// The buckets follow the library rather than fixed values, as the features
+/// depend on the mastering as much as on the music: bright tracks are the
+/// third of the library with the highest spectral centroid, dark ones the
+/// third with the lowest, and likewise for the flatness.
+///
+/// # Arguments
+/// * `db` - A reference to the database connection.
+/// * `vibes` - The vibes to combine, a track has to fit all of them.
+///
+/// # Returns
+/// * `Result<Vec<FeatureRange>, DbErr>` - A range per vibe.
+pub async fn get_vibe_ranges(
+    db: &DatabaseConnection,
+    vibes: &[Vibe],
+) -> Result<Vec<FeatureRange>, DbErr> {
+    let mut ranges = Vec::with_capacity(vibes.len());
+
+    for vibe in vibes {
+        let feature = vibe.feature();
+        let values: Vec<f64> = media_analysis::Entity::find()
+            .select_only()
+            .column(feature.column())
+            .filter(feature.column().is_not_null())
+            .order_by_asc(feature.column())
+            .into_tuple::<f64>()
+            .all(db)
+            .await?;
+
+        ranges.push(if vibe.is_high() {
+            FeatureRange {
+                feature,
+                min: quantile(&values, 2.0 / 3.0),
+                max: None,
+            }
+        } else {
+            FeatureRange {
+                feature,
+                min: None,
+                max: quantile(&values, 1.0 / 3.0),
+            }
+        });
+    }
+
+    Ok(ranges)
+}
+
+/// Keep the tracks within every range, for narrowing search results.
+///
+/// # Returns
+/// * `Result<Vec<i32>, DbErr>` - The files kept, in their order.
+pub async fn filter_by_features(
+    db: &DatabaseConnection,
+    ranges: &[FeatureRange],
+    file_ids: Vec<i32>,
+) -> Result<Vec<i32>, DbErr> {
+    if ranges.is_empty() {
+        return Ok(file_ids);
+    }
+
+    let kept: HashSet<i32> = media_analysis::Entity::find()
+        .select_only()
+        .column(media_analysis::Column::FileId)
+        .filter(media_analysis::Column::FileId.is_in(file_ids.clone()))
+        .filter(ranges_condition(ranges))
+        .into_tuple::<i32>()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+
+    Ok(file_ids.into_iter().filter(|x| kept.contains(x)).collect())
+}
@@ -1,22 +1,29 @@
 use std::error::Error;
 use std::ffi::OsString;
 use std::fmt;
-use std::fs::create_dir_all;
-use std::path::PathBuf;
+use std::fs::{self, create_dir_all, File};
+use std::path::{Path, PathBuf};
 use std::result::Result;
 
 use arroy::distances::Euclidean;
 use arroy::Database as ArroyDatabase;
-use heed::{Env, EnvOpenOptions};
-use log::{info, LevelFilter};
+use heed::byteorder::BigEndian;
+use heed::types::{Str, U64};
+use heed::{Env, EnvFlags, EnvOpenOptions};
+use log::LevelFilter;
 use sea_orm::DbErr;
-use sea_orm::{ConnectOptions, Database};
+use sea_orm::{ConnectOptions, ConnectionTrait, Database, Statement, TransactionTrait};
+use tantivy::directory::{Directory, RamDirectory};
 use tantivy::{schema::*, IndexReader, TantivyError};
 use tantivy::{Index, IndexWriter, ReloadPolicy};
+use tracing::{info, warn};
 
 use migration::Migrator;
 use migration::MigratorTrait;
 
+use crate::actions::search::SearchSynonyms;
+use crate::entities::analysis_cache;
+
 #[derive(Debug)]
 pub enum ConnectMainDbError {
     InvalidPath(OsString),
@@ -56,6 +63,15 @@ impl From<DbErr> for ConnectMainDbError {
 
 pub type MainDbConnection = sea_orm::DatabaseConnection;
 
+/// Connections reading the main database next to its writer, see
+/// `connect_main_db_readers`.
+pub const DEFAULT_MAIN_DB_READERS: u32 = 4;
+
+/// Open the main database for writing, creating and migrating it if needed.
+///
+/// All writes go through a single connection, so they never wait on each
+/// other for the lock. The database is switched to WAL mode, which lets the
+/// connections of `connect_main_db_readers` read while a scan is writing.
 pub async fn connect_main_db(lib_path: &str) -> Result<MainDbConnection, ConnectMainDbError> {
     let path: PathBuf = [lib_path, ".rune", ".0.db"].iter().collect();
 
@@ -70,22 +86,337 @@ pub async fn connect_main_db(lib_path: &str) -> Result<MainDbConnection, Connect
     let path_str = path.into_os_string().into_string().unwrap();
     let db_url = format!("sqlite:{}?mode=rwc", path_str);
     let mut opt = ConnectOptions::new(db_url);
-    opt.sqlx_logging(true)
+    opt.max_connections(1)
+        .min_connections(1)
+        .sqlx_logging(true)
         .sqlx_logging_level(LevelFilter::Debug);
 
     info!("Initializing main database: {}", path_str);
 
-    let db = Database::connect(opt).await?;
+    let mut db = Database::connect(opt).await?;
+    db.set_metric_callback(|info| metrics::DB_QUERIES.record(info.elapsed));
 
+    // Stored in the file, readers opened later use it as well
+    db.execute_unprepared("PRAGMA journal_mode=WAL").await?;
     initialize_db(&db).await?;
 
     Ok(db)
 }
 
+/// Open the local overlay holding the play state and settings of a
+/// read-only library, creating and migrating it if needed.
+///
+/// The files live in the main database of the library, the `media_files`
+/// table of the overlay stays empty. Tables referencing it are rebuilt
+/// without those foreign keys, so queues, ratings and positions can be
+/// stored for any file of the library.
+///
+/// # Arguments
+/// * `overlay_path` - The local directory of the overlay.
+///
+/// # Returns
+/// * `Result<MainDbConnection, ConnectMainDbError>` - The database connection.
+pub async fn connect_overlay_db(
+    overlay_path: &str,
+) -> Result<MainDbConnection, ConnectMainDbError> {
+    let db = connect_main_db(overlay_path).await?;
+    drop_media_file_foreign_keys(&db).await?;
+
+    Ok(db)
+}
+
+// Remove the foreign key clauses referencing `parent` from a CREATE TABLE
+// statement, returning the column and constraint list.
+fn table_body_without_foreign_keys(sql: &str, parent: &str) -> Option<String> {
+    let start = sql.find('(')?;
+    let end = sql.rfind(')')?;
+    let body = &sql[start + 1..end];
+
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut part_start = 0;
+    for (i, x) in body.char_indices() {
+        match (quote, x) {
+            (Some(q), _) if x == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '`' | '\'') => quote = Some(x),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(&body[part_start..i]);
+                part_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&body[part_start..]);
+
+    let reference = format!("REFERENCES {}", parent.to_uppercase());
+    let kept: Vec<&str> = parts
+        .into_iter()
+        .filter(|part| {
+            let normalized: String = part
+                .chars()
+                .filter(|x| !matches!(x, '"' | '`' | '[' | ']'))
+                .collect::<String>()
+                .to_uppercase();
+            let is_constraint = normalized.trim_start().starts_with("FOREIGN KEY")
+                || normalized.trim_start().starts_with("CONSTRAINT");
+
+            !(is_constraint
+                && normalized
+                    .split(&reference)
+                    .skip(1)
+                    .any(|rest| rest.starts_with(|x: char| x.is_whitespace() || x == '(')))
+        })
+        .map(|x| x.trim())
+        .collect();
+
+    Some(kept.join(", "))
+}
+
+// SQLite can't drop a constraint, the tables are copied into new ones
+async fn drop_media_file_foreign_keys(db: &MainDbConnection) -> Result<(), DbErr> {
+    let backend = db.get_database_backend();
+    let tables = db
+        .query_all(Statement::from_string(
+            backend,
+            "SELECT DISTINCT m.name, m.sql FROM sqlite_master m, pragma_foreign_key_list(m.name) f \
+             WHERE m.type = 'table' AND f.\"table\" = 'media_files'",
+        ))
+        .await?;
+    if tables.is_empty() {
+        return Ok(());
+    }
+
+    // Cascades must not run while the old tables are dropped
+    db.execute_unprepared("PRAGMA foreign_keys = OFF").await?;
+    let result = async {
+        let txn = db.begin().await?;
+        for table in tables {
+            let name: String = table.try_get("", "name")?;
+            let sql: String = table.try_get("", "sql")?;
+            let body = table_body_without_foreign_keys(&sql, "media_files")
+                .ok_or_else(|| DbErr::Custom(format!("Unable to parse the schema of {}", name)))?;
+            let indexes: Vec<String> = txn
+                .query_all(Statement::from_sql_and_values(
+                    backend,
+                    "SELECT sql FROM sqlite_master \
+                     WHERE type = 'index' AND tbl_name = ? AND sql IS NOT NULL",
+                    [name.clone().into()],
+                ))
+                .await?
+                .into_iter()
+                .map(|x| x.try_get("", "sql"))
+                .collect::<Result<_, _>>()?;
+
+            info!(
+                "Dropping the media file foreign keys of {} in the overlay",
+                name
+            );
+            for statement in [
+                format!("CREATE TABLE \"{name}_overlay\" ({body})"),
+                format!("INSERT INTO \"{name}_overlay\" SELECT * FROM \"{name}\""),
+                format!("DROP TABLE \"{name}\""),
+                format!("ALTER TABLE \"{name}_overlay\" RENAME TO \"{name}\""),
+            ]
+            .into_iter()
+            .chain(indexes)
+            {
+                txn.execute_unprepared(&statement).await?;
+            }
+        }
+        txn.commit().await
+    }
+    .await;
+    db.execute_unprepared("PRAGMA foreign_keys = ON").await?;
+
+    result
+}
+
+/// Open a pool of read-only connections to the main database.
+///
+/// Long read-only queries like browsing and statistics go through these, so
+/// they don't queue up behind the writer while a library is being scanned.
+/// The database must have been opened with `connect_main_db` first.
+///
+/// # Arguments
+/// * `lib_path` - The root directory of the media library.
+/// * `readers` - The maximum number of connections, at least one is used.
+///
+/// # Returns
+/// * `Result<MainDbConnection, ConnectMainDbError>` - The pool of readers.
+pub async fn connect_main_db_readers(
+    lib_path: &str,
+    readers: u32,
+) -> Result<MainDbConnection, ConnectMainDbError> {
+    let path: PathBuf = [lib_path, ".rune", ".0.db"].iter().collect();
+
+    if !path.exists() {
+        return Err(ConnectMainDbError::InvalidPath(path.into_os_string()));
+    }
+
+    let path_str = path.into_os_string().into_string().unwrap();
+    let db_url = format!("sqlite:{}?mode=ro", path_str);
+    let mut opt = ConnectOptions::new(db_url);
+    opt.max_connections(readers.max(1))
+        .min_connections(1)
+        .sqlx_logging(true)
+        .sqlx_logging_level(LevelFilter::Debug);
+
+    info!(
+        "Initializing {} main database readers: {}",
+        readers.max(1),
+        path_str
+    );
+
+    let mut db = Database::connect(opt).await?;
+    db.set_metric_callback(|info| metrics::DB_QUERIES.record(info.elapsed));
+
+    Ok(db)
+}
+
+/// Check if the library can be written to, e.g. it is not on a read-only
+/// volume like a mounted image or a read-only network share.
+///
+/// # Arguments
+/// * `lib_path` - The root directory of the media library.
+///
+/// # Returns
+/// * `bool` - True if files can't be created in the library.
+pub fn is_library_read_only(lib_path: &str) -> bool {
+    let probe: PathBuf = [lib_path, &format!(".rune-probe-{}", std::process::id())]
+        .iter()
+        .collect();
+
+    match File::create(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            false
+        }
+        Err(e) => {
+            warn!("Library is not writable ({}), opening read-only", e);
+            true
+        }
+    }
+}
+
+/// Check if the root of a library can currently be reached.
+///
+/// A network share that dropped off leaves a missing or unreadable root
+/// behind. An empty root is a reachable library without files.
+///
+/// # Arguments
+/// * `lib_path` - The root directory of the media library.
+///
+/// # Returns
+/// * `bool` - True if the library root is a directory that can be listed.
+pub fn is_library_root_reachable(lib_path: &Path) -> bool {
+    match fs::read_dir(lib_path) {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("Library root {:?} is unreachable: {}", lib_path, e);
+            false
+        }
+    }
+}
+
+async fn connect_read_only_url(db_url: String) -> Result<MainDbConnection, DbErr> {
+    let mut opt = ConnectOptions::new(db_url);
+    opt.sqlx_logging(true)
+        .sqlx_logging_level(LevelFilter::Debug);
+
+    let mut db = Database::connect(opt).await?;
+    db.set_metric_callback(|info| metrics::DB_QUERIES.record(info.elapsed));
+
+    Ok(db)
+}
+
+/// Open the main database of a library without ever writing to it.
+///
+/// Migrations are not applied, the schema is used as it was written.
+///
+/// An immutable connection needs no lock or shared memory file, so it
+/// opens libraries on read-only media, but it ignores the WAL. A database
+/// whose WAL still holds pages that were not checkpointed is opened as a
+/// plain read-only connection instead, which reads them. Immutable is only
+/// the fallback when that fails.
+pub async fn connect_main_db_read_only(
+    lib_path: &str,
+) -> Result<MainDbConnection, ConnectMainDbError> {
+    let path: PathBuf = [lib_path, ".rune", ".0.db"].iter().collect();
+
+    if !path.exists() {
+        return Err(ConnectMainDbError::InvalidPath(path.into_os_string()));
+    }
+
+    let wal_path = path.with_file_name(".0.db-wal");
+    let has_wal = fs::metadata(&wal_path).is_ok_and(|x| x.len() > 0);
+
+    let path_str = path.into_os_string().into_string().unwrap();
+    info!("Initializing read-only main database: {}", path_str);
+
+    if has_wal {
+        match connect_read_only_url(format!("sqlite:{}?mode=ro", path_str)).await {
+            Ok(db) => return Ok(db),
+            Err(e) => warn!(
+                "Failed to read the WAL of {}, changes that were not checkpointed are missing: {}",
+                path_str, e
+            ),
+        }
+    }
+
+    Ok(connect_read_only_url(format!("sqlite:{}?mode=ro&immutable=true", path_str)).await?)
+}
+
 pub async fn initialize_db(conn: &sea_orm::DatabaseConnection) -> Result<(), DbErr> {
     Migrator::up(conn, None).await
 }
 
+pub type AnalysisCacheConnection = sea_orm::DatabaseConnection;
+
+/// Open the analysis cache shared by all libraries, creating it if needed.
+///
+/// # Arguments
+/// * `path` - The database file, usually in the local data directory of the app.
+///
+/// # Returns
+/// * `Result<AnalysisCacheConnection, ConnectMainDbError>` - The database connection.
+pub async fn connect_analysis_cache_db(
+    path: &Path,
+) -> Result<AnalysisCacheConnection, ConnectMainDbError> {
+    let dir_path = path.parent().ok_or_else(|| {
+        ConnectMainDbError::InvalidPath("Invalid path: parent directory not found".into())
+    })?;
+
+    if !dir_path.exists() {
+        create_dir_all(dir_path)?;
+    }
+
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| ConnectMainDbError::InvalidPath(path.as_os_str().to_owned()))?;
+    let mut opt = ConnectOptions::new(format!("sqlite:{}?mode=rwc", path_str));
+    opt.sqlx_logging(true)
+        .sqlx_logging_level(LevelFilter::Debug);
+
+    info!("Initializing analysis cache: {}", path_str);
+
+    let db = Database::connect(opt).await?;
+
+    // The cache only has one table and isn't tied to a library version,
+    // so it is created directly instead of going through the migrator
+    let backend = db.get_database_backend();
+    let statement = sea_orm::Schema::new(backend)
+        .create_table_from_entity(analysis_cache::Entity)
+        .if_not_exists()
+        .to_owned();
+    db.execute(backend.build(&statement)).await?;
+
+    Ok(db)
+}
+
 #[derive(Debug)]
 enum ConnectRecommendationDbError {
     InvalidPath(OsString),
@@ -120,10 +451,17 @@ impl fmt::Display for ConnectRecommendationDbError {
 impl Error for ConnectRecommendationDbError {}
 
 const DB_SIZE: usize = 2 * 1024 * 1024 * 1024;
+const PARAMETERS_DB_NAME: &str = "parameters";
+
+/// Named values describing how the index is built and searched.
+pub type ParametersDatabase = heed::Database<Str, U64<BigEndian>>;
 
 pub struct RecommendationDbConnection {
     pub env: Env,
     pub db: ArroyDatabase<Euclidean>,
+    /// Missing when a library from before index parameters were stored is
+    /// opened read-only.
+    pub parameters: Option<ParametersDatabase>,
 }
 
 /// Initialize the recommendation database.
@@ -152,6 +490,7 @@ pub fn connect_recommendation_db(
     let env = unsafe {
         EnvOpenOptions::new()
             .map_size(DB_SIZE)
+            .max_dbs(1)
             .open(path_str)
             .map_err(|e| ConnectRecommendationDbError::EnvOpenError(Box::new(e)))?
     };
@@ -163,11 +502,60 @@ pub fn connect_recommendation_db(
     let db: ArroyDatabase<Euclidean> = env
         .create_database(&mut wtxn, None)
         .map_err(|e| ConnectRecommendationDbError::CreateDbError(Box::new(e)))?;
+    let parameters: ParametersDatabase =
+        env.create_database(&mut wtxn, Some(PARAMETERS_DB_NAME))
+            .map_err(|e| ConnectRecommendationDbError::CreateDbError(Box::new(e)))?;
 
     wtxn.commit()
         .map_err(|e| ConnectRecommendationDbError::CommitError(Box::new(e)))?;
 
-    Ok(RecommendationDbConnection { env, db })
+    Ok(RecommendationDbConnection {
+        env,
+        db,
+        parameters: Some(parameters),
+    })
+}
+
+/// Open the recommendation database of a library without ever writing to it.
+pub fn connect_recommendation_db_read_only(
+    lib_path: &str,
+) -> Result<RecommendationDbConnection, Box<dyn Error>> {
+    let path: PathBuf = [lib_path, ".rune", ".analysis"].iter().collect();
+
+    let path_str = path
+        .into_os_string()
+        .into_string()
+        .map_err(ConnectRecommendationDbError::InvalidPath)?;
+
+    info!(
+        "Initializing read-only recommendation database: {}",
+        path_str
+    );
+
+    // No lock file can be created on a read-only volume, nothing else
+    // writes to the environment while it is opened this way.
+    let env = unsafe {
+        EnvOpenOptions::new()
+            .map_size(DB_SIZE)
+            .max_dbs(1)
+            .flags(EnvFlags::READ_ONLY | EnvFlags::NO_LOCK)
+            .open(path_str)
+            .map_err(|e| ConnectRecommendationDbError::EnvOpenError(Box::new(e)))?
+    };
+
+    let rtxn = env.read_txn()?;
+    let db: ArroyDatabase<Euclidean> = env
+        .open_database(&rtxn, None)?
+        .ok_or("Recommendation database is not initialized")?;
+    let parameters: Option<ParametersDatabase> =
+        env.open_database(&rtxn, Some(PARAMETERS_DB_NAME))?;
+    drop(rtxn);
+
+    Ok(RecommendationDbConnection {
+        env,
+        db,
+        parameters,
+    })
 }
 
 pub struct SearchDbConnection {
@@ -175,30 +563,45 @@ pub struct SearchDbConnection {
     pub r: IndexReader,
     pub schema: Schema,
     pub index: Index,
+    // The index on disk had an older schema and was replaced by an empty
+    // one, it has to be rebuilt from the main database
+    pub outdated: bool,
+    // User defined aliases used to expand search queries
+    pub synonyms: SearchSynonyms,
 }
 
-pub fn connect_search_db(lib_path: &str) -> Result<SearchDbConnection, Box<dyn Error>> {
-    let path: PathBuf = [lib_path, ".rune", ".search"].iter().collect();
-    let exists = path.exists();
-
-    if !exists {
-        create_dir_all(path.clone())?;
+impl SearchDbConnection {
+    /// Commit the pending writes of the index writer and record the commit time.
+    pub fn commit(&mut self) -> tantivy::Result<u64> {
+        metrics::INDEX_COMMITS.time(|| self.w.commit())
     }
+}
 
+pub(crate) fn search_schema() -> Schema {
     let mut schema_builder = Schema::builder();
     schema_builder.add_text_field("name", TEXT | STORED);
     schema_builder.add_text_field("latinization", TEXT | STORED);
-    schema_builder.add_text_field("tid", TEXT | STORED);
+    // Readings of Chinese and Japanese names, deunicode doesn't cover them
+    schema_builder.add_text_field("pinyin", TEXT | STORED);
+    schema_builder.add_text_field("romaji", TEXT | STORED);
+    schema_builder.add_text_field("tid", STRING | STORED);
     schema_builder.add_i64_field("type", INDEXED | FAST);
     schema_builder.add_i64_field("id", INDEXED | FAST | STORED);
+    // Artist and album names of tracks, and the artist of albums
+    schema_builder.add_text_field("artist", TEXT | STORED);
+    schema_builder.add_text_field("album", TEXT | STORED);
+    // Values of user mapped tags that were made searchable
+    schema_builder.add_text_field("tags", TEXT | STORED);
+    // Lyrics of tracks, only searched when looking for lyrics
+    schema_builder.add_text_field("lyrics", TEXT | STORED);
+
+    schema_builder.build()
+}
 
-    let schema = schema_builder.build();
-    let index =
-        Index::create_in_dir(path.clone(), schema.clone()).or_else(|error| match error {
-            TantivyError::IndexAlreadyExists => Ok(Index::open_in_dir(path.clone())?),
-            _ => Err(error),
-        })?;
-
+pub(crate) fn open_search_index(
+    index: Index,
+    schema: Schema,
+) -> Result<SearchDbConnection, Box<dyn Error>> {
     let writer: IndexWriter = index.writer(15_000_000)?;
     let reader = index
         .reader_builder()
@@ -210,5 +613,78 @@ pub fn connect_search_db(lib_path: &str) -> Result<SearchDbConnection, Box<dyn E
         r: reader,
         schema,
         index,
+        outdated: false,
+        synonyms: SearchSynonyms::default(),
     })
 }
+
+pub fn connect_search_db(lib_path: &str) -> Result<SearchDbConnection, Box<dyn Error>> {
+    let path: PathBuf = [lib_path, ".rune", ".search"].iter().collect();
+    let exists = path.exists();
+
+    if !exists {
+        create_dir_all(path.clone())?;
+    }
+
+    let schema = search_schema();
+    let mut index =
+        Index::create_in_dir(path.clone(), schema.clone()).or_else(|error| match error {
+            TantivyError::IndexAlreadyExists => Ok(Index::open_in_dir(path.clone())?),
+            _ => Err(error),
+        })?;
+
+    let outdated = index.schema() != schema;
+    if outdated {
+        warn!("Search index schema changed, recreating it: {:?}", path);
+        fs::remove_dir_all(&path)?;
+        create_dir_all(&path)?;
+        index = Index::create_in_dir(path, schema.clone())?;
+    }
+
+    let mut connection = open_search_index(index, schema)?;
+    connection.outdated = outdated;
+
+    Ok(connection)
+}
+
+/// Open the search index of a library without ever writing to it.
+///
+/// The index is copied into memory, so the search actions keep working,
+/// but every change is discarded when the library is closed.
+pub fn connect_search_db_read_only(lib_path: &str) -> Result<SearchDbConnection, Box<dyn Error>> {
+    let path: PathBuf = [lib_path, ".rune", ".search"].iter().collect();
+
+    info!("Loading read-only search index: {:?}", path);
+
+    let directory = RamDirectory::create();
+    if path.is_dir() {
+        for entry in fs::read_dir(&path)? {
+            let entry = entry?;
+            // Lock files of the writer that created the index must not come along
+            let is_lock = entry.file_name().to_string_lossy().ends_with(".lock");
+            if entry.file_type()?.is_file() && !is_lock {
+                let data = fs::read(entry.path())?;
+                directory.atomic_write(Path::new(&entry.file_name()), &data)?;
+            }
+        }
+    }
+
+    let schema = search_schema();
+    let (index, outdated) = match Index::open(directory.clone()) {
+        Ok(index) if index.schema() == schema => (index, false),
+        Ok(_) => {
+            warn!("Search index schema changed, rebuilding it in memory");
+            let index = Index::create(RamDirectory::create(), schema.clone(), Default::default())?;
+            (index, true)
+        }
+        Err(_) => (
+            Index::create(directory, schema.clone(), Default::default())?,
+            false,
+        ),
+    };
+
+    let mut connection = open_search_index(index, schema)?;
+    connection.outdated = outdated;
+
+    Ok(connection)
+}
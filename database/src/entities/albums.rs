@@ -10,6 +10,9 @@ pub struct Model {
     #[sea_orm(unique)]
     pub name: String,
     pub group: String,
+    pub sort_name: Option<String>,
+    pub year: Option<i32>,
+    pub canonical_name: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -47,6 +47,10 @@ pub struct Model {
     pub chroma10: Option<f64>,
     #[sea_orm(column_type = "Double", nullable)]
     pub chroma11: Option<f64>,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub mix_in: Option<f64>,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub mix_out: Option<f64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
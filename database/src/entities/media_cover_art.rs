@@ -11,6 +11,8 @@ pub struct Model {
     pub file_hash: String,
     #[sea_orm(column_type = "Binary(BlobSize::Blob(None))")]
     pub binary: Vec<u8>,
+    pub color_palette: Option<String>,
+    pub blurhash: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
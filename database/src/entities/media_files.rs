@@ -18,6 +18,7 @@ pub struct Model {
     pub sample_rate: i32,
     #[sea_orm(column_type = "Double")]
     pub duration: f64,
+    pub deleted_at: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -3,14 +3,42 @@
 pub mod prelude;
 
 pub mod albums;
+pub mod analysis_cache;
 pub mod artists;
+pub mod collection_merges;
+pub mod directory_content_types;
+pub mod gain_offsets;
 pub mod media_analysis;
+pub mod media_checksums;
 pub mod media_cover_art;
 pub mod media_file_albums;
 pub mod media_file_artists;
 pub mod media_files;
+pub mod media_gapless;
+pub mod media_loudness;
+pub mod media_markers;
 pub mod media_metadata;
+pub mod media_seek_tables;
+pub mod media_verifications;
 pub mod media_file_playlists;
+pub mod output_device_profiles;
+pub mod pinned_collections;
+pub mod playback_exclusions;
+pub mod playback_positions;
+pub mod playback_queue;
 pub mod playlists;
+pub mod ratings;
+pub mod recent_context_items;
+pub mod recent_contexts;
+pub mod remote_files;
+pub mod remote_servers;
+pub mod search_aliases;
+pub mod settings;
 pub mod smart_playlists;
+pub mod sync_devices;
+pub mod synced_files;
+pub mod tag_mappings;
+pub mod taste_profiles;
+pub mod track_skips;
 pub mod user_logs;
+pub mod users;
@@ -13,6 +13,7 @@ pub struct Model {
     pub group: String,
     pub created_at: String,
     pub updated_at: String,
+    pub cover_art_id: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
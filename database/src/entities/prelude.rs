@@ -1,14 +1,42 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
 pub use super::albums::Entity as Albums;
+pub use super::analysis_cache::Entity as AnalysisCache;
 pub use super::artists::Entity as Artists;
+pub use super::collection_merges::Entity as CollectionMerges;
+pub use super::directory_content_types::Entity as DirectoryContentTypes;
+pub use super::gain_offsets::Entity as GainOffsets;
 pub use super::media_analysis::Entity as MediaAnalysis;
+pub use super::media_checksums::Entity as MediaChecksums;
 pub use super::media_cover_art::Entity as MediaCoverArt;
 pub use super::media_file_albums::Entity as MediaFileAlbums;
 pub use super::media_file_artists::Entity as MediaFileArtists;
 pub use super::media_files::Entity as MediaFiles;
+pub use super::media_gapless::Entity as MediaGapless;
+pub use super::media_loudness::Entity as MediaLoudness;
+pub use super::media_markers::Entity as MediaMarkers;
 pub use super::media_metadata::Entity as MediaMetadata;
+pub use super::media_seek_tables::Entity as MediaSeekTables;
+pub use super::media_verifications::Entity as MediaVerifications;
 pub use super::media_file_playlists::Entity as PlaylistItems;
+pub use super::output_device_profiles::Entity as OutputDeviceProfiles;
+pub use super::pinned_collections::Entity as PinnedCollections;
+pub use super::playback_exclusions::Entity as PlaybackExclusions;
+pub use super::playback_positions::Entity as PlaybackPositions;
+pub use super::playback_queue::Entity as PlaybackQueue;
 pub use super::playlists::Entity as Playlists;
+pub use super::ratings::Entity as Ratings;
+pub use super::recent_context_items::Entity as RecentContextItems;
+pub use super::recent_contexts::Entity as RecentContexts;
+pub use super::remote_files::Entity as RemoteFiles;
+pub use super::remote_servers::Entity as RemoteServers;
+pub use super::search_aliases::Entity as SearchAliases;
+pub use super::settings::Entity as Settings;
 pub use super::smart_playlists::Entity as SmartPlaylists;
+pub use super::sync_devices::Entity as SyncDevices;
+pub use super::synced_files::Entity as SyncedFiles;
+pub use super::tag_mappings::Entity as TagMappings;
+pub use super::taste_profiles::Entity as TasteProfiles;
+pub use super::track_skips::Entity as TrackSkips;
 pub use super::user_logs::Entity as UserLogs;
+pub use super::users::Entity as Users;
@@ -8,6 +8,7 @@ use sea_orm::entity::prelude::*;
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
+    pub user_id: i32,
     pub file_id: i32,
     pub listen_time: String,
     #[sea_orm(column_type = "Double")]
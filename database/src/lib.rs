@@ -1,4 +1,6 @@
 pub mod actions;
 pub mod connection;
 pub mod entities;
-pub mod schema;
\ No newline at end of file
+pub mod schema;
+#[cfg(feature = "test-support")]
+pub mod test_support;
@@ -0,0 +1,205 @@
+//! Helpers for testing database actions and hub handlers without a media
+//! library on disk. Enabled by the `test-support` feature.
+
+use std::error::Error;
+
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectOptions, Database, DbErr};
+use tantivy::Index;
+
+use crate::connection::{
+    initialize_db, open_search_index, search_schema, MainDbConnection, SearchDbConnection,
+};
+use crate::entities::{media_file_albums, media_file_artists, media_files, media_metadata};
+
+/// Create a migrated main database that lives in memory.
+///
+/// Every connection of an in-memory SQLite pool would see its own empty
+/// database, so the pool is limited to a single connection.
+pub async fn connect_main_db_in_memory() -> Result<MainDbConnection, DbErr> {
+    let mut opt = ConnectOptions::new("sqlite::memory:");
+    opt.max_connections(1)
+        .min_connections(1)
+        .sqlx_logging(false);
+
+    let db = Database::connect(opt).await?;
+    initialize_db(&db).await?;
+
+    Ok(db)
+}
+
+/// Create a search database with the same schema as `connect_search_db`, kept in RAM.
+pub fn connect_search_db_in_memory() -> Result<SearchDbConnection, Box<dyn Error>> {
+    let schema = search_schema();
+    let index = Index::create_in_ram(schema.clone());
+
+    open_search_index(index, schema)
+}
+
+/// Builder for rows of the `media_files` table, along with their tags and
+/// the albums and artists they belong to.
+///
+/// # Example
+/// ```ignore
+/// let file = MediaFileFixture::new("track.flac")
+///     .directory("Artist/Album")
+///     .duration(180.0)
+///     .tags(&[("artist", "Artist"), ("album", "Album")])
+///     .insert(&main_db)
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct MediaFileFixture {
+    file_name: String,
+    directory: String,
+    extension: String,
+    file_hash: String,
+    cover_art_id: Option<i32>,
+    sample_rate: i32,
+    duration: f64,
+    tags: Vec<(String, String)>,
+    album_id: Option<i32>,
+    artist_id: Option<i32>,
+}
+
+impl MediaFileFixture {
+    pub fn new(file_name: &str) -> Self {
+        let extension = file_name
+            .rsplit_once('.')
+            .map(|(_, x)| x.to_lowercase())
+            .unwrap_or_default();
+
+        MediaFileFixture {
+            file_name: file_name.to_string(),
+            directory: String::new(),
+            extension,
+            file_hash: format!("fixture:{}", file_name),
+            cover_art_id: None,
+            sample_rate: 44100,
+            duration: 180.0,
+            tags: Vec::new(),
+            album_id: None,
+            artist_id: None,
+        }
+    }
+
+    pub fn directory(mut self, directory: &str) -> Self {
+        self.directory = directory.to_string();
+        self
+    }
+
+    pub fn file_hash(mut self, file_hash: &str) -> Self {
+        self.file_hash = file_hash.to_string();
+        self
+    }
+
+    pub fn cover_art_id(mut self, cover_art_id: Option<i32>) -> Self {
+        self.cover_art_id = cover_art_id;
+        self
+    }
+
+    pub fn sample_rate(mut self, sample_rate: i32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    pub fn duration(mut self, duration: f64) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn tags(mut self, tags: &[(&str, &str)]) -> Self {
+        self.tags.extend(
+            tags.iter()
+                .map(|(key, value)| (key.to_string(), value.to_string())),
+        );
+        self
+    }
+
+    pub fn album_id(mut self, album_id: i32) -> Self {
+        self.album_id = Some(album_id);
+        self
+    }
+
+    pub fn artist_id(mut self, artist_id: i32) -> Self {
+        self.artist_id = Some(artist_id);
+        self
+    }
+
+    /// The row of the file alone, tags and links are only written by `insert`.
+    pub fn into_active_model(self) -> media_files::ActiveModel {
+        media_files::ActiveModel {
+            file_name: ActiveValue::Set(self.file_name),
+            directory: ActiveValue::Set(self.directory),
+            extension: ActiveValue::Set(self.extension),
+            file_hash: ActiveValue::Set(self.file_hash),
+            last_modified: ActiveValue::Set(Utc::now().to_rfc3339()),
+            cover_art_id: ActiveValue::Set(self.cover_art_id),
+            sample_rate: ActiveValue::Set(self.sample_rate),
+            duration: ActiveValue::Set(self.duration),
+            ..Default::default()
+        }
+    }
+
+    pub async fn insert(mut self, main_db: &MainDbConnection) -> Result<media_files::Model, DbErr> {
+        let tags = std::mem::take(&mut self.tags);
+        let album_id = self.album_id;
+        let artist_id = self.artist_id;
+        let file = self.into_active_model().insert(main_db).await?;
+
+        for (key, value) in tags {
+            media_metadata::ActiveModel {
+                file_id: ActiveValue::Set(file.id),
+                meta_key: ActiveValue::Set(key),
+                meta_value: ActiveValue::Set(value),
+                ..Default::default()
+            }
+            .insert(main_db)
+            .await?;
+        }
+
+        if let Some(album_id) = album_id {
+            media_file_albums::ActiveModel {
+                media_file_id: ActiveValue::Set(file.id),
+                album_id: ActiveValue::Set(album_id),
+                ..Default::default()
+            }
+            .insert(main_db)
+            .await?;
+        }
+
+        if let Some(artist_id) = artist_id {
+            media_file_artists::ActiveModel {
+                media_file_id: ActiveValue::Set(file.id),
+                artist_id: ActiveValue::Set(artist_id),
+                ..Default::default()
+            }
+            .insert(main_db)
+            .await?;
+        }
+
+        Ok(file)
+    }
+}
+
+/// Insert `count` media files named `track_0.mp3`, `track_1.mp3`, ...
+pub async fn insert_media_files(
+    main_db: &MainDbConnection,
+    count: usize,
+) -> Result<Vec<media_files::Model>, DbErr> {
+    let mut files = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let file = MediaFileFixture::new(&format!("track_{}.mp3", i))
+            .insert(main_db)
+            .await?;
+        files.push(file);
+    }
+
+    Ok(files)
+}
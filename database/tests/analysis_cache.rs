@@ -0,0 +1,163 @@
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+use database::actions::analysis::{
+    analyse_single_file, analysis_audio_library, empty_progress_callback,
+};
+use database::actions::analysis_cache::{cache_analysis, get_cached_analysis};
+use database::actions::analysis_exchange::AnalysisFeatures;
+use database::actions::throttle::AnalysisPace;
+use database::connection::connect_analysis_cache_db;
+use database::entities::media_analysis;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+fn features(centroid: f64) -> AnalysisFeatures {
+    let mut features = [Some(0.5); 19];
+    features[0] = Some(centroid);
+    features[18] = None;
+    features
+}
+
+#[tokio::test]
+async fn cached_hashes_keep_their_first_results() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = connect_analysis_cache_db(&dir.path().join("cache.db"))
+        .await
+        .unwrap();
+
+    cache_analysis(&cache, &[("a".to_string(), features(1.0))])
+        .await
+        .unwrap();
+    cache_analysis(
+        &cache,
+        &[
+            ("a".to_string(), features(2.0)),
+            ("b".to_string(), features(3.0)),
+        ],
+    )
+    .await
+    .unwrap();
+
+    let cached = get_cached_analysis(&cache, &["a".to_string(), "c".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(cached.len(), 1);
+    assert_eq!(cached["a"], features(1.0));
+}
+
+#[tokio::test]
+async fn cached_files_are_not_analysed_again() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = connect_analysis_cache_db(&dir.path().join("cache.db"))
+        .await
+        .unwrap();
+
+    // The file only exists in the database, decoding it would fail
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let file = MediaFileFixture::new("copy.flac")
+        .file_hash("shared")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    cache_analysis(&cache, &[("shared".to_string(), features(42.0))])
+        .await
+        .unwrap();
+
+    let library = tempfile::tempdir().unwrap();
+    analysis_audio_library(
+        &main_db,
+        library.path(),
+        Some(&cache),
+        || AnalysisPace::new(10),
+        empty_progress_callback,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let analysis = media_analysis::Entity::find()
+        .filter(media_analysis::Column::FileId.eq(file.id))
+        .one(&main_db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(analysis.spectral_centroid, Some(42.0));
+    assert_eq!(analysis.chroma10, Some(0.5));
+    assert_eq!(analysis.chroma11, None);
+}
+
+#[tokio::test]
+async fn single_files_are_analysed_on_demand() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = connect_analysis_cache_db(&dir.path().join("cache.db"))
+        .await
+        .unwrap();
+    let library = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    write_sine_fixture(
+        &library.path().join("tone.wav"),
+        FixtureFormat::Wav,
+        8000,
+        1,
+        16000,
+    )
+    .unwrap();
+    let tone = MediaFileFixture::new("tone.wav")
+        .file_hash("tone")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let other = MediaFileFixture::new("other.wav")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let analysis = analyse_single_file(&main_db, library.path(), Some(&cache), tone.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(analysis.file_id, tone.id);
+    assert!(analysis.spectral_centroid.is_some());
+
+    // Asking again returns the stored results
+    let again = analyse_single_file(&main_db, library.path(), Some(&cache), tone.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(again.id, analysis.id);
+    assert!(get_cached_analysis(&cache, &["tone".to_string()])
+        .await
+        .unwrap()
+        .contains_key("tone"));
+
+    // The library analysis leaves the file alone and only picks up the rest
+    analysis_audio_library(
+        &main_db,
+        library.path(),
+        Some(&cache),
+        || AnalysisPace::new(10),
+        empty_progress_callback,
+        None,
+    )
+    .await
+    .unwrap();
+    let analyses = media_analysis::Entity::find()
+        .filter(media_analysis::Column::FileId.eq(tone.id))
+        .all(&main_db)
+        .await
+        .unwrap();
+    assert_eq!(analyses.len(), 1);
+
+    assert!(
+        analyse_single_file(&main_db, library.path(), Some(&cache), other.id)
+            .await
+            .is_err()
+    );
+    assert!(
+        analyse_single_file(&main_db, library.path(), Some(&cache), other.id + 1)
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
@@ -0,0 +1,137 @@
+use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
+
+use database::actions::analysis_exchange::{
+    export_analysis, export_analysis_vectors, import_analysis,
+};
+use database::connection::MainDbConnection;
+use database::entities::media_analysis;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+async fn insert_analysis(main_db: &MainDbConnection, file_id: i32, centroid: f64) {
+    media_analysis::ActiveModel {
+        file_id: ActiveValue::Set(file_id),
+        spectral_centroid: ActiveValue::Set(Some(centroid)),
+        chroma3: ActiveValue::Set(Some(0.125)),
+        ..Default::default()
+    }
+    .insert(main_db)
+    .await
+    .unwrap();
+}
+
+async fn centroid_of(main_db: &MainDbConnection, file_id: i32) -> Option<Option<f64>> {
+    media_analysis::Entity::find()
+        .filter(media_analysis::Column::FileId.eq(file_id))
+        .one(main_db)
+        .await
+        .unwrap()
+        .map(|x| x.spectral_centroid)
+}
+
+#[tokio::test]
+async fn analysis_follows_file_hashes_across_libraries() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("analysis.tsv");
+
+    let source = connect_main_db_in_memory().await.unwrap();
+    let a = MediaFileFixture::new("a.flac")
+        .insert(&source)
+        .await
+        .unwrap();
+    let b = MediaFileFixture::new("b.flac")
+        .insert(&source)
+        .await
+        .unwrap();
+    insert_analysis(&source, a.id, 1234.5678).await;
+    insert_analysis(&source, b.id, 42.0).await;
+
+    assert_eq!(export_analysis(&source, &path).await.unwrap(), 2);
+
+    // The files live elsewhere on the new machine, only the hashes match
+    let target = connect_main_db_in_memory().await.unwrap();
+    let a = MediaFileFixture::new("a.flac")
+        .directory("Music")
+        .insert(&target)
+        .await
+        .unwrap();
+    let b = MediaFileFixture::new("b.flac")
+        .directory("Music")
+        .insert(&target)
+        .await
+        .unwrap();
+    let c = MediaFileFixture::new("c.flac")
+        .insert(&target)
+        .await
+        .unwrap();
+    insert_analysis(&target, b.id, 7.0).await;
+
+    let report = import_analysis(&target, &path).await.unwrap();
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.skipped, 1);
+
+    assert_eq!(centroid_of(&target, a.id).await, Some(Some(1234.5678)));
+    // Existing results are kept
+    assert_eq!(centroid_of(&target, b.id).await, Some(Some(7.0)));
+    assert_eq!(centroid_of(&target, c.id).await, None);
+
+    let imported = media_analysis::Entity::find()
+        .filter(media_analysis::Column::FileId.eq(a.id))
+        .one(&target)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(imported.chroma3, Some(0.125));
+    assert_eq!(imported.chroma4, None);
+}
+
+#[tokio::test]
+async fn foreign_files_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("analysis.tsv");
+    std::fs::write(&path, "file_hash\tspectral_centroid\n").unwrap();
+
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    assert!(import_analysis(&main_db, &path).await.is_err());
+}
+
+#[tokio::test]
+async fn feature_vectors_are_exported_as_csv() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("vectors.csv");
+
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let a = MediaFileFixture::new("Hello, World.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let b = MediaFileFixture::new("b.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let c = MediaFileFixture::new("c.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    insert_analysis(&main_db, a.id, 100.0).await;
+    insert_analysis(&main_db, b.id, 300.0).await;
+    insert_analysis(&main_db, c.id, 1000.0).await;
+
+    let exported = export_analysis_vectors(&main_db, &path, Some(&[a.id, b.id]))
+        .await
+        .unwrap();
+    assert_eq!(exported, 2);
+
+    let csv = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("row,file_id,directory,file_name,spectral_centroid,"));
+    assert!(lines[1].starts_with(&format!("track,{},", a.id)));
+    assert!(lines[1].contains("\"Hello, World.flac\",100,"));
+    assert!(lines[2].starts_with(&format!("track,{},", b.id)));
+
+    // The mean of the exported tracks only
+    let mean: Vec<&str> = lines[3].split(',').collect();
+    assert_eq!(mean.len(), 23);
+    assert_eq!(&mean[..5], ["mean", "", "", "", "200"]);
+    assert_eq!(mean[14], "0.125");
+}
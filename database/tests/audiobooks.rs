@@ -0,0 +1,98 @@
+use database::actions::audiobooks::{
+    get_audiobook_file_ids, get_audiobooks, get_playback_position, set_directory_content_type,
+    set_playback_position, ContentType,
+};
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+#[tokio::test]
+async fn closest_flagged_directory_wins() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let book = MediaFileFixture::new("01.mp3")
+        .directory("Books/Dune")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let music = MediaFileFixture::new("01.mp3")
+        .directory("Books/Soundtracks")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let other = MediaFileFixture::new("01.mp3")
+        .directory("Music")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+
+    set_directory_content_type(&main_db, "Books", ContentType::Audiobook)
+        .await
+        .unwrap();
+    set_directory_content_type(&main_db, "Books/Soundtracks", ContentType::Music)
+        .await
+        .unwrap();
+
+    let audiobooks = get_audiobook_file_ids(&main_db, &[book, music, other])
+        .await
+        .unwrap();
+    assert!(audiobooks.contains(&book));
+    assert!(!audiobooks.contains(&music));
+    assert!(!audiobooks.contains(&other));
+}
+
+#[tokio::test]
+async fn audiobooks_are_grouped_by_author_series_and_book() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let tags = [
+        ("artist", "Frank Herbert"),
+        ("content_group", "Dune Chronicles"),
+        ("album", "Dune"),
+    ];
+    let second = MediaFileFixture::new("b.mp3")
+        .directory("Books/Dune")
+        .tags(&[tags[0], tags[1], tags[2], ("track_number", "2")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let first = MediaFileFixture::new("a.mp3")
+        .directory("Books/Dune")
+        .tags(&[tags[0], tags[1], tags[2], ("track_number", "1/2")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+
+    set_directory_content_type(&main_db, "Books", ContentType::Audiobook)
+        .await
+        .unwrap();
+
+    let authors = get_audiobooks(&main_db).await.unwrap();
+    assert_eq!(authors.len(), 1);
+    assert_eq!(authors[0].name, "Frank Herbert");
+    assert_eq!(authors[0].series[0].name, "Dune Chronicles");
+    assert_eq!(authors[0].series[0].books[0].title, "Dune");
+    assert_eq!(authors[0].series[0].books[0].file_ids, vec![first, second]);
+}
+
+#[tokio::test]
+async fn playback_position_is_replaced() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let id = MediaFileFixture::new("01.mp3")
+        .directory("Books")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+
+    assert_eq!(get_playback_position(&main_db, id).await.unwrap(), None);
+
+    set_playback_position(&main_db, id, 12.0).await.unwrap();
+    set_playback_position(&main_db, id, 34.0).await.unwrap();
+
+    assert_eq!(
+        get_playback_position(&main_db, id).await.unwrap(),
+        Some(34.0)
+    );
+}
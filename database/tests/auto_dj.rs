@@ -0,0 +1,132 @@
+use sea_orm::{ActiveModelTrait, ActiveValue};
+
+use database::actions::auto_dj::{
+    chain_tracks, get_dj_tracks, tempo_difference, AutoDjOptions, DjTrack, EnergyProgression,
+    MusicalKey, BPM_META_KEY, INITIAL_KEY_META_KEY,
+};
+use database::entities::media_loudness;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+fn key(value: &str) -> MusicalKey {
+    MusicalKey::parse(value).unwrap()
+}
+
+fn track(file_id: i32, bpm: f64, key_name: &str, energy: Option<f64>) -> DjTrack {
+    DjTrack {
+        file_id,
+        bpm,
+        key: key(key_name),
+        energy,
+    }
+}
+
+#[test]
+fn keys_parse_from_names_and_camelot_codes() {
+    assert_eq!(
+        key("Am"),
+        MusicalKey {
+            tonic: 9,
+            minor: true
+        }
+    );
+    assert_eq!(key("8A"), key("Am"));
+    assert_eq!(key("8B"), key("C"));
+    assert_eq!(key("F# minor"), key("11A"));
+    assert_eq!(key("Dbmaj"), key("C#"));
+    assert_eq!(key("3b"), key("Db major"));
+
+    assert_eq!(MusicalKey::parse(""), None);
+    assert_eq!(MusicalKey::parse("13A"), None);
+    assert_eq!(MusicalKey::parse("H minor"), None);
+    assert_eq!(MusicalKey::parse("Am7"), None);
+}
+
+#[test]
+fn neighbours_on_the_wheel_mix() {
+    assert_eq!(key("Am").camelot_number(), 8);
+    assert_eq!(key("Am").distance(&key("Am")), Some(0));
+    assert_eq!(key("Am").distance(&key("C")), Some(1));
+    assert_eq!(key("Am").distance(&key("Em")), Some(1));
+    assert_eq!(key("Am").distance(&key("Dm")), Some(1));
+    assert_eq!(key("12B").distance(&key("1B")), Some(1));
+
+    assert_eq!(key("Am").distance(&key("F#m")), None);
+    assert_eq!(key("Am").distance(&key("G")), None);
+}
+
+#[test]
+fn half_and_double_time_count_as_the_same_tempo() {
+    assert!((tempo_difference(120.0, 126.0) - 0.05).abs() < 1e-9);
+    assert_eq!(tempo_difference(120.0, 60.0), 0.0);
+    assert_eq!(tempo_difference(80.0, 160.0), 0.0);
+}
+
+#[test]
+fn chains_follow_key_tempo_and_energy() {
+    let seed = track(1, 124.0, "8A", Some(-12.0));
+    let candidates = vec![
+        // Clashing key
+        track(2, 124.0, "3A", Some(-11.0)),
+        // Too fast
+        track(3, 140.0, "8A", Some(-11.0)),
+        track(4, 126.0, "9A", Some(-10.0)),
+        track(5, 125.0, "8A", Some(-11.0)),
+        // Quieter than the seed
+        track(6, 124.0, "8A", Some(-14.0)),
+        track(7, 127.0, "9B", Some(-9.0)),
+    ];
+    let options = AutoDjOptions {
+        energy: EnergyProgression::Rising,
+        ..Default::default()
+    };
+
+    let chain = chain_tracks(&seed, &candidates, &options);
+
+    assert_eq!(chain, vec![1, 5, 4, 7]);
+}
+
+#[test]
+fn chains_stop_at_their_length() {
+    let seed = track(1, 120.0, "8A", None);
+    let candidates: Vec<DjTrack> = (2..10).map(|x| track(x, 120.0, "8A", None)).collect();
+    let options = AutoDjOptions {
+        length: 3,
+        ..Default::default()
+    };
+
+    assert_eq!(chain_tracks(&seed, &candidates, &options), vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn dj_tracks_need_a_tempo_and_a_key() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let tagged = MediaFileFixture::new("tagged.flac")
+        .tags(&[(BPM_META_KEY, "128"), (INITIAL_KEY_META_KEY, "5A")])
+        .insert(&main_db)
+        .await
+        .unwrap();
+    MediaFileFixture::new("no_key.flac")
+        .tag(BPM_META_KEY, "100")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    MediaFileFixture::new("bad_bpm.flac")
+        .tags(&[(BPM_META_KEY, "fast"), (INITIAL_KEY_META_KEY, "Am")])
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    media_loudness::ActiveModel {
+        file_id: ActiveValue::Set(tagged.id),
+        integrated_loudness: ActiveValue::Set(Some(-9.5)),
+        peak: ActiveValue::Set(0.9),
+        ..Default::default()
+    }
+    .insert(&main_db)
+    .await
+    .unwrap();
+
+    let tracks = get_dj_tracks(&main_db).await.unwrap();
+
+    assert_eq!(tracks, vec![track(tagged.id, 128.0, "Cm", Some(-9.5))]);
+}
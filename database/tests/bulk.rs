@@ -0,0 +1,216 @@
+use std::fs;
+
+use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait};
+
+use database::actions::bulk::{add_files_to_playlist, delete_files, reset_analysis, DeleteMode};
+use database::actions::playlists::{
+    create_playlist, get_media_file_ids_of_playlist, DuplicatePolicy,
+};
+use database::actions::ratings::{get_ratings, set_ratings};
+use database::actions::users::DEFAULT_USER_ID;
+use database::connection::MainDbConnection;
+use database::entities::{media_analysis, media_files};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+async fn insert_files(main_db: &MainDbConnection, count: usize) -> Vec<i32> {
+    let mut ids = Vec::new();
+    for index in 0..count {
+        let file = MediaFileFixture::new(&format!("{:02}.flac", index))
+            .file_hash(&format!("hash{}", index))
+            .insert(main_db)
+            .await
+            .unwrap();
+        ids.push(file.id);
+    }
+    ids
+}
+
+#[tokio::test]
+async fn files_are_appended_to_playlists_once() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    let ids = insert_files(&main_db, 3).await;
+
+    let playlist = create_playlist(&main_db, &mut search_db, "Mix".into(), "".into())
+        .await
+        .unwrap();
+
+    let report = add_files_to_playlist(
+        &main_db,
+        playlist.id,
+        &[ids[2], ids[0]],
+        DuplicatePolicy::Skip,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.succeeded, vec![ids[2], ids[0]]);
+
+    let report = add_files_to_playlist(
+        &main_db,
+        playlist.id,
+        &[ids[0], ids[1], ids[1]],
+        DuplicatePolicy::Skip,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.succeeded, vec![ids[1]]);
+    assert_eq!(report.skipped, vec![ids[0], ids[1]]);
+
+    let items = get_media_file_ids_of_playlist(&main_db, playlist.id)
+        .await
+        .unwrap();
+    assert_eq!(items.len(), 3);
+
+    assert!(
+        add_files_to_playlist(&main_db, playlist.id + 1, &ids, DuplicatePolicy::Skip)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn duplicates_are_reported_before_anything_is_added() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    let ids = insert_files(&main_db, 3).await;
+
+    let playlist = create_playlist(&main_db, &mut search_db, "Mix".into(), "".into())
+        .await
+        .unwrap();
+    add_files_to_playlist(&main_db, playlist.id, &[ids[0]], DuplicatePolicy::Allow)
+        .await
+        .unwrap();
+
+    let report = add_files_to_playlist(&main_db, playlist.id, &ids, DuplicatePolicy::Ask)
+        .await
+        .unwrap();
+    assert!(report.succeeded.is_empty());
+    assert_eq!(report.skipped, vec![ids[0]]);
+
+    // Once confirmed, the duplicate is added again
+    let report = add_files_to_playlist(&main_db, playlist.id, &ids, DuplicatePolicy::Allow)
+        .await
+        .unwrap();
+    assert_eq!(report.succeeded, ids);
+
+    let items = get_media_file_ids_of_playlist(&main_db, playlist.id)
+        .await
+        .unwrap();
+    assert_eq!(items.len(), 4);
+}
+
+#[tokio::test]
+async fn ratings_are_set_and_cleared_together() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let ids = insert_files(&main_db, 3).await;
+
+    set_ratings(&main_db, DEFAULT_USER_ID, &ids, 4)
+        .await
+        .unwrap();
+    set_ratings(&main_db, DEFAULT_USER_ID, &ids[..1], 9)
+        .await
+        .unwrap();
+    set_ratings(&main_db, DEFAULT_USER_ID, &ids[2..], 0)
+        .await
+        .unwrap();
+
+    let ratings = get_ratings(&main_db, DEFAULT_USER_ID, &ids).await.unwrap();
+    assert_eq!(ratings.len(), 2);
+    assert_eq!(ratings[&ids[0]], 5);
+    assert_eq!(ratings[&ids[1]], 4);
+}
+
+#[tokio::test]
+async fn deleted_files_leave_the_library() {
+    let lib = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    let ids = insert_files(&main_db, 2).await;
+
+    // The second file is already gone from the disk
+    fs::write(lib.path().join("00.flac"), b"audio").unwrap();
+
+    let missing_id = ids[1] + 100;
+    let report = delete_files(
+        &main_db,
+        &mut search_db,
+        lib.path(),
+        &[ids[0], ids[1], missing_id],
+        DeleteMode::Permanent,
+        |_, _| {},
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(report.succeeded, vec![ids[0], ids[1]]);
+    assert_eq!(report.failed, vec![missing_id]);
+    assert!(report.errors.contains_key(&missing_id));
+    assert!(!lib.path().join("00.flac").exists());
+    assert!(media_files::Entity::find()
+        .all(&main_db)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn deleted_files_go_to_the_trash() {
+    let lib = tempfile::tempdir().unwrap();
+    let data = tempfile::tempdir().unwrap();
+    std::env::set_var("XDG_DATA_HOME", data.path());
+
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    let ids = insert_files(&main_db, 1).await;
+    fs::write(lib.path().join("00.flac"), b"audio").unwrap();
+
+    let report = delete_files(
+        &main_db,
+        &mut search_db,
+        lib.path(),
+        &ids,
+        DeleteMode::Trash,
+        |_, _| {},
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(report.succeeded, ids);
+    assert!(!lib.path().join("00.flac").exists());
+    assert!(data.path().join("Trash/files/00.flac").exists());
+    assert_eq!(
+        DeleteMode::from_setting(Some("true")),
+        DeleteMode::Permanent
+    );
+    assert_eq!(DeleteMode::from_setting(None), DeleteMode::Trash);
+}
+
+#[tokio::test]
+async fn analysis_results_are_reset() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let ids = insert_files(&main_db, 2).await;
+
+    for file_id in &ids {
+        media_analysis::ActiveModel {
+            file_id: ActiveValue::Set(*file_id),
+            ..Default::default()
+        }
+        .insert(&main_db)
+        .await
+        .unwrap();
+    }
+
+    let file_hashes = reset_analysis(&main_db, &ids[..1]).await.unwrap();
+    assert_eq!(file_hashes, vec!["hash0".to_string()]);
+
+    let analysed: Vec<i32> = media_analysis::Entity::find()
+        .all(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.file_id)
+        .collect();
+    assert_eq!(analysed, vec![ids[1]]);
+}
@@ -0,0 +1,77 @@
+use sea_orm::EntityTrait;
+
+use database::actions::index::{index_media_files, IGNORE_ARTICLE_KEY};
+use database::actions::settings::set_setting;
+use database::entities::{albums, artists};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+#[tokio::test]
+async fn names_differing_by_case_or_spacing_are_grouped() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let first = MediaFileFixture::new("a.flac")
+        .tags(&[("artist", "The Beatles"), ("album", "Abbey Road")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let second = MediaFileFixture::new("b.flac")
+        .tags(&[("artist", "the  beatles "), ("album", "ABBEY ROAD")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let third = MediaFileFixture::new("c.flac")
+        .tags(&[("artist", "Beatles")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    index_media_files(&main_db, &mut search_db, vec![first, second, third])
+        .await
+        .unwrap();
+
+    // The first spelling is shown
+    let artists = artists::Entity::find().all(&main_db).await.unwrap();
+    let names: Vec<_> = artists.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(names, vec!["The Beatles", "Beatles"]);
+    assert_eq!(artists[0].canonical_name.as_deref(), Some("the beatles"));
+
+    let albums = albums::Entity::find().all(&main_db).await.unwrap();
+    let names: Vec<_> = albums.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(names, vec!["Abbey Road", ""]);
+}
+
+#[tokio::test]
+async fn leading_article_can_be_ignored() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    set_setting(&main_db, IGNORE_ARTICLE_KEY, "true".to_string())
+        .await
+        .unwrap();
+
+    let first = MediaFileFixture::new("a.flac")
+        .tags(&[("artist", "The Beatles")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let second = MediaFileFixture::new("b.flac")
+        .tags(&[("artist", "Beatles")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    index_media_files(&main_db, &mut search_db, vec![first, second])
+        .await
+        .unwrap();
+
+    let artists = artists::Entity::find().all(&main_db).await.unwrap();
+    assert_eq!(artists.len(), 1);
+    assert_eq!(artists[0].name, "The Beatles");
+    assert_eq!(artists[0].group, "B");
+    assert_eq!(artists[0].canonical_name.as_deref(), Some("beatles"));
+}
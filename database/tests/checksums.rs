@@ -0,0 +1,96 @@
+use sea_orm::{ActiveModelTrait, ActiveValue};
+
+use database::actions::checksums::{get_degradation_report, record_embedded_checksum, Degradation};
+use database::actions::verification::VERIFIED_CHECKSUM_MISMATCH;
+use database::entities::media_verifications;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+use metadata::checksum::EmbeddedChecksum;
+
+#[tokio::test]
+async fn changed_checksums_are_reported() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let stable = MediaFileFixture::new("stable.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let rotten = MediaFileFixture::new("rotten.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    for file in [&stable, &rotten] {
+        let changed =
+            record_embedded_checksum(&main_db, file.id, Some(EmbeddedChecksum::FlacMd5([1; 16])))
+                .await
+                .unwrap();
+        assert!(!changed);
+    }
+    assert!(get_degradation_report(&main_db).await.unwrap().is_empty());
+
+    // A tag edit keeps the checksum, formats without one change nothing
+    let changed = record_embedded_checksum(
+        &main_db,
+        stable.id,
+        Some(EmbeddedChecksum::FlacMd5([1; 16])),
+    )
+    .await
+    .unwrap();
+    assert!(!changed);
+    let changed = record_embedded_checksum(&main_db, stable.id, None)
+        .await
+        .unwrap();
+    assert!(!changed);
+
+    let changed = record_embedded_checksum(
+        &main_db,
+        rotten.id,
+        Some(EmbeddedChecksum::FlacMd5([2; 16])),
+    )
+    .await
+    .unwrap();
+    assert!(changed);
+
+    let report = get_degradation_report(&main_db).await.unwrap();
+    assert_eq!(report.len(), 1);
+    assert_eq!(report[0].file_id, rotten.id);
+    assert_eq!(report[0].file_name, "rotten.flac");
+    match &report[0].problems[..] {
+        [Degradation::ChecksumChanged {
+            kind,
+            previous,
+            current,
+            ..
+        }] => {
+            assert_eq!(kind, "flac_md5");
+            assert_eq!(previous, &"01".repeat(16));
+            assert_eq!(current, &"02".repeat(16));
+        }
+        problems => panic!("unexpected problems: {:?}", problems),
+    }
+}
+
+#[tokio::test]
+async fn checksum_mismatches_are_reported() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let file = MediaFileFixture::new("damaged.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    media_verifications::ActiveModel {
+        file_id: ActiveValue::Set(file.id),
+        file_hash: ActiveValue::Set(file.file_hash.clone()),
+        status: ActiveValue::Set(VERIFIED_CHECKSUM_MISMATCH.to_string()),
+        detail: ActiveValue::Set(None),
+        verified_at: ActiveValue::Set("2024-01-01T00:00:00+00:00".to_string()),
+        ..Default::default()
+    }
+    .insert(&main_db)
+    .await
+    .unwrap();
+
+    let report = get_degradation_report(&main_db).await.unwrap();
+    assert_eq!(report.len(), 1);
+    assert_eq!(report[0].file_id, file.id);
+    assert_eq!(report[0].problems, vec![Degradation::ChecksumMismatch]);
+}
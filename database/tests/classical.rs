@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use sea_orm::EntityTrait;
+
+use database::actions::classical::{
+    get_album_works, get_composers, get_works_of_composer, parse_work_tags, WorkTags,
+};
+use database::actions::index::index_media_files;
+use database::entities::albums;
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+fn tags(entries: &[(&str, &str)]) -> HashMap<String, String> {
+    entries
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn work_tags_are_preferred() {
+    let parsed = parse_work_tags(&tags(&[
+        ("composer", "Ludwig van Beethoven"),
+        ("work", "Symphony No. 5 in C minor, Op. 67"),
+        ("movement_name", "Andante con moto"),
+        ("movement_number", "2/4"),
+        ("track_title", "Symphony No. 5: II. Andante con moto"),
+    ]));
+
+    assert_eq!(
+        parsed,
+        Some(WorkTags {
+            composer: "Ludwig van Beethoven".into(),
+            work: "Symphony No. 5 in C minor, Op. 67".into(),
+            movement: "Andante con moto".into(),
+            movement_number: Some(2),
+        })
+    );
+}
+
+#[test]
+fn movements_are_read_from_titles() {
+    let parsed = parse_work_tags(&tags(&[(
+        "track_title",
+        "Beethoven: Symphony No. 5 - III. Allegro",
+    )]))
+    .unwrap();
+    assert_eq!(parsed.composer, "Beethoven");
+    assert_eq!(parsed.work, "Symphony No. 5");
+    assert_eq!(parsed.movement, "III. Allegro");
+    assert_eq!(parsed.movement_number, Some(3));
+
+    let parsed = parse_work_tags(&tags(&[
+        ("composer", "Johann Sebastian Bach"),
+        (
+            "track_title",
+            "Cello Suite No. 1 in G major, BWV 1007: IV. Sarabande",
+        ),
+    ]))
+    .unwrap();
+    assert_eq!(parsed.composer, "Johann Sebastian Bach");
+    assert_eq!(parsed.work, "Cello Suite No. 1 in G major, BWV 1007");
+    assert_eq!(parsed.movement, "IV. Sarabande");
+    assert_eq!(parsed.movement_number, Some(4));
+
+    // A single piece by the tagged composer isn't a movement
+    assert_eq!(
+        parse_work_tags(&tags(&[
+            ("composer", "Ludwig van Beethoven"),
+            ("track_title", "Beethoven: Für Elise"),
+        ])),
+        None
+    );
+    assert_eq!(
+        parse_work_tags(&tags(&[("track_title", "Come Together")])),
+        None
+    );
+    assert_eq!(
+        parse_work_tags(&tags(&[("track_title", "Interlude: Reprise")])),
+        None
+    );
+}
+
+#[tokio::test]
+async fn albums_and_composers_are_grouped_by_work() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let album = [("album", "Symphonies"), ("artist", "Orchestra")];
+    let track = |number: &'static str, title: &'static str| {
+        let mut tags = album.to_vec();
+        tags.extend([
+            ("composer", "Ludwig van Beethoven"),
+            ("track_number", number),
+            ("track_title", title),
+        ]);
+        tags
+    };
+
+    let ids = vec![
+        MediaFileFixture::new("01.flac")
+            .tags(&track("1", "Symphony No. 5: I. Allegro con brio"))
+            .insert(&main_db)
+            .await
+            .unwrap()
+            .id,
+        MediaFileFixture::new("02.flac")
+            .tags(&track("2", "Symphony No. 5: II. Andante con moto"))
+            .insert(&main_db)
+            .await
+            .unwrap()
+            .id,
+        MediaFileFixture::new("03.flac")
+            .tags(&track("3", "Egmont Overture"))
+            .insert(&main_db)
+            .await
+            .unwrap()
+            .id,
+        MediaFileFixture::new("04.flac")
+            .tags(&track("4", "Symphony No. 7: I. Poco sostenuto"))
+            .insert(&main_db)
+            .await
+            .unwrap()
+            .id,
+        MediaFileFixture::new("05.flac")
+            .tags(&[
+                ("album", "Suites"),
+                ("composer", "Johann Sebastian Bach"),
+                ("sort_composer", "Bach, Johann Sebastian"),
+                ("work", "Cello Suite No. 1"),
+                ("track_title", "Prelude"),
+            ])
+            .insert(&main_db)
+            .await
+            .unwrap()
+            .id,
+    ];
+    index_media_files(&main_db, &mut search_db, ids.clone())
+        .await
+        .unwrap();
+
+    let album_id = albums::Entity::find()
+        .all(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|x| x.name == "Symphonies")
+        .unwrap()
+        .id;
+
+    let album_works = get_album_works(&main_db, album_id).await.unwrap();
+    let works: Vec<(&str, Vec<i32>)> = album_works
+        .works
+        .iter()
+        .map(|x| {
+            let movements = x.movements.iter().map(|x| x.file_id).collect();
+            (x.title.as_str(), movements)
+        })
+        .collect();
+    assert_eq!(
+        works,
+        vec![
+            ("Symphony No. 5", vec![ids[0], ids[1]]),
+            ("Symphony No. 7", vec![ids[3]]),
+        ]
+    );
+    assert_eq!(album_works.other_file_ids, vec![ids[2]]);
+    assert_eq!(album_works.works[0].movements[1].number, Some(2));
+
+    let composers: Vec<(String, usize, usize)> = get_composers(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| (x.name, x.work_count, x.track_count))
+        .collect();
+    assert_eq!(
+        composers,
+        vec![
+            ("Johann Sebastian Bach".to_string(), 1, 1),
+            ("Ludwig van Beethoven".to_string(), 2, 3),
+        ]
+    );
+
+    let works = get_works_of_composer(&main_db, "Johann Sebastian Bach")
+        .await
+        .unwrap();
+    assert_eq!(works.len(), 1);
+    assert_eq!(works[0].title, "Cello Suite No. 1");
+    assert_eq!(works[0].movements[0].title, "Prelude");
+}
@@ -0,0 +1,58 @@
+use database::actions::cold_start::{
+    get_recommendation_by_metadata, get_recommendation_with_fallback,
+};
+use database::connection::connect_recommendation_db;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+#[tokio::test]
+async fn unanalysed_files_are_recommended_by_metadata() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let seed = MediaFileFixture::new("seed.flac")
+        .tags(&[("genre", "Jazz"), ("date", "1959")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let same_genre = MediaFileFixture::new("a.flac")
+        .tags(&[("genre", "jazz "), ("date", "1961")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let same_era = MediaFileFixture::new("b.flac")
+        .tags(&[("genre", "Rock"), ("date", "1958-02")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    MediaFileFixture::new("c.flac")
+        .tags(&[("genre", "Rock"), ("date", "1995")])
+        .insert(&main_db)
+        .await
+        .unwrap();
+    // The original date counts, not the one of the reissue
+    let reissue = MediaFileFixture::new("d.flac")
+        .tags(&[("date", "2010"), ("original_date", "1959")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+
+    let recommendations: Vec<i32> = get_recommendation_by_metadata(&main_db, seed, 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    assert_eq!(recommendations, vec![same_genre, reissue, same_era]);
+
+    // Nothing is in the index yet
+    let lib = tempfile::tempdir().unwrap();
+    let recommend_db = connect_recommendation_db(lib.path().to_str().unwrap()).unwrap();
+    assert_eq!(
+        get_recommendation_with_fallback(&main_db, &recommend_db, seed, 3)
+            .await
+            .unwrap(),
+        vec![seed, same_genre, reissue]
+    );
+}
@@ -0,0 +1,108 @@
+use std::cmp::Ordering;
+
+use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait};
+
+use database::actions::collation::NameCollator;
+use database::actions::health::{check_collection_groups, HealthStatus};
+use database::actions::utils::{create_count_by_first_letter, generate_group_name};
+use database::entities::artists;
+use database::test_support::connect_main_db_in_memory;
+
+#[test]
+fn names_are_grouped_in_their_own_script() {
+    let groups: Vec<String> = [
+        "abba",
+        "Édith Piaf",
+        "Кино",
+        "Ωmega",
+        "周杰伦",
+        "あいみょん",
+        "방탄소년단",
+        "까치",
+        "2Pac",
+        "",
+    ]
+    .into_iter()
+    .map(generate_group_name)
+    .collect();
+
+    assert_eq!(groups, ["A", "E", "К", "Ω", "Z", "A", "ㅂ", "ㄱ", "#", "#"]);
+}
+
+#[test]
+fn numbers_are_compared_by_value() {
+    let collator = NameCollator::new("und");
+
+    assert_eq!(collator.compare("Vol. 2", "Vol. 10"), Ordering::Less);
+    assert_eq!(collator.compare("élan", "Ezra"), Ordering::Less);
+}
+
+#[test]
+fn locales_change_the_order() {
+    let mut names = vec!["Zorn".to_string(), "Åkesson".to_string()];
+
+    NameCollator::new("en").sort_by_key(&mut names, |x| x.clone());
+    assert_eq!(names, ["Åkesson", "Zorn"]);
+
+    // Å is the second to last letter in Swedish
+    NameCollator::new("sv").sort_by_key(&mut names, |x| x.clone());
+    assert_eq!(names, ["Zorn", "Åkesson"]);
+}
+
+#[tokio::test]
+async fn group_summaries_put_symbols_last() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    for name in ["Кино", "2Pac", "Abba"] {
+        artists::ActiveModel {
+            name: ActiveValue::Set(name.to_string()),
+            group: ActiveValue::Set(generate_group_name(name)),
+            ..Default::default()
+        }
+        .insert(&main_db)
+        .await
+        .unwrap();
+    }
+
+    let count_artists = create_count_by_first_letter::<artists::Entity>();
+    let groups: Vec<String> = count_artists(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.0)
+        .collect();
+    assert_eq!(groups, ["A", "К", "#"]);
+}
+
+#[tokio::test]
+async fn outdated_groups_are_replaced() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    // Grouped by the transliteration of older versions
+    let artist = artists::ActiveModel {
+        name: ActiveValue::Set("Кино".to_string()),
+        group: ActiveValue::Set("K".to_string()),
+        ..Default::default()
+    }
+    .insert(&main_db)
+    .await
+    .unwrap();
+
+    assert!(matches!(
+        check_collection_groups(&main_db, false).await,
+        HealthStatus::Problem(_)
+    ));
+    assert!(matches!(
+        check_collection_groups(&main_db, true).await,
+        HealthStatus::Repaired(_)
+    ));
+    assert_eq!(
+        check_collection_groups(&main_db, false).await,
+        HealthStatus::Healthy
+    );
+
+    let artist = artists::Entity::find_by_id(artist.id)
+        .one(&main_db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(artist.group, "К");
+}
@@ -0,0 +1,61 @@
+use sea_orm::{ActiveValue, EntityTrait};
+
+use database::actions::cover_art::{collect_cover_art_garbage, CoverArtGarbageReport};
+use database::connection::MainDbConnection;
+use database::entities::media_cover_art;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+async fn insert_cover_art(main_db: &MainDbConnection, file_hash: &str, size: usize) -> i32 {
+    let item = media_cover_art::ActiveModel {
+        id: ActiveValue::NotSet,
+        file_hash: ActiveValue::Set(file_hash.to_string()),
+        binary: ActiveValue::Set(vec![0; size]),
+        color_palette: ActiveValue::Set(None),
+        blurhash: ActiveValue::Set(None),
+    };
+
+    media_cover_art::Entity::insert(item)
+        .exec(main_db)
+        .await
+        .unwrap()
+        .last_insert_id
+}
+
+#[tokio::test]
+async fn only_unreferenced_covers_are_removed() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    let magic = insert_cover_art(&main_db, "", 0).await;
+    let used = insert_cover_art(&main_db, "used", 100).await;
+    let orphan = insert_cover_art(&main_db, "orphan", 250).await;
+
+    MediaFileFixture::new("track.flac")
+        .cover_art_id(Some(used))
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let report = collect_cover_art_garbage(&main_db).await.unwrap();
+
+    assert_eq!(
+        report,
+        CoverArtGarbageReport {
+            removed: 1,
+            reclaimed_bytes: 250,
+        }
+    );
+
+    let remaining: Vec<i32> = media_cover_art::Entity::find()
+        .all(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.id)
+        .collect();
+    assert_eq!(remaining, vec![magic, used]);
+    assert!(!remaining.contains(&orphan));
+
+    // Nothing left to collect
+    let report = collect_cover_art_garbage(&main_db).await.unwrap();
+    assert_eq!(report, CoverArtGarbageReport::default());
+}
@@ -0,0 +1,38 @@
+use database::actions::library::create_library;
+
+#[tokio::test]
+async fn creates_all_library_data() {
+    let lib = tempfile::tempdir().unwrap();
+
+    let data_path = create_library(lib.path()).await.unwrap();
+
+    assert!(data_path.join(".0.db").is_file());
+    assert!(data_path.join(".search").is_dir());
+    assert!(data_path.join(".analysis").is_dir());
+    assert!(!lib.path().join(".rune-staging").exists());
+}
+
+#[tokio::test]
+async fn refuses_to_overwrite_existing_library() {
+    let lib = tempfile::tempdir().unwrap();
+
+    create_library(lib.path()).await.unwrap();
+    assert!(create_library(lib.path()).await.is_err());
+}
+
+// A dangling link where the data directory goes passes the initial check,
+// so the data is created in full and only moving it into place fails
+#[cfg(unix)]
+#[tokio::test]
+async fn leaves_nothing_behind_on_failure() {
+    let lib = tempfile::tempdir().unwrap();
+    let data_path = lib.path().join(".rune");
+    std::os::unix::fs::symlink(lib.path().join("missing"), &data_path).unwrap();
+
+    assert!(create_library(lib.path()).await.is_err());
+    assert!(!lib.path().join(".rune-staging").exists());
+    assert!(std::fs::symlink_metadata(&data_path)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+}
@@ -0,0 +1,61 @@
+use database::actions::device_profiles::{
+    get_device_profile, get_profiled_device_names, save_device_profile, DeviceProfile,
+};
+use database::test_support::connect_main_db_in_memory;
+
+#[tokio::test]
+async fn profiles_are_kept_per_device() {
+    let user_db = connect_main_db_in_memory().await.unwrap();
+    let headphones = DeviceProfile {
+        volume: 0.4,
+        night_mode: false,
+        crossfeed: true,
+    };
+    let speakers = DeviceProfile {
+        volume: 0.8,
+        night_mode: true,
+        crossfeed: false,
+    };
+
+    save_device_profile(&user_db, "Headphones", headphones)
+        .await
+        .unwrap();
+    save_device_profile(&user_db, "Speakers", speakers)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        get_device_profile(&user_db, "Headphones").await.unwrap(),
+        Some(headphones)
+    );
+    assert_eq!(
+        get_device_profile(&user_db, "Speakers").await.unwrap(),
+        Some(speakers)
+    );
+    assert_eq!(get_device_profile(&user_db, "HDMI").await.unwrap(), None);
+
+    let mut names = get_profiled_device_names(&user_db).await.unwrap();
+    names.sort();
+    assert_eq!(names, vec!["Headphones", "Speakers"]);
+}
+
+#[tokio::test]
+async fn saving_again_replaces_the_profile() {
+    let user_db = connect_main_db_in_memory().await.unwrap();
+    let mut profile = DeviceProfile {
+        volume: 0.5,
+        night_mode: false,
+        crossfeed: false,
+    };
+    save_device_profile(&user_db, "DAC", profile).await.unwrap();
+
+    profile.volume = 0.25;
+    profile.crossfeed = true;
+    save_device_profile(&user_db, "DAC", profile).await.unwrap();
+
+    assert_eq!(
+        get_device_profile(&user_db, "DAC").await.unwrap(),
+        Some(profile)
+    );
+    assert_eq!(get_profiled_device_names(&user_db).await.unwrap().len(), 1);
+}
@@ -0,0 +1,222 @@
+use std::fs;
+use std::path::Path;
+
+use database::actions::device_sync::{
+    create_sync_device, get_sync_devices, remove_sync_device, sync_device, SyncSelection,
+};
+use database::actions::playlists::{add_media_file_to_playlist, create_playlist, DuplicatePolicy};
+use database::entities::media_files;
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection, EntityTrait, IntoActiveModel};
+
+#[tokio::test]
+async fn playlists_are_synced_incrementally() {
+    let lib = tempfile::tempdir().unwrap();
+    let device = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    fs::create_dir(lib.path().join("album")).unwrap();
+    let mut file_ids = Vec::new();
+    for name in ["a.flac", "b.wav"] {
+        let format = if name.ends_with("flac") {
+            FixtureFormat::Flac
+        } else {
+            FixtureFormat::Wav
+        };
+        write_sine_fixture(&lib.path().join("album").join(name), format, 8000, 1, 8000).unwrap();
+        let file = MediaFileFixture::new(name)
+            .directory("album")
+            .insert(&main_db)
+            .await
+            .unwrap();
+        file_ids.push(file.id);
+    }
+
+    let playlist = create_playlist(&main_db, &mut search_db, "Road: Trip".into(), "".into())
+        .await
+        .unwrap();
+    for file_id in &file_ids {
+        add_media_file_to_playlist(&main_db, playlist.id, *file_id, DuplicatePolicy::Allow)
+            .await
+            .unwrap();
+    }
+
+    assert!(
+        create_sync_device(&main_db, "Phone", "/nowhere", Some("mp3".into()))
+            .await
+            .is_err()
+    );
+    let phone = create_sync_device(
+        &main_db,
+        "Phone",
+        device.path().to_str().unwrap(),
+        Some("wav".into()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_sync_devices(&main_db).await.unwrap(),
+        vec![phone.clone()]
+    );
+
+    let selection = SyncSelection {
+        playlist_ids: vec![playlist.id],
+        album_ids: vec![],
+    };
+    let report = sync_device(&main_db, &main_db, lib.path(), phone.id, &selection, true)
+        .await
+        .unwrap();
+    assert_eq!((report.copied, report.transcoded), (1, 1));
+    assert!(report.failed.is_empty());
+    assert!(device.path().join("album/a.wav").exists());
+    assert!(device.path().join("album/b.wav").exists());
+
+    let m3u = fs::read_to_string(device.path().join("Road_ Trip.m3u8")).unwrap();
+    let entries: Vec<&str> = m3u.lines().filter(|x| !x.starts_with('#')).collect();
+    assert_eq!(entries, vec!["album/a.wav", "album/b.wav"]);
+
+    // Only what changed in the library is written again
+    let mut changed = media_files::Entity::find_by_id(file_ids[1])
+        .one(&main_db)
+        .await
+        .unwrap()
+        .unwrap()
+        .into_active_model();
+    changed.file_hash = ActiveValue::Set("changed".into());
+    changed.update(&main_db).await.unwrap();
+    let report = sync_device(&main_db, &main_db, lib.path(), phone.id, &selection, true)
+        .await
+        .unwrap();
+    assert_eq!(
+        (report.copied, report.transcoded, report.unchanged),
+        (1, 0, 1)
+    );
+
+    // Files no longer selected are taken off the device
+    let report = sync_device(
+        &main_db,
+        &main_db,
+        lib.path(),
+        phone.id,
+        &SyncSelection::default(),
+        true,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.removed, 2);
+    assert!(!device.path().join("album/a.wav").exists());
+
+    remove_sync_device(&main_db, phone.id).await.unwrap();
+    assert!(get_sync_devices(&main_db).await.unwrap().is_empty());
+}
+
+async fn sync_album_files(
+    main_db: &DatabaseConnection,
+    lib: &Path,
+    names: &[&str],
+) -> (Vec<media_files::Model>, i32) {
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    fs::create_dir(lib.join("album")).unwrap();
+
+    let playlist = create_playlist(main_db, &mut search_db, "Mix".into(), "".into())
+        .await
+        .unwrap();
+    let mut files = Vec::new();
+    for name in names {
+        write_sine_fixture(
+            &lib.join("album").join(name),
+            FixtureFormat::Wav,
+            8000,
+            1,
+            800,
+        )
+        .unwrap();
+        let file = MediaFileFixture::new(name)
+            .directory("album")
+            .insert(main_db)
+            .await
+            .unwrap();
+        add_media_file_to_playlist(main_db, playlist.id, file.id, DuplicatePolicy::Allow)
+            .await
+            .unwrap();
+        files.push(file);
+    }
+
+    (files, playlist.id)
+}
+
+#[tokio::test]
+async fn failed_files_keep_their_copy_on_the_device() {
+    let lib = tempfile::tempdir().unwrap();
+    let device = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let (files, playlist_id) = sync_album_files(&main_db, lib.path(), &["a.wav", "b.wav"]).await;
+
+    let phone = create_sync_device(&main_db, "Phone", device.path().to_str().unwrap(), None)
+        .await
+        .unwrap();
+    let selection = SyncSelection {
+        playlist_ids: vec![playlist_id],
+        album_ids: vec![],
+    };
+    sync_device(&main_db, &main_db, lib.path(), phone.id, &selection, true)
+        .await
+        .unwrap();
+
+    // Changed in the library, but can't be read anymore
+    let mut changed = files[1].clone().into_active_model();
+    changed.file_hash = ActiveValue::Set("changed".into());
+    changed.update(&main_db).await.unwrap();
+    fs::remove_file(lib.path().join("album/b.wav")).unwrap();
+
+    let report = sync_device(&main_db, &main_db, lib.path(), phone.id, &selection, true)
+        .await
+        .unwrap();
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.removed, 0);
+    assert!(device.path().join("album/b.wav").exists());
+
+    let m3u = fs::read_to_string(device.path().join("Mix.m3u8")).unwrap();
+    let entries: Vec<&str> = m3u.lines().filter(|x| !x.starts_with('#')).collect();
+    assert_eq!(entries, vec!["album/a.wav", "album/b.wav"]);
+}
+
+#[tokio::test]
+async fn moved_files_are_removed_from_their_old_place() {
+    let lib = tempfile::tempdir().unwrap();
+    let device = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let (files, playlist_id) = sync_album_files(&main_db, lib.path(), &["a.wav"]).await;
+
+    let phone = create_sync_device(&main_db, "Phone", device.path().to_str().unwrap(), None)
+        .await
+        .unwrap();
+    let selection = SyncSelection {
+        playlist_ids: vec![playlist_id],
+        album_ids: vec![],
+    };
+    sync_device(&main_db, &main_db, lib.path(), phone.id, &selection, false)
+        .await
+        .unwrap();
+    assert!(device.path().join("album/a.wav").exists());
+
+    fs::rename(
+        lib.path().join("album/a.wav"),
+        lib.path().join("album/c.wav"),
+    )
+    .unwrap();
+    let mut renamed = files[0].clone().into_active_model();
+    renamed.file_name = ActiveValue::Set("c.wav".into());
+    renamed.update(&main_db).await.unwrap();
+
+    let report = sync_device(&main_db, &main_db, lib.path(), phone.id, &selection, false)
+        .await
+        .unwrap();
+    assert_eq!(report.copied, 1);
+    assert!(device.path().join("album/c.wav").exists());
+    assert!(!device.path().join("album/a.wav").exists());
+}
@@ -0,0 +1,35 @@
+use sea_orm_migration::MigratorTrait;
+
+use database::actions::diagnostics::{get_library_stats, get_schema_version, LibraryStats};
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+use migration::Migrator;
+
+#[tokio::test]
+async fn stats_count_the_library() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    assert_eq!(
+        get_library_stats(&main_db).await.unwrap(),
+        LibraryStats::default()
+    );
+
+    for (file_name, duration) in [("a.flac", 120.), ("b.flac", 60.5)] {
+        MediaFileFixture::new(file_name)
+            .duration(duration)
+            .insert(&main_db)
+            .await
+            .unwrap();
+    }
+
+    let stats = get_library_stats(&main_db).await.unwrap();
+    assert_eq!(stats.tracks, 2);
+    assert_eq!(stats.total_duration, 180.5);
+    assert_eq!(stats.analysed, 0);
+}
+
+#[tokio::test]
+async fn schema_version_is_the_last_migration() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let last = Migrator::migrations().last().unwrap().name().to_string();
+
+    assert_eq!(get_schema_version(&main_db).await.unwrap(), Some(last));
+}
@@ -0,0 +1,133 @@
+use std::collections::{HashMap, HashSet};
+
+use database::actions::auto_dj::BPM_META_KEY;
+use database::actions::diversity::{
+    apply_mix_policy, diversify, get_mix_policy, set_mix_policy, spread_tempos, MixPolicy,
+    REPLAY_HOURS_KEY,
+};
+use database::actions::play_history::log_play;
+use database::actions::settings::set_setting;
+use database::actions::users::DEFAULT_USER_ID;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+#[test]
+fn diversify_limits_artists_and_replays() {
+    let artists = HashMap::from([
+        (1, vec![10]),
+        (2, vec![10]),
+        (3, vec![10, 20]),
+        (4, vec![20]),
+        (5, vec![10]),
+    ]);
+    let played = HashSet::from([4, 1]);
+    let policy = MixPolicy::default();
+
+    // The seed stays even though it was just played, and counts for its artist
+    assert_eq!(
+        diversify(vec![1, 2, 3, 4, 5, 6], &[1], &artists, &played, &policy),
+        vec![1, 2, 6]
+    );
+
+    let policy = MixPolicy {
+        max_per_artist: 0,
+        replay_hours: 0,
+        min_tempo_variance: 0.,
+    };
+    assert_eq!(
+        diversify(vec![1, 2, 3, 4, 5], &[], &artists, &HashSet::new(), &policy),
+        vec![1, 2, 3, 4, 5]
+    );
+}
+
+#[tokio::test]
+async fn mix_policy_is_stored_and_applied() {
+    let db = connect_main_db_in_memory().await.unwrap();
+    assert_eq!(get_mix_policy(&db).await.unwrap(), MixPolicy::default());
+
+    let mut ids = Vec::new();
+    for name in ["a.flac", "b.flac", "c.flac"] {
+        ids.push(MediaFileFixture::new(name).insert(&db).await.unwrap().id);
+    }
+    log_play(&db, DEFAULT_USER_ID, ids[1], 1.).await.unwrap();
+    assert_eq!(
+        apply_mix_policy(&db, &db, DEFAULT_USER_ID, ids.clone(), &[])
+            .await
+            .unwrap(),
+        vec![ids[0], ids[2]]
+    );
+
+    let policy = MixPolicy {
+        max_per_artist: 3,
+        replay_hours: 0,
+        min_tempo_variance: 0.1,
+    };
+    set_mix_policy(&db, &policy).await.unwrap();
+    assert_eq!(get_mix_policy(&db).await.unwrap(), policy);
+    assert_eq!(
+        apply_mix_policy(&db, &db, DEFAULT_USER_ID, ids.clone(), &[])
+            .await
+            .unwrap(),
+        ids
+    );
+}
+
+#[test]
+fn spread_tempos_moves_similar_tempos_apart() {
+    let tempos = HashMap::from([(1, 120.), (2, 121.), (3, 122.), (4, 90.), (5, 240.)]);
+
+    // Double tempo counts as the same, untagged files go anywhere
+    assert_eq!(
+        spread_tempos(vec![1, 2, 3, 4, 5, 6], &[], &tempos, 0.05),
+        vec![1, 4, 2, 6, 3, 5]
+    );
+
+    // Nothing moves past a kept file
+    assert_eq!(
+        spread_tempos(vec![1, 2, 3, 4], &[3], &tempos, 0.05),
+        vec![1, 2, 3, 4]
+    );
+
+    assert_eq!(
+        spread_tempos(vec![1, 2, 3, 4], &[], &tempos, 0.),
+        vec![1, 2, 3, 4]
+    );
+}
+
+#[tokio::test]
+async fn mix_policy_spreads_tagged_tempos() {
+    let db = connect_main_db_in_memory().await.unwrap();
+
+    let mut ids = Vec::new();
+    for (name, bpm) in [("a.flac", "128"), ("b.flac", "128.5"), ("c.flac", "100")] {
+        let file = MediaFileFixture::new(name)
+            .tag(BPM_META_KEY, bpm)
+            .insert(&db)
+            .await
+            .unwrap();
+        ids.push(file.id);
+    }
+
+    assert_eq!(
+        apply_mix_policy(&db, &db, DEFAULT_USER_ID, ids.clone(), &[])
+            .await
+            .unwrap(),
+        vec![ids[0], ids[2], ids[1]]
+    );
+}
+
+#[tokio::test]
+async fn replay_hours_are_clamped() {
+    let db = connect_main_db_in_memory().await.unwrap();
+    let file = MediaFileFixture::new("a.flac").insert(&db).await.unwrap();
+
+    set_setting(&db, REPLAY_HOURS_KEY, u32::MAX.to_string())
+        .await
+        .unwrap();
+    assert!(get_mix_policy(&db).await.unwrap().replay_hours < u32::MAX as i64);
+    assert_eq!(
+        apply_mix_policy(&db, &db, DEFAULT_USER_ID, vec![file.id], &[])
+            .await
+            .unwrap(),
+        vec![file.id]
+    );
+}
@@ -0,0 +1,167 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sea_orm::EntityTrait;
+
+use database::actions::drop_folder::{
+    import_files, is_valid_drop_folder, render_import_path, DropFolderWatch,
+};
+use database::entities::media_files;
+use database::test_support::{connect_main_db_in_memory, connect_search_db_in_memory};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+fn tags(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn patterns_are_rendered_from_tags() {
+    let source = Path::new("/drop/01 - take.FLAC");
+    let tags = tags(&[
+        ("artist", "Guest"),
+        ("album_artist", "AC/DC"),
+        ("album", "Back in Black"),
+        ("track_title", "Hells Bells"),
+        ("track_number", "1/10"),
+        ("date", "1980-07-25"),
+    ]);
+
+    assert_eq!(
+        render_import_path(
+            "{album_artist}/{year} - {album}/{track} {title}",
+            &tags,
+            source
+        ),
+        PathBuf::from("AC_DC/1980 - Back in Black/01 Hells Bells.flac")
+    );
+
+    // Missing tags fall back to placeholders or the original name
+    assert_eq!(
+        render_import_path("{album_artist}/{album}/{disc}/{title}", &[], source),
+        PathBuf::from("Unknown Artist/Unknown Album/01 - take.flac")
+    );
+
+    // Components can't leave the library
+    assert_eq!(
+        render_import_path("../{title}", &tags, source),
+        PathBuf::from("Hells Bells.flac")
+    );
+}
+
+#[test]
+fn files_settle_once_unchanged() {
+    let drop = tempfile::tempdir().unwrap();
+    let path = drop.path().join("track.wav");
+    write_sine_fixture(&path, FixtureFormat::Wav, 8000, 1, 800).unwrap();
+    fs::write(drop.path().join("notes.txt"), b"").unwrap();
+
+    let mut watch = DropFolderWatch::default();
+    assert!(watch.settled_files(drop.path()).is_empty());
+    assert_eq!(watch.settled_files(drop.path()), vec![path.clone()]);
+
+    // Still being copied
+    write_sine_fixture(&path, FixtureFormat::Wav, 8000, 1, 1600).unwrap();
+    assert!(watch.settled_files(drop.path()).is_empty());
+    assert_eq!(watch.settled_files(drop.path()), vec![path]);
+}
+
+#[test]
+fn drop_folders_stay_out_of_the_library() {
+    let lib = Path::new("/music");
+
+    assert!(is_valid_drop_folder(lib, Path::new("/downloads")));
+    assert!(!is_valid_drop_folder(lib, Path::new("/music/incoming")));
+    assert!(!is_valid_drop_folder(lib, Path::new("/")));
+}
+
+#[tokio::test]
+async fn dropped_files_are_moved_and_indexed() {
+    let lib = tempfile::tempdir().unwrap();
+    let drop = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    fs::create_dir_all(drop.path().join("album")).unwrap();
+    let files: Vec<PathBuf> = ["one.wav", "two.wav"]
+        .iter()
+        .map(|x| drop.path().join("album").join(x))
+        .collect();
+    for file in &files {
+        write_sine_fixture(file, FixtureFormat::Wav, 8000, 1, 800).unwrap();
+    }
+    // Already in the library under the same name
+    fs::create_dir_all(lib.path().join("Unknown Artist")).unwrap();
+    fs::write(lib.path().join("Unknown Artist/one.wav"), b"").unwrap();
+
+    let report = import_files(
+        &main_db,
+        &mut search_db,
+        lib.path(),
+        drop.path(),
+        &files,
+        Some("{album_artist}/{title}"),
+    )
+    .await
+    .unwrap();
+
+    assert!(report.failed.is_empty());
+    let destinations: Vec<PathBuf> = report
+        .imported
+        .iter()
+        .map(|x| x.destination.clone())
+        .collect();
+    assert_eq!(
+        destinations,
+        vec![
+            PathBuf::from("Unknown Artist/one (2).wav"),
+            PathBuf::from("Unknown Artist/two.wav")
+        ]
+    );
+    assert!(files.iter().all(|x| !x.exists()));
+    assert!(destinations.iter().all(|x| lib.path().join(x).is_file()));
+
+    let mut stored: Vec<String> = media_files::Entity::find()
+        .all(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| format!("{}/{}", x.directory, x.file_name))
+        .collect();
+    stored.sort();
+    assert_eq!(
+        stored,
+        vec!["Unknown Artist/one (2).wav", "Unknown Artist/two.wav"]
+    );
+}
+
+#[tokio::test]
+async fn files_keep_their_path_without_a_pattern() {
+    let lib = tempfile::tempdir().unwrap();
+    let drop = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    fs::create_dir_all(drop.path().join("Artist")).unwrap();
+    let file = drop.path().join("Artist/track.wav");
+    write_sine_fixture(&file, FixtureFormat::Wav, 8000, 1, 800).unwrap();
+
+    let report = import_files(
+        &main_db,
+        &mut search_db,
+        lib.path(),
+        drop.path(),
+        &[file],
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        report.imported[0].destination,
+        PathBuf::from("Artist/track.wav")
+    );
+    assert!(lib.path().join("Artist/track.wav").is_file());
+}
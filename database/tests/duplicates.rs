@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use database::actions::duplicates::{
+    get_duplicate_groups, get_hidden_duplicates, is_lossless, merge_duplicate_counts,
+    DuplicateGroup,
+};
+use database::actions::file::{compound_query_media_files, get_media_files, MediaFileFilter};
+use database::actions::play_history::log_play;
+use database::actions::ratings::set_ratings;
+use database::actions::track_detail::get_track_detail;
+use database::actions::users::DEFAULT_USER_ID;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+#[test]
+fn lossless_formats_are_recognised() {
+    assert!(is_lossless("flac"));
+    assert!(is_lossless(".WAV"));
+    assert!(!is_lossless("mp3"));
+}
+
+#[tokio::test]
+async fn copies_are_listed_once_with_merged_stats() {
+    let db = connect_main_db_in_memory().await.unwrap();
+    let lossy = MediaFileFixture::new("a.mp3")
+        .directory("nas")
+        .file_hash("same")
+        .insert(&db)
+        .await
+        .unwrap()
+        .id;
+    let lossless = MediaFileFixture::new("a.flac")
+        .directory("local")
+        .file_hash("same")
+        .insert(&db)
+        .await
+        .unwrap()
+        .id;
+    let other = MediaFileFixture::new("b.flac")
+        .insert(&db)
+        .await
+        .unwrap()
+        .id;
+
+    assert_eq!(
+        get_duplicate_groups(&db).await.unwrap(),
+        vec![DuplicateGroup {
+            file_hash: "same".to_string(),
+            preferred: lossless,
+            others: vec![lossy],
+        }]
+    );
+    assert_eq!(get_hidden_duplicates(&db).await.unwrap(), vec![lossy]);
+
+    let listed: Vec<i32> = get_media_files(&db, 0, 10, false)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.id)
+        .collect();
+    assert_eq!(listed, vec![lossless, other]);
+    let listed: Vec<i32> = compound_query_media_files(&db, MediaFileFilter::default(), 0, 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.id)
+        .collect();
+    assert_eq!(listed, vec![lossless, other]);
+
+    // Plays of either copy count for both
+    log_play(&db, DEFAULT_USER_ID, lossy, 1.).await.unwrap();
+    log_play(&db, DEFAULT_USER_ID, lossless, 1.).await.unwrap();
+    set_ratings(&db, DEFAULT_USER_ID, &[lossy], 4)
+        .await
+        .unwrap();
+    let lib = tempfile::tempdir().unwrap();
+    let detail = get_track_detail(&db, &db, DEFAULT_USER_ID, lib.path(), lossless)
+        .await
+        .unwrap();
+    assert_eq!(detail.play_count, 2);
+    assert_eq!(detail.rating, Some(4));
+
+    let merged =
+        merge_duplicate_counts(&db, HashMap::from([(lossy, 2), (lossless, 1), (other, 1)]))
+            .await
+            .unwrap();
+    assert_eq!(merged, HashMap::from([(lossless, 3), (other, 1)]));
+}
@@ -0,0 +1,165 @@
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+use database::actions::eras::{
+    count_by_era, filter_by_years, get_albums_by_era, get_era_facets, Era, EraCount,
+};
+use database::actions::index::index_media_files;
+use database::connection::MainDbConnection;
+use database::entities::albums;
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+async fn album_id(main_db: &MainDbConnection, name: &str) -> i32 {
+    albums::Entity::find()
+        .filter(albums::Column::Name.eq(name))
+        .one(main_db)
+        .await
+        .unwrap()
+        .unwrap()
+        .id
+}
+
+// Two tracks from 1969, one from 1965 and one without a date
+async fn setup(main_db: &MainDbConnection) -> Vec<i32> {
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    let mut file_ids = Vec::new();
+    for (file_name, album, date) in [
+        ("a.flac", "Abbey Road", Some("1969-09-26")),
+        ("b.flac", "Abbey Road", Some("1969")),
+        ("c.flac", "Rubber Soul", Some("1965-12-03")),
+        ("d.flac", "Demos", None),
+    ] {
+        let mut tags = vec![("album", album)];
+        if let Some(date) = date {
+            tags.push(("date", date));
+        }
+        file_ids.push(
+            MediaFileFixture::new(file_name)
+                .tags(&tags)
+                .insert(main_db)
+                .await
+                .unwrap()
+                .id,
+        );
+    }
+    index_media_files(main_db, &mut search_db, file_ids.clone())
+        .await
+        .unwrap();
+
+    file_ids
+}
+
+#[tokio::test]
+async fn albums_are_counted_by_year_and_decade() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    setup(&main_db).await;
+
+    assert_eq!(
+        count_by_era(&main_db, Era::Year).await.unwrap(),
+        vec![
+            EraCount {
+                start: 0,
+                albums: 1,
+                tracks: 1
+            },
+            EraCount {
+                start: 1965,
+                albums: 1,
+                tracks: 1
+            },
+            EraCount {
+                start: 1969,
+                albums: 1,
+                tracks: 2
+            },
+        ]
+    );
+    assert_eq!(
+        count_by_era(&main_db, Era::Decade).await.unwrap(),
+        vec![
+            EraCount {
+                start: 0,
+                albums: 1,
+                tracks: 1
+            },
+            EraCount {
+                start: 1960,
+                albums: 2,
+                tracks: 3
+            },
+        ]
+    );
+
+    let eras = get_albums_by_era(&main_db, Era::Decade, vec![1960, 1970])
+        .await
+        .unwrap();
+    let names: Vec<(i32, Vec<&str>)> = eras
+        .iter()
+        .map(|(start, albums)| {
+            (
+                *start,
+                albums.iter().map(|(x, _)| x.name.as_str()).collect(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        names,
+        vec![(1960, vec!["Abbey Road", "Rubber Soul"]), (1970, vec![])]
+    );
+}
+
+#[tokio::test]
+async fn search_results_are_narrowed_by_year() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let file_ids = setup(&main_db).await;
+    let abbey_road = album_id(&main_db, "Abbey Road").await;
+    let rubber_soul = album_id(&main_db, "Rubber Soul").await;
+    let demos = album_id(&main_db, "Demos").await;
+    let album_ids = vec![demos, rubber_soul, abbey_road];
+
+    let facets = get_era_facets(&main_db, Era::Decade, &album_ids, &file_ids[1..])
+        .await
+        .unwrap();
+    assert_eq!(
+        facets,
+        vec![
+            EraCount {
+                start: 0,
+                albums: 1,
+                tracks: 1
+            },
+            EraCount {
+                start: 1960,
+                albums: 2,
+                tracks: 2
+            },
+        ]
+    );
+
+    // Without bounds nothing is dropped, not even unknown years
+    assert_eq!(
+        filter_by_years(&main_db, None, None, album_ids.clone(), file_ids.clone())
+            .await
+            .unwrap(),
+        (album_ids.clone(), file_ids.clone())
+    );
+    assert_eq!(
+        filter_by_years(
+            &main_db,
+            Some(1966),
+            None,
+            album_ids.clone(),
+            file_ids.clone()
+        )
+        .await
+        .unwrap(),
+        (vec![abbey_road], file_ids[..2].to_vec())
+    );
+    assert_eq!(
+        filter_by_years(&main_db, None, Some(1965), album_ids, file_ids.clone())
+            .await
+            .unwrap(),
+        (vec![rubber_soul], vec![file_ids[2]])
+    );
+}
@@ -0,0 +1,158 @@
+use chrono::NaiveDate;
+use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait};
+
+use database::actions::exclusions::{
+    get_excluded_file_ids, get_exclusion_flags, set_exclusion_flags, Exclusion, ExclusionFlags,
+    ExclusionTarget,
+};
+use database::actions::home::get_daily_mix_seeds;
+use database::actions::index::index_media_files;
+use database::actions::users::DEFAULT_USER_ID;
+use database::entities::{albums, media_analysis};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+const NEVER_SHUFFLE: ExclusionFlags = ExclusionFlags {
+    never_shuffle: true,
+    exclude_from_recommendations: false,
+};
+const NOT_RECOMMENDED: ExclusionFlags = ExclusionFlags {
+    never_shuffle: false,
+    exclude_from_recommendations: true,
+};
+
+#[tokio::test]
+async fn files_are_excluded_by_directory_and_album() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let skit = MediaFileFixture::new("01.flac")
+        .directory("Hip-Hop/Skits")
+        .tag("album", "Interludes")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let kept = MediaFileFixture::new("01.flac")
+        .directory("Hip-Hop/Skits/Keep")
+        .tag("album", "Interludes")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let effect = MediaFileFixture::new("01.flac")
+        .directory("Effects")
+        .tag("album", "Sound Effects")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let song = MediaFileFixture::new("01.flac")
+        .directory("Hip-Hop")
+        .tag("album", "Songs")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let ids = [skit, kept, effect, song];
+    index_media_files(&main_db, &mut search_db, ids.to_vec())
+        .await
+        .unwrap();
+
+    let effects = albums::Entity::find()
+        .all(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|x| x.name == "Sound Effects")
+        .unwrap();
+
+    let skits = ExclusionTarget::Directory("Hip-Hop/Skits".to_string());
+    set_exclusion_flags(&main_db, &skits, NEVER_SHUFFLE)
+        .await
+        .unwrap();
+    // A deeper directory overrides its parent
+    set_exclusion_flags(
+        &main_db,
+        &ExclusionTarget::Directory("Hip-Hop/Skits/Keep".to_string()),
+        NOT_RECOMMENDED,
+    )
+    .await
+    .unwrap();
+    set_exclusion_flags(
+        &main_db,
+        &ExclusionTarget::Album(effects.id),
+        ExclusionFlags {
+            never_shuffle: true,
+            exclude_from_recommendations: true,
+        },
+    )
+    .await
+    .unwrap();
+
+    let mut shuffle: Vec<i32> = get_excluded_file_ids(&main_db, &ids, Exclusion::Shuffle)
+        .await
+        .unwrap()
+        .into_iter()
+        .collect();
+    shuffle.sort_unstable();
+    assert_eq!(shuffle, vec![skit, effect]);
+
+    let mut recommendations: Vec<i32> =
+        get_excluded_file_ids(&main_db, &ids, Exclusion::Recommendations)
+            .await
+            .unwrap()
+            .into_iter()
+            .collect();
+    recommendations.sort_unstable();
+    assert_eq!(recommendations, vec![kept, effect]);
+
+    // Clearing every flag removes the exclusion
+    set_exclusion_flags(&main_db, &skits, ExclusionFlags::default())
+        .await
+        .unwrap();
+    assert_eq!(get_exclusion_flags(&main_db).await.unwrap().len(), 2);
+    assert!(!get_excluded_file_ids(&main_db, &ids, Exclusion::Shuffle)
+        .await
+        .unwrap()
+        .contains(&skit));
+}
+
+#[tokio::test]
+async fn excluded_files_never_seed_daily_mixes() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    let mut ids = Vec::new();
+    for directory in ["Music", "Effects", "Effects"] {
+        let file = MediaFileFixture::new(&format!("{}.flac", ids.len()))
+            .directory(directory)
+            .insert(&main_db)
+            .await
+            .unwrap();
+        media_analysis::ActiveModel {
+            file_id: ActiveValue::Set(file.id),
+            ..Default::default()
+        }
+        .insert(&main_db)
+        .await
+        .unwrap();
+        ids.push(file.id);
+    }
+
+    set_exclusion_flags(
+        &main_db,
+        &ExclusionTarget::Directory("Effects".to_string()),
+        NOT_RECOMMENDED,
+    )
+    .await
+    .unwrap();
+
+    let day = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+    assert_eq!(
+        get_daily_mix_seeds(&main_db, &main_db, DEFAULT_USER_ID, day, 3, &[])
+            .await
+            .unwrap(),
+        vec![ids[0]]
+    );
+}
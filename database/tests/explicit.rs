@@ -0,0 +1,93 @@
+use database::actions::explicit::{
+    get_clean_mode, get_explicit_file_ids, is_explicit, set_clean_mode, ADVISORY_META_KEY,
+};
+use database::actions::file::{compound_query_media_files, get_media_files, MediaFileFilter};
+use database::actions::users::{create_user, DEFAULT_USER_ID};
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+#[test]
+fn advisory_values_are_understood() {
+    assert!(is_explicit("1"));
+    assert!(is_explicit("4"));
+    assert!(is_explicit(" Explicit "));
+    assert!(!is_explicit("2"));
+    assert!(!is_explicit("0"));
+    assert!(!is_explicit("clean"));
+}
+
+#[tokio::test]
+async fn clean_mode_hides_explicit_tracks() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let explicit = MediaFileFixture::new("explicit.flac")
+        .tag(ADVISORY_META_KEY, "1")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let clean = MediaFileFixture::new("clean.flac")
+        .tag(ADVISORY_META_KEY, "2")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let untagged = MediaFileFixture::new("untagged.flac")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let worded = MediaFileFixture::new("worded.flac")
+        .tag(ADVISORY_META_KEY, "Explicit")
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+
+    let mut found: Vec<i32> = get_explicit_file_ids(&main_db, &[explicit, clean, untagged, worded])
+        .await
+        .unwrap()
+        .into_iter()
+        .collect();
+    found.sort();
+    assert_eq!(found, vec![explicit, worded]);
+
+    let listed: Vec<i32> = get_media_files(&main_db, 0, 10, true)
+        .await
+        .unwrap()
+        .iter()
+        .map(|x| x.id)
+        .collect();
+    assert_eq!(listed, vec![clean, untagged]);
+    let listed: Vec<i32> = compound_query_media_files(
+        &main_db,
+        MediaFileFilter {
+            hide_explicit: true,
+            ..Default::default()
+        },
+        0,
+        10,
+    )
+    .await
+    .unwrap()
+    .iter()
+    .map(|x| x.id)
+    .collect();
+    assert_eq!(listed, vec![clean, untagged]);
+    assert_eq!(
+        get_media_files(&main_db, 0, 10, false).await.unwrap().len(),
+        4
+    );
+}
+
+#[tokio::test]
+async fn clean_mode_is_kept_per_profile() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let kid = create_user(&main_db, "Kid").await.unwrap();
+
+    assert!(!get_clean_mode(&main_db, kid.id).await.unwrap());
+    set_clean_mode(&main_db, kid.id, true).await.unwrap();
+    assert!(get_clean_mode(&main_db, kid.id).await.unwrap());
+    assert!(!get_clean_mode(&main_db, DEFAULT_USER_ID).await.unwrap());
+
+    set_clean_mode(&main_db, kid.id, false).await.unwrap();
+    assert!(!get_clean_mode(&main_db, kid.id).await.unwrap());
+}
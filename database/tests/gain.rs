@@ -0,0 +1,54 @@
+use database::actions::gain::{
+    get_gain_offsets, get_replay_gains_of_files, parse_replay_gain, set_gain_offset,
+};
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+#[test]
+fn replay_gain_tags_are_parsed() {
+    assert_eq!(parse_replay_gain("-6.48 dB"), Some(-6.48));
+    assert_eq!(parse_replay_gain("+2.10 db"), Some(2.1));
+    assert_eq!(parse_replay_gain(" -1.5"), Some(-1.5));
+    assert_eq!(parse_replay_gain("loud"), None);
+    assert_eq!(parse_replay_gain("NaN dB"), None);
+}
+
+#[tokio::test]
+async fn replay_gains_are_read_from_metadata() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    let tagged = MediaFileFixture::new("tagged.flac")
+        .tag("replaygain_track_gain", "-7.25 dB")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let untagged = MediaFileFixture::new("untagged.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let gains = get_replay_gains_of_files(&main_db, &[tagged.id, untagged.id])
+        .await
+        .unwrap();
+
+    assert_eq!(gains.len(), 1);
+    assert_eq!(gains.get(&tagged.id), Some(&-7.25));
+}
+
+#[tokio::test]
+async fn gain_offsets_are_updated_and_cleared() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let file = MediaFileFixture::new("track.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    set_gain_offset(&main_db, file.id, 3.0).await.unwrap();
+    set_gain_offset(&main_db, file.id, -1.5).await.unwrap();
+    assert_eq!(
+        get_gain_offsets(&main_db).await.unwrap().get(&file.id),
+        Some(&-1.5)
+    );
+
+    set_gain_offset(&main_db, file.id, 0.0).await.unwrap();
+    assert!(get_gain_offsets(&main_db).await.unwrap().is_empty());
+}
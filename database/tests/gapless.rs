@@ -0,0 +1,104 @@
+use sea_orm::{ActiveModelTrait, ActiveValue};
+
+use database::actions::gapless::{
+    check_gapless_albums, is_padded_format, set_gapless_info, AlbumGaplessReport, GaplessProblem,
+};
+use database::connection::MainDbConnection;
+use database::entities::albums;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+use metadata::gapless::GaplessInfo;
+
+async fn insert_album(main_db: &MainDbConnection, name: &str) -> i32 {
+    albums::ActiveModel {
+        name: ActiveValue::Set(name.to_string()),
+        group: ActiveValue::Set(name[..1].to_string()),
+        ..Default::default()
+    }
+    .insert(main_db)
+    .await
+    .unwrap()
+    .id
+}
+
+#[test]
+fn lossy_formats_are_padded() {
+    assert!(is_padded_format("mp3"));
+    assert!(is_padded_format("M4A"));
+    assert!(!is_padded_format("flac"));
+    assert!(!is_padded_format("opus"));
+}
+
+#[tokio::test]
+async fn albums_that_cannot_play_gapless_are_reported() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    let fine = insert_album(&main_db, "Fine").await;
+    MediaFileFixture::new("fine-1.flac")
+        .sample_rate(44100)
+        .album_id(fine)
+        .insert(&main_db)
+        .await
+        .unwrap();
+    MediaFileFixture::new("fine-2.flac")
+        .sample_rate(44100)
+        .album_id(fine)
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let trimmed = MediaFileFixture::new("fine-3.mp3")
+        .sample_rate(44100)
+        .album_id(fine)
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    set_gapless_info(
+        &main_db,
+        trimmed,
+        Some(GaplessInfo {
+            leading_frames: 1105,
+            valid_frames: None,
+        }),
+    )
+    .await
+    .unwrap();
+
+    let mixed = insert_album(&main_db, "Mixed").await;
+    MediaFileFixture::new("mixed-1.flac")
+        .sample_rate(96000)
+        .album_id(mixed)
+        .insert(&main_db)
+        .await
+        .unwrap();
+    MediaFileFixture::new("mixed-2.flac")
+        .sample_rate(44100)
+        .album_id(mixed)
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let untrimmed = MediaFileFixture::new("mixed-3.mp3")
+        .sample_rate(44100)
+        .album_id(mixed)
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+
+    let reports = check_gapless_albums(&main_db, None).await.unwrap();
+    assert_eq!(
+        reports,
+        vec![AlbumGaplessReport {
+            album_id: mixed,
+            album_name: "Mixed".to_string(),
+            problems: vec![
+                GaplessProblem::MixedSampleRates(vec![44100, 96000]),
+                GaplessProblem::MissingEncoderDelay(vec![untrimmed]),
+            ],
+        }]
+    );
+
+    assert!(check_gapless_albums(&main_db, Some(&[fine]))
+        .await
+        .unwrap()
+        .is_empty());
+}
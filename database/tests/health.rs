@@ -0,0 +1,90 @@
+use sea_orm::{ConnectionTrait, EntityTrait};
+
+use database::actions::health::{
+    check_cover_arts, check_recommendation_index, check_schema, check_search_index, HealthStatus,
+};
+use database::connection::connect_recommendation_db;
+use database::entities::media_files;
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+#[tokio::test]
+async fn fresh_libraries_are_healthy() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let recommend_db = connect_recommendation_db(dir.path().to_str().unwrap()).unwrap();
+
+    assert_eq!(check_schema(&main_db, false).await, HealthStatus::Healthy);
+    assert_eq!(
+        check_search_index(&main_db, &mut search_db, false).await,
+        HealthStatus::Healthy
+    );
+    assert_eq!(
+        check_recommendation_index(&main_db, &recommend_db, false).await,
+        HealthStatus::Healthy
+    );
+    assert_eq!(
+        check_cover_arts(&main_db, false).await,
+        HealthStatus::Healthy
+    );
+}
+
+#[tokio::test]
+async fn outdated_search_index_is_rebuilt() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    search_db.outdated = true;
+
+    assert!(matches!(
+        check_search_index(&main_db, &mut search_db, false).await,
+        HealthStatus::Problem(_)
+    ));
+    assert!(search_db.outdated);
+
+    assert!(matches!(
+        check_search_index(&main_db, &mut search_db, true).await,
+        HealthStatus::Repaired(_)
+    ));
+    assert!(!search_db.outdated);
+}
+
+#[tokio::test]
+async fn files_pointing_to_missing_covers_are_reset() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    // Like a database edited by a client that doesn't enforce foreign keys
+    main_db
+        .execute_unprepared("PRAGMA foreign_keys = OFF")
+        .await
+        .unwrap();
+    let file = MediaFileFixture::new("lost.flac")
+        .cover_art_id(Some(42))
+        .insert(&main_db)
+        .await
+        .unwrap();
+    main_db
+        .execute_unprepared("PRAGMA foreign_keys = ON")
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        check_cover_arts(&main_db, false).await,
+        HealthStatus::Problem(_)
+    ));
+    assert!(matches!(
+        check_cover_arts(&main_db, true).await,
+        HealthStatus::Repaired(_)
+    ));
+    assert_eq!(
+        check_cover_arts(&main_db, false).await,
+        HealthStatus::Healthy
+    );
+
+    let file = media_files::Entity::find_by_id(file.id)
+        .one(&main_db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(file.cover_art_id, None);
+}
@@ -0,0 +1,207 @@
+use chrono::NaiveDate;
+use sea_orm::{ActiveModelTrait, ActiveValue};
+
+use database::actions::home::{get_daily_mix_seeds, get_home_payload};
+use database::actions::library::create_library;
+use database::actions::pinned::{
+    get_pinned_collections, pin_collection, reorder_pinned_collections, unpin_collection,
+};
+use database::actions::play_history::{get_recently_played, is_played, log_play};
+use database::actions::playlists::create_playlist;
+use database::actions::search::CollectionType;
+use database::actions::users::DEFAULT_USER_ID;
+use database::connection::{connect_recommendation_db, MainDbConnection};
+use database::entities::media_analysis;
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+async fn insert_files(main_db: &MainDbConnection, count: usize) -> Vec<i32> {
+    let mut ids = Vec::new();
+    for index in 0..count {
+        let file = MediaFileFixture::new(&format!("{:02}.flac", index))
+            .insert(main_db)
+            .await
+            .unwrap();
+        ids.push(file.id);
+    }
+    ids
+}
+
+async fn insert_analysis(main_db: &MainDbConnection, file_id: i32) {
+    media_analysis::ActiveModel {
+        file_id: ActiveValue::Set(file_id),
+        ..Default::default()
+    }
+    .insert(main_db)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn pins_keep_their_order() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    pin_collection(&main_db, DEFAULT_USER_ID, CollectionType::Album, 1)
+        .await
+        .unwrap();
+    pin_collection(&main_db, DEFAULT_USER_ID, CollectionType::Playlist, 2)
+        .await
+        .unwrap();
+    pin_collection(&main_db, DEFAULT_USER_ID, CollectionType::Track, 3)
+        .await
+        .unwrap();
+    // Pinning again doesn't move it
+    pin_collection(&main_db, DEFAULT_USER_ID, CollectionType::Album, 1)
+        .await
+        .unwrap();
+
+    reorder_pinned_collections(&main_db, DEFAULT_USER_ID, &[(CollectionType::Track, 3)])
+        .await
+        .unwrap();
+    assert_eq!(
+        get_pinned_collections(&main_db, DEFAULT_USER_ID)
+            .await
+            .unwrap(),
+        vec![
+            (CollectionType::Track, 3),
+            (CollectionType::Album, 1),
+            (CollectionType::Playlist, 2),
+        ]
+    );
+
+    assert!(
+        unpin_collection(&main_db, DEFAULT_USER_ID, CollectionType::Album, 1)
+            .await
+            .unwrap()
+    );
+    assert!(
+        !unpin_collection(&main_db, DEFAULT_USER_ID, CollectionType::Album, 1)
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        get_pinned_collections(&main_db, DEFAULT_USER_ID)
+            .await
+            .unwrap(),
+        vec![(CollectionType::Track, 3), (CollectionType::Playlist, 2)]
+    );
+}
+
+#[tokio::test]
+async fn recent_plays_list_each_file_once() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let ids = insert_files(&main_db, 3).await;
+
+    assert!(is_played(30., 300.));
+    assert!(is_played(10., 20.));
+    assert!(!is_played(29., 300.));
+
+    for id in [ids[0], ids[1], ids[0], ids[2]] {
+        log_play(&main_db, DEFAULT_USER_ID, id, 1.).await.unwrap();
+        // Plays are ordered by their time
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+    }
+
+    assert_eq!(
+        get_recently_played(&main_db, DEFAULT_USER_ID, 10)
+            .await
+            .unwrap(),
+        vec![ids[2], ids[0], ids[1]]
+    );
+    assert_eq!(
+        get_recently_played(&main_db, DEFAULT_USER_ID, 1)
+            .await
+            .unwrap(),
+        vec![ids[2]]
+    );
+}
+
+#[tokio::test]
+async fn daily_mix_seeds_are_stable_within_a_day() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let ids = insert_files(&main_db, 6).await;
+    for id in &ids[..5] {
+        insert_analysis(&main_db, *id).await;
+    }
+    let day = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+
+    // Without history, any analysed file can be a seed
+    let seeds = get_daily_mix_seeds(&main_db, &main_db, DEFAULT_USER_ID, day, 3, &[])
+        .await
+        .unwrap();
+    assert_eq!(seeds.len(), 3);
+    assert!(seeds.iter().all(|x| ids[..5].contains(x)));
+    assert_eq!(
+        get_daily_mix_seeds(&main_db, &main_db, DEFAULT_USER_ID, day, 3, &[])
+            .await
+            .unwrap(),
+        seeds
+    );
+
+    // Played files come first, files without analysis can't seed a mix
+    let today = chrono::Utc::now().date_naive();
+    for id in [ids[1], ids[5]] {
+        log_play(&main_db, DEFAULT_USER_ID, id, 1.).await.unwrap();
+    }
+    assert_eq!(
+        get_daily_mix_seeds(&main_db, &main_db, DEFAULT_USER_ID, today, 3, &[])
+            .await
+            .unwrap(),
+        vec![ids[1]]
+    );
+}
+
+#[tokio::test]
+async fn home_payload_resolves_pins_and_plays() {
+    let lib = tempfile::tempdir().unwrap();
+    create_library(lib.path()).await.unwrap();
+    let recommend_db = connect_recommendation_db(lib.path().to_str().unwrap()).unwrap();
+
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    let ids = insert_files(&main_db, 2).await;
+    let playlist = create_playlist(&main_db, &mut search_db, "Mix".into(), "".into())
+        .await
+        .unwrap();
+
+    pin_collection(
+        &main_db,
+        DEFAULT_USER_ID,
+        CollectionType::Playlist,
+        playlist.id,
+    )
+    .await
+    .unwrap();
+    pin_collection(&main_db, DEFAULT_USER_ID, CollectionType::Track, ids[0])
+        .await
+        .unwrap();
+    // Gone from the library
+    pin_collection(&main_db, DEFAULT_USER_ID, CollectionType::Album, 42)
+        .await
+        .unwrap();
+    log_play(&main_db, DEFAULT_USER_ID, ids[1], 1.)
+        .await
+        .unwrap();
+
+    let today = chrono::Utc::now().date_naive();
+    let payload = get_home_payload(&main_db, &main_db, DEFAULT_USER_ID, &recommend_db, today)
+        .await
+        .unwrap();
+
+    let pinned: Vec<_> = payload
+        .pinned
+        .iter()
+        .map(|x| (x.collection_type.clone(), x.id, x.name.as_str()))
+        .collect();
+    assert_eq!(
+        pinned,
+        vec![
+            (CollectionType::Playlist, playlist.id, "Mix"),
+            (CollectionType::Track, ids[0], "00.flac"),
+        ]
+    );
+    assert_eq!(payload.recently_played, vec![ids[1]]);
+    // Nothing was analysed yet
+    assert!(payload.daily_mixes.is_empty());
+}
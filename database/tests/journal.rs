@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use database::actions::bulk::add_files_to_playlist;
+use database::actions::journal::{Operation, OperationJournal};
+use database::actions::playlists::{create_playlist, get_playlist_items, DuplicatePolicy};
+use database::actions::ratings::{get_ratings, set_ratings};
+use database::actions::users::DEFAULT_USER_ID;
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+#[tokio::test]
+async fn playlist_edits_and_ratings_are_undone_in_reverse_order() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    let mut ids = Vec::new();
+    for index in 0..3 {
+        let file = MediaFileFixture::new(&format!("{:02}.flac", index))
+            .insert(&main_db)
+            .await
+            .unwrap();
+        ids.push(file.id);
+    }
+
+    let playlist = create_playlist(&main_db, &mut search_db, "Mix".into(), "".into())
+        .await
+        .unwrap();
+    add_files_to_playlist(&main_db, playlist.id, &ids[..2], DuplicatePolicy::Skip)
+        .await
+        .unwrap();
+
+    let journal = OperationJournal::new(8);
+
+    let before = get_playlist_items(&main_db, playlist.id).await.unwrap();
+    add_files_to_playlist(&main_db, playlist.id, &[ids[2]], DuplicatePolicy::Skip)
+        .await
+        .unwrap();
+    let after = get_playlist_items(&main_db, playlist.id).await.unwrap();
+    journal.record(Operation::PlaylistItems {
+        playlist_id: playlist.id,
+        before,
+        after,
+    });
+
+    set_ratings(&main_db, DEFAULT_USER_ID, &ids[..1], 3)
+        .await
+        .unwrap();
+    let before = get_ratings(&main_db, DEFAULT_USER_ID, &ids).await.unwrap();
+    set_ratings(&main_db, DEFAULT_USER_ID, &ids, 5)
+        .await
+        .unwrap();
+    journal.record(Operation::rating_change(
+        DEFAULT_USER_ID,
+        ids.clone(),
+        before,
+        5,
+    ));
+
+    // Ratings go back to what they were, unrated files stay unrated
+    let undone = journal
+        .undo_last_operation(&main_db, &main_db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(undone.kind(), "ratings");
+    assert_eq!(
+        get_ratings(&main_db, DEFAULT_USER_ID, &ids).await.unwrap(),
+        HashMap::from([(ids[0], 3)])
+    );
+
+    journal
+        .undo_last_operation(&main_db, &main_db)
+        .await
+        .unwrap();
+    assert_eq!(
+        get_playlist_items(&main_db, playlist.id).await.unwrap(),
+        ids[..2].to_vec()
+    );
+    assert!(!journal.can_undo());
+    assert!(journal
+        .undo_last_operation(&main_db, &main_db)
+        .await
+        .unwrap()
+        .is_none());
+
+    journal
+        .redo_last_operation(&main_db, &main_db)
+        .await
+        .unwrap();
+    assert_eq!(
+        get_playlist_items(&main_db, playlist.id).await.unwrap(),
+        ids
+    );
+
+    // A new operation drops what could be redone
+    journal.record(Operation::Queue {
+        before: vec![],
+        after: ids.clone(),
+    });
+    assert!(!journal.can_redo());
+}
+
+#[tokio::test]
+async fn history_is_bounded() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let journal = OperationJournal::new(2);
+
+    for index in 0..4 {
+        journal.record(Operation::Queue {
+            before: vec![index],
+            after: vec![index + 1],
+        });
+    }
+    // Nothing changed, nothing to undo
+    journal.record(Operation::Queue {
+        before: vec![1],
+        after: vec![1],
+    });
+
+    let mut undone = Vec::new();
+    while let Some(operation) = journal
+        .undo_last_operation(&main_db, &main_db)
+        .await
+        .unwrap()
+    {
+        undone.push(operation);
+    }
+
+    assert_eq!(
+        undone,
+        vec![
+            Operation::Queue {
+                before: vec![4],
+                after: vec![3],
+            },
+            Operation::Queue {
+                before: vec![3],
+                after: vec![2],
+            },
+        ]
+    );
+}
@@ -0,0 +1,12 @@
+use database::connection::is_library_root_reachable;
+
+#[test]
+fn empty_library_roots_are_reachable() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(is_library_root_reachable(dir.path()));
+
+    let file = dir.path().join("track.flac");
+    std::fs::write(&file, b"fLaC").unwrap();
+    assert!(!is_library_root_reachable(&file));
+    assert!(!is_library_root_reachable(&dir.path().join("share")));
+}
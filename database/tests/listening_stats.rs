@@ -0,0 +1,100 @@
+use std::collections::BTreeSet;
+
+use chrono::NaiveDate;
+use sea_orm::{ActiveModelTrait, ActiveValue};
+
+use database::actions::listening_stats::{
+    count_streaks, get_listening_stats, set_daily_goal, DEFAULT_DAILY_GOAL_MINUTES,
+};
+use database::actions::users::{create_user, DEFAULT_USER_ID};
+use database::connection::MainDbConnection;
+use database::entities::{artists, user_logs};
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+fn day(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+}
+
+async fn insert_play(main_db: &MainDbConnection, user_id: i32, file_id: i32, listen_time: &str) {
+    user_logs::ActiveModel {
+        user_id: ActiveValue::Set(user_id),
+        file_id: ActiveValue::Set(file_id),
+        listen_time: ActiveValue::Set(listen_time.to_string()),
+        progress: ActiveValue::Set(0.5),
+        ..Default::default()
+    }
+    .insert(main_db)
+    .await
+    .unwrap();
+}
+
+async fn insert_track(main_db: &MainDbConnection, file_name: &str, artist: &str) -> i32 {
+    let artist = artists::ActiveModel {
+        name: ActiveValue::Set(artist.to_string()),
+        group: ActiveValue::Set(artist[..1].to_string()),
+        ..Default::default()
+    }
+    .insert(main_db)
+    .await
+    .unwrap();
+
+    MediaFileFixture::new(file_name)
+        .duration(240.)
+        .artist_id(artist.id)
+        .insert(main_db)
+        .await
+        .unwrap()
+        .id
+}
+
+#[test]
+fn streaks_last_until_the_day_ends() {
+    let days = BTreeSet::from([day(3, 1), day(3, 2), day(3, 3), day(3, 10), day(3, 11)]);
+
+    assert_eq!(count_streaks(&days, day(3, 11)), (2, 3));
+    // Not played yet today
+    assert_eq!(count_streaks(&days, day(3, 12)), (2, 3));
+    assert_eq!(count_streaks(&days, day(3, 13)), (0, 3));
+    // Later plays don't count
+    assert_eq!(count_streaks(&days, day(3, 2)), (2, 2));
+    assert_eq!(count_streaks(&BTreeSet::new(), day(3, 2)), (0, 0));
+}
+
+#[tokio::test]
+async fn stats_follow_the_play_history_of_a_profile() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let old = insert_track(&main_db, "old.flac", "Old").await;
+    let new = insert_track(&main_db, "new.flac", "New").await;
+    let guest = create_user(&main_db, "Guest").await.unwrap();
+
+    insert_play(&main_db, DEFAULT_USER_ID, old, "2024-02-20T10:00:00+00:00").await;
+    insert_play(&main_db, DEFAULT_USER_ID, old, "2024-03-09T10:00:00+00:00").await;
+    insert_play(&main_db, DEFAULT_USER_ID, new, "2024-03-10T10:00:00+00:00").await;
+    insert_play(&main_db, DEFAULT_USER_ID, new, "2024-03-10T11:00:00+00:00").await;
+    insert_play(&main_db, guest.id, old, "2024-03-10T10:00:00+00:00").await;
+
+    let stats = get_listening_stats(&main_db, &main_db, DEFAULT_USER_ID, day(3, 10))
+        .await
+        .unwrap();
+    assert_eq!(stats.current_streak, 2);
+    assert_eq!(stats.longest_streak, 2);
+    // Half of two four minute tracks
+    assert_eq!(stats.minutes_today, 4.);
+    assert_eq!(stats.daily_goal_minutes, DEFAULT_DAILY_GOAL_MINUTES);
+    assert_eq!(stats.new_artist_ids.len(), 1);
+
+    set_daily_goal(&main_db, DEFAULT_USER_ID, 2).await.unwrap();
+    let stats = get_listening_stats(&main_db, &main_db, DEFAULT_USER_ID, day(3, 10))
+        .await
+        .unwrap();
+    assert_eq!(stats.goal_progress, 1.);
+
+    // Other profiles discover artists on their own
+    let stats = get_listening_stats(&main_db, &main_db, guest.id, day(3, 10))
+        .await
+        .unwrap();
+    assert_eq!(stats.current_streak, 1);
+    assert_eq!(stats.minutes_today, 2.);
+    assert_eq!(stats.new_artist_ids.len(), 1);
+    assert_eq!(stats.daily_goal_minutes, DEFAULT_DAILY_GOAL_MINUTES);
+}
@@ -0,0 +1,100 @@
+use std::sync::{Arc, Mutex};
+
+use database::actions::gain::get_replay_gains_of_files;
+use database::actions::loudness::{get_measured_gains_of_files, scan_loudness};
+use database::actions::throttle::AnalysisPace;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+#[tokio::test]
+async fn loudness_is_measured_once() {
+    let lib = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    let mut file_ids = Vec::new();
+    for name in ["a.wav", "b.wav", "c.wav"] {
+        write_sine_fixture(&lib.path().join(name), FixtureFormat::Wav, 8000, 1, 16000).unwrap();
+        let file = MediaFileFixture::new(name).insert(&main_db).await.unwrap();
+        file_ids.push(file.id);
+    }
+    // Files that can't be read are left for the next pass
+    let missing = MediaFileFixture::new("missing.wav")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let reported = progress.clone();
+    let total = scan_loudness(
+        &main_db,
+        lib.path(),
+        || AnalysisPace::new(2),
+        move |done, total| reported.lock().unwrap().push((done, total)),
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(total, 4);
+    assert_eq!(*progress.lock().unwrap(), vec![(2, 4), (3, 4)]);
+
+    let gains = get_measured_gains_of_files(&main_db, &[file_ids[0], missing.id])
+        .await
+        .unwrap();
+    assert_eq!(gains.len(), 1);
+    // The fixture peaks at 0.6, far louder than the reference
+    assert!(gains[&file_ids[0]] < -5.0);
+
+    // Measured files are skipped
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let reported = progress.clone();
+    scan_loudness(
+        &main_db,
+        lib.path(),
+        || AnalysisPace::new(2),
+        move |done, total| reported.lock().unwrap().push((done, total)),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(*progress.lock().unwrap(), vec![(3, 4)]);
+}
+
+#[tokio::test]
+async fn replay_gain_tags_win_over_measurements() {
+    let lib = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    let mut file_ids = Vec::new();
+    for (name, tags) in [
+        ("tagged.wav", &[("replaygain_track_gain", "-7.25 dB")][..]),
+        ("untagged.wav", &[]),
+    ] {
+        write_sine_fixture(&lib.path().join(name), FixtureFormat::Wav, 8000, 1, 16000).unwrap();
+        let file = MediaFileFixture::new(name)
+            .tags(tags)
+            .insert(&main_db)
+            .await
+            .unwrap();
+        file_ids.push(file.id);
+    }
+
+    scan_loudness(
+        &main_db,
+        lib.path(),
+        || AnalysisPace::new(8),
+        |_, _| {},
+        None,
+    )
+    .await
+    .unwrap();
+
+    let measured = get_measured_gains_of_files(&main_db, &file_ids)
+        .await
+        .unwrap();
+    let gains = get_replay_gains_of_files(&main_db, &file_ids)
+        .await
+        .unwrap();
+    assert_eq!(gains[&file_ids[0]], -7.25);
+    assert_eq!(gains[&file_ids[1]], measured[&file_ids[1]]);
+}
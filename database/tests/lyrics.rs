@@ -0,0 +1,55 @@
+use database::actions::lyrics::{plain_lyrics, search_lyrics};
+use database::actions::search::{add_track_term_with_lyrics, search_for, CollectionType};
+use database::test_support::connect_search_db_in_memory;
+
+#[test]
+fn synced_lyrics_lose_their_timings() {
+    let lyrics = "[ar:Queen]\n[ti:Bohemian Rhapsody]\n\
+        [00:01.00]Is this the real life?\n\
+        [00:05.00][01:05.00]<00:05.00>Is <00:05.50>this <00:06.00>just fantasy?\n";
+
+    assert_eq!(
+        plain_lyrics(lyrics),
+        "Is this the real life?\nIs this just fantasy?"
+    );
+    assert_eq!(plain_lyrics("Plain <b>words</b>"), "Plain <b>words</b>");
+}
+
+#[test]
+fn lyrics_are_searched_apart_from_names() {
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    add_track_term_with_lyrics(
+        &mut search_db,
+        1,
+        "Bohemian Rhapsody",
+        "Queen",
+        "A Night at the Opera",
+        "",
+        "Is this the real life?\nIs this just fantasy?\nCaught in a landslide",
+    );
+    add_track_term_with_lyrics(
+        &mut search_db,
+        2,
+        "Fantasy",
+        "Earth, Wind & Fire",
+        "All 'n All",
+        "",
+        "Every man has a place",
+    );
+    search_db.commit().unwrap();
+
+    let hits = search_lyrics(&search_db, "just fantasy", 10).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].id, 1);
+    assert_eq!(hits[0].snippets.len(), 1);
+    assert_eq!(hits[0].snippets[0].line, "Is this just fantasy?");
+    assert_eq!(hits[0].snippets[0].highlights, vec![8..12, 13..20]);
+
+    // Names don't match lyrics and lyrics don't match names
+    let tracks = search_for(&mut search_db, "landslide", 10)
+        .unwrap()
+        .remove(&CollectionType::Track)
+        .unwrap_or_default();
+    assert!(tracks.is_empty());
+    assert!(search_lyrics(&search_db, "queen", 10).unwrap().is_empty());
+}
@@ -0,0 +1,49 @@
+use sea_orm::{ActiveModelTrait, ConnectionTrait, EntityTrait, TransactionTrait};
+
+use database::connection::{connect_main_db, connect_main_db_readers};
+use database::entities::prelude::MediaFiles;
+use database::test_support::MediaFileFixture;
+
+#[tokio::test]
+async fn readers_are_not_blocked_by_the_writer() {
+    let lib = tempfile::tempdir().unwrap();
+    let lib_path = lib.path().to_str().unwrap();
+
+    let main_db = connect_main_db(lib_path).await.unwrap();
+    let reader_db = connect_main_db_readers(lib_path, 2).await.unwrap();
+
+    MediaFileFixture::new("first.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    // A scan holds the writer in a transaction for a whole batch
+    let txn = main_db.begin().await.unwrap();
+    MediaFileFixture::new("second.flac")
+        .into_active_model()
+        .insert(&txn)
+        .await
+        .unwrap();
+
+    let files = MediaFiles::find().all(&reader_db).await.unwrap();
+    assert_eq!(files.len(), 1);
+
+    txn.commit().await.unwrap();
+    let files = MediaFiles::find().all(&reader_db).await.unwrap();
+    assert_eq!(files.len(), 2);
+
+    assert!(reader_db
+        .execute_unprepared("DELETE FROM media_files")
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn readers_require_existing_library() {
+    let lib = tempfile::tempdir().unwrap();
+
+    assert!(connect_main_db_readers(lib.path().to_str().unwrap(), 2)
+        .await
+        .is_err());
+    assert!(!lib.path().join(".rune").exists());
+}
@@ -0,0 +1,219 @@
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+use database::actions::index::index_media_files;
+use database::actions::merge::{
+    get_merged_names, merge_collections, preview_merge, preview_split, split_collection,
+};
+use database::actions::search::{search_for, CollectionType};
+use database::connection::MainDbConnection;
+use database::entities::{albums, artists, media_file_artists};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+async fn artist_id(main_db: &MainDbConnection, name: &str) -> Option<i32> {
+    artists::Entity::find()
+        .filter(artists::Column::Name.eq(name))
+        .one(main_db)
+        .await
+        .unwrap()
+        .map(|x| x.id)
+}
+
+async fn artist_ids_of(main_db: &MainDbConnection, file_id: i32) -> Vec<i32> {
+    media_file_artists::Entity::find()
+        .filter(media_file_artists::Column::MediaFileId.eq(file_id))
+        .all(main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.artist_id)
+        .collect()
+}
+
+#[tokio::test]
+async fn merged_artists_stay_merged_until_split() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let help = MediaFileFixture::new("help.flac")
+        .tags(&[("artist", "The Beatles"), ("album", "Help!")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let yesterday = MediaFileFixture::new("yesterday.flac")
+        .tags(&[("artist", "Beatles"), ("album", "Help!")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    index_media_files(&main_db, &mut search_db, vec![help, yesterday])
+        .await
+        .unwrap();
+
+    let target = artist_id(&main_db, "The Beatles").await.unwrap();
+    let source = artist_id(&main_db, "Beatles").await.unwrap();
+
+    assert!(
+        preview_merge(&main_db, CollectionType::Artist, target, target)
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        preview_merge(&main_db, CollectionType::Artist, source, target)
+            .await
+            .unwrap(),
+        vec![yesterday]
+    );
+
+    let moved = merge_collections(
+        &main_db,
+        &mut search_db,
+        CollectionType::Artist,
+        source,
+        target,
+    )
+    .await
+    .unwrap();
+    assert_eq!(moved, vec![yesterday]);
+    assert_eq!(artist_id(&main_db, "Beatles").await, None);
+    assert_eq!(artist_ids_of(&main_db, yesterday).await, vec![target]);
+    assert_eq!(
+        search_for(&mut search_db, "beatles", 10)
+            .unwrap()
+            .remove(&CollectionType::Artist)
+            .unwrap_or_default(),
+        vec![target as i64]
+    );
+
+    // Scanning the track again keeps it with the target
+    index_media_files(&main_db, &mut search_db, vec![yesterday])
+        .await
+        .unwrap();
+    assert_eq!(artist_ids_of(&main_db, yesterday).await, vec![target]);
+    assert_eq!(
+        get_merged_names(&main_db, CollectionType::Artist, target)
+            .await
+            .unwrap(),
+        vec!["Beatles".to_string()]
+    );
+
+    assert!(
+        preview_split(&main_db, CollectionType::Artist, target, "Wings")
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        preview_split(&main_db, CollectionType::Artist, target, "Beatles")
+            .await
+            .unwrap(),
+        vec![yesterday]
+    );
+
+    let (split, moved) = split_collection(
+        &main_db,
+        &mut search_db,
+        CollectionType::Artist,
+        target,
+        "Beatles",
+    )
+    .await
+    .unwrap();
+    assert_eq!(moved, vec![yesterday]);
+    assert_eq!(split, artist_id(&main_db, "Beatles").await);
+    assert_eq!(
+        artist_ids_of(&main_db, yesterday).await,
+        vec![split.unwrap()]
+    );
+    assert_eq!(artist_ids_of(&main_db, help).await, vec![target]);
+    assert!(get_merged_names(&main_db, CollectionType::Artist, target)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn merges_follow_the_merged_album() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let mut file_ids = Vec::new();
+    for (file_name, album) in [
+        ("a.flac", "Abbey Road"),
+        ("b.flac", "Abbey Road (Remaster)"),
+        ("c.flac", "Abbey Road [2019]"),
+    ] {
+        file_ids.push(
+            MediaFileFixture::new(file_name)
+                .tags(&[("album", album)])
+                .insert(&main_db)
+                .await
+                .unwrap()
+                .id,
+        );
+    }
+    index_media_files(&main_db, &mut search_db, file_ids.clone())
+        .await
+        .unwrap();
+
+    let album_id = |name: &'static str| {
+        let main_db = &main_db;
+        async move {
+            albums::Entity::find()
+                .filter(albums::Column::Name.eq(name))
+                .one(main_db)
+                .await
+                .unwrap()
+                .map(|x| x.id)
+        }
+    };
+
+    let original = album_id("Abbey Road").await.unwrap();
+    let remaster = album_id("Abbey Road (Remaster)").await.unwrap();
+    let anniversary = album_id("Abbey Road [2019]").await.unwrap();
+
+    // Merge in two steps, the first name follows the second merge
+    merge_collections(
+        &main_db,
+        &mut search_db,
+        CollectionType::Album,
+        anniversary,
+        remaster,
+    )
+    .await
+    .unwrap();
+    merge_collections(
+        &main_db,
+        &mut search_db,
+        CollectionType::Album,
+        remaster,
+        original,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(album_id("Abbey Road (Remaster)").await, None);
+    assert_eq!(album_id("Abbey Road [2019]").await, None);
+    assert_eq!(
+        get_merged_names(&main_db, CollectionType::Album, original)
+            .await
+            .unwrap(),
+        vec![
+            "Abbey Road [2019]".to_string(),
+            "Abbey Road (Remaster)".to_string()
+        ]
+    );
+
+    index_media_files(&main_db, &mut search_db, file_ids)
+        .await
+        .unwrap();
+    assert_eq!(albums::Entity::find().all(&main_db).await.unwrap().len(), 1);
+
+    // Playlists can't be merged
+    assert!(
+        preview_merge(&main_db, CollectionType::Playlist, original, remaster)
+            .await
+            .is_err()
+    );
+}
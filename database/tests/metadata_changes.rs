@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+use database::actions::index::index_media_files;
+use database::actions::metadata::{is_metadata_changed, FileMetadata};
+use database::actions::search::{search_for, CollectionType};
+use database::entities::{albums, media_metadata};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+fn file_metadata(tags: &[(&str, &str)]) -> FileMetadata {
+    FileMetadata {
+        path: PathBuf::from("track.flac"),
+        metadata: tags
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+        gapless: None,
+        embedded_checksum: None,
+    }
+}
+
+#[tokio::test]
+async fn tag_changes_are_detected() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let id = MediaFileFixture::new("track.flac")
+        .tags(&[("track_title", "Airbag"), ("artist", "Radiohead")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+
+    // The order of the tags doesn't matter
+    let same = file_metadata(&[("artist", "Radiohead"), ("track_title", "Airbag")]);
+    assert!(!is_metadata_changed(&main_db, id, &same).await.unwrap());
+
+    let edited = file_metadata(&[("artist", "Radiohead"), ("track_title", "Airbag (Live)")]);
+    assert!(is_metadata_changed(&main_db, id, &edited).await.unwrap());
+
+    let added = file_metadata(&[
+        ("artist", "Radiohead"),
+        ("track_title", "Airbag"),
+        ("genre", "Rock"),
+    ]);
+    assert!(is_metadata_changed(&main_db, id, &added).await.unwrap());
+}
+
+#[tokio::test]
+async fn reindexing_moves_tracks_to_the_new_album() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let id = MediaFileFixture::new("track.flac")
+        .tags(&[("track_title", "Airbag"), ("album", "OK Computr")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    index_media_files(&main_db, &mut search_db, vec![id])
+        .await
+        .unwrap();
+
+    media_metadata::Entity::update_many()
+        .col_expr(
+            media_metadata::Column::MetaValue,
+            sea_orm::sea_query::Expr::value("OK Computer"),
+        )
+        .filter(media_metadata::Column::FileId.eq(id))
+        .filter(media_metadata::Column::MetaKey.eq("album"))
+        .exec(&main_db)
+        .await
+        .unwrap();
+    index_media_files(&main_db, &mut search_db, vec![id])
+        .await
+        .unwrap();
+
+    let names: Vec<String> = albums::Entity::find()
+        .all(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.name)
+        .collect();
+    assert_eq!(names, vec!["OK Computer"]);
+
+    let results = search_for(&mut search_db, "computr", 10).unwrap();
+    assert!(!results.contains_key(&CollectionType::Album));
+    assert!(!results.contains_key(&CollectionType::Track));
+}
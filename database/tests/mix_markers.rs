@@ -0,0 +1,64 @@
+use database::actions::audiobooks::{set_directory_content_type, ContentType};
+use database::actions::mix_markers::{get_mix_markers, set_onset_markers, MarkerSource};
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+#[tokio::test]
+async fn long_mixes_are_navigated_by_their_sections() {
+    let lib = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    for dir in ["Mixes", "Albums"] {
+        std::fs::create_dir(lib.path().join(dir)).unwrap();
+        let path = lib.path().join(dir).join("a.wav");
+        write_sine_fixture(&path, FixtureFormat::Wav, 8000, 1, 8000).unwrap();
+    }
+    let flagged = MediaFileFixture::new("a.wav")
+        .directory("Mixes")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let long = MediaFileFixture::new("a.wav")
+        .directory("Albums")
+        .duration(3600.0)
+        .insert(&main_db)
+        .await
+        .unwrap();
+    set_directory_content_type(&main_db, "Mixes", ContentType::LongMix)
+        .await
+        .unwrap();
+
+    set_onset_markers(&main_db, flagged.id, &[300.0, 120.0])
+        .await
+        .unwrap();
+    let markers = get_mix_markers(&main_db, lib.path(), flagged.id)
+        .await
+        .unwrap()
+        .unwrap();
+    let positions: Vec<f64> = markers.iter().map(|x| x.position).collect();
+    assert_eq!(positions, vec![120.0, 300.0]);
+    assert!(markers.iter().all(|x| x.source == MarkerSource::Onset));
+
+    // Long files are mixes without a flag, new markers replace the old ones
+    set_onset_markers(&main_db, long.id, &[60.0]).await.unwrap();
+    set_onset_markers(&main_db, long.id, &[]).await.unwrap();
+    assert_eq!(
+        get_mix_markers(&main_db, lib.path(), long.id)
+            .await
+            .unwrap(),
+        Some(Vec::new())
+    );
+
+    // Regular tracks have no markers
+    let track = MediaFileFixture::new("b.wav")
+        .directory("Albums")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    assert_eq!(
+        get_mix_markers(&main_db, lib.path(), track.id)
+            .await
+            .unwrap(),
+        None
+    );
+}
@@ -0,0 +1,107 @@
+use sea_orm::{ActiveModelTrait, ActiveValue};
+
+use database::actions::music_map::{get_music_map, k_means, principal_components, standardize};
+use database::entities::media_analysis;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+#[test]
+fn standardized_dimensions_weigh_the_same() {
+    let vectors = vec![
+        vec![Some(1000.0), Some(0.1), None],
+        vec![Some(3000.0), Some(0.3), None],
+        vec![None, Some(0.2), None],
+    ];
+
+    let standardized = standardize(&vectors);
+
+    assert_eq!(standardized[0][0], -1.0);
+    assert_eq!(standardized[1][0], 1.0);
+    assert!((standardized[0][1] + 1.5f64.sqrt()).abs() < 1e-9);
+    assert_eq!(standardized[0][2], 0.0);
+    // Missing values take the mean
+    assert_eq!(standardized[2][0], 0.0);
+}
+
+#[test]
+fn the_first_component_follows_the_spread() {
+    // A wobble on the third dimension, even and odd values on both sides so
+    // it doesn't follow the first two
+    let vectors: Vec<Vec<f64>> = (-5..=5)
+        .map(|x| {
+            let wobble = if x % 2 == 0 { 0.1 } else { -0.1 };
+            vec![x as f64, x as f64, wobble]
+        })
+        .collect();
+
+    let components = principal_components(&vectors, 2);
+
+    assert_eq!(components.len(), 2);
+    let half = 0.5f64.sqrt();
+    assert!((components[0][0] - half).abs() < 1e-6, "{:?}", components);
+    assert!((components[0][1] - half).abs() < 1e-6, "{:?}", components);
+    let overlap: f64 = components[0]
+        .iter()
+        .zip(&components[1])
+        .map(|(a, b)| a * b)
+        .sum();
+    assert!(overlap.abs() < 1e-6);
+}
+
+#[test]
+fn separate_groups_get_separate_clusters() {
+    let mut vectors = Vec::new();
+    for i in 0..10 {
+        let jitter = i as f64 * 0.01;
+        vectors.push(vec![jitter, 0.0]);
+        vectors.push(vec![10.0 + jitter, 10.0]);
+        vectors.push(vec![-10.0, 10.0 + jitter]);
+    }
+
+    let clusters = k_means(&vectors, 3);
+
+    for group in 0..3 {
+        let labels: Vec<usize> = clusters.iter().skip(group).step_by(3).copied().collect();
+        assert!(labels.iter().all(|x| *x == labels[0]), "{:?}", clusters);
+    }
+    assert_ne!(clusters[0], clusters[1]);
+    assert_ne!(clusters[1], clusters[2]);
+    assert_ne!(clusters[0], clusters[2]);
+    assert_eq!(k_means(&vectors, 3), clusters);
+}
+
+#[tokio::test]
+async fn analysed_tracks_are_placed_on_the_map() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    let mut ids = Vec::new();
+    for (i, centroid) in [500.0, 520.0, 4000.0, 4100.0].into_iter().enumerate() {
+        let file = MediaFileFixture::new(&format!("{}.flac", i))
+            .insert(&main_db)
+            .await
+            .unwrap();
+        media_analysis::ActiveModel {
+            file_id: ActiveValue::Set(file.id),
+            spectral_centroid: ActiveValue::Set(Some(centroid)),
+            spectral_flatness: ActiveValue::Set(Some(centroid / 10000.0)),
+            ..Default::default()
+        }
+        .insert(&main_db)
+        .await
+        .unwrap();
+        ids.push(file.id);
+    }
+    // Files without analysis stay off the map
+    MediaFileFixture::new("unanalysed.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let map = get_music_map(&main_db, 2).await.unwrap();
+
+    assert_eq!(map.iter().map(|x| x.file_id).collect::<Vec<_>>(), ids);
+    assert!(map.iter().all(|p| p.x.abs() <= 1.0 && p.y.abs() <= 1.0));
+    assert_eq!(map[0].cluster, map[1].cluster);
+    assert_eq!(map[2].cluster, map[3].cluster);
+    assert_ne!(map[0].cluster, map[2].cluster);
+    assert!(map[0].x < 0.0 && map[3].x > 0.0);
+}
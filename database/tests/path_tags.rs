@@ -0,0 +1,182 @@
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+use database::actions::path_tags::{apply_path_tags, parse_path_patterns, preview_path_tags};
+use database::entities::{albums, media_metadata};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+use metadata::reader::get_metadata;
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+fn tags(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn untagged_rips_are_previewed() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let first = MediaFileFixture::new("01 In the Flesh.flac")
+        .directory("Rips/Pink Floyd/The Wall/CD1")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let second = MediaFileFixture::new("01 Hey You.flac")
+        .directory("Rips/Pink Floyd/The Wall/CD2")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    // Already tagged the way the path says
+    MediaFileFixture::new("02 Is There Anybody Out There.flac")
+        .directory("Rips/Pink Floyd/The Wall/CD2")
+        .tags(&[
+            ("artist", "Pink Floyd"),
+            ("album", "The Wall"),
+            ("track_title", "Is There Anybody Out There"),
+            ("track_number", "2"),
+            ("disc_number", "2"),
+        ])
+        .insert(&main_db)
+        .await
+        .unwrap();
+    MediaFileFixture::new("loose.flac")
+        .directory("Rips")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    MediaFileFixture::new("01 Other.flac")
+        .directory("Elsewhere/Artist/Album")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let proposals = preview_path_tags(&main_db, "Rips/", &parse_path_patterns(&[]).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        proposals.iter().map(|x| x.file_id).collect::<Vec<_>>(),
+        vec![first.id, second.id]
+    );
+    assert_eq!(
+        proposals[1].pattern,
+        "{artist}/{album}/{disc}/{track} {title}"
+    );
+    assert!(proposals[1].current.is_empty());
+    assert_eq!(
+        proposals[1].proposed,
+        tags(&[
+            ("artist", "Pink Floyd"),
+            ("album", "The Wall"),
+            ("track_title", "Hey You"),
+            ("track_number", "1"),
+            ("disc_number", "2"),
+        ])
+    );
+}
+
+#[tokio::test]
+async fn applied_tags_are_indexed() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    let lib = tempfile::tempdir().unwrap();
+    let file = MediaFileFixture::new("01 Hey You.flac")
+        .directory("Pink Floyd/The Wall")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let proposed = tags(&[
+        ("artist", "Pink Floyd"),
+        ("album", "The Wall"),
+        ("track_title", "Hey You"),
+    ]);
+    let report = apply_path_tags(
+        &main_db,
+        &mut search_db,
+        lib.path(),
+        &[(file.id, proposed.clone()), (file.id + 100, proposed)],
+        false,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(report.succeeded, vec![file.id]);
+    assert_eq!(report.failed, vec![file.id + 100]);
+
+    let stored: Vec<(String, String)> = media_metadata::Entity::find()
+        .filter(media_metadata::Column::FileId.eq(file.id))
+        .all(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| (x.meta_key, x.meta_value))
+        .collect();
+    assert_eq!(stored.len(), 3);
+    assert!(stored.contains(&("track_title".to_string(), "Hey You".to_string())));
+
+    let albums: Vec<String> = albums::Entity::find()
+        .all(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.name)
+        .collect();
+    assert_eq!(albums, vec!["The Wall"]);
+}
+
+#[tokio::test]
+async fn tags_are_written_back() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    let lib = tempfile::tempdir().unwrap();
+
+    let directory = lib.path().join("Pink Floyd/The Wall");
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("01 Hey You.flac");
+    write_sine_fixture(&path, FixtureFormat::Flac, 44100, 2, 4410).unwrap();
+    let file = MediaFileFixture::new("01 Hey You.flac")
+        .directory("Pink Floyd/The Wall")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let missing = MediaFileFixture::new("02 Gone.flac")
+        .directory("Pink Floyd/The Wall")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let proposed = tags(&[("album", "The Wall"), ("track_number", "1")]);
+    let report = apply_path_tags(
+        &main_db,
+        &mut search_db,
+        lib.path(),
+        &[(file.id, proposed.clone()), (missing.id, proposed.clone())],
+        true,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(report.succeeded, vec![file.id]);
+    // Files that can't be written are left alone in the database too
+    assert_eq!(report.failed, vec![missing.id]);
+    assert!(report.errors.contains_key(&missing.id));
+    assert!(media_metadata::Entity::find()
+        .filter(media_metadata::Column::FileId.eq(missing.id))
+        .all(&main_db)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let written = get_metadata(path.to_str().unwrap(), None).unwrap();
+    for tag in &proposed {
+        assert!(
+            written.contains(tag),
+            "{:?} missing from {:?}",
+            tag,
+            written
+        );
+    }
+}
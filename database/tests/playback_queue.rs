@@ -0,0 +1,63 @@
+use database::actions::playback_queue::{get_playback_queue, save_playback_queue};
+use database::actions::users::DEFAULT_USER_ID;
+use database::connection::connect_main_db;
+use database::test_support::{connect_main_db_in_memory, insert_media_files};
+
+#[tokio::test]
+async fn saved_queue_round_trips() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let files = insert_media_files(&main_db, 3).await.unwrap();
+    let file_ids: Vec<i32> = files.iter().rev().map(|x| x.id).collect();
+
+    save_playback_queue(&main_db, DEFAULT_USER_ID, &file_ids, Some(1), 42.5)
+        .await
+        .unwrap();
+
+    let queue = get_playback_queue(&main_db, DEFAULT_USER_ID).await.unwrap();
+    assert_eq!(queue.file_ids, file_ids);
+    assert_eq!(queue.index, Some(1));
+    assert_eq!(queue.position, 42.5);
+}
+
+#[tokio::test]
+async fn saving_replaces_previous_queue() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let files = insert_media_files(&main_db, 2).await.unwrap();
+
+    save_playback_queue(
+        &main_db,
+        DEFAULT_USER_ID,
+        &[files[0].id, files[1].id],
+        Some(1),
+        10.0,
+    )
+    .await
+    .unwrap();
+    save_playback_queue(&main_db, DEFAULT_USER_ID, &[], None, 0.0)
+        .await
+        .unwrap();
+
+    let queue = get_playback_queue(&main_db, DEFAULT_USER_ID).await.unwrap();
+    assert!(queue.file_ids.is_empty());
+    assert_eq!(queue.index, None);
+}
+
+#[tokio::test]
+async fn saved_queue_survives_reopening_the_library() {
+    let lib = tempfile::tempdir().unwrap();
+    let lib_path = lib.path().to_str().unwrap();
+
+    let main_db = connect_main_db(lib_path).await.unwrap();
+    let files = insert_media_files(&main_db, 3).await.unwrap();
+    let file_ids: Vec<i32> = files.iter().map(|x| x.id).collect();
+    save_playback_queue(&main_db, DEFAULT_USER_ID, &file_ids, Some(2), 7.0)
+        .await
+        .unwrap();
+    main_db.close().await.unwrap();
+
+    let main_db = connect_main_db(lib_path).await.unwrap();
+    let queue = get_playback_queue(&main_db, DEFAULT_USER_ID).await.unwrap();
+    assert_eq!(queue.file_ids, file_ids);
+    assert_eq!(queue.index, Some(2));
+    assert_eq!(queue.position, 7.0);
+}
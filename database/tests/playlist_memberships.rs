@@ -0,0 +1,56 @@
+use database::actions::playlists::{
+    add_item_to_playlist, create_playlist, get_playlists_of_media_files, DuplicatePolicy,
+};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+#[tokio::test]
+async fn files_list_the_playlists_they_appear_in() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let mut ids = Vec::new();
+    for name in ["a.flac", "b.flac", "c.flac"] {
+        ids.push(
+            MediaFileFixture::new(name)
+                .insert(&main_db)
+                .await
+                .unwrap()
+                .id,
+        );
+    }
+
+    let road_trip = create_playlist(&main_db, &mut search_db, "Road Trip".into(), "".into())
+        .await
+        .unwrap();
+    let favourites = create_playlist(&main_db, &mut search_db, "Favourites".into(), "".into())
+        .await
+        .unwrap();
+
+    for (playlist_id, file_id) in [
+        (road_trip.id, ids[0]),
+        (favourites.id, ids[0]),
+        // Added twice, listed once
+        (road_trip.id, ids[1]),
+        (road_trip.id, ids[1]),
+    ] {
+        add_item_to_playlist(&main_db, playlist_id, file_id, None, DuplicatePolicy::Allow)
+            .await
+            .unwrap();
+    }
+
+    let memberships = get_playlists_of_media_files(&main_db, &ids).await.unwrap();
+    let names = |file_id: i32| -> Vec<String> {
+        memberships
+            .get(&file_id)
+            .into_iter()
+            .flatten()
+            .map(|x| x.name.clone())
+            .collect()
+    };
+
+    assert_eq!(names(ids[0]), vec!["Favourites", "Road Trip"]);
+    assert_eq!(names(ids[1]), vec!["Road Trip"]);
+    assert!(!memberships.contains_key(&ids[2]));
+}
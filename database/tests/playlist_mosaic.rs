@@ -0,0 +1,127 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgb, RgbImage};
+use sea_orm::{ActiveValue, EntityTrait};
+
+use database::actions::cover_art::{collect_cover_art_garbage, sync_playlist_cover_art};
+use database::actions::playlists::{create_playlist, replace_playlist_items};
+use database::connection::MainDbConnection;
+use database::entities::{media_cover_art, playlists};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+async fn insert_cover_art(main_db: &MainDbConnection, file_hash: &str, color: [u8; 3]) -> i32 {
+    let mut binary = Cursor::new(Vec::new());
+    RgbImage::from_pixel(64, 64, Rgb(color))
+        .write_to(&mut binary, ImageFormat::Png)
+        .unwrap();
+
+    let item = media_cover_art::ActiveModel {
+        id: ActiveValue::NotSet,
+        file_hash: ActiveValue::Set(file_hash.to_string()),
+        binary: ActiveValue::Set(binary.into_inner()),
+        color_palette: ActiveValue::Set(None),
+        blurhash: ActiveValue::Set(None),
+    };
+
+    media_cover_art::Entity::insert(item)
+        .exec(main_db)
+        .await
+        .unwrap()
+        .last_insert_id
+}
+
+#[tokio::test]
+async fn playlists_get_a_mosaic_of_their_covers() {
+    let lib = tempfile::tempdir().unwrap();
+    let lib_path = lib.path().to_str().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let magic = media_cover_art::Entity::insert(media_cover_art::ActiveModel {
+        file_hash: ActiveValue::Set(String::new()),
+        binary: ActiveValue::Set(Vec::new()),
+        ..Default::default()
+    })
+    .exec(&main_db)
+    .await
+    .unwrap()
+    .last_insert_id;
+    let red = insert_cover_art(&main_db, "red", [255, 0, 0]).await;
+    let blue = insert_cover_art(&main_db, "blue", [0, 0, 255]).await;
+
+    let mut ids = Vec::new();
+    for (index, cover_art_id) in [red, red, magic, blue].into_iter().enumerate() {
+        let file = MediaFileFixture::new(&format!("{:02}.flac", index))
+            .cover_art_id(Some(cover_art_id))
+            .insert(&main_db)
+            .await
+            .unwrap();
+        ids.push(file.id);
+    }
+
+    let playlist = create_playlist(&main_db, &mut search_db, "Mix".into(), "".into())
+        .await
+        .unwrap();
+    replace_playlist_items(&main_db, playlist.id, &ids)
+        .await
+        .unwrap();
+
+    let mosaic = sync_playlist_cover_art(&main_db, lib_path, playlist.id)
+        .await
+        .unwrap()
+        .unwrap();
+    let stored = media_cover_art::Entity::find_by_id(mosaic)
+        .one(&main_db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.file_hash, format!("mosaic:{},{}", red, blue));
+    assert!(image::load_from_memory(&stored.binary).is_ok());
+
+    // Unchanged covers keep the same mosaic
+    assert_eq!(
+        sync_playlist_cover_art(&main_db, lib_path, playlist.id)
+            .await
+            .unwrap(),
+        Some(mosaic)
+    );
+
+    // Reordering the playlist changes it, the old one becomes garbage
+    replace_playlist_items(&main_db, playlist.id, &[ids[3], ids[0]])
+        .await
+        .unwrap();
+    let reordered = sync_playlist_cover_art(&main_db, lib_path, playlist.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(reordered, mosaic);
+
+    collect_cover_art_garbage(&main_db).await.unwrap();
+    let remaining: Vec<i32> = media_cover_art::Entity::find()
+        .all(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.id)
+        .collect();
+    assert_eq!(remaining, vec![magic, red, blue, reordered]);
+
+    // A single cover isn't worth a mosaic
+    replace_playlist_items(&main_db, playlist.id, &ids[..3])
+        .await
+        .unwrap();
+    assert_eq!(
+        sync_playlist_cover_art(&main_db, lib_path, playlist.id)
+            .await
+            .unwrap(),
+        None
+    );
+    let playlist = playlists::Entity::find_by_id(playlist.id)
+        .one(&main_db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(playlist.cover_art_id, None);
+}
@@ -0,0 +1,99 @@
+use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait};
+
+use database::actions::playlists::{add_media_file_to_playlist, create_playlist, DuplicatePolicy};
+use database::actions::query_cache::QueryCache;
+use database::actions::users::DEFAULT_USER_ID;
+use database::entities::media_cover_art;
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+#[tokio::test]
+async fn cover_arts_are_served_until_invalidated() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let cover_art = media_cover_art::ActiveModel {
+        file_hash: ActiveValue::Set("cafe".into()),
+        binary: ActiveValue::Set(vec![1, 2, 3]),
+        ..Default::default()
+    }
+    .insert(&main_db)
+    .await
+    .unwrap();
+
+    let cache = QueryCache::new(8);
+    let disabled = QueryCache::disabled();
+    assert_eq!(
+        cache.cover_art(&main_db, cover_art.id).await.unwrap(),
+        Some(vec![1, 2, 3])
+    );
+
+    media_cover_art::Entity::delete_by_id(cover_art.id)
+        .exec(&main_db)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        cache.cover_art(&main_db, cover_art.id).await.unwrap(),
+        Some(vec![1, 2, 3])
+    );
+    assert_eq!(
+        disabled.cover_art(&main_db, cover_art.id).await.unwrap(),
+        None
+    );
+
+    cache.invalidate_cover_arts();
+    assert_eq!(cache.cover_art(&main_db, cover_art.id).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn track_details_follow_playlist_changes() {
+    let lib = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let file = MediaFileFixture::new("missing.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let cache = QueryCache::new(8);
+
+    let detail = cache
+        .track_detail(&main_db, &main_db, DEFAULT_USER_ID, lib.path(), file.id)
+        .await
+        .unwrap();
+    assert!(detail.playlists.is_empty());
+
+    let playlist = create_playlist(&main_db, &mut search_db, "Later".into(), "".into())
+        .await
+        .unwrap();
+    add_media_file_to_playlist(&main_db, playlist.id, file.id, DuplicatePolicy::Allow)
+        .await
+        .unwrap();
+
+    let detail = cache
+        .track_detail(&main_db, &main_db, DEFAULT_USER_ID, lib.path(), file.id)
+        .await
+        .unwrap();
+    assert!(detail.playlists.is_empty());
+
+    cache.invalidate_playlists();
+    let detail = cache
+        .track_detail(&main_db, &main_db, DEFAULT_USER_ID, lib.path(), file.id)
+        .await
+        .unwrap();
+    assert_eq!(detail.playlists.len(), 1);
+
+    let playlist = create_playlist(&main_db, &mut search_db, "Never".into(), "".into())
+        .await
+        .unwrap();
+    add_media_file_to_playlist(&main_db, playlist.id, file.id, DuplicatePolicy::Allow)
+        .await
+        .unwrap();
+
+    cache.invalidate_files(&[file.id]);
+    let detail = cache
+        .track_detail(&main_db, &main_db, DEFAULT_USER_ID, lib.path(), file.id)
+        .await
+        .unwrap();
+    assert_eq!(detail.playlists.len(), 2);
+}
@@ -0,0 +1,107 @@
+use database::actions::audiobooks::{get_playback_position, set_playback_position};
+use database::actions::library::create_library;
+use database::actions::playback_queue::{get_playback_queue, save_playback_queue};
+use database::actions::ratings::{get_ratings, set_ratings};
+use database::actions::users::DEFAULT_USER_ID;
+use database::connection::{
+    connect_main_db, connect_main_db_read_only, connect_overlay_db,
+    connect_recommendation_db_read_only, connect_search_db_read_only,
+};
+use database::entities::prelude::MediaFiles;
+use database::test_support::MediaFileFixture;
+use sea_orm::EntityTrait;
+
+#[tokio::test]
+async fn opens_created_library_read_only() {
+    let lib = tempfile::tempdir().unwrap();
+    create_library(lib.path()).await.unwrap();
+    let lib_path = lib.path().to_str().unwrap();
+
+    let main_db = connect_main_db_read_only(lib_path).await.unwrap();
+    assert!(MediaFiles::find().all(&main_db).await.unwrap().is_empty());
+
+    let mut search_db = connect_search_db_read_only(lib_path).unwrap();
+    search_db.commit().unwrap();
+
+    connect_recommendation_db_read_only(lib_path).unwrap();
+}
+
+#[tokio::test]
+async fn read_only_main_db_requires_existing_library() {
+    let lib = tempfile::tempdir().unwrap();
+
+    assert!(connect_main_db_read_only(lib.path().to_str().unwrap())
+        .await
+        .is_err());
+    assert!(!lib.path().join(".rune").exists());
+}
+
+#[tokio::test]
+async fn overlay_stores_play_state_of_read_only_library() {
+    let lib = tempfile::tempdir().unwrap();
+    let overlay = tempfile::tempdir().unwrap();
+    let lib_path = lib.path().to_str().unwrap();
+
+    let main_db = connect_main_db(lib_path).await.unwrap();
+    let file = MediaFileFixture::new("track.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    main_db.close().await.unwrap();
+
+    let main_db = connect_main_db_read_only(lib_path).await.unwrap();
+    assert_eq!(MediaFiles::find().all(&main_db).await.unwrap().len(), 1);
+
+    // Reopened, as on every start
+    connect_overlay_db(overlay.path().to_str().unwrap())
+        .await
+        .unwrap()
+        .close()
+        .await
+        .unwrap();
+    let user_db = connect_overlay_db(overlay.path().to_str().unwrap())
+        .await
+        .unwrap();
+
+    save_playback_queue(&user_db, DEFAULT_USER_ID, &[file.id], Some(0), 12.5)
+        .await
+        .unwrap();
+    set_ratings(&user_db, DEFAULT_USER_ID, &[file.id], 4)
+        .await
+        .unwrap();
+    set_playback_position(&user_db, file.id, 30.0)
+        .await
+        .unwrap();
+
+    let queue = get_playback_queue(&user_db, DEFAULT_USER_ID).await.unwrap();
+    assert_eq!(queue.file_ids, vec![file.id]);
+    assert_eq!(queue.index, Some(0));
+    assert_eq!(
+        get_ratings(&user_db, DEFAULT_USER_ID, &[file.id])
+            .await
+            .unwrap()
+            .get(&file.id),
+        Some(&4)
+    );
+    assert_eq!(
+        get_playback_position(&user_db, file.id).await.unwrap(),
+        Some(30.0)
+    );
+}
+
+#[tokio::test]
+async fn read_only_main_db_reads_the_wal() {
+    let lib = tempfile::tempdir().unwrap();
+    let lib_path = lib.path().to_str().unwrap();
+
+    // The writer stays open, so its changes are still in the WAL
+    let main_db = connect_main_db(lib_path).await.unwrap();
+    MediaFileFixture::new("track.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    assert!(lib.path().join(".rune/.0.db-wal").metadata().unwrap().len() > 0);
+
+    let reader = connect_main_db_read_only(lib_path).await.unwrap();
+    assert_eq!(MediaFiles::find().all(&reader).await.unwrap().len(), 1);
+}
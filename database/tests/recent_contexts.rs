@@ -0,0 +1,88 @@
+use database::actions::recent_contexts::{
+    get_recent_contexts, record_recent_context, resume_recent_context, save_context_progress,
+    RECENT_CONTEXTS_LIMIT,
+};
+use database::actions::users::DEFAULT_USER_ID;
+use database::test_support::{connect_main_db_in_memory, insert_media_files};
+
+#[tokio::test]
+async fn progress_is_saved_to_the_latest_context() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let files = insert_media_files(&main_db, 4).await.unwrap();
+    let ids: Vec<i32> = files.iter().map(|x| x.id).collect();
+
+    record_recent_context(&main_db, DEFAULT_USER_ID, "album", 1, &ids[..2])
+        .await
+        .unwrap();
+    let mix = record_recent_context(&main_db, DEFAULT_USER_ID, "track_mix", ids[2], &ids[2..])
+        .await
+        .unwrap();
+
+    // A track was added to the mix while it played
+    let queue = [ids[2], ids[3], ids[0]];
+    assert!(
+        save_context_progress(&main_db, DEFAULT_USER_ID, &queue, Some(1), 12.5)
+            .await
+            .unwrap()
+    );
+    // The queue was replaced by something outside the context
+    assert!(
+        !save_context_progress(&main_db, DEFAULT_USER_ID, &ids[1..2], Some(0), 3.0)
+            .await
+            .unwrap()
+    );
+
+    let contexts = get_recent_contexts(&main_db, DEFAULT_USER_ID, 10)
+        .await
+        .unwrap();
+    assert_eq!(contexts.len(), 2);
+    assert_eq!(contexts[0].id, mix);
+    assert_eq!(contexts[0].file_ids, queue);
+    assert_eq!(contexts[0].index, Some(1));
+    assert_eq!(contexts[0].position, 12.5);
+    assert_eq!(contexts[1].context_type, "album");
+    assert_eq!(contexts[1].index, Some(0));
+
+    // Resuming the album makes it the latest again
+    let album = resume_recent_context(&main_db, DEFAULT_USER_ID, contexts[1].id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(album.file_ids, &ids[..2]);
+    let contexts = get_recent_contexts(&main_db, DEFAULT_USER_ID, 1)
+        .await
+        .unwrap();
+    assert_eq!(contexts[0].id, album.id);
+    assert!(
+        resume_recent_context(&main_db, DEFAULT_USER_ID + 1, album.id)
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn playing_a_context_again_replaces_it() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let files = insert_media_files(&main_db, 2).await.unwrap();
+    let ids: Vec<i32> = files.iter().map(|x| x.id).collect();
+
+    for context_id in 0..RECENT_CONTEXTS_LIMIT as i32 + 2 {
+        record_recent_context(&main_db, DEFAULT_USER_ID, "playlist", context_id, &ids)
+            .await
+            .unwrap();
+    }
+    record_recent_context(&main_db, DEFAULT_USER_ID, "playlist", 5, &ids[..1])
+        .await
+        .unwrap();
+
+    let contexts = get_recent_contexts(&main_db, DEFAULT_USER_ID, 100)
+        .await
+        .unwrap();
+    assert_eq!(contexts.len(), RECENT_CONTEXTS_LIMIT);
+    assert_eq!(contexts[0].context_id, 5);
+    assert_eq!(contexts[0].file_ids, &ids[..1]);
+    assert_eq!(contexts.iter().filter(|x| x.context_id == 5).count(), 1);
+    // The oldest ones are forgotten
+    assert!(contexts.iter().all(|x| x.context_id >= 2));
+}
@@ -0,0 +1,72 @@
+use arroy::distances::Euclidean;
+use arroy::Writer;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use database::actions::recommendation::{
+    benchmark_index, get_index_parameters, set_index_parameters, IndexParameters,
+};
+use database::connection::{connect_recommendation_db, RecommendationDbConnection};
+
+fn build_index(db_conn: &RecommendationDbConnection, n_trees: Option<usize>) {
+    let mut wtxn = db_conn.env.write_txn().unwrap();
+    let writer = Writer::<Euclidean>::new(db_conn.db, 0, 17);
+    for id in 0..200u32 {
+        let vector: Vec<f32> = (0..17).map(|x| ((id * 17 + x) as f32).sin()).collect();
+        writer.add_item(&mut wtxn, id, &vector).unwrap();
+    }
+    let mut rng = StdRng::seed_from_u64(42);
+    writer.build(&mut wtxn, &mut rng, n_trees).unwrap();
+    wtxn.commit().unwrap();
+}
+
+#[test]
+fn index_parameters_are_persisted() {
+    let lib = tempfile::tempdir().unwrap();
+    let lib_path = lib.path().to_str().unwrap();
+    let db_conn = connect_recommendation_db(lib_path).unwrap();
+    build_index(&db_conn, None);
+
+    assert_eq!(
+        get_index_parameters(&db_conn).unwrap(),
+        IndexParameters::default()
+    );
+
+    let parameters = IndexParameters {
+        n_trees: Some(4),
+        search_k_factor: 30,
+    };
+    // Another number of trees drops the old ones
+    assert!(set_index_parameters(&db_conn, parameters).unwrap());
+    assert!(benchmark_index(&db_conn, &[1], 1, 1).is_err());
+    assert!(!set_index_parameters(&db_conn, parameters).unwrap());
+    assert!(set_index_parameters(
+        &db_conn,
+        IndexParameters {
+            n_trees: Some(0),
+            search_k_factor: 30,
+        }
+    )
+    .is_err());
+
+    drop(db_conn);
+    let db_conn = connect_recommendation_db(lib_path).unwrap();
+    assert_eq!(get_index_parameters(&db_conn).unwrap(), parameters);
+}
+
+#[test]
+fn benchmark_reports_recall_per_search_factor() {
+    let lib = tempfile::tempdir().unwrap();
+    let db_conn = connect_recommendation_db(lib.path().to_str().unwrap()).unwrap();
+    build_index(&db_conn, Some(4));
+
+    let report = benchmark_index(&db_conn, &[1, 1000], 10, 5).unwrap();
+
+    assert_eq!(report.n_trees, 4);
+    assert_eq!(report.n_items, 200);
+    assert_eq!(report.results.len(), 2);
+    assert_eq!(report.results[1].search_k_factor, 1000);
+    // Looking at every node finds the exact neighbours
+    assert_eq!(report.results[1].recall, 1.0);
+    assert!(report.results[0].recall <= report.results[1].recall);
+}
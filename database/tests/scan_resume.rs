@@ -0,0 +1,56 @@
+use database::actions::metadata::{
+    empty_progress_callback, scan_audio_library, SCAN_CHECKPOINT_KEY,
+};
+use database::actions::settings::{get_setting, set_setting};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+#[tokio::test]
+async fn finished_scans_clear_the_checkpoint() {
+    let lib = tempfile::tempdir().unwrap();
+    std::fs::write(lib.path().join("a.mp3"), b"").unwrap();
+    std::fs::write(lib.path().join("b.mp3"), b"").unwrap();
+
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    set_setting(&main_db, SCAN_CHECKPOINT_KEY, "a.mp3".to_string())
+        .await
+        .unwrap();
+
+    let processed = scan_audio_library(
+        &main_db,
+        &mut search_db,
+        lib.path(),
+        false,
+        empty_progress_callback,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Only the file after the checkpoint is read again
+    assert_eq!(processed, 1);
+    assert_eq!(
+        get_setting(&main_db, SCAN_CHECKPOINT_KEY).await.unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn a_path_is_stored_once() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    MediaFileFixture::new("track.flac")
+        .directory("Artist")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let duplicate = MediaFileFixture::new("track.flac")
+        .directory("Artist")
+        .file_hash("other")
+        .insert(&main_db)
+        .await;
+    assert!(duplicate.is_err());
+}
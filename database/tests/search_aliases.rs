@@ -0,0 +1,70 @@
+use database::actions::search::{add_term, search_for, CollectionType, SearchSynonyms};
+use database::actions::search_aliases::{
+    add_search_alias, load_search_synonyms, remove_search_alias,
+};
+use database::test_support::{connect_main_db_in_memory, connect_search_db_in_memory};
+
+#[test]
+fn aliases_are_transitive() {
+    let synonyms = SearchSynonyms::new([
+        ("Tchaikovsky".to_string(), "Чайковский".to_string()),
+        ("Чайковский".to_string(), "Tschaikowsky".to_string()),
+    ]);
+
+    let mut expanded = synonyms.expand("tchaikovsky symphony");
+    expanded.sort();
+    assert_eq!(
+        expanded,
+        vec!["tschaikowsky symphony", "чайковский symphony"]
+    );
+}
+
+#[test]
+fn aliases_only_match_whole_words() {
+    let synonyms = SearchSynonyms::new([("RHCP".to_string(), "Red Hot Chili Peppers".to_string())]);
+
+    assert!(synonyms.expand("rhcpx").is_empty());
+    assert_eq!(
+        synonyms.expand("RHCP live"),
+        vec!["red hot chili peppers live"]
+    );
+}
+
+#[tokio::test]
+async fn either_form_finds_the_artist() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    add_term(&mut search_db, CollectionType::Artist, 1, "Чайковский");
+    search_db.commit().unwrap();
+
+    let alias = add_search_alias(&main_db, "Tchaikovsky", "Чайковский")
+        .await
+        .unwrap()
+        .unwrap();
+    // Adding the same alias twice keeps one row
+    let again = add_search_alias(&main_db, "Tchaikovsky", "Чайковский")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(alias.id, again.id);
+
+    search_db.synonyms = load_search_synonyms(&main_db).await.unwrap();
+    let results = search_for(&mut search_db, "tchaikovsky", 10).unwrap();
+    assert_eq!(results.get(&CollectionType::Artist), Some(&vec![1]));
+
+    assert!(remove_search_alias(&main_db, alias.id).await.unwrap());
+    search_db.synonyms = load_search_synonyms(&main_db).await.unwrap();
+    let results = search_for(&mut search_db, "tchaikovsky", 10).unwrap();
+    assert!(!results.contains_key(&CollectionType::Artist));
+}
+
+#[tokio::test]
+async fn equal_names_are_not_stored() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    assert!(add_search_alias(&main_db, "ABBA", " abba ")
+        .await
+        .unwrap()
+        .is_none());
+}
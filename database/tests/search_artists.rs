@@ -0,0 +1,126 @@
+use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
+
+use database::actions::index::{index_media_files, rebuild_search_index};
+use database::actions::search::{search_for, CollectionType};
+use database::connection::SearchDbConnection;
+use database::entities::media_metadata;
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+fn found(search_db: &mut SearchDbConnection, query: &str, r#type: CollectionType) -> Vec<i64> {
+    search_for(search_db, query, 10)
+        .unwrap()
+        .remove(&r#type)
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn tracks_are_found_by_artist_and_album() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let id = MediaFileFixture::new("airbag.flac")
+        .tags(&[
+            ("track_title", "Airbag"),
+            ("artist", "Radiohead"),
+            ("album", "OK Computer"),
+        ])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+
+    index_media_files(&main_db, &mut search_db, vec![id])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        found(&mut search_db, "radiohead", CollectionType::Track),
+        vec![id as i64]
+    );
+    assert_eq!(
+        found(&mut search_db, "computer", CollectionType::Track),
+        vec![id as i64]
+    );
+    assert_eq!(
+        found(&mut search_db, "radiohead", CollectionType::Album).len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn track_documents_follow_tag_changes() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let id = MediaFileFixture::new("song.flac")
+        .tags(&[("track_title", "Song"), ("artist", "Björk")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+
+    index_media_files(&main_db, &mut search_db, vec![id])
+        .await
+        .unwrap();
+    // Latinized names are searchable too
+    assert_eq!(
+        found(&mut search_db, "bjork", CollectionType::Track),
+        vec![id as i64]
+    );
+
+    let mut artist: media_metadata::ActiveModel = media_metadata::Entity::find()
+        .filter(media_metadata::Column::FileId.eq(id))
+        .filter(media_metadata::Column::MetaKey.eq("artist"))
+        .one(&main_db)
+        .await
+        .unwrap()
+        .unwrap()
+        .into();
+    artist.meta_value = ActiveValue::Set("Sigur Rós".to_string());
+    artist.update(&main_db).await.unwrap();
+
+    index_media_files(&main_db, &mut search_db, vec![id])
+        .await
+        .unwrap();
+
+    assert!(found(&mut search_db, "bjork", CollectionType::Track).is_empty());
+    assert_eq!(
+        found(&mut search_db, "sigur", CollectionType::Track),
+        vec![id as i64]
+    );
+}
+
+#[tokio::test]
+async fn rebuild_restores_all_documents() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let id = MediaFileFixture::new("airbag.flac")
+        .tags(&[
+            ("track_title", "Airbag"),
+            ("artist", "Radiohead"),
+            ("album", "OK Computer"),
+        ])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    index_media_files(&main_db, &mut search_db, vec![id])
+        .await
+        .unwrap();
+
+    let mut rebuilt = connect_search_db_in_memory().unwrap();
+    rebuild_search_index(&main_db, &mut rebuilt, 10)
+        .await
+        .unwrap();
+
+    for r#type in [
+        CollectionType::Track,
+        CollectionType::Artist,
+        CollectionType::Album,
+    ] {
+        assert_eq!(found(&mut rebuilt, "radiohead", r#type).len(), 1);
+    }
+}
@@ -0,0 +1,59 @@
+use database::actions::search::{
+    add_album_term, add_term, add_track_term, best_match, search_scored, CollectionType,
+};
+use database::connection::SearchDbConnection;
+use database::test_support::connect_search_db_in_memory;
+
+fn top_result(search_db: &mut SearchDbConnection, query: &str) -> Option<(CollectionType, i64)> {
+    let results = search_scored(search_db, query, 10).unwrap();
+    best_match(&results, query)
+}
+
+fn library() -> SearchDbConnection {
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    add_term(&mut search_db, CollectionType::Artist, 1, "Radiohead");
+    add_album_term(&mut search_db, 1, "Creep EP", "Radiohead");
+    add_track_term(&mut search_db, 1, "Creep", "Radiohead", "Pablo Honey");
+    add_track_term(
+        &mut search_db,
+        2,
+        "Creep (Acoustic)",
+        "Radiohead",
+        "Creep EP",
+    );
+    search_db.commit().unwrap();
+
+    search_db
+}
+
+#[test]
+fn artist_wins_for_its_name() {
+    let mut search_db = library();
+
+    assert_eq!(
+        top_result(&mut search_db, "radiohead"),
+        Some((CollectionType::Artist, 1))
+    );
+}
+
+#[test]
+fn exact_title_wins_over_partial_matches() {
+    let mut search_db = library();
+
+    assert_eq!(
+        top_result(&mut search_db, "Creep"),
+        Some((CollectionType::Track, 1))
+    );
+    assert_eq!(
+        top_result(&mut search_db, "creep ep"),
+        Some((CollectionType::Album, 1))
+    );
+}
+
+#[test]
+fn no_best_match_without_results() {
+    let mut search_db = library();
+
+    assert_eq!(top_result(&mut search_db, "portishead"), None);
+}
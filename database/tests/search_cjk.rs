@@ -0,0 +1,36 @@
+use database::actions::search::{add_term, search_for, to_pinyin, to_romaji, CollectionType};
+use database::connection::SearchDbConnection;
+use database::test_support::connect_search_db_in_memory;
+
+fn found(search_db: &mut SearchDbConnection, query: &str) -> Vec<i64> {
+    search_for(search_db, query, 10)
+        .unwrap()
+        .remove(&CollectionType::Artist)
+        .unwrap_or_default()
+}
+
+#[test]
+fn pinyin_has_syllables_and_initials() {
+    assert_eq!(to_pinyin("周杰伦"), "zhou jie lun zhoujielun zjl");
+    assert_eq!(to_pinyin("Jay 周"), "zhou");
+    assert_eq!(to_pinyin("Radiohead"), "");
+}
+
+#[test]
+fn romaji_needs_kana() {
+    assert_eq!(to_romaji("あいみょん"), "aimyon");
+    assert_eq!(to_romaji("Radiohead"), "");
+}
+
+#[test]
+fn cjk_names_are_found_by_reading() {
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    add_term(&mut search_db, CollectionType::Artist, 1, "周杰伦");
+    add_term(&mut search_db, CollectionType::Artist, 2, "あいみょん");
+    search_db.commit().unwrap();
+
+    assert_eq!(found(&mut search_db, "zjl"), vec![1]);
+    assert_eq!(found(&mut search_db, "zhoujielun"), vec![1]);
+    assert_eq!(found(&mut search_db, "aimyon"), vec![2]);
+}
@@ -0,0 +1,68 @@
+use database::actions::search::{add_term, add_track_term, suggest_queries, CollectionType};
+use database::connection::SearchDbConnection;
+use database::test_support::connect_search_db_in_memory;
+
+fn library() -> SearchDbConnection {
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    add_term(&mut search_db, CollectionType::Artist, 1, "Radiohead");
+    add_term(&mut search_db, CollectionType::Artist, 2, "Portishead");
+    add_track_term(
+        &mut search_db,
+        1,
+        "Karma Police",
+        "Radiohead",
+        "OK Computer",
+    );
+    add_track_term(
+        &mut search_db,
+        2,
+        "Paranoid Android",
+        "Radiohead",
+        "OK Computer",
+    );
+    search_db.commit().unwrap();
+
+    search_db
+}
+
+#[test]
+fn misspelled_words_are_corrected() {
+    let search_db = library();
+
+    assert_eq!(
+        suggest_queries(&search_db, "radiohaed", 3).unwrap(),
+        vec!["radiohead"]
+    );
+    assert_eq!(
+        suggest_queries(&search_db, "karma polcie", 3).unwrap(),
+        vec!["karma police"]
+    );
+}
+
+#[test]
+fn known_queries_have_no_suggestions() {
+    let search_db = library();
+
+    assert!(suggest_queries(&search_db, "paranoid android", 3)
+        .unwrap()
+        .is_empty());
+    // Too far from every indexed word
+    assert!(suggest_queries(&search_db, "zzzzzz", 3).unwrap().is_empty());
+}
+
+#[test]
+fn suggestions_are_ranked_by_document_frequency() {
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    add_term(&mut search_db, CollectionType::Artist, 1, "Lana");
+    add_term(&mut search_db, CollectionType::Artist, 2, "Luna");
+    add_track_term(&mut search_db, 1, "Bonnie and Clyde", "Luna", "Penthouse");
+    search_db.commit().unwrap();
+
+    // Both are one edit away, luna is in more documents
+    assert_eq!(
+        suggest_queries(&search_db, "lena", 3).unwrap(),
+        vec!["luna", "lana"]
+    );
+}
@@ -0,0 +1,92 @@
+use analysis::seek_table::SeekTable;
+
+use database::actions::seek_tables::{
+    build_seek_tables, get_seek_tables_of_files, insert_seek_tables, refresh_seek_table,
+};
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+// Frames of MPEG 1 layer III at 128 kbps and 44.1 kHz, silent
+fn write_mp3(path: &std::path::Path, frames: usize) {
+    let mut frame = vec![0; 417];
+    frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+    std::fs::write(path, frame.repeat(frames)).unwrap();
+}
+
+#[tokio::test]
+async fn seek_tables_are_built_for_slow_seeking_formats() {
+    let lib = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    write_mp3(&lib.path().join("a.mp3"), 100);
+    write_sine_fixture(&lib.path().join("b.wav"), FixtureFormat::Wav, 8000, 1, 8000).unwrap();
+    let mp3 = MediaFileFixture::new("a.mp3")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let wav = MediaFileFixture::new("b.wav")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let tables = build_seek_tables(&[mp3.clone(), wav.clone()], lib.path());
+    assert_eq!(tables.len(), 1);
+    assert_eq!(tables[0].0, mp3.id);
+    assert_eq!(tables[0].1.points.len(), 3);
+
+    insert_seek_tables(&main_db, &tables).await.unwrap();
+    // A new table replaces the old one
+    let empty = SeekTable {
+        sample_rate: 44100,
+        header_len: 0,
+        points: Vec::new(),
+    };
+    insert_seek_tables(&main_db, &[(mp3.id, empty.clone())])
+        .await
+        .unwrap();
+
+    let stored = get_seek_tables_of_files(&main_db, &[mp3.id, wav.id])
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[&mp3.id], empty);
+}
+
+#[tokio::test]
+async fn changed_files_get_a_new_seek_table() {
+    let lib = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    let path = lib.path().join("a.mp3");
+    write_mp3(&path, 100);
+    let mp3 = MediaFileFixture::new("a.mp3")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    insert_seek_tables(
+        &main_db,
+        &build_seek_tables(std::slice::from_ref(&mp3), lib.path()),
+    )
+    .await
+    .unwrap();
+
+    // A longer tag moves every frame
+    let mut tagged = vec![0; 1000];
+    tagged.extend(std::fs::read(&path).unwrap());
+    std::fs::write(&path, tagged).unwrap();
+    refresh_seek_table(&main_db, mp3.id, &path).await.unwrap();
+
+    let stored = get_seek_tables_of_files(&main_db, &[mp3.id]).await.unwrap();
+    assert_eq!(
+        stored[&mp3.id],
+        build_seek_tables(std::slice::from_ref(&mp3), lib.path())[0].1
+    );
+
+    // Unreadable files lose their table
+    std::fs::write(&path, b"not an mp3").unwrap();
+    refresh_seek_table(&main_db, mp3.id, &path).await.unwrap();
+    assert!(get_seek_tables_of_files(&main_db, &[mp3.id])
+        .await
+        .unwrap()
+        .is_empty());
+}
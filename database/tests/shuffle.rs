@@ -0,0 +1,78 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use database::actions::shuffle::{keep_in_place, shuffle_groups, spread_groups};
+
+#[test]
+fn shuffled_groups_stay_together_and_in_order() {
+    let items: Vec<(i32, char)> = vec![(1, 'a'), (2, 'a'), (3, 'b'), (4, 'c'), (5, 'a'), (6, 'c')];
+
+    for seed in 0..32 {
+        let result = shuffle_groups(items.clone(), &mut StdRng::seed_from_u64(seed));
+
+        let mut sorted = result.clone();
+        sorted.sort();
+        assert_eq!(sorted, vec![1, 2, 3, 4, 5, 6]);
+
+        let group_of: HashMap<i32, char> = items.iter().cloned().collect();
+        let groups: Vec<char> = result.iter().map(|x| group_of[x]).collect();
+        // Every group shows up as a single run
+        let mut runs = groups.clone();
+        runs.dedup();
+        assert_eq!(runs.len(), 3, "{:?}", groups);
+
+        // Items inside a group keep their order
+        for group in ['a', 'b', 'c'] {
+            let members: Vec<i32> = result
+                .iter()
+                .filter(|x| group_of[*x] == group)
+                .cloned()
+                .collect();
+            assert!(members.windows(2).all(|x| x[0] < x[1]), "{:?}", result);
+        }
+    }
+}
+
+#[test]
+fn spread_groups_avoid_repeats_when_possible() {
+    let items: Vec<(i32, char)> = (0..12)
+        .map(|x| (x, ['a', 'a', 'a', 'b', 'b', 'c'][x as usize % 6]))
+        .collect();
+    let group_of: HashMap<i32, char> = items.iter().cloned().collect();
+
+    for seed in 0..32 {
+        let result = spread_groups(items.clone(), &mut StdRng::seed_from_u64(seed));
+
+        let mut sorted = result.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..12).collect::<Vec<_>>());
+        assert!(
+            result
+                .windows(2)
+                .all(|x| group_of[&x[0]] != group_of[&x[1]]),
+            "{:?}",
+            result
+        );
+    }
+}
+
+#[test]
+fn spread_groups_handles_a_dominant_group() {
+    let items = vec![(1, 'a'), (2, 'a'), (3, 'a'), (4, 'b')];
+    let result = spread_groups(items, &mut StdRng::seed_from_u64(7));
+
+    assert_eq!(result.len(), 4);
+}
+
+#[test]
+fn pinned_items_keep_their_place() {
+    let pinned: HashSet<usize> = [0, 3].into_iter().collect();
+
+    assert_eq!(
+        keep_in_place(vec![4, 3, 2, 0, 1], &pinned),
+        vec![0, 4, 2, 3, 1]
+    );
+    assert_eq!(keep_in_place(vec![1, 0], &HashSet::new()), vec![1, 0]);
+}
@@ -0,0 +1,72 @@
+use database::actions::diversity::apply_mix_policy;
+use database::actions::play_history::log_play;
+use database::actions::skips::{
+    down_rank, get_skip_penalties, get_skip_stats, is_skipped, log_skip, reset_skips,
+};
+use database::actions::users::DEFAULT_USER_ID;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+#[test]
+fn early_moves_count_as_skips() {
+    assert!(is_skipped(10., 200.));
+    assert!(!is_skipped(40., 200.));
+    assert!(!is_skipped(0., 0.));
+}
+
+#[test]
+fn penalised_items_move_back() {
+    let penalties = [0., 1., 0., 0.5, 0.];
+    assert_eq!(
+        down_rank((0..5).collect(), |x: &usize| penalties[*x]),
+        vec![0, 2, 4, 3, 1]
+    );
+    assert_eq!(down_rank(vec![3, 1, 2], |_| 0.), vec![3, 1, 2]);
+}
+
+#[tokio::test]
+async fn skips_down_rank_recommendations_until_reset() {
+    let db = connect_main_db_in_memory().await.unwrap();
+    let mut ids = Vec::new();
+    for name in ["a.flac", "b.flac", "c.flac"] {
+        ids.push(MediaFileFixture::new(name).insert(&db).await.unwrap().id);
+    }
+
+    // A couple of skips don't count yet
+    log_skip(&db, ids[0]).await.unwrap();
+    log_skip(&db, ids[0]).await.unwrap();
+    assert!(get_skip_penalties(&db, &ids).await.unwrap().is_empty());
+
+    log_skip(&db, ids[0]).await.unwrap();
+    log_play(&db, DEFAULT_USER_ID, ids[0], 1.).await.unwrap();
+    let stats = get_skip_stats(&db).await.unwrap();
+    assert_eq!(stats.len(), 1);
+    assert_eq!((stats[0].skips, stats[0].plays), (3, 1));
+    assert_eq!(stats[0].penalty, 0.75);
+
+    // The seed of a mix stays first however often it was skipped
+    let mix = apply_mix_policy(
+        &db,
+        &db,
+        DEFAULT_USER_ID,
+        vec![ids[0], ids[1], ids[2]],
+        &[ids[0]],
+    )
+    .await
+    .unwrap();
+    assert_eq!(mix, vec![ids[0], ids[1], ids[2]]);
+    log_skip(&db, ids[1]).await.unwrap();
+    log_skip(&db, ids[1]).await.unwrap();
+    log_skip(&db, ids[1]).await.unwrap();
+    let mix = apply_mix_policy(&db, &db, DEFAULT_USER_ID, vec![ids[1], ids[2]], &[])
+        .await
+        .unwrap();
+    assert_eq!(mix, vec![ids[2], ids[1]]);
+
+    assert_eq!(reset_skips(&db, &[ids[1]]).await.unwrap(), 1);
+    let mix = apply_mix_policy(&db, &db, DEFAULT_USER_ID, vec![ids[1], ids[2]], &[])
+        .await
+        .unwrap();
+    assert_eq!(mix, vec![ids[1], ids[2]]);
+    assert_eq!(reset_skips(&db, &[]).await.unwrap(), 1);
+    assert!(get_skip_stats(&db).await.unwrap().is_empty());
+}
@@ -0,0 +1,155 @@
+use chrono::{Duration, Utc};
+use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait};
+
+use database::actions::albums::{get_albums_by_ids, get_media_file_ids_of_album};
+use database::actions::file::get_media_files;
+use database::actions::metadata::{clean_up_database, DELETED_GRACE_DAYS_KEY};
+use database::actions::settings::set_setting;
+use database::entities::{albums, media_files};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+#[tokio::test]
+async fn missing_files_are_kept_during_the_grace_period() {
+    let lib = tempfile::tempdir().unwrap();
+    std::fs::write(lib.path().join("present.mp3"), b"").unwrap();
+
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let present = MediaFileFixture::new("present.mp3")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let missing = MediaFileFixture::new("missing.mp3")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    clean_up_database(&main_db, &mut search_db, lib.path())
+        .await
+        .unwrap();
+
+    let missing = media_files::Entity::find_by_id(missing.id)
+        .one(&main_db)
+        .await
+        .unwrap()
+        .expect("the record is kept");
+    assert!(missing.deleted_at.is_some());
+
+    // Deleted files are hidden from listings
+    let listed: Vec<i32> = get_media_files(&main_db, 0, 10, false)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.id)
+        .collect();
+    assert_eq!(listed, vec![present.id]);
+
+    // A second cleanup inside the grace period keeps the record
+    clean_up_database(&main_db, &mut search_db, lib.path())
+        .await
+        .unwrap();
+    assert!(media_files::Entity::find_by_id(missing.id)
+        .one(&main_db)
+        .await
+        .unwrap()
+        .is_some());
+}
+
+#[tokio::test]
+async fn expired_files_are_removed() {
+    let lib = tempfile::tempdir().unwrap();
+    // An empty root looks like an unmounted drive
+    std::fs::write(lib.path().join("present.mp3"), b"").unwrap();
+
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    set_setting(&main_db, DELETED_GRACE_DAYS_KEY, "7".to_string())
+        .await
+        .unwrap();
+
+    let recent = MediaFileFixture::new("recent.mp3")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let old = MediaFileFixture::new("old.mp3")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    for (file, age) in [(&recent, Duration::days(6)), (&old, Duration::days(8))] {
+        let mut active_model: media_files::ActiveModel = file.clone().into();
+        active_model.deleted_at = ActiveValue::Set(Some((Utc::now() - age).to_rfc3339()));
+        active_model.update(&main_db).await.unwrap();
+    }
+
+    clean_up_database(&main_db, &mut search_db, lib.path())
+        .await
+        .unwrap();
+
+    let remaining: Vec<i32> = media_files::Entity::find()
+        .all(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.id)
+        .collect();
+    assert_eq!(remaining, vec![recent.id]);
+}
+
+#[tokio::test]
+async fn collections_leave_out_deleted_files() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    let album = albums::ActiveModel {
+        name: ActiveValue::Set("Album".to_string()),
+        group: ActiveValue::Set("A".to_string()),
+        ..Default::default()
+    }
+    .insert(&main_db)
+    .await
+    .unwrap();
+    let mut files = Vec::new();
+    for name in ["a.mp3", "b.mp3"] {
+        let file = MediaFileFixture::new(name)
+            .album_id(album.id)
+            .insert(&main_db)
+            .await
+            .unwrap();
+        files.push(file);
+    }
+
+    let delete = |file: &media_files::Model| {
+        let mut active_model: media_files::ActiveModel = file.clone().into();
+        active_model.deleted_at = ActiveValue::Set(Some(Utc::now().to_rfc3339()));
+        active_model.update(&main_db)
+    };
+
+    delete(&files[0]).await.unwrap();
+    assert_eq!(
+        get_media_file_ids_of_album(&main_db, album.id)
+            .await
+            .unwrap(),
+        vec![files[1].id]
+    );
+    assert_eq!(
+        get_albums_by_ids(&main_db, &[album.id])
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // An album whose every track is missing is hidden with them
+    delete(&files[1]).await.unwrap();
+    assert!(get_media_file_ids_of_album(&main_db, album.id)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(get_albums_by_ids(&main_db, &[album.id])
+        .await
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,113 @@
+use sea_orm::EntityTrait;
+
+use database::actions::albums::get_albums_groups;
+use database::actions::artists::get_artists_groups;
+use database::actions::index::index_media_files;
+use database::actions::metadata::parse_year;
+use database::entities::{albums, artists};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+#[test]
+fn years_are_read_from_dates() {
+    assert_eq!(parse_year("1969"), Some(1969));
+    assert_eq!(parse_year("1969-09-26"), Some(1969));
+    assert_eq!(parse_year(" 1969.09 "), Some(1969));
+    assert_eq!(parse_year("69"), None);
+    assert_eq!(parse_year("19690926"), None);
+    assert_eq!(parse_year("unknown"), None);
+}
+
+#[tokio::test]
+async fn sort_tags_and_original_dates_are_stored() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let ids = vec![
+        MediaFileFixture::new("come_together.flac")
+            .tags(&[
+                ("artist", "The Beatles"),
+                ("sort_artist", "Beatles, The"),
+                ("album", "Abbey Road"),
+                ("sort_album", "Abbey Road (Remaster)"),
+                ("date", "2019-09-27"),
+                ("original_date", "1969-09-26"),
+            ])
+            .insert(&main_db)
+            .await
+            .unwrap()
+            .id,
+        // A reissue track without the original date
+        MediaFileFixture::new("something.flac")
+            .tags(&[
+                ("artist", "The Beatles"),
+                ("album", "Abbey Road"),
+                ("date", "1987"),
+            ])
+            .insert(&main_db)
+            .await
+            .unwrap()
+            .id,
+        MediaFileFixture::new("air.flac")
+            .tags(&[("artist", "Bach"), ("album", "Suites"), ("date", "2001")])
+            .insert(&main_db)
+            .await
+            .unwrap()
+            .id,
+    ];
+
+    index_media_files(&main_db, &mut search_db, ids)
+        .await
+        .unwrap();
+
+    let albums = albums::Entity::find().all(&main_db).await.unwrap();
+    let abbey_road = albums.iter().find(|x| x.name == "Abbey Road").unwrap();
+    assert_eq!(abbey_road.year, Some(1969));
+    assert_eq!(
+        abbey_road.sort_name.as_deref(),
+        Some("Abbey Road (Remaster)")
+    );
+
+    let artists = artists::Entity::find().all(&main_db).await.unwrap();
+    let beatles = artists.iter().find(|x| x.name == "The Beatles").unwrap();
+    assert_eq!(beatles.sort_name.as_deref(), Some("Beatles, The"));
+    assert_eq!(beatles.group, "B");
+
+    // Sorted by sort name within the group, not by name
+    let groups = get_artists_groups(&main_db, vec!["B".to_string()])
+        .await
+        .unwrap();
+    let names: Vec<&str> = groups[0].1.iter().map(|x| x.0.name.as_str()).collect();
+    assert_eq!(names, ["Bach", "The Beatles"]);
+
+    let groups = get_albums_groups(&main_db, vec!["A".to_string(), "S".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(groups[0].1.len(), 1);
+    assert_eq!(groups[1].1[0].0.year, Some(2001));
+}
+
+#[tokio::test]
+async fn sort_tags_of_several_artists_are_ignored() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    let id = MediaFileFixture::new("duet.flac")
+        .tags(&[
+            ("artist", "The Police & Sting"),
+            ("sort_artist", "Police, The & Sting"),
+        ])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+
+    index_media_files(&main_db, &mut search_db, vec![id])
+        .await
+        .unwrap();
+
+    let artists = artists::Entity::find().all(&main_db).await.unwrap();
+    assert!(artists.len() > 1, "{:?}", artists);
+    assert!(artists.iter().all(|x| x.sort_name.is_none()));
+}
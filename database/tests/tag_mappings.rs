@@ -0,0 +1,99 @@
+use database::actions::index::{index_media_files, rebuild_search_index};
+use database::actions::search::{search_for, CollectionType};
+use database::actions::tag_mappings::{
+    get_custom_fields, get_custom_tags_of_file, get_tag_mappings, remove_tag_mapping,
+    set_tag_mapping,
+};
+use database::connection::SearchDbConnection;
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+fn found(search_db: &mut SearchDbConnection, query: &str) -> Vec<i64> {
+    search_for(search_db, query, 10)
+        .unwrap()
+        .remove(&CollectionType::Track)
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn mappings_are_validated_and_replaced() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    assert!(set_tag_mapping(&main_db, "custom1", "Mood!", false)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(set_tag_mapping(&main_db, " ", "mood", false)
+        .await
+        .unwrap()
+        .is_none());
+
+    let mapping = set_tag_mapping(&main_db, "custom1", "mood", false)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(mapping.tag_key, "CUSTOM1");
+
+    let replaced = set_tag_mapping(&main_db, "CUSTOM1", "feeling", true)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(replaced.id, mapping.id);
+    assert!(replaced.searchable);
+
+    let fields = get_custom_fields(&main_db).await.unwrap();
+    assert_eq!(fields.get("CUSTOM1").map(|x| x.as_str()), Some("feeling"));
+
+    assert!(remove_tag_mapping(&main_db, mapping.id).await.unwrap());
+    assert!(!remove_tag_mapping(&main_db, mapping.id).await.unwrap());
+    assert!(get_tag_mappings(&main_db).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn searchable_fields_find_tracks() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    set_tag_mapping(&main_db, "LABEL", "label", true)
+        .await
+        .unwrap();
+    set_tag_mapping(&main_db, "CUSTOM1", "mood", false)
+        .await
+        .unwrap();
+
+    let id = MediaFileFixture::new("airbag.flac")
+        .tags(&[
+            ("track_title", "Airbag"),
+            ("artist", "Radiohead"),
+            ("label", "Parlophone"),
+            ("mood", "Anxious"),
+        ])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    index_media_files(&main_db, &mut search_db, vec![id])
+        .await
+        .unwrap();
+
+    assert_eq!(found(&mut search_db, "parlophone"), vec![id as i64]);
+    assert!(found(&mut search_db, "anxious").is_empty());
+
+    let mut custom_tags = get_custom_tags_of_file(&main_db, id).await.unwrap();
+    custom_tags.sort();
+    assert_eq!(
+        custom_tags,
+        vec![
+            ("label".to_string(), "Parlophone".to_string()),
+            ("mood".to_string(), "Anxious".to_string()),
+        ]
+    );
+
+    // A rebuilt index keeps them searchable
+    let mut search_db = connect_search_db_in_memory().unwrap();
+    rebuild_search_index(&main_db, &mut search_db, 10)
+        .await
+        .unwrap();
+    assert_eq!(found(&mut search_db, "parlophone"), vec![id as i64]);
+}
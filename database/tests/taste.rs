@@ -0,0 +1,98 @@
+use chrono::{Duration, Utc};
+use sea_orm::{ActiveModelTrait, ActiveValue};
+
+use database::actions::play_history::log_play;
+use database::actions::ratings::set_ratings;
+use database::actions::taste::{
+    get_taste_profile, rebuild_taste_profile, update_taste_profile, TasteProfile,
+};
+use database::actions::users::DEFAULT_USER_ID;
+use database::connection::MainDbConnection;
+use database::entities::media_analysis;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+async fn insert_analysed_file(main_db: &MainDbConnection, name: &str, centroid: f64) -> i32 {
+    let file = MediaFileFixture::new(name).insert(main_db).await.unwrap();
+    media_analysis::ActiveModel {
+        file_id: ActiveValue::Set(file.id),
+        spectral_centroid: ActiveValue::Set(Some(centroid)),
+        ..Default::default()
+    }
+    .insert(main_db)
+    .await
+    .unwrap();
+    file.id
+}
+
+#[test]
+fn old_plays_fade_out_of_the_profile() {
+    let start = Utc::now();
+    let mut profile = TasteProfile {
+        vector: vec![0.],
+        weight: 0.,
+        updated_at: start,
+    };
+
+    profile.add(&[1.], 1., start);
+    assert_eq!(profile.vector, vec![1.]);
+
+    // A month later the first play counts half as much as a new one
+    profile.add(&[4.], 1., start + Duration::days(30));
+    assert!((profile.vector[0] - 3.).abs() < 1e-4);
+    assert!((profile.weight - 1.5).abs() < 1e-6);
+}
+
+#[tokio::test]
+async fn plays_and_ratings_shape_the_profile() {
+    let db = connect_main_db_in_memory().await.unwrap();
+    let low = insert_analysed_file(&db, "low.flac", 1.).await;
+    let high = insert_analysed_file(&db, "high.flac", 4.).await;
+    let unanalysed = MediaFileFixture::new("new.flac")
+        .insert(&db)
+        .await
+        .unwrap()
+        .id;
+
+    assert_eq!(get_taste_profile(&db, DEFAULT_USER_ID).await.unwrap(), None);
+    assert!(
+        !update_taste_profile(&db, &db, DEFAULT_USER_ID, unanalysed, 1.)
+            .await
+            .unwrap()
+    );
+    assert_eq!(get_taste_profile(&db, DEFAULT_USER_ID).await.unwrap(), None);
+
+    // A five star track counts twice
+    set_ratings(&db, DEFAULT_USER_ID, &[high], 5).await.unwrap();
+    assert!(update_taste_profile(&db, &db, DEFAULT_USER_ID, low, 1.)
+        .await
+        .unwrap());
+    assert!(update_taste_profile(&db, &db, DEFAULT_USER_ID, high, 1.)
+        .await
+        .unwrap());
+    let profile = get_taste_profile(&db, DEFAULT_USER_ID)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!((profile.vector[0] - 3.).abs() < 1e-3);
+    assert_eq!(profile.vector.len(), 17);
+
+    // The history gives the same profile
+    log_play(&db, DEFAULT_USER_ID, low, 1.).await.unwrap();
+    log_play(&db, DEFAULT_USER_ID, high, 1.).await.unwrap();
+    log_play(&db, DEFAULT_USER_ID, unanalysed, 1.)
+        .await
+        .unwrap();
+    let rebuilt = rebuild_taste_profile(&db, &db, DEFAULT_USER_ID)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!((rebuilt.vector[0] - 3.).abs() < 1e-3);
+    assert_eq!(
+        get_taste_profile(&db, DEFAULT_USER_ID)
+            .await
+            .unwrap()
+            .unwrap()
+            .vector,
+        rebuilt.vector
+    );
+}
@@ -0,0 +1,84 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use database::actions::throttle::{
+    analysis_pace, read_power_status_from, AnalysisPace, PowerStatus,
+};
+
+fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+#[test]
+fn power_state_is_read_from_sysfs() {
+    let dir = tempfile::tempdir().unwrap();
+    let class_dir = dir.path();
+
+    write(&class_dir.join("power_supply/AC/type"), "Mains\n");
+    write(&class_dir.join("power_supply/AC/online"), "0\n");
+    write(&class_dir.join("power_supply/BAT0/type"), "Battery\n");
+    write(&class_dir.join("power_supply/BAT0/status"), "Discharging\n");
+    write(&class_dir.join("thermal/thermal_zone0/temp"), "52000\n");
+    write(&class_dir.join("thermal/cooling_device0/cur_state"), "3\n");
+
+    assert_eq!(
+        read_power_status_from(class_dir),
+        PowerStatus {
+            on_battery: true,
+            overheated: false,
+        }
+    );
+
+    write(&class_dir.join("power_supply/BAT0/status"), "Charging\n");
+    write(&class_dir.join("thermal/thermal_zone1/temp"), "91000\n");
+
+    assert_eq!(
+        read_power_status_from(class_dir),
+        PowerStatus {
+            on_battery: false,
+            overheated: true,
+        }
+    );
+}
+
+#[test]
+fn missing_sysfs_means_no_pressure() {
+    let dir = tempfile::tempdir().unwrap();
+
+    assert_eq!(
+        read_power_status_from(&dir.path().join("missing")),
+        PowerStatus::default()
+    );
+}
+
+#[test]
+fn pace_slows_down_with_pressure() {
+    let idle = PowerStatus::default();
+    let on_battery = PowerStatus {
+        on_battery: true,
+        overheated: false,
+    };
+    let overheated = PowerStatus {
+        on_battery: true,
+        overheated: true,
+    };
+
+    assert_eq!(analysis_pace(8, false, idle), AnalysisPace::new(8));
+
+    let battery = analysis_pace(8, false, on_battery);
+    let background = analysis_pace(8, true, on_battery);
+    assert_eq!(battery.batch_size, 4);
+    assert!(background.batch_size < battery.batch_size);
+    assert!(background.pause > battery.pause);
+
+    assert_eq!(analysis_pace(1, true, on_battery).batch_size, 1);
+    assert_eq!(
+        analysis_pace(8, true, overheated),
+        AnalysisPace {
+            batch_size: 1,
+            pause: Duration::from_secs(10),
+        }
+    );
+}
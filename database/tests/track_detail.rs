@@ -0,0 +1,105 @@
+use sea_orm::{ActiveModelTrait, ActiveValue};
+
+use database::actions::playlists::{add_media_file_to_playlist, create_playlist, DuplicatePolicy};
+use database::actions::track_detail::get_track_detail;
+use database::actions::users::DEFAULT_USER_ID;
+use database::entities::user_logs;
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+#[tokio::test]
+async fn details_are_collected_in_one_call() {
+    let lib = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    write_sine_fixture(
+        &lib.path().join("tone.wav"),
+        FixtureFormat::Wav,
+        8000,
+        1,
+        8000,
+    )
+    .unwrap();
+    let file = MediaFileFixture::new("tone.wav")
+        .tags(&[("track_title", "Silence"), ("artist", "Nobody")])
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    for listen_time in ["2024-01-01T10:00:00Z", "2024-02-01T10:00:00Z"] {
+        user_logs::ActiveModel {
+            file_id: ActiveValue::Set(file.id),
+            listen_time: ActiveValue::Set(listen_time.to_string()),
+            progress: ActiveValue::Set(1.0),
+            ..Default::default()
+        }
+        .insert(&main_db)
+        .await
+        .unwrap();
+    }
+
+    let playlist = create_playlist(&main_db, &mut search_db, "Quiet".into(), "".into())
+        .await
+        .unwrap();
+    add_media_file_to_playlist(&main_db, playlist.id, file.id, DuplicatePolicy::Allow)
+        .await
+        .unwrap();
+
+    let detail = get_track_detail(&main_db, &main_db, DEFAULT_USER_ID, lib.path(), file.id)
+        .await
+        .unwrap();
+
+    assert_eq!(detail.path, lib.path().join("tone.wav"));
+    assert_eq!(detail.size, Some(44 + 16000));
+    assert_eq!(
+        detail.tags,
+        vec![
+            ("track_title".to_string(), "Silence".to_string()),
+            ("artist".to_string(), "Nobody".to_string()),
+        ]
+    );
+
+    let stream = detail.stream.unwrap();
+    assert_eq!(stream.codec, "pcm_s16le");
+    assert_eq!(stream.sample_rate, 8000);
+    assert_eq!(stream.channels, 1);
+    assert_eq!(stream.bits_per_sample, Some(16));
+    assert_eq!(stream.duration, 1.0);
+    assert_eq!(stream.bitrate, Some(8 * (44 + 16000)));
+
+    assert!(detail.analysis.is_none());
+    assert_eq!(detail.play_count, 2);
+    assert_eq!(detail.last_played.as_deref(), Some("2024-02-01T10:00:00Z"));
+    assert_eq!(
+        detail.playlists.iter().map(|x| x.id).collect::<Vec<_>>(),
+        vec![playlist.id]
+    );
+    assert_eq!(detail.cover_art_id, None);
+}
+
+#[tokio::test]
+async fn missing_files_still_have_details() {
+    let lib = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    let file = MediaFileFixture::new("gone.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let detail = get_track_detail(&main_db, &main_db, DEFAULT_USER_ID, lib.path(), file.id)
+        .await
+        .unwrap();
+    assert_eq!(detail.size, None);
+    assert!(detail.stream.is_none());
+    assert_eq!(detail.file.file_hash, "fixture:gone.flac");
+
+    assert!(
+        get_track_detail(&main_db, &main_db, DEFAULT_USER_ID, lib.path(), -1)
+            .await
+            .is_err()
+    );
+}
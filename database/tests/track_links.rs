@@ -0,0 +1,68 @@
+use sea_orm::EntityTrait;
+
+use database::actions::file::{get_track_links, TrackLinks};
+use database::actions::index::index_media_files;
+use database::connection::MainDbConnection;
+use database::entities::{albums, artists};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+
+async fn artist_id(main_db: &MainDbConnection, name: &str) -> i32 {
+    artists::Entity::find()
+        .all(main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|x| x.name == name)
+        .unwrap()
+        .id
+}
+
+#[tokio::test]
+async fn links_follow_the_order_of_the_tags() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut search_db = connect_search_db_in_memory().unwrap();
+
+    // The featured artist is known before the main artist of this track
+    let solo = MediaFileFixture::new("solo.flac")
+        .tags(&[("artist", "Guest"), ("album", "Other")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    let duet = MediaFileFixture::new("duet.flac")
+        .tags(&[("artist", "Singer feat. Guest"), ("album", "Duets")])
+        .insert(&main_db)
+        .await
+        .unwrap()
+        .id;
+    index_media_files(&main_db, &mut search_db, vec![solo, duet])
+        .await
+        .unwrap();
+
+    let album_id = albums::Entity::find()
+        .all(&main_db)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|x| x.name == "Duets")
+        .unwrap()
+        .id;
+
+    assert_eq!(
+        get_track_links(&main_db, duet).await.unwrap(),
+        TrackLinks {
+            album_ids: vec![album_id],
+            artist_ids: vec![
+                artist_id(&main_db, "Singer").await,
+                artist_id(&main_db, "Guest").await,
+            ],
+        }
+    );
+
+    assert_eq!(
+        get_track_links(&main_db, -1).await.unwrap(),
+        TrackLinks::default()
+    );
+}
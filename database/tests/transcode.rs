@@ -0,0 +1,87 @@
+use std::sync::Mutex;
+
+use database::actions::bulk::DeleteMode;
+use database::actions::file::get_file_by_id;
+use database::actions::transcode::{TranscodeJob, TranscodeQueue, TranscodeTarget};
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+#[tokio::test]
+async fn queued_files_are_transcoded_in_order() {
+    let lib = tempfile::tempdir().unwrap();
+    let export = tempfile::tempdir().unwrap();
+    let db = connect_main_db_in_memory().await.unwrap();
+
+    std::fs::create_dir(lib.path().join("album")).unwrap();
+    let mut file_ids = Vec::new();
+    for name in ["a.flac", "b.flac", "c.flac"] {
+        write_sine_fixture(
+            &lib.path().join("album").join(name),
+            FixtureFormat::Flac,
+            8000,
+            1,
+            8000,
+        )
+        .unwrap();
+        let file = MediaFileFixture::new(name)
+            .directory("album")
+            .insert(&db)
+            .await
+            .unwrap();
+        file_ids.push(file.id);
+    }
+
+    let queue = TranscodeQueue::default();
+    queue.push(vec![
+        TranscodeJob {
+            file_id: file_ids[0],
+            codec: "wav".into(),
+            target: TranscodeTarget::Folder(export.path().to_path_buf()),
+        },
+        TranscodeJob {
+            file_id: file_ids[1],
+            codec: "wav".into(),
+            target: TranscodeTarget::Replace,
+        },
+        TranscodeJob {
+            file_id: file_ids[2],
+            codec: "mp3".into(),
+            target: TranscodeTarget::Replace,
+        },
+    ]);
+    assert_eq!(queue.len(), 3);
+
+    let progress = Mutex::new(Vec::new());
+    let report = queue
+        .run(&db, lib.path(), DeleteMode::Permanent, |done, total| {
+            progress.lock().unwrap().push((done, total))
+        })
+        .await
+        .unwrap();
+    assert_eq!(report.succeeded, vec![file_ids[0], file_ids[1]]);
+    assert_eq!(report.failed, vec![file_ids[2]]);
+    assert_eq!(progress.lock().unwrap().last(), Some(&(3, 3)));
+    assert!(!queue.is_running());
+
+    // Exported files keep their directory, the library is left alone
+    assert!(export.path().join("album/a.wav").exists());
+    assert!(lib.path().join("album/a.flac").exists());
+
+    // Replaced files keep their ID
+    let replaced = get_file_by_id(&db, file_ids[1]).await.unwrap().unwrap();
+    assert_eq!(
+        (replaced.file_name.as_str(), replaced.extension.as_str()),
+        ("b.wav", "wav")
+    );
+    assert_ne!(replaced.file_hash, "fixture:b.flac");
+    assert!(lib.path().join("album/b.wav").exists());
+    assert!(!lib.path().join("album/b.flac").exists());
+
+    queue.push(vec![TranscodeJob {
+        file_id: file_ids[2],
+        codec: "wav".into(),
+        target: TranscodeTarget::Replace,
+    }]);
+    assert_eq!(queue.cancel(), vec![file_ids[2]]);
+    assert!(queue.is_empty());
+}
@@ -0,0 +1,128 @@
+use database::actions::pinned::{get_pinned_collections, pin_collection};
+use database::actions::play_history::{get_recently_played, log_play};
+use database::actions::playback_queue::{get_playback_queue, save_playback_queue};
+use database::actions::ratings::{get_ratings, set_ratings};
+use database::actions::search::CollectionType;
+use database::actions::users::{
+    create_user, get_active_user_id, get_users, remove_user, set_active_user_id, DEFAULT_USER_ID,
+};
+use database::test_support::{connect_main_db_in_memory, insert_media_files};
+
+#[tokio::test]
+async fn profiles_keep_their_own_history() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let ids: Vec<i32> = insert_media_files(&main_db, 2)
+        .await
+        .unwrap()
+        .iter()
+        .map(|x| x.id)
+        .collect();
+    let guest = create_user(&main_db, " Guest ").await.unwrap();
+    assert_eq!(guest.name, "Guest");
+    assert!(create_user(&main_db, "Guest").await.is_err());
+
+    log_play(&main_db, DEFAULT_USER_ID, ids[0], 1.)
+        .await
+        .unwrap();
+    log_play(&main_db, guest.id, ids[1], 1.).await.unwrap();
+    assert_eq!(
+        get_recently_played(&main_db, DEFAULT_USER_ID, 10)
+            .await
+            .unwrap(),
+        vec![ids[0]]
+    );
+    assert_eq!(
+        get_recently_played(&main_db, guest.id, 10).await.unwrap(),
+        vec![ids[1]]
+    );
+
+    // The same file is rated once per profile
+    set_ratings(&main_db, DEFAULT_USER_ID, &ids[..1], 5)
+        .await
+        .unwrap();
+    set_ratings(&main_db, guest.id, &ids[..1], 2).await.unwrap();
+    assert_eq!(
+        get_ratings(&main_db, DEFAULT_USER_ID, &ids[..1])
+            .await
+            .unwrap()
+            .get(&ids[0]),
+        Some(&5)
+    );
+    assert_eq!(
+        get_ratings(&main_db, guest.id, &ids[..1])
+            .await
+            .unwrap()
+            .get(&ids[0]),
+        Some(&2)
+    );
+
+    pin_collection(&main_db, guest.id, CollectionType::Album, 1)
+        .await
+        .unwrap();
+    assert!(get_pinned_collections(&main_db, DEFAULT_USER_ID)
+        .await
+        .unwrap()
+        .is_empty());
+
+    save_playback_queue(&main_db, DEFAULT_USER_ID, &ids, Some(1), 12.)
+        .await
+        .unwrap();
+    save_playback_queue(&main_db, guest.id, &ids[..1], Some(0), 3.)
+        .await
+        .unwrap();
+    let queue = get_playback_queue(&main_db, DEFAULT_USER_ID).await.unwrap();
+    assert_eq!(queue.file_ids, ids);
+    assert_eq!(queue.index, Some(1));
+    assert_eq!(queue.position, 12.);
+    let queue = get_playback_queue(&main_db, guest.id).await.unwrap();
+    assert_eq!(queue.file_ids, ids[..1]);
+    assert_eq!(queue.position, 3.);
+}
+
+#[tokio::test]
+async fn removed_profiles_leave_nothing_behind() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let ids: Vec<i32> = insert_media_files(&main_db, 1)
+        .await
+        .unwrap()
+        .iter()
+        .map(|x| x.id)
+        .collect();
+    let guest = create_user(&main_db, "Guest").await.unwrap();
+
+    assert_eq!(get_active_user_id(&main_db).await.unwrap(), DEFAULT_USER_ID);
+    assert!(!set_active_user_id(&main_db, 42).await.unwrap());
+    assert!(set_active_user_id(&main_db, guest.id).await.unwrap());
+    assert_eq!(get_active_user_id(&main_db).await.unwrap(), guest.id);
+
+    log_play(&main_db, guest.id, ids[0], 1.).await.unwrap();
+    set_ratings(&main_db, guest.id, &ids, 4).await.unwrap();
+    save_playback_queue(&main_db, guest.id, &ids, Some(0), 5.)
+        .await
+        .unwrap();
+
+    assert!(!remove_user(&main_db, DEFAULT_USER_ID).await.unwrap());
+    assert!(remove_user(&main_db, guest.id).await.unwrap());
+    assert_eq!(
+        get_users(&main_db)
+            .await
+            .unwrap()
+            .iter()
+            .map(|x| x.id)
+            .collect::<Vec<_>>(),
+        vec![DEFAULT_USER_ID]
+    );
+    assert_eq!(get_active_user_id(&main_db).await.unwrap(), DEFAULT_USER_ID);
+
+    assert!(get_recently_played(&main_db, guest.id, 10)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(get_ratings(&main_db, guest.id, &ids)
+        .await
+        .unwrap()
+        .is_empty());
+    let queue = get_playback_queue(&main_db, guest.id).await.unwrap();
+    assert!(queue.file_ids.is_empty());
+    assert_eq!(queue.index, None);
+}
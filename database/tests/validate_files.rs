@@ -0,0 +1,43 @@
+use std::fs;
+
+use database::actions::file::{validate_files, FileValidation};
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+#[tokio::test]
+async fn every_track_gets_a_status() {
+    let lib = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    write_sine_fixture(
+        &lib.path().join("good.wav"),
+        FixtureFormat::Wav,
+        8000,
+        1,
+        8000,
+    )
+    .unwrap();
+    fs::write(lib.path().join("broken.mp3"), b"definitely not audio").unwrap();
+
+    let good = MediaFileFixture::new("good.wav")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let broken = MediaFileFixture::new("broken.mp3")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let missing = MediaFileFixture::new("missing.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let ids = [missing.id, good.id, -1, broken.id];
+    let result = validate_files(&main_db, lib.path(), &ids).await.unwrap();
+
+    assert_eq!(result.len(), ids.len());
+    assert_eq!(result[0], (missing.id, FileValidation::Missing));
+    assert_eq!(result[1], (good.id, FileValidation::Ok));
+    assert_eq!(result[2], (-1, FileValidation::NotInLibrary));
+    assert!(matches!(result[3], (id, FileValidation::Undecodable(_)) if id == broken.id));
+}
@@ -0,0 +1,91 @@
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use sea_orm::{ActiveModelTrait, ActiveValue};
+
+use database::actions::verification::{
+    get_verification_failures, verify_library, VERIFIED_TRUNCATED, VERIFIED_UNREADABLE,
+};
+use database::entities::media_files;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+#[tokio::test]
+async fn damaged_files_fail_verification() {
+    let lib = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    for name in ["good.flac", "cut.flac"] {
+        write_sine_fixture(&lib.path().join(name), FixtureFormat::Flac, 8000, 1, 16000).unwrap();
+    }
+    let cut_path = lib.path().join("cut.flac");
+    let bytes = fs::read(&cut_path).unwrap();
+    fs::write(&cut_path, &bytes[..bytes.len() / 2]).unwrap();
+
+    MediaFileFixture::new("good.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let cut = MediaFileFixture::new("cut.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let missing = MediaFileFixture::new("missing.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let reported = progress.clone();
+    let failures = verify_library(
+        &main_db,
+        lib.path(),
+        move |done, total| reported.lock().unwrap().push((done, total)),
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(*progress.lock().unwrap(), vec![(3, 3)]);
+    let statuses: Vec<(i32, &str)> = failures
+        .iter()
+        .map(|x| (x.file_id, x.status.as_str()))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            (cut.id, VERIFIED_TRUNCATED),
+            (missing.id, VERIFIED_UNREADABLE)
+        ]
+    );
+    assert_eq!(failures[0].file_name, "cut.flac");
+    assert!(failures[0].detail.is_some());
+
+    // Verified files are skipped until their content changes, unreadable
+    // ones are tried again
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let reported = progress.clone();
+    verify_library(
+        &main_db,
+        lib.path(),
+        move |done, total| reported.lock().unwrap().push((done, total)),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(*progress.lock().unwrap(), vec![(3, 3)]);
+
+    write_sine_fixture(&cut_path, FixtureFormat::Flac, 8000, 1, 16000).unwrap();
+    let mut active_model: media_files::ActiveModel = cut.into();
+    active_model.file_hash = ActiveValue::Set("repaired".to_string());
+    active_model.update(&main_db).await.unwrap();
+
+    let failures = verify_library(&main_db, lib.path(), |_, _| {}, None)
+        .await
+        .unwrap();
+    assert_eq!(failures, get_verification_failures(&main_db).await.unwrap());
+    assert_eq!(
+        failures.iter().map(|x| x.file_id).collect::<Vec<_>>(),
+        vec![missing.id]
+    );
+}
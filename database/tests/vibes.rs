@@ -0,0 +1,111 @@
+use sea_orm::{ActiveModelTrait, ActiveValue};
+
+use database::actions::file::{compound_query_media_files, MediaFileFilter};
+use database::actions::vibes::{
+    filter_by_features, get_vibe_ranges, AnalysisFeature, FeatureRange, Vibe,
+};
+use database::connection::MainDbConnection;
+use database::entities::media_analysis;
+use database::test_support::{connect_main_db_in_memory, MediaFileFixture};
+
+async fn insert_analysed(main_db: &MainDbConnection, centroid: f64, flatness: f64) -> i32 {
+    let file = MediaFileFixture::new(&format!("{}-{}.flac", centroid, flatness))
+        .insert(main_db)
+        .await
+        .unwrap();
+    media_analysis::ActiveModel {
+        file_id: ActiveValue::Set(file.id),
+        spectral_centroid: ActiveValue::Set(Some(centroid)),
+        spectral_flatness: ActiveValue::Set(Some(flatness)),
+        ..Default::default()
+    }
+    .insert(main_db)
+    .await
+    .unwrap();
+
+    file.id
+}
+
+async fn listed(main_db: &MainDbConnection, ranges: &[FeatureRange]) -> Vec<i32> {
+    let filter = MediaFileFilter {
+        feature_ranges: ranges.to_vec(),
+        ..Default::default()
+    };
+
+    compound_query_media_files(main_db, filter, 0, 100)
+        .await
+        .unwrap()
+        .iter()
+        .map(|x| x.id)
+        .collect()
+}
+
+#[test]
+fn vibes_parse_by_name() {
+    assert_eq!(Vibe::parse("bright"), Some(Vibe::Bright));
+    assert_eq!(Vibe::parse(" Electronic "), Some(Vibe::Electronic));
+    assert_eq!(Vibe::parse("groovy"), None);
+}
+
+#[tokio::test]
+async fn feature_ranges_narrow_the_track_list() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let dark = insert_analysed(&main_db, 0.05, 0.3).await;
+    let middle = insert_analysed(&main_db, 0.1, 0.2).await;
+    let bright = insert_analysed(&main_db, 0.2, 0.1).await;
+    let unanalysed = MediaFileFixture::new("unanalysed.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        listed(&main_db, &[]).await,
+        vec![dark, middle, bright, unanalysed.id]
+    );
+
+    let range = FeatureRange {
+        feature: AnalysisFeature::SpectralCentroid,
+        min: Some(0.08),
+        max: None,
+    };
+    assert_eq!(listed(&main_db, &[range]).await, vec![middle, bright]);
+
+    let flat = FeatureRange {
+        feature: AnalysisFeature::SpectralFlatness,
+        min: None,
+        max: Some(0.15),
+    };
+    assert_eq!(listed(&main_db, &[range, flat]).await, vec![bright]);
+}
+
+#[tokio::test]
+async fn vibes_take_a_third_of_the_library() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let mut ids = Vec::new();
+    for i in 1..=7 {
+        ids.push(insert_analysed(&main_db, i as f64 / 10.0, 0.8 - i as f64 / 10.0).await);
+    }
+
+    let bright = get_vibe_ranges(&main_db, &[Vibe::Bright]).await.unwrap();
+    assert_eq!(listed(&main_db, &bright).await, ids[4..].to_vec());
+
+    let dark = get_vibe_ranges(&main_db, &[Vibe::Dark]).await.unwrap();
+    assert_eq!(listed(&main_db, &dark).await, ids[..3].to_vec());
+
+    // The brightest tracks are the least flat here
+    let both = get_vibe_ranges(&main_db, &[Vibe::Bright, Vibe::Acoustic])
+        .await
+        .unwrap();
+    assert_eq!(listed(&main_db, &both).await, ids[4..].to_vec());
+    let neither = get_vibe_ranges(&main_db, &[Vibe::Bright, Vibe::Electronic])
+        .await
+        .unwrap();
+    assert!(listed(&main_db, &neither).await.is_empty());
+
+    // Search results keep their order
+    let found: Vec<i32> = ids.iter().rev().copied().collect();
+    assert_eq!(
+        filter_by_features(&main_db, &bright, found).await.unwrap(),
+        vec![ids[6], ids[5], ids[4]]
+    );
+}
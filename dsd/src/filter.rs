@@ -0,0 +1,140 @@
+use std::f64::consts::PI;
+
+/// An idle pattern with as many ones as zeros, DSD's silence.
+pub(crate) const SILENCE: u8 = 0x69;
+
+/// Sample rate of DSD64, the base of the 44.1 kHz family.
+pub(crate) const DSD64_RATE: u32 = 2_822_400;
+/// Sample rate of DSD64 in the 48 kHz family.
+pub(crate) const DSD64_RATE_48K: u32 = 3_072_000;
+
+// Above 30 kHz DSD is mostly shaped noise
+const CUTOFF: f64 = 30_000.0;
+// Filter length for DSD64, scaled with the rate so the transition band
+// stays below the Nyquist frequency of the output
+const TAPS_DSD64: usize = 512;
+// DSD samples per PCM sample for DSD64, gives 176.4 kHz
+const DECIMATION_DSD64: usize = 16;
+
+/// Multiple of DSD64 of a supported rate, DSD64, DSD128 and DSD256.
+pub(crate) fn rate_multiple(dsd_rate: u32) -> Option<usize> {
+    [1, 2, 4].into_iter().find(|&multiple| {
+        dsd_rate == DSD64_RATE * multiple as u32 || dsd_rate == DSD64_RATE_48K * multiple as u32
+    })
+}
+
+/// Sample rate of the PCM a DSD stream is converted to.
+pub(crate) fn pcm_rate(dsd_rate: u32, multiple: usize) -> u32 {
+    dsd_rate / (DECIMATION_DSD64 * multiple) as u32
+}
+
+/// DSD bytes consumed for every PCM sample.
+pub(crate) fn bytes_per_sample(multiple: usize) -> usize {
+    DECIMATION_DSD64 * multiple / 8
+}
+
+fn low_pass(taps: usize, dsd_rate: u32) -> Vec<f64> {
+    let cutoff = CUTOFF / dsd_rate as f64;
+    let center = (taps - 1) as f64 / 2.0;
+
+    let coefficients: Vec<f64> = (0..taps)
+        .map(|n| {
+            let x = n as f64 - center;
+            let sinc = if x == 0.0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * PI * cutoff * x).sin() / (PI * x)
+            };
+            let phase = 2.0 * PI * n as f64 / (taps - 1) as f64;
+            let blackman = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+            sinc * blackman
+        })
+        .collect();
+
+    let sum: f64 = coefficients.iter().sum();
+    coefficients.into_iter().map(|x| x / sum).collect()
+}
+
+/// A low-pass FIR filter and decimator for one channel of DSD.
+///
+/// Every byte holds 8 samples of +1 or -1, so the filter output of a byte
+/// at a given position in the history is looked up instead of multiplied.
+pub(crate) struct Decimator {
+    // Contribution of every byte value, one table per position, oldest first
+    tables: Vec<[f32; 256]>,
+    // Ring buffer of the last bytes, `position` is the oldest one
+    history: Vec<u8>,
+    position: usize,
+    step: usize,
+    pending: usize,
+}
+
+impl Decimator {
+    /// # Arguments
+    /// * `dsd_rate` - DSD samples per second.
+    /// * `multiple` - Multiple of DSD64 of the rate, see `rate_multiple`.
+    pub fn new(dsd_rate: u32, multiple: usize) -> Self {
+        let coefficients = low_pass(TAPS_DSD64 * multiple, dsd_rate);
+
+        let tables: Vec<[f32; 256]> = coefficients
+            .chunks(8)
+            .map(|taps| {
+                let mut table = [0.0f32; 256];
+                for (byte, value) in table.iter_mut().enumerate() {
+                    // The most significant bit is the oldest sample
+                    *value = taps
+                        .iter()
+                        .enumerate()
+                        .map(|(bit, tap)| {
+                            if byte & (0x80 >> bit) != 0 {
+                                *tap
+                            } else {
+                                -tap
+                            }
+                        })
+                        .sum::<f64>() as f32;
+                }
+                table
+            })
+            .collect();
+
+        let history = vec![SILENCE; tables.len()];
+        Decimator {
+            tables,
+            history,
+            position: 0,
+            step: bytes_per_sample(multiple),
+            pending: 0,
+        }
+    }
+
+    /// Feed one byte, most significant bit first, returns a PCM sample
+    /// once enough bytes were read.
+    pub fn push(&mut self, byte: u8) -> Option<f32> {
+        self.history[self.position] = byte;
+        self.position = (self.position + 1) % self.history.len();
+
+        self.pending += 1;
+        if self.pending < self.step {
+            return None;
+        }
+        self.pending = 0;
+
+        let (newer, older) = self.history.split_at(self.position);
+        Some(
+            older
+                .iter()
+                .chain(newer)
+                .zip(&self.tables)
+                .map(|(&byte, table)| table[byte as usize])
+                .sum(),
+        )
+    }
+
+    /// Forget the history, after seeking.
+    pub fn reset(&mut self) {
+        self.history.fill(SILENCE);
+        self.position = 0;
+        self.pending = 0;
+    }
+}
@@ -0,0 +1,12 @@
+//! Playback of 1-bit DSD audio from DSF and DSDIFF files.
+//!
+//! Neither symphonia nor rodio decode DSD, so the bitstream is low-pass
+//! filtered and decimated to high-rate PCM while reading, 176.4 kHz for
+//! DSD64 and its multiples.
+
+mod filter;
+mod reader;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+
+pub use reader::{is_dsd_path, DsdError, DsdInfo, DsdReader, DsdTags, EXTENSIONS};
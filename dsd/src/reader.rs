@@ -0,0 +1,487 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::filter::{bytes_per_sample, pcm_rate, rate_multiple, Decimator};
+
+/// Extensions of DSD files, DSF and DSDIFF.
+pub const EXTENSIONS: [&str; 2] = ["dsf", "dff"];
+
+// Tags larger than this are rather a broken pointer than metadata
+const MAX_TAG_SIZE: u64 = 16 * 1024 * 1024;
+// DSDIFF interleaves single bytes, read this many per channel at once
+const DFF_GROUP_SIZE: usize = 4096;
+// Both formats define up to 6 channels and DSF blocks of 4096 bytes
+const MAX_CHANNELS: u16 = 6;
+const MAX_DSF_BLOCK_SIZE: usize = 4096;
+
+pub fn is_dsd_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| EXTENSIONS.contains(&x.to_lowercase().as_str()))
+}
+
+#[derive(Error, Debug)]
+pub enum DsdError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid DSD file: {0}")]
+    Invalid(&'static str),
+    #[error("unsupported DSD file: {0}")]
+    Unsupported(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DsdInfo {
+    /// DSD samples per second, 2822400 for DSD64.
+    pub dsd_rate: u32,
+    /// Sample rate of the converted PCM.
+    pub pcm_rate: u32,
+    pub channels: u16,
+    /// DSD samples per channel.
+    pub dsd_frames: u64,
+}
+
+impl DsdInfo {
+    pub fn pcm_frames(&self) -> u64 {
+        self.dsd_frames.saturating_mul(self.pcm_rate as u64) / self.dsd_rate as u64
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.dsd_frames as f64 / self.dsd_rate as f64)
+    }
+}
+
+/// Metadata stored next to the audio.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DsdTags {
+    /// A complete ID3v2 tag, DSF keeps one at the end of the file and many
+    /// DSDIFF writers add an `ID3 ` chunk.
+    pub id3: Option<Vec<u8>>,
+    /// Title from the DSDIFF edited master information.
+    pub title: Option<String>,
+    /// Artist from the DSDIFF edited master information.
+    pub artist: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    // Channels in alternating blocks, the bits of a byte may be reversed
+    Dsf { lsb_first: bool },
+    // Channels interleaved byte by byte, most significant bit first
+    Dff,
+}
+
+/// Reads a DSF or DSDIFF stream as interleaved PCM samples in [-1, 1].
+pub struct DsdReader<R> {
+    reader: R,
+    info: DsdInfo,
+    tags: DsdTags,
+    layout: Layout,
+    data_start: u64,
+    // Bytes per channel in one group, a DSF block or a run of DSDIFF bytes
+    group_size: usize,
+    // Bytes per channel in the whole stream and left to read from disk
+    total_bytes: u64,
+    remaining: u64,
+    group: Vec<u8>,
+    group_len: usize,
+    group_position: usize,
+    decimators: Vec<Decimator>,
+    output: Vec<f32>,
+    output_position: usize,
+}
+
+fn read_id<R: Read>(reader: &mut R) -> io::Result<[u8; 4]> {
+    let mut id = [0u8; 4];
+    reader.read_exact(&mut id)?;
+    Ok(id)
+}
+
+fn read_u16_be<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0u8; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R, big_endian: bool) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+fn read_u64<R: Read>(reader: &mut R, big_endian: bool) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(if big_endian {
+        u64::from_be_bytes(bytes)
+    } else {
+        u64::from_le_bytes(bytes)
+    })
+}
+
+fn read_tag<R: Read>(reader: &mut R, size: u64) -> Result<Vec<u8>, DsdError> {
+    if size > MAX_TAG_SIZE {
+        return Err(DsdError::Invalid("metadata chunk too large"));
+    }
+    let mut tag = Vec::with_capacity(size as usize);
+    reader.take(size).read_to_end(&mut tag)?;
+    Ok(tag)
+}
+
+// Read as much as possible, short only at the end of the stream
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+struct Header {
+    dsd_rate: u32,
+    channels: u16,
+    dsd_frames: u64,
+    layout: Layout,
+    data_start: u64,
+    group_size: usize,
+    tags: DsdTags,
+}
+
+fn read_dsf<R: Read + Seek>(reader: &mut R) -> Result<Header, DsdError> {
+    // The rest of the `DSD ` chunk, its size, the file size and where the tag is
+    let dsd_chunk_size = read_u64(reader, false)?;
+    let _file_size = read_u64(reader, false)?;
+    let metadata_offset = read_u64(reader, false)?;
+
+    reader.seek(SeekFrom::Start(dsd_chunk_size))?;
+    if &read_id(reader)? != b"fmt " {
+        return Err(DsdError::Invalid("missing fmt chunk"));
+    }
+    let fmt_size = read_u64(reader, false)?;
+    let _version = read_u32(reader, false)?;
+    if read_u32(reader, false)? != 0 {
+        return Err(DsdError::Unsupported("DSF format other than raw DSD"));
+    }
+    let _channel_type = read_u32(reader, false)?;
+    let channels = read_u32(reader, false)?;
+    let dsd_rate = read_u32(reader, false)?;
+    let lsb_first = match read_u32(reader, false)? {
+        1 => true,
+        8 => false,
+        _ => return Err(DsdError::Invalid("bits per sample must be 1 or 8")),
+    };
+    let dsd_frames = read_u64(reader, false)?;
+    let block_size = read_u32(reader, false)? as usize;
+    if block_size == 0 || block_size > MAX_DSF_BLOCK_SIZE {
+        return Err(DsdError::Invalid("block size must be 1 to 4096 bytes"));
+    }
+
+    let data_chunk = dsd_chunk_size
+        .checked_add(fmt_size)
+        .ok_or(DsdError::Invalid("fmt chunk size overflows"))?;
+    reader.seek(SeekFrom::Start(data_chunk))?;
+    if &read_id(reader)? != b"data" {
+        return Err(DsdError::Invalid("missing data chunk"));
+    }
+    let _data_size = read_u64(reader, false)?;
+    let data_start = reader.stream_position()?;
+
+    let mut tags = DsdTags::default();
+    if metadata_offset != 0 {
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(metadata_offset))?;
+        tags.id3 = Some(read_tag(reader, end.saturating_sub(metadata_offset))?);
+    }
+
+    Ok(Header {
+        dsd_rate,
+        channels: u16::try_from(channels).map_err(|_| DsdError::Invalid("too many channels"))?,
+        dsd_frames,
+        layout: Layout::Dsf { lsb_first },
+        data_start,
+        group_size: block_size,
+        tags,
+    })
+}
+
+fn read_diin_text<R: Read>(reader: &mut R, size: u64) -> Result<String, DsdError> {
+    let count = read_u32(reader, true)? as u64;
+    let text = read_tag(reader, count.min(size.saturating_sub(4)))?;
+    Ok(String::from_utf8_lossy(&text)
+        .trim_end_matches('\0')
+        .to_string())
+}
+
+// Where the chunk after one starting at `start` begins, chunks are padded to
+// an even size
+fn chunk_end(start: u64, size: u64) -> Result<u64, DsdError> {
+    start
+        .checked_add(size)
+        .and_then(|x| x.checked_add(size & 1))
+        .ok_or(DsdError::Invalid("chunk size overflows"))
+}
+
+fn read_dff<R: Read + Seek>(reader: &mut R) -> Result<Header, DsdError> {
+    let form_size = read_u64(reader, true)?;
+    if &read_id(reader)? != b"DSD " {
+        return Err(DsdError::Invalid("FRM8 is not a DSD form"));
+    }
+    let form_end = form_size
+        .checked_add(12)
+        .ok_or(DsdError::Invalid("FRM8 size overflows"))?;
+
+    let mut dsd_rate = None;
+    let mut channels = None;
+    let mut data = None;
+    let mut tags = DsdTags::default();
+
+    let mut position = reader.stream_position()?;
+    while form_end.saturating_sub(position) >= 12 {
+        reader.seek(SeekFrom::Start(position))?;
+        let id = match read_id(reader) {
+            Ok(id) => id,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        let size = read_u64(reader, true)?;
+        let start = position + 12;
+        let end = chunk_end(start, size)?;
+
+        match &id {
+            b"PROP" => {
+                if &read_id(reader)? != b"SND " {
+                    return Err(DsdError::Invalid("PROP is not a sound property chunk"));
+                }
+                let mut local = start + 4;
+                while (start + size).saturating_sub(local) >= 12 {
+                    reader.seek(SeekFrom::Start(local))?;
+                    let local_id = read_id(reader)?;
+                    let local_size = read_u64(reader, true)?;
+                    match &local_id {
+                        b"FS  " => dsd_rate = Some(read_u32(reader, true)?),
+                        b"CHNL" => channels = Some(read_u16_be(reader)?),
+                        b"CMPR" if &read_id(reader)? != b"DSD " => {
+                            return Err(DsdError::Unsupported("DST compressed DSDIFF"));
+                        }
+                        _ => {}
+                    }
+                    local = chunk_end(local + 12, local_size)?;
+                }
+            }
+            b"DSD " => data = Some((start, size)),
+            b"DST " => return Err(DsdError::Unsupported("DST compressed DSDIFF")),
+            b"DIIN" => {
+                let mut local = start;
+                while (start + size).saturating_sub(local) >= 12 {
+                    reader.seek(SeekFrom::Start(local))?;
+                    let local_id = read_id(reader)?;
+                    let local_size = read_u64(reader, true)?;
+                    match &local_id {
+                        b"DITI" => tags.title = Some(read_diin_text(reader, local_size)?),
+                        b"DIAR" => tags.artist = Some(read_diin_text(reader, local_size)?),
+                        _ => {}
+                    }
+                    local = chunk_end(local + 12, local_size)?;
+                }
+            }
+            b"ID3 " => tags.id3 = Some(read_tag(reader, size)?),
+            _ => {}
+        }
+
+        position = end;
+    }
+
+    let dsd_rate = dsd_rate.ok_or(DsdError::Invalid("missing sample rate"))?;
+    let channels = channels.ok_or(DsdError::Invalid("missing channel count"))?;
+    let (data_start, data_size) = data.ok_or(DsdError::Invalid("missing DSD chunk"))?;
+    if channels == 0 {
+        return Err(DsdError::Invalid("no channels"));
+    }
+    let dsd_frames = (data_size / channels as u64)
+        .checked_mul(8)
+        .ok_or(DsdError::Invalid("DSD chunk size overflows"))?;
+
+    Ok(Header {
+        dsd_rate,
+        channels,
+        dsd_frames,
+        layout: Layout::Dff,
+        data_start,
+        group_size: DFF_GROUP_SIZE,
+        tags,
+    })
+}
+
+impl<R: Read + Seek> DsdReader<R> {
+    /// Read the header of a DSF or DSDIFF stream, told apart by their magic.
+    pub fn new(mut reader: R) -> Result<Self, DsdError> {
+        reader.seek(SeekFrom::Start(0))?;
+        let header = match &read_id(&mut reader)? {
+            b"DSD " => read_dsf(&mut reader)?,
+            b"FRM8" => read_dff(&mut reader)?,
+            _ => return Err(DsdError::Invalid("neither DSF nor DSDIFF")),
+        };
+
+        if header.channels == 0 || header.channels > MAX_CHANNELS {
+            return Err(DsdError::Invalid("channel count must be 1 to 6"));
+        }
+        let multiple = rate_multiple(header.dsd_rate).ok_or(DsdError::Unsupported(
+            "only DSD64, DSD128 and DSD256 are supported",
+        ))?;
+        let pcm_rate = pcm_rate(header.dsd_rate, multiple);
+        if header.dsd_frames.checked_mul(pcm_rate as u64).is_none() {
+            return Err(DsdError::Invalid("sample count overflows"));
+        }
+
+        let info = DsdInfo {
+            dsd_rate: header.dsd_rate,
+            pcm_rate,
+            channels: header.channels,
+            dsd_frames: header.dsd_frames,
+        };
+        let total_bytes = header.dsd_frames.div_ceil(8);
+
+        reader.seek(SeekFrom::Start(header.data_start))?;
+        Ok(DsdReader {
+            reader,
+            info,
+            tags: header.tags,
+            layout: header.layout,
+            data_start: header.data_start,
+            group_size: header.group_size,
+            total_bytes,
+            remaining: total_bytes,
+            group: vec![0; header.group_size * header.channels as usize],
+            group_len: 0,
+            group_position: 0,
+            decimators: (0..header.channels)
+                .map(|_| Decimator::new(header.dsd_rate, multiple))
+                .collect(),
+            output: Vec::new(),
+            output_position: 0,
+        })
+    }
+
+    pub fn info(&self) -> DsdInfo {
+        self.info
+    }
+
+    pub fn tags(&self) -> &DsdTags {
+        &self.tags
+    }
+
+    /// Continue converting at the given PCM frame.
+    pub fn seek(&mut self, pcm_frame: u64) -> Result<(), DsdError> {
+        let multiple = rate_multiple(self.info.dsd_rate).unwrap_or(1);
+        let byte = pcm_frame
+            .saturating_mul(bytes_per_sample(multiple) as u64)
+            .min(self.total_bytes);
+        let group = byte / self.group_size as u64;
+        let group_start = group * self.group_size as u64;
+
+        self.reader.seek(SeekFrom::Start(
+            self.data_start + group_start * self.info.channels as u64,
+        ))?;
+        self.remaining = self.total_bytes - group_start;
+        self.group_len = 0;
+        self.group_position = 0;
+        self.output.clear();
+        self.output_position = 0;
+        self.decimators.iter_mut().for_each(Decimator::reset);
+
+        if self.read_group()? {
+            self.group_position = (byte - group_start) as usize;
+        }
+        Ok(())
+    }
+
+    // Load the next group from disk, false at the end of the stream
+    fn read_group(&mut self) -> io::Result<bool> {
+        if self.remaining == 0 {
+            return Ok(false);
+        }
+
+        let channels = self.info.channels as usize;
+        let read = read_full(&mut self.reader, &mut self.group)?;
+        let valid = match self.layout {
+            // Channel blocks follow each other, only a complete group has all channels
+            Layout::Dsf { .. } if read < self.group.len() => 0,
+            Layout::Dsf { .. } => self.group_size,
+            Layout::Dff => read / channels,
+        };
+        let valid = valid.min(self.remaining as usize);
+
+        self.remaining = if valid == 0 {
+            0
+        } else {
+            self.remaining - valid as u64
+        };
+        self.group_len = valid;
+        self.group_position = 0;
+        Ok(valid > 0)
+    }
+
+    fn byte(&self, channel: usize, index: usize) -> u8 {
+        match self.layout {
+            Layout::Dsf { lsb_first } => {
+                let byte = self.group[channel * self.group_size + index];
+                if lsb_first {
+                    byte.reverse_bits()
+                } else {
+                    byte
+                }
+            }
+            Layout::Dff => self.group[index * self.info.channels as usize + channel],
+        }
+    }
+
+    // Convert the rest of the current group, false at the end of the stream
+    fn convert(&mut self) -> io::Result<bool> {
+        self.output.clear();
+        self.output_position = 0;
+
+        while self.output.is_empty() {
+            if self.group_position >= self.group_len && !self.read_group()? {
+                return Ok(false);
+            }
+
+            for index in self.group_position..self.group_len {
+                for channel in 0..self.info.channels as usize {
+                    let byte = self.byte(channel, index);
+                    // Decimators move in lockstep, so samples come out interleaved
+                    if let Some(sample) = self.decimators[channel].push(byte) {
+                        self.output.push(sample.clamp(-1.0, 1.0));
+                    }
+                }
+            }
+            self.group_position = self.group_len;
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read + Seek> Iterator for DsdReader<R> {
+    type Item = f32;
+
+    /// Interleaved samples, the stream ends early if reading fails.
+    fn next(&mut self) -> Option<f32> {
+        if self.output_position >= self.output.len() && !self.convert().unwrap_or(false) {
+            return None;
+        }
+        let sample = self.output[self.output_position];
+        self.output_position += 1;
+        Some(sample)
+    }
+}
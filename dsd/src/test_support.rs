@@ -0,0 +1,208 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Container formats of DSD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DsdFormat {
+    Dsf,
+    Dff,
+}
+
+impl DsdFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DsdFormat::Dsf => "dsf",
+            DsdFormat::Dff => "dff",
+        }
+    }
+}
+
+const AMPLITUDE: f64 = 0.5;
+const DSF_BLOCK_SIZE: usize = 4096;
+
+/// Frequency of the tone on a channel, every channel gets its own.
+pub fn tone_frequency(channel: u16) -> f64 {
+    1000.0 * (channel + 1) as f64
+}
+
+/// The value the tone of a channel has at a given time.
+pub fn tone(channel: u16, seconds: f64) -> f64 {
+    (seconds * tone_frequency(channel) * std::f64::consts::TAU).sin() * AMPLITUDE
+}
+
+/// Modulate the tone of a channel to DSD with a second order
+/// sigma-delta modulator, packed most significant bit first.
+pub fn tone_dsd(dsd_rate: u32, channel: u16, frames: usize) -> Vec<u8> {
+    let mut first = 0.0;
+    let mut second = 0.0;
+    let mut output = 0.0;
+
+    let mut bytes = vec![0u8; frames.div_ceil(8)];
+    for n in 0..frames {
+        let input = tone(channel, n as f64 / dsd_rate as f64);
+        first += input - output;
+        second += first - output;
+        output = if second >= 0.0 { 1.0 } else { -1.0 };
+
+        if output > 0.0 {
+            bytes[n / 8] |= 0x80 >> (n % 8);
+        }
+    }
+    bytes
+}
+
+/// A minimal ID3v2.4 tag with UTF-8 text frames.
+pub fn id3v2_tag(frames: &[(&str, &str)]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (id, value) in frames {
+        let size = value.len() as u32 + 1;
+        body.extend(id.as_bytes());
+        body.extend(syncsafe(size));
+        body.extend([0u8, 0]);
+        body.push(3);
+        body.extend(value.as_bytes());
+    }
+
+    let mut tag = b"ID3".to_vec();
+    tag.extend([4u8, 0, 0]);
+    tag.extend(syncsafe(body.len() as u32));
+    tag.extend(body);
+    tag
+}
+
+fn syncsafe(value: u32) -> [u8; 4] {
+    [
+        (value >> 21) as u8 & 0x7F,
+        (value >> 14) as u8 & 0x7F,
+        (value >> 7) as u8 & 0x7F,
+        value as u8 & 0x7F,
+    ]
+}
+
+/// Write a tone per channel as a DSD file.
+///
+/// DSF gets the tags as an ID3v2 tag, DSDIFF as its own title and artist
+/// chunks, so only `TIT2` and `TPE1` survive there.
+pub fn write_tone_fixture(
+    path: &Path,
+    format: DsdFormat,
+    dsd_rate: u32,
+    channels: u16,
+    frames: usize,
+    tags: &[(&str, &str)],
+) -> io::Result<()> {
+    let data: Vec<Vec<u8>> = (0..channels)
+        .map(|channel| tone_dsd(dsd_rate, channel, frames))
+        .collect();
+
+    let bytes = match format {
+        DsdFormat::Dsf => dsf(dsd_rate, frames, &data, tags),
+        DsdFormat::Dff => dff(dsd_rate, &data, tags),
+    };
+
+    fs::write(path, bytes)
+}
+
+fn dsf(dsd_rate: u32, frames: usize, data: &[Vec<u8>], tags: &[(&str, &str)]) -> Vec<u8> {
+    let blocks = data[0].len().div_ceil(DSF_BLOCK_SIZE);
+    let data_len = (blocks * DSF_BLOCK_SIZE * data.len()) as u64;
+    let tag = if tags.is_empty() {
+        Vec::new()
+    } else {
+        id3v2_tag(tags)
+    };
+
+    let metadata_offset = if tag.is_empty() {
+        0
+    } else {
+        28 + 52 + 12 + data_len
+    };
+    let file_size = 28 + 52 + 12 + data_len + tag.len() as u64;
+
+    let mut out = Vec::new();
+    out.extend(b"DSD ");
+    out.extend(28u64.to_le_bytes());
+    out.extend(file_size.to_le_bytes());
+    out.extend(metadata_offset.to_le_bytes());
+
+    out.extend(b"fmt ");
+    out.extend(52u64.to_le_bytes());
+    out.extend(1u32.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend(if data.len() == 1 { 1u32 } else { 2u32 }.to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend(dsd_rate.to_le_bytes());
+    out.extend(1u32.to_le_bytes());
+    out.extend((frames as u64).to_le_bytes());
+    out.extend((DSF_BLOCK_SIZE as u32).to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+
+    out.extend(b"data");
+    out.extend((12 + data_len).to_le_bytes());
+    for block in 0..blocks {
+        for channel in data {
+            let start = block * DSF_BLOCK_SIZE;
+            let end = (start + DSF_BLOCK_SIZE).min(channel.len());
+            // DSF stores the oldest sample in the least significant bit
+            out.extend(channel[start..end].iter().map(|x| x.reverse_bits()));
+            out.extend(std::iter::repeat_n(0u8, start + DSF_BLOCK_SIZE - end));
+        }
+    }
+
+    out.extend(tag);
+    out
+}
+
+fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut out = id.to_vec();
+    out.extend((body.len() as u64).to_be_bytes());
+    out.extend(body);
+    if body.len() % 2 == 1 {
+        out.push(0);
+    }
+    out
+}
+
+fn dff(dsd_rate: u32, data: &[Vec<u8>], tags: &[(&str, &str)]) -> Vec<u8> {
+    let mut channels = (data.len() as u16).to_be_bytes().to_vec();
+    for index in 0..data.len() {
+        channels.extend(format!("C{:03}", index).as_bytes());
+    }
+    let mut compression = b"DSD ".to_vec();
+    compression.push(14);
+    compression.extend(b"not compressed");
+    compression.push(0);
+
+    let mut properties = b"SND ".to_vec();
+    properties.extend(chunk(b"FS  ", &dsd_rate.to_be_bytes()));
+    properties.extend(chunk(b"CHNL", &channels));
+    properties.extend(chunk(b"CMPR", &compression));
+
+    let mut samples = Vec::new();
+    for index in 0..data[0].len() {
+        samples.extend(data.iter().map(|channel| channel[index]));
+    }
+
+    let mut information = Vec::new();
+    for (id, value) in tags {
+        let local_id = match *id {
+            "TIT2" => b"DITI",
+            "TPE1" => b"DIAR",
+            _ => continue,
+        };
+        let mut text = (value.len() as u32).to_be_bytes().to_vec();
+        text.extend(value.as_bytes());
+        information.extend(chunk(local_id, &text));
+    }
+
+    let mut form = b"DSD ".to_vec();
+    form.extend(chunk(b"FVER", &0x0105_0000u32.to_be_bytes()));
+    form.extend(chunk(b"PROP", &properties));
+    form.extend(chunk(b"DSD ", &samples));
+    if !information.is_empty() {
+        form.extend(chunk(b"DIIN", &information));
+    }
+
+    chunk(b"FRM8", &form)
+}
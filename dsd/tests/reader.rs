@@ -0,0 +1,220 @@
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::path::Path;
+
+use dsd::test_support::{id3v2_tag, tone, write_tone_fixture, DsdFormat};
+use dsd::{is_dsd_path, DsdError, DsdReader};
+
+const DSD64: u32 = 2_822_400;
+const DSD128: u32 = 5_644_800;
+
+fn open(path: &Path) -> DsdReader<BufReader<File>> {
+    DsdReader::new(BufReader::new(File::open(path).unwrap())).unwrap()
+}
+
+// Root mean square difference to the tone, at the best of a few delays
+// to allow for the delay of the filter
+fn tone_error(samples: &[f32], channels: u16, channel: u16, pcm_rate: u32) -> f64 {
+    let skip = 64;
+    let frames = samples.len() / channels as usize - skip - 64;
+
+    (0..64)
+        .map(|delay| {
+            let sum: f64 = (0..frames)
+                .map(|n| {
+                    let sample = samples[(skip + n) * channels as usize + channel as usize];
+                    let time = (skip + n - delay) as f64 / pcm_rate as f64;
+                    (sample as f64 - tone(channel, time)).powi(2)
+                })
+                .sum();
+            (sum / frames as f64).sqrt()
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+#[test]
+fn dsd_files_convert_to_pcm() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for format in [DsdFormat::Dsf, DsdFormat::Dff] {
+        for (dsd_rate, channels) in [(DSD64, 1), (DSD64, 2), (DSD128, 2)] {
+            let path = dir.path().join(format!("tone.{}", format.extension()));
+            let frames = dsd_rate as usize / 10;
+            write_tone_fixture(&path, format, dsd_rate, channels, frames, &[]).unwrap();
+
+            let reader = open(&path);
+            let info = reader.info();
+            assert_eq!(info.dsd_rate, dsd_rate);
+            assert_eq!(info.pcm_rate, 176_400);
+            assert_eq!(info.channels, channels);
+            assert_eq!(info.dsd_frames, frames as u64);
+            assert!((info.duration().as_secs_f64() - 0.1).abs() < 1e-6);
+
+            let samples: Vec<f32> = reader.collect();
+            assert_eq!(samples.len() as u64, info.pcm_frames() * channels as u64);
+
+            for channel in 0..channels {
+                let error = tone_error(&samples, channels, channel, info.pcm_rate);
+                assert!(
+                    error < 0.01,
+                    "{:?} {} {}: {}",
+                    format,
+                    dsd_rate,
+                    channel,
+                    error
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn seeking_continues_like_reading_through() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for format in [DsdFormat::Dsf, DsdFormat::Dff] {
+        let path = dir.path().join(format!("tone.{}", format.extension()));
+        write_tone_fixture(&path, format, DSD64, 2, DSD64 as usize / 5, &[]).unwrap();
+
+        let samples: Vec<f32> = open(&path).collect();
+
+        // Inside a later DSF block, after the history of the filter was refilled
+        let target = 10_000;
+        let settled = 64;
+        let mut reader = open(&path);
+        reader.seek(target).unwrap();
+        let resumed: Vec<f32> = reader.skip(settled * 2).take(1000).collect();
+
+        let start = (target as usize + settled) * 2;
+        assert_eq!(resumed, samples[start..start + 1000], "{:?}", format);
+    }
+}
+
+#[test]
+fn seeking_past_the_end_ends_the_stream() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tone.dsf");
+    write_tone_fixture(&path, DsdFormat::Dsf, DSD64, 2, 10_000, &[]).unwrap();
+
+    let mut reader = open(&path);
+    reader.seek(1_000_000).unwrap();
+    assert_eq!(reader.next(), None);
+}
+
+#[test]
+fn tags_are_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let tags = [("TIT2", "Sonata"), ("TPE1", "Pianist"), ("TALB", "Recital")];
+
+    let path = dir.path().join("tagged.dsf");
+    write_tone_fixture(&path, DsdFormat::Dsf, DSD64, 2, 10_000, &tags).unwrap();
+    let reader = open(&path);
+    assert_eq!(reader.tags().id3, Some(id3v2_tag(&tags)));
+
+    let path = dir.path().join("tagged.dff");
+    write_tone_fixture(&path, DsdFormat::Dff, DSD64, 2, 10_000, &tags).unwrap();
+    let reader = open(&path);
+    assert_eq!(reader.tags().id3, None);
+    assert_eq!(reader.tags().title.as_deref(), Some("Sonata"));
+    assert_eq!(reader.tags().artist.as_deref(), Some("Pianist"));
+}
+
+#[test]
+fn other_files_are_rejected() {
+    let result = DsdReader::new(Cursor::new(b"RIFF\0\0\0\0WAVE".to_vec()));
+    assert!(matches!(result, Err(DsdError::Invalid(_))));
+
+    let result = DsdReader::new(Cursor::new(b"DSD ".to_vec()));
+    assert!(matches!(result, Err(DsdError::Io(_))));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tone.dsf");
+    // DSD512 would need too large a filter
+    write_tone_fixture(&path, DsdFormat::Dsf, DSD64 * 8, 1, 10_000, &[]).unwrap();
+    let result = DsdReader::new(File::open(&path).unwrap());
+    assert!(matches!(result, Err(DsdError::Unsupported(_))));
+}
+
+fn fixture_bytes(format: DsdFormat) -> Vec<u8> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(format!("tone.{}", format.extension()));
+    let tags = [("TIT2", "Sonata"), ("TPE1", "Pianist")];
+    write_tone_fixture(&path, format, DSD64, 2, 10_000, &tags).unwrap();
+    std::fs::read(path).unwrap()
+}
+
+// Opening and converting a broken file fails without panicking
+fn read_broken(bytes: Vec<u8>) -> Result<usize, DsdError> {
+    let reader = DsdReader::new(Cursor::new(bytes))?;
+    Ok(reader.take(100_000).count())
+}
+
+#[test]
+fn truncated_headers_are_rejected() {
+    for format in [DsdFormat::Dsf, DsdFormat::Dff] {
+        let bytes = fixture_bytes(format);
+        for len in 0..200 {
+            let _ = read_broken(bytes[..len].to_vec());
+        }
+    }
+}
+
+#[test]
+fn garbage_headers_are_rejected() {
+    for format in [DsdFormat::Dsf, DsdFormat::Dff] {
+        let bytes = fixture_bytes(format);
+        for offset in 4..200 {
+            for garbage in [[0xFF; 8], [0x00; 8], [0x7F, 0xFF, 0, 0, 0, 0, 0, 0x80]] {
+                let mut broken = bytes.clone();
+                broken[offset..offset + 8].copy_from_slice(&garbage);
+                let _ = read_broken(broken);
+            }
+        }
+    }
+}
+
+#[test]
+fn out_of_range_header_fields_are_invalid() {
+    let dsf = fixture_bytes(DsdFormat::Dsf);
+    let patch = |offset: usize, value: &[u8]| {
+        let mut broken = dsf.clone();
+        broken[offset..offset + value.len()].copy_from_slice(value);
+        read_broken(broken)
+    };
+    // Channel count, block size and sizes adding up past the end of u64
+    for (offset, value) in [
+        (52, 7u32.to_le_bytes().to_vec()),
+        (52, 0u32.to_le_bytes().to_vec()),
+        (72, 4097u32.to_le_bytes().to_vec()),
+        (72, u32::MAX.to_le_bytes().to_vec()),
+        (32, u64::MAX.to_le_bytes().to_vec()),
+        (64, u64::MAX.to_le_bytes().to_vec()),
+    ] {
+        let result = patch(offset, &value);
+        assert!(
+            matches!(result, Err(DsdError::Invalid(_))),
+            "{} {:?}: {:?}",
+            offset,
+            value,
+            result
+        );
+    }
+
+    let dff = fixture_bytes(DsdFormat::Dff);
+    let mut broken = dff.clone();
+    broken[4..12].copy_from_slice(&u64::MAX.to_be_bytes());
+    assert!(matches!(read_broken(broken), Err(DsdError::Invalid(_))));
+
+    // The size of the first chunk, FVER
+    let mut broken = dff.clone();
+    broken[20..28].copy_from_slice(&(u64::MAX - 1).to_be_bytes());
+    assert!(matches!(read_broken(broken), Err(DsdError::Invalid(_))));
+}
+
+#[test]
+fn dsd_paths_are_recognised() {
+    assert!(is_dsd_path(Path::new("/music/track.dsf")));
+    assert!(is_dsd_path(Path::new("/music/track.DFF")));
+    assert!(!is_dsd_path(Path::new("/music/track.flac")));
+    assert!(!is_dsd_path(Path::new("/music/dsf")));
+}
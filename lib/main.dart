@@ -24,6 +24,7 @@ import 'widgets/flip_animation.dart';
 import 'widgets/navigation_bar.dart';
 import 'widgets/playback_controller.dart';
 
+import 'utils/tray.dart';
 import 'utils/platform.dart';
 
 import 'routes/welcome.dart' as welcome;
@@ -54,6 +55,9 @@ void main() async {
       await windowManager.show();
       await windowManager.setPreventClose(true);
     });
+    if (trayEnabled) {
+      await TrayHandler.init();
+    }
   }
 
   runApp(
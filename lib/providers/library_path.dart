@@ -1,6 +1,6 @@
 import 'package:fluent_ui/fluent_ui.dart';
 
-import '../messages/connection.pb.dart';
+import '../messages/library_manage.pb.dart';
 import '../utils/file_storage_service.dart';
 
 class LibraryPathProvider with ChangeNotifier {
@@ -28,8 +28,8 @@ class LibraryPathProvider with ChangeNotifier {
   Future<void> setLibraryPath(String filePath, [bool scan = false]) async {
     _currentPath = filePath;
     _scanning = scan;
-    // Send the signal to Rust
-    MediaLibraryPath(path: filePath).sendSignalToRust();
+    // Send the signal to Rust, any opened library is closed first
+    OpenLibraryRequest(path: filePath).sendSignalToRust();
     notifyListeners();
     _fileStorageService.storeFilePath(filePath);
   }
@@ -23,5 +23,14 @@ class PlaybackStatusUpdateHandler {
       Provider.of<PlaybackStatusProvider>(context, listen: false)
           .updatePlaybackStatus(playbackStatusUpdate);
     });
+
+    // Progress arrives with the visualizer frames in ticks
+    PlaybackTick.rustSignalStream.listen((event) {
+      final tick = event.message;
+
+      if (!context.mounted || !tick.hasStatus()) return;
+      Provider.of<PlaybackStatusProvider>(context, listen: false)
+          .updatePlaybackStatus(tick.status);
+    });
   }
 }
@@ -0,0 +1,105 @@
+import 'dart:io';
+
+import 'package:tray_manager/tray_manager.dart';
+import 'package:window_manager/window_manager.dart';
+
+import '../config/app_title.dart';
+
+import '../messages/playback.pb.dart';
+
+/// Whether to show the tray icon, off unless the app is built with
+/// `--dart-define=TRAY=true`. Desktop only.
+const bool trayEnabled = bool.fromEnvironment('TRAY', defaultValue: false);
+
+/// Shows a tray icon with the track playing as its tooltip, and a menu to
+/// play, pause, skip and quit.
+///
+/// The tray needs the main thread of the platform, which belongs to
+/// Flutter, so it is driven from here instead of the hub.
+class TrayHandler with TrayListener {
+  static final TrayHandler _instance = TrayHandler._();
+
+  bool? _playing;
+  String? _tooltip;
+
+  TrayHandler._();
+
+  static Future<void> init() async {
+    trayManager.addListener(_instance);
+    await trayManager.setIcon(
+      Platform.isWindows ? 'assets/tray_icon.ico' : 'assets/tray_icon.png',
+    );
+    await _instance._update(false, appTitle);
+
+    PlaybackStatus.rustSignalStream.listen((event) {
+      _instance._updateStatus(event.message);
+    });
+    // Progress arrives with the visualizer frames in ticks
+    PlaybackTick.rustSignalStream.listen((event) {
+      if (event.message.hasStatus()) {
+        _instance._updateStatus(event.message.status);
+      }
+    });
+  }
+
+  void _updateStatus(PlaybackStatus status) {
+    final tooltip = status.title.isEmpty
+        ? appTitle
+        : status.artist.isEmpty
+            ? status.title
+            : '${status.title} - ${status.artist}';
+    _update(status.state == "Playing", tooltip);
+  }
+
+  Future<void> _update(bool playing, String tooltip) async {
+    // Most updates only move the position
+    if (playing == _playing && tooltip == _tooltip) return;
+
+    if (playing != _playing) {
+      _playing = playing;
+      await trayManager.setContextMenu(Menu(items: [
+        MenuItem(key: 'play_pause', label: playing ? 'Pause' : 'Play'),
+        MenuItem(key: 'next', label: 'Next'),
+        MenuItem(key: 'previous', label: 'Previous'),
+        MenuItem.separator(),
+        MenuItem(key: 'quit', label: 'Quit'),
+      ]));
+    }
+
+    if (tooltip != _tooltip) {
+      _tooltip = tooltip;
+      // Linux has no tray tooltips
+      if (!Platform.isLinux) {
+        await trayManager.setToolTip(tooltip);
+      }
+    }
+  }
+
+  @override
+  void onTrayIconMouseDown() {
+    windowManager.show();
+  }
+
+  @override
+  void onTrayIconRightMouseDown() {
+    trayManager.popUpContextMenu();
+  }
+
+  @override
+  void onTrayMenuItemClick(MenuItem menuItem) {
+    switch (menuItem.key) {
+      case 'play_pause':
+        if (_playing == true) {
+          PauseRequest().sendSignalToRust(); // GENERATED
+        } else {
+          PlayRequest().sendSignalToRust(); // GENERATED
+        }
+      case 'next':
+        NextRequest().sendSignalToRust(); // GENERATED
+      case 'previous':
+        PreviousRequest().sendSignalToRust(); // GENERATED
+      case 'quit':
+        windowManager.destroy();
+    }
+  }
+}
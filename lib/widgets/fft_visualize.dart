@@ -23,11 +23,12 @@ class FFTVisualizeState extends State<FFTVisualize>
   @override
   void initState() {
     super.initState();
-    RealtimeFFT.rustSignalStream.listen((rustSignal) {
-      if (!mounted) return;
+    PlaybackTick.rustSignalStream.listen((rustSignal) {
+      final frames = rustSignal.message.frames;
+      if (!mounted || frames.isEmpty) return;
 
       setState(() {
-        _targetFftValues = rustSignal.message.value;
+        _targetFftValues = frames.last.value;
         _lastUpdateTime = DateTime.now().millisecondsSinceEpoch;
         if (!_hasData) {
           _hasData = true;
@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::path::Path;
+
+use symphonia::core::codecs::CODEC_TYPE_NULL;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, StandardTagKey};
+use symphonia::core::probe::Hint;
+
+use crate::reader::MetadataError;
+
+/// A logical track inside a single file, e.g. a song of an album ripped to
+/// one FLAC with a cuesheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub number: u32,
+    pub title: Option<String>,
+    /// Start in seconds from the beginning of the file.
+    pub start: f64,
+    /// End in seconds, where the next chapter or the file starts or ends.
+    pub end: f64,
+}
+
+/// Read the chapters embedded in a media file.
+///
+/// Chapters come from the cues of the container, like the cuesheet block of
+/// FLAC. A lead-out cue only marks the end of the audio, it's not a chapter.
+///
+/// # Returns
+/// * `Result<Vec<Chapter>, MetadataError>` - The chapters in playback order, empty for plain files.
+pub fn read_chapters(file_path: &Path) -> Result<Vec<Chapter>, MetadataError> {
+    if !file_path.exists() {
+        return Err(MetadataError::FileNotFound);
+    }
+
+    // DSD files have no chapters
+    if dsd::is_dsd_path(file_path) {
+        return Ok(Vec::new());
+    }
+
+    let src = File::open(file_path)?;
+    let mss = MediaSourceStream::new(Box::new(src), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = file_path.extension().and_then(|x| x.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let fmt_opts: FormatOptions = Default::default();
+    let meta_opts: MetadataOptions = Default::default();
+
+    let probed = symphonia::default::get_probe().format(&hint, mss, &fmt_opts, &meta_opts)?;
+    let format = probed.format;
+
+    let Some(track) = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+    else {
+        return Ok(Vec::new());
+    };
+    let Some(sample_rate) = track.codec_params.sample_rate else {
+        return Ok(Vec::new());
+    };
+    let total_frames = track.codec_params.n_frames;
+
+    let mut cues = format.cues().to_vec();
+    cues.sort_by_key(|x| x.start_ts);
+
+    let chapters = cues
+        .iter()
+        .enumerate()
+        .filter_map(|(index, cue)| {
+            let end_ts = match cues.get(index + 1) {
+                Some(next) => next.start_ts,
+                None => total_frames?,
+            };
+            if end_ts <= cue.start_ts {
+                return None;
+            }
+
+            let title = cue
+                .tags
+                .iter()
+                .find(|x| x.std_key == Some(StandardTagKey::TrackTitle))
+                .map(|x| x.value.to_string());
+
+            Some(Chapter {
+                number: cue.index,
+                title,
+                start: cue.start_ts as f64 / sample_rate as f64,
+                end: end_ts as f64 / sample_rate as f64,
+            })
+        })
+        .collect();
+
+    Ok(chapters)
+}
@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+// The marker, the header of STREAMINFO and the STREAMINFO fields before the
+// MD5
+const FLAC_MD5_OFFSET: usize = 4 + 4 + 18;
+
+/// A checksum of the decoded audio that the format stores in the file, it
+/// stays the same when the tags are edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddedChecksum {
+    /// The MD5 of the samples in the STREAMINFO block of FLAC.
+    FlacMd5([u8; 16]),
+}
+
+impl EmbeddedChecksum {
+    /// The name the checksum is stored under.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EmbeddedChecksum::FlacMd5(_) => "flac_md5",
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        match self {
+            EmbeddedChecksum::FlacMd5(md5) => md5.iter().map(|x| format!("{:02x}", x)).collect(),
+        }
+    }
+}
+
+/// Parse the MD5 of a FLAC stream.
+///
+/// # Arguments
+/// * `head` - The start of the stream, from the `fLaC` marker on.
+///
+/// # Returns
+/// * `Option<[u8; 16]>` - The MD5, `None` for encoders that left it unset.
+pub fn parse_flac_md5(head: &[u8]) -> Option<[u8; 16]> {
+    if head.get(0..4)? != b"fLaC" {
+        return None;
+    }
+    // STREAMINFO always comes first
+    if head.get(4)? & 0x7F != 0 {
+        return None;
+    }
+
+    let md5: [u8; 16] = head
+        .get(FLAC_MD5_OFFSET..FLAC_MD5_OFFSET + 16)?
+        .try_into()
+        .ok()?;
+    (md5 != [0; 16]).then_some(md5)
+}
+
+fn read_flac_checksum(file_path: &Path) -> io::Result<Option<EmbeddedChecksum>> {
+    let mut reader = BufReader::new(File::open(file_path)?);
+
+    let mut head = [0; 10];
+    reader.read_exact(&mut head)?;
+    // Some taggers put ID3v2 in front of the stream
+    if &head[0..3] == b"ID3" {
+        let size = head[6..10]
+            .iter()
+            .fold(0u64, |size, x| (size << 7) | (*x & 0x7F) as u64);
+        let footer = if head[5] & 0x10 != 0 { 10 } else { 0 };
+        io::copy(&mut reader.by_ref().take(size + footer), &mut io::sink())?;
+        reader.read_exact(&mut head)?;
+    }
+
+    let mut stream = head.to_vec();
+    reader
+        .take((FLAC_MD5_OFFSET + 16 - head.len()) as u64)
+        .read_to_end(&mut stream)?;
+
+    Ok(parse_flac_md5(&stream).map(EmbeddedChecksum::FlacMd5))
+}
+
+/// Read the checksum a media file keeps of its audio.
+///
+/// # Arguments
+/// * `file_path` - The full path of the media file.
+///
+/// # Returns
+/// * `io::Result<Option<EmbeddedChecksum>>` - The checksum of FLAC files,
+///   `None` for formats without one.
+pub fn read_embedded_checksum(file_path: &Path) -> io::Result<Option<EmbeddedChecksum>> {
+    let extension = file_path
+        .extension()
+        .and_then(|x| x.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    match extension.as_str() {
+        "flac" => read_flac_checksum(file_path),
+        _ => Ok(None),
+    }
+}
@@ -2,6 +2,7 @@ use lofty::file::TaggedFileExt;
 use std::path::Path;
 
 use crate::crc::media_crc32;
+use crate::reader::read_dsd_tags;
 
 pub struct CoverArt {
     pub crc: String,
@@ -9,19 +10,19 @@ pub struct CoverArt {
 }
 
 pub fn extract_cover_art_binary(file_path: &Path) -> Option<CoverArt> {
-    let tagged_file = lofty::read_from_path(file_path).ok()?;
-
-    let tag = tagged_file
-        .primary_tag()
-        .or_else(|| tagged_file.first_tag())?;
-
-    let pictures = tag.pictures();
-    if pictures.is_empty() {
-        return None;
-    }
-
-    let picture = &pictures[0];
-    let cover_data = picture.data().to_vec();
+    let cover_data = if dsd::is_dsd_path(file_path) {
+        // Lofty doesn't read DSD, the pictures are in its ID3v2 tag
+        let revision = read_dsd_tags(file_path).ok()?;
+        revision.visuals().first()?.data.to_vec()
+    } else {
+        let tagged_file = lofty::read_from_path(file_path).ok()?;
+
+        let tag = tagged_file
+            .primary_tag()
+            .or_else(|| tagged_file.first_tag())?;
+
+        tag.pictures().first()?.data().to_vec()
+    };
 
     // Calculate the CRC
     let crc = media_crc32(&cover_data, 0, 0, cover_data.len());
@@ -54,6 +54,10 @@ impl FileDescription {
     }
 
     pub fn get_codec_information(&mut self) -> Result<(u32, f64), symphonia::core::errors::Error> {
+        if dsd::is_dsd_path(&self.full_path) {
+            return dsd_codec_information(&self.full_path);
+        }
+
         let format =
             get_format(self.full_path.to_str().unwrap()).expect("no supported audio tracks");
         let track = format
@@ -68,6 +72,20 @@ impl FileDescription {
 
 const CHUNK_SIZE: usize = 1024 * 400;
 
+// The DSD rate rather than the rate it's played at, that's what the file is
+fn dsd_codec_information(path: &Path) -> Result<(u32, f64), symphonia::core::errors::Error> {
+    use symphonia::core::errors::Error;
+
+    let reader = dsd::DsdReader::new(BufReader::new(File::open(path)?)).map_err(|e| match e {
+        dsd::DsdError::Io(e) => Error::IoError(e),
+        dsd::DsdError::Invalid(reason) => Error::DecodeError(reason),
+        dsd::DsdError::Unsupported(reason) => Error::Unsupported(reason),
+    })?;
+    let info = reader.info();
+
+    Ok((info.dsd_rate, info.duration().as_secs_f64()))
+}
+
 pub fn describe_file(
     file_path: &Path,
     lib_path: &Path,
@@ -0,0 +1,446 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+// Frames in a FLAC frame, the reference encoder's default
+const BLOCK_SIZE: usize = 4096;
+const BITS_PER_SAMPLE: u32 = 16;
+const MAX_FIXED_ORDER: usize = 4;
+const MAX_PARTITION_ORDER: u32 = 6;
+// 15 is the escape code of 4-bit Rice parameters
+const MAX_RICE_PARAMETER: u32 = 14;
+// Where the packed sample rate, channels and length are in STREAMINFO
+const STREAM_INFO_OFFSET: u64 = 18;
+
+/// Writes 16-bit PCM as a FLAC file.
+///
+/// Every subframe uses the fixed predictor with the smallest residual, and
+/// stereo frames are stored as mid and side when that is smaller. There is
+/// no search of linear predictors, so files are somewhat larger than the
+/// reference encoder's, but still far smaller than WAV.
+pub struct FlacWriter<W: Write + Seek> {
+    inner: W,
+    sample_rate: u32,
+    channels: usize,
+    // Interleaved samples waiting for a full block
+    pending: Vec<i16>,
+    frame_number: u32,
+    total_frames: u64,
+}
+
+impl<W: Write + Seek> FlacWriter<W> {
+    /// Start a FLAC stream, its length is filled in by `finalize`.
+    ///
+    /// # Arguments
+    /// * `inner` - Where to write the stream.
+    /// * `sample_rate` - The sample rate in Hz, at most 655350.
+    /// * `channels` - The number of channels, 1 to 8.
+    pub fn new(mut inner: W, sample_rate: u32, channels: u16) -> io::Result<Self> {
+        if !(1..=8).contains(&channels) || !(1..=655_350).contains(&sample_rate) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "FLAC can't hold this sample rate or channel count",
+            ));
+        }
+
+        inner.write_all(b"fLaC")?;
+        // STREAMINFO, the only metadata block
+        inner.write_all(&[0x80, 0, 0, 34])?;
+        inner.write_all(&(BLOCK_SIZE as u16).to_be_bytes())?;
+        inner.write_all(&(BLOCK_SIZE as u16).to_be_bytes())?;
+        // Frame sizes are left unknown
+        inner.write_all(&[0; 6])?;
+        inner.write_all(&stream_info(sample_rate, channels as usize, 0).to_be_bytes())?;
+        // No MD5 signature
+        inner.write_all(&[0; 16])?;
+
+        Ok(FlacWriter {
+            inner,
+            sample_rate,
+            channels: channels as usize,
+            pending: Vec::with_capacity(BLOCK_SIZE * channels as usize),
+            frame_number: 0,
+            total_frames: 0,
+        })
+    }
+
+    /// Write interleaved samples, they don't need to end on a whole frame.
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        self.pending.extend_from_slice(samples);
+
+        let block_len = BLOCK_SIZE * self.channels;
+        while self.pending.len() >= block_len {
+            let block: Vec<i16> = self.pending.drain(..block_len).collect();
+            self.write_frame(&block)?;
+        }
+        Ok(())
+    }
+
+    /// Write the last frame and the length of the stream.
+    pub fn finalize(mut self) -> io::Result<W> {
+        if !self.pending.len().is_multiple_of(self.channels) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the samples don't end on a whole frame",
+            ));
+        }
+        if !self.pending.is_empty() {
+            let block = std::mem::take(&mut self.pending);
+            self.write_frame(&block)?;
+        }
+
+        self.inner.seek(SeekFrom::Start(STREAM_INFO_OFFSET))?;
+        self.inner.write_all(
+            &stream_info(self.sample_rate, self.channels, self.total_frames).to_be_bytes(),
+        )?;
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_frame(&mut self, block: &[i16]) -> io::Result<()> {
+        let frames = block.len() / self.channels;
+        let mut channels: Vec<Vec<i32>> = (0..self.channels)
+            .map(|channel| {
+                block
+                    .iter()
+                    .skip(channel)
+                    .step_by(self.channels)
+                    .map(|&x| x as i32)
+                    .collect()
+            })
+            .collect();
+
+        // Stereo is also tried as mid and side, the side channel needs one
+        // more bit than the samples
+        if self.channels == 2 {
+            let pairs = channels[0].iter().zip(&channels[1]);
+            let mid = pairs.clone().map(|(l, r)| (l + r) >> 1).collect();
+            let side = pairs.map(|(l, r)| l - r).collect();
+            channels.extend([mid, side]);
+        }
+
+        let (assignment, subframes) = if self.channels == 2 {
+            let left = plan_subframe(&channels[0], BITS_PER_SAMPLE);
+            let right = plan_subframe(&channels[1], BITS_PER_SAMPLE);
+            let mid = plan_subframe(&channels[2], BITS_PER_SAMPLE);
+            let side = plan_subframe(&channels[3], BITS_PER_SAMPLE + 1);
+
+            let options = [
+                (1, left.bits + right.bits),
+                (8, left.bits + side.bits),
+                (9, side.bits + right.bits),
+                (10, mid.bits + side.bits),
+            ];
+            let (assignment, _) = options.into_iter().min_by_key(|(_, bits)| *bits).unwrap();
+            let subframes = match assignment {
+                1 => vec![left, right],
+                8 => vec![left, side],
+                9 => vec![side, right],
+                _ => vec![mid, side],
+            };
+            (assignment, subframes)
+        } else {
+            let subframes = channels
+                .iter()
+                .map(|x| plan_subframe(x, BITS_PER_SAMPLE))
+                .collect();
+            (self.channels as u8 - 1, subframes)
+        };
+
+        let mut out = BitWriter::default();
+        out.write(0xFFF8, 16);
+        // The block size is at the end of the header, the sample rate is
+        // the one of STREAMINFO
+        out.write(0x70, 8);
+        // 16-bit samples
+        out.write(((assignment as u64) << 4) | 0x08, 8);
+        for byte in utf8_number(self.frame_number) {
+            out.write(byte as u64, 8);
+        }
+        out.write(frames as u64 - 1, 16);
+        let header_crc = crc8(&out.bytes);
+        out.write(header_crc as u64, 8);
+
+        for subframe in &subframes {
+            subframe.write(&mut out);
+        }
+        out.align();
+        let frame_crc = crc16(&out.bytes);
+        out.write(frame_crc as u64, 16);
+
+        self.inner.write_all(&out.bytes)?;
+        self.frame_number += 1;
+        self.total_frames += frames as u64;
+        Ok(())
+    }
+}
+
+// Sample rate, channels, bits per sample and total frames, as packed in
+// STREAMINFO
+fn stream_info(sample_rate: u32, channels: usize, total_frames: u64) -> u64 {
+    ((sample_rate as u64) << 44)
+        | (((channels - 1) as u64) << 41)
+        | (((BITS_PER_SAMPLE - 1) as u64) << 36)
+        | (total_frames & 0xF_FFFF_FFFF)
+}
+
+enum Encoding {
+    Constant,
+    Verbatim,
+    Fixed {
+        order: usize,
+        residual: Vec<i32>,
+        partition_order: u32,
+        parameters: Vec<u32>,
+    },
+}
+
+struct Subframe<'a> {
+    samples: &'a [i32],
+    bits_per_sample: u32,
+    encoding: Encoding,
+    // Size of the subframe, to compare channel assignments
+    bits: u64,
+}
+
+fn plan_subframe(samples: &[i32], bits_per_sample: u32) -> Subframe<'_> {
+    let n = samples.len();
+    let header = 8;
+
+    if samples.iter().all(|x| *x == samples[0]) {
+        return Subframe {
+            samples,
+            bits_per_sample,
+            encoding: Encoding::Constant,
+            bits: header + bits_per_sample as u64,
+        };
+    }
+
+    // The residual of a fixed predictor of order k is the k-th difference
+    // of the samples; keep the order with the smallest one
+    let mut difference = samples.to_vec();
+    let mut best: Option<(usize, Vec<i32>, u64)> = None;
+    for order in 0..=MAX_FIXED_ORDER.min(n - 1) {
+        if order > 0 {
+            for i in (order..n).rev() {
+                difference[i] -= difference[i - 1];
+            }
+        }
+        let magnitude: u64 = difference[order..]
+            .iter()
+            .map(|x| x.unsigned_abs() as u64)
+            .sum();
+        if best.as_ref().is_none_or(|(_, _, m)| magnitude < *m) {
+            best = Some((order, difference[order..].to_vec(), magnitude));
+        }
+    }
+    let (order, residual, _) = best.unwrap();
+
+    let (partition_order, parameters, residual_bits) = plan_residual(&residual, n, order);
+    let fixed_bits = header + (order as u64 * bits_per_sample as u64) + 6 + residual_bits;
+    let verbatim_bits = header + n as u64 * bits_per_sample as u64;
+
+    if verbatim_bits <= fixed_bits {
+        Subframe {
+            samples,
+            bits_per_sample,
+            encoding: Encoding::Verbatim,
+            bits: verbatim_bits,
+        }
+    } else {
+        Subframe {
+            samples,
+            bits_per_sample,
+            encoding: Encoding::Fixed {
+                order,
+                residual,
+                partition_order,
+                parameters,
+            },
+            bits: fixed_bits,
+        }
+    }
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+// The Rice parameter and size of a partition of `count` values adding up
+// to `sum` once folded to unsigned
+fn rice_parameter(count: u64, sum: u64) -> (u32, u64) {
+    (0..=MAX_RICE_PARAMETER)
+        .map(|k| (k, 4 + count * (k as u64 + 1) + (sum >> k)))
+        .min_by_key(|(_, bits)| *bits)
+        .unwrap()
+}
+
+// Pick the partition order and Rice parameters of a residual, which
+// misses the first `order` samples of a block of `n`
+fn plan_residual(residual: &[i32], n: usize, order: usize) -> (u32, Vec<u32>, u64) {
+    // Partitions split the block evenly, and the first one must still hold
+    // some of the residual
+    let max_order = (0..=MAX_PARTITION_ORDER)
+        .rev()
+        .find(|p| n.is_multiple_of(1 << p) && (n >> p) > order)
+        .unwrap_or(0);
+
+    let size = n >> max_order;
+    let mut sums: Vec<(u64, u64)> = (0..1usize << max_order)
+        .map(|i| {
+            let start = (i * size).saturating_sub(order);
+            let end = (i + 1) * size - order;
+            let sum = residual[start..end].iter().map(|x| zigzag(*x) as u64).sum();
+            ((end - start) as u64, sum)
+        })
+        .collect();
+
+    let mut best: Option<(u32, Vec<u32>, u64)> = None;
+    for partition_order in (0..=max_order).rev() {
+        if partition_order < max_order {
+            sums = sums
+                .chunks(2)
+                .map(|x| (x[0].0 + x[1].0, x[0].1 + x[1].1))
+                .collect();
+        }
+        let (parameters, bits): (Vec<u32>, Vec<u64>) = sums
+            .iter()
+            .map(|(count, sum)| rice_parameter(*count, *sum))
+            .unzip();
+        let bits = bits.iter().sum();
+        if best.as_ref().is_none_or(|(_, _, b)| bits < *b) {
+            best = Some((partition_order, parameters, bits));
+        }
+    }
+    best.unwrap()
+}
+
+impl Subframe<'_> {
+    fn write(&self, out: &mut BitWriter) {
+        let bps = self.bits_per_sample;
+        match &self.encoding {
+            Encoding::Constant => {
+                out.write(0x00, 8);
+                out.write_signed(self.samples[0], bps);
+            }
+            Encoding::Verbatim => {
+                out.write(0x02, 8);
+                for sample in self.samples {
+                    out.write_signed(*sample, bps);
+                }
+            }
+            Encoding::Fixed {
+                order,
+                residual,
+                partition_order,
+                parameters,
+            } => {
+                out.write((0x08 | *order as u64) << 1, 8);
+                for sample in &self.samples[..*order] {
+                    out.write_signed(*sample, bps);
+                }
+                // Rice coding with 4-bit parameters
+                out.write(0, 2);
+                out.write(*partition_order as u64, 4);
+
+                let size = self.samples.len() >> partition_order;
+                let mut start = 0;
+                for (i, parameter) in parameters.iter().enumerate() {
+                    let end = (i + 1) * size - order;
+                    out.write(*parameter as u64, 4);
+                    for value in &residual[start..end] {
+                        out.write_rice(*value, *parameter);
+                    }
+                    start = end;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    len: u32,
+}
+
+impl BitWriter {
+    // Write the lowest `bits` of a value, at most 32
+    fn write(&mut self, value: u64, bits: u32) {
+        self.buffer = (self.buffer << bits) | (value & ((1 << bits) - 1));
+        self.len += bits;
+        while self.len >= 8 {
+            self.len -= 8;
+            self.bytes.push((self.buffer >> self.len) as u8);
+        }
+        self.buffer &= (1 << self.len) - 1;
+    }
+
+    fn write_signed(&mut self, value: i32, bits: u32) {
+        self.write(value as u64, bits);
+    }
+
+    fn write_rice(&mut self, value: i32, parameter: u32) {
+        let value = zigzag(value);
+        let mut quotient = value >> parameter;
+        while quotient >= 32 {
+            self.write(0, 32);
+            quotient -= 32;
+        }
+        self.write(1, quotient + 1);
+        self.write(value as u64, parameter);
+    }
+
+    fn align(&mut self) {
+        if self.len > 0 {
+            self.write(0, 8 - self.len);
+        }
+    }
+}
+
+// Frame numbers are written like UTF-8 code points, up to 31 bits
+fn utf8_number(value: u32) -> Vec<u8> {
+    if value < 0x80 {
+        return vec![value as u8];
+    }
+    let len = match value {
+        0..0x800 => 2,
+        0x800..0x1_0000 => 3,
+        0x1_0000..0x20_0000 => 4,
+        0x20_0000..0x400_0000 => 5,
+        _ => 6,
+    };
+    let mut out = vec![(0xFF00u16 >> len) as u8 | (value >> (6 * (len - 1))) as u8];
+    for i in (0..len - 1).rev() {
+        out.push(0x80 | ((value >> (6 * i)) & 0x3F) as u8);
+    }
+    out
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
@@ -0,0 +1,180 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use lofty::config::ParseOptions;
+use lofty::file::AudioFile;
+use lofty::mp4::{AtomData, AtomIdent, Mp4File};
+
+/// Frames MP3 decoders output before the first encoded frame.
+pub const MP3_DECODER_DELAY: u64 = 529;
+
+// Bytes searched for the first MP3 frame after the ID3v2 tag
+const MAX_FRAME_SEARCH: usize = 64 * 1024;
+
+/// The priming and padding lossy encoders add around the audio, trimmed so
+/// tracks of a gapless album join without a gap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GaplessInfo {
+    /// Frames to drop from the start of the decoded audio.
+    pub leading_frames: u64,
+    /// Frames of actual audio after them, `None` if the encoder didn't tell.
+    pub valid_frames: Option<u64>,
+}
+
+/// Parse the `iTunSMPB` tag iTunes writes into AAC files.
+///
+/// The value is a list of hexadecimal numbers, the second one is the encoder
+/// delay, the third one the padding and the fourth one the length of the
+/// original audio.
+pub fn parse_itunes_smpb(value: &str) -> Option<GaplessInfo> {
+    let fields: Vec<u64> = value
+        .split_whitespace()
+        .map(|x| u64::from_str_radix(x, 16).ok())
+        .collect::<Option<_>>()?;
+    let delay = *fields.get(1)?;
+    let length = *fields.get(3)?;
+
+    Some(GaplessInfo {
+        leading_frames: delay,
+        valid_frames: (length > 0).then_some(length),
+    })
+}
+
+/// Parse the LAME tag in the Xing or Info frame at the start of an MP3.
+///
+/// # Arguments
+/// * `frame` - The bytes of the first frame, starting at its header.
+///
+/// # Returns
+/// * `Option<GaplessInfo>` - The trimming, `None` for frames without a LAME
+///   tag.
+pub fn parse_lame_frame(frame: &[u8]) -> Option<GaplessInfo> {
+    let header = frame.get(0..4)?;
+    if header[0] != 0xFF || header[1] & 0xE0 != 0xE0 {
+        return None;
+    }
+    // Only layer III has a LAME tag
+    if (header[1] >> 1) & 0x03 != 1 {
+        return None;
+    }
+
+    let mpeg1 = (header[1] >> 3) & 0x03 == 3;
+    let mono = header[3] >> 6 == 3;
+    let side_info = match (mpeg1, mono) {
+        (true, true) => 17,
+        (true, false) => 32,
+        (false, true) => 9,
+        (false, false) => 17,
+    };
+    let samples_per_frame: u64 = if mpeg1 { 1152 } else { 576 };
+
+    let mut cursor = 4 + side_info;
+    let tag = frame.get(cursor..cursor + 4)?;
+    if tag != b"Xing" && tag != b"Info" {
+        return None;
+    }
+    let flags = u32::from_be_bytes(frame.get(cursor + 4..cursor + 8)?.try_into().ok()?);
+    cursor += 8;
+
+    let mut frames = None;
+    if flags & 0x01 != 0 {
+        let count = u32::from_be_bytes(frame.get(cursor..cursor + 4)?.try_into().ok()?);
+        frames = Some(count as u64);
+        cursor += 4;
+    }
+    // Byte count, table of contents and quality
+    for (flag, len) in [(0x02, 4), (0x04, 100), (0x08, 4)] {
+        if flags & flag != 0 {
+            cursor += len;
+        }
+    }
+
+    // LAME and the encoders built on it or FFmpeg name themselves first
+    let encoder = frame.get(cursor..cursor + 4)?;
+    if encoder != b"LAME" && encoder != b"Lavf" && encoder != b"Lavc" {
+        return None;
+    }
+    let delays = frame.get(cursor + 21..cursor + 24)?;
+    let delay = ((delays[0] as u64) << 4) | (delays[1] as u64 >> 4);
+    let padding = (((delays[1] & 0x0F) as u64) << 8) | delays[2] as u64;
+
+    Some(GaplessInfo {
+        leading_frames: delay + MP3_DECODER_DELAY,
+        valid_frames: frames
+            .map(|x| (x * samples_per_frame).saturating_sub(delay + padding))
+            .filter(|x| *x > 0),
+    })
+}
+
+fn read_mp3_gapless_info(file_path: &Path) -> io::Result<Option<GaplessInfo>> {
+    let mut reader = BufReader::new(File::open(file_path)?);
+
+    let mut id3 = [0; 10];
+    reader.read_exact(&mut id3)?;
+    let mut head = Vec::new();
+    if &id3[0..3] == b"ID3" {
+        // Sizes are syncsafe, seven bits per byte
+        let size = id3[6..10]
+            .iter()
+            .fold(0u64, |size, x| (size << 7) | (*x & 0x7F) as u64);
+        let footer = if id3[5] & 0x10 != 0 { 10 } else { 0 };
+        io::copy(&mut reader.by_ref().take(size + footer), &mut io::sink())?;
+    } else {
+        head.extend_from_slice(&id3);
+    }
+    reader
+        .take(MAX_FRAME_SEARCH as u64)
+        .read_to_end(&mut head)?;
+
+    let Some(start) = head
+        .windows(2)
+        .position(|x| x[0] == 0xFF && x[1] & 0xE0 == 0xE0)
+    else {
+        return Ok(None);
+    };
+
+    Ok(parse_lame_frame(&head[start..]))
+}
+
+fn read_mp4_gapless_info(file_path: &Path) -> io::Result<Option<GaplessInfo>> {
+    let mut reader = BufReader::new(File::open(file_path)?);
+    let file = Mp4File::read_from(&mut reader, ParseOptions::new().read_properties(false))
+        .map_err(io::Error::other)?;
+
+    let ident = AtomIdent::Freeform {
+        mean: Cow::Borrowed("com.apple.iTunes"),
+        name: Cow::Borrowed("iTunSMPB"),
+    };
+    let value = file.ilst().and_then(|x| x.get(&ident)).and_then(|x| {
+        x.data().find_map(|data| match data {
+            AtomData::UTF8(value) => Some(value.clone()),
+            _ => None,
+        })
+    });
+
+    Ok(value.as_deref().and_then(parse_itunes_smpb))
+}
+
+/// Read the encoder delay and padding of a lossy file.
+///
+/// # Arguments
+/// * `file_path` - The full path of the media file.
+///
+/// # Returns
+/// * `io::Result<Option<GaplessInfo>>` - The trimming of MP3 files with a
+///   LAME tag and AAC files with an `iTunSMPB` tag, `None` for other files.
+pub fn read_gapless_info(file_path: &Path) -> io::Result<Option<GaplessInfo>> {
+    let extension = file_path
+        .extension()
+        .and_then(|x| x.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    match extension.as_str() {
+        "mp3" => read_mp3_gapless_info(file_path),
+        "m4a" | "m4b" | "mp4" => read_mp4_gapless_info(file_path),
+        _ => Ok(None),
+    }
+}
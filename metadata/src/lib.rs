@@ -1,6 +1,22 @@
+pub mod chapters;
+pub mod checksum;
 pub mod crc;
+pub mod flac_encoder;
+pub mod path_tags;
+pub mod probe;
 pub mod reader;
 pub mod scanner;
+pub mod stream_info;
+pub mod tag_writer;
+pub mod transcode;
+pub mod verify;
 pub mod artist;
 pub mod describe;
-pub mod cover_art;
\ No newline at end of file
+pub mod gapless;
+pub mod cover_art;
+pub mod mosaic;
+pub mod normalize;
+pub mod palette;
+pub mod placeholder;
+#[cfg(feature = "test-support")]
+pub mod test_support;
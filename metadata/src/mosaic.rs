@@ -0,0 +1,50 @@
+use std::io::Cursor;
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageFormat, RgbImage};
+
+/// Width and height of a mosaic, in pixels.
+pub const MOSAIC_SIZE: u32 = 512;
+
+/// The most covers shown by a mosaic.
+pub const MOSAIC_TILES: usize = 4;
+
+/// Arrange covers in a 2x2 grid and encode it as JPEG.
+///
+/// Covers that can't be decoded are left out. Two or three covers are
+/// repeated so the grid stays full, with fewer a mosaic isn't worth it and
+/// `None` is returned.
+pub fn compose_mosaic(covers: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let tile_size = MOSAIC_SIZE / 2;
+    let tiles: Vec<RgbImage> = covers
+        .iter()
+        .filter_map(|x| image::load_from_memory(x).ok())
+        .take(MOSAIC_TILES)
+        .map(|x| {
+            x.resize_to_fill(tile_size, tile_size, FilterType::Triangle)
+                .to_rgb8()
+        })
+        .collect();
+
+    // Repeated covers go to opposite corners
+    let order: &[usize] = match tiles.len() {
+        0 | 1 => return None,
+        2 => &[0, 1, 1, 0],
+        3 => &[0, 1, 2, 0],
+        _ => &[0, 1, 2, 3],
+    };
+
+    let mut mosaic = RgbImage::new(MOSAIC_SIZE, MOSAIC_SIZE);
+    for (position, tile) in order.iter().enumerate() {
+        let x = (position as u32 % 2) * tile_size;
+        let y = (position as u32 / 2) * tile_size;
+        imageops::replace(&mut mosaic, &tiles[*tile], x as i64, y as i64);
+    }
+
+    let mut data = Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(mosaic)
+        .write_to(&mut data, ImageFormat::Jpeg)
+        .ok()?;
+
+    Some(data.into_inner())
+}
@@ -0,0 +1,43 @@
+use unicode_normalization::UnicodeNormalization;
+
+// Leading articles ignored when asked to, compared regardless of case
+const ARTICLES: [&str; 1] = ["the "];
+
+/// Clean up a name read from the tags: Unicode NFC, no surrounding
+/// whitespace and single spaces between words.
+pub fn clean_name(name: &str) -> String {
+    name.nfc()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Drop a leading "The" from a cleaned name, names made of the article
+/// alone are kept.
+pub fn strip_article(name: &str) -> &str {
+    for article in ARTICLES {
+        if name.len() > article.len()
+            && name.is_char_boundary(article.len())
+            && name[..article.len()].eq_ignore_ascii_case(article)
+        {
+            return &name[article.len()..];
+        }
+    }
+
+    name
+}
+
+/// The name artists and albums are grouped by, names only differing by
+/// case, whitespace, Unicode composition or, optionally, a leading "The"
+/// share it.
+pub fn canonical_name(name: &str, ignore_article: bool) -> String {
+    let name = clean_name(name);
+    let name = if ignore_article {
+        strip_article(&name)
+    } else {
+        &name
+    };
+
+    name.to_lowercase()
+}
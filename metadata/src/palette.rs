@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+// Covers are scaled down before counting, the palette doesn't need the detail
+const SAMPLE_SIZE: u32 = 64;
+// Colors closer than this (summed over the channels) count as the same color
+const MIN_DISTANCE: i32 = 64;
+
+/// The number of colors stored for every cover.
+pub const PALETTE_SIZE: usize = 5;
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> i32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (*a as i32 - *b as i32).abs())
+        .sum()
+}
+
+/// Extract the dominant colors of an encoded image, most common first.
+///
+/// Colors are returned as `0xRRGGBB`. Similar shades are merged so the
+/// palette shows distinct colors. Images that can't be decoded have an
+/// empty palette.
+pub fn extract_palette(data: &[u8], count: usize) -> Vec<u32> {
+    let image = match image::load_from_memory(data) {
+        Ok(image) => image.thumbnail(SAMPLE_SIZE, SAMPLE_SIZE).to_rgba8(),
+        Err(_) => return Vec::new(),
+    };
+
+    // Count pixels in buckets of 16 shades per channel, keeping the sums
+    // to average the colors inside every bucket
+    let mut buckets: HashMap<u16, (u32, [u32; 3])> = HashMap::new();
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a < 128 {
+            continue;
+        }
+
+        let key = ((r as u16 >> 4) << 8) | ((g as u16 >> 4) << 4) | (b as u16 >> 4);
+        let (pixels, sum) = buckets.entry(key).or_default();
+        *pixels += 1;
+        sum[0] += r as u32;
+        sum[1] += g as u32;
+        sum[2] += b as u32;
+    }
+
+    let mut buckets: Vec<(u16, u32, [u8; 3])> = buckets
+        .into_iter()
+        .map(|(key, (pixels, sum))| {
+            let color = sum.map(|x| (x / pixels) as u8);
+            (key, pixels, color)
+        })
+        .collect();
+    // The key breaks ties so the palette doesn't depend on the hash order
+    buckets.sort_by_key(|(key, pixels, _)| (std::cmp::Reverse(*pixels), *key));
+
+    let mut palette: Vec<[u8; 3]> = Vec::with_capacity(count);
+    for (_, _, color) in buckets {
+        if palette.len() >= count {
+            break;
+        }
+        if palette.iter().all(|x| distance(*x, color) >= MIN_DISTANCE) {
+            palette.push(color);
+        }
+    }
+
+    palette
+        .into_iter()
+        .map(|[r, g, b]| ((r as u32) << 16) | ((g as u32) << 8) | b as u32)
+        .collect()
+}
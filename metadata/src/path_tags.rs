@@ -0,0 +1,159 @@
+use std::path::Path;
+
+use regex::Regex;
+use thiserror::Error;
+
+/// The patterns tried when none are given, the disc folders of multi-disc
+/// rips first.
+pub const DEFAULT_PATH_PATTERNS: [&str; 4] = [
+    "{artist}/{album}/{disc}/{track} {title}",
+    "{artist}/{album}/{disc}-{track} {title}",
+    "{artist}/{album}/{track} {title}",
+    "{artist} - {album}/{track} {title}",
+];
+
+// Placeholders and the field their value is stored under
+const PLACEHOLDERS: [(&str, &str); 8] = [
+    ("artist", "artist"),
+    ("album_artist", "album_artist"),
+    ("album", "album"),
+    ("title", "track_title"),
+    ("track", "track_number"),
+    ("disc", "disc_number"),
+    ("year", "date"),
+    ("genre", "genre"),
+];
+
+#[derive(Debug, Error)]
+pub enum PathPatternError {
+    #[error("unknown placeholder {{{0}}}")]
+    UnknownPlaceholder(String),
+    #[error("placeholder is never closed")]
+    Unclosed,
+    #[error("invalid pattern: {0}")]
+    Regex(#[from] regex::Error),
+}
+
+/// A pattern reading tags from the path of a file, like
+/// `{artist}/{album}/{track} {title}`.
+///
+/// The pattern is matched against the end of the path without the
+/// extension. `/` separates directories, spaces match any run of spaces and
+/// `{disc}` also matches folders like `CD1` or `Disc 2`.
+#[derive(Debug, Clone)]
+pub struct PathPattern {
+    pattern: String,
+    regex: Regex,
+}
+
+fn placeholder_regex(name: &str) -> Option<&'static str> {
+    let regex = match name {
+        "track" => r"\d{1,3}",
+        "disc" => r"(?i:(?:cd|disc|disk)\s*)?\d{1,2}",
+        "year" => r"\d{4}",
+        _ => r"[^/]+?",
+    };
+
+    PLACEHOLDERS
+        .iter()
+        .any(|(placeholder, _)| *placeholder == name)
+        .then_some(regex)
+}
+
+// Track titles are often separated from the number by a dash or a dot
+fn clean_value(value: &str) -> &str {
+    value.trim_matches(|x: char| x.is_whitespace() || matches!(x, '-' | '.' | '_'))
+}
+
+impl PathPattern {
+    pub fn parse(pattern: &str) -> Result<Self, PathPatternError> {
+        let mut regex = String::from("(?:^|/)");
+        let mut rest = pattern;
+
+        while !rest.is_empty() {
+            if let Some(inner) = rest.strip_prefix('{') {
+                let end = inner.find('}').ok_or(PathPatternError::Unclosed)?;
+                let name = &inner[..end];
+                let group = placeholder_regex(name)
+                    .ok_or_else(|| PathPatternError::UnknownPlaceholder(name.to_string()))?;
+                regex.push_str(&format!("(?P<{}>{})", name, group));
+                rest = &inner[end + 1..];
+            } else if rest.starts_with(char::is_whitespace) {
+                regex.push_str(r"\s*");
+                rest = rest.trim_start();
+            } else {
+                let next = rest.chars().next().unwrap();
+                regex.push_str(&regex::escape(&next.to_string()));
+                rest = &rest[next.len_utf8()..];
+            }
+        }
+        regex.push('$');
+
+        Ok(PathPattern {
+            pattern: pattern.to_string(),
+            regex: Regex::new(&regex)?,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Read the tags of a file from its path.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the file, relative to the library root.
+    ///
+    /// # Returns
+    /// * `Option<Vec<(String, String)>>` - The tags by field, like
+    ///   `track_title`, `None` if the path doesn't match.
+    pub fn infer_tags(&self, path: &Path) -> Option<Vec<(String, String)>> {
+        let path = path.with_extension("");
+        let path = path.to_string_lossy().replace('\\', "/");
+        let captures = self.regex.captures(&path)?;
+
+        let mut tags = Vec::new();
+        for (placeholder, field) in PLACEHOLDERS {
+            let Some(value) = captures.name(placeholder) else {
+                continue;
+            };
+
+            let value = match placeholder {
+                // Leading zeros and disc folder names are dropped
+                "track" | "disc" => value
+                    .as_str()
+                    .trim_start_matches(|x: char| !x.is_ascii_digit())
+                    .parse::<u32>()
+                    .ok()?
+                    .to_string(),
+                _ => clean_value(value.as_str()).to_string(),
+            };
+
+            if value.is_empty() {
+                return None;
+            }
+            tags.push((field.to_string(), value));
+        }
+
+        Some(tags)
+    }
+}
+
+/// Read the tags of a file from its path with the first pattern that
+/// matches.
+///
+/// # Arguments
+/// * `patterns` - The patterns in the order they are tried.
+/// * `path` - The path of the file, relative to the library root.
+///
+/// # Returns
+/// * `Option<(&PathPattern, Vec<(String, String)>)>` - The pattern that
+///   matched and the tags by field.
+pub fn infer_path_tags<'a>(
+    patterns: &'a [PathPattern],
+    path: &Path,
+) -> Option<(&'a PathPattern, Vec<(String, String)>)> {
+    patterns
+        .iter()
+        .find_map(|pattern| pattern.infer_tags(path).map(|tags| (pattern, tags)))
+}
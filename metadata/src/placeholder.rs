@@ -0,0 +1,23 @@
+// Blurhashes only keep a few low frequency components, a small thumbnail is enough
+const SAMPLE_SIZE: u32 = 32;
+const COMPONENTS_X: u32 = 4;
+const COMPONENTS_Y: u32 = 4;
+
+/// Compute the blurhash of an encoded image, shown while the image loads.
+///
+/// Returns `None` if the image can't be decoded.
+pub fn encode_blurhash(data: &[u8]) -> Option<String> {
+    let image = image::load_from_memory(data)
+        .ok()?
+        .thumbnail(SAMPLE_SIZE, SAMPLE_SIZE)
+        .to_rgba8();
+
+    blurhash::encode(
+        COMPONENTS_X,
+        COMPONENTS_Y,
+        image.width(),
+        image.height(),
+        image.as_raw(),
+    )
+    .ok()
+}
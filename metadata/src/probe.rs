@@ -0,0 +1,102 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use thiserror::Error;
+
+// Give up if none of the first packets of the track can be decoded
+const MAX_PROBE_PACKETS: usize = 16;
+
+#[derive(Error, Debug)]
+pub enum ProbeError {
+    #[error("file not found")]
+    FileNotFound,
+    #[error("file is not readable: {0}")]
+    Unreadable(#[from] io::Error),
+    #[error("file is not decodable: {0}")]
+    Undecodable(String),
+}
+
+/// Check that a media file can be played without decoding all of it.
+///
+/// The container is probed and the first packets of the default track are
+/// decoded, which catches missing, truncated and unsupported files.
+pub fn probe_file(file_path: &Path) -> Result<(), ProbeError> {
+    if !file_path.is_file() {
+        return Err(ProbeError::FileNotFound);
+    }
+
+    let src = File::open(file_path)?;
+    if src.metadata()?.len() == 0 {
+        return Err(ProbeError::Undecodable("file is empty".to_string()));
+    }
+
+    if dsd::is_dsd_path(file_path) {
+        return probe_dsd(src);
+    }
+
+    let mss = MediaSourceStream::new(Box::new(src), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = file_path.extension().and_then(|x| x.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let fmt_opts: FormatOptions = Default::default();
+    let meta_opts: MetadataOptions = Default::default();
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, mss, &fmt_opts, &meta_opts)
+        .map_err(|e| ProbeError::Undecodable(e.to_string()))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| ProbeError::Undecodable("no supported audio track".to_string()))?;
+    let track_id = track.id;
+
+    let dec_opts: DecoderOptions = Default::default();
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &dec_opts)
+        .map_err(|e| ProbeError::Undecodable(e.to_string()))?;
+
+    let mut last_error = String::from("no audio packets");
+    for _ in 0..MAX_PROBE_PACKETS {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(e) => {
+                last_error = e.to_string();
+                break;
+            }
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(_) => return Ok(()),
+            Err(e) => last_error = e.to_string(),
+        }
+    }
+
+    Err(ProbeError::Undecodable(last_error))
+}
+
+// Converting the first samples reads the header and the first block
+fn probe_dsd(src: File) -> Result<(), ProbeError> {
+    let mut reader = dsd::DsdReader::new(io::BufReader::new(src))
+        .map_err(|e| ProbeError::Undecodable(e.to_string()))?;
+
+    match reader.next() {
+        Some(_) => Ok(()),
+        None => Err(ProbeError::Undecodable("no audio packets".to_string())),
+    }
+}
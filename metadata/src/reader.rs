@@ -3,8 +3,10 @@ use std::fs::File;
 use std::io;
 use std::path::Path;
 use symphonia::core::formats::FormatOptions;
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey, Value};
+use symphonia::core::io::{BufReader, MediaSourceStream};
+use symphonia::core::meta::{
+    MetadataBuilder, MetadataOptions, MetadataRevision, StandardTagKey, Tag, Value,
+};
 use symphonia::core::probe::Hint;
 use thiserror::Error;
 
@@ -183,11 +185,47 @@ pub fn string_to_standard_tag_key(s: &str) -> Option<StandardTagKey> {
     STRING_TO_STANDARD_TAG_KEY.get(s).cloned()
 }
 
-fn push_tags(revision: &MetadataRevision, metadata_list: &mut Vec<(String, String)>, field_blacklist: &[&str]) {
+// Tags without a standard key in Symphonia that are still worth keeping,
+// by the key they are stored with in the file
+const NON_STANDARD_TAG_KEYS: [(&str, &str); 11] = [
+    ("WORK", "work"),
+    ("TXXX:WORK", "work"),
+    // The key DJ software detected, like "Am" or "8A"
+    ("TKEY", "initial_key"),
+    ("INITIALKEY", "initial_key"),
+    ("TXXX:INITIALKEY", "initial_key"),
+    ("KEY", "initial_key"),
+    // Parental advisory, as written by iTunes and other taggers
+    ("ITUNESADVISORY", "advisory"),
+    ("TXXX:ITUNESADVISORY", "advisory"),
+    ("RTNG", "advisory"),
+    ("EXPLICIT", "advisory"),
+    ("TXXX:EXPLICIT", "advisory"),
+];
+
+fn non_standard_tag_key(key: &str) -> Option<&'static str> {
+    NON_STANDARD_TAG_KEYS
+        .iter()
+        .find(|(file_key, _)| file_key.eq_ignore_ascii_case(key))
+        .map(|(_, value)| *value)
+}
+
+fn push_tags(
+    revision: &MetadataRevision,
+    metadata_list: &mut Vec<(String, String)>,
+    field_blacklist: &[&str],
+    custom_fields: &HashMap<String, String>,
+) {
     for tag in revision.tags() {
-        let std_key = match tag.std_key {
-            Some(standard_key) => standard_tag_key_to_string(standard_key),
-            None => String::from(""),
+        let key = tag.key.to_uppercase();
+        // ID3v2 keeps user defined tags in TXXX frames named after them
+        let custom_field = custom_fields
+            .get(&key)
+            .or_else(|| custom_fields.get(key.strip_prefix("TXXX:")?));
+        let std_key = match (custom_field, tag.std_key) {
+            (Some(field), _) => field.clone(),
+            (None, Some(standard_key)) => standard_tag_key_to_string(standard_key),
+            (None, None) => non_standard_tag_key(&tag.key).unwrap_or_default().to_string(),
         };
 
         if field_blacklist.contains(&std_key.as_str()) {
@@ -215,13 +253,65 @@ pub enum MetadataError {
     Io(#[from] io::Error),
     #[error("symphonia error")]
     Symphonia(#[from] symphonia::core::errors::Error),
+    #[error("dsd error")]
+    Dsd(#[from] dsd::DsdError),
+}
+
+/// Read the tags of a DSF or DSDIFF file.
+///
+/// An ID3v2 tag is preferred, DSDIFF files without one may still carry a
+/// title and artist of their own.
+pub fn read_dsd_tags(file_path: &Path) -> Result<MetadataRevision, MetadataError> {
+    let reader = dsd::DsdReader::new(io::BufReader::new(File::open(file_path)?))?;
+    let tags = reader.tags();
+
+    let mut builder = MetadataBuilder::new();
+    if let Some(id3) = &tags.id3 {
+        symphonia_metadata::id3v2::read_id3v2(&mut BufReader::new(id3), &mut builder)?;
+    } else {
+        let fields = [
+            (StandardTagKey::TrackTitle, "DITI", &tags.title),
+            (StandardTagKey::Artist, "DIAR", &tags.artist),
+        ];
+        for (key, id, value) in fields {
+            if let Some(value) = value {
+                builder.add_tag(Tag::new(Some(key), id, Value::String(value.clone())));
+            }
+        }
+    }
+
+    Ok(builder.metadata())
 }
 
 pub fn get_metadata(file_path: &str, field_blacklist: Option<Vec<&str>>) -> Result<Vec<(String, String)>, MetadataError> {
+    get_metadata_with_custom_fields(file_path, field_blacklist, &HashMap::new())
+}
+
+/// Read the tags of a file, storing some of them under fields chosen by the user.
+///
+/// # Arguments
+/// * `file_path` - The path of the file.
+/// * `field_blacklist` - Fields that are left out, a default list if `None`.
+/// * `custom_fields` - The field of every mapped tag, by the uppercased key the tag has in
+///   the file, like `CUSTOM1`. Mapped tags don't keep their standard field.
+pub fn get_metadata_with_custom_fields(
+    file_path: &str,
+    field_blacklist: Option<Vec<&str>>,
+    custom_fields: &HashMap<String, String>,
+) -> Result<Vec<(String, String)>, MetadataError> {
     if !Path::new(file_path).exists() {
         return Err(MetadataError::FileNotFound);
     }
 
+    let blacklist = field_blacklist.unwrap_or(vec!["encoded_by", "encoder", "comment", "description"]);
+
+    // Symphonia has no format reader for DSD
+    if dsd::is_dsd_path(Path::new(file_path)) {
+        let mut metadata_list = Vec::new();
+        push_tags(&read_dsd_tags(Path::new(file_path))?, &mut metadata_list, &blacklist, custom_fields);
+        return Ok(metadata_list);
+    }
+
     // Open the media source.
     let src = File::open(file_path)?;
 
@@ -244,12 +334,10 @@ pub fn get_metadata(file_path: &str, field_blacklist: Option<Vec<&str>>) -> Resu
     let mut format = probed.format;
     let mut metadata_list = Vec::new();
 
-    let blacklist = field_blacklist.unwrap_or(vec!["encoded_by", "encoder", "comment", "description"]);
-
     if let Some(metadata_rev) = format.metadata().current() {
-        push_tags(metadata_rev, &mut metadata_list, &blacklist);
+        push_tags(metadata_rev, &mut metadata_list, &blacklist, custom_fields);
     } else if let Some(metadata_rev) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
-        push_tags(metadata_rev, &mut metadata_list, &blacklist);
+        push_tags(metadata_rev, &mut metadata_list, &blacklist, custom_fields);
     }
 
     Ok(metadata_list)
@@ -1,19 +1,24 @@
 use std::path::{Path, PathBuf};
 use walkdir::{DirEntry, WalkDir};
 
+/// Extensions of the files added to the library, every one of them must
+/// decode in the decode tests.
+pub const SUPPORTED_EXTENSIONS: [&str; 10] = [
+    "mp3", "flac", "wav", "aac", "ogg", "m4a", "aiff", "aif", "dsf", "dff",
+];
+
 fn is_audio_file(entry: &DirEntry) -> bool {
     if let Some(ext) = entry.path().extension() {
-        matches!(
-            ext.to_str().unwrap_or("").to_lowercase().as_str(),
-            "mp3" | "flac" | "wav" | "aac" | "ogg" | "m4a"
-        )
+        SUPPORTED_EXTENSIONS.contains(&ext.to_str().unwrap_or("").to_lowercase().as_str())
     } else {
         false
     }
 }
 
 fn scan_audio_files<P: AsRef<Path>>(path: &P) -> impl Iterator<Item = DirEntry> + Send {
+    // A stable order lets an interrupted scan resume where it stopped
     WalkDir::new(path)
+        .sort_by_file_name()
         .into_iter()
         .filter_map(Result::ok)
         .filter(|entry| entry.file_type().is_file() && is_audio_file(entry))
@@ -23,6 +28,7 @@ pub struct AudioScanner<'a> {
     root_path: PathBuf,
     iterator: Box<dyn Iterator<Item = DirEntry> + Send + 'a>,
     ended: bool,
+    resume_after: Option<PathBuf>,
 }
 
 impl<'a> AudioScanner<'a> {
@@ -31,13 +37,28 @@ impl<'a> AudioScanner<'a> {
             root_path: path.as_ref().to_path_buf(),
             iterator: Box::new(scan_audio_files(path)),
             ended: false,
+            resume_after: None,
         }
     }
 
+    /// Skip every file up to and including `path`, relative to the root.
+    ///
+    /// Files are visited in the order of their paths, so the files before
+    /// the checkpoint of an interrupted scan are the ones already processed.
+    pub fn resume_after<P: AsRef<Path>>(&mut self, path: P) {
+        self.resume_after = Some(self.root_path.join(path));
+    }
+
     pub fn read_files(&mut self, count: usize) -> Vec<DirEntry> {
         let mut files = Vec::new();
-        for _ in 0..count {
+        while files.len() < count {
             if let Some(file) = self.iterator.next() {
+                if let Some(checkpoint) = &self.resume_after {
+                    if file.path() <= checkpoint.as_path() {
+                        continue;
+                    }
+                    self.resume_after = None;
+                }
                 files.push(file);
             } else {
                 self.ended = true;
@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use symphonia::core::codecs::CODEC_TYPE_NULL;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::reader::MetadataError;
+
+/// How the audio of a file is encoded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamInfo {
+    /// Short name of the codec, like `flac` or `mp3`.
+    pub codec: String,
+    /// The DSD rate for DSD files.
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: Option<u32>,
+    /// Average bitrate in bits per second, from the size of the file.
+    pub bitrate: Option<u32>,
+    /// Duration in seconds.
+    pub duration: f64,
+}
+
+fn average_bitrate(size: u64, duration: f64) -> Option<u32> {
+    (duration > 0.0).then(|| (size as f64 * 8.0 / duration).round() as u32)
+}
+
+fn read_dsd_stream_info(file_path: &Path, size: u64) -> Result<StreamInfo, MetadataError> {
+    let reader = dsd::DsdReader::new(BufReader::new(File::open(file_path)?))?;
+    let info = reader.info();
+    let duration = info.duration().as_secs_f64();
+
+    Ok(StreamInfo {
+        codec: "dsd".to_string(),
+        sample_rate: info.dsd_rate,
+        channels: info.channels,
+        bits_per_sample: Some(1),
+        bitrate: average_bitrate(size, duration),
+        duration,
+    })
+}
+
+/// Read how the audio of a media file is encoded.
+///
+/// # Returns
+/// * `Result<StreamInfo, MetadataError>` - The encoding of the first audio track.
+pub fn read_stream_info(file_path: &Path) -> Result<StreamInfo, MetadataError> {
+    if !file_path.exists() {
+        return Err(MetadataError::FileNotFound);
+    }
+
+    let src = File::open(file_path)?;
+    let size = src.metadata()?.len();
+
+    if dsd::is_dsd_path(file_path) {
+        return read_dsd_stream_info(file_path, size);
+    }
+
+    let mss = MediaSourceStream::new(Box::new(src), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = file_path.extension().and_then(|x| x.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let fmt_opts: FormatOptions = Default::default();
+    let meta_opts: MetadataOptions = Default::default();
+
+    let probed = symphonia::default::get_probe().format(&hint, mss, &fmt_opts, &meta_opts)?;
+    let track = probed
+        .format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(symphonia::core::errors::Error::Unsupported(
+            "no supported audio track",
+        ))?;
+    let params = &track.codec_params;
+
+    let codec = symphonia::default::get_codecs()
+        .get_codec(params.codec)
+        .map(|x| x.short_name.to_string())
+        .unwrap_or_default();
+    let sample_rate = params.sample_rate.unwrap_or_default();
+    let duration = match (params.n_frames, sample_rate) {
+        (Some(frames), rate) if rate > 0 => frames as f64 / rate as f64,
+        _ => 0.0,
+    };
+
+    Ok(StreamInfo {
+        codec,
+        sample_rate,
+        channels: params
+            .channels
+            .map(|x| x.count() as u16)
+            .unwrap_or_default(),
+        bits_per_sample: params.bits_per_sample,
+        bitrate: average_bitrate(size, duration),
+        duration,
+    })
+}
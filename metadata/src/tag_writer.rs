@@ -0,0 +1,87 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use lofty::config::WriteOptions;
+use lofty::error::LoftyError;
+use lofty::file::TaggedFileExt;
+use lofty::tag::{Accessor, ItemKey, Tag, TagExt};
+
+// Room left for tags after the padding block added to bare FLAC files
+const FLAC_PADDING: usize = 1024;
+
+/// Write tags into a media file, creating the tag the format uses if the
+/// file has none.
+///
+/// # Arguments
+/// * `file_path` - The full path of the media file.
+/// * `tags` - The tags by field, like `track_title`. Only `artist`,
+///   `album_artist`, `album`, `track_title`, `track_number`, `disc_number`,
+///   `date` and `genre` are written, other fields are ignored.
+///
+/// # Returns
+/// * `Result<(), LoftyError>` - Nothing if the tags were saved.
+pub fn write_tags(file_path: &Path, tags: &[(String, String)]) -> Result<(), LoftyError> {
+    add_flac_padding(file_path)?;
+
+    let mut tagged_file = lofty::read_from_path(file_path)?;
+
+    if tagged_file.primary_tag().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let tag = tagged_file
+        .primary_tag_mut()
+        .expect("the primary tag was just inserted");
+
+    for (field, value) in tags {
+        match field.as_str() {
+            "artist" => tag.set_artist(value.clone()),
+            "album" => tag.set_album(value.clone()),
+            "track_title" => tag.set_title(value.clone()),
+            "genre" => tag.set_genre(value.clone()),
+            "album_artist" => {
+                tag.insert_text(ItemKey::AlbumArtist, value.clone());
+            }
+            "date" => {
+                tag.insert_text(ItemKey::RecordingDate, value.clone());
+            }
+            "track_number" => {
+                if let Ok(track) = value.parse() {
+                    tag.set_track(track);
+                }
+            }
+            "disc_number" => {
+                if let Ok(disc) = value.parse() {
+                    tag.set_disk(disc);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    tag.save_to_path(file_path, WriteOptions::default())
+}
+
+// lofty writes tags of FLAC files whose only metadata block is STREAMINFO
+// over the first audio frame, so such files get a padding block first
+fn add_flac_padding(file_path: &Path) -> io::Result<()> {
+    let mut bytes = fs::read(file_path)?;
+    // The STREAMINFO header follows the marker, its first bit flags the last
+    // metadata block
+    if bytes.len() < 8 || &bytes[..4] != b"fLaC" || bytes[4] & 0x80 == 0 {
+        return Ok(());
+    }
+    let end = 8 + u32::from_be_bytes([0, bytes[5], bytes[6], bytes[7]]) as usize;
+    if bytes.len() < end {
+        return Ok(());
+    }
+
+    bytes[4] &= 0x7F;
+    let mut padding = vec![0; 4 + FLAC_PADDING];
+    padding[0] = 0x80 | 1;
+    padding[1..4].copy_from_slice(&(FLAC_PADDING as u32).to_be_bytes()[1..]);
+    bytes.splice(end..end, padding);
+
+    fs::write(file_path, bytes)
+}
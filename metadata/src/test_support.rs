@@ -0,0 +1,290 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Container formats that can be written without an encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureFormat {
+    Wav,
+    Aiff,
+    Flac,
+}
+
+impl FixtureFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FixtureFormat::Wav => "wav",
+            FixtureFormat::Aiff => "aiff",
+            FixtureFormat::Flac => "flac",
+        }
+    }
+}
+
+/// A 440 Hz tone over a 110 Hz one, interleaved 16-bit samples.
+pub fn sine_samples(sample_rate: u32, channels: u16, frames: usize) -> Vec<i16> {
+    (0..frames)
+        .flat_map(|n| {
+            let t = n as f32 / sample_rate as f32;
+            let value = (t * 440.0 * std::f32::consts::TAU).sin() * 0.4
+                + (t * 110.0 * std::f32::consts::TAU).sin() * 0.2;
+            std::iter::repeat_n((value * i16::MAX as f32) as i16, channels as usize)
+        })
+        .collect()
+}
+
+/// Write a sine tone as an uncompressed 16-bit file of the given format.
+pub fn write_sine_fixture(
+    path: &Path,
+    format: FixtureFormat,
+    sample_rate: u32,
+    channels: u16,
+    frames: usize,
+) -> io::Result<()> {
+    let samples = sine_samples(sample_rate, channels, frames);
+
+    let bytes = match format {
+        FixtureFormat::Wav => wav(sample_rate, channels, &samples),
+        FixtureFormat::Aiff => aiff(sample_rate, channels, &samples),
+        FixtureFormat::Flac => flac(sample_rate, channels, &samples, &[]),
+    };
+
+    fs::write(path, bytes)
+}
+
+/// Write a sine tone as FLAC with a cuesheet, one track starting at every
+/// given frame.
+pub fn write_cued_flac_fixture(
+    path: &Path,
+    sample_rate: u32,
+    channels: u16,
+    frames: usize,
+    track_starts: &[u64],
+) -> io::Result<()> {
+    let samples = sine_samples(sample_rate, channels, frames);
+    fs::write(path, flac(sample_rate, channels, &samples, track_starts))
+}
+
+// A 128 kbps 44.1 kHz stereo MPEG-1 layer III frame without padding
+const MP3_FRAME_HEADER: [u8; 4] = [0xFF, 0xFB, 0x90, 0x00];
+const MP3_FRAME_LEN: usize = 417;
+
+/// Write a silent 44.1 kHz stereo MP3, behind an Info frame with the LAME
+/// tag of the given encoder delay and padding, as LAME 3.100 writes it.
+pub fn write_lame_mp3_fixture(
+    path: &Path,
+    frames: u32,
+    delay: u16,
+    padding: u16,
+) -> io::Result<()> {
+    let mut info = MP3_FRAME_HEADER.to_vec();
+    info.extend([0; 32]);
+    info.extend(b"Info");
+    // Frame count and byte count
+    info.extend(0x03u32.to_be_bytes());
+    info.extend(frames.to_be_bytes());
+    info.extend((((frames as usize + 1) * MP3_FRAME_LEN) as u32).to_be_bytes());
+    info.extend(b"LAME3.100");
+    info.extend([0; 12]);
+    info.extend([
+        (delay >> 4) as u8,
+        ((delay & 0x0F) << 4) as u8 | (padding >> 8) as u8,
+        padding as u8,
+    ]);
+    // Flags, gain, preset, music length and music checksum
+    info.extend([0; 10]);
+    let crc = crc16_arc(&info);
+    info.extend(crc.to_be_bytes());
+    info.resize(MP3_FRAME_LEN, 0);
+
+    // Side information and main data of zero decode to silence
+    let mut silence = MP3_FRAME_HEADER.to_vec();
+    silence.resize(MP3_FRAME_LEN, 0);
+
+    let mut out = info;
+    for _ in 0..frames {
+        out.extend(&silence);
+    }
+    fs::write(path, out)
+}
+
+// The checksum ending the LAME tag
+fn crc16_arc(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u16, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+fn wav(sample_rate: u32, channels: u16, samples: &[i16]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let block_align = channels * 2;
+
+    let mut out = Vec::new();
+    out.extend(b"RIFF");
+    out.extend((36 + data_len).to_le_bytes());
+    out.extend(b"WAVEfmt ");
+    out.extend(16u32.to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend(channels.to_le_bytes());
+    out.extend(sample_rate.to_le_bytes());
+    out.extend((sample_rate * block_align as u32).to_le_bytes());
+    out.extend(block_align.to_le_bytes());
+    out.extend(16u16.to_le_bytes());
+    out.extend(b"data");
+    out.extend(data_len.to_le_bytes());
+    for sample in samples {
+        out.extend(sample.to_le_bytes());
+    }
+    out
+}
+
+// The sample rate of AIFF is an 80-bit extended float
+fn extended(value: u32) -> [u8; 10] {
+    let exponent = 31 - value.leading_zeros();
+    let mantissa = (value as u64) << (63 - exponent);
+
+    let mut out = [0u8; 10];
+    out[..2].copy_from_slice(&(16383 + exponent as u16).to_be_bytes());
+    out[2..].copy_from_slice(&mantissa.to_be_bytes());
+    out
+}
+
+fn aiff(sample_rate: u32, channels: u16, samples: &[i16]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let frames = samples.len() as u32 / channels as u32;
+
+    let mut out = Vec::new();
+    out.extend(b"FORM");
+    out.extend((4 + 26 + 16 + data_len).to_be_bytes());
+    out.extend(b"AIFFCOMM");
+    out.extend(18u32.to_be_bytes());
+    out.extend(channels.to_be_bytes());
+    out.extend(frames.to_be_bytes());
+    out.extend(16u16.to_be_bytes());
+    out.extend(extended(sample_rate));
+    out.extend(b"SSND");
+    out.extend((8 + data_len).to_be_bytes());
+    out.extend(0u32.to_be_bytes());
+    out.extend(0u32.to_be_bytes());
+    for sample in samples {
+        out.extend(sample.to_be_bytes());
+    }
+    out
+}
+
+const FLAC_BLOCK_SIZE: usize = 4096;
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+// Frame numbers are written like UTF-8 code points
+fn utf8_number(value: u32) -> Vec<u8> {
+    char::from_u32(value)
+        .expect("frame number out of range")
+        .to_string()
+        .into_bytes()
+}
+
+// A cuesheet that isn't from a CD, so tracks may start at any frame
+fn cuesheet(track_starts: &[u64], frames: usize) -> Vec<u8> {
+    let mut out = vec![0u8; 128];
+    out.extend(0u64.to_be_bytes());
+    out.push(0);
+    out.extend([0u8; 258]);
+    out.push(track_starts.len() as u8 + 1);
+
+    for (index, start) in track_starts.iter().enumerate() {
+        out.extend(start.to_be_bytes());
+        out.push(index as u8 + 1);
+        out.extend([0u8; 26]);
+        // One index point at the start of the track
+        out.push(1);
+        out.extend(0u64.to_be_bytes());
+        out.extend(0x0100_0000u32.to_be_bytes());
+    }
+
+    // The lead-out track marks the end of the audio
+    out.extend((frames as u64).to_be_bytes());
+    out.push(255);
+    out.extend([0u8; 26]);
+    out.push(0);
+    out
+}
+
+// FLAC with verbatim subframes, valid but not compressed
+fn flac(sample_rate: u32, channels: u16, samples: &[i16], track_starts: &[u64]) -> Vec<u8> {
+    let channels = channels as usize;
+    let frames = samples.len() / channels;
+
+    let mut out = Vec::new();
+    out.extend(b"fLaC");
+    // STREAMINFO, the last metadata block unless a cuesheet follows
+    out.push(if track_starts.is_empty() { 0x80 } else { 0x00 });
+    out.extend(&34u32.to_be_bytes()[1..]);
+    out.extend((FLAC_BLOCK_SIZE as u16).to_be_bytes());
+    out.extend((FLAC_BLOCK_SIZE as u16).to_be_bytes());
+    out.extend([0u8; 6]);
+    let packed = ((sample_rate as u64) << 44)
+        | (((channels - 1) as u64) << 41)
+        | (15u64 << 36)
+        | frames as u64;
+    out.extend(packed.to_be_bytes());
+    out.extend([0u8; 16]);
+
+    if !track_starts.is_empty() {
+        let cuesheet = cuesheet(track_starts, frames);
+        out.push(0x85);
+        out.extend(&(cuesheet.len() as u32).to_be_bytes()[1..]);
+        out.extend(cuesheet);
+    }
+
+    for (index, block) in samples.chunks(FLAC_BLOCK_SIZE * channels).enumerate() {
+        let block_frames = block.len() / channels;
+
+        let mut frame = vec![0xFF, 0xF8, 0x70, (((channels - 1) as u8) << 4) | 0x08];
+        frame.extend(utf8_number(index as u32));
+        frame.extend(((block_frames - 1) as u16).to_be_bytes());
+        frame.push(crc8(&frame));
+
+        for channel in 0..channels {
+            frame.push(0x02);
+            for sample in block.iter().skip(channel).step_by(channels) {
+                frame.extend(sample.to_be_bytes());
+            }
+        }
+        frame.extend(crc16(&frame).to_be_bytes());
+
+        out.extend(frame);
+    }
+    out
+}
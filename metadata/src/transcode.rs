@@ -0,0 +1,190 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use thiserror::Error;
+
+use crate::flac_encoder::FlacWriter;
+
+/// Codecs files can be transcoded to, always as 16-bit audio. FLAC is the
+/// compressed target for streaming and device sync. Lossy codecs such as
+/// Opus, and with them a choice of bitrate, are out of scope: there is no
+/// encoder for them among the dependencies.
+pub const TRANSCODE_CODECS: [&str; 2] = ["flac", "wav"];
+
+#[derive(Error, Debug)]
+pub enum TranscodeError {
+    #[error("can't transcode to {0}")]
+    UnsupportedCodec(String),
+    #[error("file is not readable: {0}")]
+    Unreadable(#[from] io::Error),
+    #[error("file is not decodable: {0}")]
+    Undecodable(String),
+    #[error("failed to write the transcoded file: {0}")]
+    Write(#[from] hound::Error),
+    #[error("failed to encode the transcoded file: {0}")]
+    Encode(io::Error),
+}
+
+// Both encoders take interleaved 16-bit samples
+enum Writer {
+    Wav(hound::WavWriter<BufWriter<File>>),
+    Flac(FlacWriter<BufWriter<File>>),
+}
+
+impl Writer {
+    fn create(
+        dst: &Path,
+        codec: &str,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<Self, TranscodeError> {
+        if codec.eq_ignore_ascii_case("flac") {
+            let file = BufWriter::new(File::create(dst).map_err(TranscodeError::Encode)?);
+            let writer =
+                FlacWriter::new(file, sample_rate, channels).map_err(TranscodeError::Encode)?;
+            return Ok(Writer::Flac(writer));
+        }
+
+        Ok(Writer::Wav(hound::WavWriter::create(
+            dst,
+            hound::WavSpec {
+                channels,
+                sample_rate,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            },
+        )?))
+    }
+
+    fn write(&mut self, samples: &[i16]) -> Result<(), TranscodeError> {
+        match self {
+            Writer::Wav(writer) => {
+                for sample in samples {
+                    writer.write_sample(*sample)?;
+                }
+                Ok(())
+            }
+            Writer::Flac(writer) => writer
+                .write_samples(samples)
+                .map_err(TranscodeError::Encode),
+        }
+    }
+
+    fn finalize(self) -> Result<(), TranscodeError> {
+        match self {
+            Writer::Wav(writer) => Ok(writer.finalize()?),
+            Writer::Flac(writer) => writer
+                .finalize()
+                .map(|_| ())
+                .map_err(TranscodeError::Encode),
+        }
+    }
+}
+
+/// Whether files can be transcoded to a codec.
+pub fn is_transcode_codec(codec: &str) -> bool {
+    TRANSCODE_CODECS
+        .iter()
+        .any(|x| x.eq_ignore_ascii_case(codec))
+}
+
+/// Decode a media file and write it again with another codec.
+///
+/// # Arguments
+/// * `src` - The file to transcode.
+/// * `dst` - Where to write the transcoded file, replaced if it exists.
+/// * `codec` - One of `TRANSCODE_CODECS`, which is also the extension to give `dst`.
+pub fn transcode_file(src: &Path, dst: &Path, codec: &str) -> Result<(), TranscodeError> {
+    if !is_transcode_codec(codec) {
+        return Err(TranscodeError::UnsupportedCodec(codec.to_string()));
+    }
+    if dsd::is_dsd_path(src) {
+        return Err(TranscodeError::Undecodable(
+            "DSD files can't be transcoded".to_string(),
+        ));
+    }
+
+    let mss = MediaSourceStream::new(Box::new(File::open(src)?), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = src.extension().and_then(|x| x.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let fmt_opts: FormatOptions = Default::default();
+    let meta_opts: MetadataOptions = Default::default();
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, mss, &fmt_opts, &meta_opts)
+        .map_err(|e| TranscodeError::Undecodable(e.to_string()))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| TranscodeError::Undecodable("no supported audio track".to_string()))?;
+    let track_id = track.id;
+
+    let dec_opts: DecoderOptions = Default::default();
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &dec_opts)
+        .map_err(|e| TranscodeError::Undecodable(e.to_string()))?;
+
+    // The writer is created with the first decoded packet, which tells the
+    // layout of the audio even when the container doesn't
+    let mut writer: Option<Writer> = None;
+    let mut buffer: Option<SampleBuffer<i16>> = None;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(TranscodeError::Undecodable(e.to_string())),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupted packet is left out, like during playback
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(TranscodeError::Undecodable(e.to_string())),
+        };
+
+        let spec = *decoded.spec();
+        if writer.is_none() {
+            writer = Some(Writer::create(
+                dst,
+                codec,
+                spec.rate,
+                spec.channels.count() as u16,
+            )?);
+        }
+
+        let buffer = match &mut buffer {
+            Some(buffer) if buffer.capacity() >= decoded.capacity() * spec.channels.count() => {
+                buffer
+            }
+            _ => buffer.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+        };
+        buffer.copy_interleaved_ref(decoded);
+
+        writer.as_mut().unwrap().write(buffer.samples())?;
+    }
+
+    match writer {
+        Some(writer) => writer.finalize(),
+        None => Err(TranscodeError::Undecodable("no audio packets".to_string())),
+    }
+}
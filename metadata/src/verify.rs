@@ -0,0 +1,159 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::probe::ProbeError;
+
+// Decoders may stop a packet short of the length in the header, that is no
+// reason to call a file truncated
+const TRUNCATION_TOLERANCE: f64 = 0.01;
+
+/// What decoding a whole file found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyStatus {
+    /// Every packet decoded and the embedded checksum, if any, matched.
+    Ok,
+    /// The audio ends well before the length the header announces.
+    Truncated {
+        decoded_frames: u64,
+        expected_frames: u64,
+    },
+    /// The decoded audio doesn't match the checksum stored in the file, like
+    /// the MD5 of FLAC.
+    ChecksumMismatch,
+    /// Packets failed to decode, holds the first error.
+    Corrupt(String),
+}
+
+impl VerifyStatus {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, VerifyStatus::Ok)
+    }
+}
+
+fn is_truncated(decoded_frames: u64, expected_frames: u64) -> bool {
+    let tolerance = (expected_frames as f64 * TRUNCATION_TOLERANCE) as u64;
+    decoded_frames + tolerance < expected_frames
+}
+
+/// Decode a whole media file to find damage that probing misses.
+///
+/// This is much slower than `probe_file`, every packet is decoded and the
+/// checksum of formats that embed one is compared.
+///
+/// # Arguments
+/// * `file_path` - The full path of the media file.
+///
+/// # Returns
+/// * `Result<VerifyStatus, ProbeError>` - What decoding found, an error if
+///   the file can't be opened as audio at all.
+pub fn verify_file(file_path: &Path) -> Result<VerifyStatus, ProbeError> {
+    if !file_path.is_file() {
+        return Err(ProbeError::FileNotFound);
+    }
+
+    let src = File::open(file_path)?;
+    if dsd::is_dsd_path(file_path) {
+        return verify_dsd(src);
+    }
+
+    let mss = MediaSourceStream::new(Box::new(src), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = file_path.extension().and_then(|x| x.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let fmt_opts: FormatOptions = Default::default();
+    let meta_opts: MetadataOptions = Default::default();
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, mss, &fmt_opts, &meta_opts)
+        .map_err(|e| ProbeError::Undecodable(e.to_string()))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| ProbeError::Undecodable("no supported audio track".to_string()))?;
+    let track_id = track.id;
+    let expected_frames = track.codec_params.n_frames;
+
+    let dec_opts = DecoderOptions { verify: true };
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &dec_opts)
+        .map_err(|e| ProbeError::Undecodable(e.to_string()))?;
+
+    let mut decoded_frames: u64 = 0;
+    let mut first_error = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            // The end of the stream
+            Err(Error::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => {
+                first_error.get_or_insert(e.to_string());
+                break;
+            }
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(decoded) => decoded_frames += decoded.frames() as u64,
+            Err(e) => {
+                first_error.get_or_insert(e.to_string());
+            }
+        }
+    }
+
+    // A cut off file usually ends with a broken packet as well
+    if let Some(expected_frames) = expected_frames {
+        if is_truncated(decoded_frames, expected_frames) {
+            return Ok(VerifyStatus::Truncated {
+                decoded_frames,
+                expected_frames,
+            });
+        }
+    }
+
+    if let Some(error) = first_error {
+        return Ok(VerifyStatus::Corrupt(error));
+    }
+
+    if decoder.finalize().verify_ok == Some(false) {
+        return Ok(VerifyStatus::ChecksumMismatch);
+    }
+
+    Ok(VerifyStatus::Ok)
+}
+
+// DSD has no checksum, converting every sample finds short files
+fn verify_dsd(src: File) -> Result<VerifyStatus, ProbeError> {
+    let reader = dsd::DsdReader::new(io::BufReader::new(src))
+        .map_err(|e| ProbeError::Undecodable(e.to_string()))?;
+    let info = reader.info();
+
+    let samples = reader.count() as u64;
+    let decoded_frames = samples / info.channels.max(1) as u64;
+    let expected_frames = info.pcm_frames();
+
+    if is_truncated(decoded_frames, expected_frames) {
+        return Ok(VerifyStatus::Truncated {
+            decoded_frames,
+            expected_frames,
+        });
+    }
+
+    Ok(VerifyStatus::Ok)
+}
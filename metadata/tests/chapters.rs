@@ -0,0 +1,46 @@
+use metadata::chapters::{read_chapters, Chapter};
+use metadata::describe::describe_file;
+use metadata::test_support::{write_cued_flac_fixture, write_sine_fixture, FixtureFormat};
+
+const SAMPLE_RATE: u32 = 44100;
+
+#[test]
+fn cuesheet_tracks_become_chapters() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("album.flac");
+    write_cued_flac_fixture(&path, SAMPLE_RATE, 2, 88200, &[0, 44100, 66150]).unwrap();
+
+    let chapters = read_chapters(&path).unwrap();
+    let chapter = |number, start, end| Chapter {
+        number,
+        title: None,
+        start,
+        end,
+    };
+    assert_eq!(
+        chapters,
+        vec![
+            chapter(1, 0.0, 1.0),
+            chapter(2, 1.0, 1.5),
+            chapter(3, 1.5, 2.0)
+        ]
+    );
+
+    // The file itself still decodes as a whole
+    let mut description = describe_file(&path, dir.path()).unwrap();
+    let (_, duration) = description.get_codec_information().unwrap();
+    assert!((duration - 2.0).abs() < 0.01);
+}
+
+#[test]
+fn plain_files_have_no_chapters() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for format in [FixtureFormat::Wav, FixtureFormat::Flac] {
+        let path = dir.path().join(format!("track.{}", format.extension()));
+        write_sine_fixture(&path, format, SAMPLE_RATE, 2, 44100).unwrap();
+        assert_eq!(read_chapters(&path).unwrap(), vec![], "{:?}", format);
+    }
+
+    assert!(read_chapters(&dir.path().join("missing.flac")).is_err());
+}
@@ -0,0 +1,43 @@
+use std::fs;
+
+use metadata::checksum::{read_embedded_checksum, EmbeddedChecksum};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+// The MD5 of the audio follows the other fields of STREAMINFO
+const FLAC_MD5_OFFSET: usize = 26;
+
+#[test]
+fn flac_md5_is_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tone.flac");
+    write_sine_fixture(&path, FixtureFormat::Flac, 44100, 2, 4410).unwrap();
+
+    // The fixture leaves the MD5 unset
+    assert_eq!(read_embedded_checksum(&path).unwrap(), None);
+
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[FLAC_MD5_OFFSET..FLAC_MD5_OFFSET + 16].fill(0xAB);
+    fs::write(&path, &bytes).unwrap();
+
+    let checksum = read_embedded_checksum(&path).unwrap().unwrap();
+    assert_eq!(checksum, EmbeddedChecksum::FlacMd5([0xAB; 16]));
+    assert_eq!(checksum.kind(), "flac_md5");
+    assert_eq!(checksum.to_hex(), "ab".repeat(16));
+
+    // Behind an ID3v2 tag with 5 bytes of content
+    let mut tagged = b"ID3\x03\x00\x00\x00\x00\x00\x05hello".to_vec();
+    tagged.extend_from_slice(&bytes);
+    fs::write(&path, tagged).unwrap();
+    assert_eq!(read_embedded_checksum(&path).unwrap(), Some(checksum));
+}
+
+#[test]
+fn other_formats_have_no_checksum() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for format in [FixtureFormat::Wav, FixtureFormat::Aiff] {
+        let path = dir.path().join(format!("tone.{}", format.extension()));
+        write_sine_fixture(&path, format, 44100, 2, 4410).unwrap();
+        assert_eq!(read_embedded_checksum(&path).unwrap(), None, "{:?}", format);
+    }
+}
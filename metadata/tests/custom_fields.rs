@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use dsd::test_support::{write_tone_fixture, DsdFormat};
+use metadata::reader::{get_metadata, get_metadata_with_custom_fields};
+
+#[test]
+fn mapped_tags_are_stored_under_their_field() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tagged.dsf");
+    let tags = [
+        ("TIT2", "Sonata"),
+        ("TXXX", "CUSTOM1\0Live"),
+        ("TXXX", "WORK\0Piano Sonata No. 14"),
+    ];
+    write_tone_fixture(&path, DsdFormat::Dsf, 2_822_400, 1, 10_000, &tags).unwrap();
+    let path = path.to_str().unwrap();
+
+    // Tags nobody asked for keep no field, known ones get theirs
+    assert_eq!(
+        get_metadata(path, None).unwrap(),
+        vec![
+            ("track_title".to_string(), "Sonata".to_string()),
+            (String::new(), "Live".to_string()),
+            ("work".to_string(), "Piano Sonata No. 14".to_string()),
+        ]
+    );
+
+    let custom_fields = HashMap::from([
+        ("CUSTOM1".to_string(), "recording".to_string()),
+        ("TIT2".to_string(), "original_title".to_string()),
+    ]);
+    assert_eq!(
+        get_metadata_with_custom_fields(path, None, &custom_fields).unwrap(),
+        vec![
+            ("original_title".to_string(), "Sonata".to_string()),
+            ("recording".to_string(), "Live".to_string()),
+            ("work".to_string(), "Piano Sonata No. 14".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn advisory_tags_are_stored_under_one_field() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("explicit.dsf");
+    let tags = [("TIT2", "Song"), ("TXXX", "ITUNESADVISORY\x001")];
+    write_tone_fixture(&path, DsdFormat::Dsf, 2_822_400, 1, 10_000, &tags).unwrap();
+
+    assert_eq!(
+        get_metadata(path.to_str().unwrap(), None).unwrap(),
+        vec![
+            ("track_title".to_string(), "Song".to_string()),
+            ("advisory".to_string(), "1".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn musical_keys_are_stored_under_one_field() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("keyed.dsf");
+    let tags = [("TIT2", "Song"), ("TKEY", "Am")];
+    write_tone_fixture(&path, DsdFormat::Dsf, 2_822_400, 1, 10_000, &tags).unwrap();
+
+    assert_eq!(
+        get_metadata(path.to_str().unwrap(), None).unwrap(),
+        vec![
+            ("track_title".to_string(), "Song".to_string()),
+            ("initial_key".to_string(), "Am".to_string()),
+        ]
+    );
+}
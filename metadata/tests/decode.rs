@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+
+use symphonia::core::codecs::{
+    CodecType, CODEC_TYPE_AAC, CODEC_TYPE_ALAC, CODEC_TYPE_FLAC, CODEC_TYPE_MP3, CODEC_TYPE_OPUS,
+    CODEC_TYPE_PCM_S16BE, CODEC_TYPE_PCM_S16LE, CODEC_TYPE_VORBIS,
+};
+
+use analysis::analysis::analyze_audio;
+use dsd::test_support::{write_tone_fixture, DsdFormat};
+use metadata::cover_art::extract_cover_art_binary;
+use metadata::describe::describe_file;
+use metadata::reader::get_metadata;
+use metadata::scanner::SUPPORTED_EXTENSIONS;
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+const SAMPLE_RATE: u32 = 44100;
+const FRAMES: usize = 44100 * 2;
+
+// The codec every supported extension is decoded with
+const CODECS: [(&str, CodecType); 8] = [
+    ("mp3", CODEC_TYPE_MP3),
+    ("flac", CODEC_TYPE_FLAC),
+    ("wav", CODEC_TYPE_PCM_S16LE),
+    ("aac", CODEC_TYPE_AAC),
+    ("ogg", CODEC_TYPE_VORBIS),
+    ("m4a", CODEC_TYPE_AAC),
+    ("aiff", CODEC_TYPE_PCM_S16BE),
+    ("aif", CODEC_TYPE_PCM_S16BE),
+];
+
+fn fixture(dir: &Path, format: FixtureFormat, channels: u16) -> PathBuf {
+    let path = dir.join(format!("tone_{}.{}", channels, format.extension()));
+    write_sine_fixture(&path, format, SAMPLE_RATE, channels, FRAMES).unwrap();
+    path
+}
+
+#[test]
+fn supported_extensions_have_decoders() {
+    let codecs = symphonia::default::get_codecs();
+
+    for extension in SUPPORTED_EXTENSIONS {
+        // Converted to PCM by the dsd crate, see below
+        if dsd::EXTENSIONS.contains(&extension) {
+            continue;
+        }
+
+        let (_, codec) = CODECS
+            .iter()
+            .find(|(x, _)| *x == extension)
+            .unwrap_or_else(|| panic!("no codec listed for .{}", extension));
+        assert!(
+            codecs.get_codec(*codec).is_some(),
+            ".{} is supported without a decoder",
+            extension
+        );
+    }
+}
+
+#[test]
+fn formats_without_decoders_are_not_supported() {
+    let codecs = symphonia::default::get_codecs();
+
+    // Symphonia can't decode these yet, list them once a decoder is enabled
+    for codec in [CODEC_TYPE_OPUS, CODEC_TYPE_ALAC] {
+        assert!(codecs.get_codec(codec).is_none());
+    }
+    for extension in ["opus", "ape", "wv"] {
+        assert!(!SUPPORTED_EXTENSIONS.contains(&extension));
+    }
+}
+
+#[test]
+fn fixtures_are_described_and_analysed() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for format in [FixtureFormat::Wav, FixtureFormat::Aiff, FixtureFormat::Flac] {
+        for channels in [1, 2] {
+            let path = fixture(dir.path(), format, channels);
+
+            let mut description = describe_file(&path, dir.path()).unwrap();
+            assert_eq!(description.extension, format.extension());
+
+            let (sample_rate, duration) = description.get_codec_information().unwrap();
+            assert_eq!(sample_rate, SAMPLE_RATE, "{:?}", format);
+            assert!((duration - 2.0).abs() < 0.01, "{:?}: {}", format, duration);
+
+            get_metadata(path.to_str().unwrap(), None).unwrap();
+
+            let result = analyze_audio(path.to_str().unwrap(), 1024, 512);
+            assert!(result.spectral_centroid.is_finite(), "{:?}", format);
+            assert!(result.spectral_centroid > 0.0, "{:?}", format);
+        }
+    }
+}
+
+#[test]
+fn fixture_formats_decode_to_the_same_spectrum() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let centroids: Vec<f32> = [FixtureFormat::Wav, FixtureFormat::Aiff, FixtureFormat::Flac]
+        .into_iter()
+        .map(|format| {
+            // Analysis reads stereo plane by plane per packet, so only mono
+            // is independent of how each container splits packets
+            let path = fixture(dir.path(), format, 1);
+            analyze_audio(path.to_str().unwrap(), 1024, 512).spectral_centroid
+        })
+        .collect();
+
+    for centroid in &centroids[1..] {
+        assert!((centroid - centroids[0]).abs() < 1e-3, "{:?}", centroids);
+    }
+}
+
+#[test]
+fn dsd_fixtures_are_described_and_analysed() {
+    let dir = tempfile::tempdir().unwrap();
+    let dsd_rate = 2_822_400;
+    let tags = [("TIT2", "Sonata"), ("TPE1", "Pianist"), ("TALB", "Recital")];
+
+    for format in [DsdFormat::Dsf, DsdFormat::Dff] {
+        let path = dir.path().join(format!("tone.{}", format.extension()));
+        write_tone_fixture(&path, format, dsd_rate, 2, dsd_rate as usize, &tags).unwrap();
+
+        let mut description = describe_file(&path, dir.path()).unwrap();
+        assert_eq!(description.extension, format.extension());
+
+        let (sample_rate, duration) = description.get_codec_information().unwrap();
+        assert_eq!(sample_rate, dsd_rate, "{:?}", format);
+        assert!((duration - 1.0).abs() < 1e-6, "{:?}: {}", format, duration);
+
+        let metadata = get_metadata(path.to_str().unwrap(), None).unwrap();
+        assert!(metadata.contains(&("track_title".to_string(), "Sonata".to_string())));
+        assert!(metadata.contains(&("artist".to_string(), "Pianist".to_string())));
+        // DSDIFF only keeps title and artist without an ID3v2 tag
+        let album = ("album".to_string(), "Recital".to_string());
+        assert_eq!(
+            metadata.contains(&album),
+            format == DsdFormat::Dsf,
+            "{:?}",
+            format
+        );
+
+        assert!(extract_cover_art_binary(&path).is_none());
+
+        let result = analyze_audio(path.to_str().unwrap(), 1024, 512);
+        assert!(result.spectral_centroid.is_finite(), "{:?}", format);
+        assert!(result.spectral_centroid > 0.0, "{:?}", format);
+    }
+}
+
+#[test]
+fn dsd_tags_are_read_from_id3v2() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tagged.dsf");
+    let tags = [("TIT2", "Sonata"), ("TRCK", "3"), ("TCON", "Classical")];
+    write_tone_fixture(&path, DsdFormat::Dsf, 2_822_400, 1, 10_000, &tags).unwrap();
+
+    let metadata = get_metadata(path.to_str().unwrap(), None).unwrap();
+    assert_eq!(
+        metadata,
+        vec![
+            ("track_title".to_string(), "Sonata".to_string()),
+            ("track_number".to_string(), "3".to_string()),
+            ("genre".to_string(), "Classical".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,71 @@
+use metadata::gapless::{
+    parse_itunes_smpb, parse_lame_frame, read_gapless_info, GaplessInfo, MP3_DECODER_DELAY,
+};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+// An Info frame of a 44.1 kHz stereo MP3 with the LAME tag written by
+// LAME 3.100 for a delay of 576 and a padding of 1000 frames
+fn lame_frame(frames: u32) -> Vec<u8> {
+    let mut frame = vec![0xFF, 0xFB, 0x90, 0x00];
+    frame.extend_from_slice(&[0; 32]);
+    frame.extend_from_slice(b"Info");
+    frame.extend_from_slice(&0x0Fu32.to_be_bytes());
+    frame.extend_from_slice(&frames.to_be_bytes());
+    frame.extend_from_slice(&417_000u32.to_be_bytes());
+    frame.extend_from_slice(&[0; 100]);
+    frame.extend_from_slice(&[0, 0, 0, 100]);
+    frame.extend_from_slice(b"LAME3.100");
+    frame.extend_from_slice(&[0; 12]);
+    frame.extend_from_slice(&[(576 >> 4) as u8, 0x03, 0xE8]);
+    frame.resize(417, 0);
+    frame
+}
+
+#[test]
+fn lame_tags_give_delay_and_padding() {
+    assert_eq!(
+        parse_lame_frame(&lame_frame(100)),
+        Some(GaplessInfo {
+            leading_frames: 576 + MP3_DECODER_DELAY,
+            valid_frames: Some(100 * 1152 - 576 - 1000),
+        })
+    );
+
+    // A Xing frame without the LAME extension tells nothing
+    let mut frame = lame_frame(100);
+    frame[156..160].copy_from_slice(b"\0\0\0\0");
+    assert_eq!(parse_lame_frame(&frame), None);
+}
+
+#[test]
+fn itunes_tags_give_delay_and_length() {
+    let value = " 00000000 00000840 0000037C 00000000000F4A84 00000000 00000000";
+    assert_eq!(
+        parse_itunes_smpb(value),
+        Some(GaplessInfo {
+            leading_frames: 2112,
+            valid_frames: Some(1_002_116),
+        })
+    );
+    assert_eq!(parse_itunes_smpb("not a tag"), None);
+}
+
+#[test]
+fn gapless_info_is_read_from_files() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mp3 = dir.path().join("track.mp3");
+    let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x14".to_vec();
+    data.extend_from_slice(&[0; 20]);
+    data.extend_from_slice(&lame_frame(100));
+    std::fs::write(&mp3, data).unwrap();
+    assert_eq!(
+        read_gapless_info(&mp3).unwrap().map(|x| x.leading_frames),
+        Some(576 + MP3_DECODER_DELAY)
+    );
+
+    // Lossless files play untrimmed
+    let wav = dir.path().join("track.wav");
+    write_sine_fixture(&wav, FixtureFormat::Wav, 44100, 2, 1000).unwrap();
+    assert_eq!(read_gapless_info(&wav).unwrap(), None);
+}
@@ -0,0 +1,58 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgb, RgbImage};
+
+use metadata::mosaic::{compose_mosaic, MOSAIC_SIZE};
+
+fn cover(color: [u8; 3]) -> Vec<u8> {
+    let image = RgbImage::from_pixel(300, 200, Rgb(color));
+    let mut data = Cursor::new(Vec::new());
+    image.write_to(&mut data, ImageFormat::Png).unwrap();
+    data.into_inner()
+}
+
+fn corners(mosaic: &[u8]) -> Vec<[u8; 3]> {
+    let image = image::load_from_memory(mosaic).unwrap().to_rgb8();
+    assert_eq!(image.dimensions(), (MOSAIC_SIZE, MOSAIC_SIZE));
+
+    let quarter = MOSAIC_SIZE / 4;
+    [(1, 1), (3, 1), (1, 3), (3, 3)]
+        .iter()
+        .map(|(x, y)| image.get_pixel(x * quarter, y * quarter).0)
+        .collect()
+}
+
+fn is_close(a: [u8; 3], b: [u8; 3]) -> bool {
+    a.iter().zip(b.iter()).all(|(a, b)| a.abs_diff(*b) < 16)
+}
+
+#[test]
+fn four_covers_fill_the_grid() {
+    let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+    let covers: Vec<Vec<u8>> = colors.iter().map(|x| cover(*x)).collect();
+
+    let mosaic = compose_mosaic(&covers).unwrap();
+
+    for (actual, expected) in corners(&mosaic).into_iter().zip(colors) {
+        assert!(is_close(actual, expected), "{:?} != {:?}", actual, expected);
+    }
+}
+
+#[test]
+fn two_covers_are_repeated_diagonally() {
+    let red = [255, 0, 0];
+    let blue = [0, 0, 255];
+    let covers = vec![cover(red), b"not an image".to_vec(), cover(blue)];
+
+    let mosaic = compose_mosaic(&covers).unwrap();
+
+    let corners = corners(&mosaic);
+    assert!(is_close(corners[0], red) && is_close(corners[3], red));
+    assert!(is_close(corners[1], blue) && is_close(corners[2], blue));
+}
+
+#[test]
+fn a_single_cover_makes_no_mosaic() {
+    assert_eq!(compose_mosaic(&[cover([0, 0, 0])]), None);
+    assert_eq!(compose_mosaic(&[]), None);
+}
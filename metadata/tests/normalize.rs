@@ -0,0 +1,33 @@
+use metadata::normalize::{canonical_name, clean_name, strip_article};
+
+#[test]
+fn names_are_cleaned_up() {
+    assert_eq!(clean_name("  Sigur   Rós \t"), "Sigur Rós");
+    // Decomposed "é" is composed
+    assert_eq!(clean_name("Beyonce\u{301}"), "Beyoncé");
+}
+
+#[test]
+fn canonical_names_ignore_case_and_spacing() {
+    assert_eq!(
+        canonical_name("The  Beatles ", false),
+        canonical_name("the beatles", false)
+    );
+    assert_ne!(
+        canonical_name("The Beatles", false),
+        canonical_name("Beatles", false)
+    );
+    assert_eq!(
+        canonical_name("Beyonce\u{301}", false),
+        canonical_name("BEYONCÉ", false)
+    );
+}
+
+#[test]
+fn leading_article_is_optional() {
+    assert_eq!(canonical_name("The Beatles", true), "beatles");
+    assert_eq!(canonical_name("THE  beatles", true), "beatles");
+    assert_eq!(strip_article("Theatre of Tragedy"), "Theatre of Tragedy");
+    assert_eq!(strip_article("The"), "The");
+    assert_eq!(strip_article("The "), "The ");
+}
@@ -0,0 +1,44 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgb, RgbImage};
+
+use metadata::palette::{extract_palette, PALETTE_SIZE};
+
+fn encode(image: &RgbImage) -> Vec<u8> {
+    let mut data = Cursor::new(Vec::new());
+    image.write_to(&mut data, ImageFormat::Png).unwrap();
+    data.into_inner()
+}
+
+#[test]
+fn most_common_color_comes_first() {
+    // Three quarters red, one quarter blue
+    let image = RgbImage::from_fn(64, 64, |x, _| {
+        if x < 48 {
+            Rgb([200, 30, 30])
+        } else {
+            Rgb([20, 40, 180])
+        }
+    });
+
+    let palette = extract_palette(&encode(&image), PALETTE_SIZE);
+
+    assert_eq!(palette, vec![0xc81e1e, 0x1428b4]);
+}
+
+#[test]
+fn similar_shades_are_merged() {
+    let image = RgbImage::from_fn(32, 32, |x, y| {
+        Rgb([100 + (x % 4) as u8, 100, 100 + (y % 4) as u8])
+    });
+
+    let palette = extract_palette(&encode(&image), PALETTE_SIZE);
+
+    assert_eq!(palette.len(), 1);
+}
+
+#[test]
+fn undecodable_images_have_no_palette() {
+    assert!(extract_palette(b"not an image", PALETTE_SIZE).is_empty());
+    assert!(extract_palette(&[], PALETTE_SIZE).is_empty());
+}
@@ -0,0 +1,111 @@
+use std::path::Path;
+
+use metadata::path_tags::{infer_path_tags, PathPattern, PathPatternError, DEFAULT_PATH_PATTERNS};
+
+fn default_patterns() -> Vec<PathPattern> {
+    DEFAULT_PATH_PATTERNS
+        .iter()
+        .map(|x| PathPattern::parse(x).unwrap())
+        .collect()
+}
+
+fn tags(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn album_folders_are_read() {
+    let patterns = default_patterns();
+
+    let (pattern, inferred) = infer_path_tags(
+        &patterns,
+        Path::new("Rips/Pink Floyd/The Wall/03 - Another Brick in the Wall.flac"),
+    )
+    .unwrap();
+    assert_eq!(pattern.as_str(), "{artist}/{album}/{track} {title}");
+    assert_eq!(
+        inferred,
+        tags(&[
+            ("artist", "Pink Floyd"),
+            ("album", "The Wall"),
+            ("track_title", "Another Brick in the Wall"),
+            ("track_number", "3"),
+        ])
+    );
+
+    let (_, inferred) = infer_path_tags(
+        &patterns,
+        Path::new("Miles Davis - Kind of Blue/01. So What.mp3"),
+    )
+    .unwrap();
+    assert_eq!(
+        inferred,
+        tags(&[
+            ("artist", "Miles Davis"),
+            ("album", "Kind of Blue"),
+            ("track_title", "So What"),
+            ("track_number", "1"),
+        ])
+    );
+}
+
+#[test]
+fn disc_numbers_are_read() {
+    let patterns = default_patterns();
+
+    for path in [
+        "Pink Floyd/The Wall/CD2/01 Hey You.flac",
+        "Pink Floyd/The Wall/Disc 2/01 Hey You.flac",
+        "Pink Floyd/The Wall/2-01 Hey You.flac",
+    ] {
+        let (_, inferred) = infer_path_tags(&patterns, Path::new(path)).unwrap();
+        assert_eq!(
+            inferred,
+            tags(&[
+                ("artist", "Pink Floyd"),
+                ("album", "The Wall"),
+                ("track_title", "Hey You"),
+                ("track_number", "1"),
+                ("disc_number", "2"),
+            ]),
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn unmatched_paths_have_no_tags() {
+    let patterns = default_patterns();
+
+    assert!(infer_path_tags(&patterns, Path::new("loose track.mp3")).is_none());
+    assert!(infer_path_tags(&patterns, Path::new("Artist/Album/No Number.mp3")).is_none());
+}
+
+#[test]
+fn custom_patterns_are_checked() {
+    let pattern = PathPattern::parse("{artist}/{year} {album}/{track}").unwrap();
+    assert_eq!(
+        pattern
+            .infer_tags(Path::new("Björk/1997 Homogenic/05.flac"))
+            .unwrap(),
+        tags(&[
+            ("artist", "Björk"),
+            ("album", "Homogenic"),
+            ("track_number", "5"),
+            ("date", "1997"),
+        ])
+    );
+
+    assert!(matches!(
+        PathPattern::parse("{artist}/{composer}"),
+        Err(PathPatternError::UnknownPlaceholder(x)) if x == "composer"
+    ));
+    assert!(matches!(
+        PathPattern::parse("{artist}/{album"),
+        Err(PathPatternError::Unclosed)
+    ));
+}
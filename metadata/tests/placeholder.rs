@@ -0,0 +1,25 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgb, RgbImage};
+
+use metadata::placeholder::encode_blurhash;
+
+#[test]
+fn covers_get_a_blurhash() {
+    let image = RgbImage::from_fn(300, 300, |x, y| {
+        Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+    });
+    let mut data = Cursor::new(Vec::new());
+    image.write_to(&mut data, ImageFormat::Png).unwrap();
+
+    let blurhash = encode_blurhash(&data.into_inner()).unwrap();
+
+    // One size character, one for the maximum value, four for the average
+    // color and two for every other of the 4x4 components
+    assert_eq!(blurhash.len(), 1 + 1 + 4 + 2 * 15);
+}
+
+#[test]
+fn undecodable_covers_have_no_blurhash() {
+    assert_eq!(encode_blurhash(b"not an image"), None);
+}
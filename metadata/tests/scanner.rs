@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use metadata::scanner::AudioScanner;
+
+fn relative_paths(root: &Path, scanner: &mut AudioScanner, count: usize) -> Vec<PathBuf> {
+    scanner
+        .read_files(count)
+        .into_iter()
+        .map(|x| x.path().strip_prefix(root).unwrap().to_path_buf())
+        .collect()
+}
+
+fn library() -> tempfile::TempDir {
+    let lib = tempfile::tempdir().unwrap();
+    for path in [
+        "b/2.mp3",
+        "b/1.flac",
+        "a.mp3",
+        "a/1.mp3",
+        "c.ogg",
+        "cover.jpg",
+    ] {
+        let path = lib.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"").unwrap();
+    }
+
+    lib
+}
+
+#[test]
+fn files_are_visited_in_path_order() {
+    let lib = library();
+    let root = lib.path().to_path_buf();
+    let mut scanner = AudioScanner::new(&root);
+
+    assert_eq!(
+        relative_paths(&root, &mut scanner, 10),
+        ["a/1.mp3", "a.mp3", "b/1.flac", "b/2.mp3", "c.ogg"]
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>()
+    );
+    assert!(scanner.has_ended());
+}
+
+#[test]
+fn scans_resume_after_the_checkpoint() {
+    let lib = library();
+    let root = lib.path().to_path_buf();
+    let mut scanner = AudioScanner::new(&root);
+    scanner.resume_after("b/1.flac");
+
+    assert_eq!(
+        relative_paths(&root, &mut scanner, 1),
+        vec![PathBuf::from("b/2.mp3")]
+    );
+    assert_eq!(
+        relative_paths(&root, &mut scanner, 10),
+        vec![PathBuf::from("c.ogg")]
+    );
+}
+
+#[test]
+fn a_removed_checkpoint_file_does_not_stop_the_scan() {
+    let lib = library();
+    let root = lib.path().to_path_buf();
+    let mut scanner = AudioScanner::new(&root);
+    scanner.resume_after("b/10.mp3");
+
+    assert_eq!(
+        relative_paths(&root, &mut scanner, 10),
+        vec![PathBuf::from("b/2.mp3"), PathBuf::from("c.ogg")]
+    );
+}
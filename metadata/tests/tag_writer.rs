@@ -0,0 +1,31 @@
+use metadata::reader::get_metadata;
+use metadata::tag_writer::write_tags;
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+
+#[test]
+fn written_tags_are_read_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("track.flac");
+    write_sine_fixture(&path, FixtureFormat::Flac, 44100, 2, 4410).unwrap();
+    // The fixture has STREAMINFO as its only metadata block, the frames
+    // follow its 34 bytes
+    let frames = std::fs::read(&path).unwrap()[42..].to_vec();
+
+    let tags: Vec<(String, String)> = [
+        ("artist", "Pink Floyd"),
+        ("album", "The Wall"),
+        ("track_title", "Hey You"),
+        ("track_number", "1"),
+        ("disc_number", "2"),
+    ]
+    .iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect();
+    write_tags(&path, &tags).unwrap();
+
+    let read = get_metadata(path.to_str().unwrap(), None).unwrap();
+    for tag in &tags {
+        assert!(read.contains(tag), "{:?} missing from {:?}", tag, read);
+    }
+    assert!(std::fs::read(&path).unwrap().ends_with(&frames));
+}
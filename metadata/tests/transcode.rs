@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::path::Path;
+
+use metadata::stream_info::read_stream_info;
+use metadata::test_support::{sine_samples, write_sine_fixture, FixtureFormat};
+use metadata::transcode::{transcode_file, TranscodeError};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::probe::Hint;
+
+fn decode(path: &Path) -> Vec<i16> {
+    let mss = MediaSourceStream::new(Box::new(File::open(path).unwrap()), Default::default());
+    let mut format = symphonia::default::get_probe()
+        .format(&Hint::new(), mss, &Default::default(), &Default::default())
+        .unwrap()
+        .format;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(
+            &format.default_track().unwrap().codec_params,
+            &Default::default(),
+        )
+        .unwrap();
+
+    let mut samples = Vec::new();
+    while let Ok(packet) = format.next_packet() {
+        let decoded = decoder.decode(&packet).unwrap();
+        let mut buffer = SampleBuffer::<i16>::new(decoded.capacity() as u64, *decoded.spec());
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buffer.samples());
+    }
+    samples
+}
+
+#[test]
+fn flac_is_transcoded_to_wav() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("tone.flac");
+    let dst = dir.path().join("tone.wav");
+    write_sine_fixture(&src, FixtureFormat::Flac, 44100, 2, 44100).unwrap();
+
+    transcode_file(&src, &dst, "wav").unwrap();
+
+    let info = read_stream_info(&dst).unwrap();
+    assert_eq!(info.sample_rate, 44100);
+    assert_eq!(info.channels, 2);
+    assert_eq!(info.bits_per_sample, Some(16));
+    assert!((info.duration - 1.).abs() < 0.01);
+}
+
+#[test]
+fn wav_is_transcoded_to_smaller_lossless_flac() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("tone.wav");
+    let dst = dir.path().join("tone.flac");
+
+    // A tone on the left and noise on the right, over a partial last block
+    let mut noise = 0x2545_f491u32;
+    let samples: Vec<i16> = sine_samples(44100, 1, 10000)
+        .into_iter()
+        .flat_map(|left| {
+            noise ^= noise << 13;
+            noise ^= noise >> 17;
+            noise ^= noise << 5;
+            [left, (noise >> 20) as i16 - 2048]
+        })
+        .collect();
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: 44100,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&src, spec).unwrap();
+    for sample in &samples {
+        writer.write_sample(*sample).unwrap();
+    }
+    writer.finalize().unwrap();
+
+    transcode_file(&src, &dst, "flac").unwrap();
+
+    let info = read_stream_info(&dst).unwrap();
+    assert_eq!(info.sample_rate, 44100);
+    assert_eq!(info.channels, 2);
+    assert!((info.duration - 10000. / 44100.).abs() < 0.001);
+
+    assert_eq!(decode(&dst), samples);
+    assert!(dst.metadata().unwrap().len() < src.metadata().unwrap().len() * 3 / 4);
+}
+
+#[test]
+fn lossy_codecs_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("tone.wav");
+    write_sine_fixture(&src, FixtureFormat::Wav, 44100, 1, 4410).unwrap();
+
+    assert!(matches!(
+        transcode_file(&src, &dir.path().join("tone.mp3"), "mp3"),
+        Err(TranscodeError::UnsupportedCodec(_))
+    ));
+}
@@ -0,0 +1,78 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use metadata::probe::ProbeError;
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+use metadata::verify::{verify_file, VerifyStatus};
+
+const SAMPLE_RATE: u32 = 44100;
+const FRAMES: usize = 44100;
+
+// The MD5 of the audio follows the other fields of STREAMINFO
+const FLAC_MD5_OFFSET: usize = 26;
+
+fn fixture(dir: &Path, format: FixtureFormat) -> PathBuf {
+    let path = dir.join(format!("tone.{}", format.extension()));
+    write_sine_fixture(&path, format, SAMPLE_RATE, 2, FRAMES).unwrap();
+    path
+}
+
+#[test]
+fn intact_files_verify() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for format in [FixtureFormat::Wav, FixtureFormat::Aiff, FixtureFormat::Flac] {
+        let path = fixture(dir.path(), format);
+        assert_eq!(
+            verify_file(&path).unwrap(),
+            VerifyStatus::Ok,
+            "{:?}",
+            format
+        );
+    }
+}
+
+#[test]
+fn cut_off_files_are_truncated() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for format in [FixtureFormat::Wav, FixtureFormat::Flac] {
+        let path = fixture(dir.path(), format);
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+
+        match verify_file(&path).unwrap() {
+            VerifyStatus::Truncated {
+                decoded_frames,
+                expected_frames,
+            } => {
+                assert_eq!(expected_frames, FRAMES as u64, "{:?}", format);
+                assert!(decoded_frames < expected_frames, "{:?}", format);
+            }
+            status => panic!("{:?} verified as {:?}", format, status),
+        }
+    }
+}
+
+#[test]
+fn flac_md5_mismatches_are_found() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = fixture(dir.path(), FixtureFormat::Flac);
+
+    // The fixture leaves the MD5 unset, any other value is wrong
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[FLAC_MD5_OFFSET..FLAC_MD5_OFFSET + 16].fill(0xAB);
+    fs::write(&path, bytes).unwrap();
+
+    assert_eq!(verify_file(&path).unwrap(), VerifyStatus::ChecksumMismatch);
+}
+
+#[test]
+fn missing_files_are_errors() {
+    let dir = tempfile::tempdir().unwrap();
+
+    assert!(matches!(
+        verify_file(&dir.path().join("missing.flac")),
+        Err(ProbeError::FileNotFound)
+    ));
+}
@@ -0,0 +1,170 @@
+//! Lightweight, process wide performance counters.
+//!
+//! Every counter is a plain atomic so recording is cheap enough to stay
+//! enabled in release builds, the UI fetches a snapshot on demand.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+pub struct Counter {
+    value: AtomicU64,
+}
+
+impl Counter {
+    pub const fn new() -> Self {
+        Counter {
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.value.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Accumulates the count, total and maximum of recorded durations.
+pub struct Timer {
+    count: AtomicU64,
+    total_us: AtomicU64,
+    max_us: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimerSnapshot {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl TimerSnapshot {
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.count as u32
+        }
+    }
+}
+
+impl Timer {
+    pub const fn new() -> Self {
+        Timer {
+            count: AtomicU64::new(0),
+            total_us: AtomicU64::new(0),
+            max_us: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, duration: Duration) {
+        let us = duration.as_micros() as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    /// Run `f` and record how long it took.
+    pub fn time<T>(&self, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(start.elapsed());
+        result
+    }
+
+    pub fn snapshot(&self) -> TimerSnapshot {
+        TimerSnapshot {
+            count: self.count.load(Ordering::Relaxed),
+            total: Duration::from_micros(self.total_us.load(Ordering::Relaxed)),
+            max: Duration::from_micros(self.max_us.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.total_us.store(0, Ordering::Relaxed);
+        self.max_us.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Times the playback progress stalled while the sink was expected to play.
+pub static DECODE_UNDERRUNS: Counter = Counter::new();
+/// Latency of every statement executed on the main database.
+pub static DB_QUERIES: Timer = Timer::new();
+/// Files read by the library scanner.
+pub static SCANNED_FILES: Counter = Counter::new();
+/// Time spent on scanning batches, used with `SCANNED_FILES` for throughput.
+pub static SCAN_BATCHES: Timer = Timer::new();
+/// Time spent on committing the search index.
+pub static INDEX_COMMITS: Timer = Timer::new();
+/// Lookups answered by the query cache without touching the database.
+pub static CACHE_HITS: Counter = Counter::new();
+/// Lookups the query cache had to load from the database.
+pub static CACHE_MISSES: Counter = Counter::new();
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsSnapshot {
+    pub decode_underruns: u64,
+    pub db_queries: TimerSnapshot,
+    pub scanned_files: u64,
+    pub scan_batches: TimerSnapshot,
+    pub index_commits: TimerSnapshot,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+impl MetricsSnapshot {
+    /// Scanned files per second of scanning time.
+    pub fn scan_throughput(&self) -> f64 {
+        let seconds = self.scan_batches.total.as_secs_f64();
+        if seconds == 0.0 {
+            0.0
+        } else {
+            self.scanned_files as f64 / seconds
+        }
+    }
+}
+
+pub fn snapshot() -> MetricsSnapshot {
+    MetricsSnapshot {
+        decode_underruns: DECODE_UNDERRUNS.get(),
+        db_queries: DB_QUERIES.snapshot(),
+        scanned_files: SCANNED_FILES.get(),
+        scan_batches: SCAN_BATCHES.snapshot(),
+        index_commits: INDEX_COMMITS.snapshot(),
+        cache_hits: CACHE_HITS.get(),
+        cache_misses: CACHE_MISSES.get(),
+    }
+}
+
+pub fn reset() {
+    DECODE_UNDERRUNS.reset();
+    DB_QUERIES.reset();
+    SCANNED_FILES.reset();
+    SCAN_BATCHES.reset();
+    INDEX_COMMITS.reset();
+    CACHE_HITS.reset();
+    CACHE_MISSES.reset();
+}
@@ -12,6 +12,38 @@ mod m20230806_000009_create_artists_table;
 mod m20230806_000010_create_media_file_artists_table;
 mod m20230806_000011_create_albums_table;
 mod m20230806_000012_create_media_file_albums_table;
+mod m20240801_000013_create_playback_queue_table;
+mod m20240801_000014_create_settings_table;
+mod m20240801_000015_create_directory_content_types_table;
+mod m20240801_000016_create_playback_positions_table;
+mod m20240801_000017_create_gain_offsets_table;
+mod m20240801_000018_add_cover_art_palette;
+mod m20240801_000019_add_cover_art_blurhash;
+mod m20240801_000020_create_search_aliases_table;
+mod m20240801_000021_add_media_files_deleted_at;
+mod m20240801_000022_add_media_files_path_index;
+mod m20240801_000023_add_sort_names_and_album_year;
+mod m20240801_000024_create_tag_mappings_table;
+mod m20240801_000025_create_ratings_table;
+mod m20240801_000026_add_playlist_cover_art;
+mod m20240801_000027_create_pinned_collections_table;
+mod m20240801_000028_create_collection_merges_table;
+mod m20240801_000029_add_canonical_names;
+mod m20240801_000030_create_playback_exclusions_table;
+mod m20240801_000031_add_analysis_mix_points;
+mod m20240801_000032_create_taste_profiles_table;
+mod m20240801_000033_create_track_skips_table;
+mod m20240801_000034_create_sync_devices_tables;
+mod m20240801_000035_create_remote_library_tables;
+mod m20240801_000036_create_users_table;
+mod m20240801_000037_create_recent_contexts_tables;
+mod m20240801_000038_create_media_loudness_table;
+mod m20240801_000039_create_media_seek_tables_table;
+mod m20240801_000040_create_media_gapless_table;
+mod m20240801_000041_create_media_markers_table;
+mod m20240801_000042_create_output_device_profiles_table;
+mod m20240801_000043_create_media_verifications_table;
+mod m20240801_000044_create_media_checksums_table;
 
 pub struct Migrator;
 
@@ -31,6 +63,38 @@ impl MigratorTrait for Migrator {
             Box::new(m20230806_000010_create_media_file_artists_table::Migration),
             Box::new(m20230806_000011_create_albums_table::Migration),
             Box::new(m20230806_000012_create_media_file_albums_table::Migration),
+            Box::new(m20240801_000013_create_playback_queue_table::Migration),
+            Box::new(m20240801_000014_create_settings_table::Migration),
+            Box::new(m20240801_000015_create_directory_content_types_table::Migration),
+            Box::new(m20240801_000016_create_playback_positions_table::Migration),
+            Box::new(m20240801_000017_create_gain_offsets_table::Migration),
+            Box::new(m20240801_000018_add_cover_art_palette::Migration),
+            Box::new(m20240801_000019_add_cover_art_blurhash::Migration),
+            Box::new(m20240801_000020_create_search_aliases_table::Migration),
+            Box::new(m20240801_000021_add_media_files_deleted_at::Migration),
+            Box::new(m20240801_000022_add_media_files_path_index::Migration),
+            Box::new(m20240801_000023_add_sort_names_and_album_year::Migration),
+            Box::new(m20240801_000024_create_tag_mappings_table::Migration),
+            Box::new(m20240801_000025_create_ratings_table::Migration),
+            Box::new(m20240801_000026_add_playlist_cover_art::Migration),
+            Box::new(m20240801_000027_create_pinned_collections_table::Migration),
+            Box::new(m20240801_000028_create_collection_merges_table::Migration),
+            Box::new(m20240801_000029_add_canonical_names::Migration),
+            Box::new(m20240801_000030_create_playback_exclusions_table::Migration),
+            Box::new(m20240801_000031_add_analysis_mix_points::Migration),
+            Box::new(m20240801_000032_create_taste_profiles_table::Migration),
+            Box::new(m20240801_000033_create_track_skips_table::Migration),
+            Box::new(m20240801_000034_create_sync_devices_tables::Migration),
+            Box::new(m20240801_000035_create_remote_library_tables::Migration),
+            Box::new(m20240801_000036_create_users_table::Migration),
+            Box::new(m20240801_000037_create_recent_contexts_tables::Migration),
+            Box::new(m20240801_000038_create_media_loudness_table::Migration),
+            Box::new(m20240801_000039_create_media_seek_tables_table::Migration),
+            Box::new(m20240801_000040_create_media_gapless_table::Migration),
+            Box::new(m20240801_000041_create_media_markers_table::Migration),
+            Box::new(m20240801_000042_create_output_device_profiles_table::Migration),
+            Box::new(m20240801_000043_create_media_verifications_table::Migration),
+            Box::new(m20240801_000044_create_media_checksums_table::Migration),
         ]
     }
 }
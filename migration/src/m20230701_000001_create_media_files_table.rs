@@ -72,4 +72,5 @@ pub enum MediaFiles {
     CoverArtId,
     SampleRate,
     Duration,
+    DeletedAt,
 }
@@ -88,4 +88,6 @@ pub enum MediaAnalysis {
     Chroma9,
     Chroma10,
     Chroma11,
+    MixIn,
+    MixOut,
 }
@@ -54,4 +54,5 @@ pub enum UserLogs {
     FileId,
     ListenTime,
     Progress,
+    UserId,
 }
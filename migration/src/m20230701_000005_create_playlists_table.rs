@@ -46,4 +46,5 @@ pub enum Playlists {
     Group,
     CreatedAt,
     UpdatedAt,
+    CoverArtId,
 }
@@ -42,4 +42,6 @@ pub enum MediaCoverArt {
     Id,
     FileHash,
     Binary,
+    ColorPalette,
+    Blurhash,
 }
@@ -51,4 +51,6 @@ pub enum Artists {
     Id,
     Name,
     Group,
+    SortName,
+    CanonicalName,
 }
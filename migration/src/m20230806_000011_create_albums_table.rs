@@ -51,4 +51,7 @@ pub enum Albums {
     Id,
     Name,
     Group,
+    SortName,
+    Year,
+    CanonicalName,
 }
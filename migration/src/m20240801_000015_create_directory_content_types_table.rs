@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000015_create_directory_content_types_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DirectoryContentTypes::Table)
+                    .col(
+                        ColumnDef::new(DirectoryContentTypes::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(DirectoryContentTypes::Directory)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(DirectoryContentTypes::ContentType)
+                            .string()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DirectoryContentTypes::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum DirectoryContentTypes {
+    Table,
+    Id,
+    Directory,
+    ContentType,
+}
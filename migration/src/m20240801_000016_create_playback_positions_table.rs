@@ -0,0 +1,64 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000016_create_playback_positions_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PlaybackPositions::Table)
+                    .col(
+                        ColumnDef::new(PlaybackPositions::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PlaybackPositions::MediaFileId)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PlaybackPositions::Position)
+                            .double()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-playback_positions-file_id")
+                            .from(PlaybackPositions::Table, PlaybackPositions::MediaFileId)
+                            .to(MediaFiles::Table, MediaFiles::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PlaybackPositions::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum PlaybackPositions {
+    Table,
+    Id,
+    MediaFileId,
+    Position,
+}
@@ -0,0 +1,60 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000017_create_gain_offsets_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(GainOffsets::Table)
+                    .col(
+                        ColumnDef::new(GainOffsets::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(GainOffsets::MediaFileId)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(GainOffsets::Offset).double().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-gain_offsets-file_id")
+                            .from(GainOffsets::Table, GainOffsets::MediaFileId)
+                            .to(MediaFiles::Table, MediaFiles::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GainOffsets::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum GainOffsets {
+    Table,
+    Id,
+    MediaFileId,
+    Offset,
+}
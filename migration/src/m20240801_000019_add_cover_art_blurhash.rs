@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230728_000008_create_media_cover_art_table::MediaCoverArt;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000019_add_cover_art_blurhash"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaCoverArt::Table)
+                    .add_column(ColumnDef::new(MediaCoverArt::Blurhash).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaCoverArt::Table)
+                    .drop_column(MediaCoverArt::Blurhash)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,52 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000020_create_search_aliases_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SearchAliases::Table)
+                    .col(
+                        ColumnDef::new(SearchAliases::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(SearchAliases::Term).string().not_null())
+                    .col(ColumnDef::new(SearchAliases::Alias).string().not_null())
+                    .index(
+                        Index::create()
+                            .name("idx-search_aliases-term-alias")
+                            .col(SearchAliases::Term)
+                            .col(SearchAliases::Alias)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SearchAliases::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum SearchAliases {
+    Table,
+    Id,
+    Term,
+    Alias,
+}
@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000021_add_media_files_deleted_at"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFiles::Table)
+                    .add_column(ColumnDef::new(MediaFiles::DeletedAt).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFiles::Table)
+                    .drop_column(MediaFiles::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
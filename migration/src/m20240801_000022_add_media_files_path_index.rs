@@ -0,0 +1,48 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000022_add_media_files_path_index"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Interrupted scans could insert a file twice, keep the oldest record
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "DELETE FROM media_files WHERE id NOT IN \
+                 (SELECT MIN(id) FROM media_files GROUP BY directory, file_name)",
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-media_files-directory-file_name")
+                    .table(MediaFiles::Table)
+                    .col(MediaFiles::Directory)
+                    .col(MediaFiles::FileName)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-media_files-directory-file_name")
+                    .table(MediaFiles::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,88 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230806_000009_create_artists_table::Artists;
+use super::m20230806_000011_create_albums_table::Albums;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000023_add_sort_names_and_album_year"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite adds one column per statement
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Artists::Table)
+                    .add_column(ColumnDef::new(Artists::SortName).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Albums::Table)
+                    .add_column(ColumnDef::new(Albums::SortName).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Albums::Table)
+                    .add_column(ColumnDef::new(Albums::Year).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Fill in the years of indexed albums from the tags of their tracks,
+        // sort names follow when the tracks are indexed again
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE albums SET year = (\
+                   SELECT MIN(NULLIF(CAST(SUBSTR(COALESCE(\
+                     (SELECT meta_value FROM media_metadata \
+                      WHERE file_id = mfa.media_file_id AND meta_key = 'original_date'), \
+                     (SELECT meta_value FROM media_metadata \
+                      WHERE file_id = mfa.media_file_id AND meta_key = 'date')\
+                   ), 1, 4) AS INTEGER), 0)) \
+                   FROM media_file_albums mfa WHERE mfa.album_id = albums.id)",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Albums::Table)
+                    .drop_column(Albums::Year)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Albums::Table)
+                    .drop_column(Albums::SortName)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Artists::Table)
+                    .drop_column(Artists::SortName)
+                    .to_owned(),
+            )
+            .await
+    }
+}
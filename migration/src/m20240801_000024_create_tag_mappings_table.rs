@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000024_create_tag_mappings_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TagMappings::Table)
+                    .col(
+                        ColumnDef::new(TagMappings::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TagMappings::TagKey)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(TagMappings::Field).string().not_null())
+                    .col(
+                        ColumnDef::new(TagMappings::Searchable)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TagMappings::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TagMappings {
+    Table,
+    Id,
+    TagKey,
+    Field,
+    Searchable,
+}
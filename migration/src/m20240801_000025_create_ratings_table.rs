@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000025_create_ratings_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Ratings::Table)
+                    .col(
+                        ColumnDef::new(Ratings::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Ratings::MediaFileId)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Ratings::Rating).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-ratings-file_id")
+                            .from(Ratings::Table, Ratings::MediaFileId)
+                            .to(MediaFiles::Table, MediaFiles::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Ratings::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Ratings {
+    Table,
+    Id,
+    MediaFileId,
+    Rating,
+    UserId,
+}
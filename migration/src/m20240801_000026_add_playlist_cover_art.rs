@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000005_create_playlists_table::Playlists;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000026_add_playlist_cover_art"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Playlists::Table)
+                    .add_column(ColumnDef::new(Playlists::CoverArtId).integer().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Playlists::Table)
+                    .drop_column(Playlists::CoverArtId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
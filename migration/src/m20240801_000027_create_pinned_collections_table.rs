@@ -0,0 +1,72 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000027_create_pinned_collections_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PinnedCollections::Table)
+                    .col(
+                        ColumnDef::new(PinnedCollections::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PinnedCollections::CollectionType)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PinnedCollections::CollectionId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PinnedCollections::Position)
+                            .integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-pinned_collections-collection")
+                    .table(PinnedCollections::Table)
+                    .col(PinnedCollections::CollectionType)
+                    .col(PinnedCollections::CollectionId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PinnedCollections::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum PinnedCollections {
+    Table,
+    Id,
+    CollectionType,
+    CollectionId,
+    Position,
+    UserId,
+}
@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000028_create_collection_merges_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CollectionMerges::Table)
+                    .col(
+                        ColumnDef::new(CollectionMerges::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(CollectionMerges::CollectionType)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(CollectionMerges::SourceName)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(CollectionMerges::TargetName)
+                            .string()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-collection_merges-source")
+                    .table(CollectionMerges::Table)
+                    .col(CollectionMerges::CollectionType)
+                    .col(CollectionMerges::SourceName)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CollectionMerges::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum CollectionMerges {
+    Table,
+    Id,
+    CollectionType,
+    SourceName,
+    TargetName,
+}
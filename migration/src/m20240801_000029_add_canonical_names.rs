@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230806_000009_create_artists_table::Artists;
+use super::m20230806_000011_create_albums_table::Albums;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000029_add_canonical_names"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Filled in when the tracks are indexed again, rows without one are
+        // still matched by their name
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Artists::Table)
+                    .add_column(ColumnDef::new(Artists::CanonicalName).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Albums::Table)
+                    .add_column(ColumnDef::new(Albums::CanonicalName).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-artists-canonical_name")
+                    .table(Artists::Table)
+                    .col(Artists::CanonicalName)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-albums-canonical_name")
+                    .table(Albums::Table)
+                    .col(Albums::CanonicalName)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-albums-canonical_name")
+                    .table(Albums::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-artists-canonical_name")
+                    .table(Artists::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Albums::Table)
+                    .drop_column(Albums::CanonicalName)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Artists::Table)
+                    .drop_column(Artists::CanonicalName)
+                    .to_owned(),
+            )
+            .await
+    }
+}
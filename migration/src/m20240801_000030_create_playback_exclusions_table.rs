@@ -0,0 +1,79 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000030_create_playback_exclusions_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PlaybackExclusions::Table)
+                    .col(
+                        ColumnDef::new(PlaybackExclusions::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PlaybackExclusions::CollectionType)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PlaybackExclusions::Target)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PlaybackExclusions::NeverShuffle)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(PlaybackExclusions::ExcludeFromRecommendations)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-playback_exclusions-target")
+                    .table(PlaybackExclusions::Table)
+                    .col(PlaybackExclusions::CollectionType)
+                    .col(PlaybackExclusions::Target)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PlaybackExclusions::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum PlaybackExclusions {
+    Table,
+    Id,
+    CollectionType,
+    Target,
+    NeverShuffle,
+    ExcludeFromRecommendations,
+}
@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000003_create_media_analysis_table::MediaAnalysis;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000031_add_analysis_mix_points"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Left empty for files analysed before, they crossfade like before
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaAnalysis::Table)
+                    .add_column(ColumnDef::new(MediaAnalysis::MixIn).double().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaAnalysis::Table)
+                    .add_column(ColumnDef::new(MediaAnalysis::MixOut).double().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaAnalysis::Table)
+                    .drop_column(MediaAnalysis::MixOut)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaAnalysis::Table)
+                    .drop_column(MediaAnalysis::MixIn)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,48 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000032_create_taste_profiles_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TasteProfiles::Table)
+                    .col(
+                        ColumnDef::new(TasteProfiles::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TasteProfiles::Vector).string().not_null())
+                    .col(ColumnDef::new(TasteProfiles::Weight).double().not_null())
+                    .col(ColumnDef::new(TasteProfiles::UpdatedAt).string().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TasteProfiles::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TasteProfiles {
+    Table,
+    Id,
+    Vector,
+    Weight,
+    UpdatedAt,
+    UserId,
+}
@@ -0,0 +1,105 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000034_create_sync_devices_tables"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SyncDevices::Table)
+                    .col(
+                        ColumnDef::new(SyncDevices::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(SyncDevices::Name)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(SyncDevices::Path).string().not_null())
+                    .col(ColumnDef::new(SyncDevices::Codec).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(SyncedFiles::Table)
+                    .col(
+                        ColumnDef::new(SyncedFiles::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(SyncedFiles::DeviceId).integer().not_null())
+                    .col(ColumnDef::new(SyncedFiles::FileId).integer().not_null())
+                    .col(ColumnDef::new(SyncedFiles::TargetPath).string().not_null())
+                    .col(ColumnDef::new(SyncedFiles::FileHash).string().not_null())
+                    .col(ColumnDef::new(SyncedFiles::SyncedAt).string().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-synced_files-device_id")
+                            .from(SyncedFiles::Table, SyncedFiles::DeviceId)
+                            .to(SyncDevices::Table, SyncDevices::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-synced_files-device_id-file_id")
+                    .table(SyncedFiles::Table)
+                    .col(SyncedFiles::DeviceId)
+                    .col(SyncedFiles::FileId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SyncedFiles::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(SyncDevices::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum SyncDevices {
+    Table,
+    Id,
+    Name,
+    Path,
+    Codec,
+}
+
+#[derive(Iden)]
+pub enum SyncedFiles {
+    Table,
+    Id,
+    DeviceId,
+    FileId,
+    TargetPath,
+    FileHash,
+    SyncedAt,
+}
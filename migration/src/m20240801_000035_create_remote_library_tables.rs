@@ -0,0 +1,121 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000035_create_remote_library_tables"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RemoteServers::Table)
+                    .col(
+                        ColumnDef::new(RemoteServers::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RemoteServers::Name)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(RemoteServers::Url).string().not_null())
+                    .col(ColumnDef::new(RemoteServers::Username).string().not_null())
+                    .col(ColumnDef::new(RemoteServers::Password).string().not_null())
+                    .col(ColumnDef::new(RemoteServers::SyncedAt).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(RemoteFiles::Table)
+                    .col(
+                        ColumnDef::new(RemoteFiles::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RemoteFiles::ServerId).integer().not_null())
+                    .col(ColumnDef::new(RemoteFiles::RemoteId).string().not_null())
+                    .col(ColumnDef::new(RemoteFiles::Title).string().not_null())
+                    .col(ColumnDef::new(RemoteFiles::Artist).string().not_null())
+                    .col(ColumnDef::new(RemoteFiles::Album).string().not_null())
+                    .col(ColumnDef::new(RemoteFiles::RemoteAlbumId).string().null())
+                    .col(ColumnDef::new(RemoteFiles::TrackNumber).integer().null())
+                    .col(ColumnDef::new(RemoteFiles::Year).integer().null())
+                    .col(ColumnDef::new(RemoteFiles::Duration).double().not_null())
+                    .col(ColumnDef::new(RemoteFiles::Extension).string().not_null())
+                    .col(ColumnDef::new(RemoteFiles::CoverArt).string().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-remote_files-server_id")
+                            .from(RemoteFiles::Table, RemoteFiles::ServerId)
+                            .to(RemoteServers::Table, RemoteServers::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-remote_files-server_id-remote_id")
+                    .table(RemoteFiles::Table)
+                    .col(RemoteFiles::ServerId)
+                    .col(RemoteFiles::RemoteId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RemoteFiles::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(RemoteServers::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum RemoteServers {
+    Table,
+    Id,
+    Name,
+    Url,
+    Username,
+    Password,
+    SyncedAt,
+}
+
+#[derive(Iden)]
+pub enum RemoteFiles {
+    Table,
+    Id,
+    ServerId,
+    RemoteId,
+    Title,
+    Artist,
+    Album,
+    RemoteAlbumId,
+    TrackNumber,
+    Year,
+    Duration,
+    Extension,
+    CoverArt,
+}
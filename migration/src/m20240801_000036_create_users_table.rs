@@ -0,0 +1,257 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+use super::m20230701_000004_create_user_logs_table::UserLogs;
+use super::m20240801_000013_create_playback_queue_table::PlaybackQueue;
+use super::m20240801_000025_create_ratings_table::Ratings;
+use super::m20240801_000027_create_pinned_collections_table::PinnedCollections;
+use super::m20240801_000032_create_taste_profiles_table::TasteProfiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000036_create_users_table"
+    }
+}
+
+/// The profile everything recorded before profiles existed belongs to.
+const DEFAULT_USER_ID: i32 = 1;
+
+fn user_id_column(column: impl IntoIden) -> ColumnDef {
+    ColumnDef::new(column)
+        .integer()
+        .not_null()
+        .default(DEFAULT_USER_ID)
+        .to_owned()
+}
+
+fn create_ratings_table() -> TableCreateStatement {
+    Table::create()
+        .table(Ratings::Table)
+        .col(
+            ColumnDef::new(Ratings::Id)
+                .integer()
+                .not_null()
+                .auto_increment()
+                .primary_key(),
+        )
+        .col(&mut user_id_column(Ratings::UserId))
+        .col(ColumnDef::new(Ratings::MediaFileId).integer().not_null())
+        .col(ColumnDef::new(Ratings::Rating).integer().not_null())
+        .foreign_key(
+            ForeignKey::create()
+                .name("fk-ratings-file_id")
+                .from(Ratings::Table, Ratings::MediaFileId)
+                .to(MediaFiles::Table, MediaFiles::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade),
+        )
+        .to_owned()
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Users::Table)
+                    .col(
+                        ColumnDef::new(Users::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Users::Name).string().not_null().unique_key())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(Users::Table)
+                    .columns([Users::Id, Users::Name])
+                    .values_panic([DEFAULT_USER_ID.into(), "Default".into()])
+                    .to_owned(),
+            )
+            .await?;
+
+        // Existing rows go to the default profile. SQLite can't add a
+        // foreign key to an existing table, rows of a removed profile are
+        // deleted together with it instead.
+        for (table, column) in [
+            (UserLogs::Table.into_iden(), UserLogs::UserId.into_iden()),
+            (
+                PlaybackQueue::Table.into_iden(),
+                PlaybackQueue::UserId.into_iden(),
+            ),
+            (
+                TasteProfiles::Table.into_iden(),
+                TasteProfiles::UserId.into_iden(),
+            ),
+            (
+                PinnedCollections::Table.into_iden(),
+                PinnedCollections::UserId.into_iden(),
+            ),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .add_column(&mut user_id_column(column))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-user_logs-user_id-file_id")
+                    .table(UserLogs::Table)
+                    .col(UserLogs::UserId)
+                    .col(UserLogs::FileId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-pinned_collections-collection")
+                    .table(PinnedCollections::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-pinned_collections-collection")
+                    .table(PinnedCollections::Table)
+                    .col(PinnedCollections::UserId)
+                    .col(PinnedCollections::CollectionType)
+                    .col(PinnedCollections::CollectionId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // A file is rated once per profile, the unique file ID is part of
+        // the table definition so the table is built again
+        manager
+            .rename_table(
+                Table::rename()
+                    .table(Ratings::Table, Alias::new("ratings_old"))
+                    .to_owned(),
+            )
+            .await?;
+        manager.create_table(create_ratings_table()).await?;
+        manager
+            .get_connection()
+            .execute_unprepared(&format!(
+                "INSERT INTO ratings (id, user_id, media_file_id, rating) \
+                 SELECT id, {}, media_file_id, rating FROM ratings_old",
+                DEFAULT_USER_ID
+            ))
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Alias::new("ratings_old")).to_owned())
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-ratings-user_id-media_file_id")
+                    .table(Ratings::Table)
+                    .col(Ratings::UserId)
+                    .col(Ratings::MediaFileId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Only the default profile is kept
+        for table in [
+            "user_logs",
+            "playback_queue",
+            "taste_profiles",
+            "pinned_collections",
+            "ratings",
+        ] {
+            manager
+                .get_connection()
+                .execute_unprepared(&format!(
+                    "DELETE FROM {} WHERE user_id != {}",
+                    table, DEFAULT_USER_ID
+                ))
+                .await?;
+        }
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-pinned_collections-collection")
+                    .table(PinnedCollections::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-pinned_collections-collection")
+                    .table(PinnedCollections::Table)
+                    .col(PinnedCollections::CollectionType)
+                    .col(PinnedCollections::CollectionId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-user_logs-user_id-file_id")
+                    .table(UserLogs::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-ratings-user_id-media_file_id")
+                    .table(Ratings::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        for table in [
+            UserLogs::Table.into_iden(),
+            PlaybackQueue::Table.into_iden(),
+            TasteProfiles::Table.into_iden(),
+            PinnedCollections::Table.into_iden(),
+            Ratings::Table.into_iden(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .drop_column(Alias::new("user_id"))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .drop_table(Table::drop().table(Users::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Users {
+    Table,
+    Id,
+    Name,
+}
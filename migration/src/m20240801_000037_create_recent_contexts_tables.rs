@@ -0,0 +1,145 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000037_create_recent_contexts_tables"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RecentContexts::Table)
+                    .col(
+                        ColumnDef::new(RecentContexts::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RecentContexts::UserId).integer().not_null())
+                    .col(
+                        ColumnDef::new(RecentContexts::ContextType)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RecentContexts::ContextId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RecentContexts::CurrentIndex)
+                            .integer()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(RecentContexts::Position)
+                            .double()
+                            .not_null()
+                            .default(0.0),
+                    )
+                    .col(ColumnDef::new(RecentContexts::PlayedAt).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(RecentContextItems::Table)
+                    .col(
+                        ColumnDef::new(RecentContextItems::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RecentContextItems::RecentContextId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RecentContextItems::MediaFileId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RecentContextItems::Position)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-recent_context_items-recent_context_id")
+                            .from(
+                                RecentContextItems::Table,
+                                RecentContextItems::RecentContextId,
+                            )
+                            .to(RecentContexts::Table, RecentContexts::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-recent_context_items-media_file_id")
+                            .from(RecentContextItems::Table, RecentContextItems::MediaFileId)
+                            .to(MediaFiles::Table, MediaFiles::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-recent_contexts-user_id-context")
+                    .table(RecentContexts::Table)
+                    .col(RecentContexts::UserId)
+                    .col(RecentContexts::ContextType)
+                    .col(RecentContexts::ContextId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RecentContextItems::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(RecentContexts::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum RecentContexts {
+    Table,
+    Id,
+    UserId,
+    ContextType,
+    ContextId,
+    CurrentIndex,
+    Position,
+    PlayedAt,
+}
+
+#[derive(Iden)]
+pub enum RecentContextItems {
+    Table,
+    Id,
+    RecentContextId,
+    MediaFileId,
+    Position,
+}
@@ -0,0 +1,55 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000041_create_media_markers_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MediaMarkers::Table)
+                    .col(
+                        ColumnDef::new(MediaMarkers::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(MediaMarkers::FileId).integer().not_null())
+                    .col(ColumnDef::new(MediaMarkers::Position).double().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-media_markers-file_id")
+                            .from(MediaMarkers::Table, MediaMarkers::FileId)
+                            .to(MediaFiles::Table, MediaFiles::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MediaMarkers::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum MediaMarkers {
+    Table,
+    Id,
+    FileId,
+    Position,
+}
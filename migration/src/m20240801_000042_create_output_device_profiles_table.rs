@@ -0,0 +1,66 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000042_create_output_device_profiles_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OutputDeviceProfiles::Table)
+                    .col(
+                        ColumnDef::new(OutputDeviceProfiles::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(OutputDeviceProfiles::DeviceName)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(OutputDeviceProfiles::Volume)
+                            .double()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OutputDeviceProfiles::NightMode)
+                            .boolean()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OutputDeviceProfiles::Crossfeed)
+                            .boolean()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OutputDeviceProfiles::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum OutputDeviceProfiles {
+    Table,
+    Id,
+    DeviceName,
+    Volume,
+    NightMode,
+    Crossfeed,
+}
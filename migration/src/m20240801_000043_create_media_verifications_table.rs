@@ -0,0 +1,78 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000043_create_media_verifications_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MediaVerifications::Table)
+                    .col(
+                        ColumnDef::new(MediaVerifications::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(MediaVerifications::FileId)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(MediaVerifications::FileHash)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(MediaVerifications::Status)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(MediaVerifications::Detail).string().null())
+                    .col(
+                        ColumnDef::new(MediaVerifications::VerifiedAt)
+                            .string()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-media_verifications-file_id")
+                            .from(MediaVerifications::Table, MediaVerifications::FileId)
+                            .to(MediaFiles::Table, MediaFiles::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MediaVerifications::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum MediaVerifications {
+    Table,
+    Id,
+    FileId,
+    FileHash,
+    Status,
+    Detail,
+    VerifiedAt,
+}
@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240801_000044_create_media_checksums_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MediaChecksums::Table)
+                    .col(
+                        ColumnDef::new(MediaChecksums::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(MediaChecksums::FileId)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(MediaChecksums::Kind).string().not_null())
+                    .col(ColumnDef::new(MediaChecksums::Value).string().not_null())
+                    .col(
+                        ColumnDef::new(MediaChecksums::PreviousValue)
+                            .string()
+                            .null(),
+                    )
+                    .col(ColumnDef::new(MediaChecksums::ChangedAt).string().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-media_checksums-file_id")
+                            .from(MediaChecksums::Table, MediaChecksums::FileId)
+                            .to(MediaFiles::Table, MediaFiles::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MediaChecksums::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum MediaChecksums {
+    Table,
+    Id,
+    FileId,
+    Kind,
+    Value,
+    PreviousValue,
+    ChangedAt,
+}
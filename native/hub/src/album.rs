@@ -1,34 +1,47 @@
 use database::actions::albums::get_albums_by_ids;
 use database::actions::cover_art::get_magic_cover_art_id;
-use database::actions::library::get_album_cover_ids;
-use log::{debug, error};
+use database::actions::query_cache::QueryCache;
 use rinf::DartSignal;
 use std::sync::Arc;
+use tracing::{debug, error};
 
 use database::actions::albums::get_albums_groups;
+use database::actions::eras::{count_by_era, get_albums_by_era, Era};
+use database::actions::gapless::{check_gapless_albums, GaplessProblem};
 use database::actions::utils::create_count_by_first_letter;
 use database::connection::MainDbConnection;
 use database::entities::albums;
 
+use crate::cover_art::attach_cover_blurhashes;
 use crate::messages::album::Album;
+use crate::messages::album::AlbumEraSummaryResponse;
 use crate::messages::album::AlbumGroupSummaryResponse;
+use crate::messages::album::AlbumsEra;
+use crate::messages::album::AlbumsEraSummary;
+use crate::messages::album::AlbumsEras;
 use crate::messages::album::AlbumsGroup;
 use crate::messages::album::AlbumsGroupSummary;
 use crate::messages::album::AlbumsGroups;
+use crate::messages::album::CheckGaplessAlbumsRequest;
+use crate::messages::album::CheckGaplessAlbumsResponse;
+use crate::messages::album::FetchAlbumsByEraRequest;
+use crate::messages::album::FetchAlbumsEraSummaryRequest;
 use crate::messages::album::FetchAlbumsGroupSummaryRequest;
 use crate::messages::album::FetchAlbumsGroupsRequest;
+use crate::messages::album::GaplessAlbumProblem;
+use crate::messages::album::GaplessAlbumReport;
 use crate::FetchAlbumsByIdsRequest;
 use crate::FetchAlbumsByIdsResponse;
 
 pub async fn fetch_albums_group_summary_request(
-    main_db: Arc<MainDbConnection>,
+    reader_db: Arc<MainDbConnection>,
     _dart_signal: DartSignal<FetchAlbumsGroupSummaryRequest>,
 ) {
     debug!("Requesting summary group");
 
     let count_albums = create_count_by_first_letter::<albums::Entity>();
 
-    match count_albums(&main_db).await {
+    match count_albums(&reader_db).await {
         Ok(entry) => {
             let albums_groups = entry
                 .into_iter()
@@ -47,16 +60,16 @@ pub async fn fetch_albums_group_summary_request(
 }
 
 pub async fn fetch_albums_groups_request(
-    main_db: Arc<MainDbConnection>,
+    reader_db: Arc<MainDbConnection>,
     dart_signal: DartSignal<FetchAlbumsGroupsRequest>,
 ) {
     let request = dart_signal.message;
 
     debug!("Requesting albums groups");
 
-    match get_albums_groups(&main_db, request.group_titles).await {
+    match get_albums_groups(&reader_db, request.group_titles).await {
         Ok(entry) => {
-            AlbumsGroups {
+            let mut response = AlbumsGroups {
                 groups: entry
                     .into_iter()
                     .map(|x| AlbumsGroup {
@@ -68,12 +81,19 @@ pub async fn fetch_albums_groups_request(
                                 id: x.0.id,
                                 name: x.0.name,
                                 cover_ids: x.1.into_iter().collect(),
+                                cover_blurhashes: Vec::new(),
+                                year: x.0.year.unwrap_or_default(),
                             })
                             .collect(),
                     })
                     .collect(),
-            }
-            .send_signal_to_dart();
+            };
+            attach_cover_blurhashes(
+                &reader_db,
+                response.groups.iter_mut().flat_map(|x| x.albums.iter_mut()),
+            )
+            .await;
+            response.send_signal_to_dart();
         }
         Err(e) => {
             error!("Failed to fetch albums groups: {}", e);
@@ -82,19 +102,23 @@ pub async fn fetch_albums_groups_request(
 }
 
 pub async fn fetch_albums_by_ids_request(
-    main_db: Arc<MainDbConnection>,
+    reader_db: Arc<MainDbConnection>,
+    query_cache: Arc<QueryCache>,
     dart_signal: DartSignal<FetchAlbumsByIdsRequest>,
 ) {
     let request = dart_signal.message;
 
     debug!("Requesting albums: {:#?}", request.ids);
 
-    match get_albums_by_ids(&main_db, &request.ids).await {
+    match get_albums_by_ids(&reader_db, &request.ids).await {
         Ok(items) => {
-            let magic_cover_id = get_magic_cover_art_id(&main_db).await.unwrap_or(-1);
-            let covers = get_album_cover_ids(&main_db, &items).await.unwrap();
+            let magic_cover_id = get_magic_cover_art_id(&reader_db).await.unwrap_or(-1);
+            let covers = query_cache
+                .album_cover_ids(&reader_db, &items)
+                .await
+                .unwrap();
 
-            FetchAlbumsByIdsResponse {
+            let mut response = FetchAlbumsByIdsResponse {
                 result: items
                     .into_iter()
                     .map(|x| Album {
@@ -107,13 +131,143 @@ pub async fn fetch_albums_by_ids_request(
                             .into_iter()
                             .filter(|x| *x != magic_cover_id)
                             .collect(),
+                        cover_blurhashes: Vec::new(),
+                        year: x.year.unwrap_or_default(),
+                    })
+                    .collect(),
+            };
+            attach_cover_blurhashes(&reader_db, response.result.iter_mut()).await;
+            response.send_signal_to_dart();
+        }
+        Err(e) => {
+            error!("Failed to fetch albums groups: {}", e);
+        }
+    };
+}
+
+fn era_of(decades: bool) -> Era {
+    if decades {
+        Era::Decade
+    } else {
+        Era::Year
+    }
+}
+
+pub async fn fetch_albums_era_summary_request(
+    reader_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<FetchAlbumsEraSummaryRequest>,
+) {
+    let decades = dart_signal.message.decades;
+
+    debug!("Requesting album eras, decades: {}", decades);
+
+    match count_by_era(&reader_db, era_of(decades)).await {
+        Ok(counts) => {
+            AlbumEraSummaryResponse {
+                decades,
+                eras: counts
+                    .into_iter()
+                    .map(|x| AlbumsEraSummary {
+                        start_year: x.start,
+                        album_count: x.albums as i32,
+                        track_count: x.tracks as i32,
                     })
                     .collect(),
             }
             .send_signal_to_dart();
         }
         Err(e) => {
-            error!("Failed to fetch albums groups: {}", e);
+            error!("Failed to fetch album eras: {}", e);
+        }
+    };
+}
+
+pub async fn fetch_albums_by_era_request(
+    reader_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<FetchAlbumsByEraRequest>,
+) {
+    let request = dart_signal.message;
+
+    debug!("Requesting albums of eras: {:?}", request.start_years);
+
+    match get_albums_by_era(&reader_db, era_of(request.decades), request.start_years).await {
+        Ok(entry) => {
+            let mut response = AlbumsEras {
+                decades: request.decades,
+                eras: entry
+                    .into_iter()
+                    .map(|(start_year, albums)| AlbumsEra {
+                        start_year,
+                        albums: albums
+                            .into_iter()
+                            .map(|x| Album {
+                                id: x.0.id,
+                                name: x.0.name,
+                                cover_ids: x.1.into_iter().collect(),
+                                cover_blurhashes: Vec::new(),
+                                year: x.0.year.unwrap_or_default(),
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            };
+            attach_cover_blurhashes(
+                &reader_db,
+                response.eras.iter_mut().flat_map(|x| x.albums.iter_mut()),
+            )
+            .await;
+            response.send_signal_to_dart();
+        }
+        Err(e) => {
+            error!("Failed to fetch albums of eras: {}", e);
+        }
+    };
+}
+
+pub async fn check_gapless_albums_request(
+    reader_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<CheckGaplessAlbumsRequest>,
+) {
+    let album_ids = dart_signal.message.album_ids;
+    let album_ids = Some(album_ids.as_slice()).filter(|x| !x.is_empty());
+
+    let response = match check_gapless_albums(&reader_db, album_ids).await {
+        Ok(reports) => CheckGaplessAlbumsResponse {
+            albums: reports
+                .into_iter()
+                .map(|x| GaplessAlbumReport {
+                    album_id: x.album_id,
+                    album_name: x.album_name,
+                    problems: x
+                        .problems
+                        .into_iter()
+                        .map(|problem| match problem {
+                            GaplessProblem::MixedSampleRates(sample_rates) => GaplessAlbumProblem {
+                                kind: "mixed_sample_rates".to_string(),
+                                sample_rates,
+                                file_ids: Vec::new(),
+                            },
+                            GaplessProblem::MissingEncoderDelay(file_ids) => GaplessAlbumProblem {
+                                kind: "missing_encoder_delay".to_string(),
+                                sample_rates: Vec::new(),
+                                file_ids,
+                            },
+                        })
+                        .collect(),
+                })
+                .collect(),
+            success: true,
+            error: String::new(),
+        },
+        Err(e) => {
+            error!("Failed to check albums for gapless playback: {}", e);
+            CheckGaplessAlbumsResponse {
+                albums: Vec::new(),
+                success: false,
+                error: e.to_string(),
+            }
         }
     };
+
+    response.send_signal_to_dart();
 }
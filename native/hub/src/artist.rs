@@ -1,15 +1,16 @@
 use database::actions::artists::get_artists_by_ids;
 use database::actions::cover_art::get_magic_cover_art_id;
 use database::actions::library::get_artist_cover_ids;
-use log::{debug, error};
 use rinf::DartSignal;
 use std::sync::Arc;
+use tracing::{debug, error};
 
 use database::actions::artists::get_artists_groups;
 use database::actions::utils::create_count_by_first_letter;
 use database::connection::MainDbConnection;
 use database::entities::artists;
 
+use crate::cover_art::attach_cover_blurhashes;
 use crate::messages::artist::Artist;
 use crate::messages::artist::ArtistGroupSummaryResponse;
 use crate::messages::artist::ArtistsGroup;
@@ -21,14 +22,14 @@ use crate::FetchArtistsByIdsRequest;
 use crate::FetchArtistsByIdsResponse;
 
 pub async fn fetch_artists_group_summary_request(
-    main_db: Arc<MainDbConnection>,
+    reader_db: Arc<MainDbConnection>,
     _dart_signal: DartSignal<FetchArtistsGroupSummaryRequest>,
 ) {
     debug!("Requesting summary group");
 
     let count_artists = create_count_by_first_letter::<artists::Entity>();
 
-    match count_artists(&main_db).await {
+    match count_artists(&reader_db).await {
         Ok(entry) => {
             let artists_groups = entry
                 .into_iter()
@@ -47,16 +48,16 @@ pub async fn fetch_artists_group_summary_request(
 }
 
 pub async fn fetch_artists_groups_request(
-    main_db: Arc<MainDbConnection>,
+    reader_db: Arc<MainDbConnection>,
     dart_signal: DartSignal<FetchArtistsGroupsRequest>,
 ) {
     let request = dart_signal.message;
 
     debug!("Requesting artists groups");
 
-    match get_artists_groups(&main_db, request.group_titles).await {
+    match get_artists_groups(&reader_db, request.group_titles).await {
         Ok(entry) => {
-            ArtistsGroups {
+            let mut response = ArtistsGroups {
                 groups: entry
                     .into_iter()
                     .map(|x| ArtistsGroup {
@@ -68,12 +69,21 @@ pub async fn fetch_artists_groups_request(
                                 id: x.0.id,
                                 name: x.0.name,
                                 cover_ids: x.1.into_iter().collect(),
+                                cover_blurhashes: Vec::new(),
                             })
                             .collect(),
                     })
                     .collect(),
-            }
-            .send_signal_to_dart();
+            };
+            attach_cover_blurhashes(
+                &reader_db,
+                response
+                    .groups
+                    .iter_mut()
+                    .flat_map(|x| x.artists.iter_mut()),
+            )
+            .await;
+            response.send_signal_to_dart();
         }
         Err(e) => {
             error!("Failed to fetch artists groups: {}", e);
@@ -82,19 +92,19 @@ pub async fn fetch_artists_groups_request(
 }
 
 pub async fn fetch_artists_by_ids_request(
-    main_db: Arc<MainDbConnection>,
+    reader_db: Arc<MainDbConnection>,
     dart_signal: DartSignal<FetchArtistsByIdsRequest>,
 ) {
     let request = dart_signal.message;
 
     debug!("Requesting artists: {:#?}", request.ids);
 
-    match get_artists_by_ids(&main_db, &request.ids).await {
+    match get_artists_by_ids(&reader_db, &request.ids).await {
         Ok(items) => {
-            let magic_cover_id = get_magic_cover_art_id(&main_db).await.unwrap_or(-1);
-            let covers = get_artist_cover_ids(&main_db, &items).await.unwrap();
+            let magic_cover_id = get_magic_cover_art_id(&reader_db).await.unwrap_or(-1);
+            let covers = get_artist_cover_ids(&reader_db, &items).await.unwrap();
 
-            FetchArtistsByIdsResponse {
+            let mut response = FetchArtistsByIdsResponse {
                 result: items
                     .into_iter()
                     .map(|x| Artist {
@@ -107,10 +117,12 @@ pub async fn fetch_artists_by_ids_request(
                             .into_iter()
                             .filter(|x| *x != magic_cover_id)
                             .collect(),
+                        cover_blurhashes: Vec::new(),
                     })
                     .collect(),
-            }
-            .send_signal_to_dart();
+            };
+            attach_cover_blurhashes(&reader_db, response.result.iter_mut()).await;
+            response.send_signal_to_dart();
         }
         Err(e) => {
             error!("Failed to fetch albums groups: {}", e);
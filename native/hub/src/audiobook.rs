@@ -0,0 +1,157 @@
+use rinf::DartSignal;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, error};
+
+use database::actions::audiobooks::{
+    get_audiobook_file_ids, get_audiobooks, get_playback_position, set_directory_content_type,
+    set_playback_position, ContentType,
+};
+use database::actions::file::get_file_by_id;
+use database::connection::MainDbConnection;
+use playback::player::{PlaybackState, Player};
+
+use crate::messages::audiobook::{
+    Audiobook, AudiobookAuthor, AudiobookSeries, FetchAudiobooksRequest, FetchAudiobooksResponse,
+    SetDirectoryContentTypeRequest, SetDirectoryContentTypeResponse,
+};
+
+// How often the position of a playing audiobook is written back
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+// A chapter stopped this close to its end counts as finished
+const FINISHED_MARGIN: f64 = 5.0;
+
+pub async fn set_directory_content_type_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SetDirectoryContentTypeRequest>,
+) {
+    let request = dart_signal.message;
+    let content_type = ContentType::from(request.content_type.as_str());
+
+    match set_directory_content_type(&main_db, &request.directory, content_type).await {
+        Ok(_) => SetDirectoryContentTypeResponse { success: true }.send_signal_to_dart(),
+        Err(e) => {
+            error!("Failed to set content type: {}", e);
+            SetDirectoryContentTypeResponse { success: false }.send_signal_to_dart()
+        }
+    }
+}
+
+pub async fn fetch_audiobooks_request(
+    reader_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchAudiobooksRequest>,
+) {
+    match get_audiobooks(&reader_db).await {
+        Ok(authors) => FetchAudiobooksResponse {
+            authors: authors
+                .into_iter()
+                .map(|author| AudiobookAuthor {
+                    name: author.name,
+                    series: author
+                        .series
+                        .into_iter()
+                        .map(|series| AudiobookSeries {
+                            name: series.name,
+                            books: series
+                                .books
+                                .into_iter()
+                                .map(|book| Audiobook {
+                                    title: book.title,
+                                    file_ids: book.file_ids,
+                                })
+                                .collect(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch audiobooks: {}", e),
+    }
+}
+
+struct CurrentAudiobook {
+    id: i32,
+    duration: f64,
+    position: f64,
+}
+
+async fn save_position(user_db: &MainDbConnection, book: &CurrentAudiobook) {
+    // Chapters shorter than the margin would be finished as soon as
+    // they start
+    let finished =
+        book.duration > FINISHED_MARGIN && book.position >= book.duration - FINISHED_MARGIN;
+    let position = if finished { 0. } else { book.position };
+
+    if let Err(e) = set_playback_position(user_db, book.id, position).await {
+        error!("Failed to save audiobook position: {}", e);
+    }
+}
+
+/// Follow the player and always remember where an audiobook was left,
+/// resuming from that position the next time it is loaded.
+pub async fn remember_audiobook_positions(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+) {
+    let mut status_receiver = player.lock().await.subscribe_status();
+    let mut last_id: Option<i32> = None;
+    let mut current: Option<CurrentAudiobook> = None;
+    let mut last_saved = Instant::now();
+
+    while let Ok(status) = status_receiver.recv().await {
+        if status.id != last_id {
+            if let Some(book) = current.take() {
+                save_position(&user_db, &book).await;
+            }
+            last_id = status.id;
+
+            let Some(id) = status.id else {
+                continue;
+            };
+
+            let is_audiobook = match get_audiobook_file_ids(&main_db, &[id]).await {
+                Ok(ids) => ids.contains(&id),
+                Err(e) => {
+                    error!("Failed to get content type of {}: {}", id, e);
+                    false
+                }
+            };
+            if !is_audiobook {
+                continue;
+            }
+
+            let duration = match get_file_by_id(&main_db, id).await {
+                Ok(Some(file)) => file.duration,
+                _ => f64::MAX,
+            };
+
+            if let Ok(Some(saved)) = get_playback_position(&user_db, id).await {
+                if saved > 0. && status.position.as_secs_f64() < 1. {
+                    debug!("Resuming audiobook {} at {}", id, saved);
+                    player.lock().await.seek(saved);
+                }
+            }
+
+            current = Some(CurrentAudiobook {
+                id,
+                duration,
+                position: status.position.as_secs_f64(),
+            });
+            last_saved = Instant::now();
+            continue;
+        }
+
+        if let Some(book) = current.as_mut() {
+            book.position = status.position.as_secs_f64();
+
+            let idle = !matches!(status.state, PlaybackState::Playing);
+            if idle || last_saved.elapsed() >= SAVE_INTERVAL {
+                save_position(&user_db, book).await;
+                last_saved = Instant::now();
+            }
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use rinf::DartSignal;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use database::actions::analysis_cache::forget_cached_analysis;
+use database::actions::bulk::{
+    add_files_to_playlist, delete_files, reset_analysis, BulkReport, DeleteMode,
+    PERMANENT_DELETE_KEY,
+};
+use database::actions::journal::{Operation, OperationJournal};
+use database::actions::path_tags::{apply_path_tags, parse_path_patterns, preview_path_tags};
+use database::actions::playlists::{get_playlist_items, DuplicatePolicy};
+use database::actions::query_cache::QueryCache;
+use database::actions::ratings::{get_ratings, set_ratings};
+use database::actions::settings::{get_setting, set_setting};
+use database::connection::{
+    connect_analysis_cache_db, MainDbConnection, RecommendationDbConnection, SearchDbConnection,
+};
+
+use crate::journal::record_playlist_edit;
+use crate::library_manage::{analyse_library, analysis_cache_path, LibraryMode};
+use crate::messages::bulk::{
+    BulkAddToPlaylistRequest, BulkApplyTagsRequest, BulkDeleteRequest, BulkFailure,
+    BulkOperationProgress, BulkOperationResponse, BulkReanalyseRequest, BulkSetRatingRequest,
+    PathTag, PathTagProposal, PreviewPathTagsRequest, PreviewPathTagsResponse,
+    SetPermanentDeleteRequest,
+};
+use crate::playlist::{sync_playlist_mosaic, sync_playlist_mosaics};
+use crate::users::active_user_id;
+
+const ADD_TO_PLAYLIST: &str = "add_to_playlist";
+const RATE: &str = "rate";
+const DELETE: &str = "delete";
+const REANALYSE: &str = "reanalyse";
+const APPLY_TAGS: &str = "apply_tags";
+
+fn send_report(operation: &str, report: BulkReport) {
+    let failures = report
+        .failed
+        .iter()
+        .filter_map(|file_id| {
+            report.errors.get(file_id).map(|error| BulkFailure {
+                file_id: *file_id,
+                error: error.clone(),
+            })
+        })
+        .collect();
+
+    BulkOperationResponse {
+        operation: operation.to_string(),
+        success: report.failed.is_empty(),
+        succeeded_ids: report.succeeded,
+        skipped_ids: report.skipped,
+        failed_ids: report.failed,
+        error: String::new(),
+        failures,
+    }
+    .send_signal_to_dart();
+}
+
+fn send_error(operation: &str, file_ids: Vec<i32>, error: String) {
+    BulkOperationResponse {
+        operation: operation.to_string(),
+        success: false,
+        succeeded_ids: Vec::new(),
+        skipped_ids: Vec::new(),
+        failed_ids: file_ids,
+        error,
+        failures: Vec::new(),
+    }
+    .send_signal_to_dart();
+}
+
+fn reject_read_only(operation: &str, file_ids: Vec<i32>) {
+    warn!(
+        "Library is read-only, skipping bulk operation: {}",
+        operation
+    );
+    send_error(operation, file_ids, "The library is read-only".to_string());
+}
+
+pub async fn bulk_add_to_playlist_request(
+    main_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    journal: Arc<OperationJournal>,
+    dart_signal: DartSignal<BulkAddToPlaylistRequest>,
+) {
+    let request = dart_signal.message;
+    if lib_mode.is_read_only() {
+        return reject_read_only(ADD_TO_PLAYLIST, request.file_ids);
+    }
+
+    info!(
+        "Adding {} files to playlist {}",
+        request.file_ids.len(),
+        request.playlist_id
+    );
+
+    let before = get_playlist_items(&main_db, request.playlist_id).await;
+    let policy =
+        DuplicatePolicy::from_name(&request.duplicate_policy).unwrap_or(DuplicatePolicy::Skip);
+
+    match add_files_to_playlist(&main_db, request.playlist_id, &request.file_ids, policy).await {
+        Ok(report) => {
+            record_playlist_edit(&main_db, &journal, request.playlist_id, before).await;
+            sync_playlist_mosaic(&main_db, &lib_path, request.playlist_id).await;
+            query_cache.invalidate_playlists();
+            send_report(ADD_TO_PLAYLIST, report);
+        }
+        Err(e) => {
+            error!("Failed to add files to playlist: {}", e);
+            send_error(ADD_TO_PLAYLIST, request.file_ids, e.to_string());
+        }
+    }
+}
+
+pub async fn bulk_set_rating_request(
+    user_db: Arc<MainDbConnection>,
+    query_cache: Arc<QueryCache>,
+    journal: Arc<OperationJournal>,
+    dart_signal: DartSignal<BulkSetRatingRequest>,
+) {
+    let request = dart_signal.message;
+
+    info!(
+        "Rating {} files with {}",
+        request.file_ids.len(),
+        request.rating
+    );
+
+    let user_id = active_user_id(&user_db).await;
+    let before = get_ratings(&user_db, user_id, &request.file_ids).await;
+
+    match set_ratings(&user_db, user_id, &request.file_ids, request.rating).await {
+        Ok(_) => {
+            match before {
+                Ok(before) => journal.record(Operation::rating_change(
+                    user_id,
+                    request.file_ids.clone(),
+                    before,
+                    request.rating,
+                )),
+                Err(e) => error!("Unable to record the rating change: {}", e),
+            }
+            query_cache.invalidate_files(&request.file_ids);
+            send_report(
+                RATE,
+                BulkReport {
+                    succeeded: request.file_ids,
+                    ..Default::default()
+                },
+            );
+        }
+        Err(e) => {
+            error!("Failed to rate files: {}", e);
+            send_error(RATE, request.file_ids, e.to_string());
+        }
+    }
+}
+
+pub async fn bulk_delete_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    search_db: Arc<Mutex<SearchDbConnection>>,
+    lib_path: Arc<String>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    dart_signal: DartSignal<BulkDeleteRequest>,
+) {
+    let request = dart_signal.message;
+    if lib_mode.is_read_only() {
+        return reject_read_only(DELETE, request.file_ids);
+    }
+
+    let mode = match get_setting(user_db.as_ref(), PERMANENT_DELETE_KEY).await {
+        Ok(value) => DeleteMode::from_setting(value.as_deref()),
+        Err(e) => {
+            error!("Unable to read the deletion setting: {}", e);
+            DeleteMode::Trash
+        }
+    };
+
+    info!("Deleting {} files ({:?})", request.file_ids.len(), mode);
+
+    let mut search_db = search_db.lock().await;
+    let result = delete_files(
+        &main_db,
+        &mut search_db,
+        Path::new(lib_path.as_ref()),
+        &request.file_ids,
+        mode,
+        |progress, total| {
+            BulkOperationProgress {
+                operation: DELETE.to_string(),
+                progress: progress as i32,
+                total: total as i32,
+            }
+            .send_signal_to_dart()
+        },
+    )
+    .await;
+
+    match result {
+        Ok(report) => {
+            query_cache.invalidate_files(&report.succeeded);
+            drop(search_db);
+            sync_playlist_mosaics(&main_db, &lib_path).await;
+            send_report(DELETE, report);
+        }
+        Err(e) => {
+            error!("Failed to delete files: {}", e);
+            send_error(DELETE, request.file_ids, e.to_string());
+        }
+    }
+}
+
+pub async fn set_permanent_delete_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SetPermanentDeleteRequest>,
+) {
+    let enabled = dart_signal.message.enabled;
+    if let Err(e) = set_setting(user_db.as_ref(), PERMANENT_DELETE_KEY, enabled.to_string()).await {
+        error!("Unable to save the deletion setting: {}", e);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn bulk_reanalyse_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    recommend_db: Arc<RecommendationDbConnection>,
+    lib_path: Arc<String>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    cancel_token: Arc<CancellationToken>,
+    dart_signal: DartSignal<BulkReanalyseRequest>,
+) {
+    let request = dart_signal.message;
+    if lib_mode.is_read_only() {
+        return reject_read_only(REANALYSE, request.file_ids);
+    }
+
+    info!("Analysing {} files again", request.file_ids.len());
+
+    let file_hashes = match reset_analysis(&main_db, &request.file_ids).await {
+        Ok(file_hashes) => file_hashes,
+        Err(e) => {
+            error!("Failed to reset analysis results: {}", e);
+            return send_error(REANALYSE, request.file_ids, e.to_string());
+        }
+    };
+
+    // Otherwise the cached results would be copied right back
+    match connect_analysis_cache_db(&analysis_cache_path()).await {
+        Ok(cache) => {
+            if let Err(e) = forget_cached_analysis(&cache, &file_hashes).await {
+                warn!("Unable to update the analysis cache: {}", e);
+            }
+        }
+        Err(e) => warn!("Unable to open the analysis cache: {}", e),
+    }
+
+    analyse_library(&main_db, &user_db, &recommend_db, &lib_path, &cancel_token).await;
+    query_cache.invalidate_analysis();
+
+    send_report(
+        REANALYSE,
+        BulkReport {
+            succeeded: request.file_ids,
+            ..Default::default()
+        },
+    );
+}
+
+fn path_tags(tags: Vec<(String, String)>) -> Vec<PathTag> {
+    tags.into_iter()
+        .map(|(field, value)| PathTag { field, value })
+        .collect()
+}
+
+pub async fn preview_path_tags_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<PreviewPathTagsRequest>,
+) {
+    let request = dart_signal.message;
+
+    let patterns = match parse_path_patterns(&request.patterns) {
+        Ok(patterns) => patterns,
+        Err(e) => {
+            warn!("Invalid path pattern: {}", e);
+            PreviewPathTagsResponse {
+                proposals: Vec::new(),
+                patterns: request.patterns,
+                success: false,
+                error: e.to_string(),
+            }
+            .send_signal_to_dart();
+            return;
+        }
+    };
+    let pattern_names: Vec<String> = patterns.iter().map(|x| x.as_str().to_string()).collect();
+
+    match preview_path_tags(&main_db, &request.directory, &patterns).await {
+        Ok(proposals) => PreviewPathTagsResponse {
+            proposals: proposals
+                .into_iter()
+                .map(|x| PathTagProposal {
+                    file_id: x.file_id,
+                    directory: x.directory,
+                    file_name: x.file_name,
+                    pattern: x.pattern,
+                    current: path_tags(x.current),
+                    proposed: path_tags(x.proposed),
+                })
+                .collect(),
+            patterns: pattern_names,
+            success: true,
+            error: String::new(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => {
+            error!("Failed to read tags from paths: {}", e);
+            PreviewPathTagsResponse {
+                proposals: Vec::new(),
+                patterns: pattern_names,
+                success: false,
+                error: e.to_string(),
+            }
+            .send_signal_to_dart();
+        }
+    }
+}
+
+pub async fn bulk_apply_tags_request(
+    main_db: Arc<MainDbConnection>,
+    search_db: Arc<Mutex<SearchDbConnection>>,
+    lib_path: Arc<String>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    dart_signal: DartSignal<BulkApplyTagsRequest>,
+) {
+    let request = dart_signal.message;
+    let file_ids: Vec<i32> = request.files.iter().map(|x| x.file_id).collect();
+    if lib_mode.is_read_only() {
+        return reject_read_only(APPLY_TAGS, file_ids);
+    }
+
+    info!(
+        "Applying tags to {} files (write back: {})",
+        file_ids.len(),
+        request.write_back
+    );
+
+    let tags: Vec<(i32, Vec<(String, String)>)> = request
+        .files
+        .into_iter()
+        .map(|x| {
+            (
+                x.file_id,
+                x.tags.into_iter().map(|x| (x.field, x.value)).collect(),
+            )
+        })
+        .collect();
+
+    let mut search_db = search_db.lock().await;
+    match apply_path_tags(
+        &main_db,
+        &mut search_db,
+        Path::new(lib_path.as_ref()),
+        &tags,
+        request.write_back,
+    )
+    .await
+    {
+        Ok(report) => {
+            // Albums and artists may have been created or emptied
+            query_cache.clear();
+            send_report(APPLY_TAGS, report);
+        }
+        Err(e) => {
+            error!("Failed to apply tags: {}", e);
+            send_error(APPLY_TAGS, file_ids, e.to_string());
+        }
+    }
+}
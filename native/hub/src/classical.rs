@@ -0,0 +1,81 @@
+use rinf::DartSignal;
+use std::sync::Arc;
+use tracing::error;
+
+use database::actions::classical::{get_album_works, get_composers, get_works_of_composer, Work};
+use database::connection::MainDbConnection;
+
+use crate::messages::classical::{
+    ClassicalWork, Composer, FetchAlbumWorksRequest, FetchAlbumWorksResponse,
+    FetchComposerWorksRequest, FetchComposerWorksResponse, FetchComposersRequest,
+    FetchComposersResponse, WorkMovement,
+};
+
+fn to_classical_work(work: Work) -> ClassicalWork {
+    ClassicalWork {
+        composer: work.composer,
+        title: work.title,
+        movements: work
+            .movements
+            .into_iter()
+            .map(|x| WorkMovement {
+                file_id: x.file_id,
+                number: x.number.unwrap_or_default(),
+                title: x.title,
+            })
+            .collect(),
+    }
+}
+
+pub async fn fetch_composers_request(
+    reader_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchComposersRequest>,
+) {
+    match get_composers(&reader_db).await {
+        Ok(composers) => FetchComposersResponse {
+            composers: composers
+                .into_iter()
+                .map(|x| Composer {
+                    name: x.name,
+                    work_count: x.work_count as i32,
+                    track_count: x.track_count as i32,
+                })
+                .collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch composers: {}", e),
+    }
+}
+
+pub async fn fetch_composer_works_request(
+    reader_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<FetchComposerWorksRequest>,
+) {
+    let composer = dart_signal.message.composer;
+
+    match get_works_of_composer(&reader_db, &composer).await {
+        Ok(works) => FetchComposerWorksResponse {
+            composer,
+            works: works.into_iter().map(to_classical_work).collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch works of {}: {}", composer, e),
+    }
+}
+
+pub async fn fetch_album_works_request(
+    reader_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<FetchAlbumWorksRequest>,
+) {
+    let album_id = dart_signal.message.album_id;
+
+    match get_album_works(&reader_db, album_id).await {
+        Ok(album) => FetchAlbumWorksResponse {
+            album_id,
+            works: album.works.into_iter().map(to_classical_work).collect(),
+            other_file_ids: album.other_file_ids,
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch works of album {}: {}", album_id, e),
+    }
+}
@@ -1,25 +1,48 @@
 use crate::common::*;
+use crate::library_manage::create_library_request;
 use crate::messages;
-use log::info;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
-pub async fn receive_media_library_path<F, Fut>(main_loop: F) -> Result<()>
+pub async fn receive_media_library_path<F>(main_loop: F) -> Result<()>
 where
-    F: Fn(String) -> Fut + Send + Sync,
-    Fut: std::future::Future<Output = ()> + Send,
+    F: Fn(String, CancellationToken) -> JoinHandle<()>,
 {
     use messages::connection::*;
+    use messages::library_manage::{CreateLibraryRequest, OpenLibraryRequest};
 
     let mut receiver = MediaLibraryPath::get_dart_signal_receiver()?; // GENERATED
+    let mut open_receiver = OpenLibraryRequest::get_dart_signal_receiver()?; // GENERATED
+    let mut create_receiver = CreateLibraryRequest::get_dart_signal_receiver()?; // GENERATED
+
+    let mut current_library: Option<(CancellationToken, JoinHandle<()>)> = None;
 
     loop {
-        while let Some(dart_signal) = receiver.recv().await {
-            let media_library_path = dart_signal.message.path;
+        let media_library_path = tokio::select! {
+            Some(dart_signal) = receiver.recv() => dart_signal.message.path,
+            Some(dart_signal) = open_receiver.recv() => dart_signal.message.path,
+            Some(dart_signal) = create_receiver.recv() => {
+                create_library_request(dart_signal).await;
+                continue;
+            }
+            else => break,
+        };
 
-            info!("Received path: {}", media_library_path);
+        info!("Received path: {}", media_library_path);
 
-            main_loop(media_library_path).await;
+        // Only one library can be opened at a time, wait for the previous one
+        // to finish its shutdown sequence before touching the new one.
+        if let Some((cancel_token, handle)) = current_library.take() {
+            info!("Closing the current library");
+            cancel_token.cancel();
+            let _ = handle.await;
         }
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        let cancel_token = CancellationToken::new();
+        let handle = main_loop(media_library_path, cancel_token.clone());
+        current_library = Some((cancel_token, handle));
     }
+
+    Ok(())
 }
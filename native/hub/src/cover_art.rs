@@ -1,17 +1,99 @@
-use log::{debug, error, info, warn};
 use rinf::DartSignal;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tracing::{debug, error, info, warn};
 
-use database::actions::cover_art::get_cover_art_by_id;
+use database::actions::cover_art::collect_cover_art_garbage;
+use database::actions::cover_art::get_cover_art_blurhashes;
 use database::actions::cover_art::get_random_cover_art_ids;
-use database::actions::cover_art::sync_cover_art_by_file_id;
+use database::actions::query_cache::QueryCache;
 use database::connection::MainDbConnection;
 
+use crate::library_manage::LibraryMode;
+use crate::messages::album::Album;
+use crate::messages::artist::Artist;
 use crate::messages::cover_art::*;
+use crate::messages::library_home::PinnedCollection;
+use crate::messages::playlist::Playlist;
+
+/// Listed items that show covers, and the placeholders shown while they load.
+pub trait CoverPlaceholders {
+    fn cover_ids(&self) -> &[i32];
+    fn set_cover_blurhashes(&mut self, blurhashes: Vec<String>);
+}
+
+macro_rules! impl_cover_placeholders {
+    ($($item:ty),*) => {
+        $(
+            impl CoverPlaceholders for $item {
+                fn cover_ids(&self) -> &[i32] {
+                    &self.cover_ids
+                }
+
+                fn set_cover_blurhashes(&mut self, blurhashes: Vec<String>) {
+                    self.cover_blurhashes = blurhashes;
+                }
+            }
+        )*
+    };
+}
+
+impl_cover_placeholders!(Album, Artist, Playlist, PinnedCollection);
+
+/// Fill in the cover blurhashes of listed items with a single query.
+pub async fn attach_cover_blurhashes<'a, T>(
+    main_db: &MainDbConnection,
+    items: impl IntoIterator<Item = &'a mut T>,
+) where
+    T: CoverPlaceholders + 'a,
+{
+    let items: Vec<&mut T> = items.into_iter().collect();
+    let ids: Vec<i32> = items
+        .iter()
+        .flat_map(|x| x.cover_ids().iter().copied())
+        .collect();
+    if ids.is_empty() {
+        return;
+    }
+
+    let blurhashes = get_cover_art_blurhashes(main_db, &ids)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Unable to get cover art blurhashes: {:?}", e);
+            HashMap::new()
+        });
+
+    for item in items {
+        let item_blurhashes = item
+            .cover_ids()
+            .iter()
+            .map(|x| blurhashes.get(x).cloned().unwrap_or_default())
+            .collect();
+        item.set_cover_blurhashes(item_blurhashes);
+    }
+}
+
+async fn palette_of(
+    main_db: &MainDbConnection,
+    query_cache: &QueryCache,
+    cover_art_id: i32,
+) -> Vec<u32> {
+    query_cache
+        .cover_art_palette(main_db, cover_art_id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(
+                "Unable to get palette of cover art {}: {:?}",
+                cover_art_id, e
+            );
+            Vec::new()
+        })
+}
 
 pub async fn get_cover_art_by_file_id_request(
     main_db: Arc<MainDbConnection>,
     lib_path: Arc<String>,
+    query_cache: Arc<QueryCache>,
     dart_signal: DartSignal<GetCoverArtByFileIdRequest>,
 ) {
     let request = dart_signal.message;
@@ -19,7 +101,10 @@ pub async fn get_cover_art_by_file_id_request(
 
     debug!("Requesting cover art by file ID: {}", file_id);
 
-    match sync_cover_art_by_file_id(&main_db, &lib_path, file_id).await {
+    match query_cache
+        .cover_art_of_file(&main_db, &lib_path, file_id)
+        .await
+    {
         Ok(cover_art) => {
             match cover_art {
                 Some((cover_art_id, cover_art)) => {
@@ -28,6 +113,7 @@ pub async fn get_cover_art_by_file_id_request(
                             file_id,
                             cover_art_id,
                             cover_art: Some(cover_art),
+                            palette: palette_of(&main_db, &query_cache, cover_art_id).await,
                         }
                         .send_signal_to_dart();
                         // GENERATED
@@ -36,6 +122,7 @@ pub async fn get_cover_art_by_file_id_request(
                             file_id,
                             cover_art_id,
                             cover_art: None,
+                            palette: Vec::new(),
                         }
                         .send_signal_to_dart();
                         // GENERATED
@@ -46,6 +133,7 @@ pub async fn get_cover_art_by_file_id_request(
                         file_id,
                         cover_art_id: -1,
                         cover_art: None,
+                        palette: Vec::new(),
                     }
                     .send_signal_to_dart();
                     // GENERATED
@@ -58,6 +146,7 @@ pub async fn get_cover_art_by_file_id_request(
                 file_id,
                 cover_art_id: -1,
                 cover_art: None,
+                palette: Vec::new(),
             }
             .send_signal_to_dart();
             // GENERATED
@@ -68,28 +157,32 @@ pub async fn get_cover_art_by_file_id_request(
 
 pub async fn get_cover_art_by_cover_art_id_request(
     main_db: Arc<MainDbConnection>,
+    query_cache: Arc<QueryCache>,
     dart_signal: DartSignal<GetCoverArtByCoverArtIdRequest>,
 ) {
     let cover_art_id = dart_signal.message.cover_art_id;
 
     debug!("Requesting cover art by cover art ID: {}", cover_art_id);
 
-    match get_cover_art_by_id(&main_db, cover_art_id).await {
+    match query_cache.cover_art(&main_db, cover_art_id).await {
         Ok(entry) => match entry {
             Some(entry) => CoverArtByCoverArtIdResponse {
                 cover_art_id,
                 cover_art: Some(entry),
+                palette: palette_of(&main_db, &query_cache, cover_art_id).await,
             }
             .send_signal_to_dart(),
             _none => CoverArtByCoverArtIdResponse {
                 cover_art_id,
                 cover_art: None,
+                palette: Vec::new(),
             }
             .send_signal_to_dart(),
         },
         Err(_) => CoverArtByCoverArtIdResponse {
             cover_art_id,
             cover_art: None,
+            palette: Vec::new(),
         }
         .send_signal_to_dart(),
     };
@@ -115,3 +208,46 @@ pub async fn get_random_cover_art_ids_request(
         }
     }
 }
+
+pub async fn collect_cover_art_garbage_request(
+    main_db: Arc<MainDbConnection>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    _dart_signal: DartSignal<CollectCoverArtGarbageRequest>,
+) {
+    if lib_mode.is_read_only() {
+        warn!("Library is read-only, skipping cover art garbage collection");
+        CollectCoverArtGarbageResponse {
+            success: false,
+            removed: 0,
+            reclaimed_bytes: 0,
+        }
+        .send_signal_to_dart();
+        return;
+    }
+
+    match collect_cover_art_garbage(&main_db).await {
+        Ok(report) => {
+            query_cache.invalidate_cover_arts();
+            info!(
+                "Removed {} unused cover arts, {} bytes reclaimed",
+                report.removed, report.reclaimed_bytes
+            );
+            CollectCoverArtGarbageResponse {
+                success: true,
+                removed: report.removed as i32,
+                reclaimed_bytes: report.reclaimed_bytes,
+            }
+            .send_signal_to_dart();
+        }
+        Err(e) => {
+            error!("Unable to collect cover art garbage: {:?}", e);
+            CollectCoverArtGarbageResponse {
+                success: false,
+                removed: 0,
+                reclaimed_bytes: 0,
+            }
+            .send_signal_to_dart();
+        }
+    }
+}
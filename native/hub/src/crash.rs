@@ -0,0 +1,49 @@
+use std::backtrace::Backtrace;
+use std::panic::PanicHookInfo;
+use std::thread;
+
+use tracing::error;
+
+use crate::messages::crash::CrashReport;
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic payload".to_string()
+    }
+}
+
+/// Install a panic hook that reports panics to Dart instead of letting them
+/// disappear with the background thread or task they happened in.
+///
+/// The hook only reports, it is up to the caller (the main loop, the scan
+/// workers) to contain the unwinding so the rest of the app stays alive.
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let message = panic_message(info);
+        let location = info
+            .location()
+            .map(|x| format!("{}:{}:{}", x.file(), x.line(), x.column()))
+            .unwrap_or_default();
+        let thread = thread::current().name().unwrap_or("<unnamed>").to_string();
+        let backtrace = Backtrace::force_capture().to_string();
+
+        error!(
+            "Thread '{}' panicked at {}: {}\n{}",
+            thread, location, message, backtrace
+        );
+
+        CrashReport {
+            message,
+            location,
+            thread,
+            backtrace,
+        }
+        .send_signal_to_dart();
+    }));
+}
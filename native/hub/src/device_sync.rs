@@ -0,0 +1,142 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use rinf::DartSignal;
+use tracing::{error, info};
+
+use database::actions::device_sync::{
+    create_sync_device, get_sync_devices, remove_sync_device, sync_device, SyncSelection,
+};
+use database::connection::MainDbConnection;
+use database::entities::sync_devices;
+
+use crate::messages::device_sync::{
+    CreateSyncDeviceRequest, CreateSyncDeviceResponse, FetchSyncDevicesRequest,
+    RemoveSyncDeviceRequest, SyncDevice, SyncDeviceRequest, SyncDeviceResponse,
+    SyncDevicesResponse, SyncFailure,
+};
+
+fn to_sync_device(device: sync_devices::Model) -> SyncDevice {
+    SyncDevice {
+        id: device.id,
+        name: device.name,
+        path: device.path,
+        codec: device.codec.unwrap_or_default(),
+    }
+}
+
+async fn send_sync_devices(user_db: &MainDbConnection) {
+    match get_sync_devices(user_db).await {
+        Ok(devices) => SyncDevicesResponse {
+            devices: devices.into_iter().map(to_sync_device).collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch sync devices: {}", e),
+    }
+}
+
+pub async fn fetch_sync_devices_request(
+    user_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchSyncDevicesRequest>,
+) {
+    send_sync_devices(&user_db).await;
+}
+
+pub async fn create_sync_device_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<CreateSyncDeviceRequest>,
+) {
+    let request = dart_signal.message;
+    info!("Creating sync device {} at {}", request.name, request.path);
+
+    let codec = Some(request.codec).filter(|x| !x.is_empty());
+    match create_sync_device(&user_db, &request.name, &request.path, codec).await {
+        Ok(device) => CreateSyncDeviceResponse {
+            device: Some(to_sync_device(device)),
+            success: true,
+            error: String::new(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => {
+            error!("Failed to create sync device: {}", e);
+            CreateSyncDeviceResponse {
+                device: None,
+                success: false,
+                error: e.to_string(),
+            }
+            .send_signal_to_dart()
+        }
+    }
+}
+
+pub async fn remove_sync_device_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<RemoveSyncDeviceRequest>,
+) {
+    let device_id = dart_signal.message.device_id;
+    if let Err(e) = remove_sync_device(&user_db, device_id).await {
+        error!("Failed to remove sync device {}: {}", device_id, e);
+    }
+
+    send_sync_devices(&user_db).await;
+}
+
+pub async fn sync_device_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    dart_signal: DartSignal<SyncDeviceRequest>,
+) {
+    let request = dart_signal.message;
+    info!(
+        "Syncing device {}: playlists {:?}, albums {:?}",
+        request.device_id, request.playlist_ids, request.album_ids
+    );
+
+    let selection = SyncSelection {
+        playlist_ids: request.playlist_ids,
+        album_ids: request.album_ids,
+    };
+    let result = sync_device(
+        &main_db,
+        &user_db,
+        Path::new(lib_path.as_ref()),
+        request.device_id,
+        &selection,
+        request.prune,
+    )
+    .await;
+
+    match result {
+        Ok(report) => SyncDeviceResponse {
+            device_id: request.device_id,
+            success: true,
+            error: String::new(),
+            copied: report.copied as i32,
+            transcoded: report.transcoded as i32,
+            unchanged: report.unchanged as i32,
+            removed: report.removed as i32,
+            failed: report
+                .failed
+                .into_iter()
+                .map(|(file_id, error)| SyncFailure { file_id, error })
+                .collect(),
+            playlists: report
+                .playlists
+                .into_iter()
+                .map(|x| x.to_string_lossy().to_string())
+                .collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => {
+            error!("Failed to sync device {}: {:#}", request.device_id, e);
+            SyncDeviceResponse {
+                device_id: request.device_id,
+                success: false,
+                error: format!("{:#}", e),
+                ..Default::default()
+            }
+            .send_signal_to_dart()
+        }
+    }
+}
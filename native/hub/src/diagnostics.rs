@@ -0,0 +1,192 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rinf::DartSignal;
+use tokio::task;
+use tracing::{error, info};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use database::actions::diagnostics::{get_library_stats, get_schema_version};
+use database::connection::MainDbConnection;
+
+use crate::common::Result;
+use crate::logging::{log_dir, recent_logs};
+use crate::messages::diagnostics::*;
+
+// Log files of the previous days that come along with the current session
+const DIAGNOSTIC_LOG_FILES: usize = 2;
+const DIAGNOSTIC_LOG_LINES: usize = 2000;
+
+/// Hide the library location and the home directory, which usually carry
+/// the name of the user.
+fn anonymize(text: &str, lib_path: &str) -> String {
+    let mut text = text.replace(lib_path, "<library>");
+    if let Some(home) = dirs::home_dir().and_then(|x| x.to_str().map(str::to_string)) {
+        text = text.replace(&home, "<home>");
+    }
+    text
+}
+
+fn directory_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return fs::metadata(path).map(|x| x.len()).unwrap_or(0);
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(x) if x.is_dir() => directory_size(&entry.path()),
+            _ => entry.metadata().map(|x| x.len()).unwrap_or(0),
+        })
+        .sum()
+}
+
+// Newest first, the file names carry the day
+fn recent_log_files() -> Vec<PathBuf> {
+    let Some(dir) = log_dir() else {
+        return vec![];
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|x| x.path())
+        .filter(|x| x.extension().is_some_and(|x| x == "log"))
+        .collect();
+    files.sort();
+    files.into_iter().rev().take(DIAGNOSTIC_LOG_FILES).collect()
+}
+
+async fn describe_library(main_db: &MainDbConnection, lib_path: &str) -> String {
+    let mut lines = vec![
+        format!("Version: {}", env!("CARGO_PKG_VERSION")),
+        format!(
+            "Platform: {} {}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ),
+    ];
+
+    match get_schema_version(main_db).await {
+        Ok(version) => lines.push(format!(
+            "Schema version: {}",
+            version.unwrap_or_else(|| "none".to_string())
+        )),
+        Err(e) => lines.push(format!("Schema version: unreadable ({})", e)),
+    }
+
+    match get_library_stats(main_db).await {
+        Ok(stats) => {
+            lines.push(format!("Tracks: {}", stats.tracks));
+            lines.push(format!("Albums: {}", stats.albums));
+            lines.push(format!("Artists: {}", stats.artists));
+            lines.push(format!("Playlists: {}", stats.playlists));
+            lines.push(format!("Analysed tracks: {}", stats.analysed));
+            lines.push(format!("Cover arts: {}", stats.cover_arts));
+            lines.push(format!("Total duration: {:.0} s", stats.total_duration));
+        }
+        Err(e) => lines.push(format!("Library statistics: unreadable ({})", e)),
+    }
+
+    let data_dir = Path::new(lib_path).join(".rune");
+    for (name, path) in [
+        ("Main database", data_dir.join(".0.db")),
+        ("Search index", data_dir.join(".search")),
+        ("Recommendation index", data_dir.join(".analysis")),
+    ] {
+        lines.push(format!("{} size: {} bytes", name, directory_size(&path)));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+fn write_bundle(path: &Path, entries: Vec<(String, String)>) -> Result<()> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for (name, content) in entries {
+        zip.start_file(name, options)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    zip.finish()?;
+
+    Ok(())
+}
+
+/// Package what helps to understand a bug into a zip: the recent logs, the
+/// errors among them, the size of the library and of its indexes.
+///
+/// Paths to the library and to the home directory are hidden.
+pub async fn export_diagnostic_bundle(
+    main_db: &MainDbConnection,
+    lib_path: &str,
+    path: &Path,
+) -> Result<()> {
+    let session = recent_logs(DIAGNOSTIC_LOG_LINES);
+    // Panics are logged as errors along with their backtrace
+    let errors: Vec<&String> = session
+        .iter()
+        .filter(|x| x.contains(" ERROR ") || x.contains(" WARN "))
+        .collect();
+
+    let mut entries = vec![
+        (
+            "library.txt".to_string(),
+            describe_library(main_db, lib_path).await,
+        ),
+        (
+            "errors.log".to_string(),
+            errors.iter().map(|x| format!("{}\n", x)).collect(),
+        ),
+        (
+            "logs/session.log".to_string(),
+            session.iter().map(|x| format!("{}\n", x)).collect(),
+        ),
+    ];
+    for file in recent_log_files() {
+        let Some(name) = file.file_name().and_then(|x| x.to_str()) else {
+            continue;
+        };
+        if let Ok(content) = fs::read_to_string(&file) {
+            entries.push((format!("logs/{}", name), content));
+        }
+    }
+
+    let entries = entries
+        .into_iter()
+        .map(|(name, content)| (name, anonymize(&content, lib_path)))
+        .collect();
+    let path = path.to_path_buf();
+    task::spawn_blocking(move || write_bundle(&path, entries)).await?
+}
+
+pub async fn export_diagnostic_bundle_request(
+    main_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    dart_signal: DartSignal<ExportDiagnosticBundleRequest>,
+) {
+    let request = dart_signal.message;
+
+    info!("Exporting diagnostic bundle to: {}", request.path);
+
+    let (success, error) =
+        match export_diagnostic_bundle(&main_db, &lib_path, Path::new(&request.path)).await {
+            Ok(_) => (true, String::new()),
+            Err(e) => {
+                error!("Failed to export the diagnostic bundle: {}", e);
+                (false, e.to_string())
+            }
+        };
+
+    ExportDiagnosticBundleResponse {
+        path: request.path,
+        success,
+        error,
+    }
+    .send_signal_to_dart()
+}
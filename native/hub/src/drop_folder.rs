@@ -0,0 +1,196 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rinf::DartSignal;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use database::actions::drop_folder::{
+    import_files, is_valid_drop_folder, DropFolderWatch, DROP_FOLDER_KEY, DROP_FOLDER_PATTERN_KEY,
+};
+use database::actions::query_cache::QueryCache;
+use database::actions::settings::{get_setting, remove_setting, set_setting};
+use database::connection::{MainDbConnection, SearchDbConnection};
+
+use crate::library_manage::LibraryMode;
+use crate::messages::library_manage::{
+    DropFolderImportFailure, DropFolderImportResponse, DropFolderImportedFile, DropFolderResponse,
+    FetchDropFolderRequest, SetDropFolderRequest,
+};
+
+// Files are imported after two polls found them unchanged
+const DROP_FOLDER_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+async fn read_drop_folder(main_db: &MainDbConnection) -> (Option<String>, Option<String>) {
+    let read = |key: &'static str| async move {
+        match get_setting(main_db, key).await {
+            Ok(value) => value.filter(|x| !x.is_empty()),
+            Err(e) => {
+                error!("Unable to read {}: {}", key, e);
+                None
+            }
+        }
+    };
+
+    (
+        read(DROP_FOLDER_KEY).await,
+        read(DROP_FOLDER_PATTERN_KEY).await,
+    )
+}
+
+async fn send_drop_folder(main_db: &MainDbConnection) {
+    let (path, pattern) = read_drop_folder(main_db).await;
+
+    DropFolderResponse {
+        path: path.unwrap_or_default(),
+        pattern: pattern.unwrap_or_default(),
+        success: true,
+        error: String::new(),
+    }
+    .send_signal_to_dart();
+}
+
+pub async fn fetch_drop_folder_request(
+    main_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchDropFolderRequest>,
+) {
+    send_drop_folder(&main_db).await;
+}
+
+pub async fn set_drop_folder_request(
+    main_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    lib_mode: Arc<LibraryMode>,
+    dart_signal: DartSignal<SetDropFolderRequest>,
+) {
+    let request = dart_signal.message;
+
+    let fail = |error: String| {
+        warn!("Unable to set the drop folder: {}", error);
+        DropFolderResponse {
+            path: request.path.clone(),
+            pattern: request.pattern.clone(),
+            success: false,
+            error,
+        }
+        .send_signal_to_dart();
+    };
+
+    // Imports move files into the library
+    if lib_mode.is_read_only() {
+        fail("Library is read-only".to_string());
+        return;
+    }
+
+    if !request.path.is_empty()
+        && !is_valid_drop_folder(Path::new(lib_path.as_str()), Path::new(&request.path))
+    {
+        fail("The drop folder must be outside the library".to_string());
+        return;
+    }
+
+    for (key, value) in [
+        (DROP_FOLDER_KEY, &request.path),
+        (DROP_FOLDER_PATTERN_KEY, &request.pattern),
+    ] {
+        let result = if value.is_empty() {
+            remove_setting(main_db.as_ref(), key).await
+        } else {
+            set_setting(main_db.as_ref(), key, value.clone()).await
+        };
+
+        if let Err(e) = result {
+            fail(e.to_string());
+            return;
+        }
+    }
+
+    send_drop_folder(&main_db).await;
+}
+
+/// Import the audio files dropped into the drop folder once they stopped
+/// changing, following the setting while the library is open.
+pub async fn watch_drop_folder(
+    main_db: Arc<MainDbConnection>,
+    search_db: Arc<Mutex<SearchDbConnection>>,
+    lib_path: Arc<String>,
+    query_cache: Arc<QueryCache>,
+    cancel_token: CancellationToken,
+) {
+    let lib_path = Path::new(lib_path.as_str());
+    let mut watched: Option<PathBuf> = None;
+    let mut watch = DropFolderWatch::default();
+
+    loop {
+        tokio::select! {
+            _ = cancel_token.cancelled() => break,
+            _ = tokio::time::sleep(DROP_FOLDER_POLL_INTERVAL) => {}
+        }
+
+        let (path, pattern) = read_drop_folder(&main_db).await;
+        let drop_path = path
+            .map(PathBuf::from)
+            .filter(|x| x.is_dir() && is_valid_drop_folder(lib_path, x));
+
+        if drop_path != watched {
+            info!("Watching the drop folder {:?}", drop_path);
+            watched = drop_path;
+            watch = DropFolderWatch::default();
+        }
+        let Some(drop_path) = &watched else {
+            continue;
+        };
+
+        let files = watch.settled_files(drop_path);
+        if files.is_empty() {
+            continue;
+        }
+
+        info!("Importing {} files from the drop folder", files.len());
+        let mut search_db = search_db.lock().await;
+        let response = match import_files(
+            &main_db,
+            &mut search_db,
+            lib_path,
+            drop_path,
+            &files,
+            pattern.as_deref(),
+        )
+        .await
+        {
+            Ok(report) => DropFolderImportResponse {
+                imported: report
+                    .imported
+                    .into_iter()
+                    .map(|x| DropFolderImportedFile {
+                        source: x.source.to_string_lossy().to_string(),
+                        destination: x.destination.to_string_lossy().to_string(),
+                    })
+                    .collect(),
+                failed: report
+                    .failed
+                    .into_iter()
+                    .map(|x| DropFolderImportFailure {
+                        source: x.source.to_string_lossy().to_string(),
+                        error: x.error,
+                    })
+                    .collect(),
+                error: String::new(),
+            },
+            Err(e) => {
+                error!("Unable to import from the drop folder: {}", e);
+                DropFolderImportResponse {
+                    imported: Vec::new(),
+                    failed: Vec::new(),
+                    error: e.to_string(),
+                }
+            }
+        };
+        drop(search_db);
+        query_cache.clear();
+
+        response.send_signal_to_dart();
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use database::actions::health::{
+    check_collection_groups, check_cover_arts, check_recommendation_index, check_schema,
+    check_search_index, HealthStatus,
+};
+use database::connection::{MainDbConnection, RecommendationDbConnection, SearchDbConnection};
+
+use crate::library_manage::LibraryMode;
+use crate::messages::health::*;
+
+fn to_health_check_result(check: &str, status: HealthStatus) -> HealthCheckResult {
+    let (status, detail) = match status {
+        HealthStatus::Healthy => ("healthy", String::new()),
+        HealthStatus::Repaired(detail) => ("repaired", detail),
+        HealthStatus::Problem(detail) => ("problem", detail),
+    };
+
+    HealthCheckResult {
+        check: check.to_string(),
+        status: status.to_string(),
+        detail,
+    }
+}
+
+/// Check the schema, the indexes, the covers and the groups of a library that was just
+/// opened, repair what can be rebuilt and report what is left.
+///
+/// Read-only libraries are only checked, except their search index which is
+/// rebuilt in memory.
+pub async fn check_library_health(
+    main_db: Arc<MainDbConnection>,
+    recommend_db: Arc<RecommendationDbConnection>,
+    search_db: Arc<Mutex<SearchDbConnection>>,
+    lib_mode: Arc<LibraryMode>,
+) {
+    let repair = !lib_mode.is_read_only();
+
+    let schema = check_schema(&main_db, repair).await;
+    let search_index = {
+        let mut search_db = search_db.lock().await;
+        check_search_index(&main_db, &mut search_db, true).await
+    };
+    let recommendation_index = check_recommendation_index(&main_db, &recommend_db, repair).await;
+    let cover_arts = check_cover_arts(&main_db, repair).await;
+    let collection_groups = check_collection_groups(&main_db, repair).await;
+
+    let checks = vec![
+        to_health_check_result("schema", schema),
+        to_health_check_result("search_index", search_index),
+        to_health_check_result("recommendation_index", recommendation_index),
+        to_health_check_result("cover_arts", cover_arts),
+        to_health_check_result("collection_groups", collection_groups),
+    ];
+    let problems = checks.iter().filter(|x| x.status == "problem").count();
+    if problems == 0 {
+        info!("Library health check passed");
+    } else {
+        warn!("Library health check found {} problems", problems);
+    }
+
+    LibraryHealthReport { checks }.send_signal_to_dart(); // GENERATED
+}
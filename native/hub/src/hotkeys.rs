@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use global_hotkey::hotkey::{Code, HotKey, Modifiers};
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use tokio::sync::{mpsc, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use playback::player::{Player, PlayerStatus};
+use playback::PlayerCommand;
+
+use crate::player::play_pause_command;
+
+// Volume change of a single key press
+const VOLUME_STEP: f32 = 0.05;
+// How often the hotkey thread handles the events of the desktop and checks
+// whether the library was closed
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HotkeyAction {
+    PlayPause,
+    Next,
+    Previous,
+    VolumeUp,
+    VolumeDown,
+}
+
+// The volume uses a chord, the system keeps its own volume keys
+fn default_hotkeys() -> Vec<(HotKey, HotkeyAction)> {
+    let chord = Some(Modifiers::CONTROL | Modifiers::ALT);
+    vec![
+        (
+            HotKey::new(None, Code::MediaPlayPause),
+            HotkeyAction::PlayPause,
+        ),
+        (HotKey::new(None, Code::MediaTrackNext), HotkeyAction::Next),
+        (
+            HotKey::new(None, Code::MediaTrackPrevious),
+            HotkeyAction::Previous,
+        ),
+        (HotKey::new(chord, Code::ArrowUp), HotkeyAction::VolumeUp),
+        (
+            HotKey::new(chord, Code::ArrowDown),
+            HotkeyAction::VolumeDown,
+        ),
+    ]
+}
+
+fn to_command(action: HotkeyAction, status: &PlayerStatus) -> PlayerCommand {
+    match action {
+        HotkeyAction::PlayPause => play_pause_command(status),
+        HotkeyAction::Next => PlayerCommand::Next,
+        HotkeyAction::Previous => PlayerCommand::Previous,
+        HotkeyAction::VolumeUp => PlayerCommand::SetVolume((status.volume + VOLUME_STEP).min(1.0)),
+        HotkeyAction::VolumeDown => {
+            PlayerCommand::SetVolume((status.volume - VOLUME_STEP).max(0.0))
+        }
+    }
+}
+
+fn register_hotkeys(manager: &GlobalHotKeyManager) -> Vec<HotKey> {
+    let mut registered = vec![];
+    for (hotkey, action) in default_hotkeys() {
+        // Another application may own the key already
+        match manager.register(hotkey) {
+            Ok(_) => registered.push(hotkey),
+            Err(e) => warn!("Unable to register hotkey {:?}: {}", action, e),
+        }
+    }
+    info!("Registered {} global hotkeys", registered.len());
+    registered
+}
+
+fn release_hotkeys(manager: &GlobalHotKeyManager, registered: &[HotKey]) {
+    if let Err(e) = manager.unregister_all(registered) {
+        error!("Failed to release global hotkeys: {}", e);
+    }
+}
+
+// Forward the key presses until the library is closed, `pump` runs between
+// the waits for the platforms that need their events handled
+fn forward_hotkeys(
+    actions: &mpsc::UnboundedSender<HotkeyAction>,
+    cancel_token: &CancellationToken,
+    pump: impl Fn(),
+) {
+    let bindings: HashMap<u32, HotkeyAction> = default_hotkeys()
+        .into_iter()
+        .map(|(hotkey, action)| (hotkey.id(), action))
+        .collect();
+
+    let receiver = GlobalHotKeyEvent::receiver();
+    while !cancel_token.is_cancelled() {
+        pump();
+
+        let Ok(event) = receiver.recv_timeout(POLL_INTERVAL) else {
+            continue;
+        };
+        if event.state != HotKeyState::Pressed {
+            continue;
+        }
+
+        if let Some(action) = bindings.get(&event.id) {
+            if actions.send(*action).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+// WM_HOTKEY is posted to a hidden window of the thread that registered the
+// keys, nothing reaches the manager unless that thread dispatches it
+#[cfg(target_os = "windows")]
+fn dispatch_messages() {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        DispatchMessageW, PeekMessageW, TranslateMessage, MSG, PM_REMOVE,
+    };
+
+    unsafe {
+        let mut message: MSG = std::mem::zeroed();
+        while PeekMessageW(&mut message, 0, 0, 0, PM_REMOVE) != 0 {
+            TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+    }
+}
+
+// Windows delivers the keys to the thread that registered them and X11
+// reads them on a thread of its own, this one keeps them until the library
+// is closed
+#[cfg(not(target_os = "macos"))]
+fn watch_hotkeys(actions: mpsc::UnboundedSender<HotkeyAction>, cancel_token: CancellationToken) {
+    let manager = match GlobalHotKeyManager::new() {
+        Ok(manager) => manager,
+        Err(e) => {
+            error!("Global hotkeys are unavailable: {}", e);
+            return;
+        }
+    };
+    let registered = register_hotkeys(&manager);
+
+    #[cfg(target_os = "windows")]
+    forward_hotkeys(&actions, &cancel_token, dispatch_messages);
+    #[cfg(not(target_os = "windows"))]
+    forward_hotkeys(&actions, &cancel_token, || {});
+
+    release_hotkeys(&manager, &registered);
+}
+
+#[cfg(target_os = "macos")]
+thread_local! {
+    // Lives on the main thread, the only one whose run loop receives the keys
+    static MAIN_THREAD_HOTKEYS: std::cell::RefCell<Option<(GlobalHotKeyManager, Vec<HotKey>)>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+// macOS only delivers the keys on the run loop of the main thread, which
+// belongs to Flutter. The manager is created and released there, the
+// presses still arrive on the channel of `GlobalHotKeyEvent`.
+#[cfg(target_os = "macos")]
+fn watch_hotkeys(actions: mpsc::UnboundedSender<HotkeyAction>, cancel_token: CancellationToken) {
+    dispatch::Queue::main().exec_async(|| {
+        let manager = match GlobalHotKeyManager::new() {
+            Ok(manager) => manager,
+            Err(e) => {
+                error!("Global hotkeys are unavailable: {}", e);
+                return;
+            }
+        };
+        let registered = register_hotkeys(&manager);
+        MAIN_THREAD_HOTKEYS.with(|x| *x.borrow_mut() = Some((manager, registered)));
+    });
+
+    forward_hotkeys(&actions, &cancel_token, || {});
+
+    dispatch::Queue::main().exec_async(|| {
+        if let Some((manager, registered)) = MAIN_THREAD_HOTKEYS.with(|x| x.borrow_mut().take()) {
+            release_hotkeys(&manager, &registered);
+        }
+    });
+}
+
+/// Control the player with media keys and global shortcuts, also while the
+/// window doesn't have the focus.
+///
+/// Play/pause, next and previous use the media keys, the volume
+/// Ctrl+Alt+Up and Ctrl+Alt+Down.
+pub async fn listen_to_hotkeys(player: Arc<Mutex<Player>>, cancel_token: CancellationToken) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    thread::spawn(move || watch_hotkeys(sender, cancel_token));
+
+    while let Some(action) = receiver.recv().await {
+        let player = player.lock().await;
+        let command = to_command(action, &player.get_status());
+        player.command(command);
+    }
+}
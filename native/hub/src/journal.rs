@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use rinf::DartSignal;
+use sea_orm::DbErr;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use database::actions::journal::{Operation, OperationJournal};
+use database::actions::playlists::get_playlist_items;
+use database::actions::query_cache::QueryCache;
+use database::connection::MainDbConnection;
+use playback::player::Player;
+
+use crate::messages::journal::{
+    OperationJournalResponse, RedoLastOperationRequest, UndoLastOperationRequest,
+};
+use crate::playback::replace_queue;
+use crate::playlist::sync_playlist_mosaic;
+
+const UNDO: &str = "undo";
+const REDO: &str = "redo";
+
+/// Record an edit of a playlist, `before` are its items read before the edit.
+pub async fn record_playlist_edit(
+    main_db: &MainDbConnection,
+    journal: &OperationJournal,
+    playlist_id: i32,
+    before: Result<Vec<i32>, DbErr>,
+) {
+    let after = get_playlist_items(main_db, playlist_id).await;
+    match (before, after) {
+        (Ok(before), Ok(after)) => journal.record(Operation::PlaylistItems {
+            playlist_id,
+            before,
+            after,
+        }),
+        (Err(e), _) | (_, Err(e)) => {
+            error!(
+                "Unable to record the edit of playlist {}: {}",
+                playlist_id, e
+            )
+        }
+    }
+}
+
+async fn send_result(
+    action: &str,
+    result: Result<Option<Operation>, DbErr>,
+    main_db: &MainDbConnection,
+    lib_path: &str,
+    player: &Arc<Mutex<Player>>,
+    query_cache: &QueryCache,
+    journal: &OperationJournal,
+) {
+    let mut response = OperationJournalResponse {
+        action: action.to_string(),
+        kind: String::new(),
+        success: false,
+        playlist_id: 0,
+        file_ids: Vec::new(),
+        error: String::new(),
+        can_undo: false,
+        can_redo: false,
+    };
+
+    match result {
+        Ok(Some(operation)) => {
+            info!("Operation applied on {}: {}", action, operation.kind());
+            response.kind = operation.kind().to_string();
+            response.success = true;
+
+            match operation {
+                Operation::PlaylistItems {
+                    playlist_id, after, ..
+                } => {
+                    sync_playlist_mosaic(main_db, lib_path, playlist_id).await;
+                    query_cache.invalidate_playlists();
+                    response.playlist_id = playlist_id;
+                    response.file_ids = after;
+                }
+                Operation::Ratings { file_ids, .. } => {
+                    query_cache.invalidate_files(&file_ids);
+                    response.file_ids = file_ids;
+                }
+                Operation::Queue { after, .. } => {
+                    replace_queue(main_db, lib_path, player, &after).await;
+                    response.file_ids = after;
+                }
+            }
+        }
+        Ok(None) => info!("Nothing to {}", action),
+        Err(e) => {
+            error!("Unable to {} the last operation: {}", action, e);
+            response.error = e.to_string();
+        }
+    }
+
+    response.can_undo = journal.can_undo();
+    response.can_redo = journal.can_redo();
+    response.send_signal_to_dart();
+}
+
+pub async fn undo_last_operation_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    player: Arc<Mutex<Player>>,
+    query_cache: Arc<QueryCache>,
+    journal: Arc<OperationJournal>,
+    _dart_signal: DartSignal<UndoLastOperationRequest>,
+) {
+    let result = journal.undo_last_operation(&main_db, &user_db).await;
+    send_result(
+        UNDO,
+        result,
+        &main_db,
+        &lib_path,
+        &player,
+        &query_cache,
+        &journal,
+    )
+    .await;
+}
+
+pub async fn redo_last_operation_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    player: Arc<Mutex<Player>>,
+    query_cache: Arc<QueryCache>,
+    journal: Arc<OperationJournal>,
+    _dart_signal: DartSignal<RedoLastOperationRequest>,
+) {
+    let result = journal.redo_last_operation(&main_db, &user_db).await;
+    send_result(
+        REDO,
+        result,
+        &main_db,
+        &lib_path,
+        &player,
+        &query_cache,
+        &journal,
+    )
+    .await;
+}
@@ -1,59 +1,146 @@
 mod album;
 mod artist;
+mod audiobook;
+mod bulk;
+mod classical;
 mod common;
 mod connection;
 mod cover_art;
+mod crash;
+mod device_sync;
+mod diagnostics;
+mod drop_folder;
+mod health;
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+mod hotkeys;
+mod journal;
 mod library_home;
 mod library_manage;
+mod logging;
 mod media_file;
+mod merge;
 mod messages;
+mod metrics;
+mod notifications;
+mod output_device;
 mod playback;
 mod player;
 mod playlist;
+mod recent_contexts;
+mod remote;
 mod search;
+mod shutdown;
+mod streaming;
+mod transcode;
+mod users;
 
-use log::{debug, info};
+use futures::FutureExt;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
-use tracing_subscriber::filter::EnvFilter;
+use tracing::{debug, error, info};
 
 pub use tokio;
 
-use ::database::connection::connect_main_db;
-use ::database::connection::connect_recommendation_db;
-use ::database::connection::connect_search_db;
+use ::database::actions::journal::OperationJournal;
+use ::database::actions::query_cache::QueryCache;
+use ::database::actions::transcode::TranscodeQueue;
+use ::database::connection::is_library_read_only;
+use ::database::connection::{connect_main_db, connect_main_db_read_only, connect_overlay_db};
+use ::database::connection::{connect_main_db_readers, DEFAULT_MAIN_DB_READERS};
+use ::database::connection::{connect_recommendation_db, connect_recommendation_db_read_only};
+use ::database::connection::{connect_search_db, connect_search_db_read_only};
 use ::playback::player::Player;
 
 use crate::album::*;
 use crate::artist::*;
+use crate::audiobook::*;
+use crate::bulk::*;
+use crate::classical::*;
 use crate::connection::*;
 use crate::cover_art::*;
+use crate::crash::install_panic_hook;
+use crate::device_sync::*;
+use crate::diagnostics::*;
+use crate::drop_folder::*;
+use crate::health::check_library_health;
+use crate::journal::*;
 use crate::library_home::*;
 use crate::library_manage::*;
+use crate::logging::{initialize_logging, receive_logging_requests};
 use crate::media_file::*;
+use crate::merge::*;
+use crate::metrics::*;
+use crate::notifications::*;
+use crate::output_device::*;
 use crate::playback::*;
-use crate::player::initialize_player;
+use crate::player::{initialize_player, remember_plays, send_playback_state_snapshot};
 use crate::playlist::*;
+use crate::recent_contexts::*;
+use crate::remote::*;
 use crate::search::*;
+use crate::shutdown::shutdown;
+use crate::streaming::*;
+use crate::transcode::*;
+use crate::users::*;
 
 use messages::album::*;
 use messages::artist::*;
+use messages::audiobook::*;
+use messages::bulk::*;
+use messages::classical::*;
 use messages::cover_art::*;
+use messages::device_sync::*;
+use messages::diagnostics::*;
+use messages::journal::*;
 use messages::library_home::*;
 use messages::library_manage::*;
 use messages::media_file::*;
+use messages::merge::*;
+use messages::metrics::*;
+use messages::notifications::*;
 use messages::playback::*;
 use messages::playlist::*;
 use messages::recommend::*;
+use messages::remote::*;
 use messages::search::*;
+use messages::streaming::*;
+use messages::transcode::*;
+use messages::users::*;
 
 macro_rules! select_signal {
     ($cancel_token:expr, $( $type:ty => ($($arg:ident),*) ),* $(,)? ) => {
         paste::paste! {
+            // `tokio::select!` is limited to 64 branches, so every receiver
+            // forwards its handlers to one queue instead. Requests are still
+            // handled one at a time, in the order they arrived.
+            let (handler_sender, mut handler_receiver) = tokio::sync::mpsc::unbounded_channel::<(
+                &'static str,
+                futures::future::BoxFuture<'static, ()>,
+            )>();
+            let mut forwarders = Vec::new();
+
             $(
-                let mut [<receiver_ $type:snake>] = <$type>::get_dart_signal_receiver().unwrap();
+                {
+                    let mut receiver = <$type>::get_dart_signal_receiver().unwrap();
+                    let handler_sender = handler_sender.clone();
+                    $(let $arg = $arg.clone();)*
+
+                    forwarders.push(tokio::spawn(async move {
+                        while let Some(dart_signal) = receiver.recv().await {
+                            let handler_fn = [<$type:snake>];
+                            let handler = handler_fn($($arg.clone(),)* dart_signal);
+                            if handler_sender.send((stringify!($type), handler.map(|_| ()).boxed())).is_err() {
+                                break;
+                            }
+                        }
+                    }));
+                }
             )*
+            drop(handler_sender);
 
             loop {
                 if $cancel_token.is_cancelled() {
@@ -62,48 +149,169 @@ macro_rules! select_signal {
                 }
 
                 tokio::select! {
-                    $(
-                        dart_signal = [<receiver_ $type:snake>].recv() => {
-                            if let Some(dart_signal) = dart_signal {
-                                debug!("Processing signal: {}", stringify!($type));
-                                let handler_fn = [<$type:snake>];
-                                let _ = handler_fn($($arg.clone()),*, dart_signal).await;
-                            }
+                    _ = $cancel_token.cancelled() => {
+                        info!("Cancellation requested. Stop accepting new requests.");
+                        break;
+                    }
+                    handler = handler_receiver.recv() => {
+                        let Some((name, handler)) = handler else {
+                            break;
+                        };
+                        debug!("Processing signal: {}", name);
+                        // The panic hook reports the crash, keep serving other requests
+                        if AssertUnwindSafe(handler).catch_unwind().await.is_err() {
+                            error!("Handler panicked: {}", name);
                         }
-                    )*
-                    else => continue,
+                    }
                 }
             }
+
+            // Release the connections held by the forwarders with the library
+            for forwarder in forwarders {
+                forwarder.abort();
+            }
         }
     };
 }
 
 rinf::write_interface!();
 
-async fn player_loop(path: String) {
+fn player_loop(path: String, cancel_token: CancellationToken) -> JoinHandle<()> {
     // Ensure that the path is set before calling fetch_media_files
 
     info!("Media Library Received, initialize other receivers");
 
-    tokio::spawn(async {
+    tokio::spawn(async move {
         // Move the path into the async block
         info!("Initializing database");
-        let main_db = Arc::new(connect_main_db(&path).await.unwrap());
-        let recommend_db = Arc::new(connect_recommendation_db(&path).unwrap());
-        let search_db = Arc::new(Mutex::new(connect_search_db(&path).unwrap()));
-        let lib_path = Arc::new(path);
+        let lib_mode = if is_library_read_only(&path) {
+            LibraryMode::ReadOnly
+        } else {
+            LibraryMode::ReadWrite
+        };
+
+        let (main_db, reader_db, user_db, recommend_db, search_db) = match lib_mode {
+            LibraryMode::ReadWrite => {
+                let main_db = connect_main_db(&path).await.unwrap();
+                (
+                    main_db.clone(),
+                    connect_main_db_readers(&path, DEFAULT_MAIN_DB_READERS)
+                        .await
+                        .unwrap(),
+                    main_db,
+                    connect_recommendation_db(&path).unwrap(),
+                    connect_search_db(&path).unwrap(),
+                )
+            }
+            LibraryMode::ReadOnly => {
+                // Play state and settings go to a local overlay instead
+                let overlay = overlay_path(&path);
+                info!("Using local overlay: {:?}", overlay);
+                let main_db = connect_main_db_read_only(&path).await.unwrap();
+                (
+                    main_db.clone(),
+                    main_db,
+                    connect_overlay_db(overlay.to_str().unwrap()).await.unwrap(),
+                    connect_recommendation_db_read_only(&path).unwrap(),
+                    connect_search_db_read_only(&path).unwrap(),
+                )
+            }
+        };
 
-        // Create a cancellation token
-        let cancel_token = CancellationToken::new();
+        let main_db = Arc::new(main_db);
+        let reader_db = Arc::new(reader_db);
+        let user_db = Arc::new(user_db);
+        let recommend_db = Arc::new(recommend_db);
+        let search_db = Arc::new(Mutex::new(search_db));
+        let lib_path = Arc::new(path);
+        let lib_mode = Arc::new(lib_mode);
+        let query_cache = Arc::new(QueryCache::default());
+        let journal = Arc::new(OperationJournal::default());
+        let transcode_queue = Arc::new(TranscodeQueue::default());
+        let stream_server = Arc::new(Mutex::new(None));
 
         info!("Initializing player");
-        let player = Player::new(Some(cancel_token.clone()));
+        // The player owns its own token, it must keep its status until the
+        // shutdown sequence has flushed it.
+        let player = Player::new(None);
+        player.set_library_root(PathBuf::from(lib_path.as_str()));
         let player = Arc::new(Mutex::new(player));
 
         let cancel_token = Arc::new(cancel_token);
 
+        restore_playback_settings(&user_db, &player).await;
+        // The queue was flushed when the library was closed
+        let user_id = active_user_id(&user_db).await;
+        restore_queue(&main_db, &user_db, user_id, &lib_path, &player).await;
+        reload_search_synonyms(&user_db, &search_db).await;
+
+        tokio::spawn(check_library_health(
+            main_db.clone(),
+            recommend_db.clone(),
+            search_db.clone(),
+            lib_mode.clone(),
+        ));
+
+        if !lib_mode.is_read_only() {
+            let main_db = main_db.clone();
+            let lib_path = lib_path.clone();
+            tokio::spawn(async move { sync_playlist_mosaics(&main_db, &lib_path).await });
+        }
+
         info!("Initializing Player events");
-        tokio::spawn(initialize_player(main_db.clone(), player.clone()));
+        tokio::spawn(initialize_player(
+            main_db.clone(),
+            user_db.clone(),
+            player.clone(),
+        ));
+        tokio::spawn(remember_audiobook_positions(
+            main_db.clone(),
+            user_db.clone(),
+            player.clone(),
+        ));
+        tokio::spawn(remember_plays(
+            main_db.clone(),
+            user_db.clone(),
+            query_cache.clone(),
+            player.clone(),
+        ));
+        tokio::spawn(watch_output_devices(
+            user_db.clone(),
+            player.clone(),
+            (*cancel_token).clone(),
+        ));
+        if !lib_mode.is_read_only() {
+            tokio::spawn(watch_drop_folder(
+                main_db.clone(),
+                search_db.clone(),
+                lib_path.clone(),
+                query_cache.clone(),
+                (*cancel_token).clone(),
+            ));
+        }
+        #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+        tokio::spawn(hotkeys::listen_to_hotkeys(
+            player.clone(),
+            (*cancel_token).clone(),
+        ));
+        #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+        tokio::spawn(notify_track_changes(
+            main_db.clone(),
+            user_db.clone(),
+            lib_path.clone(),
+            query_cache.clone(),
+            player.clone(),
+        ));
+
+        // The UI may have been restarted while the hub kept running,
+        // push the full state so it doesn't need to replay the events.
+        send_playback_state_snapshot(&main_db, &user_db, &player).await;
+
+        LibraryReady {
+            path: lib_path.to_string(),
+            read_only: lib_mode.is_read_only(),
+        }
+        .send_signal_to_dart();
 
         info!("Initializing UI events");
 
@@ -111,69 +319,175 @@ async fn player_loop(path: String) {
             cancel_token,
 
             CloseLibraryRequest => (lib_path, cancel_token),
-            ScanAudioLibraryRequest => (main_db, search_db, cancel_token),
-            AnalyseAudioLibraryRequest => (main_db, recommend_db, cancel_token),
+            ScanAudioLibraryRequest => (main_db, search_db, lib_mode, query_cache, cancel_token),
+            SetDeletedFilesGracePeriodRequest => (main_db, lib_mode),
+            ExportAnalysisRequest => (main_db),
+            ExportAnalysisVectorsRequest => (main_db),
+            ExportDiagnosticBundleRequest => (main_db, lib_path),
+            ImportAnalysisRequest => (main_db, recommend_db, lib_mode, query_cache),
+            AnalyseAudioLibraryRequest => (main_db, user_db, recommend_db, lib_mode, query_cache, cancel_token),
+            ScanLoudnessRequest => (main_db, user_db, lib_mode, cancel_token),
+            SetAnalysisBackgroundPriorityRequest => (user_db),
+            SetIgnoreArticleRequest => (main_db),
+            SetCollationLocaleRequest => (main_db),
+            FetchDegradationReportRequest => (main_db),
+            SetDropFolderRequest => (main_db, lib_path, lib_mode),
+            FetchDropFolderRequest => (main_db),
+            FetchPlaybackExclusionsRequest => (main_db),
+            SetPlaybackExclusionRequest => (main_db),
+            FetchTagMappingsRequest => (main_db),
+            SetTagMappingRequest => (main_db),
+            RemoveTagMappingRequest => (main_db),
 
-            PlayFileRequest => (main_db, lib_path, player),
-            RecommendAndPlayRequest => (main_db, recommend_db, lib_path, player),
+            PlayFileRequest => (main_db, lib_path, player, journal),
+            RecommendAndPlayRequest => (main_db, user_db, recommend_db, lib_path, player),
+            StartAutoDjRequest => (main_db, user_db, lib_path, player, journal),
+            FetchRecommendationIndexParametersRequest => (recommend_db),
+            SetRecommendationIndexParametersRequest => (main_db, recommend_db, lib_mode),
+            BenchmarkRecommendationIndexRequest => (recommend_db),
+            FetchMixPolicyRequest => (user_db),
+            SetMixPolicyRequest => (user_db),
+            FetchSkipPenaltiesRequest => (user_db),
+            FetchMusicMapRequest => (main_db),
+            ResetSkipPenaltiesRequest => (user_db),
+            FetchSyncDevicesRequest => (user_db),
+            CreateSyncDeviceRequest => (user_db),
+            RemoveSyncDeviceRequest => (user_db),
+            SyncDeviceRequest => (main_db, user_db, lib_path),
+            TranscodeFilesRequest => (main_db, user_db, lib_path, lib_mode, query_cache, transcode_queue),
+            CancelTranscodeRequest => (transcode_queue),
+            StartStreamingServerRequest => (main_db, user_db, search_db, lib_path, query_cache, stream_server),
+            StopStreamingServerRequest => (user_db, stream_server),
+            FetchStreamingServerStatusRequest => (user_db, stream_server),
+            FetchRemoteServersRequest => (user_db),
+            AddRemoteServerRequest => (user_db),
+            RemoveRemoteServerRequest => (user_db),
+            SyncRemoteServerRequest => (user_db),
+            FetchRemoteFilesRequest => (user_db),
+            PlayRemoteFilesRequest => (user_db, player),
             PlayRequest => (player),
             PauseRequest => (player),
             NextRequest => (player),
             PreviousRequest => (player),
             SwitchRequest => (player),
             SeekRequest => (player),
-            RemoveRequest => (player),
+            SeekToMarkerRequest => (main_db, lib_path, player),
+            PreviewTransitionRequest => (main_db, lib_path, player),
+            RemoveRequest => (player, journal),
+            SetVolumeRequest => (user_db, player),
+            SetPlaybackModeRequest => (player),
+            SetCrossfadeRequest => (user_db, player),
+            SetPreampRequest => (user_db, player),
+            SetLimiterRequest => (user_db, player),
+            SetMonoRequest => (user_db, player),
+            SetNightModeRequest => (user_db, player),
+            SetCrossfeedRequest => (user_db, player),
+            FetchOutputDevicesRequest => (user_db),
+            SelectOutputDeviceRequest => (user_db, player),
+            SetProgressIntervalRequest => (user_db, player),
+            SetCoarseProgressRequest => (player),
+            SetTrackNotificationsRequest => (user_db),
+            SetTrackGainOffsetRequest => (user_db, player),
+            ShufflePlaylistRequest => (main_db, user_db, player),
+            GetPlaybackStateRequest => (main_db, user_db, player),
+            FetchRecentContextsRequest => (user_db),
+            ResumeContextRequest => (main_db, user_db, lib_path, player, journal),
 
-            FetchMediaFilesRequest => (main_db, lib_path),
+            FetchMediaFilesRequest => (reader_db, user_db, lib_path),
             FetchParsedMediaFileRequest => (main_db, lib_path),
-            CompoundQueryMediaFilesRequest => (main_db, lib_path),
+            CompoundQueryMediaFilesRequest => (reader_db, user_db, lib_path),
 
-            StartPlayingCollectionRequest => (main_db, lib_path, player),
+            StartPlayingCollectionRequest => (main_db, user_db, lib_path, player, journal),
             AddToQueueCollectionRequest => (main_db, lib_path, player),
             FetchMediaFileByIdsRequest => (main_db, lib_path),
-            StartRoamingCollectionRequest => (main_db, recommend_db, lib_path, player),
+            ValidateMediaFilesRequest => (main_db, lib_path),
+            FetchMediaFileChaptersRequest => (main_db, lib_path),
+            FetchMixMarkersRequest => (main_db, lib_path),
+            FetchTrackLinksRequest => (main_db),
+            FetchTrackDetailRequest => (main_db, user_db, lib_path, query_cache),
+            AnalyseTrackRequest => (main_db, recommend_db, lib_path, lib_mode, query_cache),
+            StartRoamingCollectionRequest => (main_db, user_db, recommend_db, lib_path, player),
 
-            GetCoverArtByFileIdRequest => (main_db, lib_path),
-            GetCoverArtByCoverArtIdRequest => (main_db),
+            GetCoverArtByFileIdRequest => (main_db, lib_path, query_cache),
+            GetCoverArtByCoverArtIdRequest => (main_db, query_cache),
             GetRandomCoverArtIdsRequest => (main_db),
+            CollectCoverArtGarbageRequest => (main_db, lib_mode, query_cache),
 
-            FetchArtistsGroupSummaryRequest => (main_db),
-            FetchArtistsGroupsRequest => (main_db),
-            FetchArtistsByIdsRequest => (main_db),
+            FetchArtistsGroupSummaryRequest => (reader_db),
+            FetchArtistsGroupsRequest => (reader_db),
+            FetchArtistsByIdsRequest => (reader_db),
 
-            FetchAlbumsGroupSummaryRequest => (main_db),
-            FetchAlbumsGroupsRequest => (main_db),
-            FetchAlbumsByIdsRequest => (main_db),
+            FetchAlbumsGroupSummaryRequest => (reader_db),
+            FetchAlbumsGroupsRequest => (reader_db),
+            FetchAlbumsByIdsRequest => (reader_db, query_cache),
+            FetchAlbumsEraSummaryRequest => (reader_db),
+            FetchAlbumsByEraRequest => (reader_db),
+            CheckGaplessAlbumsRequest => (reader_db),
 
-            FetchPlaylistsGroupSummaryRequest => (main_db),
-            FetchPlaylistsGroupsRequest => (main_db),
-            FetchPlaylistsByIdsRequest => (main_db),
-            FetchAllPlaylistsRequest => (main_db),
+            FetchPlaylistsGroupSummaryRequest => (reader_db),
+            FetchPlaylistsGroupsRequest => (reader_db),
+            FetchPlaylistsByIdsRequest => (reader_db),
+            FetchAllPlaylistsRequest => (reader_db),
             MovePlaylistItemRequest => (player),
-            CreatePlaylistRequest => (main_db, search_db),
-            UpdatePlaylistRequest => (main_db, search_db),
+            CreatePlaylistRequest => (main_db, search_db, lib_mode),
+            UpdatePlaylistRequest => (main_db, search_db, lib_mode, query_cache),
             CheckItemsInPlaylistRequest => (main_db),
-            AddItemToPlaylistRequest => (main_db),
-            AddMediaFileToPlaylistRequest => (main_db),
-            ReorderPlaylistItemPositionRequest => (main_db),
+            FetchPlaylistMembershipsRequest => (reader_db, player),
+            AddItemToPlaylistRequest => (main_db, lib_path, lib_mode, query_cache, journal),
+            AddMediaFileToPlaylistRequest => (main_db, lib_path, lib_mode, query_cache, journal),
+            ReorderPlaylistItemPositionRequest => (main_db, lib_path, lib_mode, query_cache, journal),
+            MergeCollectionsRequest => (main_db, search_db, lib_mode, query_cache),
+            FetchMergedNamesRequest => (main_db),
+            SplitCollectionRequest => (main_db, search_db, lib_mode, query_cache),
+            BulkAddToPlaylistRequest => (main_db, lib_path, lib_mode, query_cache, journal),
+            BulkSetRatingRequest => (user_db, query_cache, journal),
+            BulkDeleteRequest => (main_db, user_db, search_db, lib_path, lib_mode, query_cache),
+            SetPermanentDeleteRequest => (user_db),
+            BulkReanalyseRequest => (main_db, user_db, recommend_db, lib_path, lib_mode, query_cache, cancel_token),
+            PreviewPathTagsRequest => (main_db),
+            BulkApplyTagsRequest => (main_db, search_db, lib_path, lib_mode, query_cache),
+            UndoLastOperationRequest => (main_db, user_db, lib_path, player, query_cache, journal),
+            RedoLastOperationRequest => (main_db, user_db, lib_path, player, query_cache, journal),
             GetUniquePlaylistGroupsRequest => (main_db),
             GetPlaylistByIdRequest => (main_db),
 
-            FetchLibrarySummaryRequest => (main_db),
-            SearchForRequest => (search_db),
+            SetDirectoryContentTypeRequest => (main_db),
+            FetchAudiobooksRequest => (reader_db),
+
+            FetchComposersRequest => (reader_db),
+            FetchComposerWorksRequest => (reader_db),
+            FetchAlbumWorksRequest => (reader_db),
+
+            FetchLibrarySummaryRequest => (reader_db),
+            FetchHomePayloadRequest => (reader_db, user_db, recommend_db),
+            FetchListeningStatsRequest => (reader_db, user_db),
+            SetDailyGoalRequest => (reader_db, user_db),
+            FetchUsersRequest => (user_db),
+            CreateUserRequest => (user_db),
+            RemoveUserRequest => (user_db),
+            SwitchUserRequest => (main_db, user_db, lib_path, player),
+            SetCleanModeRequest => (user_db),
+            PinCollectionRequest => (user_db),
+            UnpinCollectionRequest => (user_db),
+            ReorderPinnedCollectionsRequest => (user_db),
+            SearchForRequest => (reader_db, user_db, search_db),
+            SearchLyricsRequest => (reader_db, user_db, search_db),
+            FetchSearchAliasesRequest => (user_db),
+            AddSearchAliasRequest => (user_db, search_db),
+            RemoveSearchAliasRequest => (user_db, search_db),
+
+            FetchMetricsRequest => (),
         );
-    });
+
+        shutdown(user_db, search_db, player).await;
+    })
 }
 
 async fn main() {
-    let filter = EnvFilter::new(
-        "symphonia_format_ogg=off,symphonia_core=off,sea_orm_migration::migrator=off,info",
-    );
-
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_test_writer()
-        .init();
+    initialize_logging();
+    install_panic_hook();
+
+    tokio::spawn(receive_logging_requests());
 
     // Start receiving the media library path
     let _ = receive_media_library_path(player_loop).await;
@@ -1,22 +1,37 @@
+use chrono::{Local, Utc};
+use database::actions::home::get_home_payload;
 use database::actions::library::get_latest_albums_and_artists;
-use log::{info, error};
+use database::actions::listening_stats::{get_listening_stats, set_daily_goal};
+use database::actions::pinned::{
+    get_pinned_collections, pin_collection, reorder_pinned_collections, unpin_collection,
+};
+use database::actions::search::CollectionType;
 use rinf::DartSignal;
 use std::sync::Arc;
+use tracing::{error, info};
 
-use database::connection::MainDbConnection;
+use database::connection::{MainDbConnection, RecommendationDbConnection};
 
+use crate::cover_art::attach_cover_blurhashes;
 use crate::messages::album::Album;
 use crate::messages::artist::Artist;
 use crate::messages::library_home::FetchLibrarySummaryRequest;
 use crate::messages::library_home::LibrarySummaryResponse;
+use crate::messages::library_home::{
+    CollectionKey, DailyMix, FetchHomePayloadRequest, FetchListeningStatsRequest,
+    HomePayloadResponse, ListeningStatsResponse, PinCollectionRequest, PinnedCollection,
+    PinnedCollectionsResponse, ReorderPinnedCollectionsRequest, SetDailyGoalRequest,
+    UnpinCollectionRequest,
+};
+use crate::users::active_user_id;
 
 pub async fn fetch_library_summary_request(
-    main_db: Arc<MainDbConnection>,
+    reader_db: Arc<MainDbConnection>,
     _dart_signal: DartSignal<FetchLibrarySummaryRequest>,
 ) {
     info!("Requesting library summary");
 
-    match get_latest_albums_and_artists(&main_db).await {
+    match get_latest_albums_and_artists(&reader_db).await {
         Ok(library) => {
             let albums = library
                 .0
@@ -25,6 +40,8 @@ pub async fn fetch_library_summary_request(
                     id: x.0.id,
                     name: x.0.name,
                     cover_ids: x.1.into_iter().collect(),
+                    cover_blurhashes: Vec::new(),
+                    year: x.0.year.unwrap_or_default(),
                 })
                 .collect();
 
@@ -35,10 +52,14 @@ pub async fn fetch_library_summary_request(
                     id: x.0.id,
                     name: x.0.name,
                     cover_ids: x.1.into_iter().collect(),
+                    cover_blurhashes: Vec::new(),
                 })
                 .collect();
 
-            LibrarySummaryResponse { albums, artists }.send_signal_to_dart();
+            let mut response = LibrarySummaryResponse { albums, artists };
+            attach_cover_blurhashes(&reader_db, response.albums.iter_mut()).await;
+            attach_cover_blurhashes(&reader_db, response.artists.iter_mut()).await;
+            response.send_signal_to_dart();
             // GENERATED
         }
         Err(e) => {
@@ -46,3 +67,208 @@ pub async fn fetch_library_summary_request(
         }
     };
 }
+
+fn parse_collection_type(collection_type: &str) -> Option<CollectionType> {
+    match collection_type {
+        "artist" => Some(CollectionType::Artist),
+        "album" => Some(CollectionType::Album),
+        "playlist" => Some(CollectionType::Playlist),
+        "track" => Some(CollectionType::Track),
+        _ => None,
+    }
+}
+
+fn collection_type_name(collection_type: &CollectionType) -> &'static str {
+    match collection_type {
+        CollectionType::Artist => "artist",
+        CollectionType::Album => "album",
+        CollectionType::Playlist => "playlist",
+        CollectionType::Track => "track",
+        CollectionType::Directory => "directory",
+    }
+}
+
+fn parse_collection_key(key: Option<CollectionKey>) -> Result<(CollectionType, i32), String> {
+    let key = key.ok_or("Missing collection")?;
+    let collection_type = parse_collection_type(&key.r#type)
+        .ok_or_else(|| format!("Invalid collection type: {}", key.r#type))?;
+
+    Ok((collection_type, key.id))
+}
+
+// Answer a pin change with the pins as they are now
+async fn send_pinned_collections(user_db: &MainDbConnection, result: Result<(), String>) {
+    let user_id = active_user_id(user_db).await;
+    let pinned = match get_pinned_collections(user_db, user_id).await {
+        Ok(pinned) => pinned,
+        Err(e) => {
+            error!("Failed to get pinned collections: {}", e);
+            Vec::new()
+        }
+    };
+
+    PinnedCollectionsResponse {
+        success: result.is_ok(),
+        error: result.err().unwrap_or_default(),
+        pinned: pinned
+            .into_iter()
+            .map(|(collection_type, id)| CollectionKey {
+                r#type: collection_type_name(&collection_type).to_string(),
+                id,
+            })
+            .collect(),
+    }
+    .send_signal_to_dart();
+}
+
+pub async fn pin_collection_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<PinCollectionRequest>,
+) {
+    let result = match parse_collection_key(dart_signal.message.collection) {
+        Ok((collection_type, id)) => {
+            info!("Pinning {:?} {}", collection_type, id);
+            let user_id = active_user_id(&user_db).await;
+            pin_collection(&user_db, user_id, collection_type, id)
+                .await
+                .map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = &result {
+        error!("Failed to pin collection: {}", e);
+    }
+    send_pinned_collections(&user_db, result).await;
+}
+
+pub async fn unpin_collection_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<UnpinCollectionRequest>,
+) {
+    let result = match parse_collection_key(dart_signal.message.collection) {
+        Ok((collection_type, id)) => {
+            info!("Unpinning {:?} {}", collection_type, id);
+            let user_id = active_user_id(&user_db).await;
+            unpin_collection(&user_db, user_id, collection_type, id)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = &result {
+        error!("Failed to unpin collection: {}", e);
+    }
+    send_pinned_collections(&user_db, result).await;
+}
+
+pub async fn reorder_pinned_collections_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<ReorderPinnedCollectionsRequest>,
+) {
+    let order: Result<Vec<_>, String> = dart_signal
+        .message
+        .order
+        .into_iter()
+        .map(|x| parse_collection_key(Some(x)))
+        .collect();
+
+    let user_id = active_user_id(&user_db).await;
+    let result = match order {
+        Ok(order) => reorder_pinned_collections(&user_db, user_id, &order)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = &result {
+        error!("Failed to reorder pinned collections: {}", e);
+    }
+    send_pinned_collections(&user_db, result).await;
+}
+
+pub async fn fetch_home_payload_request(
+    reader_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    recommend_db: Arc<RecommendationDbConnection>,
+    _dart_signal: DartSignal<FetchHomePayloadRequest>,
+) {
+    info!("Requesting home payload");
+
+    let today = Local::now().date_naive();
+    let user_id = active_user_id(&user_db).await;
+    match get_home_payload(&reader_db, &user_db, user_id, &recommend_db, today).await {
+        Ok(payload) => {
+            let mut response = HomePayloadResponse {
+                pinned: payload
+                    .pinned
+                    .into_iter()
+                    .map(|x| PinnedCollection {
+                        r#type: collection_type_name(&x.collection_type).to_string(),
+                        id: x.id,
+                        name: x.name,
+                        cover_ids: x.cover_ids,
+                        cover_blurhashes: Vec::new(),
+                    })
+                    .collect(),
+                recently_played: payload.recently_played,
+                daily_mixes: payload
+                    .daily_mixes
+                    .into_iter()
+                    .map(|x| DailyMix {
+                        seed_id: x.seed_id,
+                        file_ids: x.file_ids,
+                    })
+                    .collect(),
+                for_you: payload.for_you,
+            };
+            attach_cover_blurhashes(&reader_db, response.pinned.iter_mut()).await;
+            response.send_signal_to_dart();
+        }
+        Err(e) => {
+            error!("Failed to fetch home payload: {}", e);
+        }
+    };
+}
+
+async fn send_listening_stats(reader_db: &MainDbConnection, user_db: &MainDbConnection) {
+    // Plays are stored in UTC
+    let today = Utc::now().date_naive();
+    let user_id = active_user_id(user_db).await;
+    match get_listening_stats(reader_db, user_db, user_id, today).await {
+        Ok(stats) => ListeningStatsResponse {
+            current_streak: stats.current_streak as i32,
+            longest_streak: stats.longest_streak as i32,
+            minutes_today: stats.minutes_today,
+            daily_goal_minutes: stats.daily_goal_minutes as i32,
+            goal_progress: stats.goal_progress,
+            new_artist_ids: stats.new_artist_ids,
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch listening stats: {}", e),
+    }
+}
+
+pub async fn fetch_listening_stats_request(
+    reader_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchListeningStatsRequest>,
+) {
+    info!("Requesting listening stats");
+    send_listening_stats(&reader_db, &user_db).await;
+}
+
+pub async fn set_daily_goal_request(
+    reader_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SetDailyGoalRequest>,
+) {
+    let minutes = dart_signal.message.minutes.max(1) as u32;
+    let user_id = active_user_id(&user_db).await;
+    if let Err(e) = set_daily_goal(&user_db, user_id, minutes).await {
+        error!("Failed to set the daily goal: {}", e);
+    }
+    send_listening_stats(&reader_db, &user_db).await;
+}
@@ -1,24 +1,103 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use log::{debug, info};
 use rinf::DartSignal;
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
 use database::actions::analysis::analysis_audio_library;
-use database::actions::metadata::scan_audio_library;
-use database::actions::recommendation::sync_recommendation;
-use database::connection::{MainDbConnection, RecommendationDbConnection, SearchDbConnection};
+use database::actions::analysis_exchange::{
+    export_analysis, export_analysis_vectors, import_analysis,
+};
+use database::actions::checksums::{get_degradation_report, Degradation};
+use database::actions::collation::COLLATION_LOCALE_KEY;
+use database::actions::diversity::{get_mix_policy, set_mix_policy, MixPolicy};
+use database::actions::exclusions::{
+    get_exclusion_flags, set_exclusion_flags, ExclusionFlags, ExclusionTarget,
+};
+use database::actions::index::IGNORE_ARTICLE_KEY;
+use database::actions::library::create_library;
+use database::actions::loudness::scan_loudness;
+use database::actions::metadata::{scan_audio_library, DELETED_GRACE_DAYS_KEY};
+use database::actions::music_map::{get_music_map, DEFAULT_MAP_CLUSTERS};
+use database::actions::query_cache::QueryCache;
+use database::actions::recommendation::{
+    benchmark_index, get_index_parameters, set_index_parameters, sync_recommendation,
+    IndexParameters,
+};
+use database::actions::settings::{get_setting, set_setting};
+use database::actions::skips::{get_skip_stats, reset_skips};
+use database::actions::tag_mappings::{get_tag_mappings, remove_tag_mapping, set_tag_mapping};
+use database::actions::throttle::{analysis_pace, read_power_status, BACKGROUND_PRIORITY_KEY};
+use database::actions::verification::verify_library;
+use database::connection::{
+    connect_analysis_cache_db, MainDbConnection, RecommendationDbConnection, SearchDbConnection,
+};
+use database::entities::tag_mappings;
 
 use crate::messages::library_manage::{
+    DegradationProblem, DegradationReportResponse, DegradedFile, ExportAnalysisRequest,
+    ExportAnalysisResponse, ExportAnalysisVectorsRequest, ExportAnalysisVectorsResponse,
+    FetchDegradationReportRequest, FetchPlaybackExclusionsRequest, FetchTagMappingsRequest,
+    FetchTagMappingsResponse, ImportAnalysisRequest, ImportAnalysisResponse, PlaybackExclusion,
+    PlaybackExclusionsResponse, RemoveTagMappingRequest, RemoveTagMappingResponse,
     ScanAudioLibraryProgress, ScanAudioLibraryRequest, ScanAudioLibraryResponse,
+    ScanLoudnessProgress, ScanLoudnessRequest, ScanLoudnessResponse,
+    SetAnalysisBackgroundPriorityRequest, SetCollationLocaleRequest,
+    SetDeletedFilesGracePeriodRequest, SetIgnoreArticleRequest, SetPlaybackExclusionRequest,
+    SetTagMappingRequest, SetTagMappingResponse, TagMapping, VerificationFailure,
+    VerifyAudioLibraryProgress,
+};
+use crate::messages::recommend::{
+    BenchmarkRecommendationIndexRequest, BenchmarkRecommendationIndexResponse,
+    FetchMixPolicyRequest, FetchMusicMapRequest, FetchMusicMapResponse,
+    FetchRecommendationIndexParametersRequest, FetchSkipPenaltiesRequest, MixPolicyResponse,
+    MusicMapPoint, RecommendationIndexBenchmark, RecommendationIndexParameters,
+    RecommendationIndexParametersResponse, ResetSkipPenaltiesRequest, SetMixPolicyRequest,
+    SetRecommendationIndexParametersRequest, SkipPenaltiesResponse, SkipPenalty,
 };
+use crate::playlist::sync_playlist_mosaics;
 use crate::{
     AnalyseAudioLibraryProgress, AnalyseAudioLibraryRequest, AnalyseAudioLibraryResponse,
-    CloseLibraryRequest, CloseLibraryResponse,
+    CloseLibraryRequest, CloseLibraryResponse, CreateLibraryRequest, CreateLibraryResponse,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LibraryMode {
+    ReadWrite,
+    // The library is on read-only storage, user data is kept in a local overlay
+    ReadOnly,
+}
+
+impl LibraryMode {
+    pub fn is_read_only(&self) -> bool {
+        *self == LibraryMode::ReadOnly
+    }
+}
+
+/// The local directory holding the user data of a read-only library.
+pub fn overlay_path(lib_path: &str) -> PathBuf {
+    let name: String = lib_path
+        .chars()
+        .map(|x| if x.is_alphanumeric() { x } else { '_' })
+        .collect();
+
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rune")
+        .join("overlays")
+        .join(name)
+}
+
+/// The analysis cache shared by all libraries.
+pub fn analysis_cache_path() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rune")
+        .join("analysis_cache.db")
+}
+
 pub async fn close_library_request(
     lib_path: Arc<String>,
     cancel_token: Arc<CancellationToken>,
@@ -40,14 +119,48 @@ pub async fn close_library_request(
     .send_signal_to_dart()
 }
 
+pub async fn create_library_request(dart_signal: DartSignal<CreateLibraryRequest>) {
+    let request = dart_signal.message;
+
+    info!("Creating library: {}", request.path);
+
+    let (success, error) = match create_library(Path::new(&request.path)).await {
+        Ok(_) => (true, String::new()),
+        Err(e) => {
+            error!("Failed to create library: {:#}", e);
+            (false, format!("{:#}", e))
+        }
+    };
+
+    CreateLibraryResponse {
+        path: request.path,
+        success,
+        error,
+    }
+    .send_signal_to_dart()
+}
+
 pub async fn scan_audio_library_request(
     main_db: Arc<MainDbConnection>,
     search_db: Arc<Mutex<SearchDbConnection>>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
     cancel_token: Arc<CancellationToken>,
     dart_signal: DartSignal<ScanAudioLibraryRequest>,
 ) {
     let request = dart_signal.message;
 
+    if lib_mode.is_read_only() {
+        warn!("Library is read-only, skipping scan");
+        ScanAudioLibraryResponse {
+            path: request.path,
+            progress: 0,
+            verification_failures: Vec::new(),
+        }
+        .send_signal_to_dart();
+        return;
+    }
+
     debug!("Scanning library summary: {:#?}", request);
 
     let mut search_db = search_db.lock().await;
@@ -68,14 +181,279 @@ pub async fn scan_audio_library_request(
     )
     .await
     .unwrap();
+    query_cache.clear();
+
+    drop(search_db);
+    sync_playlist_mosaics(&main_db, &request.path).await;
+
+    let verification_failures = if request.verify {
+        verify_audio_library(&main_db, &request.path, &cancel_token).await
+    } else {
+        Vec::new()
+    };
 
     ScanAudioLibraryResponse {
         path: request.path.clone(),
         progress: file_processed as i32,
+        verification_failures,
+    }
+    .send_signal_to_dart()
+}
+
+async fn verify_audio_library(
+    main_db: &MainDbConnection,
+    path: &str,
+    cancel_token: &CancellationToken,
+) -> Vec<VerificationFailure> {
+    let closure_request_path = path.to_string();
+
+    match verify_library(
+        main_db,
+        Path::new(path),
+        move |progress, total| {
+            VerifyAudioLibraryProgress {
+                path: closure_request_path.clone(),
+                progress: progress.try_into().unwrap(),
+                total: total.try_into().unwrap(),
+            }
+            .send_signal_to_dart()
+        },
+        Some(cancel_token.clone()),
+    )
+    .await
+    {
+        Ok(failures) => failures
+            .into_iter()
+            .map(|x| VerificationFailure {
+                file_id: x.file_id,
+                directory: x.directory,
+                file_name: x.file_name,
+                status: x.status,
+                detail: x.detail.unwrap_or_default(),
+            })
+            .collect(),
+        Err(e) => {
+            error!("Unable to verify the library: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+pub async fn fetch_degradation_report_request(
+    main_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchDegradationReportRequest>,
+) {
+    let response = match get_degradation_report(&main_db).await {
+        Ok(files) => DegradationReportResponse {
+            files: files
+                .into_iter()
+                .map(|x| DegradedFile {
+                    file_id: x.file_id,
+                    directory: x.directory,
+                    file_name: x.file_name,
+                    problems: x
+                        .problems
+                        .into_iter()
+                        .map(|problem| match problem {
+                            Degradation::ChecksumChanged {
+                                kind,
+                                previous,
+                                current,
+                                changed_at,
+                            } => DegradationProblem {
+                                kind: "checksum_changed".to_string(),
+                                checksum_kind: kind,
+                                previous_value: previous,
+                                current_value: current,
+                                changed_at,
+                            },
+                            Degradation::ChecksumMismatch => DegradationProblem {
+                                kind: "checksum_mismatch".to_string(),
+                                ..Default::default()
+                            },
+                        })
+                        .collect(),
+                })
+                .collect(),
+            success: true,
+            error: String::new(),
+        },
+        Err(e) => {
+            error!("Failed to build the degradation report: {}", e);
+            DegradationReportResponse {
+                files: Vec::new(),
+                success: false,
+                error: e.to_string(),
+            }
+        }
+    };
+
+    response.send_signal_to_dart();
+}
+
+pub async fn set_deleted_files_grace_period_request(
+    main_db: Arc<MainDbConnection>,
+    lib_mode: Arc<LibraryMode>,
+    dart_signal: DartSignal<SetDeletedFilesGracePeriodRequest>,
+) {
+    // Cleanup only runs while scanning, which read-only libraries never do
+    if lib_mode.is_read_only() {
+        warn!("Library is read-only, ignoring the grace period");
+        return;
+    }
+
+    let days = dart_signal.message.days;
+    if let Err(e) = set_setting(main_db.as_ref(), DELETED_GRACE_DAYS_KEY, days.to_string()).await {
+        error!("Unable to save the grace period of deleted files: {}", e);
+    }
+}
+
+pub async fn export_analysis_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<ExportAnalysisRequest>,
+) {
+    let request = dart_signal.message;
+
+    info!("Exporting analysis results to: {}", request.path);
+
+    let (success, exported, error) =
+        match export_analysis(main_db.as_ref(), Path::new(&request.path)).await {
+            Ok(count) => (true, count as i32, String::new()),
+            Err(e) => {
+                error!("Failed to export analysis results: {:#}", e);
+                (false, 0, format!("{:#}", e))
+            }
+        };
+
+    ExportAnalysisResponse {
+        path: request.path,
+        success,
+        exported,
+        error,
+    }
+    .send_signal_to_dart()
+}
+
+pub async fn export_analysis_vectors_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<ExportAnalysisVectorsRequest>,
+) {
+    let request = dart_signal.message;
+    let file_ids = (!request.file_ids.is_empty()).then_some(request.file_ids.as_slice());
+
+    info!("Exporting analysis vectors to: {}", request.path);
+
+    let (success, exported, error) =
+        match export_analysis_vectors(main_db.as_ref(), Path::new(&request.path), file_ids).await {
+            Ok(count) => (true, count as i32, String::new()),
+            Err(e) => {
+                error!("Failed to export analysis vectors: {:#}", e);
+                (false, 0, format!("{:#}", e))
+            }
+        };
+
+    ExportAnalysisVectorsResponse {
+        path: request.path,
+        success,
+        exported,
+        error,
     }
     .send_signal_to_dart()
 }
 
+pub async fn import_analysis_request(
+    main_db: Arc<MainDbConnection>,
+    recommend_db: Arc<RecommendationDbConnection>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    dart_signal: DartSignal<ImportAnalysisRequest>,
+) {
+    let request = dart_signal.message;
+
+    if lib_mode.is_read_only() {
+        warn!("Library is read-only, skipping analysis import");
+        ImportAnalysisResponse {
+            path: request.path,
+            success: false,
+            imported: 0,
+            skipped: 0,
+            error: "The library is read-only".to_string(),
+        }
+        .send_signal_to_dart();
+        return;
+    }
+
+    info!("Importing analysis results from: {}", request.path);
+
+    let report = match import_analysis(main_db.as_ref(), Path::new(&request.path)).await {
+        Ok(report) => report,
+        Err(e) => {
+            error!("Failed to import analysis results: {:#}", e);
+            ImportAnalysisResponse {
+                path: request.path,
+                success: false,
+                imported: 0,
+                skipped: 0,
+                error: format!("{:#}", e),
+            }
+            .send_signal_to_dart();
+            return;
+        }
+    };
+
+    if report.imported > 0 {
+        query_cache.invalidate_analysis();
+        if let Err(e) = sync_recommendation(&main_db, &recommend_db).await {
+            error!("Recommendation synchronization failed: {:#}", e);
+        }
+    }
+
+    ImportAnalysisResponse {
+        path: request.path,
+        success: true,
+        imported: report.imported as i32,
+        skipped: report.skipped as i32,
+        error: String::new(),
+    }
+    .send_signal_to_dart()
+}
+
+pub async fn set_analysis_background_priority_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SetAnalysisBackgroundPriorityRequest>,
+) {
+    let enabled = dart_signal.message.enabled;
+    if let Err(e) = set_setting(
+        user_db.as_ref(),
+        BACKGROUND_PRIORITY_KEY,
+        enabled.to_string(),
+    )
+    .await
+    {
+        error!("Unable to save the analysis priority: {}", e);
+    }
+}
+
+pub async fn set_ignore_article_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SetIgnoreArticleRequest>,
+) {
+    let enabled = dart_signal.message.enabled;
+    if let Err(e) = set_setting(main_db.as_ref(), IGNORE_ARTICLE_KEY, enabled.to_string()).await {
+        error!("Unable to save the article setting: {}", e);
+    }
+}
+
+pub async fn set_collation_locale_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SetCollationLocaleRequest>,
+) {
+    let locale = dart_signal.message.locale;
+    if let Err(e) = set_setting(main_db.as_ref(), COLLATION_LOCALE_KEY, locale).await {
+        error!("Unable to save the collation locale: {}", e);
+    }
+}
+
 pub fn determine_batch_size() -> usize {
     let num_cores = num_cpus::get();
     let batch_size = num_cores / 3 * 2;
@@ -85,26 +463,86 @@ pub fn determine_batch_size() -> usize {
     std::cmp::min(std::cmp::max(batch_size, min_batch_size), max_batch_size)
 }
 
-pub async fn analyse_audio_library_request(
-    main_db: Arc<MainDbConnection>,
-    recommend_db: Arc<RecommendationDbConnection>,
-    cancel_token: Arc<CancellationToken>,
-    dart_signal: DartSignal<AnalyseAudioLibraryRequest>,
-) {
-    let request = dart_signal.message;
+async fn read_background_priority(user_db: &MainDbConnection) -> bool {
+    match get_setting(user_db, BACKGROUND_PRIORITY_KEY).await {
+        Ok(value) => value.is_some_and(|x| x == "true"),
+        Err(e) => {
+            error!("Unable to read the analysis priority: {}", e);
+            false
+        }
+    }
+}
 
-    debug!("Analysing media files: {:#?}", request);
+/// Measure the loudness of the files not measured yet, paced like the
+/// analysis and reporting its own progress.
+pub async fn scan_library_loudness(
+    main_db: &MainDbConnection,
+    user_db: &MainDbConnection,
+    path: &str,
+    cancel_token: &CancellationToken,
+) -> usize {
+    let background = read_background_priority(user_db).await;
+    let batch_size = determine_batch_size();
+
+    let closure_request_path = path.to_string();
 
-    // Clone the path outside the closure
-    let request_path = request.path.clone();
+    match scan_loudness(
+        main_db,
+        Path::new(path),
+        move || analysis_pace(batch_size, background, read_power_status()),
+        move |progress, total| {
+            ScanLoudnessProgress {
+                path: closure_request_path.clone(),
+                progress: progress.try_into().unwrap(),
+                total: total.try_into().unwrap(),
+            }
+            .send_signal_to_dart()
+        },
+        Some(cancel_token.clone()),
+    )
+    .await
+    {
+        Ok(total) => total,
+        Err(e) => {
+            error!("Unable to measure the loudness of the library: {}", e);
+            0
+        }
+    }
+}
+
+/// Analyse the files of a library without results, reporting the progress
+/// like a requested analysis does.
+///
+/// Loudness is measured first so ReplayGain doesn't wait for the features.
+pub async fn analyse_library(
+    main_db: &MainDbConnection,
+    user_db: &MainDbConnection,
+    recommend_db: &RecommendationDbConnection,
+    path: &str,
+    cancel_token: &CancellationToken,
+) -> usize {
+    scan_library_loudness(main_db, user_db, path, cancel_token).await;
+
+    // Analysis still works without the cache, it just can't be shared
+    let analysis_cache = match connect_analysis_cache_db(&analysis_cache_path()).await {
+        Ok(cache) => Some(cache),
+        Err(e) => {
+            warn!("Unable to open the analysis cache: {}", e);
+            None
+        }
+    };
+
+    let background = read_background_priority(user_db).await;
+    let batch_size = determine_batch_size();
 
-    // Clone the path again for use inside the closure
-    let closure_request_path = request_path.clone();
+    // Clone the path for use inside the closure
+    let closure_request_path = path.to_string();
 
     let total_files = analysis_audio_library(
-        &main_db,
-        Path::new(&request_path),
-        determine_batch_size(),
+        main_db,
+        Path::new(path),
+        analysis_cache.as_ref(),
+        move || analysis_pace(batch_size, background, read_power_status()),
         move |progress, total| {
             AnalyseAudioLibraryProgress {
                 path: closure_request_path.clone(), // Use the cloned path here
@@ -113,18 +551,435 @@ pub async fn analyse_audio_library_request(
             }
             .send_signal_to_dart()
         },
-        Some((*cancel_token).clone()),
+        Some(cancel_token.clone()),
     )
     .await
     .expect("Audio analysis failed");
 
-    sync_recommendation(&main_db, &recommend_db)
+    sync_recommendation(main_db, recommend_db)
         .await
         .expect("Recommendation synchronization failed");
 
+    total_files
+}
+
+pub async fn analyse_audio_library_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    recommend_db: Arc<RecommendationDbConnection>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    cancel_token: Arc<CancellationToken>,
+    dart_signal: DartSignal<AnalyseAudioLibraryRequest>,
+) {
+    let request = dart_signal.message;
+
+    if lib_mode.is_read_only() {
+        warn!("Library is read-only, skipping analysis");
+        AnalyseAudioLibraryResponse {
+            path: request.path,
+            total: 0,
+        }
+        .send_signal_to_dart();
+        return;
+    }
+
+    debug!("Analysing media files: {:#?}", request);
+
+    let total_files = analyse_library(
+        &main_db,
+        &user_db,
+        &recommend_db,
+        &request.path,
+        &cancel_token,
+    )
+    .await;
+    query_cache.invalidate_analysis();
+
     AnalyseAudioLibraryResponse {
-        path: request_path.clone(), // Use the original cloned path here
+        path: request.path,
         total: total_files as i32,
     }
     .send_signal_to_dart();
 }
+
+pub async fn scan_loudness_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    lib_mode: Arc<LibraryMode>,
+    cancel_token: Arc<CancellationToken>,
+    dart_signal: DartSignal<ScanLoudnessRequest>,
+) {
+    let request = dart_signal.message;
+
+    if lib_mode.is_read_only() {
+        warn!("Library is read-only, skipping loudness scan");
+        ScanLoudnessResponse {
+            path: request.path,
+            total: 0,
+        }
+        .send_signal_to_dart();
+        return;
+    }
+
+    debug!("Measuring loudness: {:#?}", request);
+
+    let total_files = scan_library_loudness(&main_db, &user_db, &request.path, &cancel_token).await;
+
+    ScanLoudnessResponse {
+        path: request.path,
+        total: total_files as i32,
+    }
+    .send_signal_to_dart();
+}
+
+fn to_tag_mapping(item: tag_mappings::Model) -> TagMapping {
+    TagMapping {
+        id: item.id,
+        tag_key: item.tag_key,
+        field: item.field,
+        searchable: item.searchable,
+    }
+}
+
+pub async fn fetch_tag_mappings_request(
+    main_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchTagMappingsRequest>,
+) {
+    match get_tag_mappings(&main_db).await {
+        Ok(mappings) => FetchTagMappingsResponse {
+            mappings: mappings.into_iter().map(to_tag_mapping).collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch tag mappings: {}", e),
+    }
+}
+
+pub async fn set_tag_mapping_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SetTagMappingRequest>,
+) {
+    let request = dart_signal.message;
+
+    let mapping = match set_tag_mapping(
+        &main_db,
+        &request.tag_key,
+        &request.field,
+        request.searchable,
+    )
+    .await
+    {
+        Ok(mapping) => mapping,
+        Err(e) => {
+            error!("Failed to set tag mapping: {}", e);
+            None
+        }
+    };
+
+    SetTagMappingResponse {
+        success: mapping.is_some(),
+        mapping: mapping.map(to_tag_mapping),
+    }
+    .send_signal_to_dart();
+}
+
+pub async fn remove_tag_mapping_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<RemoveTagMappingRequest>,
+) {
+    let id = dart_signal.message.id;
+
+    let success = match remove_tag_mapping(&main_db, id).await {
+        Ok(removed) => removed,
+        Err(e) => {
+            error!("Failed to remove tag mapping: {}", e);
+            false
+        }
+    };
+
+    RemoveTagMappingResponse { id, success }.send_signal_to_dart();
+}
+
+fn to_playback_exclusion((target, flags): (ExclusionTarget, ExclusionFlags)) -> PlaybackExclusion {
+    let (r#type, directory, album_id) = match target {
+        ExclusionTarget::Directory(directory) => ("directory", directory, 0),
+        ExclusionTarget::Album(id) => ("album", String::new(), id),
+    };
+
+    PlaybackExclusion {
+        r#type: r#type.to_string(),
+        directory,
+        album_id,
+        never_shuffle: flags.never_shuffle,
+        exclude_from_recommendations: flags.exclude_from_recommendations,
+    }
+}
+
+async fn send_playback_exclusions(main_db: &MainDbConnection) {
+    match get_exclusion_flags(main_db).await {
+        Ok(exclusions) => PlaybackExclusionsResponse {
+            exclusions: exclusions.into_iter().map(to_playback_exclusion).collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch playback exclusions: {}", e),
+    }
+}
+
+pub async fn fetch_playback_exclusions_request(
+    main_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchPlaybackExclusionsRequest>,
+) {
+    send_playback_exclusions(&main_db).await;
+}
+
+pub async fn set_playback_exclusion_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SetPlaybackExclusionRequest>,
+) {
+    let Some(exclusion) = dart_signal.message.exclusion else {
+        return;
+    };
+
+    let target = match exclusion.r#type.as_str() {
+        "directory" => ExclusionTarget::Directory(exclusion.directory),
+        "album" => ExclusionTarget::Album(exclusion.album_id),
+        x => {
+            error!("Can't exclude collections of type {}", x);
+            return;
+        }
+    };
+    let flags = ExclusionFlags {
+        never_shuffle: exclusion.never_shuffle,
+        exclude_from_recommendations: exclusion.exclude_from_recommendations,
+    };
+
+    if let Err(e) = set_exclusion_flags(&main_db, &target, flags).await {
+        error!("Failed to set playback exclusion: {}", e);
+    }
+
+    send_playback_exclusions(&main_db).await;
+}
+
+fn send_index_parameters(recommend_db: &RecommendationDbConnection, error: Option<String>) {
+    let parameters = match get_index_parameters(recommend_db) {
+        Ok(parameters) => parameters,
+        Err(e) => {
+            error!("Failed to fetch recommendation index parameters: {}", e);
+            IndexParameters::default()
+        }
+    };
+
+    RecommendationIndexParametersResponse {
+        parameters: Some(RecommendationIndexParameters {
+            n_trees: parameters.n_trees.unwrap_or(0) as u32,
+            search_k_factor: parameters.search_k_factor as u32,
+        }),
+        success: error.is_none(),
+        error: error.unwrap_or_default(),
+    }
+    .send_signal_to_dart()
+}
+
+pub async fn fetch_recommendation_index_parameters_request(
+    recommend_db: Arc<RecommendationDbConnection>,
+    _dart_signal: DartSignal<FetchRecommendationIndexParametersRequest>,
+) {
+    send_index_parameters(&recommend_db, None);
+}
+
+pub async fn set_recommendation_index_parameters_request(
+    main_db: Arc<MainDbConnection>,
+    recommend_db: Arc<RecommendationDbConnection>,
+    lib_mode: Arc<LibraryMode>,
+    dart_signal: DartSignal<SetRecommendationIndexParametersRequest>,
+) {
+    let Some(parameters) = dart_signal.message.parameters else {
+        return;
+    };
+
+    if lib_mode.is_read_only() {
+        warn!("Library is read-only, skipping recommendation index parameters");
+        send_index_parameters(&recommend_db, Some("The library is read-only".to_string()));
+        return;
+    }
+
+    let parameters = IndexParameters {
+        n_trees: Some(parameters.n_trees as usize).filter(|x| *x > 0),
+        search_k_factor: parameters.search_k_factor as usize,
+    };
+
+    let rebuild = set_index_parameters(&recommend_db, parameters)
+        .map_err(|e| format!("Failed to set recommendation index parameters: {:#}", e));
+    let error = match rebuild {
+        Ok(true) => {
+            info!("Rebuilding the recommendation index with {:?}", parameters);
+            sync_recommendation(&main_db, &recommend_db)
+                .await
+                .err()
+                .map(|e| format!("Failed to rebuild the recommendation index: {:#}", e))
+        }
+        Ok(false) => None,
+        Err(e) => Some(format!(
+            "Failed to set recommendation index parameters: {:#}",
+            e
+        )),
+    };
+
+    if let Some(error) = &error {
+        error!("{}", error);
+    }
+    send_index_parameters(&recommend_db, error);
+}
+
+pub async fn benchmark_recommendation_index_request(
+    recommend_db: Arc<RecommendationDbConnection>,
+    dart_signal: DartSignal<BenchmarkRecommendationIndexRequest>,
+) {
+    let request = dart_signal.message;
+    let search_k_factors: Vec<usize> = request
+        .search_k_factors
+        .into_iter()
+        .filter(|x| *x > 0)
+        .map(|x| x as usize)
+        .collect();
+
+    match benchmark_index(
+        &recommend_db,
+        &search_k_factors,
+        request.samples as usize,
+        request.count.max(1) as usize,
+    ) {
+        Ok(report) => BenchmarkRecommendationIndexResponse {
+            n_trees: report.n_trees as u32,
+            n_items: report.n_items,
+            results: report
+                .results
+                .into_iter()
+                .map(|x| RecommendationIndexBenchmark {
+                    search_k_factor: x.search_k_factor as u32,
+                    recall: x.recall,
+                    latency_micros: x.latency.as_micros() as u64,
+                })
+                .collect(),
+            success: true,
+            error: String::new(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => {
+            error!("Failed to benchmark the recommendation index: {}", e);
+            BenchmarkRecommendationIndexResponse {
+                n_trees: 0,
+                n_items: 0,
+                results: Vec::new(),
+                success: false,
+                error: e.to_string(),
+            }
+            .send_signal_to_dart()
+        }
+    }
+}
+
+async fn send_mix_policy(user_db: &MainDbConnection) {
+    match get_mix_policy(user_db).await {
+        Ok(policy) => MixPolicyResponse {
+            policy: Some(crate::messages::recommend::MixPolicy {
+                max_per_artist: policy.max_per_artist as u32,
+                replay_hours: policy.replay_hours as u32,
+                min_tempo_variance: policy.min_tempo_variance,
+            }),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch the mix policy: {}", e),
+    }
+}
+
+pub async fn fetch_mix_policy_request(
+    user_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchMixPolicyRequest>,
+) {
+    send_mix_policy(&user_db).await;
+}
+
+pub async fn set_mix_policy_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SetMixPolicyRequest>,
+) {
+    let Some(policy) = dart_signal.message.policy else {
+        return;
+    };
+
+    let policy = MixPolicy {
+        max_per_artist: policy.max_per_artist as usize,
+        replay_hours: policy.replay_hours as i64,
+        min_tempo_variance: policy.min_tempo_variance,
+    };
+    if let Err(e) = set_mix_policy(&user_db, &policy).await {
+        error!("Failed to set the mix policy: {}", e);
+    }
+
+    send_mix_policy(&user_db).await;
+}
+
+async fn send_skip_penalties(user_db: &MainDbConnection) {
+    match get_skip_stats(user_db).await {
+        Ok(stats) => SkipPenaltiesResponse {
+            penalties: stats
+                .into_iter()
+                .map(|x| SkipPenalty {
+                    file_id: x.file_id,
+                    skips: x.skips,
+                    plays: x.plays,
+                    last_skipped_at: x.last_skipped_at,
+                    penalty: x.penalty,
+                })
+                .collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch skip penalties: {}", e),
+    }
+}
+
+pub async fn fetch_skip_penalties_request(
+    user_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchSkipPenaltiesRequest>,
+) {
+    send_skip_penalties(&user_db).await;
+}
+
+pub async fn reset_skip_penalties_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<ResetSkipPenaltiesRequest>,
+) {
+    match reset_skips(&user_db, &dart_signal.message.file_ids).await {
+        Ok(count) => info!("Reset skip penalties of {} tracks", count),
+        Err(e) => error!("Failed to reset skip penalties: {}", e),
+    }
+
+    send_skip_penalties(&user_db).await;
+}
+
+pub async fn fetch_music_map_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<FetchMusicMapRequest>,
+) {
+    let clusters = match dart_signal.message.clusters {
+        0 => DEFAULT_MAP_CLUSTERS,
+        x => x as usize,
+    };
+
+    match get_music_map(&main_db, clusters).await {
+        Ok(points) => FetchMusicMapResponse {
+            points: points
+                .into_iter()
+                .map(|x| MusicMapPoint {
+                    file_id: x.file_id,
+                    x: x.x,
+                    y: x.y,
+                    cluster: x.cluster as u32,
+                })
+                .collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to build the music map: {}", e),
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use rinf::DartSignal;
+use tracing::{info, warn};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{Builder, Rotation};
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, Registry};
+
+use crate::common::Result;
+use crate::messages::logging::{
+    FetchLogsRequest, FetchLogsResponse, SetLogLevelRequest, SetLogLevelResponse,
+};
+
+const BASE_DIRECTIVES: &str =
+    "symphonia_format_ogg=off,symphonia_core=off,sea_orm_migration::migrator=off,info";
+
+const LOG_BUFFER_SIZE: usize = 2000;
+const MAX_LOG_FILES: usize = 7;
+
+/// Map a subsystem name to the tracing targets (module paths) it covers.
+fn subsystem_targets(subsystem: &str) -> Option<&'static [&'static str]> {
+    match subsystem {
+        "playback" => Some(&["playback", "hub::player", "hub::playback"]),
+        "scan" => Some(&[
+            "metadata",
+            "database::actions::metadata",
+            "database::actions::index",
+            "hub::library_manage",
+        ]),
+        "analysis" => Some(&[
+            "analysis",
+            "database::actions::analysis",
+            "database::actions::recommendation",
+        ]),
+        "search" => Some(&["database::actions::search", "hub::search"]),
+        _ => None,
+    }
+}
+
+/// In-memory ring buffer holding the most recent formatted log lines.
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl LogBuffer {
+    pub fn recent(&self, limit: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        let skip = lines.len().saturating_sub(limit);
+        lines.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let line = String::from_utf8_lossy(buf).trim_end().to_string();
+
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() >= LOG_BUFFER_SIZE {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = LogBuffer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+struct LoggingState {
+    filter: reload::Handle<EnvFilter, Registry>,
+    levels: Mutex<BTreeMap<String, LevelFilter>>,
+    buffer: LogBuffer,
+    _file_guard: Option<WorkerGuard>,
+}
+
+static LOGGING: OnceLock<LoggingState> = OnceLock::new();
+
+pub(crate) fn log_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|x| x.join("rune").join("logs"))
+}
+
+fn build_filter(levels: &BTreeMap<String, LevelFilter>) -> EnvFilter {
+    let mut directives = BASE_DIRECTIVES.to_string();
+
+    for (subsystem, level) in levels {
+        for target in subsystem_targets(subsystem).unwrap_or_default() {
+            directives.push_str(&format!(",{}={}", target, level));
+        }
+    }
+
+    EnvFilter::new(directives)
+}
+
+/// Install the global tracing subscriber.
+///
+/// Logs are written to the console, to a ring buffer that can be fetched by
+/// the UI, and to a daily rotated file under the app data directory if it
+/// is available on the platform.
+pub fn initialize_logging() {
+    let (filter, handle) = reload::Layer::new(build_filter(&BTreeMap::new()));
+    let buffer = LogBuffer::default();
+
+    let file_appender = log_dir().and_then(|dir| {
+        Builder::new()
+            .rotation(Rotation::DAILY)
+            .filename_prefix("rune")
+            .filename_suffix("log")
+            .max_log_files(MAX_LOG_FILES)
+            .build(dir)
+            .ok()
+    });
+    let (file_layer, file_guard) = match file_appender {
+        Some(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_test_writer())
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(buffer.clone()),
+        )
+        .with(file_layer)
+        .init();
+
+    let _ = LOGGING.set(LoggingState {
+        filter: handle,
+        levels: Mutex::new(BTreeMap::new()),
+        buffer,
+        _file_guard: file_guard,
+    });
+
+    match log_dir() {
+        Some(dir) => info!("Writing logs to {:?}", dir),
+        None => warn!("No app data directory available, file logging disabled"),
+    }
+}
+
+/// Change the log level of a subsystem at runtime.
+///
+/// # Arguments
+/// * `subsystem` - One of `playback`, `scan`, `analysis` or `search`.
+/// * `level` - The new level, or an empty string to reset to the default.
+pub fn set_log_level(subsystem: &str, level: &str) -> Result<()> {
+    let state = LOGGING.get().ok_or("Logging is not initialized")?;
+
+    if subsystem_targets(subsystem).is_none() {
+        return Err(format!("Unknown subsystem: {}", subsystem).into());
+    }
+
+    let mut levels = state.levels.lock().unwrap();
+    if level.is_empty() {
+        levels.remove(subsystem);
+    } else {
+        levels.insert(subsystem.to_string(), LevelFilter::from_str(level)?);
+    }
+
+    state.filter.reload(build_filter(&levels))?;
+
+    Ok(())
+}
+
+/// Return the most recent log lines, oldest first.
+pub fn recent_logs(limit: usize) -> Vec<String> {
+    match LOGGING.get() {
+        Some(state) => state.buffer.recent(limit),
+        None => Vec::new(),
+    }
+}
+
+pub async fn set_log_level_request(dart_signal: DartSignal<SetLogLevelRequest>) {
+    let request = dart_signal.message;
+
+    let success = match set_log_level(&request.subsystem, &request.level) {
+        Ok(_) => {
+            info!(
+                "Log level of {} set to {:?}",
+                request.subsystem, request.level
+            );
+            true
+        }
+        Err(e) => {
+            warn!("Failed to set log level: {}", e);
+            false
+        }
+    };
+
+    SetLogLevelResponse {
+        subsystem: request.subsystem,
+        success,
+    }
+    .send_signal_to_dart();
+}
+
+pub async fn fetch_logs_request(dart_signal: DartSignal<FetchLogsRequest>) {
+    let request = dart_signal.message;
+
+    FetchLogsResponse {
+        lines: recent_logs(request.limit as usize),
+    }
+    .send_signal_to_dart();
+}
+
+/// Serve the diagnostic requests, independent of whether a library is opened.
+pub async fn receive_logging_requests() -> Result<()> {
+    let mut set_log_level_receiver = SetLogLevelRequest::get_dart_signal_receiver()?;
+    let mut fetch_logs_receiver = FetchLogsRequest::get_dart_signal_receiver()?;
+
+    loop {
+        tokio::select! {
+            Some(dart_signal) = set_log_level_receiver.recv() => {
+                set_log_level_request(dart_signal).await;
+            }
+            Some(dart_signal) = fetch_logs_receiver.recv() => {
+                fetch_logs_request(dart_signal).await;
+            }
+            else => break,
+        }
+    }
+
+    Ok(())
+}
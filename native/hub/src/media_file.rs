@@ -1,23 +1,36 @@
-use database::connection::MainDbConnection;
+use database::connection::{
+    connect_analysis_cache_db, MainDbConnection, RecommendationDbConnection,
+};
 use dunce::canonicalize;
-use log::debug;
-use log::{error, info};
 use rinf::DartSignal;
 use std::path::Path;
 use std::sync::Arc;
+use tracing::debug;
+use tracing::{error, info, warn};
 
-use database::actions::file::{compound_query_media_files, get_files_by_ids};
+use database::actions::analysis::analyse_single_file;
+use database::actions::file::{compound_query_media_files, get_files_by_ids, MediaFileFilter};
+use database::actions::file::{get_file_chapters, get_track_links, validate_files, FileValidation};
 use database::actions::metadata::get_metadata_summary_by_files;
 use database::actions::metadata::get_parsed_file_by_id;
 use database::actions::metadata::MetadataSummary;
+use database::actions::mix_markers::get_mix_markers;
+use database::actions::query_cache::QueryCache;
+use database::actions::recommendation::add_to_recommendation;
+use database::actions::tag_mappings::get_custom_tags_of_file;
+use database::actions::vibes::{get_vibe_ranges, FeatureRange, Vibe};
+use database::entities::media_analysis;
 use sea_orm::DatabaseConnection;
 
 use database::actions::file::get_media_files;
 
 use crate::common::*;
+use crate::library_manage::{analysis_cache_path, LibraryMode};
 use crate::messages;
 use crate::messages::album::Album;
 use crate::messages::artist::Artist;
+use crate::messages::playlist::Playlist;
+use crate::users::{active_clean_mode, active_user_id};
 use messages::media_file::*;
 
 async fn parse_media_files(
@@ -50,6 +63,7 @@ async fn parse_media_files(
 
 pub async fn fetch_media_files_request(
     db: Arc<DatabaseConnection>,
+    user_db: Arc<DatabaseConnection>,
     lib_path: Arc<String>,
     dart_signal: DartSignal<FetchMediaFilesRequest>,
 ) -> Result<()> {
@@ -63,6 +77,7 @@ pub async fn fetch_media_files_request(
         &db,
         cursor.try_into().unwrap(),
         page_size.try_into().unwrap(),
+        active_clean_mode(&user_db).await,
     )
     .await?;
 
@@ -118,8 +133,31 @@ pub async fn fetch_media_file_by_ids_request(
     };
 }
 
+/// The feature ranges of the vibes asked for, unknown names are skipped.
+pub async fn vibe_ranges(db: &DatabaseConnection, names: &[String]) -> Vec<FeatureRange> {
+    let vibes: Vec<Vibe> = names
+        .iter()
+        .filter_map(|name| {
+            let vibe = Vibe::parse(name);
+            if vibe.is_none() {
+                warn!("Unknown vibe: {}", name);
+            }
+            vibe
+        })
+        .collect();
+
+    match get_vibe_ranges(db, &vibes).await {
+        Ok(ranges) => ranges,
+        Err(e) => {
+            error!("Unable to get the ranges of the vibes: {}", e);
+            Vec::new()
+        }
+    }
+}
+
 pub async fn compound_query_media_files_request(
     db: Arc<DatabaseConnection>,
+    user_db: Arc<DatabaseConnection>,
     lib_path: Arc<String>,
     dart_signal: DartSignal<CompoundQueryMediaFilesRequest>,
 ) -> Result<()> {
@@ -129,6 +167,7 @@ pub async fn compound_query_media_files_request(
     let artist_ids = query_media_files.artist_ids;
     let album_ids = query_media_files.album_ids;
     let playlist_ids = query_media_files.playlist_ids;
+    let feature_ranges = vibe_ranges(&db, &query_media_files.vibes).await;
 
     info!(
         "Compound query media list with artist_ids: {:?}, album_ids: {:?}, playlist_ids: {:?}, page: {}, size: {}",
@@ -153,11 +192,17 @@ pub async fn compound_query_media_files_request(
         Some(playlist_ids)
     };
 
+    let filter = MediaFileFilter {
+        artist_ids: artist_ids_option,
+        album_ids: album_ids_option,
+        playlist_ids: playlist_ids_option,
+        hide_explicit: active_clean_mode(&user_db).await,
+        feature_ranges,
+    };
+
     let media_entries = compound_query_media_files(
         &db,
-        artist_ids_option,
-        album_ids_option,
-        playlist_ids_option,
+        filter,
         cursor.try_into().unwrap(),
         page_size.try_into().unwrap(),
     )
@@ -194,6 +239,13 @@ pub async fn fetch_parsed_media_file_request(
                 Ok(parsed_files) => {
                     if let Some(media_file) = parsed_files.first() {
                         if let Some(album) = album {
+                            let custom_tags = get_custom_tags_of_file(&db, file_id)
+                                .await
+                                .unwrap_or_else(|e| {
+                                    error!("Failed to get custom tags of {}: {}", file_id, e);
+                                    Vec::new()
+                                });
+
                             FetchParsedMediaFileResponse {
                                 file: Some(media_file.clone()),
                                 artists: artists
@@ -202,13 +254,20 @@ pub async fn fetch_parsed_media_file_request(
                                         id: x.id,
                                         name: x.name,
                                         cover_ids: [].to_vec(),
+                                        cover_blurhashes: Vec::new(),
                                     })
                                     .collect(),
                                 album: Some(Album {
                                     id: album.id,
                                     name: album.name,
                                     cover_ids: [].to_vec(),
+                                    cover_blurhashes: Vec::new(),
+                                    year: album.year.unwrap_or_default(),
                                 }),
+                                custom_tags: custom_tags
+                                    .into_iter()
+                                    .map(|(field, value)| CustomTag { field, value })
+                                    .collect(),
                             }
                             .send_signal_to_dart(); // GENERATED
                         } else {
@@ -229,3 +288,303 @@ pub async fn fetch_parsed_media_file_request(
     };
     Ok(())
 }
+
+pub async fn validate_media_files_request(
+    main_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    dart_signal: DartSignal<ValidateMediaFilesRequest>,
+) -> Result<()> {
+    let ids = dart_signal.message.ids;
+    debug!("Validating {} media files", ids.len());
+
+    match validate_files(&main_db, Path::new(lib_path.as_ref()), &ids).await {
+        Ok(items) => {
+            let result = items
+                .into_iter()
+                .map(|(id, validation)| {
+                    let (status, error) = match validation {
+                        FileValidation::Ok => ("ok", String::new()),
+                        FileValidation::NotInLibrary => ("not_in_library", String::new()),
+                        FileValidation::Missing => ("missing", String::new()),
+                        FileValidation::Unreadable(e) => ("unreadable", e),
+                        FileValidation::Undecodable(e) => ("undecodable", e),
+                    };
+
+                    MediaFileValidation {
+                        id,
+                        status: status.to_string(),
+                        error,
+                    }
+                })
+                .collect();
+
+            ValidateMediaFilesResponse { result }.send_signal_to_dart(); // GENERATED
+        }
+        Err(e) => {
+            error!("Error happened while validating media files: {:#?}", e);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn fetch_media_file_chapters_request(
+    main_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    dart_signal: DartSignal<FetchMediaFileChaptersRequest>,
+) -> Result<()> {
+    let file_id = dart_signal.message.file_id;
+    debug!("Fetching chapters of file: {}", file_id);
+
+    match get_file_chapters(&main_db, Path::new(lib_path.as_ref()), file_id).await {
+        Ok(chapters) => {
+            let chapters = chapters
+                .into_iter()
+                .map(|x| MediaFileChapter {
+                    number: x.number,
+                    title: x.title.unwrap_or_default(),
+                    start: x.start,
+                    end: x.end,
+                })
+                .collect();
+
+            FetchMediaFileChaptersResponse { file_id, chapters }.send_signal_to_dart();
+            // GENERATED
+        }
+        Err(e) => {
+            error!("Error happened while fetching chapters: {:#?}", e);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn fetch_mix_markers_request(
+    main_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    dart_signal: DartSignal<FetchMixMarkersRequest>,
+) -> Result<()> {
+    let file_id = dart_signal.message.file_id;
+    debug!("Fetching mix markers of file: {}", file_id);
+
+    match get_mix_markers(&main_db, Path::new(lib_path.as_ref()), file_id).await {
+        Ok(markers) => {
+            let is_long_mix = markers.is_some();
+            let markers = markers
+                .unwrap_or_default()
+                .into_iter()
+                .map(|x| MixMarker {
+                    position: x.position,
+                    title: x.title.unwrap_or_default(),
+                    source: x.source.as_str().to_string(),
+                })
+                .collect();
+
+            FetchMixMarkersResponse {
+                file_id,
+                is_long_mix,
+                markers,
+            }
+            .send_signal_to_dart();
+        }
+        Err(e) => {
+            error!("Error happened while fetching mix markers: {:#?}", e);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn fetch_track_links_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<FetchTrackLinksRequest>,
+) -> Result<()> {
+    let file_id = dart_signal.message.file_id;
+
+    match get_track_links(&main_db, file_id).await {
+        Ok(links) => FetchTrackLinksResponse {
+            file_id,
+            album_ids: links.album_ids,
+            artist_ids: links.artist_ids,
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Error happened while fetching track links: {:#?}", e),
+    }
+
+    Ok(())
+}
+
+fn to_track_analysis(x: media_analysis::Model) -> TrackAnalysis {
+    TrackAnalysis {
+        spectral_centroid: x.spectral_centroid.unwrap_or_default(),
+        spectral_flatness: x.spectral_flatness.unwrap_or_default(),
+        spectral_slope: x.spectral_slope.unwrap_or_default(),
+        spectral_rolloff: x.spectral_rolloff.unwrap_or_default(),
+        spectral_spread: x.spectral_spread.unwrap_or_default(),
+        spectral_skewness: x.spectral_skewness.unwrap_or_default(),
+        spectral_kurtosis: x.spectral_kurtosis.unwrap_or_default(),
+        chroma: [
+            x.chroma0, x.chroma1, x.chroma2, x.chroma3, x.chroma4, x.chroma5, x.chroma6, x.chroma7,
+            x.chroma8, x.chroma9, x.chroma10, x.chroma11,
+        ]
+        .into_iter()
+        .map(|x| x.unwrap_or_default())
+        .collect(),
+    }
+}
+
+pub async fn fetch_track_detail_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    query_cache: Arc<QueryCache>,
+    dart_signal: DartSignal<FetchTrackDetailRequest>,
+) -> Result<()> {
+    let file_id = dart_signal.message.file_id;
+    debug!("Fetching details of file: {}", file_id);
+
+    let user_id = active_user_id(&user_db).await;
+    match query_cache
+        .track_detail(
+            &main_db,
+            &user_db,
+            user_id,
+            Path::new(lib_path.as_ref()),
+            file_id,
+        )
+        .await
+    {
+        Ok(detail) => {
+            let analysis = detail.analysis.map(to_track_analysis);
+
+            FetchTrackDetailResponse {
+                file_id,
+                success: true,
+                path: detail.path.to_string_lossy().to_string(),
+                size: detail.size.unwrap_or_default() as i64,
+                file_hash: detail.file.file_hash,
+                last_modified: detail.file.last_modified,
+                tags: detail
+                    .tags
+                    .into_iter()
+                    .map(|(key, value)| TrackTag { key, value })
+                    .collect(),
+                stream: detail.stream.map(|x| TrackStreamInfo {
+                    codec: x.codec,
+                    sample_rate: x.sample_rate as i32,
+                    channels: x.channels as i32,
+                    bits_per_sample: x.bits_per_sample.unwrap_or_default() as i32,
+                    bitrate: x.bitrate.unwrap_or_default() as i32,
+                    duration: x.duration,
+                }),
+                analysis,
+                play_count: detail.play_count as i32,
+                last_played: detail.last_played.unwrap_or_default(),
+                rating: detail.rating.unwrap_or_default(),
+                playlists: detail
+                    .playlists
+                    .into_iter()
+                    .map(|x| Playlist {
+                        id: x.id,
+                        name: x.name,
+                        group: x.group,
+                        cover_ids: Vec::new(),
+                        cover_blurhashes: Vec::new(),
+                        cover_art_id: x.cover_art_id.unwrap_or(-1),
+                    })
+                    .collect(),
+                cover_art_id: detail.cover_art_id.unwrap_or(-1),
+            }
+            .send_signal_to_dart();
+        }
+        Err(e) => {
+            error!("Error happened while fetching track details: {:#?}", e);
+            FetchTrackDetailResponse {
+                file_id,
+                success: false,
+                cover_art_id: -1,
+                ..Default::default()
+            }
+            .send_signal_to_dart();
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn analyse_track_request(
+    main_db: Arc<MainDbConnection>,
+    recommend_db: Arc<RecommendationDbConnection>,
+    lib_path: Arc<String>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    dart_signal: DartSignal<AnalyseTrackRequest>,
+) {
+    let file_id = dart_signal.message.file_id;
+    debug!("Analysing file on demand: {}", file_id);
+
+    if lib_mode.is_read_only() {
+        AnalyseTrackResponse {
+            file_id,
+            success: false,
+            error: "The library is read-only".to_string(),
+            analysis: None,
+        }
+        .send_signal_to_dart();
+        return;
+    }
+
+    // Analysis still works without the cache, it just can't be shared
+    let analysis_cache = match connect_analysis_cache_db(&analysis_cache_path()).await {
+        Ok(cache) => Some(cache),
+        Err(e) => {
+            warn!("Unable to open the analysis cache: {}", e);
+            None
+        }
+    };
+
+    let result = analyse_single_file(
+        &main_db,
+        Path::new(lib_path.as_ref()),
+        analysis_cache.as_ref(),
+        file_id,
+    )
+    .await;
+
+    match result {
+        Ok(Some(analysis)) => {
+            // Recommendations from the track use the new results right away
+            if let Err(e) = add_to_recommendation(&recommend_db, &analysis) {
+                error!("Unable to add the analysis to the recommendations: {}", e);
+            }
+            query_cache.invalidate_files(&[file_id]);
+
+            AnalyseTrackResponse {
+                file_id,
+                success: true,
+                error: String::new(),
+                analysis: Some(to_track_analysis(analysis)),
+            }
+            .send_signal_to_dart();
+        }
+        Ok(None) => {
+            AnalyseTrackResponse {
+                file_id,
+                success: false,
+                error: "The track is not in the library".to_string(),
+                analysis: None,
+            }
+            .send_signal_to_dart();
+        }
+        Err(e) => {
+            error!("Unable to analyse file {}: {}", file_id, e);
+            AnalyseTrackResponse {
+                file_id,
+                success: false,
+                error: e.to_string(),
+                analysis: None,
+            }
+            .send_signal_to_dart();
+        }
+    }
+}
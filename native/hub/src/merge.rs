@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use rinf::DartSignal;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use database::actions::merge::{
+    get_merged_names, merge_collections, preview_merge, preview_split, split_collection,
+};
+use database::actions::query_cache::QueryCache;
+use database::actions::search::CollectionType;
+use database::connection::{MainDbConnection, SearchDbConnection};
+
+use crate::library_manage::LibraryMode;
+use crate::messages::merge::{
+    FetchMergedNamesRequest, MergeCollectionsRequest, MergeCollectionsResponse,
+    MergedNamesResponse, SplitCollectionRequest, SplitCollectionResponse,
+};
+
+fn parse_collection_type(collection_type: &str) -> Result<CollectionType, String> {
+    match collection_type {
+        "artist" => Ok(CollectionType::Artist),
+        "album" => Ok(CollectionType::Album),
+        _ => Err(format!(
+            "Can't merge collections of type {}",
+            collection_type
+        )),
+    }
+}
+
+pub async fn merge_collections_request(
+    main_db: Arc<MainDbConnection>,
+    search_db: Arc<Mutex<SearchDbConnection>>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    dart_signal: DartSignal<MergeCollectionsRequest>,
+) {
+    let request = dart_signal.message;
+    info!(
+        "Merging {} {} into {} (preview: {})",
+        request.r#type, request.source_id, request.target_id, request.preview
+    );
+
+    let result = match parse_collection_type(&request.r#type) {
+        Ok(_) if !request.preview && lib_mode.is_read_only() => {
+            Err("The library is read-only".to_string())
+        }
+        Ok(collection_type) if request.preview => preview_merge(
+            &main_db,
+            collection_type,
+            request.source_id,
+            request.target_id,
+        )
+        .await
+        .map_err(|e| e.to_string()),
+        Ok(collection_type) => {
+            let mut search_db = search_db.lock().await;
+            merge_collections(
+                &main_db,
+                &mut search_db,
+                collection_type,
+                request.source_id,
+                request.target_id,
+            )
+            .await
+            .map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e),
+    };
+
+    if let (Ok(file_ids), false) = (&result, request.preview) {
+        query_cache.invalidate_files(file_ids);
+    }
+    if let Err(e) = &result {
+        error!("Failed to merge collections: {}", e);
+    }
+
+    MergeCollectionsResponse {
+        r#type: request.r#type,
+        source_id: request.source_id,
+        target_id: request.target_id,
+        preview: request.preview,
+        success: result.is_ok(),
+        error: result.as_ref().err().cloned().unwrap_or_default(),
+        file_ids: result.unwrap_or_default(),
+    }
+    .send_signal_to_dart();
+}
+
+pub async fn fetch_merged_names_request(
+    main_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<FetchMergedNamesRequest>,
+) {
+    let request = dart_signal.message;
+
+    let names = match parse_collection_type(&request.r#type) {
+        Ok(collection_type) => get_merged_names(&main_db, collection_type, request.id)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+
+    let names = names.unwrap_or_else(|e| {
+        error!("Failed to get merged names: {}", e);
+        Vec::new()
+    });
+
+    MergedNamesResponse {
+        r#type: request.r#type,
+        id: request.id,
+        names,
+    }
+    .send_signal_to_dart();
+}
+
+pub async fn split_collection_request(
+    main_db: Arc<MainDbConnection>,
+    search_db: Arc<Mutex<SearchDbConnection>>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    dart_signal: DartSignal<SplitCollectionRequest>,
+) {
+    let request = dart_signal.message;
+    info!(
+        "Splitting {} from {} {} (preview: {})",
+        request.name, request.r#type, request.id, request.preview
+    );
+
+    let result = match parse_collection_type(&request.r#type) {
+        Ok(_) if !request.preview && lib_mode.is_read_only() => {
+            Err("The library is read-only".to_string())
+        }
+        Ok(collection_type) if request.preview => {
+            preview_split(&main_db, collection_type, request.id, &request.name)
+                .await
+                .map(|file_ids| (None, file_ids))
+                .map_err(|e| e.to_string())
+        }
+        Ok(collection_type) => {
+            let mut search_db = search_db.lock().await;
+            split_collection(
+                &main_db,
+                &mut search_db,
+                collection_type,
+                request.id,
+                &request.name,
+            )
+            .await
+            .map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e),
+    };
+
+    if let (Ok((_, file_ids)), false) = (&result, request.preview) {
+        query_cache.invalidate_files(file_ids);
+    }
+    if let Err(e) = &result {
+        error!("Failed to split collection: {}", e);
+    }
+
+    let (new_id, file_ids, error) = match result {
+        Ok((new_id, file_ids)) => (new_id.unwrap_or(-1), file_ids, None),
+        Err(e) => (-1, Vec::new(), Some(e)),
+    };
+
+    SplitCollectionResponse {
+        r#type: request.r#type,
+        id: request.id,
+        name: request.name,
+        preview: request.preview,
+        success: error.is_none(),
+        error: error.unwrap_or_default(),
+        new_id,
+        file_ids,
+    }
+    .send_signal_to_dart();
+}
@@ -0,0 +1,27 @@
+use rinf::DartSignal;
+
+use crate::messages::metrics::{FetchMetricsRequest, FetchMetricsResponse};
+
+pub async fn fetch_metrics_request(dart_signal: DartSignal<FetchMetricsRequest>) {
+    let request = dart_signal.message;
+
+    let snapshot = ::metrics::snapshot();
+    if request.reset {
+        ::metrics::reset();
+    }
+
+    FetchMetricsResponse {
+        decode_underruns: snapshot.decode_underruns,
+        db_query_count: snapshot.db_queries.count,
+        db_query_mean_ms: snapshot.db_queries.mean().as_secs_f64() * 1000.0,
+        db_query_max_ms: snapshot.db_queries.max.as_secs_f64() * 1000.0,
+        scanned_files: snapshot.scanned_files,
+        scan_throughput: snapshot.scan_throughput(),
+        index_commit_count: snapshot.index_commits.count,
+        index_commit_mean_ms: snapshot.index_commits.mean().as_secs_f64() * 1000.0,
+        index_commit_max_ms: snapshot.index_commits.max.as_secs_f64() * 1000.0,
+        cache_hits: snapshot.cache_hits,
+        cache_misses: snapshot.cache_misses,
+    }
+    .send_signal_to_dart();
+}
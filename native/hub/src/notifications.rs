@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use rinf::DartSignal;
+use tracing::error;
+
+use database::actions::settings::{get_setting, set_setting};
+use database::connection::MainDbConnection;
+
+use crate::messages::notifications::*;
+
+const TRACK_NOTIFICATIONS_KEY: &str = "notifications.track_change";
+
+/// Whether a notification is shown when another track starts, on by default.
+pub async fn track_notifications_enabled(user_db: &MainDbConnection) -> bool {
+    match get_setting(user_db, TRACK_NOTIFICATIONS_KEY).await {
+        Ok(value) => value.is_none_or(|x| x == "true"),
+        Err(e) => {
+            error!("Unable to read track notifications setting: {}", e);
+            true
+        }
+    }
+}
+
+pub async fn set_track_notifications_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SetTrackNotificationsRequest>,
+) {
+    let enabled = dart_signal.message.enabled;
+
+    if let Err(e) = set_setting(
+        user_db.as_ref(),
+        TRACK_NOTIFICATIONS_KEY,
+        enabled.to_string(),
+    )
+    .await
+    {
+        error!("Unable to save track notifications setting: {}", e);
+    }
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+mod desktop {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use notify_rust::Notification;
+    use tokio::sync::broadcast::error::RecvError;
+    use tokio::sync::Mutex;
+    use tokio::task;
+    use tracing::{error, warn};
+
+    use database::actions::metadata::MetadataSummary;
+    use database::actions::query_cache::QueryCache;
+    use database::actions::remote::from_queue_id;
+    use database::connection::MainDbConnection;
+    use playback::player::{PlaybackState, Player};
+
+    use crate::player::CurrentMeta;
+
+    use super::track_notifications_enabled;
+
+    const APP_NAME: &str = "Rune";
+
+    // The notification server reads the artwork from a file, one is kept
+    // per cover in the temporary directory
+    async fn cover_art_path(
+        main_db: &MainDbConnection,
+        lib_path: &str,
+        query_cache: &QueryCache,
+        file_id: i32,
+    ) -> Option<PathBuf> {
+        // Remote files have no cover in the library
+        if from_queue_id(file_id).is_some() {
+            return None;
+        }
+
+        let (cover_art_id, cover_art) = match query_cache
+            .cover_art_of_file(main_db, lib_path, file_id)
+            .await
+        {
+            Ok(Some(cover_art)) if !cover_art.1.is_empty() => cover_art,
+            Ok(_) => return None,
+            Err(e) => {
+                warn!("Unable to read the cover art of {}: {}", file_id, e);
+                return None;
+            }
+        };
+
+        let path = std::env::temp_dir().join(format!("rune-cover-{}", cover_art_id));
+        if !path.exists() {
+            if let Err(e) = std::fs::write(&path, cover_art) {
+                warn!("Unable to write the notification artwork: {}", e);
+                return None;
+            }
+        }
+        Some(path)
+    }
+
+    fn show_notification(meta: MetadataSummary, cover_art: Option<PathBuf>) {
+        let mut notification = Notification::new();
+        notification
+            .appname(APP_NAME)
+            .summary(&meta.title)
+            .body(&meta.artist);
+        #[cfg(not(target_os = "macos"))]
+        if let Some(path) = cover_art.as_ref().and_then(|x| x.to_str()) {
+            notification.image_path(path);
+        }
+        #[cfg(target_os = "macos")]
+        let _ = cover_art;
+
+        if let Err(e) = notification.show() {
+            error!("Failed to show the track notification: {}", e);
+        }
+    }
+
+    /// Show a notification with the title, the artist and the cover every
+    /// time another track starts, also while the window is minimized.
+    pub async fn notify_track_changes(
+        main_db: Arc<MainDbConnection>,
+        user_db: Arc<MainDbConnection>,
+        lib_path: Arc<String>,
+        query_cache: Arc<QueryCache>,
+        player: Arc<Mutex<Player>>,
+    ) {
+        let mut status_receiver = player.lock().await.subscribe_status();
+        let mut current_meta = CurrentMeta::default();
+        let mut last_id: Option<i32> = None;
+
+        loop {
+            let status = match status_receiver.recv().await {
+                Ok(status) => status,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let Some(id) = status.id else {
+                // The same track played again after stopping is announced
+                last_id = None;
+                continue;
+            };
+            if status.state != PlaybackState::Playing || last_id == Some(id) {
+                continue;
+            }
+            last_id = Some(id);
+            if !track_notifications_enabled(&user_db).await {
+                continue;
+            }
+
+            let meta = current_meta.get(&main_db, &user_db, Some(id)).await;
+            let cover_art = cover_art_path(&main_db, &lib_path, &query_cache, id).await;
+            // Showing waits for the notification server
+            task::spawn_blocking(move || show_notification(meta, cover_art));
+        }
+    }
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+pub use desktop::notify_track_changes;
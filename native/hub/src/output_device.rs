@@ -0,0 +1,202 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use rinf::DartSignal;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use database::actions::device_profiles::{
+    get_device_profile, get_profiled_device_names, save_device_profile, DeviceProfile,
+};
+use database::actions::settings::{get_setting, set_setting};
+use database::connection::MainDbConnection;
+use playback::backend::{default_output_device_name, output_device_names};
+use playback::player::Player;
+
+use crate::messages::playback::{
+    FetchOutputDevicesRequest, FetchOutputDevicesResponse, OutputDevice, SelectOutputDeviceRequest,
+};
+use crate::playback::{CROSSFEED_KEY, NIGHT_MODE_KEY};
+
+const OUTPUT_DEVICE_KEY: &str = "playback.output_device";
+// How often the devices are checked for being plugged in or out
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+async fn selected_output_device(user_db: &MainDbConnection) -> Option<String> {
+    match get_setting(user_db, OUTPUT_DEVICE_KEY).await {
+        Ok(value) => value.filter(|x| !x.is_empty()),
+        Err(e) => {
+            error!("Unable to read output device setting: {}", e);
+            None
+        }
+    }
+}
+
+async fn bool_setting(user_db: &MainDbConnection, key: &str) -> bool {
+    match get_setting(user_db, key).await {
+        Ok(value) => value.and_then(|x| x.parse().ok()).unwrap_or(false),
+        Err(e) => {
+            error!("Unable to read {} setting: {}", key, e);
+            false
+        }
+    }
+}
+
+/// The device audio goes to, the selected one while it is plugged in and the
+/// system default otherwise.
+async fn active_output_device(selected: Option<String>) -> Option<String> {
+    tokio::task::spawn_blocking(move || match selected {
+        Some(name) if output_device_names().contains(&name) => Some(name),
+        _ => default_output_device_name(),
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Save the current volume, night mode and crossfeed for the active device.
+///
+/// # Arguments
+/// * `volume` - The volume just set, the one of the player if `None`.
+pub async fn remember_device_profile(
+    user_db: &MainDbConnection,
+    player: &Arc<Mutex<Player>>,
+    volume: Option<f32>,
+) {
+    let status = player.lock().await.get_status();
+    let Some(device) = active_output_device(status.output_device).await else {
+        return;
+    };
+
+    let profile = DeviceProfile {
+        volume: volume.unwrap_or(status.volume),
+        night_mode: bool_setting(user_db, NIGHT_MODE_KEY).await,
+        crossfeed: bool_setting(user_db, CROSSFEED_KEY).await,
+    };
+
+    if let Err(e) = save_device_profile(user_db, &device, profile).await {
+        error!("Unable to save the settings of {}: {}", device, e);
+    }
+}
+
+/// Apply the settings remembered for a device, devices seen for the first
+/// time keep the current ones.
+async fn apply_device_profile(
+    user_db: &MainDbConnection,
+    player: &Arc<Mutex<Player>>,
+    device: &str,
+) {
+    let profile = match get_device_profile(user_db, device).await {
+        Ok(Some(profile)) => profile,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Unable to read the settings of {}: {}", device, e);
+            return;
+        }
+    };
+
+    {
+        let player = player.lock().await;
+        player.set_volume(profile.volume);
+        player.set_night_mode(profile.night_mode);
+        player.set_crossfeed(profile.crossfeed);
+    }
+
+    for (key, enabled) in [
+        (NIGHT_MODE_KEY, profile.night_mode),
+        (CROSSFEED_KEY, profile.crossfeed),
+    ] {
+        if let Err(e) = set_setting(user_db, key, enabled.to_string()).await {
+            error!("Unable to save {} setting: {}", key, e);
+        }
+    }
+}
+
+pub async fn fetch_output_devices_request(
+    user_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchOutputDevicesRequest>,
+) {
+    let (names, default_name) =
+        tokio::task::spawn_blocking(|| (output_device_names(), default_output_device_name()))
+            .await
+            .unwrap_or_default();
+
+    let profiled = match get_profiled_device_names(&user_db).await {
+        Ok(names) => names,
+        Err(e) => {
+            error!("Unable to list the profiled output devices: {}", e);
+            Vec::new()
+        }
+    };
+
+    FetchOutputDevicesResponse {
+        devices: names
+            .into_iter()
+            .map(|name| OutputDevice {
+                is_default: default_name.as_ref() == Some(&name),
+                has_profile: profiled.contains(&name),
+                name,
+            })
+            .collect(),
+        selected: selected_output_device(&user_db).await.unwrap_or_default(),
+    }
+    .send_signal_to_dart();
+}
+
+pub async fn select_output_device_request(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SelectOutputDeviceRequest>,
+) {
+    let name = dart_signal.message.name;
+    if let Err(e) = set_setting(user_db.as_ref(), OUTPUT_DEVICE_KEY, name.clone()).await {
+        error!("Unable to save output device setting: {}", e);
+    }
+
+    let selected = Some(name).filter(|x| !x.is_empty());
+    player.lock().await.set_output_device(selected.clone());
+
+    if let Some(device) = active_output_device(selected).await {
+        apply_device_profile(&user_db, &player, &device).await;
+    }
+}
+
+/// Open the selected device on startup and follow devices being plugged in
+/// or out, applying the settings remembered for the device played through.
+pub async fn watch_output_devices(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    cancel_token: CancellationToken,
+) {
+    let mut selected = selected_output_device(&user_db).await;
+    if selected.is_some() {
+        player.lock().await.set_output_device(selected.clone());
+    }
+    let mut active = active_output_device(selected.clone()).await;
+    if let Some(device) = &active {
+        apply_device_profile(&user_db, &player, device).await;
+    }
+
+    loop {
+        tokio::select! {
+            _ = cancel_token.cancelled() => break,
+            _ = tokio::time::sleep(DEVICE_POLL_INTERVAL) => {}
+        }
+
+        let now_selected = selected_output_device(&user_db).await;
+        let now_active = active_output_device(now_selected.clone()).await;
+
+        // Selections apply their profile themselves
+        if now_selected == selected && now_active != active {
+            info!("Output device changed to {:?}", now_active);
+            player.lock().await.set_output_device(now_selected.clone());
+            if let Some(device) = &now_active {
+                apply_device_profile(&user_db, &player, device).await;
+            }
+        }
+
+        selected = now_selected;
+        active = now_active;
+    }
+}
@@ -1,45 +1,171 @@
 use dunce::canonicalize;
-use log::error;
 use rinf::DartSignal;
 use sea_orm::DatabaseConnection;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
+use tracing::error;
 
-use database::actions::albums::get_media_file_ids_of_album;
-use database::actions::analysis::get_centralized_analysis_result;
+use database::actions::albums::{
+    get_album_tracks_of_files, get_disc_numbers_of_files, get_media_file_ids_of_album,
+};
+use database::actions::analysis::{get_centralized_analysis_result, get_mix_points_of_files};
 use database::actions::artists::get_media_file_ids_of_artist;
+use database::actions::audiobooks::get_audiobook_file_ids;
+use database::actions::auto_dj::{
+    chain_tracks, get_dj_tracks, AutoDjOptions, DjTrack, EnergyProgression,
+};
+use database::actions::cold_start::get_recommendation_with_fallback;
+use database::actions::diversity::apply_mix_policy;
+use database::actions::exclusions::{get_excluded_file_ids, Exclusion};
+use database::actions::explicit::get_explicit_file_ids;
 use database::actions::file::get_file_by_id;
 use database::actions::file::get_files_by_ids;
+use database::actions::gain::{get_gain_offsets, get_replay_gains_of_files, set_gain_offset};
+use database::actions::gapless::get_gapless_info_of_files;
+use database::actions::journal::{Operation, OperationJournal};
+use database::actions::mix_markers::get_mix_markers;
 use database::actions::playlists::get_media_file_ids_of_playlist;
-use database::actions::recommendation::{
-    get_recommendation_by_file_id, get_recommendation_by_parameter,
-};
+use database::actions::recommendation::get_recommendation_by_parameter;
+use database::actions::seek_tables::get_seek_tables_of_files;
+use database::actions::settings::{get_setting, set_setting};
+use database::actions::shuffle::{album_shuffle, artist_shuffle, keep_in_place};
+use database::actions::skips::{down_rank, get_skip_penalties};
 use database::connection::{MainDbConnection, RecommendationDbConnection};
-use playback::player::Player;
+use playback::player::{clamp_gain, Player};
+use playback::preview::{preview_length, TransitionPreview};
+use playback::queue::{AlbumTrack, DuplicatePolicy, PlaylistItem};
+use playback::trim::EncoderTrim;
+use playback::MixPoints;
 
 use crate::common::Result;
 use crate::messages::playback::{
-    NextRequest, PauseRequest, PlayFileRequest, PlayRequest, PreviousRequest, RemoveRequest,
-    SeekRequest, SwitchRequest,
+    AddToQueueCollectionResponse, GetPlaybackStateRequest, NextRequest, PauseRequest,
+    PlayFileRequest, PlayRequest, PreviewTransitionRequest, PreviewTransitionResponse,
+    PreviousRequest, RemoveRequest, SeekRequest, SeekToMarkerRequest, SetCoarseProgressRequest,
+    SetCrossfadeRequest, SetCrossfeedRequest, SetLimiterRequest, SetMonoRequest,
+    SetNightModeRequest, SetPlaybackModeRequest, SetPreampRequest, SetProgressIntervalRequest,
+    SetTrackGainOffsetRequest, SetTrackGainOffsetResponse, SetVolumeRequest,
+    ShufflePlaylistRequest, SwitchRequest,
 };
-use crate::messages::recommend::{PlaybackRecommendation, RecommendAndPlayRequest};
+use crate::messages::recommend::{
+    PlaybackRecommendation, RecommendAndPlayRequest, StartAutoDjRequest, StartAutoDjResponse,
+};
+use crate::output_device::remember_device_profile;
+use crate::player::send_playback_state_snapshot;
+use crate::recent_contexts::{remember_context, remember_context_progress};
+use crate::users::{active_clean_mode, active_user_id};
 use crate::{
     AddToQueueCollectionRequest, MovePlaylistItemRequest, StartPlayingCollectionRequest,
     StartRoamingCollectionRequest,
 };
 
+const CROSSFADE_KEY: &str = "playback.crossfade";
+const PREAMP_KEY: &str = "playback.preamp";
+const LIMITER_KEY: &str = "playback.limiter";
+const MONO_KEY: &str = "playback.mono";
+pub(crate) const NIGHT_MODE_KEY: &str = "playback.night_mode";
+pub(crate) const CROSSFEED_KEY: &str = "playback.crossfeed";
+const PROGRESS_INTERVAL_KEY: &str = "playback.progress_interval";
+
+/// Apply the playback settings saved in the user database to a new player.
+pub async fn restore_playback_settings(user_db: &DatabaseConnection, player: &Arc<Mutex<Player>>) {
+    match get_setting(user_db, CROSSFADE_KEY).await {
+        Ok(Some(value)) => match value.parse::<f32>() {
+            Ok(seconds) => player.lock().await.set_crossfade(seconds),
+            Err(e) => error!("Invalid crossfade setting {}: {}", value, e),
+        },
+        Ok(None) => {}
+        Err(e) => error!("Unable to read crossfade setting: {}", e),
+    }
+
+    match get_setting(user_db, PREAMP_KEY).await {
+        Ok(Some(value)) => match value.parse::<f32>() {
+            Ok(db) => player.lock().await.set_preamp(db),
+            Err(e) => error!("Invalid pre-amp setting {}: {}", value, e),
+        },
+        Ok(None) => {}
+        Err(e) => error!("Unable to read pre-amp setting: {}", e),
+    }
+
+    match get_setting(user_db, LIMITER_KEY).await {
+        Ok(Some(value)) => match value.parse::<bool>() {
+            Ok(enabled) => player.lock().await.set_limiter(enabled),
+            Err(e) => error!("Invalid limiter setting {}: {}", value, e),
+        },
+        Ok(None) => {}
+        Err(e) => error!("Unable to read limiter setting: {}", e),
+    }
+
+    match get_setting(user_db, MONO_KEY).await {
+        Ok(Some(value)) => match value.parse::<bool>() {
+            Ok(enabled) => player.lock().await.set_mono(enabled),
+            Err(e) => error!("Invalid mono setting {}: {}", value, e),
+        },
+        Ok(None) => {}
+        Err(e) => error!("Unable to read mono setting: {}", e),
+    }
+
+    match get_setting(user_db, NIGHT_MODE_KEY).await {
+        Ok(Some(value)) => match value.parse::<bool>() {
+            Ok(enabled) => player.lock().await.set_night_mode(enabled),
+            Err(e) => error!("Invalid night mode setting {}: {}", value, e),
+        },
+        Ok(None) => {}
+        Err(e) => error!("Unable to read night mode setting: {}", e),
+    }
+
+    match get_setting(user_db, CROSSFEED_KEY).await {
+        Ok(Some(value)) => match value.parse::<bool>() {
+            Ok(enabled) => player.lock().await.set_crossfeed(enabled),
+            Err(e) => error!("Invalid crossfeed setting {}: {}", value, e),
+        },
+        Ok(None) => {}
+        Err(e) => error!("Unable to read crossfeed setting: {}", e),
+    }
+
+    match get_setting(user_db, PROGRESS_INTERVAL_KEY).await {
+        Ok(Some(value)) => match value.parse::<u64>() {
+            Ok(milliseconds) => player
+                .lock()
+                .await
+                .set_progress_interval(Duration::from_millis(milliseconds)),
+            Err(e) => error!("Invalid progress interval setting {}: {}", value, e),
+        },
+        Ok(None) => {}
+        Err(e) => error!("Unable to read progress interval setting: {}", e),
+    }
+
+    match get_gain_offsets(user_db).await {
+        Ok(offsets) => {
+            let player = player.lock().await;
+            for (id, offset) in offsets {
+                player.set_gain_offset(id, offset);
+            }
+        }
+        Err(e) => error!("Unable to read gain offsets: {}", e),
+    }
+}
+
 async fn play_file_by_id(
     db: Arc<DatabaseConnection>,
     player: Arc<Mutex<Player>>,
     lib_path: Arc<String>,
+    journal: Arc<OperationJournal>,
     file_id: i32,
 ) {
     match get_file_by_id(&db, file_id).await {
         Ok(Some(file)) => {
             let player_guard = player.lock().await;
+            journal.record(Operation::Queue {
+                before: player_guard.get_playlist(),
+                after: vec![file_id],
+            });
             player_guard.pause();
             player_guard.clear_playlist();
+            drop(player_guard);
 
             let file_path = canonicalize(
                 Path::new(&*lib_path)
@@ -47,21 +173,19 @@ async fn play_file_by_id(
                     .join(file.file_name),
             )
             .unwrap();
-            player_guard.add_to_playlist(file_id, file_path);
-            player_guard.play();
+            update_playlist(&db, &player, vec![(file_id, file_path)]).await;
         }
         Ok(_none) => {
-            eprintln!("File with ID {} not found", file_id);
+            error!("File with ID {} not found", file_id);
         }
         Err(e) => {
-            eprintln!("Error retrieving file with ID {}: {}", file_id, e);
+            error!("Error retrieving file with ID {}: {}", file_id, e);
         }
     }
 }
 
-
 fn files_to_playback_request(
-    lib_path: &String,
+    lib_path: &str,
     files: std::result::Result<Vec<database::entities::media_files::Model>, sea_orm::DbErr>,
 ) -> std::vec::Vec<(i32, std::path::PathBuf)> {
     match files {
@@ -85,15 +209,212 @@ fn files_to_playback_request(
     }
 }
 
+// Audiobooks are meant to be listened in order and flagged files were kept out
+// by the user, neither belongs in generated queues. Neither do explicit tracks
+// in clean mode.
+async fn excluded_from_radio(
+    db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    ids: &[i32],
+) -> HashSet<i32> {
+    let mut excluded = match get_audiobook_file_ids(db, ids).await {
+        Ok(audiobooks) => audiobooks,
+        Err(e) => {
+            error!("Unable to get audiobook files: {}", e);
+            HashSet::new()
+        }
+    };
+    match get_excluded_file_ids(db, ids, Exclusion::Recommendations).await {
+        Ok(ids) => excluded.extend(ids),
+        Err(e) => error!("Unable to get files excluded from recommendations: {}", e),
+    }
+    // Without knowing which files are explicit, none can be queued
+    if active_clean_mode(user_db).await {
+        match get_explicit_file_ids(db, ids).await {
+            Ok(ids) => excluded.extend(ids),
+            Err(e) => {
+                error!("Unable to get explicit files: {}", e);
+                excluded.extend(ids);
+            }
+        }
+    }
+
+    excluded
+}
+
+async fn exclude_from_radio(
+    db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    requests: Vec<(i32, std::path::PathBuf)>,
+) -> Vec<(i32, std::path::PathBuf)> {
+    let ids: Vec<i32> = requests.iter().map(|(id, _)| *id).collect();
+    let excluded = excluded_from_radio(db, user_db, &ids).await;
+
+    requests
+        .into_iter()
+        .filter(|(id, _)| !excluded.contains(id))
+        .collect()
+}
+
+/// Number of tracks queued by radio mode.
+const RADIO_SIZE: usize = 30;
+
+// Over-fetched recommendations are cut down to the radio size here
+async fn apply_radio_policy(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    requests: Vec<(i32, std::path::PathBuf)>,
+    keep: &[i32],
+) -> Vec<(i32, std::path::PathBuf)> {
+    let ids: Vec<i32> = requests.iter().map(|(id, _)| *id).collect();
+
+    let user_id = active_user_id(user_db).await;
+    let mut requests = match apply_mix_policy(main_db, user_db, user_id, ids, keep).await {
+        Ok(ids) => {
+            let ids: HashSet<i32> = ids.into_iter().collect();
+            requests
+                .into_iter()
+                .filter(|(id, _)| ids.contains(id))
+                .collect()
+        }
+        Err(e) => {
+            error!("Unable to apply the mix policy: {}", e);
+            requests
+        }
+    };
+    requests.truncate(RADIO_SIZE);
+
+    requests
+}
+
 pub async fn update_playlist(
+    db: &DatabaseConnection,
     player: &Arc<Mutex<Player>>,
     requests: Vec<(i32, std::path::PathBuf)>,
 ) {
+    enqueue(db, player, requests, DuplicatePolicy::Allow).await;
+}
+
+/// Queue items of files with what the player needs of them from the
+/// database, along with the mix points of the analysed ones.
+async fn playlist_items(
+    db: &DatabaseConnection,
+    requests: Vec<(i32, std::path::PathBuf)>,
+) -> (Vec<PlaylistItem>, HashMap<i32, (f64, f64)>) {
+    let ids: Vec<i32> = requests.iter().map(|(id, _)| *id).collect();
+    let album_tracks = get_album_tracks_of_files(db, &ids)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Unable to get album tracks: {}", e);
+            HashMap::new()
+        });
+    let disc_numbers = get_disc_numbers_of_files(db, &ids)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Unable to get disc numbers: {}", e);
+            HashMap::new()
+        });
+    let replay_gains = get_replay_gains_of_files(db, &ids)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Unable to get ReplayGain tags: {}", e);
+            HashMap::new()
+        });
+    let mix_points = get_mix_points_of_files(db, &ids).await.unwrap_or_else(|e| {
+        error!("Unable to get mix points: {}", e);
+        HashMap::new()
+    });
+    let mut seek_tables = get_seek_tables_of_files(db, &ids)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Unable to get seek tables: {}", e);
+            HashMap::new()
+        });
+    let gapless = get_gapless_info_of_files(db, &ids)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Unable to get gapless info: {}", e);
+            HashMap::new()
+        });
+    // Zero when the length couldn't be read while scanning
+    let durations: HashMap<i32, Duration> = match get_files_by_ids(db, &ids).await {
+        Ok(files) => files
+            .into_iter()
+            .filter(|x| x.duration.is_finite() && x.duration > 0.)
+            .map(|x| (x.id, Duration::from_secs_f64(x.duration)))
+            .collect(),
+        Err(e) => {
+            error!("Unable to get durations: {}", e);
+            HashMap::new()
+        }
+    };
+
+    let mut items = Vec::with_capacity(requests.len());
+    for (id, path) in requests {
+        let album = album_tracks
+            .get(&id)
+            .map(|(album_id, track_number)| AlbumTrack {
+                album_id: *album_id,
+                disc_number: disc_numbers.get(&id).copied(),
+                track_number: *track_number,
+            });
+        items.push(
+            PlaylistItem::new(id, path)
+                .with_album(album)
+                .with_replay_gain(replay_gains.get(&id).copied())
+                .with_seek_table(seek_tables.remove(&id).map(Arc::new))
+                .with_trim(gapless.get(&id).map(|x| EncoderTrim {
+                    leading_frames: x.leading_frames,
+                    valid_frames: x.valid_frames,
+                }))
+                .with_duration(durations.get(&id).copied()),
+        );
+    }
+
+    (items, mix_points)
+}
+
+/// Add files to the play queue and start playing, following the policy for
+/// files already queued. Returns the IDs of the files left out.
+pub async fn enqueue(
+    db: &DatabaseConnection,
+    player: &Arc<Mutex<Player>>,
+    requests: Vec<(i32, std::path::PathBuf)>,
+    policy: DuplicatePolicy,
+) -> Vec<i32> {
+    let (items, mix_points) = playlist_items(db, requests).await;
+
     let player_guard = player.lock().await;
-    for request in requests {
-        player_guard.add_to_playlist(request.0, request.1);
+    for (id, (mix_in, mix_out)) in mix_points {
+        player_guard.set_mix_points(id, mix_in, mix_out);
     }
+    let skipped = player_guard.add_items_to_playlist(items, policy);
     player_guard.play();
+    drop(player_guard);
+
+    skipped.await.unwrap_or_default()
+}
+
+/// Replace the play queue with files of the library, in the given order.
+pub async fn replace_queue(
+    db: &DatabaseConnection,
+    lib_path: &str,
+    player: &Arc<Mutex<Player>>,
+    file_ids: &[i32],
+) {
+    player.lock().await.pause();
+    player.lock().await.clear_playlist();
+
+    let files = get_files_by_ids(db, file_ids).await;
+    let mut requests: HashMap<i32, _> = files_to_playback_request(lib_path, files)
+        .into_iter()
+        .collect();
+    let requests = file_ids
+        .iter()
+        .filter_map(|id| requests.remove(id).map(|path| (*id, path)))
+        .collect();
+
+    update_playlist(db, player, requests).await;
 }
 
 macro_rules! handle_collection_request {
@@ -105,8 +426,10 @@ macro_rules! handle_collection_request {
 
         let files = get_files_by_ids(&$main_db, &media_file_ids).await;
         let requests = files_to_playback_request(&$lib_path, files);
+        let queued_ids: Vec<i32> = requests.iter().map(|(id, _)| *id).collect();
 
-        update_playlist(&$player, requests).await;
+        update_playlist(&$main_db, &$player, requests).await;
+        queued_ids
     }};
 }
 
@@ -114,18 +437,20 @@ pub async fn play_file_request(
     main_db: Arc<DatabaseConnection>,
     lib_path: Arc<String>,
     player: Arc<Mutex<Player>>,
+    journal: Arc<OperationJournal>,
     dart_signal: DartSignal<PlayFileRequest>,
 ) -> Result<()> {
     let play_file_request = dart_signal.message;
     let file_id = play_file_request.file_id;
 
-    play_file_by_id(main_db, player, lib_path, file_id).await;
+    play_file_by_id(main_db, player, lib_path, journal, file_id).await;
 
     Ok(())
 }
 
 pub async fn recommend_and_play_request(
     main_db: Arc<MainDbConnection>,
+    user_db: Arc<DatabaseConnection>,
     recommend_db: Arc<RecommendationDbConnection>,
     lib_path: Arc<String>,
     player: Arc<Mutex<Player>>,
@@ -133,42 +458,122 @@ pub async fn recommend_and_play_request(
 ) -> Result<()> {
     let file_id = dart_signal.message.file_id;
 
-    let recommendations = match get_recommendation_by_file_id(&recommend_db, file_id, 30) {
-        Ok(recs) => recs,
-        Err(e) => {
-            error!("Error getting recommendations: {:#?}", e);
-            Vec::new()
-        }
-    };
+    // Unanalysed files get metadata-based recommendations until their
+    // analysis is done
+    let recommendations =
+        match get_recommendation_with_fallback(&main_db, &recommend_db, file_id, RADIO_SIZE * 2)
+            .await
+        {
+            Ok(recs) => recs,
+            Err(e) => {
+                error!("Error getting recommendations: {:#?}", e);
+                Vec::new()
+            }
+        };
 
-    let files = get_files_by_ids(
-        &main_db,
-        &recommendations
-            .into_iter()
-            .map(|x| x.0 as i32)
-            .collect::<Vec<i32>>(),
-    )
-    .await;
+    let files = get_files_by_ids(&main_db, &recommendations).await;
 
     let requests = files_to_playback_request(&lib_path, files);
-    update_playlist(&player, requests.clone()).await;
+    let requests = exclude_from_radio(&main_db, &user_db, requests).await;
+    let requests = apply_radio_policy(&main_db, &user_db, requests, &[file_id]).await;
+    remember_context_progress(&user_db, &player).await;
+    update_playlist(&main_db, &player, requests.clone()).await;
 
     let recommended_ids: Vec<i32> = requests.into_iter().map(|(id, _)| id).collect();
+    remember_context(&user_db, "track_mix", file_id, &recommended_ids).await;
     PlaybackRecommendation { recommended_ids }.send_signal_to_dart();
 
     Ok(())
 }
 
+pub async fn start_auto_dj_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<DatabaseConnection>,
+    lib_path: Arc<String>,
+    player: Arc<Mutex<Player>>,
+    journal: Arc<OperationJournal>,
+    dart_signal: DartSignal<StartAutoDjRequest>,
+) -> Result<()> {
+    let request = dart_signal.message;
+    let seed_id = request.seed_file_id;
+
+    let tracks = get_dj_tracks(&main_db).await?;
+    let Some(seed) = tracks.iter().find(|x| x.file_id == seed_id) else {
+        StartAutoDjResponse {
+            file_ids: Vec::new(),
+            success: false,
+            error: "The track has no tempo or key".to_string(),
+        }
+        .send_signal_to_dart();
+        return Ok(());
+    };
+
+    let ids: Vec<i32> = tracks.iter().map(|x| x.file_id).collect();
+    let excluded = excluded_from_radio(&main_db, &user_db, &ids).await;
+    let candidates: Vec<DjTrack> = tracks
+        .iter()
+        .filter(|x| !excluded.contains(&x.file_id))
+        .cloned()
+        .collect();
+
+    // Zeros keep the defaults
+    let defaults = AutoDjOptions::default();
+    let options = AutoDjOptions {
+        length: match request.length {
+            0 => defaults.length,
+            x => x as usize,
+        },
+        tempo_tolerance: if request.tempo_tolerance > 0.0 {
+            request.tempo_tolerance / 100.0
+        } else {
+            defaults.tempo_tolerance
+        },
+        energy: EnergyProgression::from(request.energy.as_str()),
+        max_energy_step: if request.max_energy_step > 0.0 {
+            request.max_energy_step
+        } else {
+            defaults.max_energy_step
+        },
+    };
+
+    let file_ids = chain_tracks(seed, &candidates, &options);
+    remember_context_progress(&user_db, &player).await;
+    let before = player.lock().await.get_playlist();
+    replace_queue(&main_db, &lib_path, &player, &file_ids).await;
+
+    remember_context(&user_db, "auto_dj", seed_id, &file_ids).await;
+    journal.record(Operation::Queue {
+        before,
+        after: file_ids.clone(),
+    });
+
+    StartAutoDjResponse {
+        file_ids,
+        success: true,
+        error: String::new(),
+    }
+    .send_signal_to_dart();
+
+    Ok(())
+}
+
 pub async fn start_playing_collection_request(
     main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
     lib_path: Arc<String>,
     player: Arc<Mutex<Player>>,
+    journal: Arc<OperationJournal>,
     dart_signal: DartSignal<StartPlayingCollectionRequest>,
 ) {
+    let context_type = dart_signal.message.r#type.clone();
+    let context_id = dart_signal.message.id;
+    remember_context_progress(&user_db, &player).await;
+
+    let before = player.lock().await.get_playlist();
     player.lock().await.pause();
     player.lock().await.clear_playlist();
 
-    match dart_signal.message.r#type.as_str() {
+    let after = match context_type.as_str() {
         "artist" => handle_collection_request!(
             main_db,
             lib_path,
@@ -190,8 +595,11 @@ pub async fn start_playing_collection_request(
             dart_signal,
             get_media_file_ids_of_playlist
         ),
-        _ => {}
-    }
+        _ => Vec::new(),
+    };
+
+    remember_context(&user_db, &context_type, context_id, &after).await;
+    journal.record(Operation::Queue { before, after });
 }
 
 pub async fn add_to_queue_collection_request(
@@ -200,34 +608,31 @@ pub async fn add_to_queue_collection_request(
     player: Arc<Mutex<Player>>,
     dart_signal: DartSignal<AddToQueueCollectionRequest>,
 ) {
-    match dart_signal.message.r#type.as_str() {
-        "artist" => handle_collection_request!(
-            main_db,
-            lib_path,
-            player,
-            dart_signal,
-            get_media_file_ids_of_artist
-        ),
-        "album" => handle_collection_request!(
-            main_db,
-            lib_path,
-            player,
-            dart_signal,
-            get_media_file_ids_of_album
-        ),
-        "playlist" => handle_collection_request!(
-            main_db,
-            lib_path,
-            player,
-            dart_signal,
-            get_media_file_ids_of_playlist
-        ),
-        _ => {}
+    let request = dart_signal.message;
+    let policy = DuplicatePolicy::from_name(&request.duplicate_policy).unwrap_or_default();
+    let media_file_ids = match request.r#type.as_str() {
+        "artist" => get_media_file_ids_of_artist(&main_db, request.id).await,
+        "album" => get_media_file_ids_of_album(&main_db, request.id).await,
+        "playlist" => get_media_file_ids_of_playlist(&main_db, request.id).await,
+        _ => Ok(vec![]),
+    }
+    .unwrap_or_default();
+
+    let files = get_files_by_ids(&main_db, &media_file_ids).await;
+    let requests = files_to_playback_request(&lib_path, files);
+    let skipped_ids = enqueue(&main_db, &player, requests, policy).await;
+
+    AddToQueueCollectionResponse {
+        r#type: request.r#type,
+        id: request.id,
+        skipped_ids,
     }
+    .send_signal_to_dart();
 }
 
 pub async fn start_roaming_collection_request(
     main_db: Arc<MainDbConnection>,
+    user_db: Arc<DatabaseConnection>,
     recommend_db: Arc<RecommendationDbConnection>,
     lib_path: Arc<String>,
     player: Arc<Mutex<Player>>,
@@ -242,7 +647,8 @@ pub async fn start_roaming_collection_request(
     };
 
     let aggregated = get_centralized_analysis_result(&main_db, media_file_ids.unwrap()).await;
-    let recommendations = get_recommendation_by_parameter(&recommend_db, aggregated, 30).unwrap();
+    let recommendations =
+        get_recommendation_by_parameter(&recommend_db, aggregated, RADIO_SIZE * 2).unwrap();
 
     let files = get_files_by_ids(
         &main_db,
@@ -254,7 +660,14 @@ pub async fn start_roaming_collection_request(
     .await;
 
     let requests = files_to_playback_request(&lib_path, files);
-    update_playlist(&player, requests).await;
+    let requests = exclude_from_radio(&main_db, &user_db, requests).await;
+    let requests = apply_radio_policy(&main_db, &user_db, requests, &[]).await;
+    let mix_ids: Vec<i32> = requests.iter().map(|(id, _)| *id).collect();
+    remember_context_progress(&user_db, &player).await;
+    update_playlist(&main_db, &player, requests).await;
+
+    let context_type = format!("{}_mix", request.r#type);
+    remember_context(&user_db, &context_type, request.id, &mix_ids).await;
 }
 
 pub async fn play_request(player: Arc<Mutex<Player>>, _: DartSignal<PlayRequest>) {
@@ -287,11 +700,106 @@ pub async fn seek_request(player: Arc<Mutex<Player>>, dart_signal: DartSignal<Se
         .seek(dart_signal.message.position_seconds)
 }
 
-pub async fn remove_request(player: Arc<Mutex<Player>>, dart_signal: DartSignal<RemoveRequest>) {
-    player
-        .lock()
-        .await
-        .remove_from_playlist(dart_signal.message.index as usize)
+pub async fn seek_to_marker_request(
+    main_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SeekToMarkerRequest>,
+) -> Result<()> {
+    let request = dart_signal.message;
+
+    let markers = get_mix_markers(&main_db, Path::new(lib_path.as_ref()), request.file_id)
+        .await?
+        .unwrap_or_default();
+    let Some(marker) = usize::try_from(request.index)
+        .ok()
+        .and_then(|x| markers.get(x))
+    else {
+        error!("No marker {} in file {}", request.index, request.file_id);
+        return Ok(());
+    };
+
+    let player = player.lock().await;
+    // The mix may have ended while the markers were read
+    if player.get_status().id != Some(request.file_id) {
+        return Ok(());
+    }
+    player.seek(marker.position);
+
+    Ok(())
+}
+
+pub async fn preview_transition_request(
+    main_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<PreviewTransitionRequest>,
+) -> Result<()> {
+    let request = dart_signal.message;
+    let (from_id, to_id) = (request.from_file_id, request.to_file_id);
+
+    let files = get_files_by_ids(&main_db, &[from_id, to_id]).await?;
+    let from_duration = files.iter().find(|x| x.id == from_id).map(|x| x.duration);
+    let (items, mix_points) =
+        playlist_items(&main_db, files_to_playback_request(&lib_path, Ok(files))).await;
+    let from = items.iter().find(|x| x.id == from_id).cloned();
+    let to = items.iter().find(|x| x.id == to_id).cloned();
+
+    let (Some(from), Some(to), Some(from_duration)) = (from, to, from_duration) else {
+        error!(
+            "Unable to preview the transition from {} to {}: file not found",
+            from_id, to_id
+        );
+        PreviewTransitionResponse {
+            success: false,
+            crossfade_seconds: 0.0,
+        }
+        .send_signal_to_dart();
+        return Ok(());
+    };
+
+    let points = |id| {
+        mix_points
+            .get(&id)
+            .and_then(|(mix_in, mix_out)| MixPoints::from_seconds(*mix_in, *mix_out))
+    };
+    let preview = TransitionPreview {
+        from,
+        to,
+        from_duration: Duration::from_secs_f64(from_duration.max(0.0)),
+        from_points: points(from_id),
+        to_points: points(to_id),
+        length: preview_length(request.seconds),
+    };
+
+    let done = player.lock().await.preview_transition(preview);
+    let lead = done.await.ok().flatten();
+
+    PreviewTransitionResponse {
+        success: lead.is_some(),
+        crossfade_seconds: lead.map(|x| x.as_secs_f64()).unwrap_or_default(),
+    }
+    .send_signal_to_dart();
+
+    Ok(())
+}
+
+pub async fn remove_request(
+    player: Arc<Mutex<Player>>,
+    journal: Arc<OperationJournal>,
+    dart_signal: DartSignal<RemoveRequest>,
+) {
+    let index = dart_signal.message.index as usize;
+    let player = player.lock().await;
+
+    let before = player.get_playlist();
+    if index < before.len() {
+        let mut after = before.clone();
+        after.remove(index);
+        journal.record(Operation::Queue { before, after });
+    }
+
+    player.remove_from_playlist(index)
 }
 
 pub async fn move_playlist_item_request(
@@ -307,3 +815,292 @@ pub async fn move_playlist_item_request(
         .await
         .move_playlist_item(old_index.try_into().unwrap(), new_index.try_into().unwrap());
 }
+
+pub async fn shuffle_playlist_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<ShufflePlaylistRequest>,
+) {
+    let status = player.lock().await.get_status();
+
+    let order = match dart_signal.message.mode.as_str() {
+        "album" => album_shuffle(&main_db, &status.playlist).await,
+        "artist" => artist_shuffle(&main_db, &status.playlist).await,
+        mode => {
+            error!("Unknown shuffle mode: {}", mode);
+            return;
+        }
+    };
+
+    let mut order = match order {
+        Ok(order) => order,
+        Err(e) => {
+            error!("Unable to shuffle playlist: {}", e);
+            return;
+        }
+    };
+
+    // Keep the current track playing and queue everything else after it
+    if let Some(current) = status.index {
+        if let Some(position) = order.iter().position(|x| *x == current) {
+            order.rotate_left(position);
+        }
+    }
+
+    // Often skipped files are shuffled towards the end, the current track
+    // stays first
+    match get_skip_penalties(&user_db, &status.playlist).await {
+        Ok(penalties) if !penalties.is_empty() => {
+            let current = status.index.filter(|x| order.first() == Some(x));
+            order = down_rank(order, |index| {
+                if Some(*index) == current {
+                    return 0.;
+                }
+                status
+                    .playlist
+                    .get(*index)
+                    .and_then(|id| penalties.get(id))
+                    .copied()
+                    .unwrap_or(0.)
+            });
+        }
+        Ok(_) => {}
+        Err(e) => error!("Unable to get skip penalties: {}", e),
+    }
+
+    // Audiobooks are listened in order and files flagged to never shuffle
+    // were kept out by the user, both stay where they were queued
+    let mut excluded = match get_audiobook_file_ids(&main_db, &status.playlist).await {
+        Ok(audiobooks) => audiobooks,
+        Err(e) => {
+            error!("Unable to get audiobook files: {}", e);
+            HashSet::new()
+        }
+    };
+    match get_excluded_file_ids(&main_db, &status.playlist, Exclusion::Shuffle).await {
+        Ok(ids) => excluded.extend(ids),
+        Err(e) => error!("Unable to get files excluded from shuffling: {}", e),
+    }
+    if !excluded.is_empty() {
+        let pinned: HashSet<usize> = status
+            .playlist
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| excluded.contains(id))
+            .map(|(index, _)| index)
+            .collect();
+        order = keep_in_place(order, &pinned);
+    }
+
+    // Clean mode skips explicit tracks by taking them out of the shuffled
+    // queue, the current track keeps playing. The queue isn't shuffled when
+    // they can't be found
+    let mut explicit_positions = Vec::new();
+    if active_clean_mode(&user_db).await {
+        match get_explicit_file_ids(&main_db, &status.playlist).await {
+            Ok(explicit) => {
+                explicit_positions = order
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, index)| {
+                        Some(**index) != status.index
+                            && explicit.contains(&status.playlist[**index])
+                    })
+                    .map(|(position, _)| position)
+                    .collect();
+            }
+            Err(e) => {
+                error!("Unable to get explicit files: {}", e);
+                return;
+            }
+        }
+    }
+
+    let player = player.lock().await;
+    player.reorder_playlist(order);
+    for position in explicit_positions.into_iter().rev() {
+        player.remove_from_playlist(position);
+    }
+}
+
+pub async fn set_crossfade_request(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SetCrossfadeRequest>,
+) {
+    let seconds = dart_signal.message.seconds;
+    player.lock().await.set_crossfade(seconds);
+
+    if let Err(e) = set_setting(user_db.as_ref(), CROSSFADE_KEY, seconds.to_string()).await {
+        error!("Unable to save crossfade setting: {}", e);
+    }
+}
+
+pub async fn set_preamp_request(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SetPreampRequest>,
+) {
+    let db = clamp_gain(dart_signal.message.db);
+    player.lock().await.set_preamp(db);
+
+    if let Err(e) = set_setting(user_db.as_ref(), PREAMP_KEY, db.to_string()).await {
+        error!("Unable to save pre-amp setting: {}", e);
+    }
+}
+
+pub async fn set_limiter_request(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SetLimiterRequest>,
+) {
+    let enabled = dart_signal.message.enabled;
+    player.lock().await.set_limiter(enabled);
+
+    if let Err(e) = set_setting(user_db.as_ref(), LIMITER_KEY, enabled.to_string()).await {
+        error!("Unable to save limiter setting: {}", e);
+    }
+}
+
+pub async fn set_mono_request(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SetMonoRequest>,
+) {
+    let enabled = dart_signal.message.enabled;
+    player.lock().await.set_mono(enabled);
+
+    if let Err(e) = set_setting(user_db.as_ref(), MONO_KEY, enabled.to_string()).await {
+        error!("Unable to save mono setting: {}", e);
+    }
+}
+
+pub async fn set_night_mode_request(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SetNightModeRequest>,
+) {
+    let enabled = dart_signal.message.enabled;
+    player.lock().await.set_night_mode(enabled);
+
+    if let Err(e) = set_setting(user_db.as_ref(), NIGHT_MODE_KEY, enabled.to_string()).await {
+        error!("Unable to save night mode setting: {}", e);
+    }
+
+    remember_device_profile(&user_db, &player, None).await;
+}
+
+pub async fn set_crossfeed_request(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SetCrossfeedRequest>,
+) {
+    let enabled = dart_signal.message.enabled;
+    player.lock().await.set_crossfeed(enabled);
+
+    if let Err(e) = set_setting(user_db.as_ref(), CROSSFEED_KEY, enabled.to_string()).await {
+        error!("Unable to save crossfeed setting: {}", e);
+    }
+
+    remember_device_profile(&user_db, &player, None).await;
+}
+
+pub async fn set_progress_interval_request(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SetProgressIntervalRequest>,
+) {
+    let milliseconds = dart_signal.message.milliseconds as u64;
+    player
+        .lock()
+        .await
+        .set_progress_interval(Duration::from_millis(milliseconds));
+
+    if let Err(e) = set_setting(
+        user_db.as_ref(),
+        PROGRESS_INTERVAL_KEY,
+        milliseconds.to_string(),
+    )
+    .await
+    {
+        error!("Unable to save progress interval setting: {}", e);
+    }
+}
+
+pub async fn set_coarse_progress_request(
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SetCoarseProgressRequest>,
+) {
+    player
+        .lock()
+        .await
+        .set_coarse_progress(dart_signal.message.enabled);
+}
+
+pub async fn set_track_gain_offset_request(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SetTrackGainOffsetRequest>,
+) {
+    let offset = clamp_gain(dart_signal.message.offset);
+    let player = player.lock().await;
+
+    let Some(id) = player.get_status().id else {
+        error!("Unable to set gain offset: no track is playing");
+        SetTrackGainOffsetResponse {
+            id: 0,
+            offset,
+            success: false,
+        }
+        .send_signal_to_dart();
+        return;
+    };
+
+    player.set_gain_offset(id, offset);
+
+    let success = match set_gain_offset(&user_db, id, offset).await {
+        Ok(_) => true,
+        Err(e) => {
+            error!("Unable to save gain offset of {}: {}", id, e);
+            false
+        }
+    };
+
+    SetTrackGainOffsetResponse {
+        id,
+        offset,
+        success,
+    }
+    .send_signal_to_dart();
+}
+
+pub async fn set_volume_request(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SetVolumeRequest>,
+) {
+    let volume = dart_signal.message.volume;
+    player.lock().await.set_volume(volume);
+
+    remember_device_profile(&user_db, &player, Some(volume)).await;
+}
+
+pub async fn set_playback_mode_request(
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SetPlaybackModeRequest>,
+) {
+    player
+        .lock()
+        .await
+        .set_playback_mode(dart_signal.message.mode.into())
+}
+
+pub async fn get_playback_state_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    _dart_signal: DartSignal<GetPlaybackStateRequest>,
+) {
+    send_playback_state_snapshot(&main_db, &user_db, &player).await;
+}
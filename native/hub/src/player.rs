@@ -1,20 +1,108 @@
-use log::{debug, error, info};
 use sea_orm::DatabaseConnection;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::Mutex;
 use tokio::task;
+use tokio::time::interval;
+use tracing::{debug, error, info};
 
+use database::actions::audiobooks::get_audiobook_file_ids;
 use database::actions::metadata::{
     get_metadata_summary_by_file_id, get_metadata_summary_by_file_ids, MetadataSummary,
 };
+use database::actions::play_history::{is_played, log_play};
+use database::actions::query_cache::QueryCache;
+use database::actions::remote::{from_queue_id, get_remote_summaries};
+use database::actions::skips::{is_skipped, log_skip};
+use database::actions::taste::{get_taste_profile, rebuild_taste_profile, update_taste_profile};
 use database::connection::MainDbConnection;
-use playback::player::{Player, PlaylistStatus};
+use playback::coalesce::{CoalescedSignal, TickCoalescer, PLAYBACK_TICK_INTERVAL};
+use playback::player::{PlaybackState, Player, PlayerStatus, PlaylistStatus};
+use playback::sequence::timestamp_millis;
+use playback::PlayerCommand;
 
 use crate::common::Result;
 use crate::messages;
+use crate::remote::scrobble_remote_file;
+use crate::users::active_user_id;
+
+/// Get the metadata of queued items, remote files included.
+///
+/// Files of the library are read from `main_db`, remote files from `user_db`.
+pub async fn get_queue_summaries(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    ids: Vec<i32>,
+) -> std::result::Result<Vec<MetadataSummary>, sea_orm::DbErr> {
+    let (remote, local): (Vec<i32>, Vec<i32>) =
+        ids.into_iter().partition(|x| from_queue_id(*x).is_some());
+
+    let mut summaries = if local.is_empty() {
+        vec![]
+    } else {
+        get_metadata_summary_by_file_ids(main_db, local).await?
+    };
+    summaries.extend(get_remote_summaries(user_db, &remote).await?);
+
+    Ok(summaries)
+}
+
+async fn get_queue_summary(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    id: i32,
+) -> Result<MetadataSummary> {
+    if from_queue_id(id).is_none() {
+        return Ok(get_metadata_summary_by_file_id(main_db, id).await?);
+    }
+
+    get_remote_summaries(user_db, &[id])
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Remote file not found for queue ID: {}", id).into())
+}
+
+// Metadata of the track playing, read again only when the track changes
+#[derive(Default)]
+pub(crate) struct CurrentMeta {
+    id: Option<i32>,
+    meta: Option<MetadataSummary>,
+}
+
+impl CurrentMeta {
+    pub(crate) async fn get(
+        &mut self,
+        main_db: &DatabaseConnection,
+        user_db: &DatabaseConnection,
+        id: Option<i32>,
+    ) -> MetadataSummary {
+        let Some(id) = id else {
+            // If the index is None, send empty metadata
+            self.id = None;
+            return MetadataSummary::default();
+        };
+
+        if self.id != Some(id) {
+            // Update the cached metadata if the index has changed
+            self.meta = match get_queue_summary(main_db, user_db, id).await {
+                Ok(metadata) => Some(metadata),
+                Err(e) => {
+                    // Print the error if get_metadata_summary_by_file_id returns an error
+                    error!("Error fetching metadata: {:?}", e);
+                    None
+                }
+            };
+            self.id = Some(id);
+        }
+        self.meta.clone().unwrap_or_default()
+    }
+}
 
 pub async fn initialize_player(
     main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
     player: Arc<Mutex<Player>>,
 ) -> Result<()> {
     let mut status_receiver = player.lock().await.subscribe_status();
@@ -24,62 +112,61 @@ pub async fn initialize_player(
     // Clone main_db for each task
     let main_db_for_status = Arc::clone(&main_db);
     let main_db_for_playlist = Arc::clone(&main_db);
+    let user_db_for_status = Arc::clone(&user_db);
 
     info!("Initializing event listeners");
     task::spawn(async move {
         let main_db = Arc::clone(&main_db_for_status);
-        let mut cached_meta: Option<MetadataSummary> = None;
-        let mut last_id: Option<i32> = None;
-
-        while let Ok(status) = status_receiver.recv().await {
-            debug!("Player status updated: {:?}", status);
-
-            let meta = match status.id {
-                Some(id) => {
-                    if last_id != Some(id) {
-                        // Update the cached metadata if the index has changed
-                        match get_metadata_summary_by_file_id(&main_db, id).await {
-                            Ok(metadata) => {
-                                cached_meta = Some(metadata);
-                                last_id = Some(id);
-                            }
-                            Err(e) => {
-                                // Print the error if get_metadata_summary_by_file_id returns an error
-                                error!("Error fetching metadata: {:?}", e);
-                                cached_meta = None;
-                                last_id = Some(id);
-                            }
-                        }
-                    }
-                    cached_meta.clone().unwrap_or_default()
-                }
-                none => {
-                    // If the index is None, send empty metadata
-                    last_id = none;
-                    MetadataSummary::default()
-                }
-            };
+        let user_db = Arc::clone(&user_db_for_status);
+        let mut current_meta = CurrentMeta::default();
+        // Progress and visualizer frames cross the bridge together
+        let mut coalescer = TickCoalescer::default();
+        let mut ticks = interval(PLAYBACK_TICK_INTERVAL);
 
-            let position = status.position;
-            let duration = meta.duration;
-            let progress_percentage = if duration == 0. {
-                0.
-            } else {
-                position.as_secs_f32() / (duration as f32)
+        loop {
+            let signals = tokio::select! {
+                status = status_receiver.recv() => match status {
+                    Ok(status) => {
+                        debug!("Player status updated: {:?}", status);
+                        coalescer.push_status(status)
+                    }
+                    // The UI notices the gap in the sequence and asks for a snapshot
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                frame = realtime_fft_receiver.recv() => {
+                    match frame {
+                        Ok(frame) => coalescer.push_fft(frame),
+                        Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => break,
+                    }
+                    continue;
+                },
+                _ = ticks.tick() => coalescer
+                    .take_tick()
+                    .map(CoalescedSignal::Tick)
+                    .into_iter()
+                    .collect(),
             };
 
-            messages::playback::PlaybackStatus {
-                state: status.state.to_string(),
-                progress_seconds: position.as_secs_f32(),
-                progress_percentage,
-                artist: meta.artist.clone(),
-                album: meta.album.clone(),
-                title: meta.title.clone(),
-                duration: meta.duration,
-                id: status.id.unwrap_or(0).try_into().unwrap(),
-                index: status.index.unwrap_or(0).try_into().unwrap(),
+            for signal in signals {
+                match signal {
+                    CoalescedSignal::Status(status) => {
+                        let meta = current_meta.get(&main_db, &user_db, status.id).await;
+                        build_playback_status(&status, &meta).send_signal_to_dart();
+                    }
+                    CoalescedSignal::Tick(tick) => {
+                        let status = match &tick.status {
+                            Some(status) => {
+                                let meta = current_meta.get(&main_db, &user_db, status.id).await;
+                                Some(build_playback_status(status, &meta))
+                            }
+                            None => None,
+                        };
+                        send_playback_tick(tick, status);
+                    }
+                }
             }
-            .send_signal_to_dart();
         }
     });
 
@@ -87,37 +174,99 @@ pub async fn initialize_player(
         let main_db = Arc::clone(&main_db_for_playlist);
 
         while let Ok(playlist) = playlist_receiver.recv().await {
-            send_playlist_update(&main_db, &playlist).await;
-        }
-    });
-
-    task::spawn(async move {
-        while let Ok(value) = realtime_fft_receiver.recv().await {
-            send_realtime_fft(value).await;
+            send_playlist_update(&main_db, &user_db, &playlist).await;
         }
     });
 
     Ok(())
 }
 
-pub async fn send_playlist_update(db: &DatabaseConnection, playlist: &PlaylistStatus) {
-    use messages::playback::*;
+/// The command toggling between playing and paused.
+pub fn play_pause_command(status: &PlayerStatus) -> PlayerCommand {
+    match status.state {
+        PlaybackState::Playing => PlayerCommand::Pause,
+        _ => PlayerCommand::Play,
+    }
+}
+
+pub fn build_playback_status(
+    status: &PlayerStatus,
+    meta: &MetadataSummary,
+) -> messages::playback::PlaybackStatus {
+    let position = status.position;
+    let duration = meta.duration;
+    let progress_percentage = if duration == 0. {
+        0.
+    } else {
+        position.as_secs_f32() / (duration as f32)
+    };
 
-    let file_ids: Vec<i32> = playlist.items.clone();
+    messages::playback::PlaybackStatus {
+        state: status.state.to_string(),
+        progress_seconds: position.as_secs_f32(),
+        progress_percentage,
+        artist: meta.artist.clone(),
+        album: meta.album.clone(),
+        title: meta.title.clone(),
+        duration: meta.duration,
+        id: status.id.unwrap_or(0).try_into().unwrap(),
+        index: status.index.unwrap_or(0).try_into().unwrap(),
+        volume: status.volume,
+        playback_mode: status.playback_mode.into(),
+        offline_ids: status.offline.clone(),
+        missing_ids: status.missing.clone(),
+        limiter_reduction: status.limiter_reduction,
+        sequence: status.sequence,
+        timestamp: timestamp_millis(status.timestamp),
+    }
+}
+
+pub async fn get_playlist_items(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    file_ids: Vec<i32>,
+    played: &[bool],
+) -> std::result::Result<Vec<messages::playback::PlaylistItem>, sea_orm::DbErr> {
+    let summaries = get_queue_summaries(main_db, user_db, file_ids.clone()).await?;
+    let summaries: HashMap<i32, MetadataSummary> =
+        summaries.into_iter().map(|x| (x.id, x)).collect();
 
-    match get_metadata_summary_by_file_ids(db, file_ids).await {
-        Ok(summaries) => {
-            let items = summaries
-                .into_iter()
-                .map(|item| PlaylistItem {
+    // Keep the order of the queue, the database doesn't guarantee it. The same
+    // file may be queued more than once.
+    Ok(file_ids
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, id)| {
+            summaries
+                .get(&id)
+                .map(|item| messages::playback::PlaylistItem {
                     id: item.id,
-                    artist: item.artist,
-                    album: item.album,
-                    title: item.title,
+                    artist: item.artist.clone(),
+                    album: item.album.clone(),
+                    title: item.title.clone(),
                     duration: item.duration,
+                    played: played.get(index).copied().unwrap_or_default(),
                 })
-                .collect();
-            PlaylistUpdate { items }.send_signal_to_dart(); // GENERATED
+        })
+        .collect())
+}
+
+pub async fn send_playlist_update(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    playlist: &PlaylistStatus,
+) {
+    use messages::playback::*;
+
+    match get_playlist_items(main_db, user_db, playlist.items.clone(), &playlist.played).await {
+        Ok(items) => {
+            PlaylistUpdate {
+                items,
+                sequence: playlist.sequence,
+                timestamp: timestamp_millis(playlist.timestamp),
+                history_boundary: playlist.history_boundary as u32,
+            }
+            .send_signal_to_dart(); // GENERATED
         }
         Err(e) => {
             error!("Error happened while updating playlist: {:?}", e)
@@ -125,8 +274,193 @@ pub async fn send_playlist_update(db: &DatabaseConnection, playlist: &PlaylistSt
     }
 }
 
-pub async fn send_realtime_fft(value: Vec<f32>) {
+pub async fn send_playback_state_snapshot(
+    main_db: &DatabaseConnection,
+    user_db: &DatabaseConnection,
+    player: &Arc<Mutex<Player>>,
+) {
+    use messages::playback::*;
+
+    let status = player.lock().await.get_status();
+
+    let meta = match status.id {
+        Some(id) => match get_queue_summary(main_db, user_db, id).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                error!("Error fetching metadata: {:?}", e);
+                MetadataSummary::default()
+            }
+        },
+        None => MetadataSummary::default(),
+    };
+
+    match get_playlist_items(main_db, user_db, status.playlist.clone(), &status.played).await {
+        Ok(items) => {
+            PlaybackStateSnapshot {
+                status: Some(build_playback_status(&status, &meta)),
+                items,
+                history_boundary: status.history_boundary as u32,
+            }
+            .send_signal_to_dart(); // GENERATED
+        }
+        Err(e) => {
+            error!("Error happened while building playback snapshot: {:?}", e)
+        }
+    }
+}
+
+fn send_playback_tick(
+    tick: playback::coalesce::PlaybackTick,
+    status: Option<messages::playback::PlaybackStatus>,
+) {
     use messages::playback::*;
 
-    RealtimeFft { value }.send_signal_to_dart(); // GENERATED
+    PlaybackTick {
+        since_sequence: tick.since_sequence,
+        status,
+        frames: tick
+            .frames
+            .into_iter()
+            .map(|x| RealtimeFft {
+                value: x.data,
+                sequence: x.sequence,
+                timestamp: timestamp_millis(x.timestamp),
+            })
+            .collect(),
+    }
+    .send_signal_to_dart(); // GENERATED
+}
+
+// The track currently followed to count it as played
+struct CurrentPlay {
+    id: i32,
+    // The profile active when the track started, the play counts for it
+    user_id: i32,
+    duration: f64,
+    // Furthest position reached since the track started
+    position: f64,
+    // Chapters keep their position instead, they aren't plays or skips
+    audiobook: bool,
+}
+
+async fn finish_play(
+    main_db: &MainDbConnection,
+    user_db: &MainDbConnection,
+    query_cache: &QueryCache,
+    play: &CurrentPlay,
+) {
+    if play.audiobook || !is_played(play.position, play.duration) {
+        return;
+    }
+
+    let progress = if play.duration > 0. {
+        play.position / play.duration
+    } else {
+        1.
+    };
+    // Remote files are counted by their server, not in the local history
+    if let Some(remote_file_id) = from_queue_id(play.id) {
+        scrobble_remote_file(user_db, remote_file_id).await;
+        return;
+    }
+
+    match log_play(user_db, play.user_id, play.id, progress).await {
+        // The play count and last play of its details changed
+        Ok(_) => query_cache.invalidate_files(&[play.id]),
+        Err(e) => error!("Failed to log play of {}: {}", play.id, e),
+    }
+    if let Err(e) = update_taste_profile(main_db, user_db, play.user_id, play.id, progress).await {
+        error!("Failed to update the taste profile with {}: {}", play.id, e);
+    }
+}
+
+async fn remember_skip(user_db: &MainDbConnection, play: &CurrentPlay) {
+    // The duration is unknown when its metadata couldn't be read
+    if play.duration == f64::MAX
+        || play.audiobook
+        || from_queue_id(play.id).is_some()
+        || !is_skipped(play.position, play.duration)
+    {
+        return;
+    }
+
+    if let Err(e) = log_skip(user_db, play.id).await {
+        error!("Failed to log skip of {}: {}", play.id, e);
+    }
+}
+
+/// Follow the player and log every track that was listened to long enough
+/// to count as played, and every track skipped early.
+pub async fn remember_plays(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    query_cache: Arc<QueryCache>,
+    player: Arc<Mutex<Player>>,
+) {
+    let mut status_receiver = player.lock().await.subscribe_status();
+    let mut current: Option<CurrentPlay> = None;
+
+    // Libraries played before taste profiles were kept start from their history
+    let user_id = active_user_id(&user_db).await;
+    match get_taste_profile(&user_db, user_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            if let Err(e) = rebuild_taste_profile(&main_db, &user_db, user_id).await {
+                error!("Failed to build the taste profile: {}", e);
+            }
+        }
+        Err(e) => error!("Failed to read the taste profile: {}", e),
+    }
+
+    while let Ok(status) = status_receiver.recv().await {
+        let position = status.position.as_secs_f64();
+
+        // A track played again from the start, e.g. while repeating one track,
+        // counts as a new play
+        let restarted = current.as_ref().is_some_and(|x| {
+            Some(x.id) == status.id && position + 1. < x.position && position < 1.
+        });
+        if restarted || current.as_ref().map(|x| x.id) != status.id {
+            if let Some(play) = current.take() {
+                finish_play(&main_db, &user_db, &query_cache, &play).await;
+                // Moving on to another track early is a skip, stopping isn't
+                if !restarted && status.id.is_some() {
+                    remember_skip(&user_db, &play).await;
+                }
+            }
+
+            let Some(id) = status.id else {
+                continue;
+            };
+            let duration = match get_queue_summary(&main_db, &user_db, id).await {
+                Ok(meta) => meta.duration,
+                Err(_) => f64::MAX,
+            };
+            let audiobook = match get_audiobook_file_ids(&main_db, &[id]).await {
+                Ok(audiobooks) => audiobooks.contains(&id),
+                Err(e) => {
+                    error!("Unable to check whether {} is an audiobook: {}", id, e);
+                    false
+                }
+            };
+            current = Some(CurrentPlay {
+                id,
+                user_id: active_user_id(&user_db).await,
+                duration,
+                position,
+                audiobook,
+            });
+            continue;
+        }
+
+        if let Some(play) = current.as_mut() {
+            play.position = play.position.max(position);
+        }
+
+        if matches!(status.state, PlaybackState::Stopped) {
+            if let Some(play) = current.take() {
+                finish_play(&main_db, &user_db, &query_cache, &play).await;
+            }
+        }
+    }
 }
@@ -1,9 +1,13 @@
-use log::{debug, error};
 use rinf::DartSignal;
+use std::collections::HashSet;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tracing::{debug, error, warn};
 
 use database::actions::cover_art::get_magic_cover_art_id;
+use database::actions::cover_art::sync_all_playlist_cover_arts;
+use database::actions::cover_art::sync_playlist_cover_art;
+use database::actions::journal::OperationJournal;
 use database::actions::library::get_playlist_cover_ids;
 use database::actions::playlists::add_item_to_playlist;
 use database::actions::playlists::add_media_file_to_playlist;
@@ -11,16 +15,24 @@ use database::actions::playlists::check_items_in_playlist;
 use database::actions::playlists::create_playlist;
 use database::actions::playlists::get_all_playlists;
 use database::actions::playlists::get_playlist_by_id;
+use database::actions::playlists::get_playlist_items;
 use database::actions::playlists::get_playlists_by_ids;
 use database::actions::playlists::get_playlists_groups;
+use database::actions::playlists::get_playlists_of_media_files;
 use database::actions::playlists::get_unique_playlist_groups;
 use database::actions::playlists::reorder_playlist_item_position;
 use database::actions::playlists::update_playlist;
+use database::actions::playlists::DuplicatePolicy;
+use database::actions::query_cache::QueryCache;
 use database::actions::utils::create_count_by_first_letter;
 use database::connection::MainDbConnection;
 use database::connection::SearchDbConnection;
 use database::entities::playlists;
+use playback::player::Player;
 
+use crate::cover_art::attach_cover_blurhashes;
+use crate::journal::record_playlist_edit;
+use crate::library_manage::LibraryMode;
 use crate::messages::playlist::AddItemToPlaylistRequest;
 use crate::messages::playlist::AddItemToPlaylistResponse;
 use crate::messages::playlist::AddMediaFileToPlaylistRequest;
@@ -29,6 +41,7 @@ use crate::messages::playlist::CheckItemsInPlaylistRequest;
 use crate::messages::playlist::CheckItemsInPlaylistResponse;
 use crate::messages::playlist::CreatePlaylistRequest;
 use crate::messages::playlist::CreatePlaylistResponse;
+use crate::messages::playlist::FetchPlaylistMembershipsRequest;
 use crate::messages::playlist::FetchPlaylistsGroupSummaryRequest;
 use crate::messages::playlist::FetchPlaylistsGroupsRequest;
 use crate::messages::playlist::GetPlaylistByIdRequest;
@@ -37,6 +50,8 @@ use crate::messages::playlist::GetUniquePlaylistGroupsRequest;
 use crate::messages::playlist::GetUniquePlaylistGroupsResponse;
 use crate::messages::playlist::Playlist;
 use crate::messages::playlist::PlaylistGroupSummaryResponse;
+use crate::messages::playlist::PlaylistMembership;
+use crate::messages::playlist::PlaylistMembershipsResponse;
 use crate::messages::playlist::PlaylistsGroup;
 use crate::messages::playlist::PlaylistsGroupSummary;
 use crate::messages::playlist::PlaylistsGroups;
@@ -50,15 +65,32 @@ use crate::FetchPlaylistsByIdsRequest;
 use crate::FetchPlaylistsByIdsResponse;
 use crate::PlaylistWithoutCoverIds;
 
+/// Regenerate the mosaic of a playlist after its items changed.
+pub async fn sync_playlist_mosaic(main_db: &MainDbConnection, lib_path: &str, playlist_id: i32) {
+    if let Err(e) = sync_playlist_cover_art(main_db, lib_path, playlist_id).await {
+        error!(
+            "Unable to update the mosaic of playlist {}: {}",
+            playlist_id, e
+        );
+    }
+}
+
+/// Regenerate the mosaics of every playlist after files of the library changed.
+pub async fn sync_playlist_mosaics(main_db: &MainDbConnection, lib_path: &str) {
+    if let Err(e) = sync_all_playlist_cover_arts(main_db, lib_path).await {
+        error!("Unable to update the mosaics of playlists: {}", e);
+    }
+}
+
 pub async fn fetch_playlists_group_summary_request(
-    main_db: Arc<MainDbConnection>,
+    reader_db: Arc<MainDbConnection>,
     _dart_signal: DartSignal<FetchPlaylistsGroupSummaryRequest>,
 ) {
     debug!("Requesting summary group");
 
     let count_playlists = create_count_by_first_letter::<playlists::Entity>();
 
-    match count_playlists(&main_db).await {
+    match count_playlists(&reader_db).await {
         Ok(entry) => {
             let playlists_groups = entry
                 .into_iter()
@@ -77,16 +109,16 @@ pub async fn fetch_playlists_group_summary_request(
 }
 
 pub async fn fetch_playlists_groups_request(
-    main_db: Arc<MainDbConnection>,
+    reader_db: Arc<MainDbConnection>,
     dart_signal: DartSignal<FetchPlaylistsGroupsRequest>,
 ) {
     let request = dart_signal.message;
 
     debug!("Requesting playlists groups");
 
-    match get_playlists_groups(&main_db, request.group_titles).await {
+    match get_playlists_groups(&reader_db, request.group_titles).await {
         Ok(entry) => {
-            PlaylistsGroups {
+            let mut response = PlaylistsGroups {
                 groups: entry
                     .into_iter()
                     .map(|x| PlaylistsGroup {
@@ -99,12 +131,22 @@ pub async fn fetch_playlists_groups_request(
                                 name: x.0.name,
                                 group: x.0.group,
                                 cover_ids: x.1.into_iter().collect(),
+                                cover_blurhashes: Vec::new(),
+                                cover_art_id: x.0.cover_art_id.unwrap_or(-1),
                             })
                             .collect(),
                     })
                     .collect(),
-            }
-            .send_signal_to_dart();
+            };
+            attach_cover_blurhashes(
+                &reader_db,
+                response
+                    .groups
+                    .iter_mut()
+                    .flat_map(|x| x.playlists.iter_mut()),
+            )
+            .await;
+            response.send_signal_to_dart();
         }
         Err(e) => {
             error!("Failed to fetch playlists groups: {}", e);
@@ -113,25 +155,26 @@ pub async fn fetch_playlists_groups_request(
 }
 
 pub async fn fetch_playlists_by_ids_request(
-    main_db: Arc<MainDbConnection>,
+    reader_db: Arc<MainDbConnection>,
     dart_signal: DartSignal<FetchPlaylistsByIdsRequest>,
 ) {
     let request = dart_signal.message;
 
     debug!("Requesting playlists: {:#?}", request.ids);
 
-    match get_playlists_by_ids(&main_db, &request.ids).await {
+    match get_playlists_by_ids(&reader_db, &request.ids).await {
         Ok(items) => {
-            let magic_cover_id = get_magic_cover_art_id(&main_db).await.unwrap_or(-1);
-            let covers = get_playlist_cover_ids(&main_db, &items).await.unwrap();
+            let magic_cover_id = get_magic_cover_art_id(&reader_db).await.unwrap_or(-1);
+            let covers = get_playlist_cover_ids(&reader_db, &items).await.unwrap();
 
-            FetchPlaylistsByIdsResponse {
+            let mut response = FetchPlaylistsByIdsResponse {
                 result: items
                     .into_iter()
                     .map(|x| Playlist {
                         id: x.id,
                         name: x.name,
                         group: x.group,
+                        cover_art_id: x.cover_art_id.unwrap_or(-1),
                         cover_ids: covers
                             .get(&x.id)
                             .cloned()
@@ -139,10 +182,12 @@ pub async fn fetch_playlists_by_ids_request(
                             .into_iter()
                             .filter(|x| *x != magic_cover_id)
                             .collect(),
+                        cover_blurhashes: Vec::new(),
                     })
                     .collect(),
-            }
-            .send_signal_to_dart();
+            };
+            attach_cover_blurhashes(&reader_db, response.result.iter_mut()).await;
+            response.send_signal_to_dart();
         }
         Err(e) => {
             error!("Failed to fetch albums groups: {}", e);
@@ -151,12 +196,12 @@ pub async fn fetch_playlists_by_ids_request(
 }
 
 pub async fn fetch_all_playlists_request(
-    main_db: Arc<MainDbConnection>,
+    reader_db: Arc<MainDbConnection>,
     _dart_signal: DartSignal<FetchAllPlaylistsRequest>,
 ) {
     debug!("Fetching all playlists");
 
-    match get_all_playlists(&main_db).await {
+    match get_all_playlists(&reader_db).await {
         Ok(playlists) => {
             FetchAllPlaylistsResponse {
                 playlists: playlists
@@ -179,9 +224,17 @@ pub async fn fetch_all_playlists_request(
 pub async fn create_playlist_request(
     main_db: Arc<MainDbConnection>,
     search_db: Arc<Mutex<SearchDbConnection>>,
+    lib_mode: Arc<LibraryMode>,
     dart_signal: DartSignal<CreatePlaylistRequest>,
 ) {
     let request = dart_signal.message;
+    if lib_mode.is_read_only() {
+        warn!(
+            "Library is read-only, not creating playlist {}",
+            request.name
+        );
+        return;
+    }
 
     debug!(
         "Creating playlist: name={}, group={}",
@@ -211,9 +264,18 @@ pub async fn create_playlist_request(
 pub async fn update_playlist_request(
     main_db: Arc<MainDbConnection>,
     search_db: Arc<Mutex<SearchDbConnection>>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
     dart_signal: DartSignal<UpdatePlaylistRequest>,
 ) {
     let request = dart_signal.message;
+    if lib_mode.is_read_only() {
+        warn!(
+            "Library is read-only, not updating playlist {}",
+            request.playlist_id
+        );
+        return;
+    }
 
     debug!(
         "Updating playlist: id={}, name={:?}, group={:?}",
@@ -232,6 +294,7 @@ pub async fn update_playlist_request(
     .await
     {
         Ok(playlist) => {
+            query_cache.invalidate_playlists();
             UpdatePlaylistResponse {
                 playlist: Some(PlaylistWithoutCoverIds {
                     id: playlist.id,
@@ -270,66 +333,164 @@ pub async fn check_items_in_playlist_request(
 
 pub async fn add_item_to_playlist_request(
     main_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    journal: Arc<OperationJournal>,
     dart_signal: DartSignal<AddItemToPlaylistRequest>,
 ) {
     let request = dart_signal.message;
+    if lib_mode.is_read_only() {
+        warn!(
+            "Library is read-only, not editing playlist {}",
+            request.playlist_id
+        );
+        AddItemToPlaylistResponse {
+            success: false,
+            skipped: false,
+        }
+        .send_signal_to_dart();
+        return;
+    }
 
     debug!(
         "Adding item to playlist: playlist_id={}, media_file_id={}, position={:#?}",
         request.playlist_id, request.media_file_id, request.position
     );
 
+    let before = get_playlist_items(&main_db, request.playlist_id).await;
+
     match add_item_to_playlist(
         &main_db,
         request.playlist_id,
         request.media_file_id,
         request.position,
+        DuplicatePolicy::from_name(&request.duplicate_policy).unwrap_or_default(),
     )
     .await
+    .map_err(|e| e.to_string())
     {
-        Ok(_) => {
-            AddItemToPlaylistResponse { success: true }.send_signal_to_dart();
+        Ok(Some(_)) => {
+            record_playlist_edit(&main_db, &journal, request.playlist_id, before).await;
+            sync_playlist_mosaic(&main_db, &lib_path, request.playlist_id).await;
+            query_cache.invalidate_playlists();
+            AddItemToPlaylistResponse {
+                success: true,
+                skipped: false,
+            }
+            .send_signal_to_dart();
+        }
+        Ok(None) => {
+            debug!("Item already in playlist: id={}", request.playlist_id);
+            AddItemToPlaylistResponse {
+                success: true,
+                skipped: true,
+            }
+            .send_signal_to_dart();
         }
         Err(e) => {
             error!("Failed to add item to playlist: {}", e);
-            AddItemToPlaylistResponse { success: false }.send_signal_to_dart();
+            AddItemToPlaylistResponse {
+                success: false,
+                skipped: false,
+            }
+            .send_signal_to_dart();
         }
     }
 }
 
 pub async fn add_media_file_to_playlist_request(
     main_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    journal: Arc<OperationJournal>,
     dart_signal: DartSignal<AddMediaFileToPlaylistRequest>,
 ) {
     let request = dart_signal.message;
+    if lib_mode.is_read_only() {
+        warn!(
+            "Library is read-only, not editing playlist {}",
+            request.playlist_id
+        );
+        AddMediaFileToPlaylistResponse {
+            success: false,
+            skipped: false,
+        }
+        .send_signal_to_dart();
+        return;
+    }
 
     debug!(
         "Adding media file to playlist: playlist_id={}, media_file_id={}",
         request.playlist_id, request.media_file_id
     );
 
-    match add_media_file_to_playlist(&main_db, request.playlist_id, request.media_file_id).await {
-        Ok(_) => {
-            AddMediaFileToPlaylistResponse { success: true }.send_signal_to_dart();
+    let before = get_playlist_items(&main_db, request.playlist_id).await;
+
+    match add_media_file_to_playlist(
+        &main_db,
+        request.playlist_id,
+        request.media_file_id,
+        DuplicatePolicy::from_name(&request.duplicate_policy).unwrap_or_default(),
+    )
+    .await
+    .map_err(|e| e.to_string())
+    {
+        Ok(Some(_)) => {
+            record_playlist_edit(&main_db, &journal, request.playlist_id, before).await;
+            sync_playlist_mosaic(&main_db, &lib_path, request.playlist_id).await;
+            query_cache.invalidate_playlists();
+            AddMediaFileToPlaylistResponse {
+                success: true,
+                skipped: false,
+            }
+            .send_signal_to_dart();
+        }
+        Ok(None) => {
+            debug!("Media file already in playlist: id={}", request.playlist_id);
+            AddMediaFileToPlaylistResponse {
+                success: true,
+                skipped: true,
+            }
+            .send_signal_to_dart();
         }
         Err(e) => {
             error!("Failed to add media file to playlist: {}", e);
-            AddMediaFileToPlaylistResponse { success: false }.send_signal_to_dart();
+            AddMediaFileToPlaylistResponse {
+                success: false,
+                skipped: false,
+            }
+            .send_signal_to_dart();
         }
     }
 }
 
 pub async fn reorder_playlist_item_position_request(
     main_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    journal: Arc<OperationJournal>,
     dart_signal: DartSignal<ReorderPlaylistItemPositionRequest>,
 ) {
     let request = dart_signal.message;
+    if lib_mode.is_read_only() {
+        warn!(
+            "Library is read-only, not editing playlist {}",
+            request.playlist_id
+        );
+        ReorderPlaylistItemPositionResponse { success: false }.send_signal_to_dart();
+        return;
+    }
 
     debug!(
         "Reordering playlist item: playlist_id={}, media_file_id={}, new_position={}",
         request.playlist_id, request.media_file_id, request.new_position
     );
 
+    let before = get_playlist_items(&main_db, request.playlist_id).await;
+
     match reorder_playlist_item_position(
         &main_db,
         request.playlist_id,
@@ -337,8 +498,12 @@ pub async fn reorder_playlist_item_position_request(
         request.new_position,
     )
     .await
+    .map_err(|e| e.to_string())
     {
         Ok(_) => {
+            record_playlist_edit(&main_db, &journal, request.playlist_id, before).await;
+            sync_playlist_mosaic(&main_db, &lib_path, request.playlist_id).await;
+            query_cache.invalidate_playlists();
             ReorderPlaylistItemPositionResponse { success: true }.send_signal_to_dart();
         }
         Err(e) => {
@@ -393,3 +558,47 @@ pub async fn get_playlist_by_id_request(
         }
     }
 }
+
+pub async fn fetch_playlist_memberships_request(
+    reader_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<FetchPlaylistMembershipsRequest>,
+) {
+    let request = dart_signal.message;
+
+    debug!(
+        "Fetching playlist memberships: {:?}",
+        request.media_file_ids
+    );
+
+    let queue: HashSet<i32> = player.lock().await.get_playlist().into_iter().collect();
+
+    match get_playlists_of_media_files(&reader_db, &request.media_file_ids).await {
+        Ok(memberships) => {
+            PlaylistMembershipsResponse {
+                memberships: request
+                    .media_file_ids
+                    .into_iter()
+                    .map(|media_file_id| PlaylistMembership {
+                        media_file_id,
+                        playlists: memberships
+                            .get(&media_file_id)
+                            .into_iter()
+                            .flatten()
+                            .map(|x| PlaylistWithoutCoverIds {
+                                id: x.id,
+                                name: x.name.clone(),
+                                group: x.group.clone(),
+                            })
+                            .collect(),
+                        in_queue: queue.contains(&media_file_id),
+                    })
+                    .collect(),
+            }
+            .send_signal_to_dart();
+        }
+        Err(e) => {
+            error!("Failed to fetch playlist memberships: {}", e);
+        }
+    }
+}
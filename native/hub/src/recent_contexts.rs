@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use rinf::DartSignal;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use database::actions::journal::{Operation, OperationJournal};
+use database::actions::recent_contexts::{
+    get_recent_contexts, record_recent_context, resume_recent_context, save_context_progress,
+    RecentContext, RECENT_CONTEXTS_LIMIT,
+};
+use database::connection::MainDbConnection;
+use playback::player::Player;
+
+use crate::messages::playback::{
+    FetchRecentContextsRequest, FetchRecentContextsResponse, RecentContext as RecentContextItem,
+    ResumeContextRequest, ResumeContextResponse,
+};
+use crate::playback::replace_queue;
+use crate::users::active_user_id;
+
+/// Save where playback is in the context that was played last, before the
+/// queue is replaced or the profile is left.
+pub async fn remember_context_progress(user_db: &MainDbConnection, player: &Arc<Mutex<Player>>) {
+    let user_id = active_user_id(user_db).await;
+    let status = player.lock().await.get_status();
+
+    if let Err(e) = save_context_progress(
+        user_db,
+        user_id,
+        &status.playlist,
+        status.index,
+        status.position.as_secs_f64(),
+    )
+    .await
+    {
+        error!("Failed to save the progress of the last context: {}", e);
+    }
+}
+
+/// Remember a context that just started playing with the files queued for it.
+pub async fn remember_context(
+    user_db: &MainDbConnection,
+    context_type: &str,
+    context_id: i32,
+    file_ids: &[i32],
+) {
+    if file_ids.is_empty() {
+        return;
+    }
+
+    let user_id = active_user_id(user_db).await;
+    if let Err(e) =
+        record_recent_context(user_db, user_id, context_type, context_id, file_ids).await
+    {
+        error!(
+            "Failed to remember context {} {}: {}",
+            context_type, context_id, e
+        );
+    }
+}
+
+fn to_recent_context_item(context: RecentContext) -> RecentContextItem {
+    RecentContextItem {
+        id: context.id,
+        r#type: context.context_type,
+        context_id: context.context_id,
+        track_count: context.file_ids.len() as i32,
+        current_file_id: context
+            .index
+            .and_then(|x| context.file_ids.get(x).copied())
+            .unwrap_or_default(),
+        index: context.index.unwrap_or_default() as u32,
+        position_seconds: context.position,
+        played_at: context.played_at,
+    }
+}
+
+pub async fn fetch_recent_contexts_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<FetchRecentContextsRequest>,
+) {
+    let limit = match dart_signal.message.limit {
+        0 => RECENT_CONTEXTS_LIMIT,
+        x => x as usize,
+    };
+    let user_id = active_user_id(&user_db).await;
+
+    match get_recent_contexts(&user_db, user_id, limit).await {
+        Ok(contexts) => FetchRecentContextsResponse {
+            contexts: contexts.into_iter().map(to_recent_context_item).collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch recent contexts: {}", e),
+    }
+}
+
+pub async fn resume_context_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    player: Arc<Mutex<Player>>,
+    journal: Arc<OperationJournal>,
+    dart_signal: DartSignal<ResumeContextRequest>,
+) {
+    let id = dart_signal.message.id;
+    remember_context_progress(&user_db, &player).await;
+
+    let user_id = active_user_id(&user_db).await;
+    let context = match resume_recent_context(&user_db, user_id, id).await {
+        Ok(Some(context)) if !context.file_ids.is_empty() => context,
+        Ok(_) => {
+            warn!("Nothing to resume in context {}", id);
+            ResumeContextResponse { id, success: false }.send_signal_to_dart();
+            return;
+        }
+        Err(e) => {
+            error!("Failed to resume context {}: {}", id, e);
+            ResumeContextResponse { id, success: false }.send_signal_to_dart();
+            return;
+        }
+    };
+
+    info!(
+        "Resuming {} {} at index {:?}, position {}",
+        context.context_type, context.context_id, context.index, context.position
+    );
+
+    let before = player.lock().await.get_playlist();
+    replace_queue(&main_db, &lib_path, &player, &context.file_ids).await;
+    {
+        let player = player.lock().await;
+        if let Some(index) = context.index {
+            player.switch(index);
+            player.seek(context.position);
+        }
+        player.play();
+    }
+    journal.record(Operation::Queue {
+        before,
+        after: context.file_ids,
+    });
+
+    ResumeContextResponse { id, success: true }.send_signal_to_dart();
+}
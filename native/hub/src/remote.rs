@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rinf::DartSignal;
+use tokio::sync::Mutex;
+use tokio::task;
+use tracing::{error, info, warn};
+
+use ::remote::client::SubsonicClient;
+use database::actions::remote::{
+    add_remote_server, get_remote_files, get_remote_files_by_ids, get_remote_server_by_id,
+    get_remote_servers, remove_remote_server, replace_remote_files, to_queue_id,
+};
+use database::connection::MainDbConnection;
+use database::entities::{remote_files, remote_servers};
+use playback::player::Player;
+use playback::queue::{DuplicatePolicy, PlaylistItem};
+use playback::source::{remote_path, StreamResolver};
+
+use crate::messages::remote::{
+    AddRemoteServerRequest, AddRemoteServerResponse, FetchRemoteFilesRequest,
+    FetchRemoteFilesResponse, FetchRemoteServersRequest, PlayRemoteFilesRequest, RemoteFile,
+    RemoteServer, RemoteServersResponse, RemoveRemoteServerRequest, SyncRemoteServerRequest,
+    SyncRemoteServerResponse,
+};
+
+fn to_remote_server(server: remote_servers::Model) -> RemoteServer {
+    RemoteServer {
+        id: server.id,
+        name: server.name,
+        url: server.url,
+        username: server.username,
+        synced_at: server.synced_at.unwrap_or_default(),
+    }
+}
+
+fn to_remote_file(file: remote_files::Model) -> RemoteFile {
+    RemoteFile {
+        id: file.id,
+        server_id: file.server_id,
+        title: file.title,
+        artist: file.artist,
+        album: file.album,
+        track_number: file.track_number.unwrap_or(0),
+        duration: file.duration,
+    }
+}
+
+fn client_of(server: &remote_servers::Model) -> SubsonicClient {
+    SubsonicClient::new(&server.url, &server.username, &server.password)
+}
+
+async fn send_remote_servers(user_db: &MainDbConnection) {
+    match get_remote_servers(user_db).await {
+        Ok(servers) => RemoteServersResponse {
+            servers: servers.into_iter().map(to_remote_server).collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch remote servers: {}", e),
+    }
+}
+
+pub async fn fetch_remote_servers_request(
+    user_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchRemoteServersRequest>,
+) {
+    send_remote_servers(&user_db).await;
+}
+
+async fn connect_remote_server(
+    user_db: &MainDbConnection,
+    request: AddRemoteServerRequest,
+) -> Result<remote_servers::Model, String> {
+    // Servers are only saved once they answer with these credentials
+    let client = SubsonicClient::new(&request.url, &request.username, &request.password);
+    task::spawn_blocking(move || client.ping())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+
+    add_remote_server(
+        user_db,
+        &request.name,
+        &request.url,
+        &request.username,
+        &request.password,
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn add_remote_server_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<AddRemoteServerRequest>,
+) {
+    let request = dart_signal.message;
+    info!("Adding remote server {} at {}", request.name, request.url);
+
+    match connect_remote_server(&user_db, request).await {
+        Ok(server) => AddRemoteServerResponse {
+            server: Some(to_remote_server(server)),
+            success: true,
+            error: String::new(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => {
+            error!("Failed to add remote server: {}", e);
+            AddRemoteServerResponse {
+                server: None,
+                success: false,
+                error: e,
+            }
+            .send_signal_to_dart()
+        }
+    }
+}
+
+pub async fn remove_remote_server_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<RemoveRemoteServerRequest>,
+) {
+    let server_id = dart_signal.message.server_id;
+    if let Err(e) = remove_remote_server(&user_db, server_id).await {
+        error!("Failed to remove remote server {}: {}", server_id, e);
+    }
+
+    send_remote_servers(&user_db).await;
+}
+
+async fn sync_remote_server(user_db: &MainDbConnection, server_id: i32) -> Result<usize, String> {
+    let server = get_remote_server_by_id(user_db, server_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Remote server not found: {}", server_id))?;
+
+    let client = client_of(&server);
+    let records = task::spawn_blocking(move || client.fetch_library())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+
+    replace_remote_files(user_db, server_id, records)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+pub async fn sync_remote_server_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SyncRemoteServerRequest>,
+) {
+    let server_id = dart_signal.message.server_id;
+    info!("Syncing remote server {}", server_id);
+
+    // Reading a large library takes a while, other requests shouldn't wait
+    tokio::spawn(async move {
+        let response = match sync_remote_server(&user_db, server_id).await {
+            Ok(files) => SyncRemoteServerResponse {
+                server_id,
+                success: true,
+                error: String::new(),
+                files: files as i32,
+            },
+            Err(e) => {
+                error!("Failed to sync remote server {}: {}", server_id, e);
+                SyncRemoteServerResponse {
+                    server_id,
+                    success: false,
+                    error: e,
+                    files: 0,
+                }
+            }
+        };
+        response.send_signal_to_dart();
+
+        send_remote_servers(&user_db).await;
+    });
+}
+
+pub async fn fetch_remote_files_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<FetchRemoteFilesRequest>,
+) {
+    let request = dart_signal.message;
+
+    match get_remote_files(
+        &user_db,
+        request.server_id,
+        &request.query,
+        request.cursor.max(0) as usize,
+        request.page_size.max(0) as usize,
+    )
+    .await
+    {
+        Ok(files) => FetchRemoteFilesResponse {
+            server_id: request.server_id,
+            files: files.into_iter().map(to_remote_file).collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch remote files: {}", e),
+    }
+}
+
+pub async fn play_remote_files_request(
+    user_db: Arc<MainDbConnection>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<PlayRemoteFilesRequest>,
+) {
+    let request = dart_signal.message;
+
+    let files = match get_remote_files_by_ids(&user_db, &request.file_ids).await {
+        Ok(files) => files,
+        Err(e) => {
+            error!("Failed to get remote files: {}", e);
+            return;
+        }
+    };
+    let servers: HashMap<i32, SubsonicClient> = match get_remote_servers(&user_db).await {
+        Ok(servers) => servers.iter().map(|x| (x.id, client_of(x))).collect(),
+        Err(e) => {
+            error!("Failed to get remote servers: {}", e);
+            return;
+        }
+    };
+    let mut files: HashMap<i32, remote_files::Model> =
+        files.into_iter().map(|x| (x.id, x)).collect();
+
+    let player = player.lock().await;
+    if !request.append {
+        player.pause();
+        player.clear_playlist();
+    }
+
+    let clients = servers.clone();
+    player.set_stream_resolver(StreamResolver::new(move |server_id, remote_id| {
+        clients
+            .get(&server_id)
+            .map(|client| client.stream_url(remote_id))
+    }));
+
+    // The queue only names the server and song, the signed stream address
+    // is made when the track is loaded. Remote files have no ReplayGain,
+    // seek table or encoder trim stored, the listed length stands in for
+    // streams that don't report one.
+    let mut items = Vec::with_capacity(request.file_ids.len());
+    for id in request.file_ids {
+        let Some(file) = files.remove(&id) else {
+            warn!("Remote file not found: {}", id);
+            continue;
+        };
+        if !servers.contains_key(&file.server_id) {
+            continue;
+        }
+        let duration = (file.duration.is_finite() && file.duration > 0.)
+            .then(|| Duration::from_secs_f64(file.duration));
+        items.push(
+            PlaylistItem::new(
+                to_queue_id(file.id),
+                remote_path(file.server_id, &file.remote_id),
+            )
+            .with_duration(duration),
+        );
+    }
+    // Nothing to wait for, every file is queued
+    drop(player.add_items_to_playlist(items, DuplicatePolicy::Allow));
+    player.play();
+}
+
+/// Tell the server of a remote file it was played.
+pub async fn scrobble_remote_file(user_db: &MainDbConnection, remote_file_id: i32) {
+    let file = match get_remote_files_by_ids(user_db, &[remote_file_id]).await {
+        Ok(files) => files.into_iter().next(),
+        Err(e) => {
+            error!("Failed to get remote file {}: {}", remote_file_id, e);
+            return;
+        }
+    };
+    let Some(file) = file else {
+        return;
+    };
+    let server = match get_remote_server_by_id(user_db, file.server_id).await {
+        Ok(Some(server)) => server,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Failed to get remote server {}: {}", file.server_id, e);
+            return;
+        }
+    };
+
+    let client = client_of(&server);
+    match task::spawn_blocking(move || client.scrobble(&file.remote_id)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!("Failed to scrobble remote file {}: {}", remote_file_id, e),
+        Err(e) => error!("Failed to scrobble remote file {}: {}", remote_file_id, e),
+    }
+}
@@ -1,34 +1,87 @@
-use database::actions::search::{search_for, CollectionType};
-use log::{debug, warn};
+use database::actions::eras::{filter_by_years, get_era_facets, Era};
+use database::actions::explicit::get_explicit_file_ids;
+use database::actions::lyrics::{search_lyrics, LyricsSnippet};
+use database::actions::search::{best_match, search_scored, suggest_queries, CollectionType};
+use database::actions::search_aliases::{
+    add_search_alias, get_search_aliases, load_search_synonyms, remove_search_alias,
+};
+use database::actions::vibes::filter_by_features;
+use database::entities::search_aliases;
 use rinf::DartSignal;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tracing::{debug, error, warn};
 
-use database::connection::SearchDbConnection;
+use database::connection::{MainDbConnection, SearchDbConnection};
 
-use crate::messages::search::{SearchForRequest, SearchForResponse};
+use crate::media_file::vibe_ranges;
+use crate::messages::search::{
+    AddSearchAliasRequest, AddSearchAliasResponse, FetchSearchAliasesRequest,
+    FetchSearchAliasesResponse, LyricsFragment, LyricsLine, LyricsSearchHit,
+    RemoveSearchAliasRequest, RemoveSearchAliasResponse, SearchAlias, SearchBestMatch,
+    SearchEraFacet, SearchForRequest, SearchForResponse, SearchLyricsRequest, SearchLyricsResponse,
+};
+use crate::users::active_clean_mode;
+
+// Spelling suggestions are offered below this number of results
+const FEW_RESULTS: usize = 3;
+const SUGGESTIONS: usize = 3;
+// Results fetched per requested one when narrowing by year
+const NARROWED_LOOKAHEAD: usize = 4;
 
 pub async fn search_for_request(
+    reader_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
     search_db: Arc<Mutex<SearchDbConnection>>,
     dart_signal: DartSignal<SearchForRequest>,
 ) {
     let request = dart_signal.message;
     let query_str = request.query_str;
     let n = request.n as usize;
+    let year_from = Some(request.year_from).filter(|x| *x != 0);
+    let year_to = Some(request.year_to).filter(|x| *x != 0);
+    let feature_ranges = vibe_ranges(&reader_db, &request.vibes).await;
+    let narrowed = year_from.is_some() || year_to.is_some() || !feature_ranges.is_empty();
 
     debug!("Received search request: query_str={}, n={}", query_str, n);
 
     let mut search_db = search_db.lock().await;
 
-    match search_for(&mut search_db, &query_str, n) {
+    // Look further when narrowing, many hits may be from other years or vibes
+    let limit = if narrowed { n * NARROWED_LOOKAHEAD } else { n };
+    match search_scored(&mut search_db, &query_str, limit).map_err(|e| e.to_string()) {
         Ok(results) => {
+            let best_match = best_match(&results, &query_str).and_then(|(collection_type, id)| {
+                let r#type = match collection_type {
+                    CollectionType::Artist => "artist",
+                    CollectionType::Album => "album",
+                    CollectionType::Playlist => "playlist",
+                    CollectionType::Track => "track",
+                    _ => return None,
+                };
+                Some(SearchBestMatch {
+                    r#type: r#type.to_string(),
+                    id: id as i32,
+                })
+            });
+
+            let total: usize = results.values().map(|x| x.len()).sum();
+            let suggestions = if total < FEW_RESULTS {
+                suggest_queries(&search_db, &query_str, SUGGESTIONS).unwrap_or_else(|e| {
+                    warn!("Failed to suggest queries: {:?}", e);
+                    Vec::new()
+                })
+            } else {
+                Vec::new()
+            };
+
             let mut artists: Vec<i32> = Vec::new();
             let mut albums: Vec<i32> = Vec::new();
             let mut playlists: Vec<i32> = Vec::new();
             let mut tracks: Vec<i32> = Vec::new();
 
-            for (collection_type, ids) in results {
-                let ids: Vec<i32> = ids.iter().map(|&x| x as i32).collect();
+            for (collection_type, items) in results {
+                let ids: Vec<i32> = items.iter().map(|x| x.id as i32).collect();
                 match collection_type {
                     CollectionType::Artist => artists.extend(ids),
                     CollectionType::Album => albums.extend(ids),
@@ -37,24 +90,244 @@ pub async fn search_for_request(
                     _ => {}
                 }
             }
+            drop(search_db);
+
+            // Clean mode hides explicit tracks, whatever matched. When they
+            // can't be told apart no track is shown
+            if active_clean_mode(&user_db).await {
+                match get_explicit_file_ids(&reader_db, &tracks).await {
+                    Ok(explicit) => tracks.retain(|x| !explicit.contains(x)),
+                    Err(e) => {
+                        error!("Failed to leave explicit tracks out: {}", e);
+                        tracks.clear();
+                    }
+                }
+            }
+
+            let decades = get_era_facets(&reader_db, Era::Decade, &albums, &tracks)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to count search results by decade: {}", e);
+                    Vec::new()
+                })
+                .into_iter()
+                .map(|x| SearchEraFacet {
+                    start_year: x.start,
+                    album_count: x.albums as i32,
+                    track_count: x.tracks as i32,
+                })
+                .collect();
+
+            let (mut albums, tracks) =
+                match filter_by_years(&reader_db, year_from, year_to, albums, tracks).await {
+                    Ok(x) => x,
+                    Err(e) => {
+                        warn!("Failed to narrow search results by year: {}", e);
+                        (Vec::new(), Vec::new())
+                    }
+                };
+            let mut tracks = match filter_by_features(&reader_db, &feature_ranges, tracks).await {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("Failed to narrow search results by vibe: {}", e);
+                    Vec::new()
+                }
+            };
+            albums.truncate(n);
+            tracks.truncate(n);
+            artists.truncate(n);
+            playlists.truncate(n);
+
+            // The best match may be from another era or explicit
+            let best_match = best_match.filter(|x| match x.r#type.as_str() {
+                "album" => albums.contains(&x.id),
+                "track" => tracks.contains(&x.id),
+                _ => true,
+            });
 
             SearchForResponse {
                 artists,
                 albums,
                 playlists,
                 tracks,
+                best_match,
+                suggestions,
+                decades,
             }
             .send_signal_to_dart(); // GENERATED
         }
         Err(e) => {
-            warn!("Search request failed: {:?}", e);
+            warn!("Search request failed: {}", e);
             SearchForResponse {
                 artists: Vec::new(),
                 albums: Vec::new(),
                 playlists: Vec::new(),
                 tracks: Vec::new(),
+                best_match: None,
+                suggestions: Vec::new(),
+                decades: Vec::new(),
             }
             .send_signal_to_dart(); // GENERATED
         }
     }
 }
+
+fn to_lyrics_line(snippet: LyricsSnippet) -> LyricsLine {
+    let mut fragments = Vec::new();
+    let mut end = 0;
+    for range in snippet.highlights {
+        if range.start > end {
+            fragments.push(LyricsFragment {
+                text: snippet.line[end..range.start].to_string(),
+                highlighted: false,
+            });
+        }
+        fragments.push(LyricsFragment {
+            text: snippet.line[range.clone()].to_string(),
+            highlighted: true,
+        });
+        end = range.end;
+    }
+    if end < snippet.line.len() {
+        fragments.push(LyricsFragment {
+            text: snippet.line[end..].to_string(),
+            highlighted: false,
+        });
+    }
+
+    LyricsLine { fragments }
+}
+
+pub async fn search_lyrics_request(
+    reader_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    search_db: Arc<Mutex<SearchDbConnection>>,
+    dart_signal: DartSignal<SearchLyricsRequest>,
+) {
+    let request = dart_signal.message;
+
+    debug!(
+        "Received lyrics search request: query_str={}, n={}",
+        request.query_str, request.n
+    );
+
+    let hits = search_lyrics(
+        &*search_db.lock().await,
+        &request.query_str,
+        request.n as usize,
+    )
+    // The error isn't Send and can't be held across the awaits below
+    .map_err(|e| e.to_string());
+    let mut hits = match hits {
+        Ok(hits) => hits,
+        Err(e) => {
+            warn!("Lyrics search request failed: {}", e);
+            Vec::new()
+        }
+    };
+
+    // Clean mode hides explicit tracks, whatever matched. When they can't
+    // be told apart no track is shown
+    if active_clean_mode(&user_db).await {
+        let ids: Vec<i32> = hits.iter().map(|x| x.id as i32).collect();
+        match get_explicit_file_ids(&reader_db, &ids).await {
+            Ok(explicit) => hits.retain(|x| !explicit.contains(&(x.id as i32))),
+            Err(e) => {
+                error!("Failed to leave explicit tracks out: {}", e);
+                hits.clear();
+            }
+        }
+    }
+
+    SearchLyricsResponse {
+        query_str: request.query_str,
+        hits: hits
+            .into_iter()
+            .map(|x| LyricsSearchHit {
+                track_id: x.id as i32,
+                lines: x.snippets.into_iter().map(to_lyrics_line).collect(),
+            })
+            .collect(),
+    }
+    .send_signal_to_dart();
+}
+
+fn to_search_alias(item: search_aliases::Model) -> SearchAlias {
+    SearchAlias {
+        id: item.id,
+        term: item.term,
+        alias: item.alias,
+    }
+}
+
+/// Load the user's aliases into the search index so queries are expanded.
+pub async fn reload_search_synonyms(
+    user_db: &MainDbConnection,
+    search_db: &Arc<Mutex<SearchDbConnection>>,
+) {
+    match load_search_synonyms(user_db).await {
+        Ok(synonyms) => search_db.lock().await.synonyms = synonyms,
+        Err(e) => error!("Failed to load search aliases: {}", e),
+    }
+}
+
+pub async fn fetch_search_aliases_request(
+    user_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchSearchAliasesRequest>,
+) {
+    match get_search_aliases(&user_db).await {
+        Ok(aliases) => FetchSearchAliasesResponse {
+            aliases: aliases.into_iter().map(to_search_alias).collect(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => error!("Failed to fetch search aliases: {}", e),
+    }
+}
+
+pub async fn add_search_alias_request(
+    user_db: Arc<MainDbConnection>,
+    search_db: Arc<Mutex<SearchDbConnection>>,
+    dart_signal: DartSignal<AddSearchAliasRequest>,
+) {
+    let request = dart_signal.message;
+
+    let alias = match add_search_alias(&user_db, &request.term, &request.alias).await {
+        Ok(alias) => alias,
+        Err(e) => {
+            error!("Failed to add search alias: {}", e);
+            None
+        }
+    };
+
+    if alias.is_some() {
+        reload_search_synonyms(&user_db, &search_db).await;
+    }
+
+    AddSearchAliasResponse {
+        success: alias.is_some(),
+        alias: alias.map(to_search_alias),
+    }
+    .send_signal_to_dart();
+}
+
+pub async fn remove_search_alias_request(
+    user_db: Arc<MainDbConnection>,
+    search_db: Arc<Mutex<SearchDbConnection>>,
+    dart_signal: DartSignal<RemoveSearchAliasRequest>,
+) {
+    let id = dart_signal.message.id;
+
+    let success = match remove_search_alias(&user_db, id).await {
+        Ok(removed) => removed,
+        Err(e) => {
+            error!("Failed to remove search alias: {}", e);
+            false
+        }
+    };
+
+    if success {
+        reload_search_synonyms(&user_db, &search_db).await;
+    }
+
+    RemoveSearchAliasResponse { id, success }.send_signal_to_dart();
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use database::actions::playback_queue::save_playback_queue;
+use database::actions::recent_contexts::save_context_progress;
+use database::actions::remote::{from_queue_id, without_remote_files};
+use database::connection::{MainDbConnection, SearchDbConnection};
+use playback::player::Player;
+
+use crate::users::active_user_id;
+
+/// Upper bound for the whole shutdown sequence, a stuck database or index
+/// writer must not keep the library open forever.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Save the play queue for a profile, to find it again when the profile is
+/// used next.
+pub async fn flush_playback_state(
+    user_db: &MainDbConnection,
+    user_id: i32,
+    player: &Arc<Mutex<Player>>,
+) {
+    let status = player.lock().await.get_status();
+
+    // Remote files can't be referenced by the database, playback resumes
+    // at the next file of the library if one was playing
+    let (file_ids, index) = without_remote_files(&status.playlist, status.index);
+    let current_is_remote = status
+        .index
+        .and_then(|x| status.playlist.get(x))
+        .is_some_and(|x| from_queue_id(*x).is_some());
+    let position = if current_is_remote {
+        Duration::ZERO
+    } else {
+        status.position
+    };
+
+    info!(
+        "Flushing playback queue of profile {} ({} items, index {:?}, position {:?})",
+        user_id,
+        file_ids.len(),
+        index,
+        position
+    );
+
+    if let Err(e) =
+        save_playback_queue(user_db, user_id, &file_ids, index, position.as_secs_f32()).await
+    {
+        error!("Failed to flush playback queue: {:#?}", e);
+    }
+
+    if let Err(e) =
+        save_context_progress(user_db, user_id, &file_ids, index, position.as_secs_f64()).await
+    {
+        error!("Failed to save the progress of the last context: {:#?}", e);
+    }
+}
+
+async fn commit_search_index(search_db: &Arc<Mutex<SearchDbConnection>>) {
+    let mut search_db = search_db.lock().await;
+
+    info!("Committing pending search index writes");
+    if let Err(e) = search_db.commit() {
+        error!("Failed to commit search index: {:#?}", e);
+    }
+}
+
+/// Run the orderly shutdown sequence of an opened library.
+///
+/// `user_db` is the database holding the play state, the main database
+/// of the library or the local overlay of a read-only library.
+///
+/// This is called after the main loop stopped accepting new requests. Handlers
+/// are awaited inside the main loop, so any in-flight transaction has already
+/// completed at this point.
+pub async fn shutdown(
+    user_db: Arc<MainDbConnection>,
+    search_db: Arc<Mutex<SearchDbConnection>>,
+    player: Arc<Mutex<Player>>,
+) {
+    info!("Shutting down library");
+
+    let sequence = async {
+        let user_id = active_user_id(&user_db).await;
+        flush_playback_state(&user_db, user_id, &player).await;
+        commit_search_index(&search_db).await;
+    };
+
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, sequence)
+        .await
+        .is_err()
+    {
+        warn!("Shutdown sequence timed out, exiting anyway");
+    }
+
+    player.lock().await.terminate();
+
+    info!("Library closed");
+}
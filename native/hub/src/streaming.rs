@@ -0,0 +1,159 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rinf::DartSignal;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use database::actions::query_cache::QueryCache;
+use database::actions::settings::{get_setting, set_setting};
+use database::connection::{MainDbConnection, SearchDbConnection};
+use server::stream::{
+    generate_token, StreamServer, StreamState, DEFAULT_SERVER_PORT, SERVER_PORT_KEY,
+    SERVER_TOKEN_KEY,
+};
+
+use crate::messages::streaming::{
+    FetchStreamingServerStatusRequest, StartStreamingServerRequest, StopStreamingServerRequest,
+    StreamingServerStatus,
+};
+
+/// Where the streaming server keeps transcoded files.
+pub fn transcode_cache_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rune")
+        .join("transcoded")
+}
+
+async fn get_token(user_db: &MainDbConnection, reset: bool) -> Result<String, String> {
+    let token = get_setting(user_db, SERVER_TOKEN_KEY)
+        .await
+        .map_err(|e| e.to_string())?
+        .filter(|_| !reset);
+
+    match token {
+        Some(token) => Ok(token),
+        None => {
+            let token = generate_token();
+            set_setting(user_db, SERVER_TOKEN_KEY, token.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(token)
+        }
+    }
+}
+
+async fn get_port(user_db: &MainDbConnection, port: i32) -> Result<u16, String> {
+    if port > 0 {
+        let port = u16::try_from(port).map_err(|_| format!("Invalid port: {}", port))?;
+        set_setting(user_db, SERVER_PORT_KEY, port.to_string())
+            .await
+            .map_err(|e| e.to_string())?;
+        return Ok(port);
+    }
+
+    Ok(get_setting(user_db, SERVER_PORT_KEY)
+        .await
+        .map_err(|e| e.to_string())?
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(DEFAULT_SERVER_PORT))
+}
+
+async fn send_status(
+    user_db: &MainDbConnection,
+    stream_server: &Option<StreamServer>,
+    error: String,
+) {
+    let token = match get_setting(user_db, SERVER_TOKEN_KEY).await {
+        Ok(token) => token.unwrap_or_default(),
+        Err(e) => {
+            error!("Failed to read the streaming token: {}", e);
+            String::new()
+        }
+    };
+
+    StreamingServerStatus {
+        running: stream_server.is_some(),
+        port: stream_server
+            .as_ref()
+            .map_or(0, |x| x.local_addr().port() as i32),
+        token,
+        error,
+    }
+    .send_signal_to_dart();
+}
+
+pub async fn start_streaming_server_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    search_db: Arc<Mutex<SearchDbConnection>>,
+    lib_path: Arc<String>,
+    query_cache: Arc<QueryCache>,
+    stream_server: Arc<Mutex<Option<StreamServer>>>,
+    dart_signal: DartSignal<StartStreamingServerRequest>,
+) {
+    let request = dart_signal.message;
+    let mut stream_server = stream_server.lock().await;
+
+    // A running server is restarted, so a new port or token takes effect
+    if let Some(server) = stream_server.take() {
+        server.stop();
+    }
+
+    let started = async {
+        let token = get_token(&user_db, request.reset_token).await?;
+        let port = get_port(&user_db, request.port).await?;
+
+        StreamServer::start(
+            ("0.0.0.0", port),
+            StreamState {
+                main_db: main_db.clone(),
+                user_db: user_db.clone(),
+                search_db: search_db.clone(),
+                query_cache: query_cache.clone(),
+                lib_path: Path::new(lib_path.as_ref()).to_path_buf(),
+                token,
+                cache_dir: transcode_cache_path(),
+            },
+        )
+        .map_err(|e| format!("Failed to listen on port {}: {}", port, e))
+    }
+    .await;
+
+    let error = match started {
+        Ok(server) => {
+            info!("Streaming the library on {}", server.local_addr());
+            *stream_server = Some(server);
+            String::new()
+        }
+        Err(e) => {
+            error!("Failed to start the streaming server: {}", e);
+            e
+        }
+    };
+
+    send_status(&user_db, &stream_server, error).await;
+}
+
+pub async fn stop_streaming_server_request(
+    user_db: Arc<MainDbConnection>,
+    stream_server: Arc<Mutex<Option<StreamServer>>>,
+    _dart_signal: DartSignal<StopStreamingServerRequest>,
+) {
+    let mut stream_server = stream_server.lock().await;
+    if let Some(server) = stream_server.take() {
+        server.stop();
+    }
+
+    send_status(&user_db, &stream_server, String::new()).await;
+}
+
+pub async fn fetch_streaming_server_status_request(
+    user_db: Arc<MainDbConnection>,
+    stream_server: Arc<Mutex<Option<StreamServer>>>,
+    _dart_signal: DartSignal<FetchStreamingServerStatusRequest>,
+) {
+    let stream_server = stream_server.lock().await;
+    send_status(&user_db, &stream_server, String::new()).await;
+}
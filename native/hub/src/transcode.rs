@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rinf::DartSignal;
+use tracing::{error, info, warn};
+
+use database::actions::bulk::{DeleteMode, PERMANENT_DELETE_KEY};
+use database::actions::query_cache::QueryCache;
+use database::actions::settings::get_setting;
+use database::actions::transcode::{TranscodeJob, TranscodeQueue, TranscodeTarget};
+use database::connection::MainDbConnection;
+
+use crate::library_manage::LibraryMode;
+use crate::messages::transcode::{
+    CancelTranscodeRequest, CancelTranscodeResponse, TranscodeFailure, TranscodeFilesRequest,
+    TranscodeFilesResponse, TranscodeProgress,
+};
+
+pub async fn transcode_files_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    lib_mode: Arc<LibraryMode>,
+    query_cache: Arc<QueryCache>,
+    transcode_queue: Arc<TranscodeQueue>,
+    dart_signal: DartSignal<TranscodeFilesRequest>,
+) {
+    let request = dart_signal.message;
+
+    let target = if request.target_folder.is_empty() {
+        if lib_mode.is_read_only() {
+            warn!("Library is read-only, skipping transcoding");
+            return TranscodeFilesResponse {
+                succeeded: Vec::new(),
+                failed: request
+                    .file_ids
+                    .into_iter()
+                    .map(|file_id| TranscodeFailure {
+                        file_id,
+                        error: "The library is read-only".to_string(),
+                    })
+                    .collect(),
+            }
+            .send_signal_to_dart();
+        }
+        TranscodeTarget::Replace
+    } else {
+        TranscodeTarget::Folder(PathBuf::from(&request.target_folder))
+    };
+
+    info!(
+        "Queueing {} files to transcode to {}",
+        request.file_ids.len(),
+        request.codec
+    );
+    transcode_queue.push(
+        request
+            .file_ids
+            .into_iter()
+            .map(|file_id| TranscodeJob {
+                file_id,
+                codec: request.codec.clone(),
+                target: target.clone(),
+            })
+            .collect(),
+    );
+
+    // The running queue picks the new jobs up
+    if transcode_queue.is_running() {
+        return;
+    }
+
+    let delete_mode = match get_setting(user_db.as_ref(), PERMANENT_DELETE_KEY).await {
+        Ok(value) => DeleteMode::from_setting(value.as_deref()),
+        Err(e) => {
+            error!("Unable to read the deletion setting: {}", e);
+            DeleteMode::Trash
+        }
+    };
+
+    // Requests are handled one at a time, transcoding must not hold them up
+    tokio::spawn(async move {
+        let report = transcode_queue
+            .run(
+                &main_db,
+                Path::new(lib_path.as_ref()),
+                delete_mode,
+                |progress, total| {
+                    TranscodeProgress {
+                        progress: progress as i32,
+                        total: total as i32,
+                    }
+                    .send_signal_to_dart()
+                },
+            )
+            .await;
+
+        let Some(mut report) = report else {
+            return;
+        };
+        query_cache.invalidate_files(&report.succeeded);
+
+        TranscodeFilesResponse {
+            succeeded: report.succeeded,
+            failed: report
+                .failed
+                .into_iter()
+                .map(|file_id| TranscodeFailure {
+                    file_id,
+                    error: report.errors.remove(&file_id).unwrap_or_default(),
+                })
+                .collect(),
+        }
+        .send_signal_to_dart();
+    });
+}
+
+pub async fn cancel_transcode_request(
+    transcode_queue: Arc<TranscodeQueue>,
+    _dart_signal: DartSignal<CancelTranscodeRequest>,
+) {
+    let file_ids = transcode_queue.cancel();
+    info!("Cancelled transcoding of {} files", file_ids.len());
+
+    CancelTranscodeResponse { file_ids }.send_signal_to_dart();
+}
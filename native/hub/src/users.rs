@@ -0,0 +1,219 @@
+use std::sync::Arc;
+
+use rinf::DartSignal;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use database::actions::explicit::{get_clean_mode, set_clean_mode};
+use database::actions::playback_queue::get_playback_queue;
+use database::actions::users::{
+    create_user, get_active_user_id, get_users, remove_user, set_active_user_id, DEFAULT_USER_ID,
+};
+use database::connection::MainDbConnection;
+use database::entities::users;
+use playback::player::Player;
+
+use crate::messages::users::{
+    CreateUserRequest, CreateUserResponse, FetchUsersRequest, RemoveUserRequest,
+    SetCleanModeRequest, SwitchUserRequest, UserProfile, UsersResponse,
+};
+use crate::playback::replace_queue;
+use crate::player::send_playback_state_snapshot;
+use crate::shutdown::flush_playback_state;
+
+/// The profile plays and ratings are recorded for, the default one if it
+/// can't be read.
+pub async fn active_user_id(user_db: &MainDbConnection) -> i32 {
+    match get_active_user_id(user_db).await {
+        Ok(user_id) => user_id,
+        Err(e) => {
+            error!("Failed to read the active profile: {}", e);
+            DEFAULT_USER_ID
+        }
+    }
+}
+
+/// Whether explicit tracks are kept out for the active profile, on if it
+/// can't be read so a failure never shows them.
+pub async fn active_clean_mode(user_db: &MainDbConnection) -> bool {
+    let user_id = active_user_id(user_db).await;
+    match get_clean_mode(user_db, user_id).await {
+        Ok(enabled) => enabled,
+        Err(e) => {
+            error!("Failed to read clean mode of profile {}: {}", user_id, e);
+            true
+        }
+    }
+}
+
+async fn to_user_profile(user_db: &MainDbConnection, user: users::Model) -> UserProfile {
+    let clean_mode = get_clean_mode(user_db, user.id).await.unwrap_or_else(|e| {
+        error!("Failed to read clean mode of profile {}: {}", user.id, e);
+        false
+    });
+
+    UserProfile {
+        id: user.id,
+        name: user.name,
+        clean_mode,
+    }
+}
+
+async fn send_users(user_db: &MainDbConnection) {
+    let active_user_id = active_user_id(user_db).await;
+    match get_users(user_db).await {
+        Ok(users) => {
+            let mut profiles = Vec::with_capacity(users.len());
+            for user in users {
+                profiles.push(to_user_profile(user_db, user).await);
+            }
+
+            UsersResponse {
+                users: profiles,
+                active_user_id,
+            }
+            .send_signal_to_dart()
+        }
+        Err(e) => error!("Failed to fetch profiles: {}", e),
+    }
+}
+
+pub async fn fetch_users_request(
+    user_db: Arc<MainDbConnection>,
+    _dart_signal: DartSignal<FetchUsersRequest>,
+) {
+    send_users(&user_db).await;
+}
+
+pub async fn create_user_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<CreateUserRequest>,
+) {
+    let name = dart_signal.message.name;
+    info!("Creating profile {}", name);
+
+    let result = if name.trim().is_empty() {
+        Err("The name of the profile is empty".to_string())
+    } else {
+        create_user(&user_db, &name)
+            .await
+            .map_err(|e| e.to_string())
+    };
+
+    match result {
+        Ok(user) => CreateUserResponse {
+            user: Some(to_user_profile(&user_db, user).await),
+            success: true,
+            error: String::new(),
+        }
+        .send_signal_to_dart(),
+        Err(e) => {
+            error!("Failed to create profile: {}", e);
+            CreateUserResponse {
+                user: None,
+                success: false,
+                error: e,
+            }
+            .send_signal_to_dart()
+        }
+    }
+
+    send_users(&user_db).await;
+}
+
+pub async fn remove_user_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<RemoveUserRequest>,
+) {
+    let user_id = dart_signal.message.user_id;
+    info!("Removing profile {}", user_id);
+
+    match remove_user(&user_db, user_id).await {
+        Ok(true) => {}
+        Ok(false) => warn!("Profile {} can't be removed", user_id),
+        Err(e) => error!("Failed to remove profile {}: {}", user_id, e),
+    }
+
+    send_users(&user_db).await;
+}
+
+pub async fn set_clean_mode_request(
+    user_db: Arc<MainDbConnection>,
+    dart_signal: DartSignal<SetCleanModeRequest>,
+) {
+    let request = dart_signal.message;
+    info!(
+        "Setting clean mode of profile {} to {}",
+        request.user_id, request.enabled
+    );
+
+    if let Err(e) = set_clean_mode(&user_db, request.user_id, request.enabled).await {
+        error!(
+            "Failed to set clean mode of profile {}: {}",
+            request.user_id, e
+        );
+    }
+
+    send_users(&user_db).await;
+}
+
+/// Put back the queue a profile had when it was left, paused.
+pub async fn restore_queue(
+    main_db: &MainDbConnection,
+    user_db: &MainDbConnection,
+    user_id: i32,
+    lib_path: &str,
+    player: &Arc<Mutex<Player>>,
+) {
+    let saved = match get_playback_queue(user_db, user_id).await {
+        Ok(saved) => saved,
+        Err(e) => {
+            error!("Failed to read the queue of profile {}: {}", user_id, e);
+            Default::default()
+        }
+    };
+
+    if saved.file_ids.is_empty() {
+        let player = player.lock().await;
+        player.pause();
+        player.clear_playlist();
+        return;
+    }
+
+    replace_queue(main_db, lib_path, player, &saved.file_ids).await;
+
+    let player = player.lock().await;
+    if let Some(index) = saved.index {
+        player.switch(index);
+        player.seek(saved.position as f64);
+    }
+    player.pause();
+}
+
+pub async fn switch_user_request(
+    main_db: Arc<MainDbConnection>,
+    user_db: Arc<MainDbConnection>,
+    lib_path: Arc<String>,
+    player: Arc<Mutex<Player>>,
+    dart_signal: DartSignal<SwitchUserRequest>,
+) {
+    let user_id = dart_signal.message.user_id;
+    let previous_user_id = active_user_id(&user_db).await;
+    if user_id == previous_user_id {
+        send_users(&user_db).await;
+        return;
+    }
+
+    info!("Switching from profile {} to {}", previous_user_id, user_id);
+    match set_active_user_id(&user_db, user_id).await {
+        Ok(true) => {
+            flush_playback_state(&user_db, previous_user_id, &player).await;
+            restore_queue(&main_db, &user_db, user_id, &lib_path, &player).await;
+            send_playback_state_snapshot(&main_db, &user_db, &player).await;
+        }
+        Ok(false) => warn!("Profile not found: {}", user_id),
+        Err(e) => error!("Failed to switch to profile {}: {}", user_id, e),
+    }
+
+    send_users(&user_db).await;
+}
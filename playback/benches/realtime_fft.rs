@@ -0,0 +1,33 @@
+//! Time one update of the realtime spectrum. Run with `cargo bench -p playback`.
+//!
+//! A plain timing loop rather than criterion, so the benchmark adds no
+//! dependencies. The transform is vectorised by the planner, see
+//! `analysis::fft::WindowedFft`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use playback::realtime_fft::RealTimeFFT;
+
+const UPDATES: usize = 20_000;
+
+fn main() {
+    for window_size in [256, 512, 1024] {
+        let mut fft = RealTimeFFT::new(window_size);
+        let mut receiver = fft.subscribe();
+
+        let start = Instant::now();
+        for n in 0..UPDATES {
+            let sample = ((n as f32 * 0.1).sin() * 8000.0) as i16;
+            fft.add_data(black_box(vec![sample, sample / 2]));
+            black_box(receiver.try_recv().ok());
+        }
+        let elapsed = start.elapsed();
+
+        println!(
+            "realtime window {:>5}: {:>8.0} ns/update",
+            window_size,
+            elapsed.as_nanos() as f64 / UPDATES as f64
+        );
+    }
+}
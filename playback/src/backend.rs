@@ -0,0 +1,80 @@
+use std::any::Any;
+use std::error::Error;
+
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::{Device, OutputStream, Sink};
+use tracing::warn;
+
+/// Keeps the audio output behind a sink alive, the sink goes silent once it is dropped.
+pub type OutputHandle = Box<dyn Any>;
+
+pub type BackendError = Box<dyn Error + Send + Sync>;
+
+/// Where the decoded audio goes. The player opens a new sink for every loaded track.
+pub trait PlaybackBackend: Send + 'static {
+    fn open_sink(&self) -> Result<(Sink, OutputHandle), BackendError>;
+
+    /// Open the next sinks on the named output device, the system default
+    /// for `None`. Backends without devices ignore it.
+    fn set_output_device(&mut self, _device: Option<String>) {}
+}
+
+/// The names of the output devices of the system, which identify them.
+pub fn output_device_names() -> Vec<String> {
+    match rodio::cpal::default_host().output_devices() {
+        Ok(devices) => devices.filter_map(|x| x.name().ok()).collect(),
+        Err(e) => {
+            warn!("Unable to list output devices: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// The name of the output device the system plays through by default.
+pub fn default_output_device_name() -> Option<String> {
+    rodio::cpal::default_host()
+        .default_output_device()
+        .and_then(|x| x.name().ok())
+}
+
+fn find_output_device(name: &str) -> Option<Device> {
+    rodio::cpal::default_host()
+        .output_devices()
+        .ok()?
+        .find(|x| x.name().is_ok_and(|x| x == name))
+}
+
+/// Play through an output device of the system, the default one unless
+/// another was picked.
+#[derive(Debug, Default, Clone)]
+pub struct RodioBackend {
+    device: Option<String>,
+}
+
+impl PlaybackBackend for RodioBackend {
+    fn open_sink(&self) -> Result<(Sink, OutputHandle), BackendError> {
+        // A picked device that was unplugged falls back to the default
+        let device = self.device.as_deref().and_then(|name| {
+            let device = find_output_device(name);
+            if device.is_none() {
+                warn!(
+                    "Output device {} is gone, playing through the default",
+                    name
+                );
+            }
+            device
+        });
+
+        let (stream, stream_handle) = match device {
+            Some(device) => OutputStream::try_from_device(&device)?,
+            None => OutputStream::try_default()?,
+        };
+        let sink = Sink::try_new(&stream_handle)?;
+
+        Ok((sink, Box::new(stream)))
+    }
+
+    fn set_output_device(&mut self, device: Option<String>) {
+        self.device = device;
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use crate::player::{PlayerStatus, RealtimeFFTStatus};
+
+/// How often progress and visualizer frames are sent together.
+pub const PLAYBACK_TICK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Everything that changed often since the last tick, sent at once.
+#[derive(Debug, Clone)]
+pub struct PlaybackTick {
+    /// Sequence number of the last status sent before this tick, the tick
+    /// covers every event after it.
+    pub since_sequence: u64,
+    /// The latest status, when the position moved.
+    pub status: Option<PlayerStatus>,
+    pub frames: Vec<RealtimeFFTStatus>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CoalescedSignal {
+    Status(PlayerStatus),
+    Tick(PlaybackTick),
+}
+
+// Whether more than the position moved, such a status can't wait for a tick
+fn changes_beyond_progress(previous: &PlayerStatus, status: &PlayerStatus) -> bool {
+    previous.id != status.id
+        || previous.index != status.index
+        || previous.path != status.path
+        || previous.state != status.state
+        || previous.playlist != status.playlist
+        || previous.played != status.played
+        || previous.history_boundary != status.history_boundary
+        || previous.volume != status.volume
+        || previous.playback_mode != status.playback_mode
+        || previous.offline != status.offline
+        || previous.missing != status.missing
+}
+
+/// Holds back progress updates and visualizer frames until the next tick,
+/// so they cross to the UI in one message instead of one each.
+#[derive(Debug, Default)]
+pub struct TickCoalescer {
+    last_sent: Option<PlayerStatus>,
+    pending: Option<PlayerStatus>,
+    frames: Vec<RealtimeFFTStatus>,
+}
+
+impl TickCoalescer {
+    /// Take in a status update.
+    ///
+    /// # Returns
+    /// * `Vec<CoalescedSignal>` - What has to be sent right away, in order.
+    ///   Empty when the status waits for the next tick.
+    pub fn push_status(&mut self, status: PlayerStatus) -> Vec<CoalescedSignal> {
+        let urgent = match &self.last_sent {
+            Some(previous) => changes_beyond_progress(previous, &status),
+            None => true,
+        };
+        if !urgent {
+            self.pending = Some(status);
+            return vec![];
+        }
+
+        // What was held back is sent first, the UI sees events in order
+        let mut signals: Vec<CoalescedSignal> = self
+            .take_tick()
+            .map(CoalescedSignal::Tick)
+            .into_iter()
+            .collect();
+        self.last_sent = Some(status.clone());
+        signals.push(CoalescedSignal::Status(status));
+        signals
+    }
+
+    pub fn push_fft(&mut self, frame: RealtimeFFTStatus) {
+        self.frames.push(frame);
+    }
+
+    /// Take what was held back since the last tick, if anything.
+    pub fn take_tick(&mut self) -> Option<PlaybackTick> {
+        if self.pending.is_none() && self.frames.is_empty() {
+            return None;
+        }
+
+        let since_sequence = self.last_sent.as_ref().map_or(0, |x| x.sequence);
+        let status = self.pending.take();
+        if let Some(status) = &status {
+            self.last_sent = Some(status.clone());
+        }
+
+        Some(PlaybackTick {
+            since_sequence,
+            status,
+            frames: std::mem::take(&mut self.frames),
+        })
+    }
+}
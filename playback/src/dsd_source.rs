@@ -0,0 +1,56 @@
+use std::io::{Read, Seek};
+use std::time::Duration;
+
+use dsd::{DsdError, DsdInfo, DsdReader};
+use rodio::source::SeekError;
+use rodio::Source;
+
+/// A DSF or DSDIFF file converted to PCM while playing.
+///
+/// Output devices rarely take DSD, even as DoP, so the stream is always
+/// converted to 176.4 or 192 kHz PCM and resampled by rodio if needed.
+pub struct DsdSource<R> {
+    reader: DsdReader<R>,
+    info: DsdInfo,
+}
+
+impl<R: Read + Seek> DsdSource<R> {
+    pub fn new(reader: R) -> Result<Self, DsdError> {
+        let reader = DsdReader::new(reader)?;
+        let info = reader.info();
+        Ok(DsdSource { reader, info })
+    }
+}
+
+impl<R: Read + Seek> Iterator for DsdSource<R> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.reader.next()
+    }
+}
+
+impl<R: Read + Seek> Source for DsdSource<R> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.info.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.info.pcm_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.info.duration())
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let frame = (pos.as_secs_f64() * self.info.pcm_rate as f64) as u64;
+        self.reader
+            .seek(frame)
+            .map_err(|e| SeekError::Other(Box::new(e)))
+    }
+}
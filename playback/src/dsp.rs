@@ -0,0 +1,675 @@
+use rodio::Source;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// The limiter keeps peaks just below full scale
+const LIMITER_CEILING: f32 = 0.98;
+const LIMITER_LOOKAHEAD: Duration = Duration::from_millis(5);
+const LIMITER_RELEASE: Duration = Duration::from_millis(150);
+
+// Night mode compresses everything above the threshold, the make-up gain
+// keeps tracks at the reference level as loud as before
+const NIGHT_THRESHOLD_DB: f32 = -24.0;
+const NIGHT_RATIO: f32 = 3.0;
+const NIGHT_REFERENCE_DB: f32 = -18.0;
+const NIGHT_ATTACK: Duration = Duration::from_millis(10);
+const NIGHT_RELEASE: Duration = Duration::from_millis(300);
+// Switching night mode on or off fades between the gains instead of jumping
+const NIGHT_SWITCH_FADE: Duration = Duration::from_millis(50);
+
+// Crossfeed blends the low end of each channel into the other, like
+// speakers heard by both ears
+const CROSSFEED_CUTOFF_HZ: f32 = 700.0;
+const CROSSFEED_LEVEL: f32 = 0.3;
+
+/// Convert a gain in dB to a linear amplitude factor.
+pub fn db_to_factor(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// An amplitude factor shared between the player and a playing source, so
+/// the gain of a track can be changed without reloading it.
+#[derive(Debug, Clone)]
+pub struct SharedGain(Arc<AtomicU32>);
+
+impl SharedGain {
+    pub fn new(factor: f32) -> Self {
+        SharedGain(Arc::new(AtomicU32::new(factor.to_bits())))
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, factor: f32) {
+        self.0.store(factor.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn set_db(&self, db: f32) {
+        self.set(db_to_factor(db));
+    }
+}
+
+impl Default for SharedGain {
+    fn default() -> Self {
+        SharedGain::new(1.0)
+    }
+}
+
+/// Scale every sample by a [`SharedGain`].
+///
+/// The result may exceed full scale, a [`Limiter`] after this stage keeps
+/// it from clipping.
+pub struct Amplified<S> {
+    input: S,
+    gain: SharedGain,
+}
+
+impl<S> Amplified<S> {
+    pub fn new(input: S, gain: SharedGain) -> Self {
+        Amplified { input, gain }
+    }
+}
+
+impl<S> Iterator for Amplified<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.input.next().map(|x| x * self.gain.get())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Amplified<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.input.try_seek(pos)
+    }
+}
+
+/// Settings and state shared by the limiters of all playing tracks.
+#[derive(Debug, Clone)]
+pub struct LimiterControl {
+    enabled: Arc<AtomicBool>,
+    // The lowest gain applied since the last call to `take_reduction`
+    min_gain: Arc<AtomicU32>,
+}
+
+impl LimiterControl {
+    pub fn new(enabled: bool) -> Self {
+        LimiterControl {
+            enabled: Arc::new(AtomicBool::new(enabled)),
+            min_gain: Arc::new(AtomicU32::new(1f32.to_bits())),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// The largest gain reduction in dB since the last call, zero if the
+    /// limiter didn't engage.
+    pub fn take_reduction(&self) -> f32 {
+        let gain = f32::from_bits(self.min_gain.swap(1f32.to_bits(), Ordering::Relaxed));
+        -20.0 * gain.log10()
+    }
+
+    fn report(&self, gain: f32) {
+        // The bit patterns of positive floats sort like the floats themselves
+        self.min_gain.fetch_min(gain.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl Default for LimiterControl {
+    fn default() -> Self {
+        LimiterControl::new(true)
+    }
+}
+
+/// A lookahead peak limiter, the last stage before the output.
+///
+/// Samples are delayed by the lookahead so the gain can come down before a
+/// peak arrives instead of clipping it. Peaks the gain ramp doesn't catch in
+/// time are clamped to the ceiling. When disabled, samples pass through the
+/// delay unchanged.
+pub struct Limiter<S> {
+    input: S,
+    control: LimiterControl,
+    channels: usize,
+    lookahead: usize,
+    // Delayed samples, interleaved
+    delay: VecDeque<f32>,
+    // Frame peaks inside the lookahead window, decreasing, for a sliding maximum
+    peaks: VecDeque<(u64, f32)>,
+    frames_read: u64,
+    frames_written: u64,
+    // Samples of the frame being read from the input
+    pending: Vec<f32>,
+    // Samples of the frame being written to the output
+    ready: VecDeque<f32>,
+    gain: f32,
+    attack: f32,
+    release: f32,
+}
+
+impl<S> Limiter<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, control: LimiterControl) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let sample_rate = input.sample_rate().max(1) as f32;
+        let lookahead = ((sample_rate * LIMITER_LOOKAHEAD.as_secs_f32()) as usize).max(1);
+
+        Limiter {
+            input,
+            control,
+            channels,
+            lookahead,
+            delay: VecDeque::with_capacity((lookahead + 1) * channels),
+            peaks: VecDeque::new(),
+            frames_read: 0,
+            frames_written: 0,
+            pending: Vec::with_capacity(channels),
+            ready: VecDeque::with_capacity(channels),
+            gain: 1.0,
+            // Reach the target gain within the lookahead
+            attack: 1.0 / lookahead as f32,
+            release: 1.0 / (sample_rate * LIMITER_RELEASE.as_secs_f32()),
+        }
+    }
+
+    // Read one frame from the input into the delay line
+    fn fill(&mut self) -> bool {
+        self.pending.clear();
+        for _ in 0..self.channels {
+            match self.input.next() {
+                Some(sample) => self.pending.push(sample),
+                None => break,
+            }
+        }
+        if self.pending.is_empty() {
+            return false;
+        }
+
+        let peak = self.pending.iter().fold(0f32, |peak, x| peak.max(x.abs()));
+        while matches!(self.peaks.back(), Some((_, x)) if *x <= peak) {
+            self.peaks.pop_back();
+        }
+        self.peaks.push_back((self.frames_read, peak));
+        self.frames_read += 1;
+
+        self.delay.extend(self.pending.iter());
+        true
+    }
+
+    // Move the oldest frame of the delay line to the output, limited
+    fn drain(&mut self) {
+        while matches!(self.peaks.front(), Some((frame, _)) if *frame < self.frames_written) {
+            self.peaks.pop_front();
+        }
+        self.frames_written += 1;
+
+        let peak = self.peaks.front().map(|(_, x)| *x).unwrap_or(0.0);
+        let target = if peak > LIMITER_CEILING {
+            LIMITER_CEILING / peak
+        } else {
+            1.0
+        };
+
+        if target < self.gain {
+            // A linear ramp from unity reaches the target before the peak arrives
+            self.gain = (self.gain - (1.0 - target) * self.attack).max(target);
+        } else {
+            self.gain = (self.gain + (1.0 - self.gain) * self.release).min(target);
+        }
+
+        let enabled = self.control.is_enabled();
+        if enabled && self.gain < 1.0 {
+            self.control.report(self.gain);
+        }
+
+        for _ in 0..self.channels {
+            let Some(sample) = self.delay.pop_front() else {
+                break;
+            };
+            let sample = if enabled {
+                (sample * self.gain).clamp(-LIMITER_CEILING, LIMITER_CEILING)
+            } else {
+                sample
+            };
+            self.ready.push_back(sample);
+        }
+    }
+}
+
+impl<S> Iterator for Limiter<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(sample) = self.ready.pop_front() {
+            return Some(sample);
+        }
+
+        // Keep the delay line full, flush it once the input ended
+        while self.delay.len() < (self.lookahead + 1) * self.channels {
+            if !self.fill() {
+                break;
+            }
+        }
+        if self.delay.is_empty() {
+            return None;
+        }
+
+        self.drain();
+        self.ready.pop_front()
+    }
+}
+
+impl<S> Source for Limiter<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.input.try_seek(pos)?;
+        self.delay.clear();
+        self.peaks.clear();
+        self.ready.clear();
+        self.frames_read = 0;
+        self.frames_written = 0;
+        self.gain = 1.0;
+        Ok(())
+    }
+}
+
+/// An on/off setting shared between the player and the playing sources.
+#[derive(Debug, Clone, Default)]
+pub struct SharedSwitch(Arc<AtomicBool>);
+
+impl SharedSwitch {
+    pub fn new(enabled: bool) -> Self {
+        SharedSwitch(Arc::new(AtomicBool::new(enabled)))
+    }
+
+    pub fn get(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// Play the average of all channels on every channel while the switch is on.
+///
+/// The channel count stays the same, so switching doesn't reopen the output.
+pub struct Mono<S> {
+    input: S,
+    switch: SharedSwitch,
+    channels: usize,
+    // The rest of the frame being written to the output
+    frame: VecDeque<f32>,
+}
+
+impl<S> Mono<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, switch: SharedSwitch) -> Self {
+        let channels = input.channels().max(1) as usize;
+
+        Mono {
+            input,
+            switch,
+            channels,
+            frame: VecDeque::with_capacity(channels),
+        }
+    }
+}
+
+impl<S> Iterator for Mono<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(sample) = self.frame.pop_front() {
+            return Some(sample);
+        }
+
+        if self.channels == 1 {
+            return self.input.next();
+        }
+
+        // Work on whole frames so switching never swaps channels
+        for _ in 0..self.channels {
+            match self.input.next() {
+                Some(sample) => self.frame.push_back(sample),
+                None => break,
+            }
+        }
+        if self.frame.is_empty() {
+            return None;
+        }
+
+        if self.switch.get() {
+            let average = self.frame.iter().sum::<f32>() / self.frame.len() as f32;
+            self.frame.iter_mut().for_each(|x| *x = average);
+        }
+        self.frame.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Mono<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.input.try_seek(pos)?;
+        self.frame.clear();
+        Ok(())
+    }
+}
+
+/// Gain in dB night mode applies at an RMS level in dBFS.
+fn night_gain_db(level_db: f32) -> f32 {
+    let slope = 1.0 - 1.0 / NIGHT_RATIO;
+    let makeup = (NIGHT_REFERENCE_DB - NIGHT_THRESHOLD_DB) * slope;
+
+    makeup - (level_db - NIGHT_THRESHOLD_DB).max(0.0) * slope
+}
+
+/// A downward compressor for listening at low volume while the switch is
+/// on, loud passages are brought down and quiet ones up.
+///
+/// The level is followed on the RMS of whole frames so all channels get the
+/// same gain. Peaks the make-up gain pushes over full scale are left to the
+/// [`Limiter`] after this stage.
+pub struct Compressor<S> {
+    input: S,
+    switch: SharedSwitch,
+    channels: usize,
+    // Mean square of the frames, smoothed
+    envelope: f32,
+    // How much of the compression is applied, fades when switching
+    mix: f32,
+    attack: f32,
+    release: f32,
+    fade: f32,
+    // The rest of the frame being written to the output
+    frame: VecDeque<f32>,
+}
+
+impl<S> Compressor<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, switch: SharedSwitch) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let sample_rate = input.sample_rate().max(1) as f32;
+        let coefficient = |x: Duration| 1.0 - (-1.0 / (sample_rate * x.as_secs_f32())).exp();
+
+        Compressor {
+            input,
+            mix: if switch.get() { 1.0 } else { 0.0 },
+            switch,
+            channels,
+            envelope: 0.0,
+            attack: coefficient(NIGHT_ATTACK),
+            release: coefficient(NIGHT_RELEASE),
+            fade: coefficient(NIGHT_SWITCH_FADE),
+            frame: VecDeque::with_capacity(channels),
+        }
+    }
+}
+
+impl<S> Iterator for Compressor<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(sample) = self.frame.pop_front() {
+            return Some(sample);
+        }
+
+        for _ in 0..self.channels {
+            match self.input.next() {
+                Some(sample) => self.frame.push_back(sample),
+                None => break,
+            }
+        }
+        if self.frame.is_empty() {
+            return None;
+        }
+
+        // The level is followed while switched off too, so switching on
+        // starts from the right gain
+        let square = self.frame.iter().map(|x| x * x).sum::<f32>() / self.frame.len() as f32;
+        let coefficient = if square > self.envelope {
+            self.attack
+        } else {
+            self.release
+        };
+        self.envelope += (square - self.envelope) * coefficient;
+
+        let target = if self.switch.get() { 1.0 } else { 0.0 };
+        self.mix += (target - self.mix) * self.fade;
+        if self.mix < 1e-4 {
+            self.mix = 0.0;
+        }
+
+        let level = 10.0 * self.envelope.max(1e-12).log10();
+        let factor = db_to_factor(self.mix * night_gain_db(level));
+        self.frame.iter_mut().for_each(|x| *x *= factor);
+        self.frame.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Compressor<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.input.try_seek(pos)?;
+        self.frame.clear();
+        Ok(())
+    }
+}
+
+/// Feed a low-passed share of each stereo channel into the other while the
+/// switch is on, which makes hard-panned mixes easier on headphones.
+///
+/// Audio in the center keeps its level. Sources without exactly two
+/// channels pass through.
+pub struct Crossfeed<S> {
+    input: S,
+    switch: SharedSwitch,
+    channels: usize,
+    // The low-passed left and right channels
+    lowpass: [f32; 2],
+    coefficient: f32,
+    // The rest of the frame being written to the output
+    frame: VecDeque<f32>,
+}
+
+impl<S> Crossfeed<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, switch: SharedSwitch) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let sample_rate = input.sample_rate().max(1) as f32;
+
+        Crossfeed {
+            input,
+            switch,
+            channels,
+            lowpass: [0.0; 2],
+            coefficient: 1.0 - (-std::f32::consts::TAU * CROSSFEED_CUTOFF_HZ / sample_rate).exp(),
+            frame: VecDeque::with_capacity(channels),
+        }
+    }
+}
+
+impl<S> Iterator for Crossfeed<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(sample) = self.frame.pop_front() {
+            return Some(sample);
+        }
+
+        if self.channels != 2 {
+            return self.input.next();
+        }
+
+        for _ in 0..2 {
+            match self.input.next() {
+                Some(sample) => self.frame.push_back(sample),
+                None => break,
+            }
+        }
+        if self.frame.len() < 2 {
+            return self.frame.pop_front();
+        }
+
+        // The filters run while switched off too, so switching is smooth
+        let (left, right) = (self.frame[0], self.frame[1]);
+        self.lowpass[0] += (left - self.lowpass[0]) * self.coefficient;
+        self.lowpass[1] += (right - self.lowpass[1]) * self.coefficient;
+
+        if self.switch.get() {
+            let scale = 1.0 / (1.0 + CROSSFEED_LEVEL);
+            self.frame[0] = (left + self.lowpass[1] * CROSSFEED_LEVEL) * scale;
+            self.frame[1] = (right + self.lowpass[0] * CROSSFEED_LEVEL) * scale;
+        }
+        self.frame.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Crossfeed<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.input.try_seek(pos)?;
+        self.frame.clear();
+        self.lowpass = [0.0; 2];
+        Ok(())
+    }
+}
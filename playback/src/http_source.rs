@@ -0,0 +1,133 @@
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::time::Duration;
+
+use tracing::debug;
+use ureq::{Agent, AgentBuilder};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A media file read over HTTP.
+///
+/// Seeking drops the response being read, the next read asks the server
+/// for the rest of the file from the new position with a `Range` request.
+pub struct HttpSource {
+    url: String,
+    agent: Agent,
+    len: u64,
+    position: u64,
+    reader: Option<Box<dyn Read + Send + Sync>>,
+}
+
+fn to_io_error(e: ureq::Error) -> io::Error {
+    match e {
+        ureq::Error::Status(404, _) | ureq::Error::Status(410, _) => {
+            io::Error::new(ErrorKind::NotFound, e.to_string())
+        }
+        ureq::Error::Status(_, _) => io::Error::other(e.to_string()),
+        ureq::Error::Transport(_) => io::Error::new(ErrorKind::NotConnected, e.to_string()),
+    }
+}
+
+// The length of the whole file, from `bytes 0-99/1234` or the content length
+fn length_of(response: &ureq::Response) -> Option<u64> {
+    response
+        .header("Content-Range")
+        .and_then(|x| x.rsplit('/').next())
+        .and_then(|x| x.parse().ok())
+        .or_else(|| response.header("Content-Length")?.parse().ok())
+}
+
+impl HttpSource {
+    pub fn open(url: &str) -> io::Result<Self> {
+        let agent = AgentBuilder::new()
+            .timeout_connect(CONNECT_TIMEOUT)
+            .timeout_read(READ_TIMEOUT)
+            .build();
+
+        let response = agent
+            .get(url)
+            .set("Range", "bytes=0-")
+            .call()
+            .map_err(to_io_error)?;
+        let len = length_of(&response).unwrap_or(0);
+        if len == 0 {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "file is empty"));
+        }
+
+        Ok(HttpSource {
+            url: url.to_string(),
+            agent,
+            len,
+            position: 0,
+            reader: Some(response.into_reader()),
+        })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn reopen(&mut self) -> io::Result<Box<dyn Read + Send + Sync>> {
+        debug!("Reading {} from {}", self.url, self.position);
+        let response = self
+            .agent
+            .get(&self.url)
+            .set("Range", &format!("bytes={}-", self.position))
+            .call()
+            .map_err(to_io_error)?;
+
+        // Servers without range support send the whole file
+        let skip = if response.status() == 206 {
+            0
+        } else {
+            self.position
+        };
+        let mut reader = response.into_reader();
+        io::copy(&mut (&mut reader).take(skip), &mut io::sink())?;
+
+        Ok(reader)
+    }
+}
+
+impl Read for HttpSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.len {
+            return Ok(0);
+        }
+
+        let reader = match self.reader.as_mut() {
+            Some(reader) => reader,
+            None => {
+                let reader = self.reopen()?;
+                self.reader.insert(reader)
+            }
+        };
+        let read = reader.read(buf)?;
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl Seek for HttpSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.len.checked_add_signed(x),
+            SeekFrom::Current(x) => self.position.checked_add_signed(x),
+        }
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "seek before the start"))?;
+
+        if position != self.position {
+            self.reader = None;
+            self.position = position;
+        }
+
+        Ok(position)
+    }
+}
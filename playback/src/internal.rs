@@ -1,14 +1,29 @@
-use log::{debug, error, info, warn};
-use rodio::{Decoder, OutputStream, Sink, Source};
-use std::fs::File;
+use rodio::buffer::SamplesBuffer;
+use rodio::cpal::Sample;
+use rodio::decoder::DecoderError;
+use rodio::{Decoder, Sink, Source};
+use std::collections::HashMap;
 use std::io::BufReader;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::{interval, sleep_until, Duration, Instant};
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
+use crate::backend::{OutputHandle, PlaybackBackend};
+use crate::dsd_source::DsdSource;
+use crate::dsp::{
+    Amplified, Compressor, Crossfeed, Limiter, LimiterControl, Mono, SharedGain, SharedSwitch,
+};
+use crate::preview::{mix_transition, TransitionPreview};
+use crate::progress::{ProgressThrottle, PROGRESS_TICK};
+use crate::queue::{AlbumTrack, DuplicatePolicy, PlayQueue, PlaylistItem};
 use crate::realtime_fft::RealTimeFFT;
+use crate::seek_source::TableSeekSource;
+use crate::sequence::EventSender;
+use crate::source::{is_remote_url, open_media_source, OpenError, StreamResolver};
+use crate::trim::{is_trimmed_by_decoder, TrimmedSource};
 
 #[derive(Debug)]
 pub enum PlayerCommand {
@@ -20,10 +35,69 @@ pub enum PlayerCommand {
     Previous,
     Switch(usize),
     Seek(f64),
-    AddToPlaylist { id: i32, path: PathBuf },
+    AddToPlaylist {
+        id: i32,
+        path: PathBuf,
+        album: Option<AlbumTrack>,
+        replay_gain: Option<f32>,
+    },
+    // The IDs of the items left out by the policy are sent back
+    AddItemsToPlaylist {
+        items: Vec<PlaylistItem>,
+        policy: DuplicatePolicy,
+        skipped: oneshot::Sender<Vec<i32>>,
+    },
     RemoveFromPlaylist { index: usize },
     ClearPlaylist,
     MovePlayListItem { old_index: usize, new_index: usize },
+    ReorderPlaylist(Vec<usize>),
+    SetVolume(f32),
+    SetPlaybackMode(PlaybackMode),
+    SetCrossfade(Duration),
+    SetPreamp(f32),
+    SetLimiter(bool),
+    SetMono(bool),
+    SetNightMode(bool),
+    SetCrossfeed(bool),
+    SetOutputDevice(Option<String>),
+    SetGainOffset {
+        id: i32,
+        offset: f32,
+    },
+    SetMixPoints {
+        id: i32,
+        points: Option<MixPoints>,
+    },
+    SetProgressInterval(Duration),
+    SetCoarseProgress(bool),
+    SetStreamResolver(StreamResolver),
+    SetLibraryRoot(PathBuf),
+    // The length of the crossfade is sent back, nothing if a track failed
+    PreviewTransition {
+        preview: Box<TransitionPreview>,
+        done: oneshot::Sender<Option<Duration>>,
+    },
+}
+
+/// Where a track is best mixed in and out at, measured from its start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MixPoints {
+    // The track reaches its energy, a quieter intro can play under the previous track
+    pub mix_in: Duration,
+    // The track fades away after this
+    pub mix_out: Duration,
+}
+
+impl MixPoints {
+    /// Points in seconds found by the analysis, `None` if they are invalid.
+    pub fn from_seconds(mix_in: f64, mix_out: f64) -> Option<Self> {
+        (mix_in.is_finite() && mix_out.is_finite() && mix_in >= 0.0 && mix_out >= mix_in).then(
+            || MixPoints {
+                mix_in: Duration::from_secs_f64(mix_in),
+                mix_out: Duration::from_secs_f64(mix_out),
+            },
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -53,20 +127,105 @@ pub enum PlayerEvent {
         path: PathBuf,
         error: String,
     },
+    SourceOffline {
+        id: i32,
+        index: usize,
+        path: PathBuf,
+    },
+    FileMissing {
+        id: i32,
+        index: usize,
+        path: PathBuf,
+    },
     Progress {
         id: i32,
         index: usize,
         path: PathBuf,
         position: Duration,
     },
-    PlaylistUpdated(Vec<i32>),
+    PlaylistUpdated {
+        ids: Vec<i32>,
+        // Whether each item was played in this session
+        played: Vec<bool>,
+        // Items before this index are the history of the queue
+        history_boundary: usize,
+    },
     RealtimeFFT(Vec<f32>),
+    VolumeUpdated(f32),
+    PlaybackModeUpdated(PlaybackMode),
+    // Gain reduction of the limiter in dB, sent while it is engaged and
+    // once with zero when it releases
+    LimiterUpdated(f32),
+    // The output device sinks open on, `None` for the system default
+    OutputDeviceChanged(Option<String>),
 }
 
-#[derive(Debug, Clone)]
-pub struct PlaylistItem {
-    pub id: i32,
-    pub path: PathBuf,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackMode {
+    Sequential,
+    RepeatAll,
+    RepeatOne,
+}
+
+impl From<u32> for PlaybackMode {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => PlaybackMode::RepeatAll,
+            2 => PlaybackMode::RepeatOne,
+            _ => PlaybackMode::Sequential,
+        }
+    }
+}
+
+impl From<PlaybackMode> for u32 {
+    fn from(mode: PlaybackMode) -> Self {
+        match mode {
+            PlaybackMode::Sequential => 0,
+            PlaybackMode::RepeatAll => 1,
+            PlaybackMode::RepeatOne => 2,
+        }
+    }
+}
+
+// How early the next track of a gapless pair is appended to the sink
+const GAPLESS_LEAD: Duration = Duration::from_secs(2);
+// Intros longer than this aren't overlapped completely
+const MAX_MIX_LEAD: Duration = Duration::from_secs(15);
+
+type DecodedSource = Box<dyn Source<Item = f32> + Send>;
+
+// The length of a crossfade and when it is over, for a track of the given
+// duration followed by another
+fn crossfade_window(
+    crossfade: Duration,
+    current: Option<&MixPoints>,
+    next: Option<&MixPoints>,
+    duration: Duration,
+) -> (Duration, Duration) {
+    let end = current
+        .map(|x| x.mix_out)
+        .filter(|x| !x.is_zero() && *x < duration)
+        .unwrap_or(duration);
+    let lead = next
+        .map(|x| x.mix_in.min(MAX_MIX_LEAD))
+        .unwrap_or_default()
+        .max(crossfade);
+
+    (lead, end)
+}
+
+#[derive(Debug)]
+enum LoadError {
+    Open(OpenError),
+    Decode(rodio::decoder::DecoderError),
+}
+
+// The previous track while it fades out during a crossfade
+struct FadingSink {
+    sink: Sink,
+    _stream: Option<OutputHandle>,
+    started: Instant,
+    length: Duration,
 }
 
 #[derive(Debug, PartialEq)]
@@ -78,43 +237,96 @@ enum InternalPlaybackState {
 
 pub(crate) struct PlayerInternal {
     commands: mpsc::UnboundedReceiver<PlayerCommand>,
-    event_sender: mpsc::UnboundedSender<PlayerEvent>,
+    event_sender: EventSender,
+    backend: Box<dyn PlaybackBackend>,
     realtime_fft: Arc<Mutex<RealTimeFFT>>,
-    playlist: Vec<PlaylistItem>,
+    queue: PlayQueue,
     current_track_id: Option<i32>,
-    current_track_index: Option<usize>,
     current_track_path: Option<PathBuf>,
+    current_track_duration: Option<Duration>,
     sink: Option<Sink>,
-    _stream: Option<OutputStream>,
+    _stream: Option<OutputHandle>,
     state: InternalPlaybackState,
+    volume: f32,
+    playback_mode: PlaybackMode,
     debounce_timer: Option<Instant>,
+    // Position reported by the previous progress tick, used to detect stalls
+    last_position: Option<Duration>,
+    // Zero disables crossfading
+    crossfade: Duration,
+    transition_prepared: bool,
+    // The next track, already appended to the current sink
+    gapless_next: Option<(PlaylistItem, Option<Duration>)>,
+    fading: Option<FadingSink>,
+    // A transition being auditioned, playback is paused meanwhile
+    preview: Option<(Sink, OutputHandle)>,
+    // Global gain in dB applied to every track
+    preamp: f32,
+    // User adjustments in dB on top of ReplayGain, by file ID
+    gain_offsets: HashMap<i32, f32>,
+    // Mix points of the queued tracks that were analysed, by file ID
+    mix_points: HashMap<i32, MixPoints>,
+    // Gains of the decoded tracks, kept so they can be changed while playing
+    track_gains: HashMap<i32, SharedGain>,
+    limiter: LimiterControl,
+    limiting: bool,
+    mono: SharedSwitch,
+    night_mode: SharedSwitch,
+    crossfeed: SharedSwitch,
+    progress: ProgressThrottle,
+    // Signs the addresses of queued remote files
+    stream_resolver: Option<StreamResolver>,
+    // Tells an offline library from files missing in it
+    library_root: Option<PathBuf>,
     cancellation_token: CancellationToken,
 }
 
 impl PlayerInternal {
     pub fn new(
         commands: mpsc::UnboundedReceiver<PlayerCommand>,
-        event_sender: mpsc::UnboundedSender<PlayerEvent>,
+        event_sender: EventSender,
+        backend: Box<dyn PlaybackBackend>,
         cancellation_token: CancellationToken,
     ) -> Self {
         Self {
             commands,
             event_sender,
-            playlist: Vec::new(),
+            backend,
+            queue: PlayQueue::new(),
             current_track_id: None,
-            current_track_index: None,
             current_track_path: None,
+            current_track_duration: None,
             sink: None,
             _stream: None,
             realtime_fft: Arc::new(Mutex::new(RealTimeFFT::new(512))),
             state: InternalPlaybackState::Stopped,
+            volume: 1.0,
+            playback_mode: PlaybackMode::Sequential,
             debounce_timer: None,
+            last_position: None,
+            crossfade: Duration::ZERO,
+            transition_prepared: false,
+            gapless_next: None,
+            fading: None,
+            preview: None,
+            preamp: 0.0,
+            gain_offsets: HashMap::new(),
+            mix_points: HashMap::new(),
+            track_gains: HashMap::new(),
+            limiter: LimiterControl::default(),
+            limiting: false,
+            mono: SharedSwitch::default(),
+            night_mode: SharedSwitch::default(),
+            crossfeed: SharedSwitch::default(),
+            progress: ProgressThrottle::default(),
+            stream_resolver: None,
+            library_root: None,
             cancellation_token,
         }
     }
 
     pub async fn run(&mut self) {
-        let mut progress_interval = interval(Duration::from_millis(100));
+        let mut progress_interval = interval(PROGRESS_TICK);
 
         let mut fft_receiver = self.realtime_fft.lock().unwrap().subscribe();
         loop {
@@ -137,10 +349,28 @@ impl PlayerInternal {
                         PlayerCommand::Previous => self.previous(),
                         PlayerCommand::Switch(index) => self.switch(index),
                         PlayerCommand::Seek(position) => self.seek(position),
-                        PlayerCommand::AddToPlaylist { id, path } => self.add_to_playlist(id, path).await,
+                        PlayerCommand::AddToPlaylist { id, path, album, replay_gain } => self.add_to_playlist(id, path, album, replay_gain).await,
+                        PlayerCommand::AddItemsToPlaylist { items, policy, skipped } => self.add_items_to_playlist(items, policy, skipped),
                         PlayerCommand::RemoveFromPlaylist { index } => self.remove_from_playlist(index).await,
                         PlayerCommand::ClearPlaylist => self.clear_playlist().await,
-                        PlayerCommand::MovePlayListItem {old_index, new_index} => self.move_playlist_item(old_index, new_index).await
+                        PlayerCommand::MovePlayListItem {old_index, new_index} => self.move_playlist_item(old_index, new_index).await,
+                        PlayerCommand::ReorderPlaylist(order) => self.reorder_playlist(order),
+                        PlayerCommand::SetVolume(volume) => self.set_volume(volume),
+                        PlayerCommand::SetPlaybackMode(mode) => self.set_playback_mode(mode),
+                        PlayerCommand::SetCrossfade(duration) => self.set_crossfade(duration),
+                        PlayerCommand::SetPreamp(preamp) => self.set_preamp(preamp),
+                        PlayerCommand::SetLimiter(enabled) => self.set_limiter(enabled),
+                        PlayerCommand::SetMono(enabled) => self.set_mono(enabled),
+                        PlayerCommand::SetNightMode(enabled) => self.set_night_mode(enabled),
+                        PlayerCommand::SetCrossfeed(enabled) => self.set_crossfeed(enabled),
+                        PlayerCommand::SetOutputDevice(device) => self.set_output_device(device),
+                        PlayerCommand::SetGainOffset { id, offset } => self.set_gain_offset(id, offset),
+                        PlayerCommand::SetMixPoints { id, points } => self.set_mix_points(id, points),
+                        PlayerCommand::SetProgressInterval(interval) => self.set_progress_interval(interval),
+                        PlayerCommand::SetCoarseProgress(coarse) => self.set_coarse_progress(coarse),
+                        PlayerCommand::SetStreamResolver(resolver) => self.stream_resolver = Some(resolver),
+                        PlayerCommand::SetLibraryRoot(path) => self.library_root = Some(path),
+                        PlayerCommand::PreviewTransition { preview, done } => self.preview_transition(*preview, done),
                     }
                 },
                 Ok(fft_data) = fft_receiver.recv() => {
@@ -171,95 +401,382 @@ impl PlayerInternal {
         }
     }
 
+    // The decoded file without any processing
+    fn open_track(&self, item: &PlaylistItem) -> Result<DecodedSource, LoadError> {
+        let file = open_media_source(
+            &item.path,
+            self.stream_resolver.as_ref(),
+            self.library_root.as_deref(),
+        )
+        .map_err(LoadError::Open)?;
+        let source: DecodedSource = if dsd::is_dsd_path(&item.path) {
+            let source = DsdSource::new(BufReader::new(file))
+                .map_err(|e| LoadError::Decode(DecoderError::IoError(e.to_string())))?;
+            Box::new(source)
+        } else {
+            let source = Decoder::new(BufReader::new(file)).map_err(LoadError::Decode)?;
+            let source: DecodedSource = Box::new(source.convert_samples::<f32>());
+            let source: DecodedSource = match &item.seek_table {
+                Some(table) if !is_remote_url(&item.path) => Box::new(TableSeekSource::new(
+                    item.path.clone(),
+                    table.clone(),
+                    source,
+                )),
+                _ => source,
+            };
+            match item.trim.filter(|_| !is_trimmed_by_decoder(&item.path)) {
+                Some(trim) => Box::new(TrimmedSource::new(source, trim)),
+                None => source,
+            }
+        };
+
+        Ok(source)
+    }
+
+    fn decode(
+        &mut self,
+        item: &PlaylistItem,
+    ) -> Result<(DecodedSource, Option<Duration>), LoadError> {
+        let source = self.open_track(item)?;
+        let duration = source.total_duration();
+
+        // Create a channel to transfer FFT data
+        let (fft_tx, mut fft_rx) = mpsc::unbounded_channel();
+
+        // Create a new thread for calculating realtime FFT
+        let realtime_fft = Arc::clone(&self.realtime_fft);
+        tokio::spawn(async move {
+            while let Some(data) = fft_rx.recv().await {
+                realtime_fft.lock().unwrap().add_data(data);
+            }
+        });
+
+        let source = source.periodic_access(Duration::from_millis(16), move |sample| {
+            let data: Vec<i16> = sample
+                .take(sample.channels() as usize)
+                .map(|x| x.to_sample::<i16>())
+                .collect();
+            // The receiver is gone once the track was replaced
+            let _ = fft_tx.send(data);
+        });
+
+        let gain = self.track_gains.entry(item.id).or_default().clone();
+        gain.set_db(self.gain_of(item));
+        let source = Amplified::new(source, gain);
+
+        Ok((self.output_chain(source), duration))
+    }
+
+    // The processing after the gain of a track
+    fn output_chain<S>(&self, source: S) -> DecodedSource
+    where
+        S: Source<Item = f32> + Send + 'static,
+    {
+        let source = Compressor::new(source, self.night_mode.clone());
+        let source = Crossfeed::new(source, self.crossfeed.clone());
+
+        // Everything before the limiter may push past full scale
+        let source = Limiter::new(source, self.limiter.clone());
+        let source = Mono::new(source, self.mono.clone());
+
+        Box::new(source)
+    }
+
     fn load(&mut self, index: Option<usize>) {
-        if let Some(index) = index {
-            debug!("Loading track at index: {}", index);
-            let item = &self.playlist[index];
-            let file = File::open(item.path.clone());
-            match file {
-                Ok(file) => {
-                    let source = Decoder::new(BufReader::new(file));
-
-                    match source {
-                        Ok(source) => {
-                            let (stream, stream_handle) = OutputStream::try_default().unwrap();
-                            let sink = Sink::try_new(&stream_handle).unwrap();
-                            // Create a channel to transfer FFT data
-                            let (fft_tx, mut fft_rx) = mpsc::unbounded_channel();
-
-                            // Create a new thread for calculating realtime FFT
-                            let realtime_fft = Arc::clone(&self.realtime_fft);
-                            tokio::spawn(async move {
-                                while let Some(data) = fft_rx.recv().await {
-                                    realtime_fft.lock().unwrap().add_data(data);
-                                }
-                            });
-
-                            sink.append(source.periodic_access(
-                                Duration::from_millis(16),
-                                move |sample| {
-                                    let data: Vec<i16> =
-                                        sample.take(sample.channels() as usize).collect();
-                                    fft_tx.send(data).unwrap();
-                                },
-                            ));
-
-                            self.sink = Some(sink);
-                            self._stream = Some(stream);
-                            self.current_track_index = Some(index);
-                            self.current_track_id = Some(item.id);
-                            self.current_track_path = Some(item.path.clone());
-                            info!("Track loaded: {:?}", item.path);
-                            self.event_sender
-                                .send(PlayerEvent::Playing {
-                                    id: self.current_track_id.unwrap(),
-                                    index: self.current_track_index.unwrap(),
-                                    path: self.current_track_path.clone().unwrap(),
-                                    position: Duration::new(0, 0),
-                                })
-                                .unwrap();
-                            self.state = InternalPlaybackState::Playing;
-                        }
-                        Err(e) => {
-                            error!("Failed to decode audio: {:?}", e);
-                            self.event_sender
-                                .send(PlayerEvent::Error {
-                                    id: self.current_track_id.unwrap(),
-                                    index,
-                                    path: item.path.clone(),
-                                    error: "Failed to decode audio".to_string(),
-                                })
-                                .unwrap();
-                            self.state = InternalPlaybackState::Stopped;
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to open file: {:?}", e);
-                    self.event_sender
-                        .send(PlayerEvent::Error {
-                            id: self.current_track_id.unwrap(),
-                            index,
-                            path: item.path.clone(),
-                            error: "Failed to open file".to_string(),
-                        })
-                        .unwrap();
-                    self.state = InternalPlaybackState::Stopped;
-                }
+        self.preview = None;
+
+        let Some(mut index) = index else {
+            error!("Load command received without index");
+            return;
+        };
+
+        // Missing files are skipped until a track loads or none is left
+        while let Some(next) = self.load_index(index) {
+            index = next;
+        }
+    }
+
+    // Returns the track to try next when the file is missing
+    fn load_index(&mut self, index: usize) -> Option<usize> {
+        debug!("Loading track at index: {}", index);
+        let item = match self.queue.get(index) {
+            Some(item) => item.clone(),
+            None => {
+                error!("Load command received but index {} is out of bounds", index);
+                return None;
+            }
+        };
+
+        match self.decode(&item) {
+            Ok((source, duration)) => {
+                let (sink, stream) = self.backend.open_sink().unwrap();
+                sink.set_volume(self.volume);
+                sink.append(source);
+
+                self.cancel_transition();
+                self.sink = Some(sink);
+                self._stream = Some(stream);
+                self.last_position = None;
+                self.queue.select(index);
+                self.queue.set_available(index, true);
+                // The track joins the history
+                self.schedule_playlist_update();
+                self.current_track_id = Some(item.id);
+                self.current_track_path = Some(item.path.clone());
+                self.current_track_duration = duration;
+                info!("Track loaded: {:?}", item.path);
+                self.event_sender
+                    .send(PlayerEvent::Playing {
+                        id: self.current_track_id.unwrap(),
+                        index: self.queue.current_index().unwrap(),
+                        path: self.current_track_path.clone().unwrap(),
+                        position: Duration::new(0, 0),
+                    })
+                    .unwrap();
+                self.state = InternalPlaybackState::Playing;
+            }
+            Err(LoadError::Decode(e)) => {
+                error!("Failed to decode audio: {:?}", e);
+                self.event_sender
+                    .send(PlayerEvent::Error {
+                        id: item.id,
+                        index,
+                        path: item.path.clone(),
+                        error: "Failed to decode audio".to_string(),
+                    })
+                    .unwrap();
+                self.state = InternalPlaybackState::Stopped;
             }
+            Err(LoadError::Open(OpenError::Offline(e))) => {
+                warn!("Source offline: {:?} ({})", item.path, e);
+                self.event_sender
+                    .send(PlayerEvent::SourceOffline {
+                        id: item.id,
+                        index,
+                        path: item.path.clone(),
+                    })
+                    .unwrap();
+                self.state = InternalPlaybackState::Stopped;
+            }
+            Err(LoadError::Open(OpenError::Missing(e))) => {
+                warn!("File missing from disk: {:?} ({})", item.path, e);
+                self.queue.set_available(index, false);
+                self.event_sender
+                    .send(PlayerEvent::FileMissing {
+                        id: item.id,
+                        index,
+                        path: item.path.clone(),
+                    })
+                    .unwrap();
+                return self.skip_missing(index);
+            }
+            Err(LoadError::Open(OpenError::Unavailable(e))) => {
+                error!("Failed to open file: {:?}", e);
+                self.event_sender
+                    .send(PlayerEvent::Error {
+                        id: item.id,
+                        index,
+                        path: item.path.clone(),
+                        error: "Failed to open file".to_string(),
+                    })
+                    .unwrap();
+                self.state = InternalPlaybackState::Stopped;
+            }
+        }
+
+        None
+    }
+
+    /// Prepare the next track shortly before the current one ends.
+    ///
+    /// Consecutive tracks of an album are appended to the current sink and
+    /// play without a gap, everything else crossfades on a second sink.
+    fn prepare_transition(&mut self, position: Duration) {
+        if self.crossfade.is_zero()
+            || self.transition_prepared
+            || self.state != InternalPlaybackState::Playing
+            || self.playback_mode == PlaybackMode::RepeatOne
+        {
+            return;
+        }
+
+        let Some(duration) = self.current_track_duration else {
+            return;
+        };
+        let Some(current) = self.queue.current().cloned() else {
+            return;
+        };
+        let Some(next_index) = self.queue.next_index(self.playback_mode) else {
+            return;
+        };
+        let next = self.queue.get(next_index).unwrap().clone();
+
+        let gapless = next.follows(&current);
+        let (lead, end) = if gapless {
+            (GAPLESS_LEAD, duration)
         } else {
-            error!("Load command received without index");
+            self.mix_window(&current, &next, duration)
+        };
+        if position + lead < end {
+            return;
+        }
+
+        // Whatever happens, don't try again on every tick
+        self.transition_prepared = true;
+
+        let (source, next_duration) = match self.decode(&next) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                // The regular end of track handling reports the error
+                debug!("Unable to prepare the next track: {:?}", e);
+                return;
+            }
+        };
+
+        if gapless {
+            debug!("Queueing track {} without a gap", next_index);
+            self.sink.as_ref().unwrap().append(source);
+            self.gapless_next = Some((next, next_duration));
+            return;
+        }
+
+        let (sink, stream) = match self.backend.open_sink() {
+            Ok(output) => output,
+            Err(e) => {
+                error!("Unable to open a sink for crossfading: {:?}", e);
+                return;
+            }
+        };
+        sink.set_volume(self.volume);
+        sink.append(source.fade_in(lead));
+
+        debug!("Crossfading into track {}", next_index);
+        let fading_sink = self.sink.replace(sink).unwrap();
+        let fading_stream = self._stream.replace(stream);
+        self.fading = Some(FadingSink {
+            sink: fading_sink,
+            _stream: fading_stream,
+            started: Instant::now(),
+            length: lead,
+        });
+
+        self.start_next_track(next_index, next, next_duration);
+    }
+
+    /// How long a crossfade lasts and when it has to be over.
+    ///
+    /// With mix points from the analysis, the current track is faded out by
+    /// its mix-out point and the next one fades in until its mix-in point,
+    /// so a quiet intro plays under the outro. The crossfade setting is the
+    /// shortest fade.
+    fn mix_window(
+        &self,
+        current: &PlaylistItem,
+        next: &PlaylistItem,
+        duration: Duration,
+    ) -> (Duration, Duration) {
+        crossfade_window(
+            self.crossfade,
+            self.mix_points.get(&current.id),
+            self.mix_points.get(&next.id),
+            duration,
+        )
+    }
+
+    // The appended track of a gapless pair took over the sink
+    fn finish_gapless_transition(&mut self) {
+        let (next, next_duration) = self.gapless_next.take().unwrap();
+
+        // The queue may have changed since the track was appended
+        let next_index = self
+            .queue
+            .next_index(self.playback_mode)
+            .filter(|x| self.queue.get(*x).map(|x| x.id) == Some(next.id));
+
+        match next_index {
+            Some(next_index) => self.start_next_track(next_index, next, next_duration),
+            None => {
+                warn!("Playlist changed during a gapless transition, reloading");
+                self.end_of_track();
+            }
+        }
+    }
+
+    fn start_next_track(&mut self, index: usize, item: PlaylistItem, duration: Option<Duration>) {
+        self.event_sender
+            .send(PlayerEvent::EndOfTrack {
+                id: self.current_track_id.unwrap(),
+                index: self.queue.current_index().unwrap(),
+                path: self.current_track_path.clone().unwrap(),
+            })
+            .unwrap();
+
+        self.queue.select(index);
+        self.schedule_playlist_update();
+        self.current_track_id = Some(item.id);
+        self.current_track_path = Some(item.path.clone());
+        self.current_track_duration = duration;
+        self.last_position = None;
+        self.transition_prepared = false;
+        info!("Track started: {:?}", item.path);
+
+        let position = self.sink.as_ref().map(|x| x.get_pos()).unwrap_or_default();
+        self.event_sender
+            .send(PlayerEvent::Playing {
+                id: item.id,
+                index,
+                path: item.path,
+                position,
+            })
+            .unwrap();
+    }
+
+    fn fade_out(&mut self) {
+        let Some(fading) = &self.fading else {
+            return;
+        };
+
+        let progress = fading.started.elapsed().as_secs_f32() / fading.length.as_secs_f32();
+        if progress >= 1.0 || fading.sink.empty() {
+            self.fading = None;
+        } else {
+            fading.sink.set_volume(self.volume * (1.0 - progress));
+        }
+    }
+
+    fn cancel_transition(&mut self) {
+        self.fading = None;
+        self.gapless_next = None;
+        self.transition_prepared = false;
+    }
+
+    // Every failed item is marked unavailable before this is called, so the
+    // loop in load() ends at the latest when the queue runs out.
+    fn skip_missing(&mut self, index: usize) -> Option<usize> {
+        match self.queue.next_available_index(index, self.playback_mode) {
+            Some(next) => {
+                info!("Skipping missing file, moving to track: {}", next);
+                Some(next)
+            }
+            None => {
+                info!("No playable track left in the playlist");
+                self.event_sender.send(PlayerEvent::EndOfPlaylist).unwrap();
+                self.state = InternalPlaybackState::Stopped;
+                None
+            }
         }
     }
 
     fn play(&mut self) {
+        self.preview = None;
+
         if let Some(sink) = &self.sink {
             sink.play();
             info!("Playback started");
             self.event_sender
                 .send(PlayerEvent::Playing {
                     id: self.current_track_id.unwrap(),
-                    index: self.current_track_index.unwrap(),
+                    index: self.queue.current_index().unwrap(),
                     path: self.current_track_path.clone().unwrap(),
                     position: Duration::new(0, 0),
                 })
@@ -268,18 +785,23 @@ impl PlayerInternal {
         } else {
             info!("Loading the first track");
             self.load(Some(0));
-            self.play();
+            if self.sink.is_some() {
+                self.play();
+            }
         }
     }
 
     fn pause(&mut self) {
+        // Cut a running crossfade short, the faded track is over anyway
+        self.fading = None;
+
         if let Some(sink) = &self.sink {
             sink.pause();
             info!("Playback paused");
             self.event_sender
                 .send(PlayerEvent::Paused {
                     id: self.current_track_id.unwrap(),
-                    index: self.current_track_index.unwrap(),
+                    index: self.queue.current_index().unwrap(),
                     path: self.current_track_path.clone().unwrap(),
                     position: sink.get_pos(),
                 })
@@ -289,6 +811,9 @@ impl PlayerInternal {
     }
 
     fn stop(&mut self) {
+        self.cancel_transition();
+        self.preview = None;
+
         if let Some(sink) = self.sink.take() {
             sink.stop();
             info!("Playback stopped");
@@ -300,78 +825,143 @@ impl PlayerInternal {
     }
 
     fn next(&mut self) {
-        if let Some(index) = self.current_track_index {
-            if index + 1 < self.playlist.len() {
-                self.current_track_index = Some(index + 1);
-                debug!("Moving to next track: {}", index + 1);
-                self.load(Some(index + 1));
-            } else {
+        if self.queue.current_index().is_none() {
+            warn!("Next command received but no track is currently playing");
+            return;
+        }
+
+        match self.queue.next_index(self.playback_mode) {
+            Some(index) => {
+                debug!("Moving to next track: {}", index);
+                self.load(Some(index));
+            }
+            None => {
                 info!("End of playlist reached");
                 self.event_sender.send(PlayerEvent::EndOfPlaylist).unwrap();
                 self.state = InternalPlaybackState::Stopped;
             }
-        } else {
-            warn!("Next command received but no track is currently playing");
         }
     }
 
     fn previous(&mut self) {
-        if let Some(index) = self.current_track_index {
-            if index > 0 {
-                self.current_track_index = Some(index - 1);
-                debug!("Moving to previous track: {}", index - 1);
-                self.load(Some(index - 1));
-            } else {
-                error!("Previous command received but already at the first track");
-            }
-        } else {
+        if self.queue.current_index().is_none() {
             warn!("Previous command received but no track is currently playing");
+            return;
+        }
+
+        match self.queue.previous_index() {
+            Some(index) => {
+                debug!("Moving to previous track: {}", index);
+                self.load(Some(index));
+            }
+            None => error!("Previous command received but already at the first track"),
         }
     }
 
     fn switch(&mut self, index: usize) {
-        if index > 0 || index < self.playlist.len() {
-            self.current_track_index = Some(index);
-            debug!("Moving to previous track: {}", index);
+        if index < self.queue.len() {
+            debug!("Switching to track: {}", index);
             self.load(Some(index));
         } else {
-            warn!("Previous command received but already at the first track");
+            warn!(
+                "Switch command received but index {} is out of bounds",
+                index
+            );
         }
     }
 
     fn seek(&mut self, position: f64) {
-        if let Some(sink) = &self.sink {
-            match sink.try_seek(std::time::Duration::from_secs(position as u64)) {
-                Ok(_) => {
-                    info!("Seeking to position: {} s", position);
-                    match self.event_sender.send(PlayerEvent::Playing {
-                        id: self.current_track_id.unwrap(),
-                        index: self.current_track_index.unwrap(),
-                        path: self.current_track_path.clone().unwrap(),
-                        position: sink.get_pos(),
-                    }) {
-                        Ok(_) => (),
-                        Err(e) => error!("Failed to send Playing event: {:?}", e),
-                    }
-                    self.state = InternalPlaybackState::Playing;
+        let Some(sink) = &self.sink else {
+            warn!("Seek command received but no track is loaded");
+            return;
+        };
+
+        if !position.is_finite() {
+            error!("Seek command received with invalid position: {}", position);
+            return;
+        }
+
+        if position < 0.0 {
+            warn!(
+                "Seek target {} s is negative, seeking to the start",
+                position
+            );
+        }
+
+        // Seeking past the end behaves differently for every codec, clamp it here.
+        // Streams without a length in their header fall back to the library.
+        let target = Duration::from_secs_f64(position.max(0.0));
+        let duration = self
+            .current_track_duration
+            .or_else(|| self.queue.current().and_then(|x| x.duration));
+        if let Some(duration) = duration {
+            if target >= duration {
+                info!("Seek target {:?} is beyond the end of the track", target);
+                self.end_of_track();
+                return;
+            }
+        }
+
+        match sink.try_seek(target) {
+            Ok(_) => {
+                info!("Seeking to position: {:?}", target);
+                self.last_position = None;
+                match self.event_sender.send(PlayerEvent::Playing {
+                    id: self.current_track_id.unwrap(),
+                    index: self.queue.current_index().unwrap(),
+                    path: self.current_track_path.clone().unwrap(),
+                    position: target,
+                }) {
+                    Ok(_) => (),
+                    Err(e) => error!("Failed to send Playing event: {:?}", e),
                 }
-                Err(e) => error!("Failed to seek: {:?}", e),
+                self.state = InternalPlaybackState::Playing;
             }
-        } else {
-            warn!("Seek command received but no track is loaded");
+            Err(e) => error!("Failed to seek: {:?}", e),
         }
     }
 
-    async fn add_to_playlist(&mut self, id: i32, path: PathBuf) {
+    async fn add_to_playlist(
+        &mut self,
+        id: i32,
+        path: PathBuf,
+        album: Option<AlbumTrack>,
+        replay_gain: Option<f32>,
+    ) {
         debug!("Adding to playlist: {:?}", path);
-        self.playlist.push(PlaylistItem { id, path });
+        self.queue.push(
+            PlaylistItem::new(id, path)
+                .with_album(album)
+                .with_replay_gain(replay_gain),
+        );
+        self.schedule_playlist_update();
+    }
+
+    fn add_items_to_playlist(
+        &mut self,
+        items: Vec<PlaylistItem>,
+        policy: DuplicatePolicy,
+        skipped: oneshot::Sender<Vec<i32>>,
+    ) {
+        debug!("Adding {} items to playlist with {:?}", items.len(), policy);
+        let left_out = self.queue.extend(items, policy);
+        if !left_out.is_empty() {
+            debug!("Left out queued items: {:?}", left_out);
+        }
+        // The caller may not wait for the answer
+        let _ = skipped.send(left_out);
         self.schedule_playlist_update();
     }
 
     async fn remove_from_playlist(&mut self, index: usize) {
-        if index < self.playlist.len() {
+        let was_current = self.queue.current_index() == Some(index);
+
+        if self.queue.remove(index).is_some() {
             debug!("Removing from playlist at index: {}", index);
-            self.playlist.remove(index);
+            if was_current {
+                // The queue no longer knows where the removed track was
+                self.stop();
+            }
             self.schedule_playlist_update();
         } else {
             error!(
@@ -382,8 +972,8 @@ impl PlayerInternal {
     }
 
     async fn clear_playlist(&mut self) {
-        self.playlist.clear();
-        self.current_track_index = None;
+        self.cancel_transition();
+        self.queue.clear();
         self.sink = None;
         self._stream = None;
         info!("Playlist cleared");
@@ -393,38 +983,62 @@ impl PlayerInternal {
     }
 
     fn send_progress(&mut self) {
+        self.fade_out();
+        self.report_limiter();
+
         if let Some(sink) = &self.sink {
-            if sink.empty() {
-                self.event_sender
-                    .send(PlayerEvent::EndOfTrack {
-                        id: self.current_track_id.unwrap(),
-                        index: self.current_track_index.unwrap(),
-                        path: self.current_track_path.clone().unwrap(),
-                    })
-                    .unwrap();
+            if self.gapless_next.is_some() && sink.len() <= 1 && !sink.empty() {
+                self.finish_gapless_transition();
+            } else if sink.empty() {
+                self.end_of_track();
+            } else {
+                let position = sink.get_pos();
 
-                if self.state != InternalPlaybackState::Stopped {
-                    self.next();
+                // The sink is playing but the position didn't move since the last
+                // tick, the decoder couldn't keep up with the output.
+                if self.state == InternalPlaybackState::Playing
+                    && !sink.is_paused()
+                    && self.last_position == Some(position)
+                {
+                    metrics::DECODE_UNDERRUNS.increment();
                 }
-            } else {
-                self.event_sender
-                    .send(PlayerEvent::Progress {
-                        id: self.current_track_id.unwrap(),
-                        index: self.current_track_index.unwrap(),
-                        path: self.current_track_path.clone().unwrap(),
-                        position: sink.get_pos(),
-                    })
-                    .unwrap();
+                self.last_position = Some(position);
+
+                if self.progress.should_report(std::time::Instant::now()) {
+                    self.event_sender
+                        .send(PlayerEvent::Progress {
+                            id: self.current_track_id.unwrap(),
+                            index: self.queue.current_index().unwrap(),
+                            path: self.current_track_path.clone().unwrap(),
+                            position,
+                        })
+                        .unwrap();
+                }
+
+                self.prepare_transition(position);
             }
         }
     }
 
-    async fn move_playlist_item(&mut self, old_index: usize, new_index: usize) {
-        if old_index >= self.playlist.len() || new_index >= self.playlist.len() {
-            error!("Move command received but index is out of bounds");
-            return;
+    fn end_of_track(&mut self) {
+        self.event_sender
+            .send(PlayerEvent::EndOfTrack {
+                id: self.current_track_id.unwrap(),
+                index: self.queue.current_index().unwrap(),
+                path: self.current_track_path.clone().unwrap(),
+            })
+            .unwrap();
+
+        if self.state != InternalPlaybackState::Stopped {
+            if self.playback_mode == PlaybackMode::RepeatOne {
+                self.load(self.queue.current_index());
+            } else {
+                self.next();
+            }
         }
+    }
 
+    async fn move_playlist_item(&mut self, old_index: usize, new_index: usize) {
         if old_index == new_index {
             debug!("Move command received but old_index is the same as new_index");
             return;
@@ -435,24 +1049,271 @@ impl PlayerInternal {
             old_index, new_index
         );
 
-        let item = self.playlist.remove(old_index);
-        self.playlist.insert(new_index, item);
-
-        // Adjust current track index if necessary
-        if let Some(current_index) = self.current_track_index {
-            if old_index == current_index {
-                // The currently playing track was moved
-                self.current_track_index = Some(new_index);
-            } else if old_index < current_index && new_index >= current_index {
-                // The track was moved past the current track
-                self.current_track_index = Some(current_index - 1);
-            } else if old_index > current_index && new_index <= current_index {
-                // The track was moved before the current track
-                self.current_track_index = Some(current_index + 1);
-            }
+        if !self.queue.move_item(old_index, new_index) {
+            error!("Move command received but index is out of bounds");
+            return;
+        }
+
+        self.schedule_playlist_update();
+    }
+
+    fn reorder_playlist(&mut self, order: Vec<usize>) {
+        if !self.queue.reorder(&order) {
+            error!("Reorder command received but the order is not a permutation of the queue");
+            return;
         }
 
+        debug!("Playlist reordered");
         self.schedule_playlist_update();
+
+        // The current track keeps playing but its index changed
+        if let (Some(sink), Some(index)) = (&self.sink, self.queue.current_index()) {
+            self.event_sender
+                .send(PlayerEvent::Progress {
+                    id: self.current_track_id.unwrap(),
+                    index,
+                    path: self.current_track_path.clone().unwrap(),
+                    position: sink.get_pos(),
+                })
+                .unwrap();
+        }
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        self.volume = volume;
+        if let Some(sink) = &self.sink {
+            sink.set_volume(volume);
+        }
+        debug!("Volume set to: {}", volume);
+        self.event_sender
+            .send(PlayerEvent::VolumeUpdated(volume))
+            .unwrap();
+    }
+
+    fn set_playback_mode(&mut self, mode: PlaybackMode) {
+        self.playback_mode = mode;
+        debug!("Playback mode set to: {:?}", mode);
+        self.event_sender
+            .send(PlayerEvent::PlaybackModeUpdated(mode))
+            .unwrap();
+    }
+
+    fn set_progress_interval(&mut self, interval: Duration) {
+        self.progress.set_interval(interval);
+        debug!("Progress interval set to: {:?}", self.progress.interval());
+    }
+
+    fn set_coarse_progress(&mut self, coarse: bool) {
+        self.progress.set_coarse(coarse);
+        debug!(
+            "Coarse progress: {}, interval: {:?}",
+            coarse,
+            self.progress.interval()
+        );
+    }
+
+    fn set_crossfade(&mut self, duration: Duration) {
+        self.crossfade = duration;
+        debug!("Crossfade set to: {:?}", duration);
+    }
+
+    // Pre-amp, ReplayGain and the user offset combined, in dB
+    fn gain_of(&self, item: &PlaylistItem) -> f32 {
+        let offset = self.gain_offsets.get(&item.id).copied().unwrap_or(0.0);
+        self.preamp + item.replay_gain.unwrap_or(0.0) + offset
+    }
+
+    // Apply changed gain settings to the tracks that are already decoded
+    fn update_track_gains(&mut self) {
+        let ids = self.queue.ids();
+        self.track_gains.retain(|id, _| ids.contains(id));
+
+        for item in self.queue.items() {
+            if let Some(gain) = self.track_gains.get(&item.id) {
+                gain.set_db(self.gain_of(item));
+            }
+        }
+    }
+
+    fn set_preamp(&mut self, preamp: f32) {
+        self.preamp = preamp;
+        debug!("Pre-amp set to: {} dB", preamp);
+        self.update_track_gains();
+    }
+
+    fn set_limiter(&mut self, enabled: bool) {
+        self.limiter.set_enabled(enabled);
+        debug!("Limiter enabled: {}", enabled);
+    }
+
+    fn set_mono(&mut self, enabled: bool) {
+        // Takes effect on the next frame of every playing source
+        self.mono.set(enabled);
+        debug!("Mono output enabled: {}", enabled);
+    }
+
+    fn set_night_mode(&mut self, enabled: bool) {
+        // Fades in or out on every playing source
+        self.night_mode.set(enabled);
+        debug!("Night mode enabled: {}", enabled);
+    }
+
+    fn set_crossfeed(&mut self, enabled: bool) {
+        self.crossfeed.set(enabled);
+        debug!("Crossfeed enabled: {}", enabled);
+    }
+
+    /// Play the end of one track crossfading into the next with the current
+    /// settings, pausing playback meanwhile.
+    ///
+    /// The mix is rendered before it plays, so a track that fails to decode
+    /// is reported right away.
+    fn preview_transition(
+        &mut self,
+        preview: TransitionPreview,
+        done: oneshot::Sender<Option<Duration>>,
+    ) {
+        self.preview = None;
+
+        let (buffer, lead) = match self.render_transition(&preview) {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                error!(
+                    "Unable to preview the transition from {} to {}: {:?}",
+                    preview.from.id, preview.to.id, e
+                );
+                let _ = done.send(None);
+                return;
+            }
+        };
+
+        let (sink, stream) = match self.backend.open_sink() {
+            Ok(output) => output,
+            Err(e) => {
+                error!("Unable to open a sink for the preview: {:?}", e);
+                let _ = done.send(None);
+                return;
+            }
+        };
+
+        if self.state == InternalPlaybackState::Playing {
+            self.pause();
+        }
+        debug!(
+            "Previewing the transition from {} to {}",
+            preview.from.id, preview.to.id
+        );
+        sink.set_volume(self.volume);
+        sink.append(buffer);
+        self.preview = Some((sink, stream));
+
+        let _ = done.send(Some(lead));
+    }
+
+    fn render_transition(
+        &self,
+        preview: &TransitionPreview,
+    ) -> Result<(SamplesBuffer<f32>, Duration), LoadError> {
+        let mut outgoing = self.open_track(&preview.from)?;
+        let duration = outgoing.total_duration().unwrap_or(preview.from_duration);
+        let (lead, end) = crossfade_window(
+            self.crossfade,
+            preview.from_points.as_ref(),
+            preview.to_points.as_ref(),
+            duration,
+        );
+
+        // Center the crossfade, unless the track is too short for it
+        let length = preview.length.max(lead);
+        let fade_start = end.saturating_sub(lead);
+        let start = fade_start.saturating_sub((length - lead) / 2);
+        if let Err(e) = outgoing.try_seek(start) {
+            warn!(
+                "Unable to seek for the preview, playing from the start: {:?}",
+                e
+            );
+        }
+        let incoming = self.open_track(&preview.to)?;
+
+        let from_gain = SharedGain::default();
+        from_gain.set_db(self.gain_of(&preview.from));
+        let to_gain = SharedGain::default();
+        to_gain.set_db(self.gain_of(&preview.to));
+
+        let mixed = mix_transition(
+            Amplified::new(outgoing, from_gain),
+            Amplified::new(incoming, to_gain),
+            fade_start - start,
+            lead,
+            length,
+        );
+        let source = self.output_chain(mixed);
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+        let samples: Vec<f32> = source.collect();
+
+        Ok((SamplesBuffer::new(channels, sample_rate, samples), lead))
+    }
+
+    fn set_output_device(&mut self, device: Option<String>) {
+        info!("Output device set to: {:?}", device);
+        self.backend.set_output_device(device.clone());
+        self.event_sender
+            .send(PlayerEvent::OutputDeviceChanged(device))
+            .unwrap();
+
+        // Move the track that is loaded over to the new device
+        let (Some(sink), Some(index)) = (&self.sink, self.queue.current_index()) else {
+            return;
+        };
+        let position = sink.get_pos();
+        let paused = self.state == InternalPlaybackState::Paused;
+
+        self.load(Some(index));
+        if !position.is_zero() {
+            self.seek(position.as_secs_f64());
+        }
+        if paused {
+            self.pause();
+        }
+    }
+
+    fn report_limiter(&mut self) {
+        let reduction = self.limiter.take_reduction();
+        let limiting = reduction > 0.0;
+
+        if limiting || self.limiting {
+            if limiting && !self.limiting {
+                debug!("Limiter engaged, reducing by {:.1} dB", reduction);
+            }
+            self.event_sender
+                .send(PlayerEvent::LimiterUpdated(reduction.max(0.0)))
+                .unwrap();
+        }
+        self.limiting = limiting;
+    }
+
+    fn set_gain_offset(&mut self, id: i32, offset: f32) {
+        if offset == 0.0 {
+            self.gain_offsets.remove(&id);
+        } else {
+            self.gain_offsets.insert(id, offset);
+        }
+        debug!("Gain offset of {} set to: {} dB", id, offset);
+        self.update_track_gains();
+    }
+
+    fn set_mix_points(&mut self, id: i32, points: Option<MixPoints>) {
+        // Forget tracks that left the queue
+        let ids = self.queue.ids();
+        self.mix_points.retain(|x, _| ids.contains(x));
+
+        match points {
+            Some(points) => self.mix_points.insert(id, points),
+            None => self.mix_points.remove(&id),
+        };
+        debug!("Mix points of {} set to: {:?}", id, points);
     }
 
     fn schedule_playlist_update(&mut self) {
@@ -462,9 +1323,11 @@ impl PlayerInternal {
 
     fn send_playlist_updated(&self) {
         self.event_sender
-            .send(PlayerEvent::PlaylistUpdated(
-                self.playlist.clone().into_iter().map(|x| x.id).collect(),
-            ))
+            .send(PlayerEvent::PlaylistUpdated {
+                ids: self.queue.ids(),
+                played: self.queue.played(),
+                history_boundary: self.queue.history_boundary(),
+            })
             .unwrap();
     }
 }
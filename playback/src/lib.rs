@@ -1,5 +1,19 @@
+pub mod backend;
+pub mod coalesce;
+pub mod dsd_source;
+pub mod dsp;
+pub mod http_source;
 mod internal;
 pub mod player;
-mod realtime_fft;
+pub mod preview;
+pub mod progress;
+pub mod queue;
+pub mod realtime_fft;
+pub mod seek_source;
+pub mod sequence;
+pub mod source;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+pub mod trim;
 
-pub use internal::{PlayerCommand, PlayerEvent};
+pub use internal::{MixPoints, PlaybackMode, PlayerCommand, PlayerEvent};
@@ -1,12 +1,39 @@
-use log::{debug, error};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
-use tokio::sync::{broadcast, mpsc};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, mpsc, oneshot};
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, error};
+
+use crate::backend::{PlaybackBackend, RodioBackend};
+use crate::internal::{MixPoints, PlaybackMode, PlayerCommand, PlayerEvent, PlayerInternal};
+use crate::preview::TransitionPreview;
+use crate::queue::{AlbumTrack, DuplicatePolicy, PlaylistItem};
+use crate::sequence::{EventSender, SequencedEvent};
+use crate::source::StreamResolver;
+
+// Gain adjustments beyond this are almost certainly mistakes
+pub const MAX_GAIN_DB: f32 = 24.0;
+
+pub fn clamp_gain(db: f32) -> f32 {
+    if db.is_finite() {
+        db.clamp(-MAX_GAIN_DB, MAX_GAIN_DB)
+    } else {
+        0.0
+    }
+}
+
+// Longer crossfades would overlap most of a track
+pub const MAX_CROSSFADE_SECS: f32 = 30.0;
 
-use crate::internal::{PlayerCommand, PlayerEvent, PlayerInternal};
+pub fn clamp_crossfade(seconds: f32) -> Duration {
+    if seconds.is_finite() {
+        Duration::from_secs_f32(seconds.clamp(0.0, MAX_CROSSFADE_SECS))
+    } else {
+        Duration::ZERO
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct PlayerStatus {
@@ -16,14 +43,42 @@ pub struct PlayerStatus {
     pub position: Duration,
     pub state: PlaybackState,
     pub playlist: Vec<i32>,
+    // Whether each item of the playlist was played in this session
+    pub played: Vec<bool>,
+    // Items of the playlist before this index are its history
+    pub history_boundary: usize,
+    pub volume: f32,
+    pub playback_mode: PlaybackMode,
+    // Tracks whose storage couldn't be reached the last time they were loaded
+    pub offline: Vec<i32>,
+    // Tracks that were deleted or moved while they were queued
+    pub missing: Vec<i32>,
+    // Current gain reduction of the limiter in dB, zero while it is idle
+    pub limiter_reduction: f32,
+    // The output device picked for playback, `None` for the system default
+    pub output_device: Option<String>,
+    // Sequence number and time of the last event applied, zero before any
+    pub sequence: u64,
+    pub timestamp: SystemTime,
 }
 
 #[derive(Debug, Clone)]
 pub struct PlaylistStatus {
     pub items: Vec<i32>,
+    pub played: Vec<bool>,
+    pub history_boundary: usize,
+    pub sequence: u64,
+    pub timestamp: SystemTime,
 }
 
 #[derive(Debug, Clone)]
+pub struct RealtimeFFTStatus {
+    pub data: Vec<f32>,
+    pub sequence: u64,
+    pub timestamp: SystemTime,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum PlaybackState {
     Playing,
     Paused,
@@ -47,7 +102,7 @@ pub struct Player {
     current_status: Arc<Mutex<PlayerStatus>>,
     status_sender: broadcast::Sender<PlayerStatus>,
     playlist_sender: broadcast::Sender<PlaylistStatus>,
-    realtime_fft_sender: broadcast::Sender<Vec<f32>>,
+    realtime_fft_sender: broadcast::Sender<RealtimeFFTStatus>,
     cancellation_token: CancellationToken,
 }
 
@@ -60,6 +115,14 @@ impl Default for Player {
 impl Player {
     // Create a new Player instance and return the Player and the event receiver
     pub fn new(cancellation_token: Option<CancellationToken>) -> Self {
+        Self::with_backend(RodioBackend::default(), cancellation_token)
+    }
+
+    // Create a new Player instance playing through the given backend
+    pub fn with_backend<B: PlaybackBackend>(
+        backend: B,
+        cancellation_token: Option<CancellationToken>,
+    ) -> Self {
         // Create an unbounded channel for sending commands
         let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
         // Create an unbounded channel for receiving events
@@ -84,6 +147,16 @@ impl Player {
             position: Duration::new(0, 0),
             state: PlaybackState::Stopped,
             playlist: Vec::new(),
+            played: Vec::new(),
+            history_boundary: 0,
+            volume: 1.0,
+            playback_mode: PlaybackMode::Sequential,
+            offline: Vec::new(),
+            missing: Vec::new(),
+            limiter_reduction: 0.0,
+            output_device: None,
+            sequence: 0,
+            timestamp: SystemTime::UNIX_EPOCH,
         }));
 
         let commands = Arc::new(Mutex::new(cmd_tx));
@@ -101,8 +174,12 @@ impl Player {
         let internal_cancellation_token = cancellation_token.clone();
         thread::spawn(move || {
             // Create a PlayerInternal instance, passing in the command receiver and event sender
-            let mut internal =
-                PlayerInternal::new(cmd_rx, event_sender, internal_cancellation_token.clone());
+            let mut internal = PlayerInternal::new(
+                cmd_rx,
+                EventSender::new(event_sender),
+                Box::new(backend),
+                internal_cancellation_token.clone(),
+            );
             // Create a new Tokio runtime for asynchronous tasks
             let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
             // Run the main loop of PlayerInternal within the Tokio runtime
@@ -115,8 +192,15 @@ impl Player {
         let playlist_sender_clone = playlist_sender.clone();
         let realtime_fft_sender_clone = realtime_fft_sender.clone();
         thread::spawn(move || {
-            while let Some(event) = event_receiver.blocking_recv() {
+            while let Some(SequencedEvent {
+                sequence,
+                timestamp,
+                event,
+            }) = event_receiver.blocking_recv()
+            {
                 let mut status = status_clone.lock().unwrap();
+                status.sequence = sequence;
+                status.timestamp = timestamp;
                 match event {
                     PlayerEvent::Playing {
                         id,
@@ -124,6 +208,8 @@ impl Player {
                         path,
                         position,
                     } => {
+                        status.offline.retain(|x| *x != id);
+                        status.missing.retain(|x| *x != id);
                         status.id = Some(id);
                         status.index = Some(index);
                         status.path = Some(path);
@@ -178,21 +264,60 @@ impl Player {
                         error,
                     } => {
                         // Handle error event, possibly log it
-                        eprintln!("Error at index {}({}): {:?} - {}", index, id, path, error);
+                        error!("Error at index {}({}): {:?} - {}", index, id, path, error);
                     }
-                    PlayerEvent::PlaylistUpdated(playlist) => {
-                        status.playlist = playlist.clone();
+                    PlayerEvent::SourceOffline { id, index, path } => {
+                        error!("Source offline at index {}({}): {:?}", index, id, path);
+                        if !status.offline.contains(&id) {
+                            status.offline.push(id);
+                        }
+                        status.state = PlaybackState::Stopped;
+                    }
+                    PlayerEvent::FileMissing { id, index, path } => {
+                        error!("File missing at index {}({}): {:?}", index, id, path);
+                        if !status.missing.contains(&id) {
+                            status.missing.push(id);
+                        }
+                    }
+                    PlayerEvent::PlaylistUpdated {
+                        ids,
+                        played,
+                        history_boundary,
+                    } => {
+                        status.playlist = ids.clone();
+                        status.played = played.clone();
+                        status.history_boundary = history_boundary;
                         debug!("Sending playlist status");
                         if let Err(e) = playlist_sender_clone.send(PlaylistStatus {
-                            items: playlist.clone(),
+                            items: ids,
+                            played,
+                            history_boundary,
+                            sequence,
+                            timestamp,
                         }) {
                             error!("Failed to send playlist status: {:?}", e);
                         } else {
                             debug!("Playlist status sent successfully");
                         }
                     }
+                    PlayerEvent::VolumeUpdated(volume) => {
+                        status.volume = volume;
+                    }
+                    PlayerEvent::PlaybackModeUpdated(mode) => {
+                        status.playback_mode = mode;
+                    }
+                    PlayerEvent::LimiterUpdated(reduction) => {
+                        status.limiter_reduction = reduction;
+                    }
+                    PlayerEvent::OutputDeviceChanged(device) => {
+                        status.output_device = device;
+                    }
                     PlayerEvent::RealtimeFFT(data) => {
-                        match realtime_fft_sender_clone.send(data) {
+                        match realtime_fft_sender_clone.send(RealtimeFFTStatus {
+                            data,
+                            sequence,
+                            timestamp,
+                        }) {
                             Ok(_) => {}
                             Err(e) => {
                                 error!("Unable to send realtime FFT data: {:?}", e);
@@ -225,7 +350,7 @@ impl Player {
         self.playlist_sender.subscribe()
     }
 
-    pub fn subscribe_realtime_fft(&self) -> broadcast::Receiver<Vec<f32>> {
+    pub fn subscribe_realtime_fft(&self) -> broadcast::Receiver<RealtimeFFTStatus> {
         self.realtime_fft_sender.subscribe()
     }
 
@@ -271,12 +396,61 @@ impl Player {
         self.command(PlayerCommand::Switch(index));
     }
 
-    pub fn seek(&self, position_ms: f64) {
-        self.command(PlayerCommand::Seek(position_ms));
+    pub fn seek(&self, position_seconds: f64) {
+        self.command(PlayerCommand::Seek(position_seconds));
     }
 
     pub fn add_to_playlist(&self, id: i32, path: PathBuf) {
-        self.command(PlayerCommand::AddToPlaylist { id, path });
+        self.command(PlayerCommand::AddToPlaylist {
+            id,
+            path,
+            album: None,
+            replay_gain: None,
+        });
+    }
+
+    // Add a track together with its place on the album, used to keep
+    // consecutive album tracks gapless when crossfading
+    pub fn add_album_track_to_playlist(&self, id: i32, path: PathBuf, album: AlbumTrack) {
+        self.command(PlayerCommand::AddToPlaylist {
+            id,
+            path,
+            album: Some(album),
+            replay_gain: None,
+        });
+    }
+
+    // Add a track with everything known about it, the ReplayGain track gain
+    // is in dB and applied when the track is loaded
+    pub fn add_track_to_playlist(
+        &self,
+        id: i32,
+        path: PathBuf,
+        album: Option<AlbumTrack>,
+        replay_gain: Option<f32>,
+    ) {
+        self.command(PlayerCommand::AddToPlaylist {
+            id,
+            path,
+            album,
+            replay_gain,
+        });
+    }
+
+    // Add tracks together, following the policy for those already queued.
+    // The receiver gets the IDs of the tracks that were left out.
+    pub fn add_items_to_playlist(
+        &self,
+        items: Vec<PlaylistItem>,
+        policy: DuplicatePolicy,
+    ) -> oneshot::Receiver<Vec<i32>> {
+        let (skipped, receiver) = oneshot::channel();
+        self.command(PlayerCommand::AddItemsToPlaylist {
+            items,
+            policy,
+            skipped,
+        });
+        receiver
     }
 
     pub fn remove_from_playlist(&self, index: usize) {
@@ -293,4 +467,109 @@ impl Player {
             new_index,
         })
     }
+
+    // Rearrange the playlist, the item at order[i] moves to index i
+    pub fn reorder_playlist(&self, order: Vec<usize>) {
+        self.command(PlayerCommand::ReorderPlaylist(order));
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.command(PlayerCommand::SetVolume(volume));
+    }
+
+    // Crossfade between tracks, zero disables it
+    pub fn set_crossfade(&self, seconds: f32) {
+        self.command(PlayerCommand::SetCrossfade(clamp_crossfade(seconds)));
+    }
+
+    // Gain in dB applied to every track on top of ReplayGain
+    pub fn set_preamp(&self, db: f32) {
+        self.command(PlayerCommand::SetPreamp(clamp_gain(db)));
+    }
+
+    // Interval between progress events, the position is still checked every
+    // tick for transitions
+    pub fn set_progress_interval(&self, interval: Duration) {
+        self.command(PlayerCommand::SetProgressInterval(interval));
+    }
+
+    // Report progress once a second at most, for a UI nobody is looking at
+    pub fn set_coarse_progress(&self, coarse: bool) {
+        self.command(PlayerCommand::SetCoarseProgress(coarse));
+    }
+
+    // Sign the addresses remote files queued with `remote_path` are streamed
+    // from when they are loaded
+    pub fn set_stream_resolver(&self, resolver: StreamResolver) {
+        self.command(PlayerCommand::SetStreamResolver(resolver));
+    }
+
+    // Files that can't be found are only reported offline when the root of
+    // their library can't be reached either
+    pub fn set_library_root(&self, path: PathBuf) {
+        self.command(PlayerCommand::SetLibraryRoot(path));
+    }
+
+    // Keep peaks pushed over full scale by the gain stages from clipping
+    pub fn set_limiter(&self, enabled: bool) {
+        self.command(PlayerCommand::SetLimiter(enabled));
+    }
+
+    // Play the same downmix on all channels, for listeners who hear only one side
+    pub fn set_mono(&self, enabled: bool) {
+        self.command(PlayerCommand::SetMono(enabled));
+    }
+
+    // Compress loud passages and bring up quiet ones, for listening at low volume
+    pub fn set_night_mode(&self, enabled: bool) {
+        self.command(PlayerCommand::SetNightMode(enabled));
+    }
+
+    // Blend the channels a little for headphones
+    pub fn set_crossfeed(&self, enabled: bool) {
+        self.command(PlayerCommand::SetCrossfeed(enabled));
+    }
+
+    // Play through the named output device, the system default for `None`,
+    // the loaded track moves over at its position
+    pub fn set_output_device(&self, device: Option<String>) {
+        self.command(PlayerCommand::SetOutputDevice(device));
+    }
+
+    // Gain in dB applied to one file on top of ReplayGain and the pre-amp,
+    // takes effect immediately if the file is playing
+    pub fn set_gain_offset(&self, id: i32, db: f32) {
+        self.command(PlayerCommand::SetGainOffset {
+            id,
+            offset: clamp_gain(db),
+        });
+    }
+
+    // Points in seconds to mix a file in and out at when crossfading, found
+    // by the analysis, invalid points are ignored
+    pub fn set_mix_points(&self, id: i32, mix_in: f64, mix_out: f64) {
+        self.command(PlayerCommand::SetMixPoints {
+            id,
+            points: MixPoints::from_seconds(mix_in, mix_out),
+        });
+    }
+
+    // Play a short mix around the crossfade between two tracks with the
+    // current settings, pausing playback. The receiver gets the length of
+    // the crossfade, or nothing if a track couldn't be played
+    pub fn preview_transition(
+        &self,
+        preview: TransitionPreview,
+    ) -> oneshot::Receiver<Option<Duration>> {
+        let (done, receiver) = oneshot::channel();
+        self.command(PlayerCommand::PreviewTransition {
+            preview: Box::new(preview),
+            done,
+        });
+        receiver
+    }
+
+    pub fn set_playback_mode(&self, mode: PlaybackMode) {
+        self.command(PlayerCommand::SetPlaybackMode(mode));
+    }
 }
@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use rodio::Source;
+
+use crate::internal::MixPoints;
+use crate::queue::PlaylistItem;
+
+// Previews shorter than this don't leave room to hear the tracks around the
+// crossfade, longer ones are better played for real
+const MIN_PREVIEW_LENGTH: f64 = 2.0;
+const MAX_PREVIEW_LENGTH: f64 = 60.0;
+const DEFAULT_PREVIEW_LENGTH: f64 = 10.0;
+
+/// Two tracks to audition the crossfade between.
+#[derive(Debug, Clone)]
+pub struct TransitionPreview {
+    pub from: PlaylistItem,
+    pub to: PlaylistItem,
+    // Length of the outgoing track, used when the decoder can't tell it
+    pub from_duration: Duration,
+    pub from_points: Option<MixPoints>,
+    pub to_points: Option<MixPoints>,
+    // How much to play in total, the crossfade in the middle
+    pub length: Duration,
+}
+
+/// The length of a preview asked for in seconds, kept within sensible
+/// bounds.
+pub fn preview_length(seconds: f64) -> Duration {
+    let seconds = if seconds.is_finite() {
+        seconds.clamp(MIN_PREVIEW_LENGTH, MAX_PREVIEW_LENGTH)
+    } else {
+        DEFAULT_PREVIEW_LENGTH
+    };
+    Duration::from_secs_f64(seconds)
+}
+
+/// A linear fade of a source, starting after `start` and lasting `length`.
+///
+/// A fade out ends the source with the fade, a fade in plays on at full
+/// volume after it.
+pub struct Fade<S> {
+    input: S,
+    channels: u64,
+    // Frames before the fade and frames it lasts
+    start: u64,
+    length: u64,
+    fading_in: bool,
+    // Samples played so far
+    position: u64,
+}
+
+impl<S: Source<Item = f32>> Fade<S> {
+    pub fn fade_in(input: S, start: Duration, length: Duration) -> Self {
+        Self::new(input, start, length, true)
+    }
+
+    pub fn fade_out(input: S, start: Duration, length: Duration) -> Self {
+        Self::new(input, start, length, false)
+    }
+
+    fn new(input: S, start: Duration, length: Duration, fading_in: bool) -> Self {
+        let sample_rate = input.sample_rate() as f64;
+        Fade {
+            channels: input.channels().max(1) as u64,
+            start: (start.as_secs_f64() * sample_rate) as u64,
+            length: (length.as_secs_f64() * sample_rate) as u64,
+            fading_in,
+            position: 0,
+            input,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Fade<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let frame = self.position / self.channels;
+        if !self.fading_in && frame >= self.start + self.length {
+            return None;
+        }
+
+        let sample = self.input.next()?;
+        self.position += 1;
+
+        let progress = if frame < self.start {
+            0.0
+        } else if self.length == 0 {
+            1.0
+        } else {
+            ((frame - self.start) as f32 / self.length as f32).min(1.0)
+        };
+        let gain = if self.fading_in {
+            progress
+        } else {
+            1.0 - progress
+        };
+
+        Some(sample * gain)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Fade<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Mix the end of one track into the start of the next, the way a
+/// crossfade plays them.
+///
+/// # Arguments
+/// * `outgoing` - The first track, already at the position the mix starts.
+/// * `incoming` - The next track from its start.
+/// * `offset` - When the crossfade starts, from the start of the mix.
+/// * `lead` - How long the crossfade lasts, zero for a cut.
+/// * `length` - The length of the mix.
+pub fn mix_transition<A, B>(
+    outgoing: A,
+    incoming: B,
+    offset: Duration,
+    lead: Duration,
+    length: Duration,
+) -> impl Source<Item = f32>
+where
+    A: Source<Item = f32>,
+    B: Source<Item = f32>,
+{
+    let mixed = Fade::fade_out(outgoing, offset, lead)
+        .mix(Fade::fade_in(incoming, Duration::ZERO, lead).delay(offset));
+    Trim::new(mixed, length)
+}
+
+// Ends a source after a number of frames. `take_duration` stops a sample
+// short when the length is a whole number of samples.
+struct Trim<S> {
+    input: S,
+    // Samples left to play
+    remaining: u64,
+}
+
+impl<S: Source<Item = f32>> Trim<S> {
+    fn new(input: S, length: Duration) -> Self {
+        let frames = (length.as_secs_f64() * input.sample_rate() as f64).round() as u64;
+        Trim {
+            remaining: frames * input.channels().max(1) as u64,
+            input,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Trim<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        self.input.next()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Trim<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input
+            .current_frame_len()
+            .map(|x| x.min(self.remaining as usize))
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
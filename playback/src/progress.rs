@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+/// How often the player checks its position, transitions are prepared at
+/// this pace whatever the reported rate.
+pub const PROGRESS_TICK: Duration = Duration::from_millis(100);
+/// Interval of progress reports in coarse mode.
+pub const COARSE_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Decides which position checks are reported as progress, so a listener
+/// that doesn't need every tick isn't woken for it.
+#[derive(Debug, Clone)]
+pub struct ProgressThrottle {
+    interval: Duration,
+    coarse: bool,
+    last_report: Option<Instant>,
+}
+
+impl Default for ProgressThrottle {
+    fn default() -> Self {
+        Self {
+            interval: PROGRESS_TICK,
+            coarse: false,
+            last_report: None,
+        }
+    }
+}
+
+impl ProgressThrottle {
+    /// The interval between reports, never shorter than a tick.
+    pub fn interval(&self) -> Duration {
+        let interval = self.interval.max(PROGRESS_TICK);
+        if self.coarse {
+            interval.max(COARSE_PROGRESS_INTERVAL)
+        } else {
+            interval
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    // Used while nobody looks at the position, like a backgrounded window
+    pub fn set_coarse(&mut self, coarse: bool) {
+        self.coarse = coarse;
+        // Coming back, the position is reported right away
+        if !coarse {
+            self.last_report = None;
+        }
+    }
+
+    /// Whether the tick at `now` is reported, it is remembered if so.
+    pub fn should_report(&mut self, now: Instant) -> bool {
+        // Ticks aren't exact, a bit of slack keeps the reports on the
+        // requested pace
+        let slack = PROGRESS_TICK / 10;
+        let due = match self.last_report {
+            Some(last) => now.saturating_duration_since(last) + slack >= self.interval(),
+            None => true,
+        };
+
+        if due {
+            self.last_report = Some(now);
+        }
+        due
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use analysis::seek_table::SeekTable;
+
+use crate::internal::PlaybackMode;
+use crate::trim::EncoderTrim;
+
+/// Where a track sits on its album.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlbumTrack {
+    pub album_id: i32,
+    pub disc_number: Option<i32>,
+    pub track_number: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaylistItem {
+    pub id: i32,
+    pub path: PathBuf,
+    // False once the file was found missing from disk, skipped while advancing
+    pub available: bool,
+    pub album: Option<AlbumTrack>,
+    // ReplayGain track gain in dB, read from the tags
+    pub replay_gain: Option<f32>,
+    // True once the track started playing in this session
+    pub played: bool,
+    // Byte offsets for formats the decoder seeks slowly, built by the analysis
+    pub seek_table: Option<Arc<SeekTable>>,
+    // Encoder delay and padding of lossy files, trimmed for gapless playback
+    pub trim: Option<EncoderTrim>,
+    // Length stored in the library, for when the decoder can't tell
+    pub duration: Option<Duration>,
+}
+
+impl PlaylistItem {
+    pub fn new(id: i32, path: PathBuf) -> Self {
+        PlaylistItem {
+            id,
+            path,
+            available: true,
+            album: None,
+            replay_gain: None,
+            played: false,
+            seek_table: None,
+            trim: None,
+            duration: None,
+        }
+    }
+
+    pub fn with_album(mut self, album: Option<AlbumTrack>) -> Self {
+        self.album = album;
+        self
+    }
+
+    pub fn with_replay_gain(mut self, replay_gain: Option<f32>) -> Self {
+        self.replay_gain = replay_gain;
+        self
+    }
+
+    pub fn with_seek_table(mut self, seek_table: Option<Arc<SeekTable>>) -> Self {
+        self.seek_table = seek_table;
+        self
+    }
+
+    pub fn with_trim(mut self, trim: Option<EncoderTrim>) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    pub fn with_duration(mut self, duration: Option<Duration>) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Whether this item is the track right after `previous` on the same album,
+    /// such pairs are played without a gap.
+    pub fn follows(&self, previous: &PlaylistItem) -> bool {
+        match (self.album, previous.album) {
+            (Some(this), Some(previous)) => {
+                this.album_id == previous.album_id
+                    && this.disc_number == previous.disc_number
+                    && previous.track_number.checked_add(1) == Some(this.track_number)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What happens to tracks that are already queued when they are added again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Add them anyway.
+    #[default]
+    Allow,
+    /// Leave them out and add the others.
+    Skip,
+    /// Add nothing when any of them is queued, so the user can be asked.
+    Ask,
+}
+
+impl DuplicatePolicy {
+    /// Read the policy from its name, "allow", "skip" or "ask".
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "allow" => Some(DuplicatePolicy::Allow),
+            "skip" => Some(DuplicatePolicy::Skip),
+            "ask" => Some(DuplicatePolicy::Ask),
+            _ => None,
+        }
+    }
+}
+
+/// The play queue and the index of the current track in it.
+///
+/// All index bookkeeping lives here so it can be verified without an audio
+/// output. The invariant kept by every method: `current_index` is either
+/// `None` or a valid index, and keeps pointing at the same item when other
+/// items are added, removed or moved.
+#[derive(Debug, Clone, Default)]
+pub struct PlayQueue {
+    items: Vec<PlaylistItem>,
+    current: Option<usize>,
+}
+
+impl PlayQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn items(&self) -> &[PlaylistItem] {
+        &self.items
+    }
+
+    pub fn ids(&self) -> Vec<i32> {
+        self.items.iter().map(|x| x.id).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&PlaylistItem> {
+        self.items.get(index)
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn current(&self) -> Option<&PlaylistItem> {
+        self.current.and_then(|x| self.items.get(x))
+    }
+
+    pub fn push(&mut self, item: PlaylistItem) {
+        self.items.push(item);
+    }
+
+    /// Append items, leaving out those already queued or repeated among the
+    /// items as the policy says.
+    ///
+    /// Returns the IDs of the duplicates that weren't added, with `Ask`
+    /// nothing is added when there are any.
+    pub fn extend(&mut self, items: Vec<PlaylistItem>, policy: DuplicatePolicy) -> Vec<i32> {
+        if policy == DuplicatePolicy::Allow {
+            self.items.extend(items);
+            return Vec::new();
+        }
+
+        let mut present: HashSet<i32> = self.items.iter().map(|x| x.id).collect();
+        let (added, skipped): (Vec<PlaylistItem>, Vec<PlaylistItem>) =
+            items.into_iter().partition(|x| present.insert(x.id));
+        let skipped: Vec<i32> = skipped.into_iter().map(|x| x.id).collect();
+
+        if policy == DuplicatePolicy::Skip || skipped.is_empty() {
+            self.items.extend(added);
+        }
+        skipped
+    }
+
+    /// Which items were played in this session, in queue order.
+    pub fn played(&self) -> Vec<bool> {
+        self.items.iter().map(|x| x.played).collect()
+    }
+
+    /// The number of played items at the start of the queue, not counting the
+    /// current one. The items before this index are the history.
+    pub fn history_boundary(&self) -> usize {
+        let end = self.current.unwrap_or(self.items.len());
+        self.items[..end].iter().take_while(|x| x.played).count()
+    }
+
+    /// Make the item at `index` the current one and mark it as played, returns
+    /// false if the index is out of bounds.
+    pub fn select(&mut self, index: usize) -> bool {
+        if index < self.items.len() {
+            self.current = Some(index);
+            self.items[index].played = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Mark whether the file of the item at `index` can be played, returns false
+    /// if the index is out of bounds.
+    pub fn set_available(&mut self, index: usize, available: bool) -> bool {
+        match self.items.get_mut(index) {
+            Some(item) => {
+                item.available = available;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget the current item without touching the queue.
+    pub fn deselect(&mut self) {
+        self.current = None;
+    }
+
+    /// Remove the item at `index`.
+    ///
+    /// If the current item is removed the queue has no current item afterwards,
+    /// the caller is responsible for stopping its playback.
+    pub fn remove(&mut self, index: usize) -> Option<PlaylistItem> {
+        if index >= self.items.len() {
+            return None;
+        }
+
+        let item = self.items.remove(index);
+
+        if let Some(current) = self.current {
+            if index == current {
+                self.current = None;
+            } else if index < current {
+                self.current = Some(current - 1);
+            }
+        }
+
+        Some(item)
+    }
+
+    /// Move the item at `old_index` to `new_index`, returns false if either index is out of bounds.
+    pub fn move_item(&mut self, old_index: usize, new_index: usize) -> bool {
+        if old_index >= self.items.len() || new_index >= self.items.len() {
+            return false;
+        }
+
+        if old_index == new_index {
+            return true;
+        }
+
+        let item = self.items.remove(old_index);
+        self.items.insert(new_index, item);
+
+        if let Some(current) = self.current {
+            if old_index == current {
+                // The current item was moved
+                self.current = Some(new_index);
+            } else if old_index < current && new_index >= current {
+                // An item was moved from before the current item to after it
+                self.current = Some(current - 1);
+            } else if old_index > current && new_index <= current {
+                // An item was moved from after the current item to before it
+                self.current = Some(current + 1);
+            }
+        }
+
+        true
+    }
+
+    /// Rearrange the queue so that the item at `order[i]` ends up at index `i`.
+    ///
+    /// Returns false and leaves the queue untouched unless `order` is a
+    /// permutation of all indices.
+    pub fn reorder(&mut self, order: &[usize]) -> bool {
+        if order.len() != self.items.len() {
+            return false;
+        }
+
+        let mut seen = vec![false; order.len()];
+        for &index in order {
+            if index >= seen.len() || seen[index] {
+                return false;
+            }
+            seen[index] = true;
+        }
+
+        self.items = order.iter().map(|&x| self.items[x].clone()).collect();
+        self.current = self
+            .current
+            .and_then(|current| order.iter().position(|&x| x == current));
+
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.current = None;
+    }
+
+    /// The index that follows the current item, `None` at the end of the queue.
+    pub fn next_index(&self, mode: PlaybackMode) -> Option<usize> {
+        let current = self.current?;
+
+        if current + 1 < self.items.len() {
+            Some(current + 1)
+        } else if mode == PlaybackMode::RepeatAll && !self.items.is_empty() {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// The first available item after `index`, wrapping around in `RepeatAll`
+    /// mode. `None` if no other item can be played.
+    pub fn next_available_index(&self, index: usize, mode: PlaybackMode) -> Option<usize> {
+        let len = self.items.len();
+        let candidates = (index + 1..len).chain(if mode == PlaybackMode::RepeatAll {
+            0..index.min(len)
+        } else {
+            0..0
+        });
+
+        candidates.into_iter().find(|x| self.items[*x].available)
+    }
+
+    /// The index before the current item, `None` at the start of the queue.
+    pub fn previous_index(&self) -> Option<usize> {
+        self.current?.checked_sub(1)
+    }
+}
@@ -1,13 +1,16 @@
-use rustfft::{num_complex::Complex, FftPlanner};
-use std::sync::{Arc, Mutex};
-use std::thread;
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use std::collections::VecDeque;
+use std::sync::Arc;
 use tokio::sync::broadcast;
 
 pub struct RealTimeFFT {
-    window_size: usize,
-    window: Arc<Mutex<Vec<f32>>>,
+    window: VecDeque<f32>,
     fft_window: Vec<f32>,
     fft_result_tx: broadcast::Sender<Vec<f32>>,
+    // Planned once, the planner picks the SIMD implementation of the CPU
+    fft: Arc<dyn Fft<f32>>,
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
 }
 
 pub fn build_nuttall_window(window_size: usize) -> Vec<f32> {
@@ -27,61 +30,55 @@ pub fn build_nuttall_window(window_size: usize) -> Vec<f32> {
 impl RealTimeFFT {
     pub fn new(window_size: usize) -> Self {
         let (fft_result_tx, _) = broadcast::channel(30);
-        let window = vec![0.0; window_size];
+        let fft = FftPlanner::new().plan_fft_forward(window_size);
+        let scratch = vec![Complex::new(0.0, 0.0); fft.get_inplace_scratch_len()];
+
         RealTimeFFT {
-            window_size,
-            window: Arc::new(Mutex::new(window)),
-            fft_result_tx,
+            window: VecDeque::from(vec![0.0; window_size]),
             fft_window: build_nuttall_window(window_size),
+            fft_result_tx,
+            fft,
+            buffer: vec![Complex::new(0.0, 0.0); window_size],
+            scratch,
         }
     }
 
-    pub fn add_data(&self, data: Vec<i16>) {
-        let window_size = self.window_size;
-        let mut window = self.window.lock().unwrap();
-
+    pub fn add_data(&mut self, data: Vec<i16>) {
         // Calculate average value of data from all channels
         let avg: f32 = data.iter().map(|&x| x as f32).sum::<f32>() / data.len() as f32;
 
         // update the window
-        window.push(avg);
-        if window.len() > window_size {
-            window.remove(0);
+        self.window.push_back(avg);
+        if self.window.len() > self.fft_window.len() {
+            self.window.pop_front();
         }
 
-        let fft_window = window.clone();
-        let fft_result_tx = self.fft_result_tx.clone();
-        let hanning_window = self.fft_window.clone();
-
-        // Calculate the data in a new thread
-        thread::spawn(move || {
-            let mut planner = FftPlanner::new();
-            let fft = planner.plan_fft_forward(window_size);
-
-            // Create a complex vector
-            let mut buffer: Vec<Complex<f32>> = fft_window
-                .into_iter()
-                .map(|x| Complex::new(x, 0.0))
-                .collect();
-
-            // Execute FFT
-            fft.process(&mut buffer);
-
-            let mut amp_spectrum: Vec<f32> = buffer.iter().map(|c| c.norm()).collect();
-
-            for (i, value) in amp_spectrum.iter_mut().enumerate() {
-                *value *= hanning_window[i];
-            }
-
-            let max_value = amp_spectrum
-                .iter()
-                .cloned()
-                .max_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap();
-
-            // Send the FFT result
-            let _ = fft_result_tx.send(amp_spectrum.into_iter().map(|x| x / max_value).collect());
-        });
+        // A transform of this size takes microseconds, far less than
+        // handing the window to another thread
+        for (value, &sample) in self.buffer.iter_mut().zip(self.window.iter()) {
+            *value = Complex::new(sample, 0.0);
+        }
+        self.fft
+            .process_with_scratch(&mut self.buffer, &mut self.scratch);
+
+        let amp_spectrum: Vec<f32> = self
+            .buffer
+            .iter()
+            .zip(&self.fft_window)
+            .map(|(c, weight)| c.norm() * weight)
+            .collect();
+
+        let max_value = amp_spectrum.iter().cloned().fold(0.0, f32::max);
+        let scale = if max_value > 0.0 {
+            1.0 / max_value
+        } else {
+            0.0
+        };
+
+        // Send the FFT result
+        let _ = self
+            .fft_result_tx
+            .send(amp_spectrum.into_iter().map(|x| x * scale).collect());
     }
 
     pub fn subscribe(&self) -> broadcast::Receiver<Vec<f32>> {
@@ -0,0 +1,186 @@
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use analysis::seek_table::SeekTable;
+use rodio::source::SeekError;
+use rodio::{Decoder, Source};
+use tracing::warn;
+
+use crate::source::open_media_file;
+
+// Frames decoded before the target at least, MP3 frames borrow bits from
+// the previous ones and the first frame after a jump comes out broken
+const PREROLL_FRAMES: u64 = 2304;
+
+/// The first `header_len` bytes of a file followed by everything from
+/// `offset` on, so a decoder sees a complete stream that starts at a seek
+/// point.
+pub struct SplicedReader<R> {
+    inner: R,
+    header_len: u64,
+    offset: u64,
+    len: u64,
+    position: u64,
+}
+
+impl<R: Read + Seek> SplicedReader<R> {
+    pub fn new(mut inner: R, header_len: u64, offset: u64) -> io::Result<Self> {
+        let file_len = inner.seek(SeekFrom::End(0))?;
+        let offset = offset.clamp(header_len, file_len);
+        inner.seek(SeekFrom::Start(0))?;
+
+        Ok(SplicedReader {
+            inner,
+            header_len,
+            offset,
+            len: header_len + file_len - offset,
+            position: 0,
+        })
+    }
+
+    fn inner_position(&self) -> u64 {
+        if self.position < self.header_len {
+            self.position
+        } else {
+            self.position - self.header_len + self.offset
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for SplicedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Never read across the splice in one go
+        let remaining = if self.position < self.header_len {
+            self.header_len - self.position
+        } else {
+            self.len - self.position
+        };
+        let wanted = buf.len().min(remaining as usize);
+        if wanted == 0 {
+            return Ok(0);
+        }
+
+        self.inner.seek(SeekFrom::Start(self.inner_position()))?;
+        let read = self.inner.read(&mut buf[..wanted])?;
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for SplicedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.len.checked_add_signed(x),
+            SeekFrom::Current(x) => self.position.checked_add_signed(x),
+        };
+
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek before the start",
+            )),
+        }
+    }
+}
+
+type InnerSource = Box<dyn Source<Item = f32> + Send>;
+
+/// A decoded file that seeks with a precomputed seek table.
+///
+/// A seek opens the file again, decodes from the closest point before the
+/// target and drops the frames up to it. Without a usable point the
+/// decoder seeks on its own.
+pub struct TableSeekSource {
+    path: PathBuf,
+    table: Arc<SeekTable>,
+    inner: InnerSource,
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+}
+
+impl TableSeekSource {
+    pub fn new(path: PathBuf, table: Arc<SeekTable>, inner: InnerSource) -> Self {
+        TableSeekSource {
+            path,
+            channels: inner.channels(),
+            sample_rate: inner.sample_rate(),
+            total_duration: inner.total_duration(),
+            table,
+            inner,
+        }
+    }
+
+    fn decode_from(&self, target: u64) -> Result<InnerSource, Box<dyn std::error::Error>> {
+        let point = self
+            .table
+            .point_before(target.saturating_sub(PREROLL_FRAMES))
+            .ok_or("no seek point before the target")?;
+
+        let file = open_media_file(&self.path)?;
+        let reader = SplicedReader::new(file, self.table.header_len, point.byte_offset)?;
+        let mut source = Decoder::new(BufReader::new(reader))?.convert_samples::<f32>();
+        if source.channels() != self.channels || source.sample_rate() != self.sample_rate {
+            return Err("the stream changed after the seek point".into());
+        }
+
+        let skipped = (target - point.frame) * self.channels as u64;
+        for _ in 0..skipped {
+            if source.next().is_none() {
+                break;
+            }
+        }
+
+        Ok(Box::new(source))
+    }
+}
+
+impl Iterator for TableSeekSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.inner.next()
+    }
+}
+
+impl Source for TableSeekSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // The table counts frames at the rate of the file
+        let target = (pos.as_secs_f64() * self.table.sample_rate as f64) as u64;
+
+        match self.decode_from(target) {
+            Ok(source) => {
+                self.inner = source;
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Seeking {:?} without its seek table: {}", self.path, e);
+                self.inner.try_seek(pos)
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::SendError;
+
+use crate::internal::PlayerEvent;
+
+/// A player event with its place in the event stream.
+#[derive(Debug, Clone)]
+pub struct SequencedEvent {
+    /// Increases by one with every event, starting at one.
+    pub sequence: u64,
+    pub timestamp: SystemTime,
+    pub event: PlayerEvent,
+}
+
+/// Sends player events, numbering them in the order they are sent so a
+/// listener can tell when some were missed or came out of order.
+#[derive(Debug, Clone)]
+pub struct EventSender {
+    sender: mpsc::UnboundedSender<SequencedEvent>,
+    last_sequence: Arc<AtomicU64>,
+}
+
+impl EventSender {
+    pub fn new(sender: mpsc::UnboundedSender<SequencedEvent>) -> Self {
+        Self {
+            sender,
+            last_sequence: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn send(&self, event: PlayerEvent) -> Result<(), SendError<PlayerEvent>> {
+        let sequence = self.last_sequence.fetch_add(1, Ordering::SeqCst) + 1;
+        self.sender
+            .send(SequencedEvent {
+                sequence,
+                timestamp: SystemTime::now(),
+                event,
+            })
+            .map_err(|e| SendError(e.0.event))
+    }
+}
+
+/// Milliseconds since the Unix epoch, how timestamps are sent to the UI.
+pub fn timestamp_millis(timestamp: SystemTime) -> i64 {
+    timestamp
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_millis() as i64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,106 @@
+use std::io::{self, ErrorKind, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub use analysis::media_file::{open_library_file, open_media_file, OpenError};
+
+use crate::http_source::HttpSource;
+
+// Queued remote files point at the server and song, the address to stream
+// from is signed when the track is loaded
+const REMOTE_SCHEME: &str = "remote://";
+
+/// The path a song of a remote server is queued with.
+pub fn remote_path(server_id: i32, remote_id: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}/{}", REMOTE_SCHEME, server_id, remote_id))
+}
+
+/// The server and song ID of a path made by `remote_path`.
+pub fn parse_remote_path(path: &Path) -> Option<(i32, &str)> {
+    let (server_id, remote_id) = path
+        .to_str()?
+        .strip_prefix(REMOTE_SCHEME)?
+        .split_once('/')?;
+
+    Some((server_id.parse().ok()?, remote_id))
+}
+
+/// Whether a queued path is the address of a file on a remote server.
+pub fn is_remote_url(path: &Path) -> bool {
+    parse_remote_path(path).is_some()
+        || path
+            .to_str()
+            .is_some_and(|x| x.starts_with("http://") || x.starts_with("https://"))
+}
+
+/// Signs the address a remote song is streamed from, given the server and
+/// song ID, so credentials are never kept in the queue.
+#[derive(Clone)]
+pub struct StreamResolver(Arc<ResolveStream>);
+
+type ResolveStream = dyn Fn(i32, &str) -> Option<String> + Send + Sync;
+
+impl StreamResolver {
+    pub fn new(resolve: impl Fn(i32, &str) -> Option<String> + Send + Sync + 'static) -> Self {
+        StreamResolver(Arc::new(resolve))
+    }
+
+    pub fn resolve(&self, server_id: i32, remote_id: &str) -> Option<String> {
+        (self.0)(server_id, remote_id)
+    }
+}
+
+impl std::fmt::Debug for StreamResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StreamResolver")
+    }
+}
+
+/// Anything media can be decoded from.
+pub trait MediaSource: Read + Seek + Send + Sync {}
+
+impl<T: Read + Seek + Send + Sync> MediaSource for T {}
+
+/// Open a media file of the library, or stream one from a remote server if
+/// the path is an address.
+///
+/// # Arguments
+/// * `path` - The full path of the media file, or its address.
+/// * `resolver` - Signs the addresses of paths made by `remote_path`.
+/// * `lib_path` - The root of the library, to tell an offline library from
+///   a missing file.
+///
+/// # Returns
+/// * `Result<Box<dyn MediaSource>, OpenError>` - The source, or why it can't
+///   be played right now.
+pub fn open_media_source(
+    path: &Path,
+    resolver: Option<&StreamResolver>,
+    lib_path: Option<&Path>,
+) -> Result<Box<dyn MediaSource>, OpenError> {
+    if !is_remote_url(path) {
+        let file = match lib_path {
+            Some(lib_path) => open_library_file(path, lib_path),
+            None => open_media_file(path),
+        };
+        return file.map(|x| Box::new(x) as Box<dyn MediaSource>);
+    }
+
+    let url = match parse_remote_path(path) {
+        Some((server_id, remote_id)) => resolver
+            .and_then(|x| x.resolve(server_id, remote_id))
+            .ok_or_else(|| {
+                OpenError::Missing(io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("no server {} to stream from", server_id),
+                ))
+            })?,
+        None => path.to_str().unwrap_or_default().to_string(),
+    };
+    match HttpSource::open(&url) {
+        Ok(source) => Ok(Box::new(source)),
+        Err(e) if e.kind() == ErrorKind::NotConnected => Err(OpenError::Offline(e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(OpenError::Missing(e)),
+        Err(e) => Err(OpenError::Unavailable(e)),
+    }
+}
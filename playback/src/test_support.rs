@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rodio::{Sink, Source};
+
+use crate::backend::{BackendError, OutputHandle, PlaybackBackend};
+
+const TICK: Duration = Duration::from_millis(10);
+
+/// A backend without any audio device.
+///
+/// Samples are pulled from the sink in real time and discarded, so position,
+/// end of track and the realtime FFT behave as with a real output.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullBackend;
+
+struct NullOutput {
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for NullOutput {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl PlaybackBackend for NullBackend {
+    fn open_sink(&self) -> Result<(Sink, OutputHandle), BackendError> {
+        let (sink, mut output) = Sink::new_idle();
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread_stopped = stopped.clone();
+        thread::spawn(move || {
+            while !thread_stopped.load(Ordering::Relaxed) {
+                let samples_per_tick =
+                    output.sample_rate() as u128 * output.channels() as u128 * TICK.as_millis()
+                        / 1000;
+
+                for _ in 0..samples_per_tick.max(1) {
+                    if output.next().is_none() {
+                        return;
+                    }
+                }
+
+                thread::sleep(TICK);
+            }
+        });
+
+        Ok((sink, Box::new(NullOutput { stopped })))
+    }
+}
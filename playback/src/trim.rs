@@ -0,0 +1,121 @@
+use std::path::Path;
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::Source;
+
+/// The priming and padding an encoder added around the audio of a lossy
+/// file, read from its LAME or `iTunSMPB` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderTrim {
+    /// Frames to drop from the start of the decoded audio.
+    pub leading_frames: u64,
+    /// Frames of actual audio after them, played to the end when unknown.
+    pub valid_frames: Option<u64>,
+}
+
+/// Whether the decoder drops the encoder delay and padding of a file on its
+/// own. rodio opens MP3s with the gapless mode of symphonia, which reads the
+/// LAME tag itself, so they must not be trimmed again.
+pub fn is_trimmed_by_decoder(path: &Path) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| x.eq_ignore_ascii_case("mp3"))
+}
+
+/// A decoded file without its encoder delay and padding, so tracks that
+/// follow each other join without a gap.
+///
+/// Positions are the ones of the trimmed audio, seeks skip the delay on
+/// their own.
+pub struct TrimmedSource<S> {
+    input: S,
+    trim: EncoderTrim,
+    channels: u16,
+    sample_rate: u32,
+    // Samples still to drop before the first one played
+    pending_skip: u64,
+    // Samples played since the start of the trimmed audio
+    position: u64,
+}
+
+impl<S: Source<Item = f32>> TrimmedSource<S> {
+    pub fn new(input: S, trim: EncoderTrim) -> Self {
+        let channels = input.channels();
+        TrimmedSource {
+            sample_rate: input.sample_rate(),
+            pending_skip: trim.leading_frames * channels as u64,
+            position: 0,
+            input,
+            trim,
+            channels,
+        }
+    }
+
+    fn end_sample(&self) -> Option<u64> {
+        self.trim.valid_frames.map(|x| x * self.channels as u64)
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for TrimmedSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        while self.pending_skip > 0 {
+            self.pending_skip -= 1;
+            self.input.next()?;
+        }
+
+        if self.end_sample().is_some_and(|end| self.position >= end) {
+            return None;
+        }
+
+        let sample = self.input.next()?;
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for TrimmedSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        let remaining = self.input.current_frame_len()?;
+        match self.end_sample() {
+            Some(end) => Some(remaining.min(end.saturating_sub(self.position) as usize)),
+            None => Some(remaining),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        let leading =
+            Duration::from_secs_f64(self.trim.leading_frames as f64 / self.sample_rate as f64);
+
+        match self.trim.valid_frames {
+            Some(frames) => Some(Duration::from_secs_f64(
+                frames as f64 / self.sample_rate as f64,
+            )),
+            None => self
+                .input
+                .total_duration()
+                .map(|x| x.saturating_sub(leading)),
+        }
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let leading =
+            Duration::from_secs_f64(self.trim.leading_frames as f64 / self.sample_rate as f64);
+        self.input.try_seek(pos + leading)?;
+
+        let frame = (pos.as_secs_f64() * self.sample_rate as f64) as u64;
+        self.pending_skip = 0;
+        self.position = frame * self.channels as u64;
+        Ok(())
+    }
+}
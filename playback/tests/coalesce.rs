@@ -0,0 +1,92 @@
+use std::time::{Duration, SystemTime};
+
+use playback::coalesce::{CoalescedSignal, TickCoalescer};
+use playback::player::{PlaybackState, PlayerStatus, RealtimeFFTStatus};
+use playback::PlaybackMode;
+
+fn status(sequence: u64, id: i32, position: u64) -> PlayerStatus {
+    PlayerStatus {
+        id: Some(id),
+        index: Some(0),
+        path: None,
+        position: Duration::from_secs(position),
+        state: PlaybackState::Playing,
+        playlist: vec![id],
+        played: vec![true],
+        history_boundary: 0,
+        volume: 1.0,
+        playback_mode: PlaybackMode::Sequential,
+        offline: vec![],
+        missing: vec![],
+        limiter_reduction: 0.0,
+        output_device: None,
+        sequence,
+        timestamp: SystemTime::now(),
+    }
+}
+
+fn frame(sequence: u64) -> RealtimeFFTStatus {
+    RealtimeFFTStatus {
+        data: vec![0.5; 4],
+        sequence,
+        timestamp: SystemTime::now(),
+    }
+}
+
+fn sequences(signals: &[CoalescedSignal]) -> Vec<u64> {
+    signals
+        .iter()
+        .map(|x| match x {
+            CoalescedSignal::Status(status) => status.sequence,
+            CoalescedSignal::Tick(tick) => tick.status.as_ref().map_or(0, |x| x.sequence),
+        })
+        .collect()
+}
+
+#[test]
+fn progress_and_frames_wait_for_the_tick() {
+    let mut coalescer = TickCoalescer::default();
+    assert!(coalescer.take_tick().is_none());
+
+    // The first status is always sent
+    assert_eq!(sequences(&coalescer.push_status(status(1, 7, 0))), vec![1]);
+
+    coalescer.push_fft(frame(2));
+    assert!(coalescer.push_status(status(2, 7, 0)).is_empty());
+    assert!(coalescer.push_status(status(3, 7, 1)).is_empty());
+    coalescer.push_fft(frame(4));
+
+    let tick = coalescer.take_tick().unwrap();
+    assert_eq!(tick.since_sequence, 1);
+    assert_eq!(tick.status.unwrap().sequence, 3);
+    assert_eq!(
+        tick.frames.iter().map(|x| x.sequence).collect::<Vec<_>>(),
+        vec![2, 4]
+    );
+    assert!(coalescer.take_tick().is_none());
+
+    // Frames alone still go out
+    coalescer.push_fft(frame(5));
+    let tick = coalescer.take_tick().unwrap();
+    assert_eq!(tick.since_sequence, 3);
+    assert!(tick.status.is_none());
+}
+
+#[test]
+fn other_changes_are_sent_right_away_after_the_held_back_ones() {
+    let mut coalescer = TickCoalescer::default();
+    coalescer.push_status(status(1, 7, 0));
+    assert!(coalescer.push_status(status(2, 7, 1)).is_empty());
+
+    let mut paused = status(3, 7, 1);
+    paused.state = PlaybackState::Paused;
+    let signals = coalescer.push_status(paused);
+    assert!(matches!(signals[0], CoalescedSignal::Tick(_)));
+    assert!(matches!(signals[1], CoalescedSignal::Status(_)));
+    assert_eq!(sequences(&signals), vec![2, 3]);
+
+    // Another track, nothing was held back
+    let signals = coalescer.push_status(status(4, 8, 0));
+    assert_eq!(sequences(&signals), vec![4]);
+    assert!(coalescer.take_tick().is_none());
+}
@@ -0,0 +1,176 @@
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use analysis::seek_table::{SeekPoint, SeekTable};
+use dsd::test_support::{write_tone_fixture, DsdFormat};
+use rodio::{Decoder, Source};
+
+use metadata::gapless::read_gapless_info;
+use metadata::test_support::{
+    sine_samples, write_lame_mp3_fixture, write_sine_fixture, FixtureFormat,
+};
+use playback::dsd_source::DsdSource;
+use playback::seek_source::{SplicedReader, TableSeekSource};
+use playback::source::open_media_file;
+use playback::trim::{is_trimmed_by_decoder, EncoderTrim, TrimmedSource};
+
+const SAMPLE_RATE: u32 = 48000;
+const FRAMES: usize = 10000;
+
+#[test]
+fn lossless_fixtures_play_back_unchanged() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for format in [FixtureFormat::Wav, FixtureFormat::Aiff, FixtureFormat::Flac] {
+        for channels in [1, 2] {
+            let path = dir
+                .path()
+                .join(format!("tone_{}.{}", channels, format.extension()));
+            write_sine_fixture(&path, format, SAMPLE_RATE, channels, FRAMES).unwrap();
+
+            let file = open_media_file(&path).unwrap();
+            let decoder = Decoder::new(BufReader::new(file)).unwrap();
+            assert_eq!(decoder.channels(), channels, "{:?}", format);
+            assert_eq!(decoder.sample_rate(), SAMPLE_RATE, "{:?}", format);
+
+            let samples: Vec<i16> = decoder.collect();
+            assert_eq!(
+                samples,
+                sine_samples(SAMPLE_RATE, channels, FRAMES),
+                "{:?} with {} channels",
+                format,
+                channels
+            );
+        }
+    }
+}
+
+#[test]
+fn dsd_fixtures_play_back_as_pcm() {
+    let dir = tempfile::tempdir().unwrap();
+    let dsd_rate = 2_822_400;
+
+    for format in [DsdFormat::Dsf, DsdFormat::Dff] {
+        let path = dir.path().join(format!("tone.{}", format.extension()));
+        write_tone_fixture(&path, format, dsd_rate, 2, dsd_rate as usize / 2, &[]).unwrap();
+
+        let file = open_media_file(&path).unwrap();
+        let mut source = DsdSource::new(BufReader::new(file)).unwrap();
+        assert_eq!(source.channels(), 2, "{:?}", format);
+        assert_eq!(source.sample_rate(), 176_400, "{:?}", format);
+        assert_eq!(
+            source.total_duration(),
+            Some(Duration::from_millis(500)),
+            "{:?}",
+            format
+        );
+
+        source.try_seek(Duration::from_millis(250)).unwrap();
+        let samples: Vec<f32> = source.collect();
+        assert_eq!(samples.len(), 176_400 / 4 * 2, "{:?}", format);
+
+        // Both tones are at half of full scale
+        let peak = samples.iter().fold(0f32, |peak, x| peak.max(x.abs()));
+        assert!((peak - 0.5).abs() < 0.02, "{:?}: {}", format, peak);
+    }
+}
+
+// The canonical header written by the WAV fixtures
+const WAV_HEADER_LEN: u64 = 44;
+
+#[test]
+fn spliced_readers_skip_to_the_offset() {
+    let data: Vec<u8> = (0..100).collect();
+    let mut reader = SplicedReader::new(std::io::Cursor::new(data), 10, 60).unwrap();
+
+    let mut spliced = Vec::new();
+    reader.read_to_end(&mut spliced).unwrap();
+    assert_eq!(spliced, (0..10).chain(60..100).collect::<Vec<u8>>());
+}
+
+#[test]
+fn seek_tables_land_on_the_exact_frame() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tone.wav");
+    write_sine_fixture(&path, FixtureFormat::Wav, 8000, 1, 16000).unwrap();
+
+    // A point every 1000 frames of the 16-bit mono data
+    let table = SeekTable {
+        sample_rate: 8000,
+        header_len: WAV_HEADER_LEN,
+        points: (0..16)
+            .map(|x| SeekPoint {
+                frame: x * 1000,
+                byte_offset: WAV_HEADER_LEN + x * 2000,
+            })
+            .collect(),
+    };
+
+    let decoder = Decoder::new(BufReader::new(open_media_file(&path).unwrap())).unwrap();
+    let mut source = TableSeekSource::new(
+        path.clone(),
+        Arc::new(table),
+        Box::new(decoder.convert_samples::<f32>()),
+    );
+    source.try_seek(Duration::from_millis(600)).unwrap();
+    let seeked: Vec<f32> = source.by_ref().take(100).collect();
+
+    let expected: Vec<f32> = Decoder::new(BufReader::new(open_media_file(&path).unwrap()))
+        .unwrap()
+        .convert_samples::<f32>()
+        .skip(4800)
+        .take(100)
+        .collect();
+    assert_eq!(seeked, expected);
+    // The length is the decoder's, rodio rounds it oddly for whole seconds
+    let decoder = Decoder::new(BufReader::new(open_media_file(&path).unwrap())).unwrap();
+    assert_eq!(source.total_duration(), decoder.total_duration());
+}
+
+#[test]
+fn mp3_decoder_trims_lame_delay_and_padding() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("silence.mp3");
+    write_lame_mp3_fixture(&path, 100, 576, 1000).unwrap();
+
+    // The trimming is found and stored by the scan
+    assert!(read_gapless_info(&path).unwrap().is_some());
+    assert!(is_trimmed_by_decoder(&path));
+    assert!(!is_trimmed_by_decoder(Path::new("track.m4a")));
+
+    let decoder = Decoder::new(BufReader::new(open_media_file(&path).unwrap())).unwrap();
+    assert_eq!(decoder.channels(), 2);
+    let frames = decoder.count() / 2;
+    assert_eq!(frames, 100 * 1152 - 576 - 1000);
+}
+
+#[test]
+fn encoder_delay_and_padding_are_trimmed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tone.wav");
+    write_sine_fixture(&path, FixtureFormat::Wav, 8000, 2, 8000).unwrap();
+    let decode = || {
+        Decoder::new(BufReader::new(open_media_file(&path).unwrap()))
+            .unwrap()
+            .convert_samples::<f32>()
+    };
+    let trim = EncoderTrim {
+        leading_frames: 1000,
+        valid_frames: Some(4000),
+    };
+
+    let mut source = TrimmedSource::new(decode(), trim);
+    assert_eq!(source.total_duration(), Some(Duration::from_millis(500)));
+    let trimmed: Vec<f32> = source.by_ref().collect();
+    let expected: Vec<f32> = decode().skip(2000).take(8000).collect();
+    assert_eq!(trimmed, expected);
+
+    // Positions leave the delay out
+    let mut source = TrimmedSource::new(decode(), trim);
+    source.try_seek(Duration::from_millis(250)).unwrap();
+    let seeked: Vec<f32> = source.collect();
+    let expected: Vec<f32> = decode().skip(6000).take(4000).collect();
+    assert_eq!(seeked, expected);
+}
@@ -0,0 +1,141 @@
+use rodio::buffer::SamplesBuffer;
+
+use playback::dsp::{
+    db_to_factor, Amplified, Compressor, Crossfeed, Limiter, LimiterControl, Mono, SharedGain,
+    SharedSwitch,
+};
+
+#[test]
+fn gain_is_applied() {
+    let gain = SharedGain::new(2.0);
+    let source = SamplesBuffer::new(1, 44100, vec![0.1f32, -0.1, 0.75]);
+
+    let samples: Vec<f32> = Amplified::new(source, gain).collect();
+
+    assert_eq!(samples, vec![0.2, -0.2, 1.5]);
+}
+
+#[test]
+fn gain_changes_while_playing() {
+    let gain = SharedGain::default();
+    let source = SamplesBuffer::new(1, 44100, vec![0.5f32; 4]);
+    let mut amplified = Amplified::new(source, gain.clone());
+
+    assert_eq!(amplified.next(), Some(0.5));
+    gain.set_db(-6.0);
+    assert_eq!(amplified.next(), Some(0.5 * db_to_factor(-6.0)));
+}
+
+#[test]
+fn limiter_keeps_peaks_below_full_scale() {
+    let control = LimiterControl::default();
+    let input: Vec<f32> = (0..44100).map(|x| 1.8 * (x as f32 * 0.05).sin()).collect();
+    let source = SamplesBuffer::new(2, 44100, input.clone());
+
+    let output: Vec<f32> = Limiter::new(source, control.clone()).collect();
+
+    assert_eq!(output.len(), input.len());
+    assert!(output.iter().all(|x| x.abs() < 1.0));
+    assert!(control.take_reduction() > 4.0);
+    assert_eq!(control.take_reduction(), 0.0);
+}
+
+#[test]
+fn limiter_leaves_quiet_audio_alone() {
+    let control = LimiterControl::default();
+    let input: Vec<f32> = (0..4410).map(|x| 0.5 * (x as f32 * 0.05).sin()).collect();
+    let source = SamplesBuffer::new(1, 44100, input.clone());
+
+    let output: Vec<f32> = Limiter::new(source, control.clone()).collect();
+
+    assert_eq!(output, input);
+    assert_eq!(control.take_reduction(), 0.0);
+}
+
+#[test]
+fn disabled_limiter_passes_peaks_through() {
+    let control = LimiterControl::new(false);
+    let source = SamplesBuffer::new(1, 44100, vec![1.5f32; 1000]);
+
+    let output: Vec<f32> = Limiter::new(source, control.clone()).collect();
+
+    assert!(output.iter().all(|x| *x == 1.5));
+    assert_eq!(control.take_reduction(), 0.0);
+}
+
+#[test]
+fn mono_switches_between_frames() {
+    let switch = SharedSwitch::default();
+    let source = SamplesBuffer::new(2, 44100, vec![1.0f32, 0.0, 1.0, 0.0, 1.0, 0.0]);
+    let mut mono = Mono::new(source, switch.clone());
+
+    assert_eq!(mono.next(), Some(1.0));
+    // The rest of the frame is not affected
+    switch.set(true);
+    assert_eq!(mono.next(), Some(0.0));
+
+    let rest: Vec<f32> = mono.by_ref().take(2).collect();
+    assert_eq!(rest, vec![0.5, 0.5]);
+
+    switch.set(false);
+    let rest: Vec<f32> = mono.collect();
+    assert_eq!(rest, vec![1.0, 0.0]);
+}
+
+// A square wave, its RMS level is its amplitude
+fn square(amplitude: f32, frames: usize) -> Vec<f32> {
+    (0..frames)
+        .map(|x| if x % 2 == 0 { amplitude } else { -amplitude })
+        .collect()
+}
+
+#[test]
+fn night_mode_tames_loud_passages_only() {
+    let switch = SharedSwitch::new(true);
+
+    let loud = SamplesBuffer::new(1, 44100, square(0.9, 44100));
+    let output: Vec<f32> = Compressor::new(loud, switch.clone()).collect();
+    assert!(output.last().unwrap().abs() < 0.4, "{:?}", output.last());
+
+    // Tracks at the reference level keep their volume
+    let reference = db_to_factor(-18.0);
+    let normal = SamplesBuffer::new(1, 44100, square(reference, 44100));
+    let output: Vec<f32> = Compressor::new(normal, switch).collect();
+    let level = output.last().unwrap().abs();
+    assert!((level / reference - 1.0).abs() < 0.05, "{}", level);
+}
+
+#[test]
+fn night_mode_off_passes_audio_through() {
+    let input = square(0.9, 4410);
+    let source = SamplesBuffer::new(2, 44100, input.clone());
+
+    let output: Vec<f32> = Compressor::new(source, SharedSwitch::default()).collect();
+
+    assert_eq!(output, input);
+}
+
+#[test]
+fn crossfeed_blends_low_frequencies_into_the_other_channel() {
+    let input: Vec<f32> = (0..4410).flat_map(|_| [1.0f32, 0.0]).collect();
+    let source = SamplesBuffer::new(2, 44100, input);
+
+    let output: Vec<f32> = Crossfeed::new(source, SharedSwitch::new(true)).collect();
+
+    let (left, right) = (output[output.len() - 2], output[output.len() - 1]);
+    assert!((left - 1.0 / 1.3).abs() < 0.01, "{}", left);
+    assert!((right - 0.3 / 1.3).abs() < 0.01, "{}", right);
+}
+
+#[test]
+fn crossfeed_leaves_mono_and_switched_off_audio_alone() {
+    let input = square(0.5, 4410);
+
+    let stereo = SamplesBuffer::new(2, 44100, input.clone());
+    let output: Vec<f32> = Crossfeed::new(stereo, SharedSwitch::default()).collect();
+    assert_eq!(output, input);
+
+    let mono = SamplesBuffer::new(1, 44100, input.clone());
+    let output: Vec<f32> = Crossfeed::new(mono, SharedSwitch::new(true)).collect();
+    assert_eq!(output, input);
+}
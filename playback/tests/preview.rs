@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use rodio::buffer::SamplesBuffer;
+
+use playback::preview::{mix_transition, preview_length, Fade};
+
+const SAMPLE_RATE: u32 = 1000;
+
+fn constant(level: f32, seconds: usize) -> SamplesBuffer<f32> {
+    SamplesBuffer::new(1, SAMPLE_RATE, vec![level; seconds * SAMPLE_RATE as usize])
+}
+
+#[test]
+fn transition_crossfades_in_the_middle() {
+    let output: Vec<f32> = mix_transition(
+        constant(1.0, 10),
+        constant(0.5, 10),
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        Duration::from_secs(3),
+    )
+    .collect();
+
+    assert_eq!(output.len(), 3000);
+    assert_eq!(output[0], 1.0);
+    assert_eq!(output[999], 1.0);
+    // Halfway through the crossfade both tracks play at half volume
+    assert!((output[1500] - 0.75).abs() < 0.01, "{}", output[1500]);
+    assert!((output[2500] - 0.5).abs() < 1e-6, "{}", output[2500]);
+}
+
+#[test]
+fn transition_without_crossfade_cuts() {
+    let output: Vec<f32> = mix_transition(
+        constant(1.0, 10),
+        constant(0.5, 10),
+        Duration::from_secs(1),
+        Duration::ZERO,
+        Duration::from_secs(2),
+    )
+    .collect();
+
+    assert_eq!(output.len(), 2000);
+    assert!(output[..1000].iter().all(|x| *x == 1.0));
+    assert!(output[1000..].iter().all(|x| *x == 0.5));
+}
+
+#[test]
+fn fade_out_ends_the_source() {
+    let output: Vec<f32> = Fade::fade_out(
+        constant(1.0, 10),
+        Duration::from_secs(1),
+        Duration::from_secs(2),
+    )
+    .collect();
+
+    assert_eq!(output.len(), 3000);
+    assert!((output[2000] - 0.5).abs() < 0.01);
+    assert!(output[2999] < 0.01);
+}
+
+#[test]
+fn preview_length_is_bounded() {
+    assert_eq!(preview_length(8.0), Duration::from_secs(8));
+    assert_eq!(preview_length(0.5), Duration::from_secs(2));
+    assert_eq!(preview_length(600.0), Duration::from_secs(60));
+    assert_eq!(preview_length(f64::NAN), Duration::from_secs(10));
+}
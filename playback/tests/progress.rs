@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+use playback::progress::{ProgressThrottle, COARSE_PROGRESS_INTERVAL, PROGRESS_TICK};
+
+// Ticks at the pace of the player, returns the ticks that were reported
+fn reported_ticks(throttle: &mut ProgressThrottle, start: Instant, ticks: u32) -> Vec<u32> {
+    (0..ticks)
+        .filter(|x| throttle.should_report(start + PROGRESS_TICK * *x))
+        .collect()
+}
+
+#[test]
+fn every_tick_is_reported_by_default() {
+    let mut throttle = ProgressThrottle::default();
+    assert_eq!(throttle.interval(), PROGRESS_TICK);
+    assert_eq!(
+        reported_ticks(&mut throttle, Instant::now(), 5),
+        vec![0, 1, 2, 3, 4]
+    );
+}
+
+#[test]
+fn reports_follow_the_configured_interval() {
+    let mut throttle = ProgressThrottle::default();
+    throttle.set_interval(Duration::from_millis(250));
+    assert_eq!(
+        reported_ticks(&mut throttle, Instant::now(), 10),
+        vec![0, 3, 6, 9]
+    );
+
+    // Faster than the player checks its position isn't possible
+    throttle.set_interval(Duration::from_millis(10));
+    assert_eq!(throttle.interval(), PROGRESS_TICK);
+}
+
+#[test]
+fn coarse_mode_reports_once_a_second() {
+    let start = Instant::now();
+    let mut throttle = ProgressThrottle::default();
+    throttle.set_coarse(true);
+    assert_eq!(throttle.interval(), COARSE_PROGRESS_INTERVAL);
+    assert_eq!(reported_ticks(&mut throttle, start, 25), vec![0, 10, 20]);
+
+    // A longer configured interval is kept
+    throttle.set_interval(Duration::from_secs(2));
+    assert_eq!(throttle.interval(), Duration::from_secs(2));
+
+    // Back to the configured interval, with a report right away
+    throttle.set_interval(PROGRESS_TICK);
+    throttle.set_coarse(false);
+    assert!(throttle.should_report(start + PROGRESS_TICK * 21));
+}
@@ -0,0 +1,329 @@
+use std::path::PathBuf;
+
+use proptest::prelude::*;
+
+use playback::queue::{AlbumTrack, DuplicatePolicy, PlayQueue, PlaylistItem};
+use playback::PlaybackMode;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Add,
+    Remove(usize),
+    Move(usize, usize),
+    Switch(usize),
+    Next(PlaybackMode),
+    Previous,
+    Clear,
+}
+
+fn mode() -> impl Strategy<Value = PlaybackMode> {
+    prop_oneof![
+        Just(PlaybackMode::Sequential),
+        Just(PlaybackMode::RepeatAll),
+        Just(PlaybackMode::RepeatOne),
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    // Indices go past the usual queue length to cover out of bounds requests
+    prop_oneof![
+        4 => Just(Op::Add),
+        2 => (0..12usize).prop_map(Op::Remove),
+        3 => (0..12usize, 0..12usize).prop_map(|(a, b)| Op::Move(a, b)),
+        2 => (0..12usize).prop_map(Op::Switch),
+        2 => mode().prop_map(Op::Next),
+        1 => Just(Op::Previous),
+        1 => Just(Op::Clear),
+    ]
+}
+
+/// A straightforward model of the queue that tracks the current item by id
+/// instead of by index.
+#[derive(Debug, Default)]
+struct Model {
+    ids: Vec<i32>,
+    current: Option<i32>,
+    next_id: i32,
+}
+
+impl Model {
+    fn position(&self) -> Option<usize> {
+        self.current
+            .and_then(|id| self.ids.iter().position(|x| *x == id))
+    }
+
+    fn apply(&mut self, op: &Op) -> Option<i32> {
+        match *op {
+            Op::Add => {
+                self.next_id += 1;
+                self.ids.push(self.next_id);
+                return Some(self.next_id);
+            }
+            Op::Remove(index) => {
+                if index < self.ids.len() {
+                    let id = self.ids.remove(index);
+                    if self.current == Some(id) {
+                        self.current = None;
+                    }
+                }
+            }
+            Op::Move(old_index, new_index) => {
+                if old_index < self.ids.len() && new_index < self.ids.len() {
+                    let id = self.ids.remove(old_index);
+                    self.ids.insert(new_index, id);
+                }
+            }
+            Op::Switch(index) => {
+                if index < self.ids.len() {
+                    self.current = Some(self.ids[index]);
+                }
+            }
+            Op::Next(mode) => {
+                if let Some(position) = self.position() {
+                    if position + 1 < self.ids.len() {
+                        self.current = Some(self.ids[position + 1]);
+                    } else if mode == PlaybackMode::RepeatAll {
+                        self.current = Some(self.ids[0]);
+                    }
+                }
+            }
+            Op::Previous => {
+                if let Some(position) = self.position() {
+                    if position > 0 {
+                        self.current = Some(self.ids[position - 1]);
+                    }
+                }
+            }
+            Op::Clear => {
+                self.ids.clear();
+                self.current = None;
+            }
+        }
+
+        None
+    }
+}
+
+/// Drive the queue the same way the player does.
+fn apply(queue: &mut PlayQueue, op: &Op, added: Option<i32>) {
+    match *op {
+        Op::Add => queue.push(PlaylistItem::new(
+            added.unwrap(),
+            PathBuf::from(format!("{}.mp3", added.unwrap())),
+        )),
+        Op::Remove(index) => {
+            queue.remove(index);
+        }
+        Op::Move(old_index, new_index) => {
+            queue.move_item(old_index, new_index);
+        }
+        Op::Switch(index) => {
+            queue.select(index);
+        }
+        Op::Next(mode) => {
+            if let Some(index) = queue.next_index(mode) {
+                queue.select(index);
+            }
+        }
+        Op::Previous => {
+            if let Some(index) = queue.previous_index() {
+                queue.select(index);
+            }
+        }
+        Op::Clear => queue.clear(),
+    }
+}
+
+proptest! {
+    #[test]
+    fn queue_matches_model(ops in prop::collection::vec(op(), 0..64)) {
+        let mut queue = PlayQueue::new();
+        let mut model = Model::default();
+
+        for op in &ops {
+            let added = model.apply(op);
+            apply(&mut queue, op, added);
+
+            // The current index is always valid
+            if let Some(index) = queue.current_index() {
+                prop_assert!(index < queue.len(), "{:?}: index {} out of {}", op, index, queue.len());
+            }
+
+            // The reported playlist matches the model, ids are never lost or duplicated
+            prop_assert_eq!(queue.ids(), model.ids.clone(), "{:?}", op);
+
+            // The current index keeps pointing at the same item
+            prop_assert_eq!(queue.current().map(|x| x.id), model.current, "{:?}", op);
+        }
+    }
+
+    #[test]
+    fn moving_keeps_items(
+        len in 1..16usize,
+        current in 0..16usize,
+        old_index in 0..16usize,
+        new_index in 0..16usize,
+    ) {
+        let mut queue = PlayQueue::new();
+        for id in 0..len as i32 {
+            queue.push(PlaylistItem::new(id, PathBuf::new()));
+        }
+        queue.select(current);
+        let current_id = queue.current().map(|x| x.id);
+
+        let moved = queue.move_item(old_index, new_index);
+        prop_assert_eq!(moved, old_index < len && new_index < len);
+
+        let mut ids = queue.ids();
+        ids.sort();
+        prop_assert_eq!(ids, (0..len as i32).collect::<Vec<_>>());
+        prop_assert_eq!(queue.current().map(|x| x.id), current_id);
+    }
+
+    #[test]
+    fn skipping_never_lands_on_missing_items(
+        available in prop::collection::vec(any::<bool>(), 1..16),
+        from in 0..16usize,
+        mode in mode(),
+    ) {
+        let mut queue = PlayQueue::new();
+        for (id, available) in available.iter().enumerate() {
+            queue.push(PlaylistItem::new(id as i32, PathBuf::new()));
+            queue.set_available(id, *available);
+        }
+        let from = from % available.len();
+
+        match queue.next_available_index(from, mode) {
+            Some(next) => {
+                prop_assert!(next != from);
+                prop_assert!(available[next]);
+                // Nothing playable was jumped over
+                let skipped: Vec<usize> = if next > from {
+                    (from + 1..next).collect()
+                } else {
+                    (from + 1..available.len()).chain(0..next).collect()
+                };
+                prop_assert!(skipped.iter().all(|x| !available[*x]));
+                if next < from {
+                    prop_assert_eq!(mode, PlaybackMode::RepeatAll);
+                }
+            }
+            None => {
+                let rest: Vec<usize> = if mode == PlaybackMode::RepeatAll {
+                    (0..available.len()).filter(|x| *x != from).collect()
+                } else {
+                    (from + 1..available.len()).collect()
+                };
+                prop_assert!(rest.iter().all(|x| !available[*x]));
+            }
+        }
+    }
+
+    #[test]
+    fn reordering_follows_the_current_item(
+        order in (1..16usize).prop_flat_map(|len| Just((0..len).collect::<Vec<_>>()).prop_shuffle()),
+        current in 0..16usize,
+    ) {
+        let mut queue = PlayQueue::new();
+        for id in 0..order.len() as i32 {
+            queue.push(PlaylistItem::new(id, PathBuf::new()));
+        }
+        queue.select(current);
+        let current_id = queue.current().map(|x| x.id);
+
+        prop_assert!(queue.reorder(&order));
+        prop_assert_eq!(queue.ids(), order.iter().map(|x| *x as i32).collect::<Vec<_>>());
+        prop_assert_eq!(queue.current().map(|x| x.id), current_id);
+
+        // Anything but a permutation is rejected
+        let mut invalid = order.clone();
+        invalid.push(0);
+        prop_assert!(!queue.reorder(&invalid));
+    }
+}
+
+#[test]
+fn only_the_next_track_of_the_same_album_follows() {
+    let track_on = |album_id, disc_number, track_number| {
+        PlaylistItem::new(0, PathBuf::new()).with_album(Some(AlbumTrack {
+            album_id,
+            disc_number,
+            track_number,
+        }))
+    };
+    let track = |album_id, track_number| track_on(album_id, None, track_number);
+
+    assert!(track(1, 2).follows(&track(1, 1)));
+    assert!(track_on(1, Some(1), 2).follows(&track_on(1, Some(1), 1)));
+    assert!(!track_on(1, Some(2), 2).follows(&track_on(1, Some(1), 1)));
+    assert!(!track(1, i32::MIN).follows(&track(1, i32::MAX)));
+    assert!(!track(1, 3).follows(&track(1, 1)));
+    assert!(!track(1, 1).follows(&track(1, 2)));
+    assert!(!track(2, 2).follows(&track(1, 1)));
+    assert!(!PlaylistItem::new(0, PathBuf::new()).follows(&track(1, 1)));
+}
+
+#[test]
+fn duplicates_follow_the_policy() {
+    let items = |ids: &[i32]| -> Vec<PlaylistItem> {
+        ids.iter()
+            .map(|id| PlaylistItem::new(*id, PathBuf::new()))
+            .collect()
+    };
+    let queue_of = |ids: &[i32]| {
+        let mut queue = PlayQueue::new();
+        queue.extend(items(ids), DuplicatePolicy::Allow);
+        queue
+    };
+
+    let mut queue = queue_of(&[1, 2]);
+    assert!(queue
+        .extend(items(&[2, 3, 3]), DuplicatePolicy::Allow)
+        .is_empty());
+    assert_eq!(queue.ids(), [1, 2, 2, 3, 3]);
+
+    let mut queue = queue_of(&[1, 2]);
+    assert_eq!(
+        queue.extend(items(&[2, 3, 3]), DuplicatePolicy::Skip),
+        [2, 3]
+    );
+    assert_eq!(queue.ids(), [1, 2, 3]);
+
+    // Nothing is added until the user decides
+    let mut queue = queue_of(&[1, 2]);
+    assert_eq!(queue.extend(items(&[3, 2]), DuplicatePolicy::Ask), [2]);
+    assert_eq!(queue.ids(), [1, 2]);
+    assert!(queue
+        .extend(items(&[3, 4]), DuplicatePolicy::Ask)
+        .is_empty());
+    assert_eq!(queue.ids(), [1, 2, 3, 4]);
+}
+
+#[test]
+fn played_items_before_the_current_one_are_history() {
+    let mut queue = PlayQueue::new();
+    for id in 1..=4 {
+        queue.push(PlaylistItem::new(id, PathBuf::new()));
+    }
+    assert_eq!(queue.history_boundary(), 0);
+
+    queue.select(0);
+    queue.select(1);
+    assert_eq!(queue.played(), [true, true, false, false]);
+    // The current track isn't history yet
+    assert_eq!(queue.history_boundary(), 1);
+
+    // Played items keep their mark when they move
+    queue.move_item(0, 3);
+    assert_eq!(queue.ids(), [2, 3, 4, 1]);
+    assert_eq!(queue.played(), [true, false, false, true]);
+    assert_eq!(queue.history_boundary(), 0);
+
+    queue.select(2);
+    assert_eq!(queue.history_boundary(), 1);
+    queue.deselect();
+    assert_eq!(queue.history_boundary(), 1);
+
+    queue.clear();
+    assert!(queue.played().is_empty());
+}
@@ -0,0 +1,47 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use tokio::sync::mpsc;
+
+use playback::sequence::{timestamp_millis, EventSender};
+use playback::PlayerEvent;
+
+#[test]
+fn events_are_numbered_in_the_order_they_are_sent() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let events = EventSender::new(sender);
+    // Events sent from elsewhere share the numbering
+    let other = events.clone();
+
+    events.send(PlayerEvent::Stopped).unwrap();
+    other.send(PlayerEvent::VolumeUpdated(0.5)).unwrap();
+    events.send(PlayerEvent::EndOfPlaylist).unwrap();
+
+    let received: Vec<_> = std::iter::from_fn(|| receiver.try_recv().ok()).collect();
+    assert_eq!(
+        received.iter().map(|x| x.sequence).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert!(received
+        .windows(2)
+        .all(|x| x[0].timestamp <= x[1].timestamp));
+    assert!(matches!(received[1].event, PlayerEvent::VolumeUpdated(_)));
+}
+
+#[test]
+fn events_are_returned_when_nobody_listens() {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let events = EventSender::new(sender);
+    drop(receiver);
+
+    let error = events.send(PlayerEvent::Stopped).unwrap_err();
+    assert!(matches!(error.0, PlayerEvent::Stopped));
+}
+
+#[test]
+fn timestamps_are_sent_in_milliseconds() {
+    assert_eq!(timestamp_millis(UNIX_EPOCH), 0);
+    assert_eq!(
+        timestamp_millis(UNIX_EPOCH + Duration::from_millis(1_500)),
+        1_500
+    );
+}
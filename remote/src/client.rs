@@ -0,0 +1,220 @@
+use std::time::Duration;
+
+use database::actions::remote::RemoteFileRecord;
+use md5::{Digest, Md5};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde_json::Value;
+use thiserror::Error;
+use tracing::debug;
+use ureq::{Agent, AgentBuilder};
+
+/// The version of the API requests are made with, old enough for any server.
+pub const CLIENT_API_VERSION: &str = "1.13.0";
+const CLIENT_NAME: &str = "rune";
+
+// Albums asked for at once while reading a whole library
+const ALBUM_PAGE_SIZE: usize = 500;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Error, Debug)]
+pub enum RemoteError {
+    #[error("request failed: {0}")]
+    Http(String),
+    #[error("server refused the request ({code}): {message}")]
+    Api { code: i64, message: String },
+    #[error("unexpected answer: {0}")]
+    Parse(String),
+}
+
+impl From<ureq::Error> for RemoteError {
+    fn from(e: ureq::Error) -> Self {
+        RemoteError::Http(e.to_string())
+    }
+}
+
+/// A client of the Subsonic REST API.
+///
+/// Every request is signed with a new salt, the password itself is never
+/// sent.
+#[derive(Debug, Clone)]
+pub struct SubsonicClient {
+    base_url: String,
+    username: String,
+    password: String,
+    agent: Agent,
+}
+
+impl SubsonicClient {
+    /// # Arguments
+    /// * `base_url` - The address of the server, without `/rest`.
+    /// * `username` - The user to log in with.
+    /// * `password` - The password of the user.
+    pub fn new(base_url: &str, username: &str, password: &str) -> Self {
+        SubsonicClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            agent: AgentBuilder::new()
+                .timeout_connect(CONNECT_TIMEOUT)
+                .timeout_read(READ_TIMEOUT)
+                .build(),
+        }
+    }
+
+    fn url(&self, method: &str, params: &[(&str, &str)]) -> String {
+        let salt: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(12)
+            .map(char::from)
+            .collect();
+        let token = hex::encode(Md5::digest(format!("{}{}", self.password, salt)));
+
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        query
+            .append_pair("u", &self.username)
+            .append_pair("t", &token)
+            .append_pair("s", &salt)
+            .append_pair("v", CLIENT_API_VERSION)
+            .append_pair("c", CLIENT_NAME)
+            .append_pair("f", "json");
+        for (name, value) in params {
+            query.append_pair(name, value);
+        }
+
+        format!("{}/rest/{}?{}", self.base_url, method, query.finish())
+    }
+
+    fn call(&self, method: &str, params: &[(&str, &str)]) -> Result<Value, RemoteError> {
+        debug!("Calling {} on {}", method, self.base_url);
+        let body = self
+            .agent
+            .get(&self.url(method, params))
+            .call()?
+            .into_string()
+            .map_err(|e| RemoteError::Http(e.to_string()))?;
+        let body: Value =
+            serde_json::from_str(&body).map_err(|e| RemoteError::Parse(e.to_string()))?;
+
+        let response = body
+            .get("subsonic-response")
+            .cloned()
+            .ok_or_else(|| RemoteError::Parse("not a Subsonic response".to_string()))?;
+        if response["status"] != "ok" {
+            return Err(RemoteError::Api {
+                code: response["error"]["code"].as_i64().unwrap_or(0),
+                message: text(&response["error"]["message"]).unwrap_or_default(),
+            });
+        }
+
+        Ok(response)
+    }
+
+    /// Check the address and the credentials.
+    pub fn ping(&self) -> Result<(), RemoteError> {
+        self.call("ping", &[]).map(|_| ())
+    }
+
+    /// Read every song on the server, album by album.
+    pub fn fetch_library(&self) -> Result<Vec<RemoteFileRecord>, RemoteError> {
+        let mut records = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let size = ALBUM_PAGE_SIZE.to_string();
+            let offset_param = offset.to_string();
+            let response = self.call(
+                "getAlbumList2",
+                &[
+                    ("type", "alphabeticalByName"),
+                    ("size", &size),
+                    ("offset", &offset_param),
+                ],
+            )?;
+            let albums = list(&response["albumList2"]["album"]);
+
+            for album in &albums {
+                let Some(album_id) = text(&album["id"]) else {
+                    continue;
+                };
+                let response = self.call("getAlbum", &[("id", &album_id)])?;
+                let album = &response["album"];
+                records.extend(
+                    list(&album["song"])
+                        .iter()
+                        .filter_map(|song| record_of(song, album)),
+                );
+            }
+
+            offset += albums.len();
+            if albums.len() < ALBUM_PAGE_SIZE {
+                break;
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// The address a song is streamed from, as it is stored on the server.
+    pub fn stream_url(&self, remote_id: &str) -> String {
+        self.url("stream", &[("id", remote_id), ("format", "raw")])
+    }
+
+    /// Tell the server a song was played.
+    pub fn scrobble(&self, remote_id: &str) -> Result<(), RemoteError> {
+        self.call("scrobble", &[("id", remote_id), ("submission", "true")])
+            .map(|_| ())
+    }
+}
+
+// Servers differ in whether IDs are strings or numbers
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(x) => Some(x.clone()),
+        Value::Number(x) => Some(x.to_string()),
+        _ => None,
+    }
+}
+
+// Lists of a single item may be sent as the item itself
+fn list(value: &Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items.clone(),
+        Value::Null => vec![],
+        item => vec![item.clone()],
+    }
+}
+
+// Songs without tags of their own are described by their album
+fn record_of(song: &Value, album: &Value) -> Option<RemoteFileRecord> {
+    let text_or_album = |name: &str, album_name: &str| {
+        text(&song[name])
+            .filter(|x| !x.is_empty())
+            .or_else(|| text(&album[album_name]))
+            .unwrap_or_default()
+    };
+    let number = |name: &str| {
+        song[name]
+            .as_i64()
+            .or_else(|| song[name].as_str()?.parse().ok())
+    };
+
+    Some(RemoteFileRecord {
+        remote_id: text(&song["id"])?,
+        title: text(&song["title"]).unwrap_or_default(),
+        artist: text_or_album("artist", "artist"),
+        album: text_or_album("album", "name"),
+        remote_album_id: text(&song["albumId"])
+            .or_else(|| text(&song["parent"]))
+            .or_else(|| text(&album["id"])),
+        track_number: number("track").map(|x| x as i32),
+        year: number("year")
+            .or_else(|| album["year"].as_i64())
+            .map(|x| x as i32),
+        duration: number("duration").unwrap_or(0) as f64,
+        extension: text(&song["suffix"]).unwrap_or_default(),
+        cover_art: text(&song["coverArt"]),
+    })
+}
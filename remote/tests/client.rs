@@ -0,0 +1,232 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use database::actions::metadata::get_metadata_summary_by_file_ids;
+use database::actions::playback_queue::{get_playback_queue, save_playback_queue};
+use database::actions::query_cache::QueryCache;
+use database::actions::remote::{
+    add_remote_server, from_queue_id, get_remote_files, get_remote_summaries, remove_remote_server,
+    replace_remote_files, to_queue_id, without_remote_files,
+};
+use database::actions::users::DEFAULT_USER_ID;
+use database::entities::{albums, artists, media_file_albums, media_file_artists};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+use playback::source::{open_media_source, parse_remote_path, remote_path, StreamResolver};
+use remote::client::{RemoteError, SubsonicClient};
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use server::stream::{StreamServer, StreamState};
+use tokio::sync::Mutex;
+
+const TOKEN: &str = "secret";
+
+#[test]
+fn queue_ids_of_remote_files_never_collide() {
+    assert_eq!(from_queue_id(to_queue_id(7)), Some(7));
+    assert_eq!(from_queue_id(7), None);
+    assert!(to_queue_id(1) < 0);
+}
+
+#[test]
+fn remote_paths_name_the_server_and_song() {
+    let path = remote_path(3, "al-1/tr-2");
+
+    assert_eq!(parse_remote_path(&path), Some((3, "al-1/tr-2")));
+    assert_eq!(
+        parse_remote_path(std::path::Path::new("/music/a.flac")),
+        None
+    );
+}
+
+#[tokio::test]
+async fn queues_with_remote_files_are_saved_without_them() {
+    let main_db = connect_main_db_in_memory().await.unwrap();
+    let first = MediaFileFixture::new("a.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let second = MediaFileFixture::new("b.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let queue = [first.id, to_queue_id(4), to_queue_id(5), second.id];
+
+    assert_eq!(
+        without_remote_files(&queue, Some(3)),
+        (vec![first.id, second.id], Some(1))
+    );
+    // A remote file playing resumes at the next file of the library
+    assert_eq!(
+        without_remote_files(&queue, Some(1)),
+        (vec![first.id, second.id], Some(1))
+    );
+    assert_eq!(
+        without_remote_files(&queue[..3], Some(2)),
+        (vec![first.id], None)
+    );
+
+    let (file_ids, index) = without_remote_files(&queue, Some(3));
+    save_playback_queue(&main_db, DEFAULT_USER_ID, &file_ids, index, 5.0)
+        .await
+        .unwrap();
+    let saved = get_playback_queue(&main_db, DEFAULT_USER_ID).await.unwrap();
+    assert_eq!(saved.file_ids, vec![first.id, second.id]);
+    assert_eq!(saved.index, Some(1));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn remote_libraries_are_cached_and_streamed() {
+    let lib = tempfile::tempdir().unwrap();
+    let cache = tempfile::tempdir().unwrap();
+    let main_db = Arc::new(connect_main_db_in_memory().await.unwrap());
+    let user_db = connect_main_db_in_memory().await.unwrap();
+
+    let artist = artists::ActiveModel {
+        name: ActiveValue::Set("Artist".to_string()),
+        group: ActiveValue::Set("A".to_string()),
+        ..Default::default()
+    }
+    .insert(&*main_db)
+    .await
+    .unwrap();
+    let album = albums::ActiveModel {
+        name: ActiveValue::Set("Album".to_string()),
+        group: ActiveValue::Set("A".to_string()),
+        ..Default::default()
+    }
+    .insert(&*main_db)
+    .await
+    .unwrap();
+
+    for (track, name) in [(2, "b.wav"), (1, "a.wav")] {
+        write_sine_fixture(&lib.path().join(name), FixtureFormat::Wav, 8000, 1, 800).unwrap();
+        let file = MediaFileFixture::new(name).insert(&main_db).await.unwrap();
+        media_file_albums::ActiveModel {
+            media_file_id: ActiveValue::Set(file.id),
+            album_id: ActiveValue::Set(album.id),
+            track_number: ActiveValue::Set(Some(track)),
+            ..Default::default()
+        }
+        .insert(&*main_db)
+        .await
+        .unwrap();
+        media_file_artists::ActiveModel {
+            media_file_id: ActiveValue::Set(file.id),
+            artist_id: ActiveValue::Set(artist.id),
+            ..Default::default()
+        }
+        .insert(&*main_db)
+        .await
+        .unwrap();
+    }
+
+    let server = StreamServer::start(
+        "127.0.0.1:0",
+        StreamState {
+            main_db: main_db.clone(),
+            user_db: main_db.clone(),
+            search_db: Arc::new(Mutex::new(connect_search_db_in_memory().unwrap())),
+            query_cache: Arc::new(QueryCache::default()),
+            lib_path: lib.path().to_path_buf(),
+            token: TOKEN.to_string(),
+            cache_dir: cache.path().to_path_buf(),
+        },
+    )
+    .unwrap();
+    let url = format!("http://{}/", server.local_addr());
+
+    let client = SubsonicClient::new(&url, "me", "wrong");
+    let result = tokio::task::spawn_blocking(move || client.ping())
+        .await
+        .unwrap();
+    assert!(matches!(result, Err(RemoteError::Api { code: 40, .. })));
+
+    let client = SubsonicClient::new(&url, "me", TOKEN);
+    let records = {
+        let client = client.clone();
+        tokio::task::spawn_blocking(move || client.fetch_library())
+            .await
+            .unwrap()
+            .unwrap()
+    };
+    assert_eq!(records.len(), 2);
+    assert!(records
+        .iter()
+        .all(|x| x.artist == "Artist" && x.album == "Album"));
+
+    let remote_server = add_remote_server(&user_db, "Home", &url, "me", TOKEN)
+        .await
+        .unwrap();
+    assert!(!remote_server.url.ends_with('/'));
+    assert_eq!(
+        replace_remote_files(&user_db, remote_server.id, records.clone())
+            .await
+            .unwrap(),
+        2
+    );
+
+    // Songs come by track, and keep their ID when synced again
+    let files = get_remote_files(&user_db, remote_server.id, "", 0, 10)
+        .await
+        .unwrap();
+    assert_eq!(files[0].track_number, Some(1));
+    assert_eq!(files[1].track_number, Some(2));
+    replace_remote_files(&user_db, remote_server.id, records)
+        .await
+        .unwrap();
+    let synced = get_remote_files(&user_db, remote_server.id, "", 0, 10)
+        .await
+        .unwrap();
+    assert_eq!(
+        synced.iter().map(|x| x.id).collect::<Vec<_>>(),
+        files.iter().map(|x| x.id).collect::<Vec<_>>()
+    );
+    assert!(
+        get_remote_files(&user_db, remote_server.id, "nothing", 0, 10)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    // Queued remote files are described from the cache, not the library
+    let queue_id = to_queue_id(files[0].id);
+    let summaries = get_remote_summaries(&user_db, &[queue_id]).await.unwrap();
+    assert_eq!(summaries[0].id, queue_id);
+    assert_eq!(summaries[0].title, files[0].title);
+    assert!(get_metadata_summary_by_file_ids(&user_db, vec![queue_id])
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Streams can be read from anywhere in the file
+    let content = std::fs::read(lib.path().join("a.wav")).unwrap();
+    let path = remote_path(remote_server.id, &files[0].remote_id);
+    assert!(!path.to_string_lossy().contains(TOKEN));
+    let resolver = StreamResolver::new(move |_, remote_id| Some(client.stream_url(remote_id)));
+    let (whole, tail) = tokio::task::spawn_blocking(move || {
+        let mut source = open_media_source(&path, Some(&resolver), None).unwrap();
+        let mut whole = Vec::new();
+        source.read_to_end(&mut whole).unwrap();
+
+        source.seek(SeekFrom::Start(100)).unwrap();
+        let mut tail = Vec::new();
+        source.read_to_end(&mut tail).unwrap();
+        (whole, tail)
+    })
+    .await
+    .unwrap();
+    assert_eq!(whole, content);
+    assert_eq!(tail, content[100..]);
+
+    remove_remote_server(&user_db, remote_server.id)
+        .await
+        .unwrap();
+    assert!(get_remote_files(&user_db, remote_server.id, "", 0, 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    server.stop();
+}
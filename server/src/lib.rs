@@ -0,0 +1,3 @@
+pub mod range;
+pub mod stream;
+pub mod subsonic;
@@ -0,0 +1,30 @@
+/// Parse the `Range` header of a request for a file of `len` bytes.
+///
+/// Only a single range is supported, requests for several ranges get the
+/// first one.
+///
+/// # Returns
+/// * `Option<(u64, u64)>` - The first and the last byte requested, `None` if
+///   the range can't be satisfied.
+pub fn parse_range(header: &str, len: u64) -> Option<(u64, u64)> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    let spec = spec.split(',').next()?.trim();
+    let (start, end) = spec.split_once('-')?;
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            if suffix == 0 {
+                return None;
+            }
+            (len.saturating_sub(suffix), len.checked_sub(1)?)
+        }
+        (start, "") => (start.parse().ok()?, len.checked_sub(1)?),
+        (start, end) => {
+            let end: u64 = end.parse().ok()?;
+            (start.parse().ok()?, end.min(len.checked_sub(1)?))
+        }
+    };
+
+    (start <= end && start < len).then_some((start, end))
+}
@@ -0,0 +1,535 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use database::actions::file::get_file_by_id;
+use database::actions::query_cache::QueryCache;
+use database::connection::{MainDbConnection, SearchDbConnection};
+use metadata::transcode::{is_transcode_codec, transcode_file};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use tokio::runtime::Handle;
+use tokio::sync::Mutex;
+use tracing::{debug, error, info, warn};
+
+use crate::range::parse_range;
+use crate::subsonic;
+
+/// The token clients need to stream the library.
+pub const SERVER_TOKEN_KEY: &str = "server.token";
+pub const SERVER_PORT_KEY: &str = "server.port";
+pub const DEFAULT_SERVER_PORT: u16 = 7863;
+
+// Requests are a request line and a few headers, anything larger is refused
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+const MAX_HEADERS: usize = 32;
+
+/// Connections served at once, more are answered with 503 and closed.
+pub const MAX_CONNECTIONS: usize = 32;
+// A request must be sent in full within this time, idle sockets are closed
+const REQUEST_DEADLINE: Duration = Duration::from_secs(10);
+// A player that stopped reading a stream for this long is gone
+const WRITE_TIMEOUT: Duration = Duration::from_secs(60);
+// Refused clients get this long to send their request before the close
+const REFUSE_DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Generate a random token for the server.
+pub fn generate_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// What the server needs to answer requests.
+#[derive(Clone)]
+pub struct StreamState {
+    pub main_db: Arc<MainDbConnection>,
+    /// Where plays reported by Subsonic clients are logged.
+    pub user_db: Arc<MainDbConnection>,
+    pub search_db: Arc<Mutex<SearchDbConnection>>,
+    /// The cache of the app, plays, ratings and playlist changes made by
+    /// clients are dropped from it.
+    pub query_cache: Arc<QueryCache>,
+    pub lib_path: PathBuf,
+    pub token: String,
+    /// Where transcoded files are kept, so seeking doesn't transcode again.
+    pub cache_dir: PathBuf,
+}
+
+/// An HTTP server streaming the files of a library.
+///
+/// * `GET /ping` answers `pong`, to check the address and the token.
+/// * `GET /files/<id>` streams a file, with support for `Range` requests.
+///   `?format=wav` transcodes it first.
+/// * `/rest/*` answers Subsonic clients, see [`subsonic`].
+///
+/// Every request needs the token, as `Authorization: Bearer <token>` or
+/// as `?token=<token>` for players that can't set headers.
+///
+/// Connections are served on their own threads, at most
+/// [`MAX_CONNECTIONS`] at once. The database is queried through the runtime
+/// the server was started from.
+#[derive(Debug)]
+pub struct StreamServer {
+    local_addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl StreamServer {
+    /// Start listening, the server runs until it is stopped or dropped.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn start(addr: impl ToSocketAddrs, state: StreamState) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let runtime = Handle::current();
+
+        info!("Streaming server listening on {}", local_addr);
+        let state = Arc::new(state);
+        let accept_stopped = stopped.clone();
+        let accept_thread = thread::Builder::new()
+            .name("stream-server".to_string())
+            .spawn(move || accept_loop(listener, state, runtime, accept_stopped))?;
+
+        Ok(StreamServer {
+            local_addr,
+            stopped,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting requests, streams already started are finished.
+    ///
+    /// Returns once the port is released, so it can be bound again.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let Some(accept_thread) = self.accept_thread.take() else {
+            return;
+        };
+        self.stopped.store(true, Ordering::SeqCst);
+
+        // Wake the listener up so it sees the server is stopped. A server
+        // on every interface is reached through loopback, connecting to the
+        // unspecified address fails on some systems
+        let mut wake_addr = self.local_addr;
+        if wake_addr.ip().is_unspecified() {
+            wake_addr.set_ip(match wake_addr {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        if let Err(e) = TcpStream::connect(wake_addr) {
+            // The listener stays blocked, joining it would never return
+            warn!("Failed to wake the streaming server up: {}", e);
+            return;
+        }
+
+        // The listener is closed when the accept loop returns
+        if accept_thread.join().is_err() {
+            error!("The streaming server thread panicked");
+        }
+    }
+}
+
+impl Drop for StreamServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+// Counts a connection as served until it is dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn acquire(active: &Arc<AtomicUsize>) -> Option<Self> {
+        active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
+                (x < MAX_CONNECTIONS).then_some(x + 1)
+            })
+            .ok()
+            .map(|_| ConnectionSlot(active.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Closing a socket with an unread request resets the connection and the
+// client never sees the 503, so what it sent is read and dropped first
+fn refuse(mut stream: TcpStream) {
+    let _ = respond(&mut stream, 503, "Service Unavailable", &[], b"");
+    let _ = stream.shutdown(Shutdown::Write);
+
+    let deadline = Instant::now() + REFUSE_DRAIN_TIMEOUT;
+    let _ = stream.set_read_timeout(Some(REFUSE_DRAIN_TIMEOUT));
+    let mut buffer = [0; 1024];
+    while Instant::now() < deadline {
+        match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+}
+
+fn accept_loop(
+    listener: TcpListener,
+    state: Arc<StreamState>,
+    runtime: Handle,
+    stopped: Arc<AtomicBool>,
+) {
+    let active = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        if stopped.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = stream.set_write_timeout(Some(WRITE_TIMEOUT)) {
+            debug!("Failed to set the write timeout: {}", e);
+            continue;
+        }
+
+        let Some(slot) = ConnectionSlot::acquire(&active) else {
+            warn!(
+                "Too many connections, refusing {:?}",
+                stream.peer_addr().ok()
+            );
+            refuse(stream);
+            continue;
+        };
+        let state = state.clone();
+        let runtime = runtime.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            let peer = stream.peer_addr().ok();
+            if let Err(e) = handle_connection(stream, &state, &runtime) {
+                debug!("Connection from {:?} failed: {}", peer, e);
+            }
+        });
+    }
+
+    info!("Streaming server stopped");
+}
+
+pub(crate) struct Request {
+    pub method: String,
+    pub path: String,
+    /// Parameters can be given more than once, they are kept in order.
+    pub query: Vec<(String, String)>,
+    pub headers: HashMap<String, String>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn params<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.query
+            .iter()
+            .filter(move |(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn token(&self) -> Option<&str> {
+        self.header("authorization")
+            .and_then(|x| x.strip_prefix("Bearer "))
+            .or_else(|| self.param("token"))
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> io::Result<Option<Request>> {
+    let mut buffer = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    let deadline = Instant::now() + REQUEST_DEADLINE;
+
+    loop {
+        // A client sending a byte now and then must not keep the thread
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(None);
+        }
+        stream.set_read_timeout(Some(remaining))?;
+
+        let read = match stream.read(&mut chunk) {
+            Ok(read) => read,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+        if read == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut request = httparse::Request::new(&mut headers);
+        match request.parse(&buffer) {
+            Ok(httparse::Status::Complete(_)) => {
+                let target = request.path.unwrap_or("/");
+                let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+                return Ok(Some(Request {
+                    method: request.method.unwrap_or_default().to_string(),
+                    path: path.to_string(),
+                    query: form_urlencoded::parse(query.as_bytes())
+                        .into_owned()
+                        .collect(),
+                    headers: request
+                        .headers
+                        .iter()
+                        .map(|x| {
+                            (
+                                x.name.to_lowercase(),
+                                String::from_utf8_lossy(x.value).to_string(),
+                            )
+                        })
+                        .collect(),
+                }));
+            }
+            Ok(httparse::Status::Partial) if buffer.len() < MAX_REQUEST_SIZE => continue,
+            _ => return Ok(None),
+        }
+    }
+}
+
+pub(crate) fn respond(
+    stream: &mut TcpStream,
+    status: u16,
+    reason: &str,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> io::Result<()> {
+    let mut head = format!("HTTP/1.1 {} {}\r\n", status, reason);
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if !headers.iter().any(|(name, _)| *name == "Content-Length") {
+        head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+// Compare without leaking how much of the token was right
+pub(crate) fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+pub fn content_type(extension: &str) -> &'static str {
+    match extension.to_lowercase().as_str() {
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/flac",
+        "wav" => "audio/wav",
+        "ogg" | "opus" => "audio/ogg",
+        "m4a" | "aac" => "audio/mp4",
+        "aiff" | "aif" => "audio/aiff",
+        _ => "application/octet-stream",
+    }
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    state: &StreamState,
+    runtime: &Handle,
+) -> io::Result<()> {
+    let Some(request) = read_request(&mut stream)? else {
+        return respond(&mut stream, 400, "Bad Request", &[], b"");
+    };
+    debug!("{} {}", request.method, request.path);
+
+    // Subsonic clients authenticate their own way
+    if request.path.starts_with("/rest/") {
+        return subsonic::handle(&mut stream, state, runtime, &request);
+    }
+
+    if request.method != "GET" && request.method != "HEAD" {
+        let allow = [("Allow", "GET, HEAD".to_string())];
+        return respond(&mut stream, 405, "Method Not Allowed", &allow, b"");
+    }
+    if !request
+        .token()
+        .is_some_and(|x| token_matches(x, &state.token))
+    {
+        return respond(&mut stream, 401, "Unauthorized", &[], b"");
+    }
+
+    if request.path == "/ping" {
+        return respond(&mut stream, 200, "OK", &[], b"pong");
+    }
+
+    let file_id = request
+        .path
+        .strip_prefix("/files/")
+        .and_then(|x| x.parse::<i32>().ok());
+    match file_id {
+        Some(file_id) => {
+            let format = request.param("format");
+            stream_file(&mut stream, state, runtime, &request, file_id, format)
+        }
+        None => respond(&mut stream, 404, "Not Found", &[], b""),
+    }
+}
+
+fn transcoded_path(
+    state: &StreamState,
+    src: &Path,
+    file_hash: &str,
+    codec: &str,
+) -> io::Result<PathBuf> {
+    let name: String = file_hash
+        .chars()
+        .map(|x| if x.is_ascii_alphanumeric() { x } else { '_' })
+        .collect();
+    let path = state.cache_dir.join(format!("{}.{}", name, codec));
+    if path.exists() {
+        return Ok(path);
+    }
+
+    // Requests for the same file may come in together, every one writes its
+    // own file and the last one wins
+    fs::create_dir_all(&state.cache_dir)?;
+    let partial = state
+        .cache_dir
+        .join(format!("{}.{}.{}", name, generate_token(), codec));
+    if let Err(e) = transcode_file(src, &partial, codec) {
+        let _ = fs::remove_file(&partial);
+        return Err(io::Error::other(e));
+    }
+    fs::rename(&partial, &path)?;
+
+    Ok(path)
+}
+
+pub(crate) fn stream_file(
+    stream: &mut TcpStream,
+    state: &StreamState,
+    runtime: &Handle,
+    request: &Request,
+    file_id: i32,
+    format: Option<&str>,
+) -> io::Result<()> {
+    let file = match runtime.block_on(get_file_by_id(&state.main_db, file_id)) {
+        Ok(Some(file)) if file.deleted_at.is_none() => file,
+        Ok(_) => return respond(stream, 404, "Not Found", &[], b""),
+        Err(e) => {
+            error!("Failed to find file {}: {}", file_id, e);
+            return respond(stream, 500, "Internal Server Error", &[], b"");
+        }
+    };
+    let src = state.lib_path.join(&file.directory).join(&file.file_name);
+
+    let format = format
+        .map(|x| x.to_lowercase())
+        .filter(|x| x != "original" && x != "raw" && !x.eq_ignore_ascii_case(&file.extension));
+    let (path, extension) = match format {
+        None => (src, file.extension.clone()),
+        Some(codec) if is_transcode_codec(&codec) => {
+            match transcoded_path(state, &src, &file.file_hash, &codec) {
+                Ok(path) => (path, codec),
+                Err(e) => {
+                    error!("Failed to transcode file {}: {}", file_id, e);
+                    return respond(stream, 500, "Internal Server Error", &[], b"");
+                }
+            }
+        }
+        Some(codec) => {
+            let body = format!("Can't transcode to {}", codec);
+            return respond(stream, 415, "Unsupported Media Type", &[], body.as_bytes());
+        }
+    };
+
+    send_file(stream, request, &path, &extension)
+}
+
+fn send_file(
+    stream: &mut TcpStream,
+    request: &Request,
+    path: &Path,
+    extension: &str,
+) -> io::Result<()> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            warn!("Failed to open {:?}: {}", path, e);
+            return respond(stream, 404, "Not Found", &[], b"");
+        }
+    };
+    let len = file.metadata()?.len();
+
+    let mut headers = vec![
+        ("Content-Type", content_type(extension).to_string()),
+        ("Accept-Ranges", "bytes".to_string()),
+    ];
+    let (status, reason, start, end) = match request.header("range") {
+        None => (200, "OK", 0, len),
+        Some(range) => match parse_range(range, len) {
+            Some((start, end)) => {
+                headers.push(("Content-Range", format!("bytes {}-{}/{}", start, end, len)));
+                (206, "Partial Content", start, end + 1)
+            }
+            None => {
+                headers.push(("Content-Range", format!("bytes */{}", len)));
+                return respond(stream, 416, "Range Not Satisfiable", &headers, b"");
+            }
+        },
+    };
+    headers.push(("Content-Length", (end - start).to_string()));
+    respond(stream, status, reason, &headers, b"")?;
+
+    if request.method == "HEAD" {
+        return Ok(());
+    }
+
+    file.seek(io::SeekFrom::Start(start))?;
+    io::copy(&mut file.take(end - start), stream)?;
+    stream.flush()
+}
@@ -0,0 +1,324 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use database::actions::albums::get_albums_by_ids;
+use database::actions::artists::get_artists_by_ids;
+use database::actions::file::get_files_by_ids;
+use database::actions::metadata::get_metadata_summary_by_files;
+use database::actions::playlists::get_playlist_items;
+use database::entities::{albums, artists, media_file_albums, media_file_artists, playlists};
+use sea_orm::prelude::*;
+use sea_orm::QueryOrder;
+
+use crate::stream::content_type;
+
+use super::response::Element;
+
+// Subsonic IDs are opaque strings, the prefix tells what an ID points to
+pub const ARTIST_PREFIX: &str = "ar-";
+pub const ALBUM_PREFIX: &str = "al-";
+pub const SONG_PREFIX: &str = "tr-";
+pub const PLAYLIST_PREFIX: &str = "pl-";
+pub const COVER_ART_PREFIX: &str = "ca-";
+
+pub fn to_id(prefix: &str, id: i32) -> String {
+    format!("{}{}", prefix, id)
+}
+
+/// Read an ID written by `to_id`, bare numbers are accepted too.
+pub fn parse_id(value: &str, prefix: &str) -> Option<i32> {
+    value.strip_prefix(prefix).unwrap_or(value).parse().ok()
+}
+
+pub fn artist(model: &artists::Model) -> Element {
+    Element::new("artist")
+        .attr("id", to_id(ARTIST_PREFIX, model.id))
+        .attr("name", model.name.as_str())
+}
+
+// The first artist of every file
+async fn get_artist_of_files(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, artists::Model>, DbErr> {
+    let links = media_file_artists::Entity::find()
+        .filter(media_file_artists::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .order_by_asc(media_file_artists::Column::Id)
+        .all(main_db)
+        .await?;
+
+    let mut first = HashMap::new();
+    for link in links {
+        first.entry(link.media_file_id).or_insert(link.artist_id);
+    }
+
+    let artist_ids: Vec<i32> = first
+        .values()
+        .copied()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let artists: HashMap<i32, artists::Model> = get_artists_by_ids(main_db, &artist_ids)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect();
+
+    Ok(first
+        .into_iter()
+        .filter_map(|(file_id, artist_id)| Some((file_id, artists.get(&artist_id)?.clone())))
+        .collect())
+}
+
+/// Build the elements of songs, in the order of the IDs.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `lib_path` - The root path of the library, to find the size of the files.
+/// * `file_ids` - The IDs of the files, deleted files are left out.
+/// * `name` - The name of the elements, like `song`, `child` or `entry`.
+pub async fn songs(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    file_ids: &[i32],
+    name: &'static str,
+) -> Result<Vec<Element>, DbErr> {
+    let files: Vec<_> = get_files_by_ids(main_db, file_ids)
+        .await?
+        .into_iter()
+        .filter(|x| x.deleted_at.is_none())
+        .collect();
+    let summaries: HashMap<i32, _> = get_metadata_summary_by_files(main_db, files.clone())
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect();
+    let files: HashMap<i32, _> = files.into_iter().map(|x| (x.id, x)).collect();
+
+    let album_links: HashMap<i32, media_file_albums::Model> = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.media_file_id, x))
+        .collect();
+    let artists = get_artist_of_files(main_db, file_ids).await?;
+
+    let mut elements = Vec::new();
+    for file_id in file_ids {
+        let (Some(file), Some(summary)) = (files.get(file_id), summaries.get(file_id)) else {
+            continue;
+        };
+        let path = Path::new(&file.directory).join(&file.file_name);
+        let title = if summary.title.is_empty() {
+            Path::new(&file.file_name)
+                .file_stem()
+                .map(|x| x.to_string_lossy().to_string())
+                .unwrap_or_default()
+        } else {
+            summary.title.clone()
+        };
+        let album_link = album_links.get(file_id);
+        let artist = artists.get(file_id);
+
+        elements.push(
+            Element::new(name)
+                .attr("id", to_id(SONG_PREFIX, file.id))
+                .attr_opt(
+                    "parent",
+                    album_link.map(|x| to_id(ALBUM_PREFIX, x.album_id)),
+                )
+                .attr("isDir", false)
+                .attr("title", title)
+                .attr("album", summary.album.as_str())
+                .attr("artist", summary.artist.as_str())
+                .attr_opt(
+                    "track",
+                    album_link
+                        .and_then(|x| x.track_number)
+                        .or(summary.track_number),
+                )
+                .attr_opt("year", summary.year)
+                .attr_opt(
+                    "coverArt",
+                    file.cover_art_id.map(|x| to_id(COVER_ART_PREFIX, x)),
+                )
+                .attr_opt(
+                    "size",
+                    std::fs::metadata(lib_path.join(&path))
+                        .ok()
+                        .map(|x| x.len()),
+                )
+                .attr("contentType", content_type(&file.extension))
+                .attr("suffix", file.extension.as_str())
+                .attr("duration", file.duration.round() as i64)
+                .attr("path", path.to_string_lossy().replace('\\', "/"))
+                .attr("type", "music")
+                .attr("mediaType", "song")
+                .attr_opt(
+                    "albumId",
+                    album_link.map(|x| to_id(ALBUM_PREFIX, x.album_id)),
+                )
+                .attr_opt("artistId", artist.map(|x| to_id(ARTIST_PREFIX, x.id))),
+        );
+    }
+
+    Ok(elements)
+}
+
+/// Get the files of albums, ordered by track number.
+pub async fn get_files_of_albums(
+    main_db: &DatabaseConnection,
+    album_ids: &[i32],
+) -> Result<HashMap<i32, Vec<i32>>, DbErr> {
+    let mut links = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::AlbumId.is_in(album_ids.to_vec()))
+        .all(main_db)
+        .await?;
+    links.sort_by_key(|x| (x.track_number.unwrap_or(i32::MAX), x.media_file_id));
+
+    let mut files: HashMap<i32, Vec<i32>> = HashMap::new();
+    for link in links {
+        files
+            .entry(link.album_id)
+            .or_default()
+            .push(link.media_file_id);
+    }
+
+    Ok(files)
+}
+
+/// Build the elements of albums, in the given order.
+///
+/// The artist of an album is the one most of its files have.
+pub async fn albums(
+    main_db: &DatabaseConnection,
+    albums: &[albums::Model],
+    name: &'static str,
+) -> Result<Vec<Element>, DbErr> {
+    let album_ids: Vec<i32> = albums.iter().map(|x| x.id).collect();
+    let album_files = get_files_of_albums(main_db, &album_ids).await?;
+
+    let file_ids: Vec<i32> = album_files.values().flatten().copied().collect();
+    let files: HashMap<i32, _> = get_files_by_ids(main_db, &file_ids)
+        .await?
+        .into_iter()
+        .filter(|x| x.deleted_at.is_none())
+        .map(|x| (x.id, x))
+        .collect();
+    let artists = get_artist_of_files(main_db, &file_ids).await?;
+
+    let mut elements = Vec::new();
+    for album in albums {
+        let files: Vec<_> = album_files
+            .get(&album.id)
+            .into_iter()
+            .flatten()
+            .filter_map(|x| files.get(x))
+            .collect();
+
+        let mut counts: HashMap<i32, usize> = HashMap::new();
+        for file in &files {
+            if let Some(artist) = artists.get(&file.id) {
+                *counts.entry(artist.id).or_default() += 1;
+            }
+        }
+        let artist = counts
+            .into_iter()
+            .max_by_key(|(id, count)| (*count, -id))
+            .and_then(|(id, _)| artists.values().find(|x| x.id == id));
+
+        elements.push(
+            Element::new(name)
+                .attr("id", to_id(ALBUM_PREFIX, album.id))
+                .attr_opt("parent", artist.map(|x| to_id(ARTIST_PREFIX, x.id)))
+                .attr("isDir", true)
+                .attr("name", album.name.as_str())
+                .attr("title", album.name.as_str())
+                .attr("album", album.name.as_str())
+                .attr_opt("artist", artist.map(|x| x.name.clone()))
+                .attr_opt("artistId", artist.map(|x| to_id(ARTIST_PREFIX, x.id)))
+                .attr("songCount", files.len())
+                .attr(
+                    "duration",
+                    files.iter().map(|x| x.duration).sum::<f64>().round() as i64,
+                )
+                .attr_opt(
+                    "coverArt",
+                    files
+                        .iter()
+                        .find_map(|x| x.cover_art_id)
+                        .map(|x| to_id(COVER_ART_PREFIX, x)),
+                )
+                .attr_opt("year", album.year),
+        );
+    }
+
+    Ok(elements)
+}
+
+/// Get the albums any file of an artist is on, the oldest first.
+pub async fn get_albums_of_artist(
+    main_db: &DatabaseConnection,
+    artist_id: i32,
+) -> Result<Vec<albums::Model>, DbErr> {
+    let file_ids: Vec<i32> = media_file_artists::Entity::find()
+        .filter(media_file_artists::Column::ArtistId.eq(artist_id))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| x.media_file_id)
+        .collect();
+    let album_ids: Vec<i32> = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::MediaFileId.is_in(file_ids))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| x.album_id)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let mut albums = get_albums_by_ids(main_db, &album_ids).await?;
+    albums.sort_by(|a, b| (a.year, &a.name).cmp(&(b.year, &b.name)));
+
+    Ok(albums)
+}
+
+/// Build the element of a playlist, with its entries if asked for.
+pub async fn playlist(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    model: &playlists::Model,
+    with_entries: bool,
+) -> Result<Element, DbErr> {
+    let file_ids = get_playlist_items(main_db, model.id).await?;
+    let files: Vec<_> = get_files_by_ids(main_db, &file_ids)
+        .await?
+        .into_iter()
+        .filter(|x| x.deleted_at.is_none())
+        .collect();
+
+    let element = Element::new("playlist")
+        .attr("id", to_id(PLAYLIST_PREFIX, model.id))
+        .attr("name", model.name.as_str())
+        .attr("owner", "rune")
+        .attr("public", false)
+        .attr("songCount", files.len())
+        .attr(
+            "duration",
+            files.iter().map(|x| x.duration).sum::<f64>().round() as i64,
+        )
+        .attr("created", model.created_at.as_str())
+        .attr("changed", model.updated_at.as_str())
+        .attr_opt(
+            "coverArt",
+            model.cover_art_id.map(|x| to_id(COVER_ART_PREFIX, x)),
+        );
+
+    if !with_entries {
+        return Ok(element);
+    }
+
+    Ok(element.items(songs(main_db, lib_path, &file_ids, "entry").await?))
+}
@@ -0,0 +1,687 @@
+//! The Subsonic REST API, so existing Subsonic and OpenSubsonic clients can
+//! browse and play a library.
+//!
+//! There are no user accounts, any user name is accepted with the token of
+//! the server as its password, sent in any of the ways the API allows.
+
+mod items;
+pub mod response;
+
+use std::collections::HashMap;
+use std::io;
+use std::net::TcpStream;
+
+use chrono::{DateTime, Utc};
+use database::actions::albums::get_albums_by_ids;
+use database::actions::artists::{get_artist_by_id, get_artists_by_ids};
+use database::actions::cover_art::get_cover_art_by_id;
+use database::actions::file::get_random_files;
+use database::actions::play_history::{get_play_counts_since, get_recently_played, log_play};
+use database::actions::playlists::{
+    create_playlist, get_all_playlists, get_playlist_by_id, get_playlist_items, remove_playlist,
+    replace_playlist_items, update_playlist,
+};
+use database::actions::ratings::set_ratings;
+use database::actions::search::{search_for, CollectionType};
+use database::actions::users::get_active_user_id;
+use database::entities::{albums, artists, media_file_albums, media_files};
+use md5::{Digest, Md5};
+use metadata::transcode::is_transcode_codec;
+use sea_orm::prelude::*;
+use sea_orm::sea_query::{Func, SimpleExpr};
+use sea_orm::{Order, QueryOrder, QuerySelect};
+use tokio::runtime::Handle;
+use tracing::{debug, error};
+
+use crate::stream::{respond, stream_file, token_matches, Request, StreamState};
+
+use self::items::*;
+use self::response::Element;
+
+/// The version of the API the server speaks.
+pub const SUBSONIC_API_VERSION: &str = "1.16.1";
+
+/// The group playlists created by Subsonic clients are put in.
+pub const SUBSONIC_PLAYLIST_GROUP: &str = "Subsonic";
+
+// Error codes of the API
+const ERROR_GENERIC: u32 = 0;
+const ERROR_MISSING_PARAMETER: u32 = 10;
+const ERROR_WRONG_CREDENTIALS: u32 = 40;
+const ERROR_NOT_FOUND: u32 = 70;
+
+// The most items a list can be asked for
+const MAX_LIST_SIZE: usize = 500;
+// Recently played albums are found through this many files played last
+const MAX_RECENT_FILES: usize = 5000;
+
+#[derive(Debug)]
+struct Failure {
+    code: u32,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: u32, message: impl Into<String>) -> Self {
+        Failure {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn not_found(what: &str) -> Self {
+        Failure::new(ERROR_NOT_FOUND, format!("{} not found", what))
+    }
+}
+
+impl From<DbErr> for Failure {
+    fn from(e: DbErr) -> Self {
+        error!("Subsonic request failed: {}", e);
+        Failure::new(ERROR_GENERIC, e.to_string())
+    }
+}
+
+enum Reply {
+    Response(Vec<Element>),
+    CoverArt(Vec<u8>),
+}
+
+fn required<'a>(request: &'a Request, name: &str) -> Result<&'a str, Failure> {
+    request.param(name).ok_or_else(|| {
+        Failure::new(
+            ERROR_MISSING_PARAMETER,
+            format!("Required parameter is missing: {}", name),
+        )
+    })
+}
+
+fn required_id(request: &Request, name: &str, prefix: &str) -> Result<i32, Failure> {
+    let value = required(request, name)?;
+    parse_id(value, prefix).ok_or_else(|| Failure::not_found(value))
+}
+
+fn ids(request: &Request, name: &str, prefix: &str) -> Vec<i32> {
+    request
+        .params(name)
+        .filter_map(|x| parse_id(x, prefix))
+        .collect()
+}
+
+fn size(request: &Request, name: &str, default: usize) -> usize {
+    request
+        .param(name)
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(default)
+        .min(MAX_LIST_SIZE)
+}
+
+fn offset(request: &Request, name: &str) -> usize {
+    request
+        .param(name)
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(0)
+}
+
+fn authenticate(request: &Request, token: &str) -> Result<(), Failure> {
+    let wrong = || Failure::new(ERROR_WRONG_CREDENTIALS, "Wrong username or password");
+
+    if let Some(api_key) = request.param("apiKey") {
+        return token_matches(api_key, token)
+            .then_some(())
+            .ok_or_else(wrong);
+    }
+
+    required(request, "u")?;
+    if let Some(password) = request.param("p") {
+        let password = match password.strip_prefix("enc:") {
+            Some(encoded) => hex::decode(encoded)
+                .ok()
+                .and_then(|x| String::from_utf8(x).ok())
+                .ok_or_else(wrong)?,
+            None => password.to_string(),
+        };
+        return token_matches(&password, token)
+            .then_some(())
+            .ok_or_else(wrong);
+    }
+
+    let salted = required(request, "t")?;
+    let salt = required(request, "s")?;
+    let expected = hex::encode(Md5::digest(format!("{}{}", token, salt)));
+    token_matches(&salted.to_lowercase(), &expected)
+        .then_some(())
+        .ok_or_else(wrong)
+}
+
+/// Answer a request to `/rest/<method>`, the `.view` suffix is optional.
+pub(crate) fn handle(
+    stream: &mut TcpStream,
+    state: &StreamState,
+    runtime: &Handle,
+    request: &Request,
+) -> io::Result<()> {
+    let format = request.param("f").unwrap_or("xml").to_string();
+    let method = request
+        .path
+        .trim_start_matches("/rest/")
+        .trim_end_matches(".view");
+    debug!("Subsonic call: {}", method);
+
+    if let Err(e) = authenticate(request, &state.token) {
+        return send(stream, response::failed(e.code, &e.message), &format);
+    }
+
+    if method == "stream" || method == "download" {
+        let file_id = match required_id(request, "id", SONG_PREFIX) {
+            Ok(file_id) => file_id,
+            Err(e) => return send(stream, response::failed(e.code, &e.message), &format),
+        };
+
+        // Codecs that can't be written are streamed as they are
+        let codec = request
+            .param("format")
+            .filter(|x| method == "stream" && is_transcode_codec(x));
+        return stream_file(stream, state, runtime, request, file_id, codec);
+    }
+
+    match runtime.block_on(call(state, method, request)) {
+        Ok(Reply::Response(content)) => send(stream, response::ok(content), &format),
+        Ok(Reply::CoverArt(body)) => {
+            let headers = [("Content-Type", image_type(&body).to_string())];
+            respond(stream, 200, "OK", &headers, &body)
+        }
+        Err(e) => send(stream, response::failed(e.code, &e.message), &format),
+    }
+}
+
+fn send(stream: &mut TcpStream, content: Element, format: &str) -> io::Result<()> {
+    let (content_type, body) = response::render(&content, format);
+    respond(
+        stream,
+        200,
+        "OK",
+        &[("Content-Type", content_type.to_string())],
+        &body,
+    )
+}
+
+fn image_type(body: &[u8]) -> &'static str {
+    if body.starts_with(b"\x89PNG") {
+        "image/png"
+    } else if body.starts_with(b"GIF8") {
+        "image/gif"
+    } else if body.len() > 12 && &body[8..12] == b"WEBP" {
+        "image/webp"
+    } else {
+        "image/jpeg"
+    }
+}
+
+async fn call(state: &StreamState, method: &str, request: &Request) -> Result<Reply, Failure> {
+    let content = match method {
+        "ping" => vec![],
+        "getLicense" => vec![Element::new("license").attr("valid", true)],
+        "getMusicFolders" => vec![Element::new("musicFolders").item(
+            Element::new("musicFolder")
+                .attr("id", 1)
+                .attr("name", "Library"),
+        )],
+        "getIndexes" => vec![get_indexes(state, "indexes").await?],
+        "getArtists" => vec![get_indexes(state, "artists").await?],
+        "getArtist" => vec![get_artist(state, request).await?],
+        "getAlbum" => vec![get_album(state, request).await?],
+        "getSong" => vec![get_song(state, request).await?],
+        "getMusicDirectory" => vec![get_music_directory(state, request).await?],
+        "getAlbumList" => vec![get_album_list(state, request, "albumList").await?],
+        "getAlbumList2" => vec![get_album_list(state, request, "albumList2").await?],
+        "getRandomSongs" => vec![get_random_songs(state, request).await?],
+        "search2" => vec![search(state, request, "searchResult2").await?],
+        "search3" => vec![search(state, request, "searchResult3").await?],
+        "getPlaylists" => vec![get_playlists(state).await?],
+        "getPlaylist" => vec![get_playlist(state, request).await?],
+        "createPlaylist" => vec![create_or_replace_playlist(state, request).await?],
+        "updatePlaylist" => {
+            edit_playlist(state, request).await?;
+            vec![]
+        }
+        "deletePlaylist" => {
+            let playlist_id = required_id(request, "id", PLAYLIST_PREFIX)?;
+            let mut search_db = state.search_db.lock().await;
+            remove_playlist(&state.main_db, &mut search_db, playlist_id).await?;
+            state.query_cache.invalidate_playlists();
+            vec![]
+        }
+        "scrobble" => {
+            scrobble(state, request).await?;
+            vec![]
+        }
+        "setRating" => {
+            let file_id = required_id(request, "id", SONG_PREFIX)?;
+            let rating = required(request, "rating")?
+                .parse()
+                .map_err(|_| Failure::new(ERROR_GENERIC, "Invalid rating"))?;
+            // Plays and ratings go to the profile active in the app
+            let user_id = get_active_user_id(&state.user_db).await?;
+            set_ratings(&state.user_db, user_id, &[file_id], rating).await?;
+            state.query_cache.invalidate_files(&[file_id]);
+            vec![]
+        }
+        "getCoverArt" => {
+            let cover_art_id = required_id(request, "id", COVER_ART_PREFIX)?;
+            return get_cover_art_by_id(&state.main_db, cover_art_id)
+                .await?
+                .map(Reply::CoverArt)
+                .ok_or_else(|| Failure::not_found("Cover art"));
+        }
+        _ => {
+            return Err(Failure::new(
+                ERROR_NOT_FOUND,
+                format!("Unknown method: {}", method),
+            ))
+        }
+    };
+
+    Ok(Reply::Response(content))
+}
+
+async fn get_indexes(state: &StreamState, name: &'static str) -> Result<Element, Failure> {
+    let artists = artists::Entity::find()
+        .order_by_asc(artists::Column::Name)
+        .all(&*state.main_db)
+        .await?;
+
+    let mut groups: Vec<(String, Vec<Element>)> = Vec::new();
+    for model in &artists {
+        match groups.iter_mut().find(|(group, _)| *group == model.group) {
+            Some((_, items)) => items.push(artist(model)),
+            None => groups.push((model.group.clone(), vec![artist(model)])),
+        }
+    }
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let element = Element::new(name).attr("ignoredArticles", "");
+    let element = match name {
+        "indexes" => element.attr("lastModified", 0),
+        _ => element,
+    };
+
+    Ok(element.items(
+        groups
+            .into_iter()
+            .map(|(group, items)| Element::new("index").attr("name", group).items(items)),
+    ))
+}
+
+async fn get_artist(state: &StreamState, request: &Request) -> Result<Element, Failure> {
+    let artist_id = required_id(request, "id", ARTIST_PREFIX)?;
+    let model = get_artist_by_id(&state.main_db, artist_id)
+        .await?
+        .ok_or_else(|| Failure::not_found("Artist"))?;
+
+    let album_models = get_albums_of_artist(&state.main_db, artist_id).await?;
+    Ok(artist(&model)
+        .attr("albumCount", album_models.len())
+        .items(albums(&state.main_db, &album_models, "album").await?))
+}
+
+async fn get_album(state: &StreamState, request: &Request) -> Result<Element, Failure> {
+    let album_id = required_id(request, "id", ALBUM_PREFIX)?;
+    let model = get_albums_by_ids(&state.main_db, &[album_id])
+        .await?
+        .pop()
+        .ok_or_else(|| Failure::not_found("Album"))?;
+
+    let file_ids = get_files_of_albums(&state.main_db, &[album_id])
+        .await?
+        .remove(&album_id)
+        .unwrap_or_default();
+    let element = albums(&state.main_db, &[model], "album").await?.remove(0);
+
+    Ok(element.items(songs(&state.main_db, &state.lib_path, &file_ids, "song").await?))
+}
+
+async fn get_song(state: &StreamState, request: &Request) -> Result<Element, Failure> {
+    let file_id = required_id(request, "id", SONG_PREFIX)?;
+    songs(&state.main_db, &state.lib_path, &[file_id], "song")
+        .await?
+        .pop()
+        .ok_or_else(|| Failure::not_found("Song"))
+}
+
+// Folder based clients browse artists, then their albums, then the songs
+async fn get_music_directory(state: &StreamState, request: &Request) -> Result<Element, Failure> {
+    let id = required(request, "id")?;
+
+    if let Some(artist_id) = id.strip_prefix(ARTIST_PREFIX).and_then(|x| x.parse().ok()) {
+        let model = get_artist_by_id(&state.main_db, artist_id)
+            .await?
+            .ok_or_else(|| Failure::not_found("Artist"))?;
+        let album_models = get_albums_of_artist(&state.main_db, artist_id).await?;
+
+        return Ok(Element {
+            name: "directory",
+            ..artist(&model)
+        }
+        .items(albums(&state.main_db, &album_models, "child").await?));
+    }
+
+    let album_id = parse_id(id, ALBUM_PREFIX).ok_or_else(|| Failure::not_found(id))?;
+    let model = get_albums_by_ids(&state.main_db, &[album_id])
+        .await?
+        .pop()
+        .ok_or_else(|| Failure::not_found("Album"))?;
+    let file_ids = get_files_of_albums(&state.main_db, &[album_id])
+        .await?
+        .remove(&album_id)
+        .unwrap_or_default();
+    let element = albums(&state.main_db, &[model], "directory")
+        .await?
+        .remove(0);
+
+    Ok(element.items(songs(&state.main_db, &state.lib_path, &file_ids, "child").await?))
+}
+
+// Albums in the order of the files given, each one once
+async fn get_albums_of_files(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<Vec<i32>, DbErr> {
+    let album_of: HashMap<i32, i32> = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.media_file_id, x.album_id))
+        .collect();
+
+    let mut album_ids = Vec::new();
+    for file_id in file_ids {
+        if let Some(album_id) = album_of.get(file_id) {
+            if !album_ids.contains(album_id) {
+                album_ids.push(*album_id);
+            }
+        }
+    }
+
+    Ok(album_ids)
+}
+
+async fn get_album_list(
+    state: &StreamState,
+    request: &Request,
+    name: &'static str,
+) -> Result<Element, Failure> {
+    let list_type = required(request, "type")?;
+    let size = size(request, "size", 10);
+    let offset = offset(request, "offset");
+    let main_db = &*state.main_db;
+
+    let query = albums::Entity::find()
+        .offset(offset as u64)
+        .limit(size as u64);
+    let models = match list_type {
+        "random" => {
+            query
+                .order_by(SimpleExpr::FunctionCall(Func::random()), Order::Asc)
+                .all(main_db)
+                .await?
+        }
+        "newest" => query.order_by_desc(albums::Column::Id).all(main_db).await?,
+        "byYear" => {
+            let from: i32 = required(request, "fromYear")?.parse().unwrap_or(0);
+            let to: i32 = required(request, "toYear")?.parse().unwrap_or(i32::MAX);
+            let query = query.filter(albums::Column::Year.between(from.min(to), from.max(to)));
+            let order = if from > to { Order::Desc } else { Order::Asc };
+            query
+                .order_by(albums::Column::Year, order)
+                .all(main_db)
+                .await?
+        }
+        "recent" | "frequent" => {
+            let user_id = get_active_user_id(&state.user_db).await?;
+            let file_ids = if list_type == "recent" {
+                get_recently_played(&state.user_db, user_id, MAX_RECENT_FILES).await?
+            } else {
+                let mut counts: Vec<(i32, usize)> =
+                    get_play_counts_since(&state.user_db, user_id, DateTime::<Utc>::UNIX_EPOCH)
+                        .await?
+                        .into_iter()
+                        .collect();
+                counts.sort_by_key(|(file_id, count)| (std::cmp::Reverse(*count), *file_id));
+                counts.into_iter().map(|(file_id, _)| file_id).collect()
+            };
+            let album_ids: Vec<i32> = get_albums_of_files(main_db, &file_ids)
+                .await?
+                .into_iter()
+                .skip(offset)
+                .take(size)
+                .collect();
+
+            let mut models = get_albums_by_ids(main_db, &album_ids).await?;
+            models.sort_by_key(|x| album_ids.iter().position(|id| *id == x.id));
+            models
+        }
+        // Stars and genres aren't kept
+        "starred" | "highest" | "byGenre" => vec![],
+        _ => {
+            query
+                .order_by_asc(albums::Column::Name)
+                .all(main_db)
+                .await?
+        }
+    };
+
+    Ok(Element::new(name).items(albums(main_db, &models, "album").await?))
+}
+
+async fn get_random_songs(state: &StreamState, request: &Request) -> Result<Element, Failure> {
+    let size = size(request, "size", 10);
+    let file_ids: Vec<i32> = get_random_files(&state.main_db, size)
+        .await?
+        .into_iter()
+        .map(|x| x.id)
+        .collect();
+
+    Ok(Element::new("randomSongs")
+        .items(songs(&state.main_db, &state.lib_path, &file_ids, "song").await?))
+}
+
+async fn search(
+    state: &StreamState,
+    request: &Request,
+    name: &'static str,
+) -> Result<Element, Failure> {
+    // Some clients quote the query, an empty one lists everything
+    let query = required(request, "query")?.trim().trim_matches('"').trim();
+    let artist_count = size(request, "artistCount", 20);
+    let artist_offset = offset(request, "artistOffset");
+    let album_count = size(request, "albumCount", 20);
+    let album_offset = offset(request, "albumOffset");
+    let song_count = size(request, "songCount", 20);
+    let song_offset = offset(request, "songOffset");
+    let main_db = &*state.main_db;
+
+    let (artist_ids, album_ids, song_ids): (Vec<i32>, Vec<i32>, Vec<i32>) = if query.is_empty() {
+        let artist_ids = artists::Entity::find()
+            .order_by_asc(artists::Column::Name)
+            .offset(artist_offset as u64)
+            .limit(artist_count as u64)
+            .all(main_db)
+            .await?
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        let album_ids = albums::Entity::find()
+            .order_by_asc(albums::Column::Name)
+            .offset(album_offset as u64)
+            .limit(album_count as u64)
+            .all(main_db)
+            .await?
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        let song_ids = media_files::Entity::find()
+            .filter(media_files::Column::DeletedAt.is_null())
+            .order_by_asc(media_files::Column::Id)
+            .offset(song_offset as u64)
+            .limit(song_count as u64)
+            .all(main_db)
+            .await?
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        (artist_ids, album_ids, song_ids)
+    } else {
+        let n = (artist_offset + artist_count)
+            .max(album_offset + album_count)
+            .max(song_offset + song_count);
+        let mut results = {
+            let mut search_db = state.search_db.lock().await;
+            search_for(&mut search_db, query, n)
+                .map_err(|e| Failure::new(ERROR_GENERIC, e.to_string()))?
+        };
+        let mut take = |collection_type: CollectionType, offset: usize, count: usize| {
+            results
+                .remove(&collection_type)
+                .unwrap_or_default()
+                .into_iter()
+                .skip(offset)
+                .take(count)
+                .map(|x| x as i32)
+                .collect::<Vec<i32>>()
+        };
+        (
+            take(CollectionType::Artist, artist_offset, artist_count),
+            take(CollectionType::Album, album_offset, album_count),
+            take(CollectionType::Track, song_offset, song_count),
+        )
+    };
+
+    let mut artist_models = get_artists_by_ids(main_db, &artist_ids).await?;
+    artist_models.sort_by_key(|x| artist_ids.iter().position(|id| *id == x.id));
+    let mut album_models = get_albums_by_ids(main_db, &album_ids).await?;
+    album_models.sort_by_key(|x| album_ids.iter().position(|id| *id == x.id));
+
+    Ok(Element::new(name)
+        .items(artist_models.iter().map(artist))
+        .items(albums(main_db, &album_models, "album").await?)
+        .items(songs(main_db, &state.lib_path, &song_ids, "song").await?))
+}
+
+async fn get_playlists(state: &StreamState) -> Result<Element, Failure> {
+    let models = get_all_playlists(&state.main_db)
+        .await
+        .map_err(|e| Failure::new(ERROR_GENERIC, e.to_string()))?;
+
+    let mut items = Vec::new();
+    for model in &models {
+        items.push(playlist(&state.main_db, &state.lib_path, model, false).await?);
+    }
+
+    Ok(Element::new("playlists").items(items))
+}
+
+async fn get_playlist(state: &StreamState, request: &Request) -> Result<Element, Failure> {
+    let playlist_id = required_id(request, "id", PLAYLIST_PREFIX)?;
+    let model = get_playlist_by_id(&state.main_db, playlist_id)
+        .await?
+        .ok_or_else(|| Failure::not_found("Playlist"))?;
+
+    Ok(playlist(&state.main_db, &state.lib_path, &model, true).await?)
+}
+
+// Called with a name to create a playlist, or with the ID of one to
+// replace its songs
+async fn create_or_replace_playlist(
+    state: &StreamState,
+    request: &Request,
+) -> Result<Element, Failure> {
+    let playlist_id = match request.param("playlistId") {
+        Some(id) => parse_id(id, PLAYLIST_PREFIX).ok_or_else(|| Failure::not_found(id))?,
+        None => {
+            let name = required(request, "name")?;
+            let mut search_db = state.search_db.lock().await;
+            create_playlist(
+                &state.main_db,
+                &mut search_db,
+                name.to_string(),
+                SUBSONIC_PLAYLIST_GROUP.to_string(),
+            )
+            .await
+            .map_err(|e| Failure::new(ERROR_GENERIC, e.to_string()))?
+            .id
+        }
+    };
+
+    replace_playlist_items(
+        &state.main_db,
+        playlist_id,
+        &ids(request, "songId", SONG_PREFIX),
+    )
+    .await?;
+    state.query_cache.invalidate_playlists();
+
+    let model = get_playlist_by_id(&state.main_db, playlist_id)
+        .await?
+        .ok_or_else(|| Failure::not_found("Playlist"))?;
+    Ok(playlist(&state.main_db, &state.lib_path, &model, true).await?)
+}
+
+async fn edit_playlist(state: &StreamState, request: &Request) -> Result<(), Failure> {
+    let playlist_id = required_id(request, "playlistId", PLAYLIST_PREFIX)?;
+    get_playlist_by_id(&state.main_db, playlist_id)
+        .await?
+        .ok_or_else(|| Failure::not_found("Playlist"))?;
+
+    if let Some(name) = request.param("name") {
+        let mut search_db = state.search_db.lock().await;
+        update_playlist(
+            &state.main_db,
+            &mut search_db,
+            playlist_id,
+            Some(name.to_string()),
+            None,
+        )
+        .await
+        .map_err(|e| Failure::new(ERROR_GENERIC, e.to_string()))?;
+        state.query_cache.invalidate_playlists();
+    }
+
+    let removed: Vec<usize> = request
+        .params("songIndexToRemove")
+        .filter_map(|x| x.parse().ok())
+        .collect();
+    let added = ids(request, "songIdToAdd", SONG_PREFIX);
+    if removed.is_empty() && added.is_empty() {
+        return Ok(());
+    }
+
+    let items: Vec<i32> = get_playlist_items(&state.main_db, playlist_id)
+        .await?
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !removed.contains(index))
+        .map(|(_, file_id)| file_id)
+        .chain(added)
+        .collect();
+    replace_playlist_items(&state.main_db, playlist_id, &items).await?;
+    state.query_cache.invalidate_playlists();
+
+    Ok(())
+}
+
+async fn scrobble(state: &StreamState, request: &Request) -> Result<(), Failure> {
+    // Clients also report what is playing now, only finished plays count
+    if request.param("submission") == Some("false") {
+        return Ok(());
+    }
+
+    let user_id = get_active_user_id(&state.user_db).await?;
+    let file_ids = ids(request, "id", SONG_PREFIX);
+    for file_id in &file_ids {
+        log_play(&state.user_db, user_id, *file_id, 1.).await?;
+    }
+    state.query_cache.invalidate_files(&file_ids);
+
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use serde_json::{Map, Value as Json};
+
+use super::SUBSONIC_API_VERSION;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl From<&str> for Value {
+    fn from(x: &str) -> Self {
+        Value::Str(x.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(x: String) -> Self {
+        Value::Str(x)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(x: i32) -> Self {
+        Value::Int(x.into())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(x: i64) -> Self {
+        Value::Int(x)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(x: u64) -> Self {
+        Value::Int(x as i64)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(x: usize) -> Self {
+        Value::Int(x as i64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Value::Float(x)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(x: bool) -> Self {
+        Value::Bool(x)
+    }
+}
+
+impl Value {
+    fn to_text(&self) -> String {
+        match self {
+            Value::Str(x) => x.clone(),
+            Value::Int(x) => x.to_string(),
+            Value::Float(x) => x.to_string(),
+            Value::Bool(x) => x.to_string(),
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        match self {
+            Value::Str(x) => Json::from(x.as_str()),
+            Value::Int(x) => Json::from(*x),
+            Value::Float(x) => Json::from(*x),
+            Value::Bool(x) => Json::from(*x),
+        }
+    }
+}
+
+/// An element of a Subsonic response, written as XML or as JSON.
+///
+/// Children added with `item` are lists, they become JSON arrays even when
+/// there is a single one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub name: &'static str,
+    pub attrs: Vec<(&'static str, Value)>,
+    pub children: Vec<(Element, bool)>,
+}
+
+impl Element {
+    pub fn new(name: &'static str) -> Self {
+        Element {
+            name,
+            attrs: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn attr(mut self, name: &'static str, value: impl Into<Value>) -> Self {
+        self.attrs.push((name, value.into()));
+        self
+    }
+
+    pub fn attr_opt<T: Into<Value>>(self, name: &'static str, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.attr(name, value),
+            None => self,
+        }
+    }
+
+    pub fn child(mut self, child: Element) -> Self {
+        self.children.push((child, false));
+        self
+    }
+
+    pub fn item(mut self, child: Element) -> Self {
+        self.children.push((child, true));
+        self
+    }
+
+    pub fn items(mut self, children: impl IntoIterator<Item = Element>) -> Self {
+        self.children
+            .extend(children.into_iter().map(|x| (x, true)));
+        self
+    }
+
+    fn write_xml(&self, out: &mut String) {
+        out.push('<');
+        out.push_str(self.name);
+        for (name, value) in &self.attrs {
+            out.push_str(&format!(" {}=\"{}\"", name, escape_xml(&value.to_text())));
+        }
+
+        if self.children.is_empty() {
+            out.push_str("/>");
+            return;
+        }
+
+        out.push('>');
+        for (child, _) in &self.children {
+            child.write_xml(out);
+        }
+        out.push_str(&format!("</{}>", self.name));
+    }
+
+    fn to_json(&self) -> Json {
+        let mut object = Map::new();
+        for (name, value) in &self.attrs {
+            object.insert(name.to_string(), value.to_json());
+        }
+
+        for (child, list) in &self.children {
+            let value = child.to_json();
+            match object.get_mut(child.name) {
+                Some(Json::Array(items)) => items.push(value),
+                Some(existing) => *existing = Json::Array(vec![existing.take(), value]),
+                None if *list => {
+                    object.insert(child.name.to_string(), Json::Array(vec![value]));
+                }
+                None => {
+                    object.insert(child.name.to_string(), value);
+                }
+            }
+        }
+
+        Json::Object(object)
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for x in text.chars() {
+        match x {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters aren't allowed in XML 1.0
+            x if x.is_control() && x != '\t' && x != '\n' && x != '\r' => {}
+            x => escaped.push(x),
+        }
+    }
+    escaped
+}
+
+fn root(status: &str) -> Element {
+    Element::new("subsonic-response")
+        .attr("status", status)
+        .attr("version", SUBSONIC_API_VERSION)
+        .attr("type", "rune")
+        .attr("serverVersion", env!("CARGO_PKG_VERSION"))
+        .attr("openSubsonic", true)
+}
+
+/// The response to a successful call, holding the given elements.
+pub fn ok(content: Vec<Element>) -> Element {
+    content.into_iter().fold(root("ok"), Element::child)
+}
+
+/// The response to a failed call, with one of the codes of the API.
+pub fn failed(code: u32, message: &str) -> Element {
+    root("failed").child(
+        Element::new("error")
+            .attr("code", code as i64)
+            .attr("message", message),
+    )
+}
+
+/// Write a response as the client asked for it.
+///
+/// # Returns
+/// * `(&str, Vec<u8>)` - The content type and the body.
+pub fn render(response: &Element, format: &str) -> (&'static str, Vec<u8>) {
+    match format {
+        "json" => {
+            let mut object = Map::new();
+            object.insert(response.name.to_string(), response.to_json());
+            (
+                "application/json",
+                Json::Object(object).to_string().into_bytes(),
+            )
+        }
+        _ => {
+            let mut out = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+            let response = response
+                .clone()
+                .attr("xmlns", "http://subsonic.org/restapi");
+            response.write_xml(&mut out);
+            ("text/xml; charset=utf-8", out.into_bytes())
+        }
+    }
+}
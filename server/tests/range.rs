@@ -0,0 +1,19 @@
+use server::range::parse_range;
+
+#[test]
+fn ranges_are_clamped_to_the_file() {
+    assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+    assert_eq!(parse_range("bytes=900-", 1000), Some((900, 999)));
+    assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+    assert_eq!(parse_range("bytes=500-5000", 1000), Some((500, 999)));
+    assert_eq!(parse_range("bytes=0-9, 20-29", 1000), Some((0, 9)));
+}
+
+#[test]
+fn unsatisfiable_ranges_are_refused() {
+    assert_eq!(parse_range("bytes=1000-", 1000), None);
+    assert_eq!(parse_range("bytes=50-10", 1000), None);
+    assert_eq!(parse_range("bytes=-0", 1000), None);
+    assert_eq!(parse_range("bytes=0-", 0), None);
+    assert_eq!(parse_range("items=0-10", 1000), None);
+}
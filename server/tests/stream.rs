@@ -0,0 +1,210 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use database::actions::query_cache::QueryCache;
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+use server::stream::{StreamServer, StreamState, MAX_CONNECTIONS};
+use tokio::sync::Mutex;
+
+const TOKEN: &str = "secret";
+
+fn get(addr: SocketAddr, request: &str) -> (String, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    let split = response.windows(4).position(|x| x == b"\r\n\r\n").unwrap();
+    let head = String::from_utf8(response[..split].to_vec()).unwrap();
+    (head, response[split + 4..].to_vec())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn files_are_streamed_with_ranges() {
+    let lib = tempfile::tempdir().unwrap();
+    let cache = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    write_sine_fixture(
+        &lib.path().join("a.flac"),
+        FixtureFormat::Flac,
+        8000,
+        1,
+        8000,
+    )
+    .unwrap();
+    let file = MediaFileFixture::new("a.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+    let content = std::fs::read(lib.path().join("a.flac")).unwrap();
+
+    let server = StreamServer::start(
+        "127.0.0.1:0",
+        StreamState {
+            main_db: Arc::new(main_db),
+            user_db: Arc::new(connect_main_db_in_memory().await.unwrap()),
+            search_db: Arc::new(Mutex::new(connect_search_db_in_memory().unwrap())),
+            query_cache: Arc::new(QueryCache::default()),
+            lib_path: lib.path().to_path_buf(),
+            token: TOKEN.to_string(),
+            cache_dir: cache.path().to_path_buf(),
+        },
+    )
+    .unwrap();
+    let addr = server.local_addr();
+
+    let (head, _) = get(addr, "GET /ping HTTP/1.1\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 401"));
+    let (head, body) = get(addr, "GET /ping?token=secret HTTP/1.1\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 200"));
+    assert_eq!(body, b"pong");
+
+    let request = format!(
+        "GET /files/{} HTTP/1.1\r\nAuthorization: Bearer {}\r\n\r\n",
+        file.id, TOKEN
+    );
+    let (head, body) = get(addr, &request);
+    assert!(head.starts_with("HTTP/1.1 200"));
+    assert!(head.contains("Content-Type: audio/flac"));
+    assert_eq!(body, content);
+
+    let request = format!(
+        "GET /files/{}?token={} HTTP/1.1\r\nRange: bytes=4-9\r\n\r\n",
+        file.id, TOKEN
+    );
+    let (head, body) = get(addr, &request);
+    assert!(head.starts_with("HTTP/1.1 206"));
+    assert!(head.contains(&format!("Content-Range: bytes 4-9/{}", content.len())));
+    assert_eq!(body, &content[4..10]);
+
+    let request = format!(
+        "GET /files/{}?token={}&format=wav HTTP/1.1\r\n\r\n",
+        file.id, TOKEN
+    );
+    let (head, body) = get(addr, &request);
+    assert!(head.starts_with("HTTP/1.1 200"));
+    assert!(head.contains("Content-Type: audio/wav"));
+    assert_eq!(&body[..4], b"RIFF");
+
+    let request = format!(
+        "GET /files/{}?token={}&format=opus HTTP/1.1\r\n\r\n",
+        file.id, TOKEN
+    );
+    assert!(get(addr, &request).0.starts_with("HTTP/1.1 415"));
+    let request = format!("GET /files/999?token={} HTTP/1.1\r\n\r\n", TOKEN);
+    assert!(get(addr, &request).0.starts_with("HTTP/1.1 404"));
+
+    server.stop();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn connections_are_limited() {
+    let lib = tempfile::tempdir().unwrap();
+    let cache = tempfile::tempdir().unwrap();
+    let server = StreamServer::start(
+        "127.0.0.1:0",
+        StreamState {
+            main_db: Arc::new(connect_main_db_in_memory().await.unwrap()),
+            user_db: Arc::new(connect_main_db_in_memory().await.unwrap()),
+            search_db: Arc::new(Mutex::new(connect_search_db_in_memory().unwrap())),
+            query_cache: Arc::new(QueryCache::default()),
+            lib_path: lib.path().to_path_buf(),
+            token: TOKEN.to_string(),
+            cache_dir: cache.path().to_path_buf(),
+        },
+    )
+    .unwrap();
+    let addr = server.local_addr();
+
+    // Sockets that never send a request
+    let idle: Vec<TcpStream> = (0..MAX_CONNECTIONS)
+        .map(|_| TcpStream::connect(addr).unwrap())
+        .collect();
+    let (head, _) = get(addr, "GET /ping?token=secret HTTP/1.1\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 503"));
+
+    drop(idle);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let (head, _) = get(addr, "GET /ping?token=secret HTTP/1.1\r\n\r\n");
+        if head.starts_with("HTTP/1.1 200") {
+            break;
+        }
+        assert!(Instant::now() < deadline, "connections were never released");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    server.stop();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_transcodes_leave_nothing_behind() {
+    let lib = tempfile::tempdir().unwrap();
+    let cache = tempfile::tempdir().unwrap();
+    let main_db = connect_main_db_in_memory().await.unwrap();
+
+    std::fs::write(lib.path().join("a.flac"), b"not a flac file").unwrap();
+    let file = MediaFileFixture::new("a.flac")
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+    let server = StreamServer::start(
+        "127.0.0.1:0",
+        StreamState {
+            main_db: Arc::new(main_db),
+            user_db: Arc::new(connect_main_db_in_memory().await.unwrap()),
+            search_db: Arc::new(Mutex::new(connect_search_db_in_memory().unwrap())),
+            query_cache: Arc::new(QueryCache::default()),
+            lib_path: lib.path().to_path_buf(),
+            token: TOKEN.to_string(),
+            cache_dir: cache.path().to_path_buf(),
+        },
+    )
+    .unwrap();
+
+    let request = format!(
+        "GET /files/{}?token={}&format=wav HTTP/1.1\r\n\r\n",
+        file.id, TOKEN
+    );
+    assert!(get(server.local_addr(), &request)
+        .0
+        .starts_with("HTTP/1.1 500"));
+    assert_eq!(std::fs::read_dir(cache.path()).unwrap().count(), 0);
+
+    server.stop();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stopped_server_releases_its_port() {
+    let lib = tempfile::tempdir().unwrap();
+    let cache = tempfile::tempdir().unwrap();
+    let state = || async {
+        StreamState {
+            main_db: Arc::new(connect_main_db_in_memory().await.unwrap()),
+            user_db: Arc::new(connect_main_db_in_memory().await.unwrap()),
+            search_db: Arc::new(Mutex::new(connect_search_db_in_memory().unwrap())),
+            query_cache: Arc::new(QueryCache::default()),
+            lib_path: lib.path().to_path_buf(),
+            token: TOKEN.to_string(),
+            cache_dir: cache.path().to_path_buf(),
+        }
+    };
+
+    let server = StreamServer::start("0.0.0.0:0", state().await).unwrap();
+    let port = server.local_addr().port();
+    server.stop();
+
+    // A restart binds the same port right away
+    for _ in 0..3 {
+        let server = StreamServer::start(("0.0.0.0", port), state().await).unwrap();
+        assert_eq!(server.local_addr().port(), port);
+        server.stop();
+    }
+}
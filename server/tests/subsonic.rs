@@ -0,0 +1,203 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+
+use database::actions::play_history::get_recently_played;
+use database::actions::query_cache::QueryCache;
+use database::actions::users::DEFAULT_USER_ID;
+use database::entities::{albums, artists, media_file_albums, media_file_artists};
+use database::test_support::{
+    connect_main_db_in_memory, connect_search_db_in_memory, MediaFileFixture,
+};
+use md5::{Digest, Md5};
+use metadata::test_support::{write_sine_fixture, FixtureFormat};
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serde_json::Value;
+use server::stream::{StreamServer, StreamState};
+use tokio::sync::Mutex;
+
+const TOKEN: &str = "secret";
+
+fn get(addr: SocketAddr, path: &str) -> Vec<u8> {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .write_all(format!("GET {} HTTP/1.1\r\n\r\n", path).as_bytes())
+        .unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    let split = response.windows(4).position(|x| x == b"\r\n\r\n").unwrap();
+    response[split + 4..].to_vec()
+}
+
+fn call(addr: SocketAddr, method: &str, params: &str) -> Value {
+    let salt = "c19b2d";
+    let hash = hex::encode(Md5::digest(format!("{}{}", TOKEN, salt)));
+    let path = format!(
+        "/rest/{}.view?u=me&t={}&s={}&v=1.16.1&c=test&f=json{}",
+        method, hash, salt, params
+    );
+
+    let body = serde_json::from_slice::<Value>(&get(addr, &path)).unwrap();
+    body["subsonic-response"].clone()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subsonic_clients_can_browse_and_play() {
+    let lib = tempfile::tempdir().unwrap();
+    let cache = tempfile::tempdir().unwrap();
+    let main_db = Arc::new(connect_main_db_in_memory().await.unwrap());
+
+    let artist = artists::ActiveModel {
+        name: ActiveValue::Set("Artist".to_string()),
+        group: ActiveValue::Set("A".to_string()),
+        ..Default::default()
+    }
+    .insert(&*main_db)
+    .await
+    .unwrap();
+    let album = albums::ActiveModel {
+        name: ActiveValue::Set("Album & Co".to_string()),
+        group: ActiveValue::Set("A".to_string()),
+        ..Default::default()
+    }
+    .insert(&*main_db)
+    .await
+    .unwrap();
+
+    let mut file_ids = Vec::new();
+    for (track, name) in [(2, "b.wav"), (1, "a.wav")] {
+        write_sine_fixture(&lib.path().join(name), FixtureFormat::Wav, 8000, 1, 800).unwrap();
+        let file = MediaFileFixture::new(name).insert(&main_db).await.unwrap();
+        media_file_albums::ActiveModel {
+            media_file_id: ActiveValue::Set(file.id),
+            album_id: ActiveValue::Set(album.id),
+            track_number: ActiveValue::Set(Some(track)),
+            ..Default::default()
+        }
+        .insert(&*main_db)
+        .await
+        .unwrap();
+        media_file_artists::ActiveModel {
+            media_file_id: ActiveValue::Set(file.id),
+            artist_id: ActiveValue::Set(artist.id),
+            ..Default::default()
+        }
+        .insert(&*main_db)
+        .await
+        .unwrap();
+        file_ids.push(file.id);
+    }
+
+    let query_cache = Arc::new(QueryCache::default());
+    let server = StreamServer::start(
+        "127.0.0.1:0",
+        StreamState {
+            main_db: main_db.clone(),
+            user_db: main_db.clone(),
+            search_db: Arc::new(Mutex::new(connect_search_db_in_memory().unwrap())),
+            query_cache: query_cache.clone(),
+            lib_path: lib.path().to_path_buf(),
+            token: TOKEN.to_string(),
+            cache_dir: cache.path().to_path_buf(),
+        },
+    )
+    .unwrap();
+    let addr = server.local_addr();
+
+    // Wrong passwords fail, in XML unless asked otherwise
+    let body = String::from_utf8(get(addr, "/rest/ping?u=me&p=wrong")).unwrap();
+    assert!(body.contains(r#"status="failed""#));
+    assert!(body.contains(r#"code="40""#));
+    let body = String::from_utf8(get(addr, "/rest/ping?u=me&p=enc:736563726574")).unwrap();
+    assert!(body.contains(r#"status="ok""#));
+    assert_eq!(call(addr, "ping", "")["status"], "ok");
+
+    let artists = call(addr, "getArtists", "");
+    assert_eq!(
+        artists["artists"]["index"][0]["artist"][0]["id"],
+        format!("ar-{}", artist.id)
+    );
+    let response = call(addr, "getArtist", &format!("&id=ar-{}", artist.id));
+    assert_eq!(response["artist"]["albumCount"], 1);
+
+    // Songs come in the order of their tracks
+    let response = call(addr, "getAlbum", &format!("&id=al-{}", album.id));
+    assert_eq!(response["album"]["name"], "Album & Co");
+    assert_eq!(response["album"]["artist"], "Artist");
+    assert_eq!(response["album"]["songCount"], 2);
+    let songs: Vec<&str> = response["album"]["song"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(songs, vec!["a.wav", "b.wav"]);
+
+    let response = call(addr, "getSong", "&id=tr-999");
+    assert_eq!(response["status"], "failed");
+    assert_eq!(response["error"]["code"], 70);
+    assert_eq!(call(addr, "getAlbum", "")["error"]["code"], 10);
+
+    let params = format!(
+        "&name=Mix&songId=tr-{}&songId=tr-{}",
+        file_ids[0], file_ids[1]
+    );
+    let playlist = call(addr, "createPlaylist", &params)["playlist"].clone();
+    assert_eq!(playlist["songCount"], 2);
+    let playlist_id = playlist["id"].as_str().unwrap().to_string();
+
+    let params = format!("&playlistId={}&songIndexToRemove=0", playlist_id);
+    assert_eq!(call(addr, "updatePlaylist", &params)["status"], "ok");
+    let response = call(addr, "getPlaylist", &format!("&id={}", playlist_id));
+    assert_eq!(
+        response["playlist"]["entry"][0]["id"],
+        format!("tr-{}", file_ids[1])
+    );
+
+    let response = call(addr, "deletePlaylist", &format!("&id={}", playlist_id));
+    assert_eq!(response["status"], "ok");
+    assert!(call(addr, "getPlaylists", "")["playlists"]
+        .get("playlist")
+        .is_none());
+
+    call(
+        addr,
+        "scrobble",
+        &format!("&id=tr-{}&submission=false", file_ids[0]),
+    );
+    assert!(get_recently_played(&main_db, DEFAULT_USER_ID, 10)
+        .await
+        .unwrap()
+        .is_empty());
+    call(addr, "scrobble", &format!("&id=tr-{}", file_ids[0]));
+    assert_eq!(
+        get_recently_played(&main_db, DEFAULT_USER_ID, 10)
+            .await
+            .unwrap(),
+        vec![file_ids[0]]
+    );
+
+    // Plays and ratings of clients reach the details the app has cached
+    let detail =
+        || query_cache.track_detail(&main_db, &main_db, DEFAULT_USER_ID, lib.path(), file_ids[0]);
+    assert_eq!(detail().await.unwrap().play_count, 1);
+    call(addr, "scrobble", &format!("&id=tr-{}", file_ids[0]));
+    assert_eq!(detail().await.unwrap().play_count, 2);
+    call(
+        addr,
+        "setRating",
+        &format!("&id=tr-{}&rating=4", file_ids[0]),
+    );
+    assert_eq!(detail().await.unwrap().rating, Some(4));
+
+    // Codecs that can't be written are streamed as they are
+    let content = std::fs::read(lib.path().join("b.wav")).unwrap();
+    let path = format!(
+        "/rest/stream?u=me&p={}&id=tr-{}&format=mp3",
+        TOKEN, file_ids[0]
+    );
+    assert_eq!(get(addr, &path), content);
+
+    server.stop();
+}